@@ -13,7 +13,7 @@ pub extern "system" fn JNI_OnLoad(vm: jni::JavaVM, _: *mut c_void) -> jni::sys::
 
 #[unsafe(no_mangle)]
 fn android_main(app: winit::platform::android::activity::AndroidApp) {
-    let wgpu_options = crate::ui::create_egui_options();
+    let wgpu_options = crate::ui::create_egui_options(None);
 
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
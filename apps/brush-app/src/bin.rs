@@ -8,10 +8,11 @@ mod ui;
 #[cfg(not(target_family = "wasm"))]
 #[allow(clippy::unnecessary_wraps)]
 fn main() -> Result<(), anyhow::Error> {
-    use brush_cli::Cli;
+    use crate::ui::app::App;
+    use brush_cli::{Cli, Command};
     use clap::Parser;
 
-    let args = Cli::parse().validate()?;
+    let command = Cli::parse().into_command()?;
 
     #[cfg(target_family = "windows")]
     {
@@ -22,7 +23,8 @@ fn main() -> Result<(), anyhow::Error> {
         // Safety: FFI. Buffer is valid for duration of call
         let is_console = unsafe { GetConsoleProcessList(buffer.as_mut_ptr(), 1) != 1 };
 
-        if args.with_viewer && !is_console {
+        let with_viewer = matches!(&command, Command::Train(args) if args.with_viewer);
+        if with_viewer && !is_console {
             // Safety: FFI
             unsafe {
                 winapi::um::wincon::FreeConsole();
@@ -45,47 +47,44 @@ fn main() -> Result<(), anyhow::Error> {
         .build()
         .expect("Failed to initialize tokio runtime")
         .block_on(async move {
+            let args = match command {
+                Command::Train(args) if args.with_viewer => args,
+                command => return brush_cli::run_command(command).await,
+            };
+
             let init_process = brush_cli::build_process(&args);
 
-            if args.with_viewer {
-                use crate::ui::app::App;
-
-                let logger = env_logger::Builder::from_default_env()
-                    .target(env_logger::Target::Stdout)
-                    .build();
-                let max = logger.filter();
-                crate::ui::log_panel::install_global_logger(Box::new(logger), max);
-
-                let icon = eframe::icon_data::from_png_bytes(
-                    &include_bytes!("../assets/icon-256.png")[..],
-                )
-                .expect("Failed to load icon");
-
-                let native_options = eframe::NativeOptions {
-                    viewport: egui::ViewportBuilder::default()
-                        .with_inner_size(egui::Vec2::new(1450.0, 1200.0))
-                        .with_active(true)
-                        .with_icon(std::sync::Arc::new(icon)),
-                    wgpu_options: ui::create_egui_options(),
-                    persist_window: true,
-                    ..Default::default()
-                };
-
-                let title = if cfg!(debug_assertions) {
-                    "Brush  -  Debug"
-                } else {
-                    "Brush"
-                };
-
-                eframe::run_native(
-                    title,
-                    native_options,
-                    Box::new(move |cc| Ok(Box::new(App::new(cc, init_process)))),
-                )?;
+            let logger = env_logger::Builder::from_default_env()
+                .target(env_logger::Target::Stdout)
+                .build();
+            let max = logger.filter();
+            crate::ui::log_panel::install_global_logger(Box::new(logger), max);
+
+            let icon =
+                eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256.png")[..])
+                    .expect("Failed to load icon");
+
+            let native_options = eframe::NativeOptions {
+                viewport: egui::ViewportBuilder::default()
+                    .with_inner_size(egui::Vec2::new(1450.0, 1200.0))
+                    .with_active(true)
+                    .with_icon(std::sync::Arc::new(icon)),
+                wgpu_options: ui::create_egui_options(args.gpu.clone()),
+                persist_window: true,
+                ..Default::default()
+            };
+
+            let title = if cfg!(debug_assertions) {
+                "Brush  -  Debug"
             } else {
-                let process = init_process.expect("Must provide a source");
-                brush_cli::run_headless(process, args.train_stream).await?;
-            }
+                "Brush"
+            };
+
+            eframe::run_native(
+                title,
+                native_options,
+                Box::new(move |cc| Ok(Box::new(App::new(cc, init_process)))),
+            )?;
 
             anyhow::Result::<(), anyhow::Error>::Ok(())
         })?;
@@ -10,8 +10,9 @@ use std::sync::Arc;
 use tracing::trace_span;
 
 use crate::ui::{
-    UiMode, camera_controls::CameraClamping, datasets::DatasetPanel, log_panel::LogPanel,
-    panels::AppPane, scene::ScenePanel, settings_panel::SettingsPanel, stats::StatsPanel,
+    UiMode, camera_controls::CameraClamping, compare::ComparePanel, datasets::DatasetPanel,
+    log_panel::LogPanel, metrics::MetricsPanel, panels::AppPane, scene::ScenePanel,
+    settings_panel::SettingsPanel, splat_backbuffer::DebugView, stats::StatsPanel,
     training_panel::TrainingPanel, ui_process::UiProcess,
 };
 
@@ -25,6 +26,8 @@ pub enum Pane {
     Training(#[serde(skip)] TrainingPanel),
     Settings(#[serde(skip)] SettingsPanel),
     Log(#[serde(skip)] LogPanel),
+    Metrics(#[serde(skip)] MetricsPanel),
+    Compare(#[serde(skip)] ComparePanel),
 }
 
 impl Pane {
@@ -36,6 +39,8 @@ impl Pane {
             Self::Training(p) => p,
             Self::Settings(p) => p,
             Self::Log(p) => p,
+            Self::Metrics(p) => p,
+            Self::Compare(p) => p,
         }
     }
 
@@ -47,6 +52,8 @@ impl Pane {
             Self::Training(p) => p,
             Self::Settings(p) => p,
             Self::Log(p) => p,
+            Self::Metrics(p) => p,
+            Self::Compare(p) => p,
         }
     }
 
@@ -76,6 +83,14 @@ impl Pane {
         #[allow(clippy::default_constructed_unit_structs)] // Pane derives Default via serde.
         RefCell::new(Self::Log(LogPanel::default()))
     }
+
+    fn metrics() -> RefCell<Self> {
+        RefCell::new(Self::Metrics(MetricsPanel::default()))
+    }
+
+    fn compare() -> RefCell<Self> {
+        RefCell::new(Self::Compare(ComparePanel::default()))
+    }
 }
 
 type PaneRef = RefCell<Pane>;
@@ -147,12 +162,22 @@ impl egui_tiles::Behavior<PaneRef> for AppTree {
 pub struct CameraSettings {
     pub speed_scale: Option<f32>,
     pub splat_scale: Option<f32>,
+    /// Supersampling factor for the splat render, `None` or 1 to render at display resolution.
+    pub ssaa: Option<u32>,
+    /// Resolution scale to render at while the camera moves, re-rendering at
+    /// full resolution once it stops. `None` or 1 always renders at full resolution.
+    pub motion_scale: Option<f32>,
+    /// Highest SH degree to evaluate when rendering, `None` for all bands of the splats.
+    /// The stored coefficients are unaffected.
+    pub sh_degree_override: Option<u32>,
     pub background: Option<Vec3>,
     pub grid_enabled: Option<bool>,
+    /// Debug visualization to show instead of the splat colors.
+    pub debug_view: DebugView,
     pub clamping: CameraClamping,
 }
 
-const TREE_STORAGE_KEY: &str = "brush_tile_tree_v3";
+const TREE_STORAGE_KEY: &str = "brush_tile_tree_v4";
 
 pub struct App {
     tree: egui_tiles::Tree<PaneRef>,
@@ -170,6 +195,8 @@ impl App {
             let training_pane = tiles.insert_pane(Pane::training());
             let settings_pane = tiles.insert_pane(Pane::settings());
             let log_pane = tiles.insert_pane(Pane::log());
+            let metrics_pane = tiles.insert_pane(Pane::metrics());
+            let compare_pane = tiles.insert_pane(Pane::compare());
             Self::build_default_layout(
                 &mut tiles,
                 scene_pane,
@@ -178,6 +205,8 @@ impl App {
                 training_pane,
                 settings_pane,
                 log_pane,
+                metrics_pane,
+                compare_pane,
             )
         };
 
@@ -197,6 +226,8 @@ impl App {
             && has(tree, |p| matches!(p, Pane::Training(_)))
             && has(tree, |p| matches!(p, Pane::Settings(_)))
             && has(tree, |p| matches!(p, Pane::Log(_)))
+            && has(tree, |p| matches!(p, Pane::Metrics(_)))
+            && has(tree, |p| matches!(p, Pane::Compare(_)))
     }
 
     pub fn new(
@@ -249,6 +280,7 @@ impl App {
         &self.tree_ctx.process
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_default_layout(
         tiles: &mut Tiles<PaneRef>,
         scene_pane: TileId,
@@ -257,16 +289,22 @@ impl App {
         training_pane: TileId,
         settings_pane: TileId,
         log_pane: TileId,
+        metrics_pane: TileId,
+        compare_pane: TileId,
     ) -> TileId {
-        // Stats / Log / Settings share a tabbed area
-        let bottom_tabs = tiles.insert_tab_tile(vec![stats_pane, log_pane, settings_pane]);
+        // Stats / Metrics / Log / Settings share a tabbed area
+        let bottom_tabs =
+            tiles.insert_tab_tile(vec![stats_pane, metrics_pane, log_pane, settings_pane]);
+
+        // The dataset view and the comparison with the render share a tabbed area.
+        let dataset_tabs = tiles.insert_tab_tile(vec![dataset_pane, compare_pane]);
 
         let mut sidebar = egui_tiles::Linear::new(
             egui_tiles::LinearDir::Vertical,
-            vec![training_pane, dataset_pane, bottom_tabs],
+            vec![training_pane, dataset_tabs, bottom_tabs],
         );
         sidebar.shares.set_share(training_pane, 0.12);
-        sidebar.shares.set_share(dataset_pane, 0.53);
+        sidebar.shares.set_share(dataset_tabs, 0.53);
         sidebar.shares.set_share(bottom_tabs, 0.35);
         let sidebar_id = tiles.insert_container(sidebar);
 
@@ -333,6 +371,8 @@ impl eframe::App for App {
             let training_pane = find_pane(&tree.tiles, |p| matches!(p, Pane::Training(_)));
             let settings_pane = find_pane(&tree.tiles, |p| matches!(p, Pane::Settings(_)));
             let log_pane = find_pane(&tree.tiles, |p| matches!(p, Pane::Log(_)));
+            let metrics_pane = find_pane(&tree.tiles, |p| matches!(p, Pane::Metrics(_)));
+            let compare_pane = find_pane(&tree.tiles, |p| matches!(p, Pane::Compare(_)));
 
             // Remove all container tiles
             let container_ids: Vec<TileId> = tree
@@ -353,6 +393,8 @@ impl eframe::App for App {
                 training_pane,
                 settings_pane,
                 log_pane,
+                metrics_pane,
+                compare_pane,
             ));
         }
 
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use brush_async::AsyncMap;
+use brush_dataset::{Dataset, scene::SceneView};
+use brush_process::{
+    message::{ProcessMessage, TrainMessage},
+    slot::Slot,
+};
+use brush_render::gaussian_splats::{PreparedSplats, Splats};
+use brush_train::{config::TrainConfig, eval::eval_stats};
+use eframe::egui_wgpu::RenderState;
+use egui::{Color32, ColorImage, RichText, TextureHandle, TextureOptions, pos2};
+use image::RgbImage;
+use web_time::{Duration, Instant};
+
+use crate::ui::{UiMode, panels::AppPane, ui_process::UiProcess};
+
+/// Splats change every few steps while training, only re-render the comparison this often.
+const SPLAT_REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+struct CompareRequest {
+    splats: Slot<Splats>,
+    view: SceneView,
+    train_config: Arc<TrainConfig>,
+    ctx: egui::Context,
+}
+
+/// A training view next to a render of the splats from its camera.
+#[derive(Clone)]
+struct Comparison {
+    view_name: String,
+    psnr: f32,
+    gt: TextureHandle,
+    render: TextureHandle,
+    diff: TextureHandle,
+}
+
+/// Shows the ground truth of the training view nearest to the camera next to a
+/// render from that view, to tell apart problems of the model from problems of
+/// the data.
+pub struct ComparePanel {
+    dataset: Dataset,
+    train_config: Arc<TrainConfig>,
+    pipe: Option<AsyncMap<CompareRequest, Option<Comparison>>>,
+    /// Bumped whenever the splats change.
+    splat_version: u32,
+    /// The view index and splat version of the last request.
+    requested: Option<(usize, u32)>,
+    last_request: Option<Instant>,
+    show_diff: bool,
+}
+
+impl Default for ComparePanel {
+    fn default() -> Self {
+        Self {
+            dataset: Dataset::empty(),
+            train_config: Arc::new(TrainConfig::default()),
+            pipe: None,
+            splat_version: 0,
+            requested: None,
+            last_request: None,
+            show_diff: false,
+        }
+    }
+}
+
+/// Map an error in [0, 1] to the same blue to red ramp as the debug views.
+fn heat_color(t: f32) -> [u8; 3] {
+    let ramp = |center: f32| ((1.5 - (t * 4.0 - center).abs()).clamp(0.0, 1.0) * 255.0) as u8;
+    [ramp(3.0), ramp(2.0), ramp(1.0)]
+}
+
+/// Heatmap of the per pixel absolute error, averaged over the channels. The
+/// error is scaled up 4x, as most errors would otherwise be hard to see.
+fn diff_heatmap(render: &RgbImage, gt: &RgbImage) -> RgbImage {
+    let mut diff = RgbImage::new(render.width(), render.height());
+    for ((out, r), g) in diff.pixels_mut().zip(render.pixels()).zip(gt.pixels()) {
+        let err =
+            r.0.iter()
+                .zip(g.0)
+                .map(|(&a, b)| (f32::from(a) - f32::from(b)).abs())
+                .sum::<f32>()
+                / (3.0 * 255.0);
+        out.0 = heat_color((err * 4.0).min(1.0));
+    }
+    diff
+}
+
+fn load_texture(ctx: &egui::Context, name: &str, img: &RgbImage) -> TextureHandle {
+    let size = [img.width() as usize, img.height() as usize];
+    let color_img = ColorImage::from_rgb(size, img.as_raw());
+    ctx.load_texture(name, color_img, TextureOptions::default())
+}
+
+async fn compare_view(req: &CompareRequest) -> anyhow::Result<Comparison> {
+    let splats = req.splats.latest().context("No splats to render")?;
+    let device = splats.device();
+    let prepared = PreparedSplats::new(splats, None).await;
+    let gt_img = req.view.image.load().await?;
+    let sample = eval_stats(
+        &prepared,
+        &req.view.camera,
+        gt_img,
+        req.view.image.alpha_mode(),
+        req.train_config.ssim_mode,
+        req.train_config.ssim_window(),
+        None,
+        None,
+        &device,
+    )
+    .await?;
+
+    let psnr = sample.psnr.into_scalar_async::<f32>().await?;
+    let [h, w, _] = sample.rendered.dims();
+    let pixels = sample
+        .rendered
+        .into_data_async()
+        .await?
+        .into_vec::<f32>()?
+        .into_iter()
+        .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect();
+    let render =
+        RgbImage::from_raw(w as u32, h as u32, pixels).context("Unexpected render size")?;
+    let gt = sample.gt_img.to_rgb8();
+    let diff = diff_heatmap(&render, &gt);
+
+    Ok(Comparison {
+        view_name: req.view.image.img_name(),
+        psnr,
+        gt: load_texture(&req.ctx, "compare_gt", &gt),
+        render: load_texture(&req.ctx, "compare_render", &render),
+        diff: load_texture(&req.ctx, "compare_diff", &diff),
+    })
+}
+
+/// Draw `tex` as large as fits in `rect`, centered.
+fn draw_fitted(ui: &egui::Ui, tex: &TextureHandle, rect: egui::Rect) {
+    let aspect = tex.aspect_ratio();
+    let mut size = rect.size();
+    if size.x / size.y > aspect {
+        size.x = size.y * aspect;
+    } else {
+        size.y = size.x / aspect;
+    }
+    let img_rect = egui::Rect::from_center_size(rect.center(), size);
+    ui.painter().image(
+        tex.id(),
+        img_rect,
+        egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+        Color32::WHITE,
+    );
+}
+
+impl AppPane for ComparePanel {
+    fn title(&self) -> egui::WidgetText {
+        let Some(comparison) = self.pipe.as_ref().and_then(|p| p.latest()).flatten() else {
+            return "Compare".into();
+        };
+        format!(
+            "Compare  |  {}  |  PSNR {:.2}",
+            comparison.view_name, comparison.psnr
+        )
+        .into()
+    }
+
+    fn init(&mut self, _state: &RenderState, process: &UiProcess) {
+        self.pipe = Some(AsyncMap::new(
+            process.actor(),
+            async move |req: &CompareRequest| match compare_view(req).await {
+                Ok(comparison) => Some(comparison),
+                Err(error) => {
+                    log::warn!("Failed to compare view: {error:#}");
+                    None
+                }
+            },
+            |req: &CompareRequest| req.ctx.request_repaint(),
+        ));
+    }
+
+    fn is_visible(&self, process: &UiProcess) -> bool {
+        process.ui_mode() == UiMode::Default && process.is_training()
+    }
+
+    fn on_message(&mut self, message: &ProcessMessage, _process: &UiProcess) {
+        match message {
+            ProcessMessage::NewProcess => {
+                self.dataset = Dataset::empty();
+                self.requested = None;
+            }
+            ProcessMessage::TrainMessage(TrainMessage::TrainConfig { config }) => {
+                self.train_config = Arc::new(config.train_config.clone());
+            }
+            ProcessMessage::TrainMessage(TrainMessage::Dataset { dataset }) => {
+                self.dataset = dataset.clone();
+                self.requested = None;
+            }
+            ProcessMessage::SplatsUpdated { .. } => {
+                self.splat_version += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, process: &UiProcess) {
+        let Some(pipe) = self.pipe.as_ref() else {
+            return;
+        };
+
+        let mv = process.current_camera().world_to_local() * process.model_local_to_world();
+        let scene = &self.dataset.train;
+        if let Some(nearest) = scene.get_nearest_view(mv.inverse()) {
+            let key = (nearest, self.splat_version);
+            let view_changed = self.requested.is_none_or(|(view, _)| view != nearest);
+            let refresh_due = self
+                .last_request
+                .is_none_or(|t| t.elapsed() >= SPLAT_REFRESH);
+            if self.requested != Some(key) && (view_changed || refresh_due) {
+                pipe.request(CompareRequest {
+                    splats: process.current_splats(),
+                    view: scene.views[nearest].clone(),
+                    train_config: self.train_config.clone(),
+                    ctx: ui.ctx().clone(),
+                });
+                self.requested = Some(key);
+                self.last_request = Some(Instant::now());
+            } else if self.requested != Some(key) {
+                // Check back once the refresh is due.
+                ui.ctx().request_repaint_after(SPLAT_REFRESH);
+            }
+        }
+
+        let Some(comparison) = pipe.latest().flatten() else {
+            ui.centered_and_justified(|ui| {
+                ui.label(
+                    RichText::new("Waiting for training to start")
+                        .size(14.0)
+                        .color(Color32::from_rgb(140, 140, 140))
+                        .italics(),
+                );
+            });
+            return;
+        };
+
+        let full_rect = egui::Rect::from_min_size(ui.cursor().min, ui.available_size());
+        ui.painter()
+            .rect_filled(full_rect, 0.0, Color32::from_gray(20));
+        let half = full_rect.width() / 2.0;
+        let (left, right) = full_rect.split_left_right_at_x(full_rect.min.x + half);
+        draw_fitted(ui, &comparison.gt, left.shrink(2.0));
+        let right_tex = if self.show_diff {
+            &comparison.diff
+        } else {
+            &comparison.render
+        };
+        draw_fitted(ui, right_tex, right.shrink(2.0));
+        ui.allocate_rect(full_rect, egui::Sense::hover());
+    }
+
+    fn inner_margin(&self) -> f32 {
+        0.0
+    }
+
+    fn top_bar_right_ui(&mut self, ui: &mut egui::Ui, _process: &UiProcess) {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.toggle_value(&mut self.show_diff, "Difference")
+                .on_hover_text("Show a heatmap of the error instead of the render");
+        });
+    }
+}
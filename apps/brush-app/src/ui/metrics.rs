@@ -0,0 +1,401 @@
+use std::fmt::Write;
+
+use brush_async::Actor;
+use brush_process::message::{ProcessMessage, TrainMessage};
+use egui::{Align2, Color32, FontId, RichText, Sense, Shape, Stroke, pos2, vec2};
+
+use crate::ui::UiMode;
+use crate::ui::panels::AppPane;
+use crate::ui::ui_process::UiProcess;
+
+/// Most points a series keeps before halving its resolution.
+const MAX_POINTS: usize = 1024;
+const PLOT_HEIGHT: f32 = 90.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Metric {
+    Loss,
+    Splats,
+    Visible,
+    Intersections,
+    Psnr,
+    Ssim,
+}
+
+impl Metric {
+    const ALL: [Self; 6] = [
+        Self::Loss,
+        Self::Splats,
+        Self::Visible,
+        Self::Intersections,
+        Self::Psnr,
+        Self::Ssim,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Loss => "loss",
+            Self::Splats => "splats",
+            Self::Visible => "visible",
+            Self::Intersections => "intersections",
+            Self::Psnr => "eval_psnr",
+            Self::Ssim => "eval_ssim",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Loss => "Loss",
+            Self::Splats => "Splat count",
+            Self::Visible => "Visible splats",
+            Self::Intersections => "Tile intersections",
+            Self::Psnr => "Eval PSNR",
+            Self::Ssim => "Eval SSIM",
+        }
+    }
+
+    fn color(self) -> Color32 {
+        match self {
+            Self::Loss => Color32::from_rgb(230, 120, 90),
+            Self::Splats => Color32::from_rgb(100, 170, 240),
+            Self::Visible => Color32::from_rgb(120, 200, 160),
+            Self::Intersections => Color32::from_rgb(200, 160, 240),
+            Self::Psnr => Color32::from_rgb(240, 200, 90),
+            Self::Ssim => Color32::from_rgb(240, 140, 200),
+        }
+    }
+}
+
+/// `(iter, value)` points of one metric. To keep memory and drawing bounded on
+/// long runs, neighbouring points are averaged in pairs whenever the series
+/// fills up, after which each point covers twice as many samples.
+#[derive(Debug)]
+struct Series {
+    max_points: usize,
+    points: Vec<[f64; 2]>,
+    /// Number of samples each stored point averages.
+    stride: usize,
+    /// Sum of the samples since the last stored point.
+    pending: [f64; 2],
+    pending_count: usize,
+}
+
+impl Series {
+    fn new(max_points: usize) -> Self {
+        debug_assert!(
+            max_points >= 2 && max_points.is_multiple_of(2),
+            "Series needs an even number of points to downsample in pairs"
+        );
+        Self {
+            max_points,
+            points: Vec::new(),
+            stride: 1,
+            pending: [0.0; 2],
+            pending_count: 0,
+        }
+    }
+
+    fn push(&mut self, iter: u32, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.pending[0] += iter as f64;
+        self.pending[1] += value;
+        self.pending_count += 1;
+
+        if self.pending_count == self.stride {
+            let n = self.stride as f64;
+            self.points.push([self.pending[0] / n, self.pending[1] / n]);
+            self.pending = [0.0; 2];
+            self.pending_count = 0;
+
+            if self.points.len() >= self.max_points {
+                self.points = self
+                    .points
+                    .chunks_exact(2)
+                    .map(|p| [(p[0][0] + p[1][0]) / 2.0, (p[0][1] + p[1][1]) / 2.0])
+                    .collect();
+                self.stride *= 2;
+            }
+        }
+    }
+
+    /// The stored points, followed by the average of any samples that don't
+    /// make up a full point yet.
+    fn points(&self) -> Vec<[f64; 2]> {
+        let mut points = self.points.clone();
+        if self.pending_count > 0 {
+            let n = self.pending_count as f64;
+            points.push([self.pending[0] / n, self.pending[1] / n]);
+        }
+        points
+    }
+
+    fn clear(&mut self) {
+        *self = Self::new(self.max_points);
+    }
+}
+
+pub struct MetricsPanel {
+    series: [Series; Metric::ALL.len()],
+    log_scale: [bool; Metric::ALL.len()],
+    /// Splat count of the latest `SplatsUpdated`, recorded on the next train step.
+    num_splats: u32,
+    export_actor: Actor,
+}
+
+impl Default for MetricsPanel {
+    fn default() -> Self {
+        Self {
+            series: std::array::from_fn(|_| Series::new(MAX_POINTS)),
+            log_scale: Metric::ALL.map(|m| m == Metric::Loss),
+            num_splats: 0,
+            export_actor: Actor::new("metrics-panel-export"),
+        }
+    }
+}
+
+impl MetricsPanel {
+    fn push(&mut self, metric: Metric, iter: u32, value: f64) {
+        self.series[metric as usize].push(iter, value);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.series
+            .iter()
+            .all(|s| s.points.is_empty() && s.pending_count == 0)
+    }
+
+    /// All series as `metric,iter,value` rows.
+    fn to_csv(&self) -> String {
+        let mut csv = "metric,iter,value\n".to_owned();
+        for metric in Metric::ALL {
+            for [iter, value] in self.series[metric as usize].points() {
+                let _ = writeln!(csv, "{},{iter:.0},{value}", metric.name());
+            }
+        }
+        csv
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value == 0.0 || (1e-2..1e5).contains(&value.abs()) {
+        format!("{value:.3}")
+    } else {
+        format!("{value:.2e}")
+    }
+}
+
+fn plot(ui: &mut egui::Ui, metric: Metric, points: &[[f64; 2]], log_scale: bool) {
+    let (rect, response) =
+        ui.allocate_exact_size(vec2(ui.available_width(), PLOT_HEIGHT), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let weak = ui.visuals().weak_text_color();
+    if points.is_empty() {
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            "No data yet",
+            FontId::proportional(11.0),
+            weak,
+        );
+        return;
+    }
+
+    let scale = |y: f64| {
+        if log_scale {
+            y.max(f64::MIN_POSITIVE).log10()
+        } else {
+            y
+        }
+    };
+    let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p[0]), hi.max(p[0]))
+    });
+    let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p[1]), hi.max(p[1]))
+    });
+    let (lo, hi) = (scale(min_y), scale(max_y));
+    let span_x = (max_x - min_x).max(1.0);
+    let span_y = if hi > lo { hi - lo } else { 1.0 };
+
+    let plot_rect = rect.shrink2(vec2(4.0, 6.0));
+    let to_screen = |p: [f64; 2]| {
+        let tx = ((p[0] - min_x) / span_x) as f32;
+        let ty = ((scale(p[1]) - lo) / span_y) as f32;
+        pos2(
+            plot_rect.left() + tx * plot_rect.width(),
+            plot_rect.bottom() - ty * plot_rect.height(),
+        )
+    };
+
+    let line: Vec<_> = points.iter().map(|&p| to_screen(p)).collect();
+    painter.add(Shape::line(line, Stroke::new(1.5, metric.color())));
+
+    let font = FontId::monospace(10.0);
+    painter.text(
+        rect.left_top() + vec2(4.0, 2.0),
+        Align2::LEFT_TOP,
+        format_value(max_y),
+        font.clone(),
+        weak,
+    );
+    painter.text(
+        rect.left_bottom() + vec2(4.0, -2.0),
+        Align2::LEFT_BOTTOM,
+        format_value(min_y),
+        font,
+        weak,
+    );
+
+    if let Some(pos) = response.hover_pos() {
+        let nearest = points.iter().min_by(|a, b| {
+            (to_screen(**a).x - pos.x)
+                .abs()
+                .total_cmp(&(to_screen(**b).x - pos.x).abs())
+        });
+        if let Some(&p) = nearest {
+            let at = to_screen(p);
+            painter.vline(at.x, rect.y_range(), Stroke::new(1.0, weak));
+            painter.circle_filled(at, 3.0, metric.color());
+            response.on_hover_text(format!("iter {:.0}: {}", p[0], format_value(p[1])));
+        }
+    }
+}
+
+impl AppPane for MetricsPanel {
+    fn title(&self) -> egui::WidgetText {
+        "Metrics".into()
+    }
+
+    fn is_visible(&self, process: &UiProcess) -> bool {
+        process.ui_mode() == UiMode::Default && process.is_training()
+    }
+
+    fn on_message(&mut self, message: &ProcessMessage, _: &UiProcess) {
+        match message {
+            ProcessMessage::NewProcess => {
+                self.series.iter_mut().for_each(Series::clear);
+                self.num_splats = 0;
+            }
+            ProcessMessage::SplatsUpdated { num_splats, .. } => {
+                self.num_splats = *num_splats;
+            }
+            ProcessMessage::TrainMessage(train) => match train {
+                TrainMessage::TrainStep {
+                    iter,
+                    loss,
+                    num_visible,
+                    num_intersections,
+                    ..
+                } => {
+                    if let Some(loss) = loss {
+                        self.push(Metric::Loss, *iter, *loss as f64);
+                    }
+                    self.push(Metric::Splats, *iter, self.num_splats as f64);
+                    self.push(Metric::Visible, *iter, *num_visible as f64);
+                    self.push(Metric::Intersections, *iter, *num_intersections as f64);
+                }
+                TrainMessage::EvalResult {
+                    iter,
+                    avg_psnr,
+                    avg_ssim,
+                    ..
+                } => {
+                    self.push(Metric::Psnr, *iter, *avg_psnr as f64);
+                    self.push(Metric::Ssim, *iter, *avg_ssim as f64);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn top_bar_right_ui(&mut self, ui: &mut egui::Ui, _process: &UiProcess) {
+        if ui
+            .add_enabled(!self.is_empty(), egui::Button::new("Export CSV").small())
+            .clicked()
+        {
+            let csv = self.to_csv().into_bytes();
+            self.export_actor
+                .run(move || async move {
+                    if let Err(e) = rrfd::save_file("metrics.csv", csv).await {
+                        log::error!("Failed to export metrics: {e}");
+                    }
+                })
+                .detach();
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _process: &UiProcess) {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for metric in Metric::ALL {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(metric.title()).strong());
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.checkbox(&mut self.log_scale[metric as usize], "Log");
+                    });
+                });
+                let points = self.series[metric as usize].points();
+                plot(ui, metric, &points, self.log_scale[metric as usize]);
+                ui.add_space(6.0);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_downsamples() {
+        let mut series = Series::new(8);
+        for i in 0..8 {
+            series.push(i, i as f64);
+        }
+        // Filling up averages neighbouring pairs.
+        assert_eq!(series.stride, 2);
+        assert_eq!(
+            series.points(),
+            vec![[0.5, 0.5], [2.5, 2.5], [4.5, 4.5], [6.5, 6.5]]
+        );
+
+        // New samples now make up a point per two samples, with a partial
+        // point until the second one arrives.
+        series.push(8, 8.0);
+        assert_eq!(series.points().last(), Some(&[8.0, 8.0]));
+        series.push(9, 10.0);
+        assert_eq!(series.points().len(), 5);
+        assert_eq!(series.points().last(), Some(&[8.5, 9.0]));
+
+        // The series never grows past its limit.
+        for i in 10..1000 {
+            series.push(i, 1.0);
+        }
+        assert!(series.points().len() <= 8);
+        assert_eq!(series.points().last().map(|p| p[1]), Some(1.0));
+    }
+
+    #[test]
+    fn test_series_skips_non_finite() {
+        let mut series = Series::new(8);
+        series.push(0, f64::NAN);
+        series.push(1, f64::INFINITY);
+        series.push(2, 3.0);
+        assert_eq!(series.points(), vec![[2.0, 3.0]]);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let mut panel = MetricsPanel::default();
+        panel.push(Metric::Loss, 50, 0.25);
+        panel.push(Metric::Psnr, 100, 24.5);
+        assert_eq!(
+            panel.to_csv(),
+            "metric,iter,value\nloss,50,0.25\neval_psnr,100,24.5\n"
+        );
+    }
+}
@@ -4,12 +4,14 @@ pub mod camera_controls;
 pub mod ui_process;
 
 pub mod log_panel;
+mod metrics;
 mod panels;
 mod scene;
 pub mod splat_backbuffer;
 mod stats;
 mod widget_3d;
 
+mod compare;
 mod datasets;
 
 mod training_panel;
@@ -17,7 +19,7 @@ mod training_panel;
 mod settings_panel;
 mod settings_popup;
 
-use eframe::egui_wgpu::WgpuConfiguration;
+use eframe::egui_wgpu::{NativeAdapterSelectorMethod, WgpuConfiguration};
 use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 use wgpu::{Adapter, ExperimentalFeatures, Features};
@@ -34,13 +36,26 @@ pub enum UiMode {
     EmbeddedViewer,
 }
 
-pub fn create_egui_options() -> WgpuConfiguration {
+/// wgpu setup for the UI, which Burn shares. `gpu` picks the adapter like
+/// `--gpu`, see [`brush_process::adapter::select_adapter`].
+pub fn create_egui_options(gpu: Option<String>) -> WgpuConfiguration {
+    let native_adapter_selector = gpu.map(|selector| -> NativeAdapterSelectorMethod {
+        Arc::new(
+            move |adapters: &[Adapter], _surface: Option<&wgpu::Surface<'_>>| {
+                let infos: Vec<_> = adapters.iter().map(|a| a.get_info()).collect();
+                let index = brush_process::adapter::select_adapter(&selector, &infos)
+                    .map_err(|e| e.to_string())?;
+                Ok(adapters[index].clone())
+            },
+        )
+    });
+
     WgpuConfiguration {
         wgpu_setup: eframe::egui_wgpu::WgpuSetup::CreateNew(
             eframe::egui_wgpu::WgpuSetupCreateNew {
                 instance_descriptor: wgpu::InstanceDescriptor::new_without_display_handle(),
                 display_handle: None,
-                native_adapter_selector: None,
+                native_adapter_selector,
                 power_preference: wgpu::PowerPreference::HighPerformance,
                 device_descriptor: Arc::new(|adapter: &Adapter| wgpu::DeviceDescriptor {
                     label: Some("egui+burn"),
@@ -1,5 +1,6 @@
 use brush_process::DataSource;
 use brush_process::{create_process, message::ProcessMessage};
+use brush_render::bounding_box::BoundingBox;
 use brush_render::camera::{focal_to_fov, fov_to_focal};
 use core::f32;
 use eframe::egui_wgpu::RenderState;
@@ -12,7 +13,7 @@ use web_time::Instant;
 
 use crate::ui::panels::AppPane;
 use crate::ui::settings_popup::SettingsPopup;
-use crate::ui::splat_backbuffer::SplatBackbuffer;
+use crate::ui::splat_backbuffer::{DebugView, SplatBackbuffer};
 use crate::ui::ui_process::{BackgroundStyle, UiProcess};
 use crate::ui::widget_3d::GridWidget;
 use crate::ui::{UiMode, draw_checkerboard};
@@ -93,6 +94,9 @@ pub struct ScenePanel {
     /// Whether animation playback is paused.
     #[serde(skip)]
     paused: bool,
+    /// How much of the splat file is loaded, while it's still loading.
+    #[serde(skip)]
+    load_progress: Option<f32>,
     #[serde(skip)]
     err: Option<ErrorDisplay>,
     #[serde(skip)]
@@ -345,6 +349,7 @@ impl ScenePanel {
         self.frame = 0.0;
         self.frame_count = 0;
         self.paused = false;
+        self.load_progress = None;
         self.last_rendered_iter = 0;
         self.warnings.clear();
         self.seen_warning_count = 0;
@@ -497,23 +502,92 @@ impl ScenePanel {
             });
     }
 
+    // Crop box & opacity threshold, applied to the viewer and to exports.
+    fn draw_filter_controls(ui: &mut egui::Ui, process: &UiProcess) {
+        let mut filter = process.splat_filter();
+        let mut changed = false;
+
+        let mut crop_enabled = filter.crop.is_some();
+        if ui.checkbox(&mut crop_enabled, "Crop").changed() {
+            filter.crop = crop_enabled
+                .then(|| BoundingBox::from_min_max(Vec3::splat(-5.0), Vec3::splat(5.0)));
+            changed = true;
+        }
+
+        if let Some(crop) = filter.crop {
+            let (mut min, mut max) = (crop.min(), crop.max());
+            egui::Grid::new("crop_grid")
+                .num_columns(3)
+                .spacing([6.0, 4.0])
+                .show(ui, |ui| {
+                    for (axis, label) in ["X", "Y", "Z"].into_iter().enumerate() {
+                        ui.label(RichText::new(label).size(12.0));
+                        changed |= ui
+                            .add(Slider::new(&mut min[axis], -20.0..=max[axis]).show_value(true))
+                            .changed();
+                        changed |= ui
+                            .add(Slider::new(&mut max[axis], min[axis]..=20.0).show_value(true))
+                            .changed();
+                        ui.end_row();
+                    }
+                });
+            filter.crop = Some(BoundingBox::from_min_max(min, max));
+        }
+
+        ui.label(RichText::new("Min Opacity").size(12.0));
+        let mut min_opacity = filter.min_opacity.unwrap_or(0.0);
+        if ui
+            .add(Slider::new(&mut min_opacity, 0.0..=1.0).show_value(true))
+            .changed()
+        {
+            filter.min_opacity = (min_opacity > 0.0).then_some(min_opacity);
+            changed = true;
+        }
+
+        if changed {
+            process.set_splat_filter(filter);
+        }
+    }
+
     fn draw_controls_content(ui: &mut egui::Ui, process: &UiProcess) {
         ui.spacing_mut().item_spacing.y = 6.0;
 
-        // FOV slider
-        ui.label(RichText::new("Field of View").size(12.0));
+        let mut orthographic = process.is_cam_orthographic();
+        if ui.checkbox(&mut orthographic, "Orthographic").changed() {
+            process.set_cam_orthographic(orthographic);
+        }
+
         let current_camera = process.current_camera();
-        let mut fov_degrees = current_camera.fov_y.to_degrees() as f32;
+        if orthographic {
+            // View height slider, the extent is stored in the fov fields.
+            ui.label(RichText::new("View Height").size(12.0));
+            let mut height = current_camera.fov_y as f32;
+
+            let response = ui.add(
+                Slider::new(&mut height, 0.01..=1000.0)
+                    .logarithmic(true)
+                    .show_value(true)
+                    .custom_formatter(|val, _| format!("{val:.2}")),
+            );
 
-        let response = ui.add(
-            Slider::new(&mut fov_degrees, 10.0..=140.0)
-                .suffix("°")
-                .show_value(true)
-                .custom_formatter(|val, _| format!("{val:.0}°")),
-        );
+            if response.changed() {
+                process.set_cam_ortho_height(height as f64);
+            }
+        } else {
+            // FOV slider
+            ui.label(RichText::new("Field of View").size(12.0));
+            let mut fov_degrees = current_camera.fov_y.to_degrees() as f32;
+
+            let response = ui.add(
+                Slider::new(&mut fov_degrees, 10.0..=140.0)
+                    .suffix("°")
+                    .show_value(true)
+                    .custom_formatter(|val, _| format!("{val:.0}°")),
+            );
 
-        if response.changed() {
-            process.set_cam_fov(fov_degrees.to_radians() as f64);
+            if response.changed() {
+                process.set_cam_fov(fov_degrees.to_radians() as f64);
+            }
         }
 
         // Splat scale slider
@@ -533,6 +607,77 @@ impl ScenePanel {
             process.set_cam_settings(&settings);
         }
 
+        // Supersampling factor
+        ui.label(RichText::new("Anti-aliasing").size(12.0));
+        let mut settings = process.get_cam_settings();
+        let mut ssaa = settings.ssaa.unwrap_or(1);
+
+        let response = ui.add(
+            Slider::new(&mut ssaa, 1..=4)
+                .show_value(true)
+                .custom_formatter(|val, _| format!("{val:.0}x")),
+        );
+
+        if response.changed() {
+            settings.ssaa = Some(ssaa);
+            process.set_cam_settings(&settings);
+        }
+
+        // Render quality, by how many SH bands are evaluated
+        ui.label(RichText::new("Quality").size(12.0));
+        let mut settings = process.get_cam_settings();
+        let mut sh_degree = settings.sh_degree_override;
+        let qualities = [("Low", Some(0)), ("Medium", Some(1)), ("Full", None)];
+        let selected = qualities
+            .iter()
+            .find(|(_, degree)| *degree == sh_degree)
+            .map_or("Custom", |(label, _)| *label);
+        egui::ComboBox::from_id_salt("render_quality")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (label, degree) in qualities {
+                    ui.selectable_value(&mut sh_degree, degree, label);
+                }
+            })
+            .response
+            .on_hover_text("Lower qualities skip the view dependent colors, which renders faster");
+        if sh_degree != settings.sh_degree_override {
+            settings.sh_degree_override = sh_degree;
+            process.set_cam_settings(&settings);
+        }
+
+        // Resolution while the camera moves
+        ui.label(RichText::new("Motion Resolution").size(12.0));
+        let mut settings = process.get_cam_settings();
+        let mut motion_scale = settings.motion_scale.unwrap_or(1.0);
+
+        let response = ui.add(
+            Slider::new(&mut motion_scale, 0.25..=1.0)
+                .show_value(true)
+                .custom_formatter(|val, _| format!("{:.0}%", val * 100.0)),
+        );
+
+        if response.changed() {
+            settings.motion_scale = Some(motion_scale);
+            process.set_cam_settings(&settings);
+        }
+
+        // Debug visualization
+        ui.label(RichText::new("Debug view").size(12.0));
+        let mut settings = process.get_cam_settings();
+        let mut debug_view = settings.debug_view;
+        egui::ComboBox::from_id_salt("debug_view")
+            .selected_text(debug_view.label())
+            .show_ui(ui, |ui| {
+                for view in DebugView::ALL {
+                    ui.selectable_value(&mut debug_view, view, view.label());
+                }
+            });
+        if debug_view != settings.debug_view {
+            settings.debug_view = debug_view;
+            process.set_cam_settings(&settings);
+        }
+
         // Fly speed slider
         ui.label(RichText::new("Fly Speed").size(12.0));
         let mut settings = process.get_cam_settings();
@@ -552,6 +697,10 @@ impl ScenePanel {
 
         ui.add_space(6.0);
 
+        Self::draw_filter_controls(ui, process);
+
+        ui.add_space(6.0);
+
         // Grid toggle
         let mut settings = process.get_cam_settings();
         let mut enabled = settings.grid_enabled.unwrap_or(false);
@@ -611,8 +760,12 @@ impl AppPane for ScenePanel {
                         ..Default::default()
                     },
                 );
+                let mut details = format!("  |  {t}");
+                if let Some(progress) = self.load_progress {
+                    details += &format!("  |  Loading {:.0}%", progress * 100.0);
+                }
                 job.append(
-                    &format!("  |  {t}"),
+                    &details,
                     0.0,
                     egui::TextFormat {
                         color: Color32::from_rgb(140, 140, 140),
@@ -846,6 +999,7 @@ impl AppPane for ScenePanel {
                 up_axis,
                 frame,
                 total_frames,
+                progress,
                 ..
             } => {
                 self.has_splats = true;
@@ -854,6 +1008,7 @@ impl AppPane for ScenePanel {
                 // For non-training updates (e.g., loading), always redraw
                 if !process.is_training() {
                     self.splats_dirty = true;
+                    self.load_progress = (*progress < 1.0).then_some(*progress);
 
                     // When training, datasets handle this.
                     if let Some(up_axis) = up_axis {
@@ -879,16 +1034,20 @@ impl AppPane for ScenePanel {
                     }
                 }
             }
+            ProcessMessage::DoneLoading => {
+                self.load_progress = None;
+            }
             ProcessMessage::Warning { error } => {
                 self.warnings.push(ErrorDisplay::new(error));
             }
             ProcessMessage::TrainMessage(brush_process::message::TrainMessage::TrainConfig {
                 config,
             }) => {
-                let bg = &config.train_config.background_color;
-                let mut settings = process.get_cam_settings();
-                settings.background = Some(glam::vec3(bg[0], bg[1], bg[2]));
-                process.set_cam_settings(&settings);
+                if let Some(background) = config.train_config.initial_background() {
+                    let mut settings = process.get_cam_settings();
+                    settings.background = Some(background);
+                    process.set_cam_settings(&settings);
+                }
             }
             ProcessMessage::TrainMessage(brush_process::message::TrainMessage::Dataset {
                 dataset,
@@ -1067,6 +1226,11 @@ impl AppPane for ScenePanel {
                         self.frame as usize,
                         settings.background.unwrap_or(Vec3::ZERO),
                         settings.splat_scale,
+                        settings.sh_degree_override,
+                        settings.ssaa,
+                        settings.motion_scale,
+                        process.splat_filter(),
+                        settings.debug_view,
                         self.splats_dirty,
                     );
                     self.splats_dirty = false;
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use brush_process::adapter::AdapterDescription;
 use brush_process::config::TrainStreamConfig;
 use brush_process::message::{ProcessMessage, TrainMessage};
 
@@ -7,12 +8,14 @@ use crate::ui::UiMode;
 use crate::ui::panels::AppPane;
 use crate::ui::settings_popup::draw_settings;
 use crate::ui::ui_process::UiProcess;
+use eframe::egui_wgpu::RenderState;
 
 #[derive(Default)]
 pub struct SettingsPanel {
     config: Option<TrainStreamConfig>,
     base_path: Option<PathBuf>,
     save_status: Option<(String, web_time::Instant)>,
+    adapter: Option<String>,
 }
 
 impl AppPane for SettingsPanel {
@@ -20,6 +23,10 @@ impl AppPane for SettingsPanel {
         "Settings".into()
     }
 
+    fn init(&mut self, state: &RenderState, _process: &UiProcess) {
+        self.adapter = Some(state.adapter.get_info().summary());
+    }
+
     fn is_visible(&self, process: &UiProcess) -> bool {
         process.ui_mode() == UiMode::Default && process.is_training()
     }
@@ -50,6 +57,18 @@ impl AppPane for SettingsPanel {
             ui.add_space(4.0);
         }
 
+        // On WASM, adapter info is mostly private, not worth showing.
+        if !cfg!(target_family = "wasm")
+            && let Some(adapter) = &self.adapter
+        {
+            ui.label(
+                egui::RichText::new(format!("Training on {adapter}"))
+                    .size(12.0)
+                    .weak(),
+            );
+            ui.add_space(4.0);
+        }
+
         let Some(config) = &mut self.config else {
             ui.label("Waiting for training to start...");
             return;
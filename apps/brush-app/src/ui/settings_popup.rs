@@ -1,9 +1,11 @@
 use std::ops::RangeInclusive;
 use std::path::PathBuf;
 
+use brush_dataset::config::EvalSplitStrategy;
 use brush_process::config::TrainStreamConfig;
 use brush_render::AlphaMode;
 use brush_render::gaussian_splats::SplatRenderMode;
+use brush_train::background::BackgroundMode;
 use egui::{Align2, Slider, Ui};
 use tokio::sync::oneshot::Sender;
 
@@ -118,6 +120,14 @@ pub(crate) fn draw_settings(ui: &mut Ui, args: &mut TrainStreamConfig, enabled:
             true,
             enabled,
         );
+        slider(
+            ui,
+            &mut tc.lr_exposure,
+            1e-4..=1e-2,
+            "exposure",
+            true,
+            enabled,
+        );
     });
 
     ui.collapsing("Growth & refinement", |ui| {
@@ -190,10 +200,24 @@ pub(crate) fn draw_settings(ui: &mut Ui, args: &mut TrainStreamConfig, enabled:
             false,
             enabled,
         );
+        ui.add_enabled(
+            enabled,
+            egui::Checkbox::new(&mut tc.learn_exposure, "Learn per-view exposure"),
+        );
     });
 
     ui.collapsing("Background", |ui| {
         let tc = &mut args.train_config;
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.horizontal(|ui| {
+                let mode = &mut tc.background;
+                ui.selectable_value(mode, BackgroundMode::Color, "Color");
+                ui.selectable_value(mode, BackgroundMode::Black, "Black");
+                ui.selectable_value(mode, BackgroundMode::White, "White");
+                ui.selectable_value(mode, BackgroundMode::Random, "Random");
+                ui.selectable_value(mode, BackgroundMode::Learned, "Learned");
+            });
+        });
         ui.horizontal(|ui| {
             ui.add_enabled_ui(enabled, |ui| {
                 let mut color = egui::Color32::from_rgb(
@@ -324,6 +348,20 @@ pub(crate) fn draw_settings(ui: &mut Ui, args: &mut TrainStreamConfig, enabled:
                 .prefix("1 out of ")
                 .suffix(" frames"),
         );
+
+        let strategy = &mut args.load_config.eval_split_strategy;
+        let random = match *strategy {
+            EvalSplitStrategy::Random { seed } => EvalSplitStrategy::Random { seed },
+            _ => EvalSplitStrategy::Random { seed: 0 },
+        };
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(strategy, EvalSplitStrategy::EveryN, "Every nth");
+                ui.selectable_value(strategy, EvalSplitStrategy::FarthestPoint, "Spread out")
+                    .on_hover_text("Pick eval frames far apart from each other");
+                ui.selectable_value(strategy, random, "Random");
+            });
+        });
     }
 
     let mut subsample_frames = args.load_config.subsample_frames.is_some();
@@ -441,6 +479,10 @@ pub(crate) fn draw_settings(ui: &mut Ui, args: &mut TrainStreamConfig, enabled:
             enabled,
             egui::Checkbox::new(&mut pc.eval_save_to_disk, "Save Eval images to disk"),
         );
+        ui.add_enabled(
+            enabled,
+            egui::Checkbox::new(&mut pc.eval_lpips, "Compute LPIPS"),
+        );
     });
 
     ui.add_space(15.0);
@@ -1,10 +1,20 @@
 struct Uniforms {
     img_width: u32,
     img_height: u32,
+    // Non-zero when the image is f32 RGBA instead of packed RGBA8.
+    float_image: u32,
+    // Same for the image being faded out.
+    prev_width: u32,
+    prev_height: u32,
+    prev_float_image: u32,
+    // How far the fade from the previous image is, 1 when there's none.
+    fade: f32,
+    _padding: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
 @group(0) @binding(1) var<storage, read> image_data: array<u32>;
+@group(0) @binding(2) var<storage, read> prev_data: array<u32>;
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -22,18 +32,7 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     return out;
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let pixel_x = u32(in.uv.x * f32(uniforms.img_width));
-    let pixel_y = u32(in.uv.y * f32(uniforms.img_height));
-
-    if (pixel_x >= uniforms.img_width || pixel_y >= uniforms.img_height) {
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
-    }
-
-    let idx = pixel_y * uniforms.img_width + pixel_x;
-    let packed = image_data[idx];
-
+fn unpack_rgba8(packed: u32) -> vec4<f32> {
     // Unpack RGBA8: R|(G<<8)|(B<<16)|(A<<24)
     let r = f32(packed & 0xFFu) / 255.0;
     let g = f32((packed >> 8u) & 0xFFu) / 255.0;
@@ -41,3 +40,85 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let a = f32((packed >> 24u) & 0xFFu) / 255.0;
     return vec4<f32>(r, g, b, a);
 }
+
+fn load_image(idx: u32) -> vec4<f32> {
+    if (uniforms.float_image != 0u) {
+        let base = idx * 4u;
+        return clamp(vec4<f32>(
+            bitcast<f32>(image_data[base]),
+            bitcast<f32>(image_data[base + 1u]),
+            bitcast<f32>(image_data[base + 2u]),
+            bitcast<f32>(image_data[base + 3u]),
+        ), vec4<f32>(0.0), vec4<f32>(1.0));
+    }
+    return unpack_rgba8(image_data[idx]);
+}
+
+fn load_prev(idx: u32) -> vec4<f32> {
+    if (uniforms.prev_float_image != 0u) {
+        let base = idx * 4u;
+        return clamp(vec4<f32>(
+            bitcast<f32>(prev_data[base]),
+            bitcast<f32>(prev_data[base + 1u]),
+            bitcast<f32>(prev_data[base + 2u]),
+            bitcast<f32>(prev_data[base + 3u]),
+        ), vec4<f32>(0.0), vec4<f32>(1.0));
+    }
+    return unpack_rgba8(prev_data[idx]);
+}
+
+// The four texels around `uv` and their bilinear weights. When the image is
+// as large as the viewport this lands exactly on a texel, so it only upscales
+// the smaller renders made while the camera moves.
+struct Taps {
+    idx: vec4<u32>,
+    weight: vec4<f32>,
+}
+
+fn bilinear_taps(uv: vec2<f32>, width: u32, height: u32) -> Taps {
+    let size = vec2<f32>(f32(width), f32(height));
+    let pos = clamp(uv * size - 0.5, vec2<f32>(0.0), size - 1.0);
+    let p0 = vec2<u32>(floor(pos));
+    let p1 = min(p0 + 1u, vec2<u32>(width - 1u, height - 1u));
+    let f = fract(pos);
+
+    var taps: Taps;
+    taps.idx = vec4<u32>(
+        p0.y * width + p0.x,
+        p0.y * width + p1.x,
+        p1.y * width + p0.x,
+        p1.y * width + p1.x,
+    );
+    taps.weight = vec4<f32>(
+        (1.0 - f.x) * (1.0 - f.y),
+        f.x * (1.0 - f.y),
+        (1.0 - f.x) * f.y,
+        f.x * f.y,
+    );
+    return taps;
+}
+
+fn sample_image(uv: vec2<f32>) -> vec4<f32> {
+    let t = bilinear_taps(uv, uniforms.img_width, uniforms.img_height);
+    return load_image(t.idx.x) * t.weight.x + load_image(t.idx.y) * t.weight.y
+        + load_image(t.idx.z) * t.weight.z + load_image(t.idx.w) * t.weight.w;
+}
+
+fn sample_prev(uv: vec2<f32>) -> vec4<f32> {
+    let t = bilinear_taps(uv, uniforms.prev_width, uniforms.prev_height);
+    return load_prev(t.idx.x) * t.weight.x + load_prev(t.idx.y) * t.weight.y
+        + load_prev(t.idx.z) * t.weight.z + load_prev(t.idx.w) * t.weight.w;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (any(in.uv < vec2<f32>(0.0)) || any(in.uv >= vec2<f32>(1.0))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+
+    let color = sample_image(in.uv);
+    if (uniforms.fade >= 1.0) {
+        return color;
+    }
+    return mix(sample_prev(in.uv), color, uniforms.fade);
+}
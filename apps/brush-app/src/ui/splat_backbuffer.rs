@@ -1,15 +1,75 @@
 use brush_async::{Actor, AsyncMap};
 use brush_process::slot::Slot;
 use brush_render::{
-    TextureMode, burn_glue::resolve_to_cube_float, camera::Camera, gaussian_splats::Splats,
-    render_splats,
+    TextureMode,
+    burn_glue::resolve_to_cube_float,
+    camera::Camera,
+    gaussian_splats::{SplatFilter, Splats},
+    render_splats, render_splats_supersampled,
+    shaders::helpers::TILE_WIDTH,
 };
-use burn::tensor::Tensor;
+use burn::tensor::{Int, Tensor, s};
 use egui::Rect;
 use glam::{UVec2, Vec3};
+use web_time::{Duration, Instant};
 
 use eframe::egui_wgpu::{self, CallbackTrait, wgpu};
 
+/// Debug visualization shown instead of the splat colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off,
+    /// Heatmap of the number of splats blended into each pixel.
+    Overdraw,
+    /// Heatmap of the number of splats intersecting each tile.
+    TileDepth,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Overdraw, Self::TileDepth];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Overdraw => "Overdraw",
+            Self::TileDepth => "Tile depth",
+        }
+    }
+}
+
+/// Map counts to an f32 RGBA `[H, W, 4]` image, blue for few to red for the
+/// most. Counts are log scaled as a few very deep spots would otherwise wash
+/// out the rest, and zero is left transparent.
+fn heatmap(counts: Tensor<2, Int>) -> Tensor<3> {
+    let [h, w] = counts.dims();
+    let counts = counts.float().reshape([h, w, 1]);
+    let covered = counts.clone().greater_elem(0.0).float();
+    let scaled = counts.log1p();
+    let t = scaled.clone() / scaled.max().clamp_min(f32::EPSILON).reshape([1, 1, 1]);
+    // Piecewise linear "jet" ramp.
+    let ramp = |center: f32| {
+        (t.clone() * 4.0 - center)
+            .abs()
+            .neg()
+            .add_scalar(1.5)
+            .clamp(0.0, 1.0)
+    };
+    Tensor::cat(vec![ramp(3.0), ramp(2.0), ramp(1.0), covered], 2)
+}
+
+/// Expand per tile values to per pixel values, cropped to `img_size`.
+fn tiles_to_pixels(tiles: Tensor<2, Int>, img_size: UVec2) -> Tensor<2, Int> {
+    let [ty, tx] = tiles.dims();
+    let tw = TILE_WIDTH as usize;
+    tiles
+        .reshape([ty, 1, tx, 1])
+        .repeat_dim(1, tw)
+        .repeat_dim(3, tw)
+        .reshape([ty * tw, tx * tw])
+        .slice(s![0..img_size.y as usize, 0..img_size.x as usize])
+}
+
 #[derive(Clone)]
 struct RenderRequest {
     splats: Slot<Splats>,
@@ -23,11 +83,56 @@ struct LastRenderState {
     camera: Camera,
     background: Vec3,
     splat_scale: Option<f32>,
+    sh_degree: Option<u32>,
+    ssaa: u32,
+    filter: SplatFilter,
+    debug_view: DebugView,
     img_size: UVec2,
 }
 
+/// How long the camera has to stay still before rendering at full resolution again.
+const SETTLE_TIME: Duration = Duration::from_millis(150);
+/// How long the full resolution render takes to fade in over the motion render.
+const FADE_TIME: Duration = Duration::from_millis(120);
+
+/// Tracks camera motion, to render at a reduced resolution while the camera
+/// moves, see [`crate::ui::app::CameraSettings::motion_scale`].
+#[derive(Default)]
+struct MotionTracker {
+    last_camera: Option<Camera>,
+    last_motion: Option<Instant>,
+}
+
+impl MotionTracker {
+    /// Whether the camera counts as moving at `now`: it changed since the last
+    /// frame, or did so less than [`SETTLE_TIME`] ago.
+    fn update(&mut self, camera: &Camera, now: Instant) -> bool {
+        if self.last_camera.is_some_and(|last| last != *camera) {
+            self.last_motion = Some(now);
+        }
+        self.last_camera = Some(*camera);
+        self.last_motion
+            .is_some_and(|t| now.saturating_duration_since(t) < SETTLE_TIME)
+    }
+}
+
+struct Fade {
+    from: Tensor<3>,
+    start: Instant,
+}
+
+fn image_size(image: &Tensor<3>) -> UVec2 {
+    let shape = image.shape();
+    UVec2::new(shape[1] as u32, shape[0] as u32)
+}
+
 pub struct SplatBackbuffer {
     pipe: AsyncMap<RenderRequest, Tensor<3>>,
+    motion: MotionTracker,
+    /// The image painted last frame.
+    shown: Option<Tensor<3>>,
+    /// Cross-fade from a motion render to the full resolution render.
+    fade: Option<Fade>,
 }
 
 impl SplatBackbuffer {
@@ -45,25 +150,73 @@ impl SplatBackbuffer {
         let pipe = AsyncMap::new(
             actor,
             async move |req: &RenderRequest| {
-                let (image, _) = render_splats(
-                    req.splats.get(req.state.frame).unwrap(),
-                    &req.state.camera,
-                    req.state.img_size,
-                    req.state.background,
-                    req.state.splat_scale,
-                    TextureMode::Packed,
-                )
-                .await;
-                image
+                let state = &req.state;
+                let splats = req
+                    .splats
+                    .get(state.frame)
+                    .unwrap()
+                    .filter(&state.filter)
+                    .await;
+                let splats = match state.sh_degree {
+                    Some(degree) => splats.clamp_sh_degree(degree),
+                    None => splats,
+                };
+                match state.debug_view {
+                    DebugView::Off => {}
+                    DebugView::Overdraw => {
+                        let counts = splats.render_overdraw(&state.camera, state.img_size).await;
+                        return heatmap(counts);
+                    }
+                    DebugView::TileDepth => {
+                        let (_, aux) = render_splats(
+                            splats,
+                            &state.camera,
+                            state.img_size,
+                            state.background,
+                            state.splat_scale,
+                            TextureMode::Packed,
+                        )
+                        .await;
+                        return heatmap(tiles_to_pixels(aux.tile_counts(), state.img_size));
+                    }
+                }
+                if state.ssaa > 1 {
+                    // Downsampling needs the f32 image, the painter reads either layout.
+                    render_splats_supersampled(
+                        splats,
+                        &state.camera,
+                        state.img_size,
+                        state.background,
+                        state.splat_scale,
+                        state.ssaa,
+                    )
+                    .await
+                } else {
+                    let (image, _) = render_splats(
+                        splats,
+                        &state.camera,
+                        state.img_size,
+                        state.background,
+                        state.splat_scale,
+                        TextureMode::Packed,
+                    )
+                    .await;
+                    image
+                }
             },
             |req: &RenderRequest| req.ctx.request_repaint(),
         );
 
-        Self { pipe }
+        Self {
+            pipe,
+            motion: MotionTracker::default(),
+            shown: None,
+            fade: None,
+        }
     }
 
     pub fn paint(
-        &self,
+        &mut self,
         rect: Rect,
         ui: &egui::Ui,
         splats: &Slot<Splats>,
@@ -71,21 +224,47 @@ impl SplatBackbuffer {
         frame: usize,
         background: Vec3,
         splat_scale: Option<f32>,
+        sh_degree: Option<u32>,
+        ssaa: Option<u32>,
+        motion_scale: Option<f32>,
+        filter: SplatFilter,
+        debug_view: DebugView,
         splats_dirty: bool,
     ) {
         // Calculate pixel size for rendering
         let ppp = ui.ctx().pixels_per_point();
-        let img_size = UVec2::new(
+        let full_size = UVec2::new(
             (rect.width() * ppp).round() as u32,
             (rect.height() * ppp).round() as u32,
         );
 
+        let now = Instant::now();
+        let moving = self.motion.update(camera, now);
+        let motion_scale = motion_scale.filter(|&scale| moving && scale < 1.0);
+        if motion_scale.is_some() {
+            // Check back once the camera might have settled.
+            ui.ctx().request_repaint_after(SETTLE_TIME);
+        }
+        let img_size = match motion_scale {
+            Some(scale) => (full_size.as_vec2() * scale.max(0.05))
+                .round()
+                .as_uvec2()
+                .max(UVec2::ONE),
+            None => full_size,
+        };
+        // Supersampling while moving defeats the point of the lower resolution.
+        let ssaa = if motion_scale.is_some() { None } else { ssaa };
+
         // Check if we need to re-render
         let current_state = LastRenderState {
             frame,
             camera: *camera,
             background,
             splat_scale,
+            sh_degree,
+            ssaa: ssaa.unwrap_or(1).max(1),
+            filter,
+            debug_view,
             img_size,
         };
 
@@ -101,18 +280,32 @@ impl SplatBackbuffer {
         }
 
         if let Some(image) = self.pipe.latest() {
-            let shape = image.shape();
-            let img_height = shape[0] as u32;
-            let img_width = shape[1] as u32;
+            // Fade in when a sharper render replaces a motion render.
+            if let Some(shown) = &self.shown {
+                let (old, new) = (image_size(shown), image_size(&image));
+                if old != new && old.cmple(new).all() {
+                    self.fade = Some(Fade {
+                        from: shown.clone(),
+                        start: now,
+                    });
+                }
+            }
+            let fade = self.fade.as_ref().and_then(|fade| {
+                let t = now.saturating_duration_since(fade.start).as_secs_f32()
+                    / FADE_TIME.as_secs_f32();
+                (t < 1.0).then(|| (fade.from.clone(), t))
+            });
+            if fade.is_some() {
+                ui.ctx().request_repaint();
+            } else {
+                self.fade = None;
+            }
+            self.shown = Some(image.clone());
 
             ui.painter()
                 .add(eframe::egui_wgpu::Callback::new_paint_callback(
                     rect,
-                    SplatBackbufferPainter {
-                        last_img: image,
-                        img_width,
-                        img_height,
-                    },
+                    SplatBackbufferPainter { image, fade },
                 ));
         }
     }
@@ -123,6 +316,13 @@ impl SplatBackbuffer {
 struct Uniforms {
     img_width: u32,
     img_height: u32,
+    float_image: u32,
+    prev_width: u32,
+    prev_height: u32,
+    prev_float_image: u32,
+    /// How far the fade from the previous image is, 1 when there's none.
+    fade: f32,
+    _padding: u32,
 }
 
 pub struct SplatBackbufferResources {
@@ -170,6 +370,17 @@ impl SplatBackbufferResources {
                     },
                     count: None,
                 },
+                // Storage buffer of the image being faded out
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -221,9 +432,14 @@ impl SplatBackbufferResources {
 }
 
 struct SplatBackbufferPainter {
-    last_img: Tensor<3>,
-    img_width: u32,
-    img_height: u32,
+    image: Tensor<3>,
+    /// The image to fade from, and how far the fade is.
+    fade: Option<(Tensor<3>, f32)>,
+}
+
+/// Size of an image, and whether it's f32 RGBA rather than packed RGBA8.
+fn image_layout(image: &Tensor<3>) -> (UVec2, u32) {
+    (image_size(image), u32::from(image.shape()[2] == 4))
 }
 
 impl CallbackTrait for SplatBackbufferPainter {
@@ -239,22 +455,40 @@ impl CallbackTrait for SplatBackbufferPainter {
             return Vec::new();
         };
 
+        // Without a fade, bind the current image in the fade slot too.
+        let (prev, fade) = match &self.fade {
+            Some((prev, t)) => (prev, *t),
+            None => (&self.image, 1.0),
+        };
+        let (img_size, float_image) = image_layout(&self.image);
+        let (prev_size, prev_float_image) = image_layout(prev);
+
         // Update uniform buffer with image dimensions
         queue.write_buffer(
             &res.uniform_buffer,
             0,
             bytemuck::cast_slice(&[Uniforms {
-                img_width: self.img_width,
-                img_height: self.img_height,
+                img_width: img_size.x,
+                img_height: img_size.y,
+                float_image,
+                prev_width: prev_size.x,
+                prev_height: prev_size.y,
+                prev_float_image,
+                fade,
+                _padding: 0,
             }]),
         );
 
-        // Extract the wgpu buffer from the Burn tensor
-        let prim_tensor = resolve_to_cube_float(self.last_img.clone());
-        let img_res_handle = prim_tensor
-            .client
-            .get_resource(prim_tensor.handle)
-            .expect("Failed to get img resource");
+        // Extract the wgpu buffers from the Burn tensors
+        let resource = |image: &Tensor<3>| {
+            let prim_tensor = resolve_to_cube_float(image.clone());
+            prim_tensor
+                .client
+                .get_resource(prim_tensor.handle)
+                .expect("Failed to get img resource")
+        };
+        let img_res_handle = resource(&self.image);
+        let prev_res_handle = resource(prev);
 
         // Create a new bind group with the current tensor buffer
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -269,6 +503,10 @@ impl CallbackTrait for SplatBackbufferPainter {
                     binding: 1,
                     resource: img_res_handle.resource().buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: prev_res_handle.resource().buffer.as_entire_binding(),
+                },
             ],
         });
 
@@ -295,3 +533,43 @@ impl CallbackTrait for SplatBackbufferPainter {
         render_pass.draw(0..3, 0..1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_render::camera::Camera;
+    use brush_render::kernels::camera_model::CameraModel;
+
+    fn camera(x: f32) -> Camera {
+        Camera::new(
+            Vec3::new(x, 0.0, 0.0),
+            glam::Quat::IDENTITY,
+            0.8,
+            0.8,
+            glam::vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        )
+    }
+
+    #[test]
+    fn test_motion_tracker() {
+        let mut tracker = MotionTracker::default();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        // A still camera renders at full resolution.
+        assert!(!tracker.update(&camera(0.0), start));
+        assert!(!tracker.update(&camera(0.0), start + ms(16)));
+
+        // Moving drops to the motion resolution.
+        assert!(tracker.update(&camera(1.0), start + ms(32)));
+        assert!(tracker.update(&camera(2.0), start + ms(48)));
+
+        // It stays low until the camera has been still for the settle time.
+        assert!(tracker.update(&camera(2.0), start + ms(100)));
+        assert!(!tracker.update(&camera(2.0), start + ms(48) + SETTLE_TIME));
+
+        // And drops again on the next move.
+        assert!(tracker.update(&camera(3.0), start + ms(1000)));
+    }
+}
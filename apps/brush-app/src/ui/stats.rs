@@ -141,13 +141,24 @@ impl AppPane for StatsPanel {
                     iter: _,
                     avg_psnr,
                     avg_ssim,
+                    avg_lpips,
+                    avg_corrected,
+                    report: _,
+                    refined_poses: _,
                 } => {
-                    self.last_eval = Some(format!("{avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM"));
+                    let mut eval = format!("{avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM");
+                    if let Some(lpips) = avg_lpips {
+                        eval += &format!(", {lpips:.3} LPIPS");
+                    }
+                    if let Some((psnr, ssim)) = avg_corrected {
+                        eval += &format!(" ({psnr:.2} PSNR, {ssim:.3} SSIM exposure corrected)");
+                    }
+                    self.last_eval = Some(eval);
                 }
                 TrainMessage::DoneTraining => {
                     self.training_complete = true;
                 }
-                TrainMessage::RefineStep { .. } => {}
+                TrainMessage::RefineStep { .. } | TrainMessage::SplatsExported { .. } => {}
             },
             _ => {}
         }
@@ -1,8 +1,11 @@
+use std::path::Path;
+
 use anyhow::Error;
 use brush_async::Actor;
 use brush_process::config::TrainStreamConfig;
 use brush_process::message::{ProcessMessage, TrainMessage};
-use brush_render::gaussian_splats::Splats;
+use brush_render::gaussian_splats::{SplatFilter, Splats};
+use brush_serde::{AppearanceMetadata, ExportFormat};
 use egui::RichText;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use web_time::Duration;
@@ -21,6 +24,8 @@ pub struct TrainingPanel {
     export_channel: (UnboundedSender<Error>, UnboundedReceiver<Error>),
     training_done: bool,
     lod_progress: Option<(u32, u32)>,
+    /// Apply the viewer's model transform to the splats on export.
+    bake_transform: bool,
     // Owns the export worker thread. One Actor for the whole panel
     // lifetime; export clicks just queue more work on it.
     export_actor: Actor,
@@ -38,6 +43,7 @@ impl Default for TrainingPanel {
             export_channel: tokio::sync::mpsc::unbounded_channel(),
             training_done: false,
             lod_progress: None,
+            bake_transform: false,
             export_actor: Actor::new("training-panel-export"),
         }
     }
@@ -64,6 +70,7 @@ impl TrainingPanel {
                 iter,
                 total_elapsed,
                 lod_progress,
+                ..
             } => {
                 self.train_progress = Some(*iter);
                 self.lod_progress = *lod_progress;
@@ -91,9 +98,34 @@ impl TrainingPanel {
     }
 }
 
-async fn export(splat: Splats, up_axis: Option<glam::Vec3>) -> Result<(), Error> {
-    let data = brush_serde::splat_to_ply(splat, up_axis).await?;
-    rrfd::save_file("export.ply", data).await?;
+async fn export(
+    splat: Splats,
+    filter: SplatFilter,
+    up_axis: Option<glam::Vec3>,
+    bake_transform: Option<glam::Affine3A>,
+) -> Result<(), Error> {
+    let target = rrfd::pick_save_target("export.ply").await?;
+    // Only export what's visible in the viewer.
+    let splat = splat.filter(&filter).await;
+    // The baked splats are in the viewer's frame, which already has the up axis applied.
+    let (splat, up_axis) = match bake_transform {
+        Some(transform) => (splat.transformed(transform), None),
+        None => (splat, up_axis),
+    };
+    // Pick the format from whatever extension the user typed, falling back to ply.
+    let format = ExportFormat::from_path(Path::new(&target.name)).unwrap_or_default();
+    let appearance = AppearanceMetadata::default();
+
+    // Stream PLY files straight to disk, large scenes can be hundreds of megabytes.
+    #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+    if format == ExportFormat::Ply {
+        let mut file = tokio::io::BufWriter::new(target.create().await?);
+        brush_serde::stream_splat_to_ply(splat, up_axis, &appearance, &mut file).await?;
+        return Ok(());
+    }
+
+    let data = brush_serde::splat_export(splat, up_axis, &appearance, format).await?;
+    target.save(data).await?;
     Ok(())
 }
 
@@ -256,6 +288,21 @@ impl AppPane for TrainingPanel {
                 {
                     process.set_train_paused(!paused);
                 }
+
+                if ui
+                    .add(
+                        egui::Button::new(
+                            RichText::new("⏹").size(14.0).color(egui::Color32::WHITE),
+                        )
+                        .min_size(egui::vec2(28.0, 20.0))
+                        .corner_radius(6.0)
+                        .fill(egui::Color32::from_rgb(70, 70, 75)),
+                    )
+                    .on_hover_text("Stop training")
+                    .clicked()
+                {
+                    process.stop_training();
+                }
             }
 
             if process.is_training() {
@@ -294,16 +341,26 @@ impl AppPane for TrainingPanel {
                             return;
                         };
                         let up_axis = process.up_axis();
+                        let filter = process.splat_filter();
+                        let bake_transform =
+                            self.bake_transform.then(|| process.model_local_to_world());
 
                         self.export_actor
                             .run(move || async move {
-                                if let Err(e) = export(splats, up_axis).await {
+                                if let Err(e) =
+                                    export(splats, filter, up_axis, bake_transform).await
+                                {
                                     let _ = sender.send(e);
                                     ctx.request_repaint();
                                 }
                             })
                             .detach();
                     }
+                    ui.checkbox(&mut self.bake_transform, "Bake orientation")
+                        .on_hover_text(
+                            "Export the splats as currently oriented in the viewer, \
+                             instead of in their original coordinates",
+                        );
                 });
             }
         });
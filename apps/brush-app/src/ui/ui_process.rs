@@ -1,7 +1,11 @@
 use anyhow::Result;
 use brush_async::Actor;
-use brush_process::{RunningProcess, message::ProcessMessage, slot::Slot};
-use brush_render::{camera::Camera, gaussian_splats::Splats, kernels::camera_model::CameraModel};
+use brush_process::{CancellationToken, RunningProcess, message::ProcessMessage, slot::Slot};
+use brush_render::{
+    camera::Camera,
+    gaussian_splats::{SplatFilter, Splats},
+    kernels::camera_model::CameraModel,
+};
 use burn_wgpu::WgpuDevice;
 use egui::{Response, TextureHandle};
 use glam::{Affine3A, Quat, Vec3};
@@ -19,9 +23,18 @@ enum ControlMessage {
 struct ProcessHandle {
     messages: mpsc::UnboundedReceiver<anyhow::Result<ProcessMessage>>,
     control: mpsc::UnboundedSender<ControlMessage>,
+    cancel: CancellationToken,
     splat_view: Slot<Splats>,
 }
 
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        // Nothing listens to the process anymore, eg. as a new source was picked. Stop it
+        // now, rather than when it next sends a message, so downloads are dropped promptly.
+        self.cancel.cancel();
+    }
+}
+
 /// A thread-safe wrapper around the UI process.
 /// This allows the UI process to be accessed from multiple threads.
 ///
@@ -83,7 +96,17 @@ impl UiProcess {
     }
 
     pub fn tick_controls(&self, response: &Response, ui: &egui::Ui) {
-        self.write().controls.tick(response, ui);
+        let mut inner = self.write();
+        let old_distance = inner.controls.focus_distance;
+        inner.controls.tick(response, ui);
+
+        // Moving closer doesn't change what an orthographic camera sees, so scrolling
+        // scales the view extent along with the focus distance instead.
+        if inner.camera.camera_model == CameraModel::Orthographic {
+            let scale = (inner.controls.focus_distance / old_distance) as f64;
+            inner.camera.fov_x *= scale;
+            inner.camera.fov_y *= scale;
+        }
     }
 
     pub fn model_local_to_world(&self) -> glam::Affine3A {
@@ -106,6 +129,16 @@ impl UiProcess {
         }
     }
 
+    /// Stop training for good. The process still finishes with `DoneTraining`, so the
+    /// current splats can be exported afterwards.
+    pub fn stop_training(&self) {
+        if let Some(process) = self.read().process_handle.as_ref() {
+            process.cancel.cancel();
+        }
+        // A paused process has to wake up to notice it was cancelled.
+        self.set_train_paused(false);
+    }
+
     pub fn is_train_paused(&self) -> bool {
         self.read().train_paused
     }
@@ -158,6 +191,42 @@ impl UiProcess {
         self.read().repaint();
     }
 
+    pub fn is_cam_orthographic(&self) -> bool {
+        self.read().camera.camera_model == CameraModel::Orthographic
+    }
+
+    /// Switch between a perspective and orthographic projection. The view extent
+    /// at the focal point is kept, so the focused content stays the same size.
+    pub fn set_cam_orthographic(&self, orthographic: bool) {
+        if self.is_cam_orthographic() == orthographic {
+            return;
+        }
+        let mut inner = self.write();
+        let distance = inner.controls.focus_distance as f64;
+        let camera = &mut inner.camera;
+        if orthographic {
+            camera.fov_x = 2.0 * distance * (camera.fov_x / 2.0).tan();
+            camera.fov_y = 2.0 * distance * (camera.fov_y / 2.0).tan();
+            camera.camera_model = CameraModel::Orthographic;
+        } else {
+            camera.fov_x = 2.0 * (camera.fov_x / (2.0 * distance)).atan();
+            camera.fov_y = 2.0 * (camera.fov_y / (2.0 * distance)).atan();
+            camera.camera_model = CameraModel::Pinhole;
+        }
+        drop(inner);
+        self.read().repaint();
+    }
+
+    /// Set the height of the orthographic view in world units.
+    pub fn set_cam_ortho_height(&self, height: f64) {
+        let mut inner = self.write();
+        let aspect = inner.camera.fov_x / inner.camera.fov_y;
+        inner.camera.fov_y = height;
+        inner.camera.fov_x = aspect * height;
+        drop(inner);
+        self.read().repaint();
+    }
+
     pub fn focus_view(&self, cam: &Camera) {
         // Also focus this view.
         let mut inner = self.write();
@@ -190,6 +259,16 @@ impl UiProcess {
         self.read().up_axis
     }
 
+    /// The filter applied to the splats in the viewer and on export.
+    pub fn splat_filter(&self) -> SplatFilter {
+        self.read().splat_filter
+    }
+
+    pub fn set_splat_filter(&self, filter: SplatFilter) {
+        self.write().splat_filter = filter;
+        self.read().repaint();
+    }
+
     /// Connect to an existing running process.
     pub fn connect_to_process(&self, process: RunningProcess) {
         {
@@ -249,6 +328,7 @@ impl UiProcess {
         self.write().process_handle = Some(ProcessHandle {
             messages: receiver,
             control: train_sender,
+            cancel: process.cancel,
             splat_view: process.splat_view,
         });
     }
@@ -339,6 +419,7 @@ struct UiProcessInner {
     is_training: bool,
     camera: Camera,
     splat_scale: Option<f32>,
+    splat_filter: SplatFilter,
     controls: CameraController,
     process_handle: Option<ProcessHandle>,
     ui_mode: UiMode,
@@ -372,6 +453,7 @@ impl UiProcessInner {
             camera,
             controls,
             splat_scale: None,
+            splat_filter: SplatFilter::default(),
             is_loading: false,
             is_training: false,
             train_iter: 0,
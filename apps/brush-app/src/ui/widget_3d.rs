@@ -1,4 +1,5 @@
 use brush_render::camera::Camera;
+use brush_render::kernels::camera_model::CameraModel;
 use eframe::egui_wgpu::{self, RenderState, wgpu};
 use egui::Rect;
 use glam::{Mat4, Vec3};
@@ -247,7 +248,20 @@ impl egui_wgpu::CallbackTrait for GridWidgetPainter {
 
         let aspect =
             screen_descriptor.size_in_pixels[0] as f32 / screen_descriptor.size_in_pixels[1] as f32;
-        let proj_matrix = Mat4::perspective_lh(self.camera.fov_y as f32, aspect, 0.1, 1000.0);
+        let proj_matrix = if self.camera.camera_model == CameraModel::Orthographic {
+            let half_height = self.camera.fov_y as f32 / 2.0;
+            let half_width = half_height * aspect;
+            Mat4::orthographic_lh(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                0.1,
+                1000.0,
+            )
+        } else {
+            Mat4::perspective_lh(self.camera.fov_y as f32, aspect, 0.1, 1000.0)
+        };
         let y_flip = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0));
         let view_matrix = self.camera.world_to_local();
         let world_view = Mat4::from(view_matrix) * Mat4::from(self.model_transform.inverse());
@@ -46,10 +46,15 @@ impl CameraSettings {
         max_yaw: Option<f32>,
         splat_scale: Option<f32>,
         grid_enabled: Option<bool>,
+        ssaa: Option<u32>,
+        motion_scale: Option<f32>,
     ) -> Self {
         Self(crate::ui::app::CameraSettings {
             speed_scale,
             splat_scale,
+            ssaa,
+            motion_scale,
+            sh_degree_override: None,
             clamping: crate::ui::camera_controls::CameraClamping {
                 min_focus_distance,
                 max_focus_distance,
@@ -60,6 +65,7 @@ impl CameraSettings {
             },
             background: background.map(|v| v.to_glam()),
             grid_enabled,
+            debug_view: crate::ui::splat_backbuffer::DebugView::Off,
         })
     }
 }
@@ -94,7 +100,7 @@ impl EmbeddedApp {
         &self,
         canvas: web_sys::HtmlCanvasElement,
     ) -> Result<(), wasm_bindgen::JsValue> {
-        let wgpu_options = crate::ui::create_egui_options();
+        let wgpu_options = crate::ui::create_egui_options(None);
         self.runner
             .start(
                 canvas,
@@ -1,13 +1,18 @@
 // brush-c is a native-only FFI shim. The crate compiles to an empty stub on wasm.
 #![cfg(not(target_family = "wasm"))]
 
-use brush_process::DataSource;
-use brush_process::burn_init_setup;
+use brush_process::adapter::AdapterError;
 use brush_process::config::TrainStreamConfig;
 use brush_process::message::TrainMessage;
+use brush_process::render::{RenderOptions, render_ply_file};
+use brush_process::{CancellationToken, DataSource, RunningProcess};
+use brush_process::{burn_init_setup, burn_init_setup_with_adapter};
 use brush_process::{create_process, message::ProcessMessage};
-use std::convert::TryFrom;
+use burn_wgpu::WgpuDevice;
 use std::ffi::{CStr, c_char, c_void};
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+use thiserror::Error;
 use tokio::sync::OnceCell;
 use tokio_stream::StreamExt;
 
@@ -15,30 +20,74 @@ use tokio_stream::StreamExt;
 pub enum TrainExitCode {
     Success = 0,
     Error = 1,
+    /// Training was stopped with [`brush_cancel_training`].
+    Cancelled = 2,
 }
 
 #[repr(C)]
 pub enum ProgressMessage {
     NewProcess,
-    Training { iter: u32 },
+    Training {
+        iter: u32,
+        /// Number of splats in the model at this step.
+        num_splats: u32,
+        /// Most recent training loss. Reading it back is slow, so it's only updated every
+        /// few steps, and is NaN until the first one is known.
+        loss: f32,
+    },
     DoneTraining,
 }
 
-impl TryFrom<ProcessMessage> for ProgressMessage {
-    type Error = ();
+/// Turns process messages into [`ProgressMessage`]s. The splat count and loss aren't part
+/// of every training step message, so the last known values are kept around.
+struct ProgressState {
+    num_splats: u32,
+    loss: f32,
+}
+
+impl Default for ProgressState {
+    fn default() -> Self {
+        Self {
+            num_splats: 0,
+            loss: f32::NAN,
+        }
+    }
+}
 
-    fn try_from(value: ProcessMessage) -> Result<Self, Self::Error> {
-        match value {
-            ProcessMessage::NewProcess => Ok(Self::NewProcess),
-            ProcessMessage::TrainMessage(TrainMessage::TrainStep { iter, .. }) => {
-                Ok(Self::Training { iter })
+impl ProgressState {
+    fn update(&mut self, message: ProcessMessage) -> Option<ProgressMessage> {
+        match message {
+            ProcessMessage::NewProcess => Some(ProgressMessage::NewProcess),
+            ProcessMessage::SplatsUpdated { num_splats, .. } => {
+                self.num_splats = num_splats;
+                None
+            }
+            ProcessMessage::TrainMessage(TrainMessage::RefineStep {
+                cur_splat_count, ..
+            }) => {
+                self.num_splats = cur_splat_count;
+                None
+            }
+            ProcessMessage::TrainMessage(TrainMessage::TrainStep { iter, loss, .. }) => {
+                if let Some(loss) = loss {
+                    self.loss = loss;
+                }
+                Some(ProgressMessage::Training {
+                    iter,
+                    num_splats: self.num_splats,
+                    loss: self.loss,
+                })
+            }
+            ProcessMessage::TrainMessage(TrainMessage::DoneTraining) => {
+                Some(ProgressMessage::DoneTraining)
             }
-            ProcessMessage::TrainMessage(TrainMessage::DoneTraining) => Ok(Self::DoneTraining),
-            _ => Err(()),
+            _ => None,
         }
     }
 }
 
+/// Options of a training run. Start from [`brush_default_train_options`] rather than a
+/// zeroed struct, zero isn't the default for most fields.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct TrainOptions {
@@ -47,6 +96,25 @@ pub struct TrainOptions {
     pub max_resolution: u32,
     pub export_every: u32,
     pub output_path: *const c_char,
+    /// Index of the GPU adapter to train on, as logged at startup. Negative to use the
+    /// default high performance adapter. The first call of a process picks the adapter,
+    /// later calls asking for a different one fail with [`TrainExitCode::Error`].
+    pub adapter_index: i32,
+}
+
+/// The default [`TrainOptions`], training on the default adapter and writing to the
+/// default output path.
+#[unsafe(no_mangle)]
+pub extern "C" fn brush_default_train_options() -> TrainOptions {
+    let config = TrainStreamConfig::default();
+    TrainOptions {
+        total_train_steps: config.train_config.total_train_iters,
+        refine_every: config.train_config.refine_every,
+        max_resolution: config.load_config.max_resolution,
+        export_every: config.process_config.export_every,
+        output_path: std::ptr::null(),
+        adapter_index: DEFAULT_ADAPTER,
+    }
 }
 
 impl TrainOptions {
@@ -76,7 +144,140 @@ impl TrainOptions {
 pub type ProgressCallback =
     extern "C" fn(progress_message: ProgressMessage, user_data: *mut c_void);
 
-static SETUP: OnceCell<()> = OnceCell::const_new();
+/// Invoked with the serialized splats whenever they are exported. `data` is only valid for
+/// the duration of the callback, copy it if it needs to outlive the call.
+pub type ExportCallback =
+    extern "C" fn(data: *const u8, len: usize, iter: u32, user_data: *mut c_void);
+
+/// [`TrainOptions::adapter_index`] of the default adapter. Any negative index means the same.
+const DEFAULT_ADAPTER: i32 = -1;
+
+/// The adapter index the backend was initialized with, and its device.
+static SETUP: OnceCell<(i32, WgpuDevice)> = OnceCell::const_new();
+
+#[derive(Debug, Error)]
+enum SetupError {
+    #[error(transparent)]
+    Adapter(#[from] AdapterError),
+    #[error(
+        "Already running on adapter {initialized}, a process can't switch to adapter {requested}"
+    )]
+    AdapterMismatch { initialized: i32, requested: i32 },
+}
+
+/// Initialize the backend once per process, on the adapter picked by `adapter_index`, or the
+/// default adapter if it's `None`. Later calls must ask for the same adapter or `None`.
+async fn setup(adapter_index: Option<i32>) -> Result<WgpuDevice, SetupError> {
+    let requested = adapter_index.map(|i| i.max(DEFAULT_ADAPTER));
+    let (initialized, device) = SETUP
+        .get_or_try_init(async move || {
+            let index = requested.unwrap_or(DEFAULT_ADAPTER);
+            let device = if index >= 0 {
+                burn_init_setup_with_adapter(&index.to_string()).await?
+            } else {
+                burn_init_setup().await
+            };
+            Ok::<_, SetupError>((index, device))
+        })
+        .await?;
+    match requested {
+        Some(requested) if requested != *initialized => Err(SetupError::AdapterMismatch {
+            initialized: *initialized,
+            requested,
+        }),
+        _ => Ok(device.clone()),
+    }
+}
+
+/// Read a C string argument, `None` if it's null.
+///
+/// # Safety
+///
+/// If `ptr` is not null, it must point to a valid, null-terminated C string.
+unsafe fn read_path(ptr: *const c_char) -> Option<PathBuf> {
+    if ptr.is_null() {
+        return None;
+    }
+    // SAFETY: Not null, caller guarantees the string is a valid C-string.
+    let path = unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned();
+    Some(PathBuf::from(path))
+}
+
+/// Renders a PLY file to a PNG image, without any training.
+///
+/// The camera is read from a JSON file in the format of a camera path, of which the first
+/// keyframe is used. The simplest form is a single keyframe in Brush's camera convention
+/// (+Z forward, +Y down):
+///
+/// ```json
+/// {"keyframes": [{"time": 0.0, "position": [x, y, z], "rotation": [x, y, z, w], "fov": 50.0}]}
+/// ```
+///
+/// `position` is the camera position, `rotation` the camera-to-world rotation quaternion,
+/// and `fov` the vertical field of view in degrees. The horizontal field of view follows from
+/// `width / height`. A `camera_path.json` exported from nerfstudio's viewer works as well.
+///
+/// Returns [`TrainExitCode::Error`] if any path is null, a file can't be read or written,
+/// or the size is zero.
+///
+/// # Safety
+///
+/// `ply_path`, `camera_json_path` and `out_png_path` must be null or point to valid,
+/// null-terminated C strings, valid for reading for the duration of this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn render_ply_to_png(
+    ply_path: *const c_char,
+    camera_json_path: *const c_char,
+    width: u32,
+    height: u32,
+    out_png_path: *const c_char,
+) -> TrainExitCode {
+    // SAFETY: Caller upholds the invariants documented above.
+    let paths = unsafe {
+        (
+            read_path(ply_path),
+            read_path(camera_json_path),
+            read_path(out_png_path),
+        )
+    };
+    let (Some(ply_path), Some(camera_path), Some(out_path)) = paths else {
+        return TrainExitCode::Error;
+    };
+    if width == 0 || height == 0 {
+        return TrainExitCode::Error;
+    }
+
+    // See `TrainJob::run`, panics must not unwind into the caller.
+    let result = std::panic::catch_unwind(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create tokio runtime")
+            .block_on(async {
+                let device = setup(None).await?;
+                render_ply_file(
+                    &ply_path,
+                    &camera_path,
+                    glam::uvec2(width, height),
+                    RenderOptions::default(),
+                    &out_path,
+                    &device.into(),
+                )
+                .await
+            })
+    });
+
+    match result {
+        Ok(Ok(())) => TrainExitCode::Success,
+        Ok(Err(e)) => {
+            log::error!("{e:#}");
+            TrainExitCode::Error
+        }
+        Err(_) => TrainExitCode::Error,
+    }
+}
 
 /// Trains a model from a dataset and saves the result.
 ///
@@ -112,40 +313,214 @@ pub unsafe extern "C" fn train_and_save(
     progress_callback: ProgressCallback,
     user_data: *mut c_void,
 ) -> TrainExitCode {
-    if dataset_path.is_null() || options.is_null() {
-        return TrainExitCode::Error;
+    // SAFETY: Caller upholds the same invariants as documented above.
+    unsafe { run_training(dataset_path, options, progress_callback, None, user_data) }
+}
+
+/// Trains a model from a dataset like [`train_and_save`], and additionally hands the
+/// serialized splats to `export_callback` at every export point.
+///
+/// Exports happen every `export_every` steps and when training completes. The splats are
+/// still written to `output_path` as well. The buffer passed to `export_callback` holds a PLY
+/// file and is only valid for the duration of the callback.
+///
+/// # Safety
+///
+/// The same invariants as [`train_and_save`] apply. Additionally, `user_data` is also passed
+/// to `export_callback`, and must remain valid for the entire duration of this function call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn train_with_export_callback(
+    dataset_path: *const c_char,
+    options: *const TrainOptions,
+    progress_callback: ProgressCallback,
+    export_callback: ExportCallback,
+    user_data: *mut c_void,
+) -> TrainExitCode {
+    // SAFETY: Caller upholds the invariants documented above.
+    unsafe {
+        run_training(
+            dataset_path,
+            options,
+            progress_callback,
+            Some(export_callback),
+            user_data,
+        )
     }
+}
+
+/// Trains a model from a dataset like [`train_and_save`], but on a background thread.
+///
+/// Returns immediately with a handle to the training run, or null if `dataset_path` or
+/// `options` is null. `progress_callback` is invoked from the background thread. Use
+/// [`brush_poll_training`] to check whether training is done, [`brush_cancel_training`] to
+/// stop it, and [`brush_join_training`] to get the result. Every handle must be passed to
+/// [`brush_join_training`] exactly once to free it.
+///
+/// # Safety
+///
+/// - `dataset_path` and `options` (including its `output_path`) follow the same rules as for
+///   [`train_and_save`], but only need to be valid for the duration of this call.
+///
+/// - `user_data` is handed to `progress_callback` on the background thread, so whatever it
+///   points to must be safe to access from another thread (or only be accessed from the
+///   callback), and must remain valid until [`brush_join_training`] returns.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn train_and_save_async(
+    dataset_path: *const c_char,
+    options: *const TrainOptions,
+    progress_callback: ProgressCallback,
+    user_data: *mut c_void,
+) -> *mut TrainingHandle {
+    // SAFETY: Caller upholds the invariants documented above.
+    let Some(job) = (unsafe { TrainJob::read(dataset_path, options) }) else {
+        return std::ptr::null_mut();
+    };
+
+    let cancel = job.process.cancel.clone();
+    let user_data = UserData(user_data);
+    let thread = std::thread::spawn(move || {
+        // Move the whole wrapper in, not just its (non-Send) pointer field.
+        let user_data = user_data;
+        job.run(progress_callback, None, user_data.0)
+    });
+    Box::into_raw(Box::new(TrainingHandle { cancel, thread }))
+}
+
+/// A training run started by [`train_and_save_async`].
+pub struct TrainingHandle {
+    cancel: CancellationToken,
+    thread: JoinHandle<TrainExitCode>,
+}
+
+/// The `user_data` pointer, moved to the training thread. The caller of
+/// [`train_and_save_async`] promises it can be used from there.
+struct UserData(*mut c_void);
+
+// SAFETY: See the safety docs of `train_and_save_async`.
+unsafe impl Send for UserData {}
+
+/// Whether the training run has finished, after which [`brush_join_training`] won't block.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`train_and_save_async`] that hasn't been joined yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn brush_poll_training(handle: *const TrainingHandle) -> bool {
+    // SAFETY: Caller guarantees the handle is alive.
+    unsafe { &*handle }.thread.is_finished()
+}
+
+/// Stop a training run. While loading, loading is abandoned right away. Once training started
+/// it stops before its next step, and the progress callback still gets
+/// [`ProgressMessage::DoneTraining`]. [`brush_join_training`] then returns
+/// [`TrainExitCode::Cancelled`]. Does nothing if training already finished.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`train_and_save_async`] that hasn't been joined yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn brush_cancel_training(handle: *const TrainingHandle) {
+    // SAFETY: Caller guarantees the handle is alive.
+    unsafe { &*handle }.cancel.cancel();
+}
+
+/// Wait for a training run to finish and free its handle. After this no more callbacks are
+/// invoked, and the handle must not be used again.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`train_and_save_async`] that hasn't been joined yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn brush_join_training(handle: *mut TrainingHandle) -> TrainExitCode {
+    // SAFETY: Caller guarantees the handle is alive, and gives up ownership of it.
+    let handle = unsafe { Box::from_raw(handle) };
+    handle.thread.join().unwrap_or(TrainExitCode::Error)
+}
+
+/// # Safety
+///
+/// See [`train_and_save`].
+unsafe fn run_training(
+    dataset_path: *const c_char,
+    options: *const TrainOptions,
+    progress_callback: ProgressCallback,
+    export_callback: Option<ExportCallback>,
+    user_data: *mut c_void,
+) -> TrainExitCode {
+    // SAFETY: Caller upholds the invariants of `train_and_save`.
+    match unsafe { TrainJob::read(dataset_path, options) } {
+        Some(job) => job.run(progress_callback, export_callback, user_data),
+        None => TrainExitCode::Error,
+    }
+}
+
+/// A training call, with its arguments copied out of the caller's memory. The process
+/// doesn't do anything until it's run.
+struct TrainJob {
+    process: RunningProcess,
+    adapter_index: i32,
+}
+
+impl TrainJob {
+    /// Returns `None` if either pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// See [`train_and_save`].
+    unsafe fn read(dataset_path: *const c_char, options: *const TrainOptions) -> Option<Self> {
+        if dataset_path.is_null() || options.is_null() {
+            return None;
+        }
 
-    // A Rust panic must not unwind across this `extern "C"` boundary (that
-    // aborts the whole process). Catch it and surface it as an error code.
-    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let dataset_path_str =
             // SAFETY: Checked if dataset_path is not null, caller guarantees the string is a valid C-string.
             unsafe { CStr::from_ptr(dataset_path).to_string_lossy().into_owned() };
 
-        let source = DataSource::Path(dataset_path_str);
-
-        // SAFETY: Option is checked to not be null before the future.
+        // SAFETY: Option is checked to not be null.
         let train_options = unsafe { *options };
         // SAFETY: Caller guarantees the output_path is a valid C-string if not null.
-        let process_args = unsafe { train_options.into_train_stream_config() };
-        let mut process = create_process(source, async move |_| Some(process_args));
+        let config = unsafe { train_options.into_train_stream_config() };
+        let source = DataSource::Path(dataset_path_str);
+        Some(Self {
+            process: create_process(source, async move |_| Some(config)),
+            adapter_index: train_options.adapter_index,
+        })
+    }
 
-        tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .expect("Failed to create tokio runtime")
-            .block_on(async {
-                SETUP
-                    .get_or_init(async move || {
-                        burn_init_setup().await;
-                    })
-                    .await;
+    /// Train to completion on the current thread, or until the process is cancelled.
+    fn run(
+        self,
+        progress_callback: ProgressCallback,
+        export_callback: Option<ExportCallback>,
+        user_data: *mut c_void,
+    ) -> TrainExitCode {
+        // A Rust panic must not unwind across this `extern "C"` boundary (that
+        // aborts the whole process). Catch it and surface it as an error code.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let Self {
+                mut process,
+                adapter_index,
+            } = self;
+
+            let train = async {
+                if let Err(e) = setup(Some(adapter_index)).await {
+                    log::error!("{e}");
+                    return TrainExitCode::Error;
+                }
 
+                let mut progress = ProgressState::default();
                 while let Some(message_result) = process.stream.next().await {
                     match message_result {
+                        Ok(ProcessMessage::TrainMessage(TrainMessage::SplatsExported {
+                            iter,
+                            data,
+                        })) => {
+                            if let Some(export_callback) = export_callback {
+                                export_callback(data.as_ptr(), data.len(), iter, user_data);
+                            }
+                        }
                         Ok(message) => {
-                            if let Ok(progress_message) = message.try_into() {
+                            if let Some(progress_message) = progress.update(message) {
                                 progress_callback(progress_message, user_data);
                             }
                         }
@@ -155,9 +530,20 @@ pub unsafe extern "C" fn train_and_save(
                     }
                 }
 
-                TrainExitCode::Success
-            })
-    }));
+                if process.cancel.is_cancelled() {
+                    TrainExitCode::Cancelled
+                } else {
+                    TrainExitCode::Success
+                }
+            };
 
-    result.unwrap_or(TrainExitCode::Error)
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to create tokio runtime")
+                .block_on(train)
+        }));
+
+        result.unwrap_or(TrainExitCode::Error)
+    }
 }
@@ -3,14 +3,22 @@
 use std::ffi::{CString, c_void};
 use std::fs;
 use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-use brush_c::{ProgressMessage, TrainExitCode, TrainOptions, train_and_save};
+use brush_c::{
+    ProgressMessage, TrainExitCode, TrainOptions, TrainingHandle, brush_cancel_training,
+    brush_default_train_options, brush_join_training, brush_poll_training, render_ply_to_png,
+    train_and_save, train_and_save_async, train_with_export_callback,
+};
 
 #[repr(C)]
 struct CallbackState {
     call_count: AtomicUsize,
     finished_called: std::sync::atomic::AtomicBool,
+    /// Highest splat count and loss (as f32 bits) reported by a training step.
+    max_splats: AtomicU32,
+    last_loss: AtomicU32,
 }
 
 extern "C" fn test_progress_callback(process_message: ProgressMessage, user_data: *mut c_void) {
@@ -25,8 +33,16 @@ extern "C" fn test_progress_callback(process_message: ProgressMessage, user_data
         ProgressMessage::NewProcess => {
             println!("FFI Test: Training starting...");
         }
-        ProgressMessage::Training { iter } => {
-            println!("FFI Test: Training iteration: {iter:.2}%");
+        ProgressMessage::Training {
+            iter,
+            num_splats,
+            loss,
+        } => {
+            println!("FFI Test: Training iteration: {iter}, splats: {num_splats}, loss: {loss}");
+            state.max_splats.fetch_max(num_splats, Ordering::SeqCst);
+            if loss.is_finite() {
+                state.last_loss.store(loss.to_bits(), Ordering::SeqCst);
+            }
         }
         ProgressMessage::DoneTraining => {
             println!("FFI Test: Training finished!");
@@ -57,6 +73,8 @@ fn test_train_and_save_ffi_short() {
     let mut callback_state = CallbackState {
         call_count: AtomicUsize::new(0),
         finished_called: std::sync::atomic::AtomicBool::new(false),
+        max_splats: AtomicU32::new(0),
+        last_loss: AtomicU32::new(0),
     };
 
     let options = TrainOptions {
@@ -65,6 +83,7 @@ fn test_train_and_save_ffi_short() {
         export_every: 10,
         max_resolution: 50,
         output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
     };
 
     // SAFETY: paths are valid, user_data is valid for lifetime of callback_state
@@ -79,6 +98,15 @@ fn test_train_and_save_ffi_short() {
 
     assert!(matches!(status, TrainExitCode::Success));
     assert!(callback_state.call_count.load(Ordering::SeqCst) > 2);
+    assert!(
+        callback_state.max_splats.load(Ordering::SeqCst) > 0,
+        "training steps should report the splat count"
+    );
+    let loss = f32::from_bits(callback_state.last_loss.load(Ordering::SeqCst));
+    assert!(
+        loss > 0.0,
+        "training steps should report the loss, got {loss}"
+    );
 
     let output_files: Vec<_> = fs::read_dir(output_path)
         .unwrap()
@@ -87,6 +115,77 @@ fn test_train_and_save_ffi_short() {
     assert!(!output_files.is_empty(), "No output file was created");
 }
 
+#[repr(C)]
+struct ExportState {
+    export_count: AtomicUsize,
+    last_iter: AtomicUsize,
+    valid_header: std::sync::atomic::AtomicBool,
+}
+
+extern "C" fn test_progress_noop(_: ProgressMessage, _: *mut c_void) {}
+
+extern "C" fn test_export_callback(data: *const u8, len: usize, iter: u32, user_data: *mut c_void) {
+    // SAFETY: user_data is a pointer to an ExportState struct
+    let state = unsafe { (user_data as *const ExportState).as_ref().unwrap() };
+    // SAFETY: The buffer is valid for the duration of the callback.
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    state.export_count.fetch_add(1, Ordering::SeqCst);
+    state.last_iter.store(iter as usize, Ordering::SeqCst);
+    if !data.starts_with(b"ply") {
+        state
+            .valid_header
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_train_with_export_callback_ffi() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dataset_path = Path::new(manifest_dir)
+        .join("tests")
+        .join("data")
+        .join("test_dataset");
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_test_export_")
+        .tempdir()
+        .unwrap();
+    let output_path_cstr = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let dataset_path_cstr = CString::new(dataset_path.to_str().unwrap()).unwrap();
+
+    let mut export_state = ExportState {
+        export_count: AtomicUsize::new(0),
+        last_iter: AtomicUsize::new(0),
+        valid_header: std::sync::atomic::AtomicBool::new(true),
+    };
+
+    let options = TrainOptions {
+        total_train_steps: 10,
+        refine_every: 5,
+        export_every: 5,
+        max_resolution: 50,
+        output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
+    };
+
+    // SAFETY: paths are valid, user_data is valid for lifetime of export_state
+    let status = unsafe {
+        train_with_export_callback(
+            dataset_path_cstr.as_ptr(),
+            &options,
+            test_progress_noop,
+            test_export_callback,
+            std::ptr::from_mut(&mut export_state).cast::<c_void>(),
+        )
+    };
+
+    assert!(matches!(status, TrainExitCode::Success));
+    // Exports at step 5 and at the final step.
+    assert_eq!(export_state.export_count.load(Ordering::SeqCst), 2);
+    assert_eq!(export_state.last_iter.load(Ordering::SeqCst), 10);
+    assert!(export_state.valid_header.load(Ordering::SeqCst));
+}
+
 #[test]
 fn test_train_and_save_ffi_invalid_path() {
     let invalid_dataset_path = "/path/that/does/not/exist/and/should/fail";
@@ -101,6 +200,8 @@ fn test_train_and_save_ffi_invalid_path() {
     let mut callback_state = CallbackState {
         call_count: AtomicUsize::new(0),
         finished_called: std::sync::atomic::AtomicBool::new(false),
+        max_splats: AtomicU32::new(0),
+        last_loss: AtomicU32::new(0),
     };
 
     let options = TrainOptions {
@@ -109,6 +210,7 @@ fn test_train_and_save_ffi_invalid_path() {
         export_every: 10,
         max_resolution: 50,
         output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
     };
 
     // SAFETY: The paths are valid, and the callback state is alive for the duration of the call.
@@ -137,6 +239,8 @@ fn test_train_and_save_ffi_null_options() {
     let mut callback_state = CallbackState {
         call_count: AtomicUsize::new(0),
         finished_called: std::sync::atomic::AtomicBool::new(false),
+        max_splats: AtomicU32::new(0),
+        last_loss: AtomicU32::new(0),
     };
 
     // SAFETY: The paths are valid, and the callback state is alive for the duration of the call.
@@ -167,6 +271,7 @@ fn test_train_and_save_ffi_null_dataset() {
         export_every: 10,
         max_resolution: 50,
         output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
     };
 
     // SAFETY: The paths are valid, and the callback state is null.
@@ -181,3 +286,368 @@ fn test_train_and_save_ffi_null_dataset() {
 
     assert!(matches!(status_null_dataset, TrainExitCode::Error));
 }
+
+#[test]
+fn test_adapter_mismatch_ffi() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dataset_path = Path::new(manifest_dir)
+        .join("tests")
+        .join("data")
+        .join("test_dataset");
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_test_adapter_")
+        .tempdir()
+        .unwrap();
+    let output_path_cstr = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let dataset_path_cstr = CString::new(dataset_path.to_str().unwrap()).unwrap();
+
+    let defaults = brush_default_train_options();
+    assert!(
+        defaults.adapter_index < 0,
+        "Defaults to the default adapter"
+    );
+    assert!(defaults.output_path.is_null());
+    assert!(defaults.total_train_steps > 0 && defaults.refine_every > 0);
+
+    let options = TrainOptions {
+        total_train_steps: 2,
+        max_resolution: 50,
+        output_path: output_path_cstr.as_ptr(),
+        ..defaults
+    };
+    let train = |options: &TrainOptions| {
+        // SAFETY: The paths are valid, and the callback state is null.
+        unsafe {
+            train_and_save(
+                dataset_path_cstr.as_ptr(),
+                options,
+                test_progress_callback,
+                std::ptr::null_mut(),
+            )
+        }
+    };
+
+    // Every test in this process sets up the default adapter, so asking for a specific one
+    // afterwards has to fail rather than silently train on the default one.
+    assert!(matches!(train(&options), TrainExitCode::Success));
+    let pinned = TrainOptions {
+        adapter_index: 0,
+        ..options
+    };
+    assert!(matches!(train(&pinned), TrainExitCode::Error));
+}
+
+/// Poll `handle` until `done` holds, failing after a minute.
+fn wait_for(handle: *mut TrainingHandle, done: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        // SAFETY: The handle is alive until joined.
+        if unsafe { brush_poll_training(handle) } {
+            break;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "Timed out waiting for training"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_train_and_save_async_ffi() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dataset_path = Path::new(manifest_dir)
+        .join("tests")
+        .join("data")
+        .join("test_dataset");
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_test_async_")
+        .tempdir()
+        .unwrap();
+    let output_path_cstr = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let dataset_path_cstr = CString::new(dataset_path.to_str().unwrap()).unwrap();
+
+    let callback_state = CallbackState {
+        call_count: AtomicUsize::new(0),
+        finished_called: std::sync::atomic::AtomicBool::new(false),
+        max_splats: AtomicU32::new(0),
+        last_loss: AtomicU32::new(0),
+    };
+
+    let options = TrainOptions {
+        total_train_steps: 10,
+        refine_every: 5,
+        export_every: 10,
+        max_resolution: 50,
+        output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
+    };
+
+    // SAFETY: paths are valid for the call, the callback state only uses atomics and outlives
+    // the join below.
+    let handle = unsafe {
+        train_and_save_async(
+            dataset_path_cstr.as_ptr(),
+            &options,
+            test_progress_callback,
+            std::ptr::from_ref(&callback_state)
+                .cast_mut()
+                .cast::<c_void>(),
+        )
+    };
+    assert!(!handle.is_null());
+
+    // The strings only need to live for the call itself.
+    drop(dataset_path_cstr);
+    drop(output_path_cstr);
+
+    wait_for(handle, || false);
+    // SAFETY: The handle hasn't been joined yet.
+    let status = unsafe { brush_join_training(handle) };
+
+    assert!(matches!(status, TrainExitCode::Success));
+    assert!(callback_state.finished_called.load(Ordering::SeqCst));
+}
+
+/// Cancels its training run from the progress callback once it's a few steps in.
+struct CancelState {
+    handle: AtomicPtr<TrainingHandle>,
+    cancel_iter: AtomicU32,
+    last_iter: AtomicU32,
+    finished_called: AtomicBool,
+}
+
+extern "C" fn cancel_progress_callback(process_message: ProgressMessage, user_data: *mut c_void) {
+    // SAFETY: user_data is a pointer to a CancelState struct.
+    let state = unsafe { &*user_data.cast::<CancelState>() };
+
+    match process_message {
+        ProgressMessage::Training { iter, .. } => {
+            state.last_iter.store(iter, Ordering::SeqCst);
+            let handle = state.handle.load(Ordering::SeqCst);
+            if iter >= 10 && !handle.is_null() && state.cancel_iter.load(Ordering::SeqCst) == 0 {
+                state.cancel_iter.store(iter, Ordering::SeqCst);
+                // SAFETY: The handle is only joined after training stops calling back.
+                unsafe { brush_cancel_training(handle) };
+            }
+        }
+        ProgressMessage::DoneTraining => {
+            state.finished_called.store(true, Ordering::SeqCst);
+        }
+        ProgressMessage::NewProcess => {}
+    }
+}
+
+#[test]
+fn test_cancel_training_ffi() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dataset_path = Path::new(manifest_dir)
+        .join("tests")
+        .join("data")
+        .join("test_dataset");
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_test_cancel_")
+        .tempdir()
+        .unwrap();
+    let output_path_cstr = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let dataset_path_cstr = CString::new(dataset_path.to_str().unwrap()).unwrap();
+
+    let cancel_state = CancelState {
+        handle: AtomicPtr::new(std::ptr::null_mut()),
+        cancel_iter: AtomicU32::new(0),
+        last_iter: AtomicU32::new(0),
+        finished_called: AtomicBool::new(false),
+    };
+
+    // Far more steps than the test could run.
+    let options = TrainOptions {
+        total_train_steps: 1_000_000,
+        refine_every: 100,
+        export_every: 1_000_000,
+        max_resolution: 50,
+        output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
+    };
+
+    // SAFETY: paths are valid for the call, the cancel state only uses atomics and outlives
+    // the join below.
+    let handle = unsafe {
+        train_and_save_async(
+            dataset_path_cstr.as_ptr(),
+            &options,
+            cancel_progress_callback,
+            std::ptr::from_ref(&cancel_state)
+                .cast_mut()
+                .cast::<c_void>(),
+        )
+    };
+    assert!(!handle.is_null());
+    cancel_state.handle.store(handle, Ordering::SeqCst);
+
+    // SAFETY: The handle hasn't been joined yet.
+    let status = unsafe { brush_join_training(handle) };
+
+    assert!(matches!(status, TrainExitCode::Cancelled));
+    // The step the cancel happened in finishes, but no step after it runs.
+    let cancel_iter = cancel_state.cancel_iter.load(Ordering::SeqCst);
+    assert!(cancel_iter >= 10, "Training was never cancelled");
+    assert_eq!(
+        cancel_state.last_iter.load(Ordering::SeqCst),
+        cancel_iter,
+        "Training kept going after being cancelled"
+    );
+    assert!(
+        cancel_state.finished_called.load(Ordering::SeqCst),
+        "A cancelled run should still report DoneTraining"
+    );
+}
+
+#[test]
+fn test_cancel_before_training_ffi() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dataset_path = Path::new(manifest_dir)
+        .join("tests")
+        .join("data")
+        .join("test_dataset");
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_test_cancel_early_")
+        .tempdir()
+        .unwrap();
+    let output_path_cstr = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let dataset_path_cstr = CString::new(dataset_path.to_str().unwrap()).unwrap();
+
+    let cancel_state = CancelState {
+        handle: AtomicPtr::new(std::ptr::null_mut()),
+        cancel_iter: AtomicU32::new(0),
+        last_iter: AtomicU32::new(0),
+        finished_called: AtomicBool::new(false),
+    };
+
+    let options = TrainOptions {
+        total_train_steps: 1_000_000,
+        refine_every: 100,
+        export_every: 1_000_000,
+        max_resolution: 50,
+        output_path: output_path_cstr.as_ptr(),
+        adapter_index: -1,
+    };
+
+    // SAFETY: paths are valid for the call, the cancel state only uses atomics and outlives
+    // the join below.
+    let handle = unsafe {
+        train_and_save_async(
+            dataset_path_cstr.as_ptr(),
+            &options,
+            cancel_progress_callback,
+            std::ptr::from_ref(&cancel_state)
+                .cast_mut()
+                .cast::<c_void>(),
+        )
+    };
+    assert!(!handle.is_null());
+
+    // Cancel while the dataset is still loading, before the callback could.
+    let start = Instant::now();
+    // SAFETY: The handle hasn't been joined yet.
+    unsafe { brush_cancel_training(handle) };
+    // SAFETY: The handle hasn't been joined yet.
+    let status = unsafe { brush_join_training(handle) };
+
+    assert!(matches!(status, TrainExitCode::Cancelled));
+    assert_eq!(
+        cancel_state.last_iter.load(Ordering::SeqCst),
+        0,
+        "No training step should run after cancelling"
+    );
+    assert!(
+        start.elapsed() < Duration::from_secs(30),
+        "Cancelling should stop loading right away"
+    );
+}
+
+#[test]
+fn test_train_and_save_async_ffi_null_args() {
+    // SAFETY: Null arguments are rejected before anything is read.
+    let handle = unsafe {
+        train_and_save_async(
+            std::ptr::null(),
+            std::ptr::null(),
+            test_progress_callback,
+            std::ptr::null_mut(),
+        )
+    };
+    assert!(handle.is_null());
+}
+
+#[test]
+fn test_render_ply_to_png_ffi() {
+    let data_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data");
+    let temp_dir = tempfile::Builder::new()
+        .prefix("ffi_render_test_")
+        .tempdir()
+        .unwrap();
+
+    // Look at the splat at the origin from 4 units in front of it.
+    let camera_path = temp_dir.path().join("camera.json");
+    fs::write(
+        &camera_path,
+        r#"{"keyframes": [{"time": 0.0, "position": [0.0, 0.0, -4.0], "rotation": [0.0, 0.0, 0.0, 1.0], "fov": 45.0}]}"#,
+    )
+    .unwrap();
+    let out_path = temp_dir.path().join("render.png");
+
+    let ply_cstr = CString::new(data_dir.join("red_splat.ply").to_str().unwrap()).unwrap();
+    let camera_cstr = CString::new(camera_path.to_str().unwrap()).unwrap();
+    let out_cstr = CString::new(out_path.to_str().unwrap()).unwrap();
+
+    // SAFETY: All paths are valid C strings.
+    let status = unsafe {
+        render_ply_to_png(
+            ply_cstr.as_ptr(),
+            camera_cstr.as_ptr(),
+            48,
+            32,
+            out_cstr.as_ptr(),
+        )
+    };
+    assert!(matches!(status, TrainExitCode::Success));
+
+    let img = image::open(&out_path).unwrap().into_rgba8();
+    assert_eq!(img.dimensions(), (48, 32));
+    let [r, g, b, a] = img.get_pixel(24, 16).0;
+    assert!(
+        r > 200 && g < 20 && b < 20 && a > 200,
+        "center {:?}",
+        [r, g, b, a]
+    );
+
+    // A missing camera file or null path is an error rather than a crash.
+    let missing_cstr =
+        CString::new(temp_dir.path().join("missing.json").to_str().unwrap()).unwrap();
+    // SAFETY: All paths are valid C strings or null.
+    let (missing, null) = unsafe {
+        (
+            render_ply_to_png(
+                ply_cstr.as_ptr(),
+                missing_cstr.as_ptr(),
+                48,
+                32,
+                out_cstr.as_ptr(),
+            ),
+            render_ply_to_png(
+                std::ptr::null(),
+                camera_cstr.as_ptr(),
+                48,
+                32,
+                out_cstr.as_ptr(),
+            ),
+        )
+    };
+    assert!(matches!(missing, TrainExitCode::Error));
+    assert!(matches!(null, TrainExitCode::Error));
+}
@@ -2,16 +2,22 @@
 #![cfg(not(target_family = "wasm"))]
 
 use brush_async::Actor;
+use brush_dataset::config::LoadDatasetConfig;
 use brush_process::DataSource;
 use brush_process::RunningProcess;
 use brush_process::config::TrainStreamConfig;
 use brush_process::create_process;
 use brush_process::message::ProcessMessage;
 use brush_process::message::TrainMessage;
+use brush_process::render::RenderOptions;
+use brush_serde::ExportFormat;
+use burn_wgpu::WgpuDevice;
 
-use clap::{Error, Parser, builder::ArgPredicate, error::ErrorKind};
+use clap::{Args, Error, Parser, Subcommand, ValueEnum, builder::ArgPredicate, error::ErrorKind};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use indicatif_log_bridge::LogWrapper;
+use serde::Serialize;
+use std::path::PathBuf;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
@@ -22,9 +28,42 @@ use tracing::trace_span;
     author,
     version,
     arg_required_else_help = false,
+    args_conflicts_with_subcommands = true,
     about = "Brush - universal splats"
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Without a subcommand, the arguments of `train`.
+    #[clap(flatten)]
+    pub train: TrainArgs,
+}
+
+impl Cli {
+    /// The subcommand to run, which is `train` when none was given.
+    pub fn into_command(self) -> Result<Command, Error> {
+        match self.command.unwrap_or(Command::Train(self.train)) {
+            Command::Train(args) => Ok(Command::Train(args.validate()?)),
+            command => Ok(command),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Train on a dataset, or view a splat file (the default without a subcommand).
+    Train(TrainArgs),
+    /// Render a splat file along a camera path to an image sequence.
+    Render(RenderArgs),
+    /// Score a splat file against the views of a dataset, and output the metrics as JSON.
+    Eval(EvalArgs),
+    /// Convert a splat file to another format.
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+pub struct TrainArgs {
     /// Source to load from (path or URL).
     #[arg(value_name = "PATH_OR_URL")]
     pub source: Option<DataSource>,
@@ -37,25 +76,211 @@ pub struct Cli {
     )]
     pub with_viewer: bool,
 
+    /// Never create a window or UI surface, just train on the command line.
+    /// Implied when a source is given and no display is available, unless
+    /// --with-viewer is passed, which is then an error.
+    #[arg(long, conflicts_with = "with_viewer")]
+    pub headless: bool,
+
+    /// Check the dataset at the source for problems (missing or unreadable
+    /// images, mismatched intrinsics, ...) and exit without training.
+    #[arg(long, requires = "source")]
+    pub validate_dataset: bool,
+
+    /// GPU adapter to use, by index or by (part of) its name. The available
+    /// adapters are logged at startup. Defaults to the high performance GPU.
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub gpu: Option<String>,
+
+    /// How to report progress when training without a viewer. With json, one
+    /// JSON object per line is printed to stdout and logs go to stderr.
+    #[arg(long, value_enum, default_value = "human")]
+    pub progress_format: ProgressFormat,
+
     #[clap(flatten)]
     pub train_stream: TrainStreamConfig,
 }
 
-impl Cli {
+/// See [`TrainArgs::progress_format`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Progress bars and logs.
+    #[default]
+    Human,
+    /// Newline delimited JSON, see [`ProgressLine`].
+    Json,
+}
+
+/// One line of `--progress-format json` output, tagged by its `type`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProgressLine {
+    NewProcess,
+    StartLoading {
+        name: String,
+    },
+    DoneLoading,
+    Dataset {
+        train_views: usize,
+        eval_views: usize,
+    },
+    TrainStep {
+        iter: u32,
+        num_splats: u32,
+        /// Only read back every few steps.
+        loss: Option<f32>,
+        elapsed_secs: f64,
+    },
+    RefineStep {
+        iter: u32,
+        num_splats: u32,
+    },
+    EvalResult {
+        iter: u32,
+        psnr: f32,
+        ssim: f32,
+        lpips: Option<f32>,
+    },
+    SplatsExported {
+        iter: u32,
+    },
+    Warning {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+    DoneTraining,
+}
+
+impl TrainArgs {
     pub fn validate(self) -> Result<Self, Error> {
+        self.validate_for_display(display_available())
+    }
+
+    fn validate_for_display(mut self, has_display: bool) -> Result<Self, Error> {
+        if !self.headless && self.source.is_some() && !has_display {
+            // With a source the viewer is off by default, so it was asked for.
+            if self.with_viewer {
+                return Err(Error::raw(
+                    ErrorKind::ArgumentConflict,
+                    "--with-viewer needs a display, but neither DISPLAY nor WAYLAND_DISPLAY is set",
+                ));
+            }
+            log::info!("No display found, running headless");
+            self.headless = true;
+        }
+        if self.headless || self.validate_dataset {
+            self.with_viewer = false;
+        }
         if !self.with_viewer && self.source.is_none() {
             return Err(Error::raw(
                 ErrorKind::MissingRequiredArgument,
-                "When --with-viewer is false, --source must be provided",
+                "When --with-viewer is false or --headless is set, --source must be provided",
             ));
         }
         Ok(self)
     }
 }
 
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Splat file to render, in any format Brush can load.
+    pub splats: PathBuf,
+
+    /// Camera path JSON file to render along.
+    #[arg(required_unless_present = "orbit")]
+    pub camera_path: Option<PathBuf>,
+
+    /// Render an orbit around the splats instead of a camera path.
+    #[arg(long, conflicts_with = "camera_path")]
+    pub orbit: bool,
+
+    /// Number of frames of the orbit.
+    #[arg(long, default_value = "120", requires = "orbit")]
+    pub frames: usize,
+
+    /// Directory to write the frames to, as numbered PNG files.
+    #[arg(long, default_value = "render")]
+    pub out_dir: PathBuf,
+
+    /// Width of the frames in pixels.
+    #[arg(long, default_value = "1920")]
+    pub width: u32,
+
+    /// Height of the frames in pixels.
+    #[arg(long, default_value = "1080")]
+    pub height: u32,
+
+    /// Frames per second of camera path time.
+    #[arg(long, default_value = "30")]
+    pub fps: f32,
+
+    /// Multiplier on the size of all splats.
+    #[arg(long)]
+    pub splat_scale: Option<f32>,
+
+    /// GPU adapter to use, like for training.
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub gpu: Option<String>,
+}
+
+#[derive(Args)]
+pub struct EvalArgs {
+    /// Splat file to evaluate, in any format Brush can load.
+    pub splats: PathBuf,
+
+    /// Dataset to compare against (path or URL). Uses its eval views when
+    /// --eval-split-every is set, and all views otherwise.
+    #[arg(value_name = "PATH_OR_URL")]
+    pub source: DataSource,
+
+    /// File to write the metrics JSON to, instead of printing it.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// GPU adapter to use, like for training.
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub gpu: Option<String>,
+
+    #[clap(flatten)]
+    pub load_config: LoadDatasetConfig,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Splat file to convert, in any format Brush can load.
+    pub input: PathBuf,
+
+    /// File to write.
+    pub output: PathBuf,
+
+    /// Format to write. Picked from the extension of the output file by default.
+    #[arg(long, value_parser = brush_process::config::export_format_parser())]
+    pub format: Option<ExportFormat>,
+
+    /// GPU adapter to use, like for training.
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub gpu: Option<String>,
+}
+
+/// Whether there's a display to open a window on. Only known on Linux & the
+/// BSDs, elsewhere there's assumed to always be one.
+fn display_available() -> bool {
+    if cfg!(all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "android")
+    )) {
+        std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some()
+    } else {
+        true
+    }
+}
+
 /// Build the training process described by `args`, or `None` if no source was
 /// given. Shared by the standalone CLI binary and brush-app's headless path.
-pub fn build_process(args: &Cli) -> Option<RunningProcess> {
+pub fn build_process(args: &TrainArgs) -> Option<RunningProcess> {
     let source = args.source.clone()?;
     let cli_config = args.train_stream.clone();
     Some(create_process(source, async move |init| {
@@ -63,24 +288,146 @@ pub fn build_process(args: &Cli) -> Option<RunningProcess> {
     }))
 }
 
-/// Initialize the backend, then drive `process` to completion on the CLI UI.
+/// Load the dataset at `source` without training and print a report of its
+/// issues. Fails if any of them would break training, so the process exits
+/// nonzero.
+pub async fn validate_dataset(
+    source: DataSource,
+    load_config: &LoadDatasetConfig,
+) -> Result<(), anyhow::Error> {
+    let vfs = source.into_vfs().await?;
+    let report = brush_dataset::validate::validate_dataset(vfs, load_config).await;
+    print!("{report}");
+    if report.has_blocking_issues() {
+        anyhow::bail!("Dataset has blocking issues, see the errors above");
+    }
+    Ok(())
+}
+
+/// Initialize the backend on the adapter picked by `gpu`, or the default one if
+/// `None`.
+async fn init_gpu(gpu: Option<&str>) -> Result<WgpuDevice, anyhow::Error> {
+    if let Some(selector) = gpu {
+        Ok(brush_process::burn_init_setup_with_adapter(selector).await?)
+    } else {
+        Ok(brush_process::burn_init_setup().await)
+    }
+}
+
+/// Initialize the backend on the adapter picked by `gpu` (the default one if
+/// `None`), then drive `process` to completion, reporting progress in `format`.
 pub async fn run_headless(
     process: RunningProcess,
     train_stream_config: TrainStreamConfig,
+    gpu: Option<&str>,
+    format: ProgressFormat,
 ) -> Result<(), anyhow::Error> {
-    brush_process::burn_init_setup().await;
-    run_cli_ui(process, train_stream_config).await
+    init_gpu(gpu).await?;
+    match format {
+        ProgressFormat::Human => run_cli_ui(process, train_stream_config).await,
+        ProgressFormat::Json => run_json_progress(process).await,
+    }
 }
 
-/// Run the CLI: pin the trainer stream to a dedicated [`Actor`] thread,
-/// drive the indicatif UI on the main task.
-pub async fn run_cli_ui(
+/// Run `command` to completion on the command line. Training with a viewer is up
+/// to the caller, this fails for it.
+pub async fn run_command(command: Command) -> Result<(), anyhow::Error> {
+    match command {
+        Command::Train(args) => {
+            if args.validate_dataset {
+                let source = args.source.clone().expect("source must be present");
+                return validate_dataset(source, &args.train_stream.load_config).await;
+            }
+            anyhow::ensure!(!args.with_viewer, "Training with a viewer needs a window");
+            // `validate` guarantees a source is present when the viewer is off.
+            let process = build_process(&args).expect("source must be present");
+            run_headless(
+                process,
+                args.train_stream,
+                args.gpu.as_deref(),
+                args.progress_format,
+            )
+            .await
+        }
+        Command::Render(args) => {
+            let device = init_gpu(args.gpu.as_deref()).await?.into();
+            let opts = RenderOptions {
+                splat_scale: args.splat_scale,
+                ..Default::default()
+            };
+            let img_size = glam::uvec2(args.width, args.height);
+            let frames = match &args.camera_path {
+                Some(camera_path) => {
+                    brush_process::render::render_splat_file(
+                        &args.splats,
+                        camera_path,
+                        img_size,
+                        args.fps,
+                        opts,
+                        &args.out_dir,
+                        &device,
+                    )
+                    .await?
+                }
+                None => {
+                    brush_process::render::render_orbit_file(
+                        &args.splats,
+                        args.frames,
+                        img_size,
+                        opts,
+                        &args.out_dir,
+                        &device,
+                    )
+                    .await?
+                }
+            };
+            println!("Rendered {frames} frames to {}", args.out_dir.display());
+            Ok(())
+        }
+        Command::Eval(args) => {
+            let device = init_gpu(args.gpu.as_deref()).await?.into();
+            let train_config = TrainStreamConfig::default().train_config;
+            let report = brush_process::eval::eval_splat_file(
+                &args.splats,
+                args.source,
+                &args.load_config,
+                &train_config,
+                &device,
+            )
+            .await?;
+            let json = report.to_json();
+            if let Some(out) = &args.out {
+                std::fs::write(out, json)?;
+            } else {
+                println!("{json}");
+            }
+            Ok(())
+        }
+        Command::Convert(args) => {
+            let device = init_gpu(args.gpu.as_deref()).await?.into();
+            let num_splats = brush_process::convert::convert_splat_file(
+                &args.input,
+                &args.output,
+                args.format,
+                &device,
+            )
+            .await?;
+            println!("Wrote {num_splats} splats to {}", args.output.display());
+            Ok(())
+        }
+    }
+}
+
+/// Pump the trainer stream from a dedicated [`Actor`] thread, so the caller can
+/// consume its output on the main task. The actor has to be held while
+/// receiving, dropping it kills the pump.
+fn spawn_trainer(
     mut process: RunningProcess,
-    #[allow(unused)] train_stream_config: TrainStreamConfig,
-) -> Result<(), anyhow::Error> {
-    // Pump the trainer stream from a dedicated Actor thread; the
-    // indicatif UI loop below consumes its output on the main task.
-    let (tx, mut messages) = mpsc::unbounded_channel();
+) -> (
+    Actor,
+    mpsc::UnboundedReceiver<anyhow::Result<ProcessMessage>>,
+) {
+    let (tx, messages) = mpsc::unbounded_channel();
     let trainer = Actor::new("cli-trainer");
     trainer
         .run(move || async move {
@@ -91,10 +438,105 @@ pub async fn run_cli_ui(
             }
         })
         .detach();
+    (trainer, messages)
+}
+
+/// Print a [`ProgressLine`] for every message of `process`, see
+/// [`ProgressFormat::Json`]. Logs go to stderr so stdout only has JSON.
+pub async fn run_json_progress(process: RunningProcess) -> Result<(), anyhow::Error> {
+    let (_trainer, mut messages) = spawn_trainer(process);
 
-    // Hold the actor for the lifetime of the UI loop; dropping it
-    // would kill the pump.
-    let _trainer = trainer;
+    env_logger::builder()
+        .target(env_logger::Target::Stderr)
+        .try_init()
+        .expect("Failed to initialize logger");
+
+    let print = |line: &ProgressLine| {
+        let json = serde_json::to_string(line).expect("Progress serializes to JSON");
+        println!("{json}");
+    };
+
+    let mut num_splats = 0;
+    while let Some(msg) = messages.recv().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(error) => {
+                print(&ProgressLine::Error {
+                    message: format!("{error:#}"),
+                });
+                return Err(error);
+            }
+        };
+
+        let line = match msg {
+            ProcessMessage::NewProcess => ProgressLine::NewProcess,
+            ProcessMessage::StartLoading { name, .. } => ProgressLine::StartLoading { name },
+            ProcessMessage::DoneLoading => ProgressLine::DoneLoading,
+            ProcessMessage::SplatsUpdated {
+                num_splats: count, ..
+            } => {
+                num_splats = count;
+                continue;
+            }
+            ProcessMessage::Warning { error } => ProgressLine::Warning {
+                message: format!("{error:#}"),
+            },
+            ProcessMessage::TrainMessage(train) => match train {
+                TrainMessage::TrainConfig { .. } => continue,
+                TrainMessage::Dataset { dataset } => ProgressLine::Dataset {
+                    train_views: dataset.train.views.len(),
+                    eval_views: dataset.eval.as_ref().map_or(0, |v| v.views.len()),
+                },
+                TrainMessage::TrainStep {
+                    iter,
+                    total_elapsed,
+                    loss,
+                    ..
+                } => ProgressLine::TrainStep {
+                    iter,
+                    num_splats,
+                    loss,
+                    elapsed_secs: total_elapsed.as_secs_f64(),
+                },
+                TrainMessage::RefineStep {
+                    cur_splat_count,
+                    iter,
+                } => {
+                    num_splats = cur_splat_count;
+                    ProgressLine::RefineStep {
+                        iter,
+                        num_splats: cur_splat_count,
+                    }
+                }
+                TrainMessage::EvalResult {
+                    iter,
+                    avg_psnr,
+                    avg_ssim,
+                    avg_lpips,
+                    ..
+                } => ProgressLine::EvalResult {
+                    iter,
+                    psnr: avg_psnr,
+                    ssim: avg_ssim,
+                    lpips: avg_lpips,
+                },
+                TrainMessage::SplatsExported { iter, .. } => ProgressLine::SplatsExported { iter },
+                TrainMessage::DoneTraining => ProgressLine::DoneTraining,
+            },
+        };
+        print(&line);
+    }
+
+    Ok(())
+}
+
+/// Run the CLI: pin the trainer stream to a dedicated [`Actor`] thread,
+/// drive the indicatif UI on the main task.
+pub async fn run_cli_ui(
+    process: RunningProcess,
+    #[allow(unused)] train_stream_config: TrainStreamConfig,
+) -> Result<(), anyhow::Error> {
+    let (_trainer, mut messages) = spawn_trainer(process);
 
     // Initialize the logger with indicatif integration to prevent
     // progress bars from clobbering log output.
@@ -256,14 +698,28 @@ pub async fn run_cli_ui(
                     iter,
                     avg_psnr,
                     avg_ssim,
+                    avg_lpips,
+                    avg_corrected,
+                    report: _,
+                    refined_poses,
                 } => {
-                    log::info!("Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}");
-
-                    eval_spinner.set_message(format!(
-                        "Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}"
-                    ));
+                    let mut message = format!("Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}");
+                    if let Some(lpips) = avg_lpips {
+                        message += &format!(", lpips {lpips}");
+                    }
+                    if let Some((psnr, ssim)) = avg_corrected {
+                        message += &format!(" (exposure corrected: PSNR {psnr}, ssim {ssim})");
+                    }
+                    if let Some(poses) = refined_poses.filter(|p| !p.is_empty()) {
+                        let count = poses.len() as f32;
+                        let rot = poses.iter().map(|p| p.rotation_deg).sum::<f32>() / count;
+                        let trans = poses.iter().map(|p| p.translation).sum::<f32>() / count;
+                        message += &format!(", mean pose correction {rot:.3}° {trans:.4}");
+                    }
+                    log::info!("{message}");
+                    eval_spinner.set_message(message);
                 }
-                TrainMessage::DoneTraining => {}
+                TrainMessage::SplatsExported { .. } | TrainMessage::DoneTraining => {}
             },
             ProcessMessage::DoneLoading => {
                 log::info!("Completed loading.");
@@ -292,3 +748,168 @@ pub async fn run_cli_ui(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Command {
+        let cli = Cli::try_parse_from(std::iter::once("brush").chain(args.iter().copied()))
+            .expect("Failed to parse args");
+        cli.into_command().expect("Invalid args")
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_train() {
+        let Command::Train(args) = parse(&["train", "data", "--headless", "--sh-degree", "2"])
+        else {
+            panic!("Expected train");
+        };
+        assert!(
+            args.headless && !args.with_viewer,
+            "--headless turns off the viewer"
+        );
+        assert_eq!(args.train_stream.model_config.sh_degree, 2);
+
+        // Without a subcommand the arguments are those of train.
+        let Command::Train(args) = parse(&["data", "--headless", "--sh-degree", "2"]) else {
+            panic!("Expected train");
+        };
+        assert!(args.source.is_some(), "Source should be parsed");
+        assert_eq!(args.train_stream.model_config.sh_degree, 2);
+
+        let Command::Train(args) = parse(&[]) else {
+            panic!("Expected train");
+        };
+        assert!(args.with_viewer, "No arguments opens the viewer");
+    }
+
+    #[test]
+    fn test_no_display() {
+        let args = |args: &[&str]| {
+            let cli = Cli::try_parse_from(std::iter::once("brush").chain(args.iter().copied()))
+                .expect("Failed to parse args");
+            cli.train
+        };
+
+        let validated = args(&["data"])
+            .validate_for_display(false)
+            .expect("Should fall back to headless");
+        assert!(
+            validated.headless && !validated.with_viewer,
+            "No display runs headless"
+        );
+
+        let err = args(&["data", "--with-viewer"])
+            .validate_for_display(false)
+            .err()
+            .expect("An explicit --with-viewer can't run headless");
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+
+        let validated = args(&["data", "--with-viewer"])
+            .validate_for_display(true)
+            .expect("Valid with a display");
+        assert!(
+            validated.with_viewer && !validated.headless,
+            "A display keeps the viewer"
+        );
+    }
+
+    #[test]
+    fn test_parse_render() {
+        let Command::Render(args) = parse(&[
+            "render",
+            "scene.ply",
+            "path.json",
+            "--width",
+            "640",
+            "--height",
+            "480",
+            "--fps",
+            "24",
+        ]) else {
+            panic!("Expected render");
+        };
+        assert_eq!(args.splats, PathBuf::from("scene.ply"));
+        assert_eq!(args.camera_path, Some(PathBuf::from("path.json")));
+        assert!(!args.orbit);
+        assert_eq!((args.width, args.height), (640, 480));
+        assert_eq!(args.fps, 24.0);
+        assert_eq!(args.out_dir, PathBuf::from("render"));
+    }
+
+    #[test]
+    fn test_parse_render_orbit() {
+        let Command::Render(args) = parse(&[
+            "render",
+            "scene.ply",
+            "--orbit",
+            "--frames",
+            "4",
+            "--out-dir",
+            "frames",
+        ]) else {
+            panic!("Expected render");
+        };
+        assert!(args.orbit);
+        assert_eq!(args.camera_path, None);
+        assert_eq!(args.frames, 4);
+        assert_eq!(args.out_dir, PathBuf::from("frames"));
+
+        let cli = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("brush").chain(args.iter().copied()))
+        };
+        assert!(
+            cli(&["render", "scene.ply"]).is_err(),
+            "Needs a camera path or --orbit"
+        );
+        assert!(
+            cli(&["render", "scene.ply", "path.json", "--orbit"]).is_err(),
+            "A camera path and --orbit conflict"
+        );
+    }
+
+    #[test]
+    fn test_parse_eval() {
+        let Command::Eval(args) = parse(&[
+            "eval",
+            "scene.ply",
+            "data",
+            "--eval-split-every",
+            "8",
+            "--out",
+            "metrics.json",
+        ]) else {
+            panic!("Expected eval");
+        };
+        assert_eq!(args.splats, PathBuf::from("scene.ply"));
+        assert_eq!(args.load_config.eval_split_every, Some(8));
+        assert_eq!(args.out, Some(PathBuf::from("metrics.json")));
+    }
+
+    #[test]
+    fn test_parse_convert() {
+        let Command::Convert(args) = parse(&["convert", "scene.ply", "scene.spz"]) else {
+            panic!("Expected convert");
+        };
+        assert_eq!(args.input, PathBuf::from("scene.ply"));
+        assert_eq!(args.output, PathBuf::from("scene.spz"));
+        assert_eq!(args.format, None);
+
+        let Command::Convert(args) =
+            parse(&["convert", "scene.spz", "scene.ply", "--format", "ply-ascii"])
+        else {
+            panic!("Expected convert");
+        };
+        assert_eq!(args.format, Some(ExportFormat::PlyAscii));
+
+        let missing_output = Cli::try_parse_from(["brush", "convert", "scene.ply"]);
+        assert!(missing_output.is_err(), "convert needs an output");
+    }
+}
@@ -1,29 +1,28 @@
 #![recursion_limit = "256"]
 
 // Headless trainer binary. The viewer lives in brush-app (the `brush` binary);
-// this is a lean build of just the training path for quick CLI iteration.
+// this is a lean build of just the headless commands for quick CLI iteration.
 #[cfg(not(target_family = "wasm"))]
 fn main() -> anyhow::Result<()> {
-    use brush_cli::{Cli, build_process, run_headless};
+    use brush_cli::{Cli, Command, run_command};
     use clap::Parser;
 
-    let args = Cli::parse().validate()?;
+    let command = Cli::parse().into_command()?;
 
-    if args.with_viewer {
+    if let Command::Train(args) = &command
+        && args.with_viewer
+    {
         anyhow::bail!(
             "brush-cli is headless and can't open a viewer. Pass a source to train, \
              or build the `brush` binary (brush-app) for the viewer."
         );
     }
 
-    // `validate` guarantees a source is present when the viewer is off.
-    let process = build_process(&args).expect("source must be present");
-
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("Failed to initialize tokio runtime")
-        .block_on(run_headless(process, args.train_stream))
+        .block_on(run_command(command))
 }
 
 #[cfg(target_family = "wasm")]
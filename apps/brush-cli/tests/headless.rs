@@ -0,0 +1,82 @@
+#![cfg(not(target_family = "wasm"))]
+
+use std::path::Path;
+use std::process::Command;
+
+// Train a few steps with no display around, like in a container or on CI. This
+// must never touch any windowing code.
+#[test]
+fn test_headless_train_without_display() {
+    let dataset = Path::new(env!("CARGO_MANIFEST_DIR")).join("../brush-c/tests/data/test_dataset");
+    let export_path = std::env::temp_dir().join("brush_cli_headless_test");
+    let _ = std::fs::remove_dir_all(&export_path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_brush-cli"))
+        .arg(&dataset)
+        .arg("--headless")
+        .args(["--total-train-iters", "5"])
+        .args(["--export-every", "5"])
+        .args(["--max-resolution", "50"])
+        .arg("--export-path")
+        .arg(&export_path)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .output()
+        .expect("Failed to run brush-cli");
+
+    assert!(
+        output.status.success(),
+        "brush-cli failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(export_path.join("export_5.ply").exists());
+}
+
+// With --progress-format json every line on stdout is a JSON object, ending with
+// the end of training.
+#[test]
+fn test_headless_json_progress() {
+    let dataset = Path::new(env!("CARGO_MANIFEST_DIR")).join("../brush-c/tests/data/test_dataset");
+    let export_path = std::env::temp_dir().join("brush_cli_json_progress_test");
+    let _ = std::fs::remove_dir_all(&export_path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_brush-cli"))
+        .arg("train")
+        .arg(&dataset)
+        .arg("--headless")
+        .args(["--progress-format", "json"])
+        .args(["--total-train-iters", "10"])
+        .args(["--max-resolution", "50"])
+        .arg("--export-path")
+        .arg(&export_path)
+        .output()
+        .expect("Failed to run brush-cli");
+
+    assert!(
+        output.status.success(),
+        "brush-cli failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).expect("stdout should be UTF-8");
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Every line should be JSON"))
+        .collect();
+    let types: Vec<&str> = lines
+        .iter()
+        .map(|line| line["type"].as_str().expect("Every line has a type"))
+        .collect();
+
+    assert_eq!(types.first(), Some(&"new_process"));
+    assert_eq!(types.last(), Some(&"done_training"));
+    let last_step = lines
+        .iter()
+        .rfind(|line| line["type"] == "train_step")
+        .expect("Should report training steps");
+    assert_eq!(last_step["iter"], 10);
+    assert!(
+        last_step["num_splats"].as_u64().is_some_and(|n| n > 0),
+        "Steps should report the splat count"
+    );
+}
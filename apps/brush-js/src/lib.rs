@@ -48,8 +48,11 @@ impl BrushMessage {
                 TrainMessage::RefineStep { .. } => BrushMessageKind::RefineStep,
                 TrainMessage::EvalResult { .. } => BrushMessageKind::EvalResult,
                 TrainMessage::DoneTraining => BrushMessageKind::DoneTraining,
-                // Filtered before reaching JS; arm exists only for exhaustiveness.
-                TrainMessage::TrainConfig { .. } => BrushMessageKind::DoneLoading,
+                // Filtered before reaching JS, or native only (exports); arm
+                // exists only for exhaustiveness.
+                TrainMessage::TrainConfig { .. } | TrainMessage::SplatsExported { .. } => {
+                    BrushMessageKind::DoneLoading
+                }
             },
             ProcessMessage::Warning { .. } => BrushMessageKind::Warning,
             ProcessMessage::DoneLoading => BrushMessageKind::DoneLoading,
@@ -118,6 +121,14 @@ impl BrushMessage {
         }
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn lpips(&self) -> Option<f32> {
+        match &self.inner {
+            ProcessMessage::TrainMessage(TrainMessage::EvalResult { avg_lpips, .. }) => *avg_lpips,
+            _ => None,
+        }
+    }
+
     #[wasm_bindgen(getter)]
     pub fn name(&self) -> Option<String> {
         match &self.inner {
@@ -137,6 +137,9 @@ fn generate_training_batch(resolution: (u32, u32), camera_pos: Vec3) -> SceneBat
         has_alpha: false,
         alpha_mode: AlphaMode::Transparent,
         camera,
+        view_index: 0,
+        hdr: None,
+        depth: None,
     }
 }
 
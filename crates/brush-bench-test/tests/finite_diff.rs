@@ -768,6 +768,7 @@ fn random_camera_with_model(seed: u64) -> (Camera, &'static str) {
         CameraModel::KannalaBrandt4(_) => "KB4",
         CameraModel::RadialTangential8(_) => "RT8",
         CameraModel::ThinPrismFisheye(_) => "TPF",
+        CameraModel::Orthographic => "Ortho",
     };
     (
         Camera::new(cam_pos, glam::Quat::IDENTITY, fov, fov, center, model),
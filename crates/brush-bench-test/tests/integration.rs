@@ -13,9 +13,12 @@ use brush_render::{
     kernels::camera_model::CameraModel::Pinhole,
 };
 use brush_render_bwd::render_splats;
-use brush_train::{config::TrainConfig, train::SplatTrainer};
+use brush_train::{
+    config::{OptimizerKind, TrainConfig},
+    train::SplatTrainer,
+};
 use burn::module::AutodiffModule;
-use burn::tensor::{Device, TensorData};
+use burn::tensor::{Device, Distribution, Tensor, TensorData, s};
 use glam::{Quat, Vec3};
 use rand::{RngExt, SeedableRng};
 use wasm_bindgen_test::wasm_bindgen_test;
@@ -127,6 +130,9 @@ fn generate_test_batch(resolution: (u32, u32)) -> SceneBatch {
         has_alpha: false,
         alpha_mode: AlphaMode::Transparent,
         camera,
+        view_index: 0,
+        hdr: None,
+        depth: None,
     }
 }
 
@@ -182,6 +188,46 @@ async fn test_forward_rendering() {
     assert!(data.iter().all(|&v| v.is_finite()));
 }
 
+// The differentiable forward composites the background behind the splats:
+// rendering on white instead of black adds exactly the transmittance.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_render_background_compositing() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let splats = generate_test_splats(&device, 1000);
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -8.0),
+        Quat::IDENTITY,
+        45.0,
+        45.0,
+        glam::vec2(0.5, 0.5),
+        Pinhole,
+    );
+    let img_size = glam::uvec2(64, 64);
+
+    let black = render_splats(splats.clone(), &camera, img_size, Vec3::ZERO).await;
+    let white = render_splats(splats, &camera, img_size, Vec3::ONE).await;
+
+    let alpha = black.img.clone().slice(s![.., .., 3..4]);
+    let rgb_diff = white.img.clone().slice(s![.., .., 0..3]) - black.img.slice(s![.., .., 0..3]);
+    let max_err = (rgb_diff - (1.0 - alpha.clone()))
+        .abs()
+        .max()
+        .into_scalar_async::<f32>()
+        .await
+        .unwrap();
+    assert!(max_err < 1e-4, "Background not composited, error {max_err}");
+
+    // Alpha itself doesn't depend on the background.
+    let alpha_diff = (white.img.slice(s![.., .., 3..4]) - alpha)
+        .abs()
+        .max()
+        .into_scalar_async::<f32>()
+        .await
+        .unwrap();
+    assert_eq!(alpha_diff, 0.0);
+}
+
 #[wasm_bindgen_test(unsupported = tokio::test)]
 async fn test_training_step() {
     let device =
@@ -228,6 +274,1310 @@ async fn test_multi_step_training() {
     assert!(splats.num_splats() > 0);
 }
 
+// Plain SGD (with momentum) also fits the scene, just with larger learning rates.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_sgd_reduces_loss() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut config = TrainConfig::default();
+    config.optimizer = OptimizerKind::Sgd;
+    config.background_noise_strength = 0.0;
+    config.lr_coeffs_dc *= 10.0;
+    config.lr_opac *= 10.0;
+    config.lr_scale *= 10.0;
+    config.lr_rotation *= 10.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE),
+    );
+
+    let mut splats = generate_test_splats(&device, 100);
+    let mut losses = vec![];
+    for _ in 0..100 {
+        let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+        losses.push(stats.loss.into_scalar_async::<f32>().await.unwrap());
+    }
+    let first = losses[..5].iter().sum::<f32>() / 5.0;
+    let last = losses[95..].iter().sum::<f32>() / 5.0;
+    assert!(last.is_finite());
+    assert!(last < first, "Loss went from {first} to {last}");
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_lr_warmup() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((32, 32));
+    let mut config = TrainConfig::default();
+    config.warmup_steps = 4;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE),
+    );
+
+    let mut splats = generate_test_splats(&device, 100);
+    let mut lrs = vec![];
+    for _ in 0..6 {
+        let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+        lrs.push((stats.lr_mean, stats.lr_scale));
+    }
+
+    // Zero at the start, halfway there mid-warmup, and the full rate after.
+    assert_eq!(lrs[0], (0.0, 0.0));
+    assert!((lrs[2].0 / lrs[4].0 - 0.5).abs() < 1e-6);
+    assert!((lrs[2].1 - 0.5 * config.lr_scale).abs() < 1e-9);
+    assert!((lrs[4].1 - config.lr_scale).abs() < 1e-9);
+    // After the warmup the mean rate decays again.
+    assert!(lrs[5].0 < lrs[4].0);
+    assert!(lrs[4].0 > lrs[3].0);
+}
+
+/// Render `splats` into a packed, opaque `size` x `size` training image.
+async fn render_packed(splats: &Splats, camera: &Camera, size: u32) -> TensorData {
+    use brush_render::TextureMode;
+    use brush_render::gaussian_splats::render_splats as render_splats_fwd;
+
+    let (img, _) = render_splats_fwd(
+        splats.valid(),
+        camera,
+        glam::uvec2(size, size),
+        Vec3::ZERO,
+        None,
+        TextureMode::Float,
+    )
+    .await;
+    let packed: Vec<i32> = img
+        .into_data_async()
+        .await
+        .unwrap()
+        .into_vec::<f32>()
+        .unwrap()
+        .chunks(4)
+        .map(|c| {
+            let [r, g, b, _] = [c[0], c[1], c[2], c[3]].map(|v| (v.clamp(0.0, 1.0) * 255.0) as u8);
+            u32::from_le_bytes([r, g, b, 255]) as i32
+        })
+        .collect();
+    TensorData::new(packed, [size as usize, size as usize])
+}
+
+// A single splat in front of the camera, with a depth target closer than the
+// splat. The color target is the initial render, so only the depth loss moves it.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_depth_loss_pulls_splat_to_target() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -4.0),
+        Quat::IDENTITY,
+        0.8,
+        0.8,
+        glam::vec2(0.5, 0.5),
+        Pinhole,
+    );
+    let splats = Splats::from_raw(
+        vec![0.0, 0.0, 0.0],
+        vec![1.0, 0.0, 0.0, 0.0],
+        vec![-1.0, -1.0, -1.0],
+        vec![0.5, 0.5, 0.5],
+        vec![0.95],
+        SplatRenderMode::Default,
+        &device,
+    );
+
+    let img_packed = render_packed(&splats, &camera, 64).await;
+    // Only the center of the image has a depth, the rest is ignored.
+    let depth: Vec<f32> = (0..64 * 64)
+        .map(|i| {
+            let (x, y) = (i % 64, i / 64);
+            if (28..36).contains(&x) && (28..36).contains(&y) {
+                2.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let batch = SceneBatch {
+        img_packed,
+        has_alpha: false,
+        alpha_mode: AlphaMode::Transparent,
+        camera,
+        view_index: 0,
+        hdr: None,
+        depth: Some(TensorData::new(depth, [64, 64])),
+    };
+
+    let mut config = TrainConfig::default();
+    config.depth_loss_weight = 10.0;
+    config.lr_mean = 1e-2;
+    config.lr_mean_end = 1e-2;
+    config.lr_coeffs_dc = 0.0;
+    config.lr_opac = 0.0;
+    config.lr_scale = 0.0;
+    config.lr_rotation = 0.0;
+    config.mean_noise_weight = 0.0;
+    config.opac_decay = 0.0;
+    config.background_noise_strength = 0.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+    );
+    let mut splats = splats;
+    for _ in 0..100 {
+        let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+    }
+
+    // The splat started at a depth of 4, and should have moved towards the camera.
+    let mean = splats
+        .means()
+        .into_data_async()
+        .await
+        .unwrap()
+        .into_vec::<f32>()
+        .unwrap();
+    assert!(
+        mean[2] < -0.2,
+        "Splat didn't move towards the target: {mean:?}"
+    );
+}
+
+// A single needle-like splat, with the initial render as the target so only
+// the scale regularizer moves it.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_scale_reg_makes_splat_isotropic() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -4.0),
+        Quat::IDENTITY,
+        0.8,
+        0.8,
+        glam::vec2(0.5, 0.5),
+        Pinhole,
+    );
+    let splats = Splats::from_raw(
+        vec![0.0, 0.0, 0.0],
+        vec![1.0, 0.0, 0.0, 0.0],
+        vec![-1.0, -3.0, -3.0],
+        vec![0.5, 0.5, 0.5],
+        vec![0.95],
+        SplatRenderMode::Default,
+        &device,
+    );
+    let batch = SceneBatch {
+        img_packed: render_packed(&splats, &camera, 64).await,
+        has_alpha: false,
+        alpha_mode: AlphaMode::Transparent,
+        camera,
+        view_index: 0,
+        hdr: None,
+        depth: None,
+    };
+
+    let mut config = TrainConfig::default();
+    config.scale_reg_weight = 10.0;
+    config.max_scale_ratio = 2.0;
+    config.lr_scale = 2e-2;
+    config.lr_mean = 0.0;
+    config.lr_mean_end = 0.0;
+    config.lr_coeffs_dc = 0.0;
+    config.lr_opac = 0.0;
+    config.lr_rotation = 0.0;
+    config.mean_noise_weight = 0.0;
+    config.opac_decay = 0.0;
+    config.background_noise_strength = 0.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+    );
+    let mut splats = splats;
+    for _ in 0..50 {
+        let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+    }
+
+    // The splat started 7.4x (e^2) longer than wide.
+    let log_scales = splats
+        .log_scales()
+        .into_data_async()
+        .await
+        .unwrap()
+        .into_vec::<f32>()
+        .unwrap();
+    let log_ratio = log_scales.iter().copied().fold(f32::MIN, f32::max)
+        - log_scales.iter().copied().fold(f32::MAX, f32::min);
+    assert!(
+        log_ratio < 1.5,
+        "Splat didn't become more isotropic: {log_scales:?}"
+    );
+}
+
+// Two splats, one far off its target color and one close to it. With the
+// adaptive mean learning rate the badly fit splat, which has the larger
+// screen-space gradient, moves further in a step.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_adaptive_mean_lr() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -4.0),
+        Quat::IDENTITY,
+        0.8,
+        0.8,
+        glam::vec2(0.5, 0.5),
+        Pinhole,
+    );
+    let two_splats = |colors: [f32; 2]| {
+        Splats::from_raw(
+            vec![-0.8, 0.1, 0.0, 0.8, -0.1, 0.0],
+            vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            vec![-1.5; 6],
+            [[colors[0]; 3], [colors[1]; 3]].concat(),
+            vec![0.5, 0.5],
+            SplatRenderMode::Default,
+            &device,
+        )
+    };
+    let batch = SceneBatch {
+        img_packed: render_packed(&two_splats([-1.0, 0.2]), &camera, 64).await,
+        has_alpha: false,
+        alpha_mode: AlphaMode::Transparent,
+        camera,
+        view_index: 0,
+        hdr: None,
+        depth: None,
+    };
+
+    // How far each splat moves in a single step.
+    let step_distances = async |adaptive_mean_lr: bool| {
+        let mut config = TrainConfig::default();
+        config.adaptive_mean_lr = adaptive_mean_lr;
+        config.lr_coeffs_dc = 0.0;
+        config.lr_opac = 0.0;
+        config.lr_scale = 0.0;
+        config.lr_rotation = 0.0;
+        config.mean_noise_weight = 0.0;
+        config.opac_decay = 0.0;
+        config.background_noise_strength = 0.0;
+        let mut trainer = SplatTrainer::new(
+            &config,
+            &device,
+            BoundingBox::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+        );
+        let splats = two_splats([1.0, 0.3]);
+        let before = splats.means().into_data_async().await.unwrap();
+        let (splats, _) = trainer.step(batch.clone(), splats).await;
+        let after = splats.means().into_data_async().await.unwrap();
+        let (before, after) = (
+            before.into_vec::<f32>().unwrap(),
+            after.into_vec::<f32>().unwrap(),
+        );
+        let distance = |i: usize| {
+            (0..3)
+                .map(|c| (after[i * 3 + c] - before[i * 3 + c]).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        };
+        [distance(0), distance(1)]
+    };
+
+    // Adam's first step moves every coordinate with a gradient by the
+    // learning rate, so without the adaptive rate both splats move alike.
+    let [far, close] = step_distances(false).await;
+    assert!(
+        far > 0.0 && close > 0.0,
+        "Splats didn't move: {far} {close}"
+    );
+    assert!((far / close - 1.0).abs() < 0.25, "{far} vs {close}");
+
+    let [far, close] = step_distances(true).await;
+    assert!(
+        far > 1.5 * close,
+        "Badly fit splat should move further: {far} vs {close}"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_eval_report() {
+    use brush_render::gaussian_splats::PreparedSplats;
+    use brush_train::eval::{EvalReport, EvalViewReport, eval_stats};
+
+    let device: Device = brush_cube::test_helpers::test_device().await.into();
+    let splats = generate_test_splats(&device, 500);
+    let prepared = PreparedSplats::new(splats.clone(), None).await;
+    let lpips = lpips::load_vgg_lpips(&device);
+    let config = TrainConfig::default();
+
+    let mut report = EvalReport {
+        iter: 10,
+        views: vec![],
+    };
+    for (i, resolution) in [(64, 64), (48, 32)].into_iter().enumerate() {
+        let batch = generate_test_batch(resolution);
+        let [h, w] = [batch.img_packed.shape[0], batch.img_packed.shape[1]];
+        let pixels: Vec<u8> =
+            bytemuck::cast_slice(&batch.img_packed.to_vec::<i32>().unwrap()).to_vec();
+        let gt_img = image::RgbaImage::from_raw(w as u32, h as u32, pixels).unwrap();
+        let sample = eval_stats(
+            &prepared,
+            &batch.camera,
+            gt_img.into(),
+            AlphaMode::Transparent,
+            config.ssim_mode,
+            config.ssim_window(),
+            None,
+            Some(&lpips),
+            &device,
+        )
+        .await
+        .unwrap();
+        let view = EvalViewReport::from_sample(format!("view_{i}"), &sample, splats.num_splats())
+            .await
+            .unwrap();
+        report.views.push(view);
+    }
+
+    let csv = report.to_csv();
+    let rows: Vec<_> = csv.lines().skip(1).collect();
+    assert_eq!(rows.len(), 2);
+    for row in rows {
+        let fields: Vec<_> = row.split(',').collect();
+        assert_eq!(fields.len(), 6);
+        for metric in &fields[1..4] {
+            let value: f32 = metric.parse().unwrap();
+            assert!(value.is_finite(), "Non finite metric in {row}");
+        }
+        assert_eq!(fields[5], "500");
+    }
+    assert!(report.avg_lpips().is_some_and(f32::is_finite));
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["views"].as_array().unwrap().len(), 2);
+}
+
+// Evaluating against the 8-bit render itself should give a perfect LPIPS.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_eval_lpips_identical_is_zero() {
+    use brush_render::gaussian_splats::PreparedSplats;
+    use brush_train::eval::eval_stats;
+
+    let device: Device = brush_cube::test_helpers::test_device().await.into();
+    let splats = generate_test_splats(&device, 500);
+    let prepared = PreparedSplats::new(splats, None).await;
+    let lpips = lpips::load_vgg_lpips(&device);
+    let config = TrainConfig::default();
+
+    let batch = generate_test_batch((64, 64));
+    let [h, w] = [batch.img_packed.shape[0], batch.img_packed.shape[1]];
+    let pixels: Vec<u8> = bytemuck::cast_slice(&batch.img_packed.to_vec::<i32>().unwrap()).to_vec();
+    let eval = async |gt_img: image::DynamicImage| {
+        eval_stats(
+            &prepared,
+            &batch.camera,
+            gt_img,
+            AlphaMode::Transparent,
+            config.ssim_mode,
+            config.ssim_window(),
+            None,
+            Some(&lpips),
+            &device,
+        )
+        .await
+        .unwrap()
+    };
+
+    let first = eval(
+        image::RgbaImage::from_raw(w as u32, h as u32, pixels)
+            .unwrap()
+            .into(),
+    )
+    .await;
+    let rendered: Vec<f32> = first
+        .rendered
+        .into_data_async()
+        .await
+        .unwrap()
+        .to_vec()
+        .unwrap();
+    let rendered: Vec<u8> = rendered
+        .chunks_exact(3)
+        .flat_map(|p| [p[0], p[1], p[2], 1.0].map(|v| (v * 255.0).round() as u8))
+        .collect();
+    let gt_img = image::RgbaImage::from_raw(w as u32, h as u32, rendered).unwrap();
+
+    let sample = eval(gt_img.into()).await;
+    let lpips = sample.lpips.expect("LPIPS requested");
+    let lpips: f32 = lpips.into_scalar_async().await.unwrap();
+    assert!(lpips.abs() < 1e-4, "LPIPS of identical images is {lpips}");
+}
+
+// Two views of the same scene from the same camera, one at half the
+// brightness. The learned per-view corrections should explain the difference.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_learned_exposure_compensates_brightness() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let bright = generate_test_batch((64, 64));
+    let dark_pixels: Vec<i32> = bright
+        .img_packed
+        .to_vec::<i32>()
+        .unwrap()
+        .into_iter()
+        .map(|p| {
+            let [r, g, b, a] = (p as u32).to_le_bytes();
+            let [r, g, b] = [r, g, b].map(|c| c / 2);
+            u32::from_le_bytes([r, g, b, a]) as i32
+        })
+        .collect();
+    let dark = SceneBatch {
+        img_packed: TensorData::new(dark_pixels, bright.img_packed.shape.clone()),
+        view_index: 1,
+        ..bright.clone()
+    };
+
+    let mut config = TrainConfig::default();
+    config.learn_exposure = true;
+    config.lr_exposure = 1e-2;
+    config.background_noise_strength = 0.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE),
+    );
+
+    let mut splats = generate_test_splats(&device, 100);
+    for i in 0..400 {
+        let batch = if i % 2 == 0 { &bright } else { &dark };
+        let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+    }
+
+    // Apply both corrections to a typical color of the scene.
+    let color = Vec3::new(0.5, 0.5, 0.6);
+    let mut corrected = [0.0; 2];
+    for (view, out) in corrected.iter_mut().enumerate() {
+        let m: Vec<f32> = trainer
+            .exposure(view)
+            .unwrap()
+            .into_data_async()
+            .await
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        let row =
+            |r: usize| Vec3::new(m[r * 4], m[r * 4 + 1], m[r * 4 + 2]).dot(color) + m[r * 4 + 3];
+        *out = Vec3::new(row(0), row(1), row(2)).length();
+    }
+    let ratio = corrected[1] / corrected[0];
+    assert!((ratio - 0.5).abs() < 0.1, "Brightness ratio {ratio}");
+}
+
+// With SH warmup the first steps only render the base color. The higher bands
+// can't change the loss and get no gradient, until they're enabled.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_sh_warmup() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut config = TrainConfig::default();
+    config.sh_warmup_every = Some(2);
+    config.background_noise_strength = 0.0;
+
+    async fn read_rest(splats: &Splats) -> Vec<f32> {
+        splats
+            .sh_coeffs
+            .val()
+            .slice(s![.., 1..])
+            .into_data_async()
+            .await
+            .unwrap()
+            .to_vec()
+            .unwrap()
+    }
+
+    let mut first_losses = vec![];
+    for rest_scale in [0.0, 1.0] {
+        let mut splats = generate_test_splats(&device, 100).with_sh_degree(1);
+        splats.sh_coeffs = splats.sh_coeffs.map(|coeffs| {
+            let [n, c, _] = coeffs.dims();
+            let rest = Tensor::random([n, c - 1, 3], Distribution::Uniform(-1.0, 1.0), &device);
+            Tensor::cat(vec![coeffs.slice(s![.., 0..1]), rest * rest_scale], 1)
+                .detach()
+                .require_grad()
+        });
+        let rest_before = read_rest(&splats).await;
+
+        let mut trainer = SplatTrainer::new(
+            &config,
+            &device,
+            BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE),
+        );
+        assert_eq!(trainer.active_sh_degree(), 0);
+        let (splats, stats) = trainer.step(batch.clone(), splats).await;
+        first_losses.push(stats.loss.into_scalar_async::<f32>().await.unwrap());
+        let (splats, _) = trainer.step(batch.clone(), splats).await;
+        assert_eq!(read_rest(&splats).await, rest_before);
+
+        assert_eq!(trainer.active_sh_degree(), 1);
+        let (splats, _) = trainer.step(batch.clone(), splats).await;
+        assert_ne!(read_rest(&splats).await, rest_before);
+    }
+
+    assert!(
+        (first_losses[0] - first_losses[1]).abs() < 1e-6,
+        "Higher SH bands changed the loss during warmup: {first_losses:?}"
+    );
+}
+
+// Against a fixed black background, splats can cover the transparent parts of a
+// view with dark, opaque splats. A random background each step can only be
+// matched by leaving them transparent.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_random_background_keeps_empty_regions_transparent() {
+    use brush_render::TextureMode;
+    use brush_render::gaussian_splats::render_splats as render_splats_fwd;
+    use brush_train::background::BackgroundMode;
+
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let (width, height) = (64, 64);
+    let batch = generate_test_batch((width, height));
+    // Make the right half of the view fully transparent.
+    let pixels: Vec<i32> = batch
+        .img_packed
+        .to_vec::<i32>()
+        .unwrap()
+        .into_iter()
+        .enumerate()
+        .map(|(i, p)| if i as u32 % width >= width / 2 { 0 } else { p })
+        .collect();
+    let batch = SceneBatch {
+        img_packed: TensorData::new(pixels, batch.img_packed.shape.clone()),
+        has_alpha: true,
+        alpha_mode: AlphaMode::Transparent,
+        ..batch
+    };
+
+    let mut empty_alpha = vec![];
+    for mode in [BackgroundMode::Black, BackgroundMode::Random] {
+        let mut config = TrainConfig::default();
+        config.background = mode;
+        config.background_noise_strength = 0.0;
+        // Only the background should keep the empty region transparent.
+        config.match_alpha_weight = 0.0;
+        let mut trainer = SplatTrainer::new(
+            &config,
+            &device,
+            BoundingBox::from_min_max(Vec3::splat(-2.0), Vec3::splat(2.0)),
+        );
+        let mut splats = generate_test_splats(&device, 500);
+        for _ in 0..300 {
+            let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+            splats = new_splats;
+        }
+
+        let (img, _) = render_splats_fwd(
+            splats.valid(),
+            &batch.camera,
+            glam::uvec2(width, height),
+            Vec3::ZERO,
+            None,
+            TextureMode::Float,
+        )
+        .await;
+        let alpha = img
+            .slice(s![.., (width / 2) as usize.., 3..4])
+            .mean()
+            .into_scalar_async::<f32>()
+            .await
+            .unwrap();
+        empty_alpha.push(alpha);
+    }
+
+    assert!(
+        empty_alpha[1] < empty_alpha[0],
+        "Random background should leave less alpha in empty regions: {empty_alpha:?}"
+    );
+}
+
+// With the alpha channel as a mask, zero alpha pixels should be ignored rather
+// than trained to be transparent, so the render there should stay close to
+// the render before training.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_alpha_mask_ignores_masked_pixels() {
+    use brush_render::TextureMode;
+    use brush_render::gaussian_splats::render_splats as render_splats_fwd;
+
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let (width, height) = (64, 64);
+    let batch = generate_test_batch((width, height));
+    // Zero alpha over the right half of the view.
+    let pixels: Vec<i32> = batch
+        .img_packed
+        .to_vec::<i32>()
+        .unwrap()
+        .into_iter()
+        .enumerate()
+        .map(|(i, p)| if i as u32 % width >= width / 2 { 0 } else { p })
+        .collect();
+    let batch = SceneBatch {
+        img_packed: TensorData::new(pixels, batch.img_packed.shape.clone()),
+        has_alpha: true,
+        ..batch
+    };
+
+    let render_right_half = async |splats: &Splats| {
+        let (img, _) = render_splats_fwd(
+            splats.valid(),
+            &batch.camera,
+            glam::uvec2(width, height),
+            Vec3::ZERO,
+            None,
+            TextureMode::Float,
+        )
+        .await;
+        img.slice(s![.., (width / 2) as usize.., ..])
+    };
+    let initial = render_right_half(&generate_test_splats(&device, 500)).await;
+
+    let mut changes = vec![];
+    for alpha_mode in [AlphaMode::Transparent, AlphaMode::Masked] {
+        let batch = SceneBatch {
+            alpha_mode,
+            ..batch.clone()
+        };
+        let mut trainer = SplatTrainer::new(
+            &TrainConfig::default(),
+            &device,
+            BoundingBox::from_min_max(Vec3::splat(-2.0), Vec3::splat(2.0)),
+        );
+        let mut splats = generate_test_splats(&device, 500);
+        for _ in 0..50 {
+            let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+            splats = new_splats;
+        }
+        let change = (render_right_half(&splats).await - initial.clone())
+            .abs()
+            .mean()
+            .into_scalar_async::<f32>()
+            .await
+            .unwrap();
+        changes.push(change);
+    }
+
+    assert!(
+        changes[1] < changes[0],
+        "Masked pixels should barely change the render behind them: {changes:?}"
+    );
+}
+
+// Train against a view rendered from a known camera, but with a slightly
+// rotated camera. With the splats frozen, pose refinement should move the
+// camera back so the scene reprojects close to where it should.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_pose_refine_recovers_rotation() {
+    use brush_render::TextureMode;
+    use brush_render::gaussian_splats::render_splats as render_splats_fwd;
+
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let img_size = glam::uvec2(128, 128);
+    let true_cam = Camera::new(
+        Vec3::new(0.0, 0.0, -8.0),
+        Quat::IDENTITY,
+        0.8,
+        0.8,
+        glam::vec2(0.5, 0.5),
+        Pinhole,
+    );
+    let gt_splats = generate_test_splats(&device, 500);
+    let (gt_img, _) = render_splats_fwd(
+        gt_splats.valid(),
+        &true_cam,
+        img_size,
+        Vec3::ZERO,
+        None,
+        TextureMode::Float,
+    )
+    .await;
+    let gt_packed: Vec<i32> = gt_img
+        .into_data_async()
+        .await
+        .unwrap()
+        .into_vec::<f32>()
+        .unwrap()
+        .chunks(4)
+        .map(|c| {
+            let [r, g, b, _] = [c[0], c[1], c[2], c[3]].map(|v| (v.clamp(0.0, 1.0) * 255.0) as u8);
+            u32::from_le_bytes([r, g, b, 255]) as i32
+        })
+        .collect();
+
+    let perturbed_cam = Camera {
+        rotation: Quat::from_rotation_y(0.05),
+        ..true_cam
+    };
+    let batch = SceneBatch {
+        img_packed: TensorData::new(gt_packed, [128, 128]),
+        has_alpha: false,
+        alpha_mode: AlphaMode::Transparent,
+        camera: perturbed_cam,
+        view_index: 0,
+        hdr: None,
+        depth: None,
+    };
+
+    let mut config = TrainConfig::default();
+    config.pose_refine = true;
+    config.lr_pose = 1e-3;
+    // Freeze the splats so only the pose can explain the difference.
+    config.lr_mean = 1e-12;
+    config.lr_mean_end = 1e-12;
+    config.lr_coeffs_dc = 0.0;
+    config.lr_opac = 0.0;
+    config.lr_scale = 0.0;
+    config.lr_rotation = 0.0;
+    config.mean_noise_weight = 0.0;
+    config.opac_decay = 0.0;
+    config.background_noise_strength = 0.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::splat(-2.0), Vec3::splat(2.0)),
+    );
+    let mut splats = gt_splats;
+    for _ in 0..300 {
+        let (new_splats, _) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+    }
+    let refined_cam = trainer.refined_camera(0, &perturbed_cam).await;
+
+    // Mean pixel error of some points in the scene compared to the true camera.
+    let reproj_error = |cam: &Camera| {
+        let points = [
+            Vec3::ZERO,
+            Vec3::new(1.5, 1.5, 0.0),
+            Vec3::new(-1.5, 1.5, 2.0),
+            Vec3::new(1.5, -1.5, -2.0),
+        ];
+        let project = |cam: &Camera, p: Vec3| {
+            let local = cam.world_to_local().transform_point3(p);
+            cam.focal(img_size) * local.truncate() / local.z + cam.center(img_size)
+        };
+        points
+            .iter()
+            .map(|&p| project(cam, p).distance(project(&true_cam, p)))
+            .sum::<f32>()
+            / points.len() as f32
+    };
+    let before = reproj_error(&perturbed_cam);
+    let after = reproj_error(&refined_cam);
+    assert!(
+        after < before * 0.5,
+        "Pose refinement didn't recover the rotation: {before} -> {after} pixels"
+    );
+}
+
+// Averaging the loss of two views in one step should move the splats in
+// about the same direction as taking a step on each view.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_batch_matches_sequential_steps() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let first = generate_test_batch((64, 64));
+    let second = SceneBatch {
+        camera: Camera {
+            position: Vec3::new(0.5, 0.2, 3.0),
+            ..first.camera
+        },
+        view_index: 1,
+        ..first.clone()
+    };
+    let mut config = TrainConfig::default();
+    config.background_noise_strength = 0.0;
+    config.mean_noise_weight = 0.0;
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+
+    async fn read_means(splats: &Splats) -> Vec<f32> {
+        splats
+            .means()
+            .into_data_async()
+            .await
+            .unwrap()
+            .to_vec()
+            .unwrap()
+    }
+    let initial = generate_test_splats(&device, 200);
+    let start = read_means(&initial).await;
+
+    let mut trainer = SplatTrainer::new(&config, &device, bounds);
+    let (splats, _) = trainer.step(first.clone(), initial.clone()).await;
+    let (splats, _) = trainer.step(second.clone(), splats).await;
+    let sequential = read_means(&splats).await;
+
+    let mut trainer = SplatTrainer::new(&config, &device, bounds);
+    let (splats, _) = trainer.step_batch(vec![first, second], initial).await;
+    let batched = read_means(&splats).await;
+
+    let delta = |end: &[f32]| -> Vec<f32> { end.iter().zip(&start).map(|(e, s)| e - s).collect() };
+    let (a, b) = (delta(&sequential), delta(&batched));
+    let dot: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let cos = dot / (norm(&a) * norm(&b));
+    assert!(
+        cos > 0.5,
+        "Update directions differ, cosine similarity {cos}"
+    );
+}
+
+// Resuming from a checkpoint halfway should follow the same loss trajectory
+// as training straight through. Noise is disabled as the RNG state isn't part
+// of the checkpoint, with noise only a resume from the same checkpoint is
+// reproducible, see `test_seeded_resume_is_reproducible`.
+#[cfg(not(target_family = "wasm"))]
+#[tokio::test]
+async fn test_checkpoint_resume_matches() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut config = TrainConfig::default();
+    config.background_noise_strength = 0.0;
+    config.mean_noise_weight = 0.0;
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+
+    async fn train(
+        trainer: &mut SplatTrainer,
+        mut splats: Splats,
+        batch: &SceneBatch,
+        steps: u32,
+    ) -> (Splats, f32) {
+        let mut loss = 0.0;
+        for _ in 0..steps {
+            let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+            splats = new_splats;
+            loss = stats.loss.into_scalar_async::<f32>().await.unwrap();
+        }
+        (splats, loss)
+    }
+
+    let mut trainer = SplatTrainer::new(&config, &device, bounds);
+    let splats = generate_test_splats(&device, 100);
+    let (_, straight_loss) = train(&mut trainer, splats, &batch, 100).await;
+
+    let mut trainer = SplatTrainer::new(&config, &device, bounds);
+    let splats = generate_test_splats(&device, 100);
+    let (splats, _) = train(&mut trainer, splats, &batch, 50).await;
+
+    let path = std::env::temp_dir().join("brush_checkpoint_resume_test");
+    trainer.save_checkpoint(&splats, 50, &path).unwrap();
+    drop(trainer);
+
+    let (mut trainer, splats, iter) =
+        SplatTrainer::load_checkpoint(&config, &path, &device).unwrap();
+    assert_eq!(iter, 50);
+    assert_eq!(splats.num_splats(), 100);
+    let (_, resumed_loss) = train(&mut trainer, splats, &batch, 50).await;
+
+    assert!(
+        (straight_loss - resumed_loss).abs() <= straight_loss * 0.01,
+        "straight {straight_loss} vs resumed {resumed_loss}"
+    );
+}
+
+// The RNG isn't checkpointed, but resuming the same checkpoint twice with the
+// same seed takes the same random decisions, noise and growth included.
+#[cfg(not(target_family = "wasm"))]
+#[tokio::test]
+async fn test_seeded_resume_is_reproducible() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut config = TrainConfig::default();
+    config.refine_every = 10;
+    config.growth_grad_threshold = 0.0;
+    assert!(
+        config.background_noise_strength > 0.0 && config.mean_noise_weight > 0.0,
+        "Noise should be on by default"
+    );
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+
+    device.seed(TEST_SEED);
+    let mut trainer = SplatTrainer::new(&config, &device, bounds).with_seed(TEST_SEED);
+    let mut splats = generate_test_splats(&device, 100);
+    for iter in 1..=15 {
+        splats = trainer.step(batch.clone(), splats).await.0;
+        if iter % config.refine_every == 0 {
+            splats = trainer.refine(iter, splats).await.0;
+        }
+    }
+    let path = std::env::temp_dir().join("brush_seeded_resume_test");
+    trainer.save_checkpoint(&splats, 15, &path).unwrap();
+    drop(trainer);
+
+    async fn resume(
+        config: &TrainConfig,
+        device: &Device,
+        path: &std::path::Path,
+        batch: &SceneBatch,
+    ) -> (Vec<u32>, Vec<f32>) {
+        device.seed(TEST_SEED);
+        let (trainer, mut splats, start_iter) =
+            SplatTrainer::load_checkpoint(config, path, device).unwrap();
+        let mut trainer = trainer.with_seed(TEST_SEED.wrapping_add(start_iter as u64));
+        let mut counts = vec![];
+        let mut losses = vec![];
+        for iter in start_iter + 1..=start_iter + 20 {
+            let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+            splats = new_splats;
+            losses.push(stats.loss.into_scalar_async::<f32>().await.unwrap());
+            if iter % config.refine_every == 0 {
+                splats = trainer.refine(iter, splats).await.0;
+            }
+            counts.push(splats.num_splats());
+        }
+        (counts, losses)
+    }
+
+    let (counts_a, losses_a) = resume(&config, &device, &path, &batch).await;
+    let (counts_b, losses_b) = resume(&config, &device, &path, &batch).await;
+
+    assert!(
+        counts_a.last() > counts_a.first(),
+        "splats should have grown after resuming"
+    );
+    assert_eq!(counts_a, counts_b);
+    for (step, (a, b)) in losses_a.iter().zip(&losses_b).enumerate() {
+        assert!((a - b).abs() <= a.abs() * 1e-3, "step {step}: {a} vs {b}");
+    }
+}
+
+// Two runs with the same seed take the same random decisions (background noise,
+// mean noise and which splats grow), so the splat counts match exactly and the
+// losses only differ by GPU float reordering.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_seeded_training_is_reproducible() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut config = TrainConfig::default();
+    config.refine_every = 10;
+    config.growth_grad_threshold = 0.0;
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+
+    async fn train(
+        config: &TrainConfig,
+        device: &Device,
+        bounds: BoundingBox,
+        batch: &SceneBatch,
+    ) -> (Vec<u32>, Vec<f32>) {
+        device.seed(TEST_SEED);
+        let mut trainer = SplatTrainer::new(config, device, bounds).with_seed(TEST_SEED);
+        let mut splats = generate_test_splats(device, 100);
+        let mut counts = vec![];
+        let mut losses = vec![];
+        for iter in 1..=30 {
+            let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+            splats = new_splats;
+            losses.push(stats.loss.into_scalar_async::<f32>().await.unwrap());
+            if iter % config.refine_every == 0 {
+                splats = trainer.refine(iter, splats).await.0;
+            }
+            counts.push(splats.num_splats());
+        }
+        (counts, losses)
+    }
+
+    let (counts_a, losses_a) = train(&config, &device, bounds, &batch).await;
+    let (counts_b, losses_b) = train(&config, &device, bounds, &batch).await;
+
+    assert!(
+        counts_a.last() > counts_a.first(),
+        "splats should have grown"
+    );
+    assert_eq!(counts_a, counts_b);
+    for (iter, (a, b)) in losses_a.iter().zip(&losses_b).enumerate() {
+        assert!((a - b).abs() <= a.abs() * 1e-3, "iter {iter}: {a} vs {b}");
+    }
+}
+
+// Nothing is refined before `refine_start_iter`, or after `refine_stop_iter`.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_refine_schedule() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+    let mut config = TrainConfig::default();
+    config.refine_every = 5;
+    config.growth_grad_threshold = 0.0;
+    config.refine_start_iter = 20;
+    config.refine_stop_iter = Some(25);
+
+    let mut trainer = SplatTrainer::new(&config, &device, bounds).with_seed(TEST_SEED);
+    let mut splats = generate_test_splats(&device, 100);
+    let mut refined_at = vec![];
+    for iter in 1..=40 {
+        splats = trainer.step(batch.clone(), splats).await.0;
+        if config.refine_due(iter) {
+            let (new_splats, stats) = trainer.refine(iter, splats).await;
+            splats = new_splats;
+            refined_at.push(iter);
+            assert!(stats.num_added > 0, "iter {iter} should have grown splats");
+        }
+        if iter < 20 {
+            assert_eq!(
+                splats.num_splats(),
+                100,
+                "refined before the start at {iter}"
+            );
+        }
+    }
+    assert_eq!(refined_at, [20, 25]);
+
+    // By default refines run up to 95% of training, as they always have.
+    let config = TrainConfig::default();
+    let total = config.total_train_iters;
+    let last = (1..=total).rev().find(|&i| config.refine_due(i)).unwrap();
+    assert!(last as f32 <= total as f32 * 0.95);
+    assert!(last + config.refine_every > (total as f32 * 0.95) as u32);
+    assert!(config.refine_due(config.refine_every));
+}
+
+async fn train_and_refine(
+    config: &TrainConfig,
+    device: &Device,
+    steps: u32,
+) -> (Splats, brush_train::msg::RefineStats) {
+    let batch = generate_test_batch((64, 64));
+    let bounds = BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE);
+    let mut trainer = SplatTrainer::new(config, device, bounds).with_seed(TEST_SEED);
+    let mut splats = generate_test_splats(device, 100);
+    for _ in 0..steps {
+        splats = trainer.step(batch.clone(), splats).await.0;
+    }
+    trainer.refine(steps, splats).await
+}
+
+// An opacity reset clamps every splat down to (at most) 0.01 opacity.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_opacity_reset() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let mut config = TrainConfig::default();
+    config.refine_every = 5;
+    config.opac_decay = 0.0;
+
+    async fn max_opacity(splats: &Splats) -> f32 {
+        splats
+            .opacities()
+            .max()
+            .into_scalar_async::<f32>()
+            .await
+            .unwrap()
+    }
+
+    let (splats, stats) = train_and_refine(&config, &device, 5).await;
+    assert!(!stats.opacity_reset);
+    assert!(max_opacity(&splats).await > 0.5);
+
+    config.opacity_reset_every = Some(5);
+    let (splats, stats) = train_and_refine(&config, &device, 5).await;
+    assert!(stats.opacity_reset);
+    let max = max_opacity(&splats).await;
+    assert!(
+        max <= 0.0101,
+        "Opacity after reset should be at most 0.01, got {max}"
+    );
+
+    // Only at the first refine after a multiple of the reset period.
+    config.opacity_reset_every = Some(8);
+    let (_, stats) = train_and_refine(&config, &device, 5).await;
+    assert!(!stats.opacity_reset);
+}
+
+// Splats bigger on screen than `split_at_screen_size` get split, even without
+// any gradient driven growth.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_split_at_screen_size() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let mut config = TrainConfig::default();
+    config.growth_stop_iter = 0;
+
+    config.split_at_screen_size = 0.0;
+    let (_, stats) = train_and_refine(&config, &device, 5).await;
+    assert_eq!(stats.num_split_oversized, 0);
+    assert_eq!(stats.num_split_high_grad, 0);
+
+    config.split_at_screen_size = 0.01;
+    let (splats, stats) = train_and_refine(&config, &device, 5).await;
+    assert!(
+        stats.num_split_oversized > 0,
+        "No oversized splats were split"
+    );
+    assert_eq!(stats.num_split_high_grad, 0);
+    assert_eq!(
+        splats.num_splats(),
+        100 - stats.num_pruned + stats.num_added
+    );
+}
+
+// A single round splat that gets split by growth. Both halves shrink along
+// every axis by `split_scale_factor`, and `split_keep_opacity` leaves their
+// opacity alone.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_split_factors() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+
+    async fn split(config: &TrainConfig, device: &Device) -> (Vec<f32>, Vec<f32>) {
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, -4.0),
+            Quat::IDENTITY,
+            0.8,
+            0.8,
+            glam::vec2(0.5, 0.5),
+            Pinhole,
+        );
+        let splats = Splats::from_raw(
+            vec![0.0, 0.0, 0.0],
+            vec![1.0, 0.0, 0.0, 0.0],
+            vec![-1.0, -1.0, -1.0],
+            vec![0.5, 0.5, 0.5],
+            vec![0.95],
+            SplatRenderMode::Default,
+            device,
+        );
+        let batch = SceneBatch {
+            camera,
+            ..generate_test_batch((64, 64))
+        };
+        let bounds = BoundingBox::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let mut trainer = SplatTrainer::new(config, device, bounds).with_seed(TEST_SEED);
+        let (splats, _) = trainer.step(batch, splats).await;
+        let (splats, stats) = trainer.refine(1, splats).await;
+        assert_eq!(stats.num_split_high_grad, 1);
+
+        let read = async |t: Tensor<1>| {
+            t.into_data_async()
+                .await
+                .unwrap()
+                .into_vec::<f32>()
+                .unwrap()
+        };
+        (
+            read(splats.log_scales().flatten(0, 1)).await,
+            read(splats.opacities()).await,
+        )
+    }
+
+    let mut config = TrainConfig::default();
+    // Only the split changes the splat.
+    config.lr_mean = 0.0;
+    config.lr_mean_end = 0.0;
+    config.lr_coeffs_dc = 0.0;
+    config.lr_opac = 0.0;
+    config.lr_scale = 0.0;
+    config.lr_rotation = 0.0;
+    config.mean_noise_weight = 0.0;
+    config.opac_decay = 0.0;
+    config.split_at_screen_size = 0.0;
+    config.growth_grad_threshold = 0.0;
+    config.growth_select_fraction = 1.0;
+
+    for factor in [config.split_scale_factor, 0.5] {
+        config.split_scale_factor = factor;
+        let (log_scales, opacities) = split(&config, &device).await;
+        assert_eq!(log_scales.len(), 6);
+        for s in log_scales {
+            assert!(
+                (s - (-1.0 + factor.ln())).abs() < 1e-4,
+                "Expected children to shrink by {factor}, got log scale {s}"
+            );
+        }
+        assert!(opacities.iter().all(|&o| o < 0.9), "{opacities:?}");
+    }
+
+    // Factors that would barely shrink the splat are clamped.
+    config.split_scale_factor = 1.0;
+    let (log_scales, _) = split(&config, &device).await;
+    assert!(
+        log_scales
+            .iter()
+            .all(|&s| (s - (-1.0 + 0.9_f32.ln())).abs() < 1e-4)
+    );
+
+    config.split_keep_opacity = true;
+    let (_, opacities) = split(&config, &device).await;
+    assert!(
+        opacities.iter().all(|&o| (o - 0.95).abs() < 1e-4),
+        "{opacities:?}"
+    );
+}
+
+// A step whose ground truth contains NaNs must be skipped rather than poison
+// the splats, and training must carry on normally afterwards.
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn test_nan_batch_is_skipped() {
+    use brush_dataset::hdr::{HdrSample, ToneMapping};
+
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let batch = generate_test_batch((64, 64));
+    let mut bad_batch = batch.clone();
+    bad_batch.view_index = 3;
+    bad_batch.hdr = Some(HdrSample {
+        img: TensorData::new(vec![f32::NAN; 64 * 64 * 4], [64, 64, 4]),
+        tone_mapping: ToneMapping::None,
+    });
+
+    let mut config = TrainConfig::default();
+    config.nan_check_every = 1;
+    config.mean_noise_weight = 0.0;
+    let mut trainer = SplatTrainer::new(
+        &config,
+        &device,
+        BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE),
+    );
+
+    async fn read_means(splats: &Splats) -> Vec<f32> {
+        splats
+            .means()
+            .into_data_async()
+            .await
+            .unwrap()
+            .to_vec()
+            .unwrap()
+    }
+
+    let splats = generate_test_splats(&device, 100);
+    let (splats, stats) = trainer.step(batch.clone(), splats).await;
+    assert!(stats.non_finite_views.is_empty());
+
+    let before = read_means(&splats).await;
+    let (mut splats, stats) = trainer.step(bad_batch, splats).await;
+    assert_eq!(stats.non_finite_views, vec![3]);
+    assert_eq!(read_means(&splats).await, before);
+
+    for _ in 0..5 {
+        let (new_splats, stats) = trainer.step(batch.clone(), splats).await;
+        splats = new_splats;
+        assert!(stats.non_finite_views.is_empty());
+        let loss = stats.loss.into_scalar_async::<f32>().await.unwrap();
+        assert!(loss.is_finite());
+    }
+    assert!(read_means(&splats).await.iter().all(|m| m.is_finite()));
+}
+
 // Training with a camera pointing away from every splat — num_visible == 0
 // every step. The training loop must not crash on this; all gradients should
 // be zero (or at least finite) and the optimizer step should be a no-op.
@@ -253,6 +1603,9 @@ async fn train_with_zero_visible_does_not_crash() {
         has_alpha: false,
         alpha_mode: AlphaMode::Transparent,
         camera,
+        view_index: 0,
+        hdr: None,
+        depth: None,
     };
 
     let config = TrainConfig::default();
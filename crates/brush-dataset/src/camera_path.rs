@@ -0,0 +1,324 @@
+//! Keyframed camera paths, eg. to render a turntable or fly-through video of a scene.
+//!
+//! Two JSON formats are accepted:
+//! - Brush's own, a list of keyframes in Brush's camera convention (+Z forward, +Y down):
+//!   `{"keyframes": [{"time": 0.0, "position": [x, y, z], "rotation": [x, y, z, w], "fov": 50.0}]}`
+//! - The `camera_path.json` nerfstudio's viewer exports. Its `camera_path` entries are used as
+//!   keyframes, `1 / fps` seconds apart.
+//!
+//! In both, `fov` is the vertical field of view in degrees.
+
+use brush_render::camera::{Camera, focal_to_fov, fov_to_focal};
+use brush_render::kernels::camera_model::CameraModel;
+use glam::{Quat, UVec2, Vec3};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::formats::opengl_c2w_to_pose;
+
+#[derive(Debug, Error)]
+pub enum CameraPathError {
+    #[error("Error decoding camera path JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Camera path has no keyframes")]
+    Empty,
+
+    #[error("Keyframe times must increase, but keyframe {index} at {time}s comes after {prev}s")]
+    TimeNotIncreasing { index: usize, time: f32, prev: f32 },
+
+    #[error(
+        "Camera path entry {index} has a {len}-element camera_to_world, expected a 4x4 (16 elements)"
+    )]
+    InvalidMatrix { index: usize, len: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Time of the keyframe in seconds.
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+}
+
+#[derive(Deserialize)]
+struct BrushPathFile {
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Deserialize)]
+struct NerfstudioPathFile {
+    camera_path: Vec<NerfstudioPathFrame>,
+    fps: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct NerfstudioPathFrame {
+    /// Row-major OpenGL camera-to-world matrix.
+    camera_to_world: Vec<f32>,
+    fov: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+}
+
+/// Frame rate nerfstudio paths are assumed to be at when they don't say.
+const DEFAULT_NERFSTUDIO_FPS: f32 = 30.0;
+
+impl CameraPath {
+    /// A path through `keyframes`, which have to be sorted by strictly increasing time.
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Result<Self, CameraPathError> {
+        if keyframes.is_empty() {
+            return Err(CameraPathError::Empty);
+        }
+        for (index, pair) in keyframes.windows(2).enumerate() {
+            if pair[1].time <= pair[0].time {
+                return Err(CameraPathError::TimeNotIncreasing {
+                    index: index + 1,
+                    time: pair[1].time,
+                    prev: pair[0].time,
+                });
+            }
+        }
+        for keyframe in &mut keyframes {
+            keyframe.rotation = keyframe.rotation.normalize();
+        }
+        Ok(Self { keyframes })
+    }
+
+    /// Parse a camera path in either Brush's or nerfstudio's format, see the module docs.
+    pub fn from_json(json: &str) -> Result<Self, CameraPathError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        if value.get("camera_path").is_none() {
+            let file: BrushPathFile = serde_json::from_value(value)?;
+            return Self::new(file.keyframes);
+        }
+
+        let file: NerfstudioPathFile = serde_json::from_value(value)?;
+        let fps = file.fps.unwrap_or(DEFAULT_NERFSTUDIO_FPS);
+        let keyframes = file
+            .camera_path
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                if frame.camera_to_world.len() != 16 {
+                    return Err(CameraPathError::InvalidMatrix {
+                        index,
+                        len: frame.camera_to_world.len(),
+                    });
+                }
+                let c2w = glam::Mat4::from_cols_slice(&frame.camera_to_world).transpose();
+                let (position, rotation) = opengl_c2w_to_pose(c2w);
+                Ok(Keyframe {
+                    time: index as f32 / fps,
+                    position,
+                    rotation,
+                    fov: frame.fov,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(keyframes)
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn start_time(&self) -> f32 {
+        self.keyframes[0].time
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    /// The keyframe at `time`, clamped to the ends of the path. The position follows a
+    /// Catmull-Rom spline through the keyframes, the rotation is slerped and the fov
+    /// interpolated linearly.
+    pub fn sample(&self, time: f32) -> Keyframe {
+        let kf = &self.keyframes;
+        if kf.len() == 1 {
+            return kf[0];
+        }
+        let time = time.clamp(self.start_time(), self.end_time());
+        // Index of the segment `time` is in.
+        let i = kf
+            .partition_point(|k| k.time <= time)
+            .saturating_sub(1)
+            .min(kf.len() - 2);
+        let (k1, k2) = (kf[i], kf[i + 1]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        // The end points are repeated to get a tangent at the ends of the path.
+        let p0 = kf[i.saturating_sub(1)].position;
+        let p3 = kf[(i + 2).min(kf.len() - 1)].position;
+        Keyframe {
+            time,
+            position: catmull_rom(p0, k1.position, k2.position, p3, t),
+            rotation: k1.rotation.slerp(k2.rotation, t),
+            fov: k1.fov + (k2.fov - k1.fov) * t,
+        }
+    }
+
+    /// Times of the frames of a video of the path at `fps` frames per second, from the
+    /// first to the last keyframe.
+    pub fn frame_times(&self, fps: f32) -> Vec<f32> {
+        let duration = self.end_time() - self.start_time();
+        let count = (duration * fps).round() as usize + 1;
+        (0..count)
+            .map(|i| (self.start_time() + i as f32 / fps).min(self.end_time()))
+            .collect()
+    }
+
+    /// The camera at `time` for an image of `img_size`. The horizontal field of view
+    /// follows from the aspect ratio of the image.
+    pub fn camera(&self, time: f32, img_size: UVec2) -> Camera {
+        let keyframe = self.sample(time);
+        let fov_y = f64::from(keyframe.fov).to_radians();
+        let focal = fov_to_focal(fov_y, img_size.y, &CameraModel::Pinhole);
+        let fov_x = focal_to_fov(focal, img_size.x, &CameraModel::Pinhole);
+        Camera::new(
+            keyframe.position,
+            keyframe.rotation,
+            fov_x,
+            fov_y,
+            glam::vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        )
+    }
+}
+
+/// Uniform Catmull-Rom spline between `p1` (at `t = 0`) and `p2` (at `t = 1`).
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn keyframe(time: f32, position: Vec3, yaw: f32, fov: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position,
+            rotation: Quat::from_rotation_y(yaw),
+            fov,
+        }
+    }
+
+    fn test_path() -> CameraPath {
+        CameraPath::new(vec![
+            keyframe(0.0, Vec3::new(0.0, 0.0, -3.0), 0.0, 40.0),
+            keyframe(1.0, Vec3::new(3.0, 0.0, 0.0), -1.5, 50.0),
+            keyframe(3.0, Vec3::new(0.0, 1.0, 3.0), -3.0, 60.0),
+        ])
+        .unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_sample_hits_keyframes() {
+        let path = test_path();
+        for k in path.keyframes() {
+            let sample = path.sample(k.time);
+            assert!(sample.position.abs_diff_eq(k.position, 1e-5));
+            assert!(sample.rotation.abs_diff_eq(k.rotation, 1e-5));
+            assert!((sample.fov - k.fov).abs() < 1e-5);
+        }
+
+        // Outside of the path the ends are held.
+        let [first, _, last] = path.keyframes() else {
+            unreachable!()
+        };
+        assert!(path.sample(-1.0).position.abs_diff_eq(first.position, 1e-5));
+        assert!(path.sample(10.0).position.abs_diff_eq(last.position, 1e-5));
+
+        // In between, things are interpolated.
+        let mid = path.sample(2.0);
+        assert!((mid.fov - 55.0).abs() < 1e-5);
+        let yaw = mid.rotation.to_euler(glam::EulerRot::YXZ).0;
+        assert!((yaw + 2.25).abs() < 1e-4);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_time_must_increase() {
+        let frames = |times: &[f32]| {
+            times
+                .iter()
+                .map(|&t| keyframe(t, Vec3::ZERO, 0.0, 50.0))
+                .collect::<Vec<_>>()
+        };
+        assert!(CameraPath::new(frames(&[0.0, 1.0, 2.0])).is_ok());
+        assert!(matches!(
+            CameraPath::new(frames(&[0.0, 2.0, 1.0])),
+            Err(CameraPathError::TimeNotIncreasing { index: 2, .. })
+        ));
+        assert!(matches!(
+            CameraPath::new(frames(&[0.0, 0.0])),
+            Err(CameraPathError::TimeNotIncreasing { index: 1, .. })
+        ));
+        assert!(matches!(
+            CameraPath::new(vec![]),
+            Err(CameraPathError::Empty)
+        ));
+
+        let times = test_path().frame_times(10.0);
+        assert_eq!(times.len(), 31);
+        assert_eq!(times[0], 0.0);
+        assert_eq!(times[30], 3.0);
+        assert!(times.windows(2).all(|w| w[1] > w[0]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_parse_formats() {
+        let brush = r#"{"keyframes": [
+            {"time": 0.0, "position": [1.0, 2.0, 3.0], "rotation": [0.0, 0.0, 0.0, 1.0], "fov": 45.0},
+            {"time": 2.0, "position": [1.0, 2.0, 5.0], "rotation": [0.0, 0.0, 0.0, 1.0], "fov": 45.0}
+        ]}"#;
+        let path = CameraPath::from_json(brush).unwrap();
+        assert_eq!(path.keyframes().len(), 2);
+        assert_eq!(path.end_time(), 2.0);
+        assert!(
+            path.sample(1.0)
+                .position
+                .abs_diff_eq(Vec3::new(1.0, 2.0, 4.0), 1e-5)
+        );
+
+        // A nerfstudio camera looking down its -Z axis from (1, 2, 3).
+        let nerfstudio = r#"{
+            "camera_type": "perspective", "render_height": 480, "render_width": 640, "fps": 24,
+            "camera_path": [
+                {"camera_to_world": [1, 0, 0, 1, 0, 1, 0, 2, 0, 0, 1, 3, 0, 0, 0, 1], "fov": 50, "aspect": 1.33},
+                {"camera_to_world": [1, 0, 0, 1, 0, 1, 0, 2, 0, 0, 1, 4, 0, 0, 0, 1], "fov": 60, "aspect": 1.33}
+            ]
+        }"#;
+        let path = CameraPath::from_json(nerfstudio).unwrap();
+        assert_eq!(path.end_time(), 1.0 / 24.0);
+        let first = path.keyframes()[0];
+        assert_eq!(first.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(first.fov, 50.0);
+        // Brush cameras look down +Z, so the forward axis flips.
+        assert!((first.rotation * Vec3::Z).abs_diff_eq(-Vec3::Z, 1e-5));
+
+        let camera = path.camera(0.0, glam::uvec2(640, 480));
+        assert!((camera.fov_y - 50f64.to_radians()).abs() < 1e-6);
+        assert!(camera.fov_x > camera.fov_y);
+
+        assert!(matches!(
+            CameraPath::from_json(r#"{"camera_path": [{"camera_to_world": [1, 0], "fov": 50}]}"#),
+            Err(CameraPathError::InvalidMatrix { index: 0, len: 2 })
+        ));
+    }
+}
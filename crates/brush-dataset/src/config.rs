@@ -1,6 +1,9 @@
+use crate::hdr::ToneMapping;
 use brush_render::AlphaMode;
 use clap::Args;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 /// Default Cache budget for packed scene batches. 6 GB on native; less on
 /// wasm since the whole heap is bounded by browser limits.
@@ -34,6 +37,11 @@ pub struct LoadDatasetConfig {
     /// Create an eval dataset by selecting every nth image
     #[arg(long, help_heading = "Dataset Options")]
     pub eval_split_every: Option<usize>,
+    /// How to pick the eval images when splitting with eval-split-every, which then only sets
+    /// how many there are. One of every-n, farthest-point (spread out over the camera positions)
+    /// or random[:seed].
+    #[arg(long, help_heading = "Dataset Options", default_value = "every-n")]
+    pub eval_split_strategy: EvalSplitStrategy,
     /// Load only every nth frame
     #[arg(long, help_heading = "Dataset Options")]
     pub subsample_frames: Option<u32>,
@@ -43,11 +51,92 @@ pub struct LoadDatasetConfig {
     /// Whether to interpret an alpha channel (or masks) as transparency or masking.
     #[arg(long, help_heading = "Dataset Options")]
     pub alpha_mode: Option<AlphaMode>,
+    /// Use the alpha channel of the images as a mask, for datasets without a masks folder.
+    /// Pixels with zero alpha are then ignored, instead of trained to be transparent.
+    /// Shorthand for --alpha-mode masked.
+    #[arg(long, help_heading = "Dataset Options", conflicts_with = "alpha_mode")]
+    #[serde(default)]
+    pub alpha_as_mask: bool,
+    /// Tone mapping applied to HDR (EXR, 16-bit) images and the render before computing the loss.
+    #[arg(long, help_heading = "Dataset Options", default_value = "none")]
+    pub tone_mapping: ToneMapping,
     /// Max size of the cache for frames of the dataset, larger values usually improve performance for large datasets at the cost of more memory usage, can be e.g. 6G, 6000M, 6000MiB, 6000MB
     #[arg(long, help_heading = "Dataset Options", default_value = DEFAULT_MAX_SCENE_BATCH_CACHE_SIZE, value_parser = parse_size)]
     pub max_scene_batch_cache_size: u64,
 }
 
+impl LoadDatasetConfig {
+    /// The alpha mode set by the user, if any. Formats fall back to their own
+    /// default when this is `None`.
+    pub fn alpha_mode_override(&self) -> Option<AlphaMode> {
+        if self.alpha_as_mask {
+            Some(AlphaMode::Masked)
+        } else {
+            self.alpha_mode
+        }
+    }
+}
+
+/// How views are picked for the eval split, see [`LoadDatasetConfig::eval_split_strategy`].
+///
+/// (De)serialized as its CLI string so it round trips through args files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum EvalSplitStrategy {
+    /// Every nth view in dataset order. Captures from a video put all of these close together.
+    #[default]
+    EveryN,
+    /// Greedily pick the views furthest away from the views picked so far.
+    FarthestPoint,
+    /// A random selection of views, the same for the same seed.
+    Random { seed: u64 },
+}
+
+impl fmt::Display for EvalSplitStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EveryN => write!(f, "every-n"),
+            Self::FarthestPoint => write!(f, "farthest-point"),
+            Self::Random { seed } => write!(f, "random:{seed}"),
+        }
+    }
+}
+
+impl FromStr for EvalSplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "every-n" => Ok(Self::EveryN),
+            "farthest-point" => Ok(Self::FarthestPoint),
+            "random" => Ok(Self::Random { seed: 0 }),
+            _ => s
+                .strip_prefix("random:")
+                .and_then(|seed| seed.parse().ok())
+                .map(|seed| Self::Random { seed })
+                .ok_or_else(|| {
+                    format!(
+                        "invalid eval split strategy {s:?}, expected every-n, farthest-point or random[:seed]"
+                    )
+                }),
+        }
+    }
+}
+
+impl From<EvalSplitStrategy> for String {
+    fn from(strategy: EvalSplitStrategy) -> Self {
+        strategy.to_string()
+    }
+}
+
+impl TryFrom<String> for EvalSplitStrategy {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 fn parse_size(s: &str) -> Result<u64, parse_size::Error> {
     parse_size::parse_size(s)
 }
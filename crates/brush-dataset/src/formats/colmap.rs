@@ -8,7 +8,7 @@ use super::{DatasetLoadResult, FormatError};
 use crate::{
     Dataset,
     config::LoadDatasetConfig,
-    formats::{find_image_by_name, find_mask_path, split_eval_every},
+    formats::{find_image_by_name, find_mask_path, missing_image_warning, split_eval},
     scene::{LoadImage, SceneView},
 };
 use brush_render::kernels::camera_model::CameraModel;
@@ -22,7 +22,7 @@ use brush_render::{
     camera::{self, Camera},
     sh::rgb_to_sh,
 };
-use brush_serde::{ParseMetadata, SplatData, SplatMessage};
+use brush_serde::{AppearanceMetadata, ParseMetadata, SplatData, SplatMessage};
 use brush_vfs::BrushVfs;
 use colmap_reader::{ColmapCamera, ColmapCameraModel};
 
@@ -35,7 +35,6 @@ async fn select_colmap_model(vfs: &BrushVfs) -> Option<PathBuf> {
     let candidates: Vec<PathBuf> = vfs
         .files_ending_in("cameras.bin")
         .chain(vfs.files_ending_in("cameras.txt"))
-        .map(Path::to_path_buf)
         .collect();
 
     if candidates.len() <= 1 {
@@ -140,6 +139,7 @@ async fn load_dataset_inner(
         .parent()
         .expect("colmap cameras file must have a parent")
         .to_path_buf();
+    let model_dir = points_dir.clone();
 
     // One actor for both halves of the colmap load — the camera/image
     // parse and the points3d parse run concurrently on the same thread
@@ -186,12 +186,13 @@ async fn load_dataset_inner(
             let center_uv =
                 center / glam::vec2(colmap_camera.width as f32, colmap_camera.height as f32);
 
-            let Some(path) = find_image_by_name(&vfs, &img_info.name) else {
-                warnings.push(format!("Skipped '{}': image file not found", img_info.name));
+            let Some(path) = find_colmap_image(&vfs, &model_dir, &img_info.name, &mut warnings)
+            else {
+                warnings.push(missing_image_warning(&img_info.name));
                 continue;
             };
 
-            let mask_path = find_mask_path(&vfs, path);
+            let mask_path = find_mask_path(&vfs, &path);
 
             // Convert w2c to c2w.
             let world_to_cam =
@@ -211,16 +212,20 @@ async fn load_dataset_inner(
 
             let image = LoadImage::new(
                 vfs.clone(),
-                path.to_path_buf(),
-                mask_path.map(|p| p.to_path_buf()),
+                path,
+                mask_path,
                 load_args.max_resolution,
-                load_args.alpha_mode,
+                load_args.alpha_mode_override(),
             );
 
-            views.push(SceneView { camera, image });
+            views.push(SceneView {
+                camera,
+                image,
+                intrinsics_size: Some((colmap_camera.width as u32, colmap_camera.height as u32)),
+            });
         }
 
-        let (train_views, eval_views) = split_eval_every(views, load_args.eval_split_every);
+        let (train_views, eval_views) = split_eval(views, &load_args);
 
         Result::<_, FormatError>::Ok((Dataset::from_views(train_views, eval_views), warnings))
     });
@@ -238,7 +243,7 @@ async fn load_dataset_inner(
         );
         // At this point the VFS has said this file exists so just unwrap.
         let mut points_file = vfs_init
-            .reader_at_path(points_path)
+            .reader_at_path(&points_path)
             .await
             .expect("unreachable");
 
@@ -285,6 +290,9 @@ async fn load_dataset_inner(
                 render_mode: None,
                 total_splats: n_splats as u32,
                 progress: 1.0,
+                appearance: AppearanceMetadata::default(),
+                sh_degree: 0,
+                rest_coeff_count: 0,
             },
             data,
         })
@@ -301,6 +309,33 @@ async fn load_dataset_inner(
     })
 }
 
+/// Find the image file for a COLMAP image `name`. Normally that's a path
+/// relative to the image folder, but some exports store it relative to the
+/// model instead, eg. `../images/frame001.jpg` from `sparse/0`. Tries `name`
+/// resolved against the model's parent directory, then any file whose path ends
+/// in `name`, and finally (with a warning) any file with the same file name.
+fn find_colmap_image(
+    vfs: &BrushVfs,
+    model_dir: &Path,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> Option<PathBuf> {
+    let model_relative = model_dir
+        .parent()
+        .and_then(|base| vfs.find_path(&base.join(name)));
+    if let Some(path) = model_relative.or_else(|| find_image_by_name(vfs, name)) {
+        return Some(path);
+    }
+
+    let file_name = Path::new(name).file_name()?.to_str()?;
+    let path = find_image_by_name(vfs, file_name)?;
+    warnings.push(format!(
+        "Image '{name}' not found at its path, using '{}' instead",
+        path.display()
+    ));
+    Some(path)
+}
+
 fn build_camera_model(colmap_camera: &ColmapCamera) -> CameraModel {
     let p = &colmap_camera.params;
     // Param layouts follow COLMAP's `src/colmap/sensor/models.h`. Indices
@@ -381,3 +416,96 @@ fn build_camera_model(colmap_camera: &ColmapCamera) -> CameraModel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EvalSplitStrategy;
+    use crate::hdr::ToneMapping;
+    use std::io::Cursor;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn load_config() -> LoadDatasetConfig {
+        LoadDatasetConfig {
+            max_frames: None,
+            max_resolution: 1920,
+            eval_split_every: None,
+            eval_split_strategy: EvalSplitStrategy::EveryN,
+            subsample_frames: None,
+            subsample_points: None,
+            alpha_mode: None,
+            alpha_as_mask: false,
+            tone_mapping: ToneMapping::None,
+            max_scene_batch_cache_size: 0,
+        }
+    }
+
+    fn png() -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        image::RgbImage::new(8, 4)
+            .write_to(&mut data, image::ImageFormat::Png)
+            .expect("Failed to encode png");
+        data.into_inner()
+    }
+
+    /// A text COLMAP model in `sparse/0` with one image line per name.
+    fn colmap_vfs(names: &[&str], images: &[&str]) -> Arc<BrushVfs> {
+        let images_txt: String = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("{} 1 0 0 0 0 0 {i} 1 {name}\n\n", i + 1))
+            .collect();
+        let mut files = vec![
+            (
+                PathBuf::from("sparse/0/cameras.txt"),
+                b"1 PINHOLE 8 4 10 10 4 2\n".to_vec(),
+            ),
+            (
+                PathBuf::from("sparse/0/images.txt"),
+                images_txt.into_bytes(),
+            ),
+        ];
+        files.extend(images.iter().map(|p| (PathBuf::from(p), png())));
+        Arc::new(BrushVfs::create_test_vfs_with_data(files))
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_model_relative_image_paths() {
+        let vfs = colmap_vfs(
+            &["../images/a.png", "../images/b.png", "../../images/c.png"],
+            &["images/a.png", "images/b.png", "images/c.png"],
+        );
+        let result = load_dataset(vfs, &load_config()).await.unwrap().unwrap();
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+        let mut paths: Vec<_> = result
+            .dataset
+            .train
+            .views
+            .iter()
+            .map(|v| v.image.path().to_path_buf())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            ["images/a.png", "images/b.png", "images/c.png"].map(PathBuf::from)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_image_found_by_file_name() {
+        // The referenced folder doesn't exist, but a file with the same name does.
+        let vfs = colmap_vfs(&["../frames/a.png", "missing.png"], &["images/a.png"]);
+        let result = load_dataset(vfs, &load_config()).await.unwrap().unwrap();
+
+        let views = &result.dataset.train.views;
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].image.path(), Path::new("images/a.png"));
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings[0].contains("../frames/a.png"));
+        assert_eq!(result.warnings[1], missing_image_warning("missing.png"));
+    }
+}
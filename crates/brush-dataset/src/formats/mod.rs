@@ -1,10 +1,18 @@
-use crate::{Dataset, config::LoadDatasetConfig, scene::SceneView};
-use brush_serde::{DeserializeError, SplatMessage, load_splat_from_ply};
+use crate::{
+    Dataset,
+    config::{EvalSplitStrategy, LoadDatasetConfig},
+    scene::SceneView,
+};
+use brush_serde::{DeserializeError, ImportFormat, SplatMessage, load_splat};
 
 use brush_vfs::BrushVfs;
 use image::ImageError;
 use itertools::{Either, Itertools};
-use std::{path::Path, sync::Arc};
+use rand::{SeedableRng, seq::SliceRandom};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 pub mod colmap;
 pub mod nerfstudio;
@@ -84,22 +92,25 @@ pub async fn load_dataset(
         .into());
     }
 
-    // If there's an initial ply file, override the init stream with that.
-    let mut ply_paths: Vec<_> = vfs.files_with_extension("ply").collect();
-    ply_paths.sort();
+    // If there's an initial splat file, override the init stream with that.
+    let mut splat_paths: Vec<_> = vfs
+        .file_paths()
+        .filter_map(|p| ImportFormat::from_path(&p).map(|f| (p, f)))
+        .collect();
+    splat_paths.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-    let main_ply = ply_paths
+    let main_splat = splat_paths
         .iter()
-        .find(|p| p.file_name().is_some_and(|n| n == "init.ply"))
-        .or_else(|| ply_paths.last());
+        .find(|(p, _)| p.file_stem().is_some_and(|n| n == "init"))
+        .or_else(|| splat_paths.last());
 
-    let init_splat = if let Some(main_ply) = main_ply {
-        log::info!("Using ply {main_ply:?} as initial point cloud.");
+    let init_splat = if let Some((main_splat, format)) = main_splat {
+        log::info!("Using {main_splat:?} as initial point cloud.");
         let reader = vfs
-            .reader_at_path(main_ply)
+            .reader_at_path(main_splat)
             .await
             .map_err(DeserializeError)?;
-        Some(load_splat_from_ply(reader, load_args.subsample_points).await?)
+        Some(load_splat(reader, *format, load_args.subsample_points).await?)
     } else {
         result.init_splat
     };
@@ -114,32 +125,49 @@ pub async fn load_dataset(
 /// Resolve a bare image name (as stored by colmap / `RealityCapture`, which only
 /// record a filename) to a path in the VFS by brute-force suffix search. Masks
 /// are skipped so an image never resolves to its own mask.
-fn find_image_by_name<'a>(vfs: &'a BrushVfs, name: &str) -> Option<&'a Path> {
+fn find_image_by_name(vfs: &BrushVfs, name: &str) -> Option<PathBuf> {
     vfs.files_ending_in(name)
         .filter(|p| !p.iter().any(|f| f == "masks"))
         .min()
 }
 
+/// Warning for a view that was skipped because its image isn't in the VFS.
+pub(crate) fn missing_image_warning(name: &str) -> String {
+    format!("Skipped '{name}': image file not found")
+}
+
+/// Inverse of [`missing_image_warning`], so dataset validation can list the
+/// missing images separately from other loader warnings.
+pub(crate) fn parse_missing_image_warning(warning: &str) -> Option<&str> {
+    warning
+        .strip_prefix("Skipped '")?
+        .strip_suffix("': image file not found")
+}
+
 /// Convert an OpenGL/Blender camera-to-world matrix (the nerfstudio
 /// `transform_matrix` convention: +X right, +Y up, +Z back) into brush's
 /// camera pose (+X right, +Y down, +Z forward).
-fn opengl_c2w_to_pose(mut c2w: glam::Mat4) -> (glam::Vec3, glam::Quat) {
+pub(crate) fn opengl_c2w_to_pose(mut c2w: glam::Mat4) -> (glam::Vec3, glam::Quat) {
     c2w.y_axis *= -1.0;
     c2w.z_axis *= -1.0;
     let (_, rotation, translation) = c2w.to_scale_rotation_translation();
     (translation, rotation)
 }
 
-/// Split views into (train, eval) by selecting every `eval_split_every`-th view
-/// for eval. With `None`, every view is a train view.
-fn split_eval_every(
+/// Split views into (train, eval). `eval_split_every` sets how many views go to
+/// eval, one in every n, and `eval_split_strategy` which ones. Without
+/// `eval_split_every`, every view is a train view.
+fn split_eval(
     views: Vec<SceneView>,
-    eval_split_every: Option<usize>,
+    load_args: &LoadDatasetConfig,
 ) -> (Vec<SceneView>, Vec<SceneView>) {
-    views.into_iter().enumerate().partition_map(|(i, v)| {
-        if let Some(split) = eval_split_every
-            && i % split == 0
-        {
+    let Some(every) = load_args.eval_split_every else {
+        return (views, vec![]);
+    };
+    let positions: Vec<_> = views.iter().map(|v| v.camera.position).collect();
+    let is_eval = eval_view_mask(&positions, every, load_args.eval_split_strategy);
+    views.into_iter().zip(is_eval).partition_map(|(v, eval)| {
+        if eval {
             Either::Right(v)
         } else {
             Either::Left(v)
@@ -147,7 +175,80 @@ fn split_eval_every(
     })
 }
 
-fn find_mask_path<'a>(vfs: &'a BrushVfs, path: &'a Path) -> Option<&'a Path> {
+/// Which of the views at `positions` are eval views.
+fn eval_view_mask(
+    positions: &[glam::Vec3],
+    every: usize,
+    strategy: EvalSplitStrategy,
+) -> Vec<bool> {
+    let num_views = positions.len();
+    // Pick as many views as every nth view would, which includes the first one.
+    let count = num_views.div_ceil(every);
+
+    let picked = match strategy {
+        EvalSplitStrategy::EveryN => return (0..num_views).map(|i| i % every == 0).collect(),
+        EvalSplitStrategy::FarthestPoint => farthest_point_indices(positions, count),
+        EvalSplitStrategy::Random { seed } => {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut indices: Vec<usize> = (0..num_views).collect();
+            indices.shuffle(&mut rng);
+            indices.truncate(count);
+            indices
+        }
+    };
+
+    let mut mask = vec![false; num_views];
+    for i in picked {
+        mask[i] = true;
+    }
+    mask
+}
+
+/// Greedy farthest point sampling of `count` positions. Starts at the position
+/// furthest from the centroid, then keeps adding the position furthest from
+/// all positions picked so far.
+fn farthest_point_indices(positions: &[glam::Vec3], count: usize) -> Vec<usize> {
+    let count = count.min(positions.len());
+    if count == 0 {
+        return vec![];
+    }
+
+    let furthest = |dists: &[f32]| {
+        dists
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+            .expect("Need at least one position")
+    };
+
+    let centroid = positions.iter().sum::<glam::Vec3>() / positions.len() as f32;
+    let to_centroid: Vec<f32> = positions
+        .iter()
+        .map(|p| p.distance_squared(centroid))
+        .collect();
+    let first = furthest(&to_centroid);
+
+    // Distance from each position to the closest picked one. Picked positions
+    // are set to -inf so duplicate positions can't get them picked twice.
+    let mut min_dists = vec![f32::INFINITY; positions.len()];
+    let mut picked = vec![first];
+    let mut last = first;
+
+    while picked.len() < count {
+        for (dist, p) in min_dists.iter_mut().zip(positions) {
+            *dist = dist.min(p.distance_squared(positions[last]));
+        }
+        for &i in &picked {
+            min_dists[i] = f32::NEG_INFINITY;
+        }
+        last = furthest(&min_dists);
+        picked.push(last);
+    }
+    picked
+}
+
+fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
     let search_name = path.file_name().expect("File must have a name");
     let search_stem = path.file_stem().expect("File must have a name");
     let mut search_mask = search_stem.to_owned();
@@ -194,6 +295,60 @@ mod tests {
     use std::path::{Path, PathBuf};
     use wasm_bindgen_test::wasm_bindgen_test;
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_eval_split_every_n() {
+        let positions = vec![glam::Vec3::ZERO; 10];
+        let mask = eval_view_mask(&positions, 4, EvalSplitStrategy::EveryN);
+        let eval: Vec<_> = (0..10).filter(|&i| mask[i]).collect();
+        assert_eq!(eval, vec![0, 4, 8]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_eval_split_farthest_point() {
+        // Cameras on a line, as in a walk along a wall.
+        let line: Vec<_> = (0..6).map(|i| glam::vec3(i as f32, 1.0, 0.0)).collect();
+        let mut picked = farthest_point_indices(&line, 2);
+        picked.sort_unstable();
+        assert_eq!(picked, vec![0, 5]);
+
+        // The next pick fills the largest gap.
+        let picked = farthest_point_indices(&line, 3);
+        assert!(picked[2] == 2 || picked[2] == 3);
+
+        // Duplicate positions still give distinct views.
+        let same = vec![glam::Vec3::ONE; 4];
+        let mut picked = farthest_point_indices(&same, 4);
+        picked.sort_unstable();
+        assert_eq!(picked, vec![0, 1, 2, 3]);
+
+        // Same number of eval views as every nth view.
+        let mask = eval_view_mask(&line, 3, EvalSplitStrategy::FarthestPoint);
+        assert_eq!(mask, vec![true, false, false, false, false, true]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_eval_split_random() {
+        let positions = vec![glam::Vec3::ZERO; 20];
+        let split = |seed| eval_view_mask(&positions, 4, EvalSplitStrategy::Random { seed });
+        assert_eq!(split(3).iter().filter(|&&e| e).count(), 5);
+        assert_eq!(split(3), split(3));
+        assert_ne!(split(3), split(4));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_eval_split_strategy_parse() {
+        for strategy in [
+            EvalSplitStrategy::EveryN,
+            EvalSplitStrategy::FarthestPoint,
+            EvalSplitStrategy::Random { seed: 42 },
+        ] {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert_eq!("random".parse(), Ok(EvalSplitStrategy::Random { seed: 0 }));
+        assert!("random:abc".parse::<EvalSplitStrategy>().is_err());
+        assert!("nth".parse::<EvalSplitStrategy>().is_err());
+    }
+
     #[wasm_bindgen_test(unsupported = test)]
     fn test_find_mask() {
         // Basic matching with same extension
@@ -203,7 +358,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/img.png")),
-            Some(Path::new("masks/img.png"))
+            Some(PathBuf::from("masks/img.png"))
         );
         // Different extensions are ok.
         let vfs = BrushVfs::create_test_vfs(vec![
@@ -212,7 +367,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/img.jpeg")),
-            Some(Path::new("masks/img.png"))
+            Some(PathBuf::from("masks/img.png"))
         );
     }
 
@@ -225,7 +380,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/foo.png")),
-            Some(Path::new("masks/foo.png.mask"))
+            Some(PathBuf::from("masks/foo.png.mask"))
         );
 
         // Test img.mask.png format
@@ -235,7 +390,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/bar.jpeg")),
-            Some(Path::new("masks/bar.mask.png"))
+            Some(PathBuf::from("masks/bar.mask.png"))
         );
     }
 
@@ -248,7 +403,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/foo/bar/img.png")),
-            Some(Path::new("masks/foo/bar/img.png"))
+            Some(PathBuf::from("masks/foo/bar/img.png"))
         );
         // Should not match wrong subpath
         let vfs = BrushVfs::create_test_vfs(vec![
@@ -266,7 +421,7 @@ mod tests {
         ]);
         assert_eq!(
             find_mask_path(&vfs, Path::new("images/IMG.PNG")),
-            Some(Path::new("masks/img.png"))
+            Some(PathBuf::from("masks/img.png"))
         );
     }
 }
@@ -1,9 +1,13 @@
-use super::{DatasetLoadResult, FormatError, find_mask_path, opengl_c2w_to_pose};
+use super::{
+    DatasetLoadResult, FormatError, find_mask_path, missing_image_warning, opengl_c2w_to_pose,
+    split_eval,
+};
 use crate::{
     Dataset,
     config::LoadDatasetConfig,
     scene::{LoadImage, SceneView},
 };
+use brush_render::AlphaMode;
 use brush_render::camera::fov_to_focal;
 use brush_render::camera::{Camera, focal_to_fov};
 use brush_render::kernels::camera_model::CameraModel;
@@ -57,6 +61,10 @@ struct JsonScene {
     /// Second tangential distortion parameter used by `OPENCV`
     p2: Option<f64>,
 
+    /// Whether the alpha channel of the images masks out pixels, rather than
+    /// marking them transparent.
+    mask_from_alpha: Option<bool>,
+
     frames: Vec<FrameData>,
 }
 
@@ -142,6 +150,7 @@ async fn read_transforms_file(
     transforms_path: &Path,
     vfs: Arc<BrushVfs>,
     load_args: &LoadDatasetConfig,
+    alpha_mode: Option<AlphaMode>,
     warnings: &mut Vec<String>,
 ) -> Result<Vec<SceneView>, FormatError> {
     let mut results = vec![];
@@ -170,35 +179,35 @@ async fn read_transforms_file(
             .expect("Transforms path must be a filename")
             .join(&frame.file_path);
 
+        // Assume png's by default if no extension is specified (eg. the NeRF
+        // synthetic scenes use `./train/r_0`).
+        if path.extension().is_none() {
+            path = path.with_extension("png");
+        }
+
         // Check if path exists.
         if vfs.reader_at_path(&path).await.is_err() {
-            warnings.push(format!(
-                "Skipped '{}': image file not found",
-                frame.file_path
-            ));
+            warnings.push(missing_image_warning(&frame.file_path));
             continue;
         }
 
-        // Assume png's by default if no extension is specified.
-        if path.extension().is_none() {
-            path = path.with_extension("png");
-        }
-        let mask_path = find_mask_path(&vfs, &path).map(|p| p.to_path_buf());
+        let mask_path = find_mask_path(&vfs, &path);
         let image = LoadImage::new(
             vfs.clone(),
             path,
             mask_path,
             load_args.max_resolution,
-            load_args.alpha_mode,
+            alpha_mode,
         );
 
         let w = frame.w.or(scene.w);
         let h = frame.h.or(scene.h);
         // If the json omits the size, read it from the image header (cheap, no
         // full decode).
-        let (w, h) = match (w, h) {
-            (Some(w), Some(h)) => (w as u32, h as u32),
-            _ => image.dimensions().await?,
+        let intrinsics_size = w.zip(h).map(|(w, h)| (w as u32, h as u32));
+        let (w, h) = match intrinsics_size {
+            Some(size) => size,
+            None => image.dimensions().await?,
         };
 
         let camera_model = resolve_camera_model(
@@ -260,7 +269,11 @@ async fn read_transforms_file(
             continue;
         }
 
-        let view = SceneView { image, camera };
+        let view = SceneView {
+            image,
+            camera,
+            intrinsics_size,
+        };
         results.push(view);
     }
     Ok(results)
@@ -275,7 +288,7 @@ pub async fn read_dataset(
     let json_files: Vec<_> = vfs.files_with_extension("json").collect();
 
     let transforms_path = if json_files.len() == 1 {
-        json_files.first()?
+        json_files.first()?.clone()
     } else {
         // If there's multiple options, only pick files which are either exactly
         // transforms.json or end with transforms_train.json (a la transforms_train.json)
@@ -283,7 +296,6 @@ pub async fn read_dataset(
             .next()
             .or_else(|| vfs.files_ending_in("transforms_train.json").next())?
     };
-    let transforms_path = transforms_path.to_path_buf();
     Some(read_dataset_inner(vfs, load_args, json_files, transforms_path).await)
 }
 
@@ -301,23 +313,40 @@ async fn read_dataset_inner(
         .read_to_string(&mut buf)
         .await?;
     let train_scene: JsonScene = serde_json::from_str(&buf)?;
+
+    // NeRF synthetic (Blender) scenes come as transforms_{train,val,test}.json
+    // with RGBA renders on a transparent background.
+    let is_blender = transforms_path.ends_with("transforms_train.json");
+    let alpha_mode = load_args
+        .alpha_mode_override()
+        .or(train_scene.mask_from_alpha.map(|mask| {
+            if mask {
+                AlphaMode::Masked
+            } else {
+                AlphaMode::Transparent
+            }
+        }))
+        .or(is_blender.then_some(AlphaMode::Transparent));
+
     let train_handles = read_transforms_file(
         train_scene.clone(),
         &transforms_path,
         vfs.clone(),
         load_args,
+        alpha_mode,
         &mut warnings,
     )
     .await?;
 
-    // Use transforms_val as eval, or _test if no _val is present. (Brush doesn't really have any notion of a test set).
+    // Use transforms_test as eval, or _val if no _test is present. This matches
+    // how the NeRF synthetic scenes are usually evaluated.
     let eval_trans_path = json_files
         .iter()
-        .find(|x| x.ends_with("transforms_val.json"))
+        .find(|x| x.ends_with("transforms_test.json"))
         .or_else(|| {
             json_files
                 .iter()
-                .find(|x| x.ends_with("transforms_test.json"))
+                .find(|x| x.ends_with("transforms_val.json"))
         });
     // If a separate eval file is specified, read it.
     let val_views = if let Some(eval_trans_path) = eval_trans_path {
@@ -333,6 +362,7 @@ async fn read_dataset_inner(
                 eval_trans_path,
                 vfs.clone(),
                 load_args,
+                alpha_mode,
                 &mut warnings,
             )
             .await?,
@@ -341,20 +371,12 @@ async fn read_dataset_inner(
         None
     };
 
-    let mut train_views = vec![];
-    let mut eval_views = vec![];
-    for (i, view) in train_handles.into_iter().enumerate() {
-        if let Some(eval_period) = load_args.eval_split_every {
-            // Include extra eval images only when the dataset doesn't have them.
-            if i % eval_period == 0 && val_views.is_none() {
-                eval_views.push(view);
-            } else {
-                train_views.push(view);
-            }
-        } else {
-            train_views.push(view);
-        }
-    }
+    // Include extra eval images only when the dataset doesn't have them.
+    let (train_views, mut eval_views) = if val_views.is_none() {
+        split_eval(train_handles, load_args)
+    } else {
+        (train_handles, vec![])
+    };
 
     if let Some(val_views) = val_views {
         eval_views.extend(val_views);
@@ -385,3 +407,257 @@ async fn read_dataset_inner(
         warnings,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EvalSplitStrategy;
+    use crate::hdr::ToneMapping;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    const CAMERA_ANGLE_X: f64 = 0.691_111_2;
+
+    fn load_config() -> LoadDatasetConfig {
+        LoadDatasetConfig {
+            max_frames: None,
+            max_resolution: 1920,
+            eval_split_every: None,
+            eval_split_strategy: EvalSplitStrategy::EveryN,
+            subsample_frames: None,
+            subsample_points: None,
+            alpha_mode: None,
+            alpha_as_mask: false,
+            tone_mapping: ToneMapping::None,
+            max_scene_batch_cache_size: 0,
+        }
+    }
+
+    fn rgba_png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        image::RgbaImage::new(width, height)
+            .write_to(&mut data, image::ImageFormat::Png)
+            .expect("Failed to encode png");
+        data.into_inner()
+    }
+
+    fn transforms(split: &str, count: usize) -> Vec<u8> {
+        let frames: Vec<_> = (0..count)
+            .map(|i| {
+                serde_json::json!({
+                    "file_path": format!("./{split}/r_{i}"),
+                    "rotation": 0.0,
+                    "transform_matrix": [
+                        [1.0, 0.0, 0.0, 0.0],
+                        [0.0, 1.0, 0.0, 0.0],
+                        [0.0, 0.0, 1.0, 4.0],
+                        [0.0, 0.0, 0.0, 1.0],
+                    ],
+                })
+            })
+            .collect();
+        serde_json::to_vec(&serde_json::json!({
+            "camera_angle_x": CAMERA_ANGLE_X,
+            "frames": frames,
+        }))
+        .unwrap()
+    }
+
+    fn synthetic_vfs(splits: &[(&str, usize)]) -> Arc<BrushVfs> {
+        let mut files = vec![];
+        for &(split, count) in splits {
+            files.push((
+                PathBuf::from(format!("transforms_{split}.json")),
+                transforms(split, count),
+            ));
+            for i in 0..count {
+                files.push((PathBuf::from(format!("{split}/r_{i}.png")), rgba_png(8, 4)));
+            }
+        }
+        Arc::new(BrushVfs::create_test_vfs_with_data(files))
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_synthetic_splits() {
+        let vfs = synthetic_vfs(&[("train", 3), ("val", 2), ("test", 4)]);
+        let result = read_dataset(vfs, &load_config()).await.unwrap().unwrap();
+        assert!(result.warnings.is_empty());
+
+        let train = &result.dataset.train.views;
+        assert_eq!(train.len(), 3);
+        // The test split is preferred over val for evaluation.
+        let eval = result.dataset.eval.expect("Should have an eval split");
+        assert_eq!(eval.views.len(), 4);
+        assert!(eval.views[0].image.path().starts_with("test"));
+
+        for view in train.iter().chain(eval.views.iter()) {
+            assert_eq!(view.image.path().extension().unwrap(), "png");
+            assert_eq!(view.image.alpha_mode(), AlphaMode::Transparent);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_synthetic_val_fallback_and_focal() {
+        let vfs = synthetic_vfs(&[("train", 2), ("val", 1)]);
+        let result = read_dataset(vfs, &load_config()).await.unwrap().unwrap();
+        assert_eq!(result.dataset.train.views.len(), 2);
+        let eval = result.dataset.eval.expect("Should fall back to val");
+        assert_eq!(eval.views.len(), 1);
+
+        // The focal length follows from camera_angle_x and the image width,
+        // and is shared with the y axis (square pixels).
+        let camera = &result.dataset.train.views[0].camera;
+        let focal = camera.focal(glam::uvec2(8, 4));
+        let expected = 0.5 * 8.0 / (0.5 * CAMERA_ANGLE_X).tan();
+        assert!((focal.x as f64 - expected).abs() < 1e-3);
+        assert!((focal.y as f64 - expected).abs() < 1e-3);
+    }
+
+    fn intrinsics_vfs(scene: serde_json::Value) -> Arc<BrushVfs> {
+        Arc::new(BrushVfs::create_test_vfs_with_data(vec![
+            (
+                PathBuf::from("transforms.json"),
+                serde_json::to_vec(&scene).unwrap(),
+            ),
+            (PathBuf::from("images/a.png"), rgba_png(8, 4)),
+            (PathBuf::from("images/b.png"), rgba_png(8, 4)),
+        ]))
+    }
+
+    fn frame(file_path: &str) -> serde_json::Value {
+        serde_json::json!({
+            "file_path": file_path,
+            "transform_matrix": [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 4.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        })
+    }
+
+    fn assert_intrinsics(camera: &Camera, focal: glam::Vec2, center: glam::Vec2) {
+        let size = glam::uvec2(8, 4);
+        assert!((camera.focal(size) - focal).abs().max_element() < 1e-3);
+        assert!((camera.center(size) - center).abs().max_element() < 1e-4);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_global_intrinsics() {
+        let vfs = intrinsics_vfs(serde_json::json!({
+            "fl_x": 10.0,
+            "fl_y": 12.0,
+            "cx": 4.5,
+            "cy": 1.5,
+            "w": 8,
+            "h": 4,
+            "frames": [frame("images/a.png"), frame("images/b.png")],
+        }));
+        let result = read_dataset(vfs, &load_config()).await.unwrap().unwrap();
+        let views = &result.dataset.train.views;
+        assert_eq!(views.len(), 2);
+        for view in views.iter() {
+            assert!(matches!(view.camera.camera_model, CameraModel::Pinhole));
+            assert_intrinsics(&view.camera, glam::vec2(10.0, 12.0), glam::vec2(4.5, 1.5));
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_per_frame_intrinsics() {
+        let mut fisheye = frame("images/b.png");
+        fisheye.as_object_mut().unwrap().extend(
+            serde_json::json!({
+                "camera_model": "OPENCV_FISHEYE",
+                "fl_x": 6.0,
+                "fl_y": 7.0,
+                "cx": 3.0,
+                "cy": 2.5,
+                "k1": 0.05,
+                "k2": -0.01,
+                "k3": 0.002,
+                "k4": -0.001,
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        );
+        // The first frame falls back to the global intrinsics.
+        let vfs = intrinsics_vfs(serde_json::json!({
+            "fl_x": 10.0,
+            "fl_y": 12.0,
+            "w": 8,
+            "h": 4,
+            "frames": [frame("images/a.png"), fisheye],
+        }));
+        let result = read_dataset(vfs, &load_config()).await.unwrap().unwrap();
+        let views = &result.dataset.train.views;
+        assert_eq!(views.len(), 2);
+
+        assert!(matches!(views[0].camera.camera_model, CameraModel::Pinhole));
+        assert_intrinsics(
+            &views[0].camera,
+            glam::vec2(10.0, 12.0),
+            glam::vec2(4.0, 2.0),
+        );
+
+        let CameraModel::KannalaBrandt4(params) = views[1].camera.camera_model else {
+            panic!("Expected a fisheye camera");
+        };
+        assert_eq!(
+            [params.k1, params.k2, params.k3, params.k4],
+            [0.05, -0.01, 0.002, -0.001]
+        );
+        assert_intrinsics(&views[1].camera, glam::vec2(6.0, 7.0), glam::vec2(3.0, 2.5));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_mask_from_alpha() {
+        let scene = |mask_from_alpha: Option<bool>| {
+            let mut scene = serde_json::json!({
+                "fl_x": 10.0,
+                "w": 8,
+                "h": 4,
+                "frames": [frame("images/a.png")],
+            });
+            if let Some(mask) = mask_from_alpha {
+                scene["mask_from_alpha"] = mask.into();
+            }
+            scene
+        };
+        let alpha_mode = async |scene: serde_json::Value, config: &LoadDatasetConfig| {
+            let result = read_dataset(intrinsics_vfs(scene), config)
+                .await
+                .unwrap()
+                .unwrap();
+            result.dataset.train.views[0].image.alpha_mode()
+        };
+
+        let config = load_config();
+        assert_eq!(
+            alpha_mode(scene(Some(true)), &config).await,
+            AlphaMode::Masked
+        );
+        assert_eq!(
+            alpha_mode(scene(Some(false)), &config).await,
+            AlphaMode::Transparent
+        );
+
+        // Without a hint, the option makes the alpha channel a mask.
+        let mask_config = LoadDatasetConfig {
+            alpha_as_mask: true,
+            ..load_config()
+        };
+        assert_eq!(
+            alpha_mode(scene(None), &mask_config).await,
+            AlphaMode::Masked
+        );
+        assert_eq!(
+            alpha_mode(scene(Some(false)), &mask_config).await,
+            AlphaMode::Masked
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
-    DatasetLoadResult, FormatError, find_image_by_name, find_mask_path, opengl_c2w_to_pose,
-    split_eval_every,
+    DatasetLoadResult, FormatError, find_image_by_name, find_mask_path, missing_image_warning,
+    opengl_c2w_to_pose, split_eval,
 };
 use crate::{
     Dataset,
@@ -13,7 +13,6 @@ use brush_render::kernels::camera_model::CameraModel::{Pinhole, RadialTangential
 use brush_render::kernels::camera_model::radial_tangential_8::RadialTangential8Params;
 use brush_vfs::BrushVfs;
 use std::collections::HashMap;
-use std::path::Path;
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
 
@@ -122,18 +121,18 @@ async fn read_dataset_inner(
             warned_brown4 = true;
         }
 
-        let Some(image_path) = find_image_by_name(&vfs, name).map(Path::to_path_buf) else {
-            warnings.push(format!("Skipped '{name}': image file not found"));
+        let Some(image_path) = find_image_by_name(&vfs, name) else {
+            warnings.push(missing_image_warning(name));
             continue;
         };
 
-        let mask_path = find_mask_path(&vfs, &image_path).map(Path::to_path_buf);
+        let mask_path = find_mask_path(&vfs, &image_path);
         let image = LoadImage::new(
             vfs.clone(),
             image_path,
             mask_path,
             load_args.max_resolution,
-            load_args.alpha_mode,
+            load_args.alpha_mode_override(),
         );
 
         // The csv carries no image dimensions; intrinsics are resolution
@@ -149,10 +148,14 @@ async fn read_dataset_inner(
             continue;
         }
 
-        views.push(SceneView { camera, image });
+        views.push(SceneView {
+            camera,
+            image,
+            intrinsics_size: None,
+        });
     }
 
-    let (train_views, eval_views) = split_eval_every(views, load_args.eval_split_every);
+    let (train_views, eval_views) = split_eval(views, load_args);
 
     Ok(DatasetLoadResult {
         init_splat: None,
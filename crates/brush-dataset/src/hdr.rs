@@ -0,0 +1,116 @@
+//! High dynamic range (EXR, 16-bit PNG) training images.
+//!
+//! HDR images are kept as linear f32 all the way to the trainer, so values
+//! above 1.0 survive. The fused loss kernels read 8-bit packed GT, so HDR
+//! views additionally get a tone mapped 8-bit copy for those, see
+//! [`crate::scene::SceneBatch::hdr`].
+
+use burn::tensor::{Tensor, TensorData};
+use clap::ValueEnum;
+use image::{ColorType, DynamicImage, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+
+/// Curve applied to HDR renders and ground truth before computing the loss.
+/// The splats themselves are still trained in linear space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMapping {
+    /// Compare linear values directly. Highlights above 1.0 only count
+    /// towards the L1 term, SSIM sees them clamped.
+    #[default]
+    None,
+    /// `x / (1 + x)`.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMapping {
+    pub fn apply(self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            Self::None => x,
+            Self::Reinhard => x / (1.0 + x),
+            Self::Aces => {
+                ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Same as [`Self::apply`], elementwise on a tensor.
+    pub fn apply_tensor<const D: usize>(self, x: Tensor<D>) -> Tensor<D> {
+        match self {
+            Self::None => x,
+            Self::Reinhard => {
+                let x = x.clamp_min(0.0);
+                x.clone() / (x + 1.0)
+            }
+            Self::Aces => {
+                let x = x.clamp_min(0.0);
+                let num = x.clone() * (x.clone() * 2.51 + 0.03);
+                let den = x.clone() * (x * 2.43 + 0.59) + 0.14;
+                (num / den).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Whether the image has more precision than 8 bits per channel.
+pub fn is_hdr(image: &DynamicImage) -> bool {
+    !matches!(
+        image.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    )
+}
+
+/// Linear ground truth of an HDR view.
+#[derive(Clone, Debug)]
+pub struct HdrSample {
+    /// `[H, W, 4]` f32, premultiplied RGBA. Not clamped.
+    pub img: TensorData,
+    pub tone_mapping: ToneMapping,
+}
+
+impl HdrSample {
+    pub fn new(sample: &Rgba32FImage, tone_mapping: ToneMapping) -> Self {
+        let (w, h) = sample.dimensions();
+        Self {
+            img: TensorData::new(sample.as_raw().clone(), [h as usize, w as usize, 4]),
+            tone_mapping,
+        }
+    }
+}
+
+/// Tone map the color channels of an HDR sample into an 8-bit image, as input
+/// for the packed loss kernels. Alpha is left as is.
+pub fn tone_map_to_ldr(sample: &Rgba32FImage, tone_mapping: ToneMapping) -> DynamicImage {
+    let mut mapped = sample.clone();
+    for pixel in mapped.pixels_mut() {
+        for c in &mut pixel.0[0..3] {
+            *c = tone_mapping.apply(*c);
+        }
+    }
+    DynamicImage::ImageRgba32F(mapped).into_rgba8().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ToneMapping, is_hdr};
+    use image::{DynamicImage, Rgb32FImage, RgbImage};
+
+    #[test]
+    fn tone_mapping_keeps_order_and_range() {
+        for tm in [ToneMapping::Reinhard, ToneMapping::Aces] {
+            let values = [0.0, 0.25, 1.0, 4.0, 100.0].map(|x| tm.apply(x));
+            assert!(values.windows(2).all(|w| w[0] <= w[1]));
+            assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        }
+        assert_eq!(ToneMapping::None.apply(4.0), 4.0);
+    }
+
+    #[test]
+    fn detects_hdr_images() {
+        assert!(!is_hdr(&DynamicImage::ImageRgb8(RgbImage::new(1, 1))));
+        assert!(is_hdr(&DynamicImage::ImageRgb32F(Rgb32FImage::new(1, 1))));
+    }
+}
@@ -1,9 +1,12 @@
 #![recursion_limit = "256"]
 
+pub mod camera_path;
 pub mod config;
+pub mod hdr;
 pub mod load_image;
 pub mod scene;
 pub mod scene_loader;
+pub mod validate;
 
 mod formats;
 
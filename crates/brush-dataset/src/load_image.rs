@@ -1,5 +1,7 @@
 use brush_render::AlphaMode;
 use brush_vfs::BrushVfs;
+
+use crate::hdr::is_hdr;
 use image::{DynamicImage, GenericImageView, ImageBuffer};
 use std::{
     io::{self, Cursor},
@@ -64,8 +66,6 @@ impl LoadImage {
 
         // Copy over mask.
         if let Some(mask_path) = &self.mask_path {
-            // Add in alpha channel if needed to the image to copy the mask into.
-            let mut masked_img = img.into_rgba8();
             let mut mask_bytes = vec![];
             self.vfs
                 .reader_at_path(mask_path)
@@ -75,27 +75,36 @@ impl LoadImage {
             let mut mask_img = image::load_from_memory(&mask_bytes)?;
 
             // Resize mask image if needed. This is allowed to squash the mask.
-            if mask_img.dimensions() != masked_img.dimensions() {
+            if mask_img.dimensions() != img.dimensions() {
                 mask_img = mask_img.resize_exact(
-                    masked_img.width(),
-                    masked_img.height(),
+                    img.width(),
+                    img.height(),
                     image::imageops::FilterType::Triangle,
                 );
             }
 
-            if mask_img.color().has_alpha() {
-                let mask_img = mask_img.into_rgba8();
-                for (pixel, mask_pixel) in masked_img.pixels_mut().zip(mask_img.pixels()) {
-                    pixel[3] = mask_pixel[3];
+            // Mask values as bytes, from the alpha channel if the mask has one.
+            let mask_values: Vec<u8> = if mask_img.color().has_alpha() {
+                mask_img.into_rgba8().pixels().map(|p| p[3]).collect()
+            } else {
+                mask_img.into_rgb8().pixels().map(|p| p[0]).collect()
+            };
+
+            // Add in alpha channel if needed to the image to copy the mask into.
+            // HDR images stay f32 so the mask doesn't quantize their colors.
+            img = if is_hdr(&img) {
+                let mut masked_img = img.into_rgba32f();
+                for (pixel, mask) in masked_img.pixels_mut().zip(mask_values) {
+                    pixel[3] = mask as f32 / 255.0;
                 }
+                masked_img.into()
             } else {
-                let mask_img = mask_img.into_rgb8();
-                for (pixel, mask_pixel) in masked_img.pixels_mut().zip(mask_img.pixels()) {
-                    pixel[3] = mask_pixel[0];
+                let mut masked_img = img.into_rgba8();
+                for (pixel, mask) in masked_img.pixels_mut().zip(mask_values) {
+                    pixel[3] = mask;
                 }
-            }
-
-            img = masked_img.into();
+                masked_img.into()
+            };
         }
 
         let scale = self.output_scale(img.width(), img.height());
@@ -142,21 +151,14 @@ impl LoadImage {
     /// (the dimension fields are reported once fully present), so a partial
     /// buffer can't yield wrong dimensions.
     pub async fn dimensions(&self) -> image::ImageResult<(u32, u32)> {
-        let mut reader = self.vfs.reader_at_path(&self.path).await?;
-        let dims = brush_vfs::read_until_parsed(&mut reader, 64 * 1024, |bytes| {
-            image::ImageReader::new(Cursor::new(bytes))
-                .with_guessed_format()
-                .ok()
-                .and_then(|r| r.into_dimensions().ok())
-        })
-        .await?;
-        dims.ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("could not determine image dimensions for {:?}", self.path),
-            )
-            .into()
-        })
+        header_dimensions(&self.vfs, &self.path).await
+    }
+
+    /// Like [`Self::dimensions`], for the mask, if there is one. The mask gets
+    /// resized to the image on load, so these can differ.
+    pub async fn mask_dimensions(&self) -> Option<image::ImageResult<(u32, u32)>> {
+        let mask_path = self.mask_path.as_ref()?;
+        Some(header_dimensions(&self.vfs, mask_path).await)
     }
 
     pub fn alpha_mode(&self) -> AlphaMode {
@@ -184,6 +186,28 @@ impl LoadImage {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    pub fn mask_path(&self) -> Option<&Path> {
+        self.mask_path.as_deref()
+    }
+}
+
+async fn header_dimensions(vfs: &BrushVfs, path: &Path) -> image::ImageResult<(u32, u32)> {
+    let mut reader = vfs.reader_at_path(path).await?;
+    let dims = brush_vfs::read_until_parsed(&mut reader, 64 * 1024, |bytes| {
+        image::ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|r| r.into_dimensions().ok())
+    })
+    .await?;
+    dims.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("could not determine image dimensions for {path:?}"),
+        )
+        .into()
+    })
 }
 
 /// Decode `bytes`, hinting `jpeg-decoder`'s IDCT scaler to land at or just
@@ -4,6 +4,8 @@ use glam::{Affine3A, Vec3, vec3};
 use image::DynamicImage;
 use std::sync::Arc;
 
+use crate::hdr::{HdrSample, ToneMapping, is_hdr, tone_map_to_ldr};
+
 pub use crate::load_image::LoadImage;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -17,6 +19,9 @@ pub enum ViewType {
 pub struct SceneView {
     pub image: LoadImage,
     pub camera: Camera,
+    /// Image size the camera intrinsics were calibrated at, if the format
+    /// records one.
+    pub intrinsics_size: Option<(u32, u32)>,
 }
 
 // Encapsulates a multi-view scene including cameras and the splats.
@@ -64,31 +69,53 @@ impl Scene {
             .map(|v| SceneView {
                 image: v.image.with_scale(scale),
                 camera: v.camera,
+                intrinsics_size: v.intrinsics_size,
             })
             .collect();
         Self::new(views)
     }
 
+    /// Index of the view whose camera is closest to `reference` (a camera's
+    /// local to world transform), in both position and rotation.
     pub fn get_nearest_view(&self, reference: Affine3A) -> Option<usize> {
-        self.views
-            .iter()
-            .enumerate() // This will give us (index, view) pairs
-            .min_by(|(_, a), (_, b)| {
-                let score_a = camera_distance_penalty(a.camera.local_to_world(), reference);
-                let score_b = camera_distance_penalty(b.camera.local_to_world(), reference);
-                score_a
-                    .partial_cmp(&score_b)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .map(|(index, _)| index) // We return the index instead of the camera
+        nearest_camera(self.views.iter().map(|v| &v.camera), reference)
     }
 }
 
+fn nearest_camera<'a>(
+    cameras: impl IntoIterator<Item = &'a Camera>,
+    reference: Affine3A,
+) -> Option<usize> {
+    cameras
+        .into_iter()
+        .map(|cam| camera_distance_penalty(cam.local_to_world(), reference))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(index, _)| index)
+}
+
 // Converts an image to a train sample. The tensor will be a floating point image with a [0, 1] image.
+// HDR images become linear f32 samples instead, and keep values above 1.
 //
 // This assume the input image has un-premultiplied alpha, whereas the output has pre-multiplied alpha.
 pub fn view_to_sample_image(image: DynamicImage, alpha_mode: AlphaMode) -> DynamicImage {
-    if image.color().has_alpha() && alpha_mode == AlphaMode::Transparent {
+    if is_hdr(&image) {
+        let has_alpha = image.color().has_alpha();
+        if has_alpha && alpha_mode == AlphaMode::Transparent {
+            let mut rgba = image.into_rgba32f();
+            for pixel in rgba.pixels_mut() {
+                let a = pixel[3];
+                for c in &mut pixel.0[0..3] {
+                    *c *= a;
+                }
+            }
+            DynamicImage::ImageRgba32F(rgba)
+        } else if has_alpha {
+            DynamicImage::ImageRgba32F(image.into_rgba32f())
+        } else {
+            DynamicImage::ImageRgb32F(image.into_rgb32f())
+        }
+    } else if image.color().has_alpha() && alpha_mode == AlphaMode::Transparent {
         let mut rgba_bytes = image.to_rgba8();
         // Assume image has un-multiplied alpha and convert it to pre-multiplied.
         // Perform multiplication in byte space before converting to float.
@@ -128,6 +155,26 @@ pub fn sample_to_packed_data(sample: DynamicImage) -> (TensorData, bool) {
     (TensorData::new(packed, [h as usize, w as usize]), has_alpha)
 }
 
+/// Split an HDR sample into its linear ground truth and a tone mapped 8-bit
+/// sample for [`sample_to_packed_data`]. LDR samples pass through unchanged.
+pub fn split_hdr_sample(
+    sample: DynamicImage,
+    tone_mapping: ToneMapping,
+) -> (DynamicImage, Option<HdrSample>) {
+    if !is_hdr(&sample) {
+        return (sample, None);
+    }
+    let has_alpha = sample.color().has_alpha();
+    let rgba = sample.into_rgba32f();
+    let ldr = tone_map_to_ldr(&rgba, tone_mapping);
+    let ldr = if has_alpha {
+        ldr
+    } else {
+        DynamicImage::ImageRgb8(ldr.into_rgb8())
+    };
+    (ldr, Some(HdrSample::new(&rgba, tone_mapping)))
+}
+
 #[derive(Clone, Debug)]
 pub struct SceneBatch {
     /// `[H, W]` u32, each entry packs `[r g b a]` u8.
@@ -137,6 +184,14 @@ pub struct SceneBatch {
     pub has_alpha: bool,
     pub alpha_mode: AlphaMode,
     pub camera: Camera,
+    /// Index of the view in the training scene this batch was sampled from.
+    pub view_index: usize,
+    /// Linear ground truth for HDR views. `img_packed` then holds the tone
+    /// mapped image.
+    pub hdr: Option<HdrSample>,
+    /// `[H, W]` f32 view-space depth to supervise the render with. Pixels
+    /// without a positive, finite depth are ignored.
+    pub depth: Option<TensorData>,
 }
 
 impl SceneBatch {
@@ -147,9 +202,68 @@ impl SceneBatch {
 
 #[cfg(test)]
 mod tests {
-    use super::sample_to_packed_data;
+    use super::{nearest_camera, sample_to_packed_data};
+    use brush_render::{camera::Camera, kernels::camera_model::CameraModel};
+    use glam::{Quat, Vec3, vec2};
     use image::{DynamicImage, ImageBuffer, RgbImage, RgbaImage};
 
+    fn camera_at(position: Vec3, rotation: Quat) -> Camera {
+        Camera::new(
+            position,
+            rotation,
+            0.8,
+            0.8,
+            vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        )
+    }
+
+    #[test]
+    fn nearest_camera_by_position() {
+        let cameras = [
+            camera_at(Vec3::new(-2.0, 0.0, 0.0), Quat::IDENTITY),
+            camera_at(Vec3::new(0.0, 0.0, 0.0), Quat::IDENTITY),
+            camera_at(Vec3::new(3.0, 0.0, 0.0), Quat::IDENTITY),
+        ];
+        let reference = camera_at(Vec3::new(2.2, 0.1, 0.0), Quat::IDENTITY);
+        assert_eq!(
+            nearest_camera(&cameras, reference.local_to_world()),
+            Some(2)
+        );
+        // An exact match always wins.
+        assert_eq!(
+            nearest_camera(&cameras, cameras[0].local_to_world()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn nearest_camera_by_rotation() {
+        // Two cameras at the same spot, looking in opposite directions.
+        let cameras = [
+            camera_at(Vec3::ZERO, Quat::IDENTITY),
+            camera_at(Vec3::ZERO, Quat::from_rotation_y(std::f32::consts::PI)),
+        ];
+        let reference = camera_at(Vec3::new(0.0, 0.0, 0.2), Quat::from_rotation_y(2.9));
+        assert_eq!(
+            nearest_camera(&cameras, reference.local_to_world()),
+            Some(1)
+        );
+        let reference = camera_at(Vec3::new(0.0, 0.0, 0.2), Quat::from_rotation_y(0.2));
+        assert_eq!(
+            nearest_camera(&cameras, reference.local_to_world()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn nearest_camera_empty() {
+        assert_eq!(
+            nearest_camera(&[], Camera::default().local_to_world()),
+            None
+        );
+    }
+
     #[test]
     fn packs_rgba_samples_without_changing_channels() {
         let image =
@@ -179,4 +293,35 @@ mod tests {
             &[0xff0b_0a09_u32 as i32, 0xff0e_0d0c_u32 as i32]
         );
     }
+
+    #[test]
+    fn exr_highlights_survive_into_training_data() {
+        use super::{split_hdr_sample, view_to_sample_image};
+        use crate::hdr::ToneMapping;
+        use brush_render::AlphaMode;
+        use image::{ImageFormat, Rgb32FImage};
+
+        let pixels = vec![0.25, 0.5, 1.0, 4.0, 16.0, 2.5];
+        let image = Rgb32FImage::from_raw(2, 1, pixels.clone()).expect("valid RGB image");
+        let mut exr = std::io::Cursor::new(vec![]);
+        DynamicImage::ImageRgb32F(image)
+            .write_to(&mut exr, ImageFormat::OpenExr)
+            .expect("EXR encode");
+
+        let decoded = image::load_from_memory(exr.get_ref()).expect("EXR decode");
+        let sample = view_to_sample_image(decoded, AlphaMode::Transparent);
+        let (ldr, hdr) = split_hdr_sample(sample, ToneMapping::Reinhard);
+        let hdr = hdr.expect("EXR is HDR");
+
+        assert_eq!(hdr.img.shape.dims(), [1, 2, 4]);
+        let values = hdr.img.as_slice::<f32>().expect("f32 tensor");
+        let rgb: Vec<f32> = values.chunks(4).flat_map(|p| p[0..3].to_vec()).collect();
+        assert_eq!(rgb, pixels);
+
+        // The packed copy is tone mapped rather than clamped, so 4 and 16 stay distinct.
+        let (packed, has_alpha) = sample_to_packed_data(ldr);
+        assert!(!has_alpha);
+        let bytes: Vec<u8> = bytemuck::cast_slice(packed.as_slice::<i32>().expect("i32")).to_vec();
+        assert!(bytes[4] < bytes[5] && bytes[5] < 255);
+    }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc};
 
 use brush_async::Actor;
 use rand::{SeedableRng, seq::SliceRandom};
@@ -6,50 +6,105 @@ use tokio::sync::{Mutex, mpsc};
 
 use crate::{
     config::LoadDatasetConfig,
-    scene::{Scene, SceneBatch, sample_to_packed_data, view_to_sample_image},
+    hdr::ToneMapping,
+    scene::{Scene, SceneBatch, sample_to_packed_data, split_hdr_sample, view_to_sample_image},
 };
 
 /// Shared cache of GPU-ready scene batches. Each slot holds at most one
-/// batch; once the running total passes `budget_bytes`, new batches bypass
-/// the cache and just get re-decoded + re-packed on every visit.
+/// batch; once the running total would pass `budget_bytes`, the least
+/// recently used batches are evicted to make room. Images are only ever
+/// decoded when a view is visited, so memory stays bounded by the budget no
+/// matter how large the dataset is.
 ///
 /// Caching the packed batch (instead of the decoded `DynamicImage`) skips
 /// the per-hit decode → premultiply → repack work: a cache hit is now a
 /// single copy of the already-packed `[H, W]` u32 buffer.
 struct BatchCache {
     slots: Vec<Option<Arc<SceneBatch>>>,
+    // Cached view indices, least recently used first.
+    lru: VecDeque<usize>,
     used_bytes: u64,
     budget_bytes: u64,
+    stats: CacheStats,
+}
+
+/// Counters of the scene loader's batch cache, see [`SceneLoader::cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of decoded batches currently held by the cache.
+    pub resident: usize,
+    pub resident_bytes: u64,
+    pub hits: u64,
+    /// Visits that had to load and decode the image.
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+fn batch_bytes(batch: &SceneBatch) -> u64 {
+    // Track exact bytes: rounding to whole MB let sub-MB images slip in
+    // for free and bypass the budget entirely.
+    let hdr_bytes = batch.hdr.as_ref().map_or(0, |hdr| hdr.img.as_bytes().len());
+    (batch.img_packed.as_bytes().len() + hdr_bytes)
+        .try_into()
+        .expect("shouldn't exceed ~18 Exabytes...")
 }
 
 impl BatchCache {
     fn new(n_views: usize, budget_bytes: u64) -> Self {
         Self {
             slots: vec![None; n_views],
+            lru: VecDeque::new(),
             used_bytes: 0,
             budget_bytes,
+            stats: CacheStats::default(),
         }
     }
 
-    fn get(&self, index: usize) -> Option<Arc<SceneBatch>> {
-        self.slots[index].clone()
+    fn touch(&mut self, index: usize) {
+        if let Some(pos) = self.lru.iter().position(|&i| i == index) {
+            self.lru.remove(pos);
+        }
+        self.lru.push_back(index);
+    }
+
+    fn get(&mut self, index: usize) -> Option<Arc<SceneBatch>> {
+        let batch = self.slots[index].clone();
+        if batch.is_some() {
+            self.stats.hits += 1;
+            self.touch(index);
+        } else {
+            self.stats.misses += 1;
+        }
+        batch
     }
 
     fn insert(&mut self, index: usize, batch: Arc<SceneBatch>) {
         if self.slots[index].is_some() {
             return;
         }
-        // Track exact bytes: rounding to whole MB let sub-MB images slip in
-        // for free and bypass the budget entirely.
-        let size_bytes: u64 = batch
-            .img_packed
-            .as_bytes()
-            .len()
-            .try_into()
-            .expect("shouldn't exceed ~18 Exabytes...");
-        if self.used_bytes + size_bytes < self.budget_bytes {
-            self.slots[index] = Some(batch);
-            self.used_bytes += size_bytes;
+        let size_bytes = batch_bytes(&batch);
+        if size_bytes >= self.budget_bytes {
+            return;
+        }
+        while self.used_bytes + size_bytes >= self.budget_bytes {
+            let Some(evict) = self.lru.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.slots[evict].take() {
+                self.used_bytes -= batch_bytes(&evicted);
+                self.stats.evictions += 1;
+            }
+        }
+        self.slots[index] = Some(batch);
+        self.used_bytes += size_bytes;
+        self.touch(index);
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            resident: self.lru.len(),
+            resident_bytes: self.used_bytes,
+            ..self.stats
         }
     }
 }
@@ -59,15 +114,11 @@ pub struct SceneLoader {
     // Owns the loader actor threads. Dropping cancels them; their
     // senders then drop, the channel closes, and `next_batch` returns.
     _actors: Vec<Actor>,
+    cache: Arc<Mutex<BatchCache>>,
 }
 
 impl SceneLoader {
     pub fn new(scene: &Scene, seed: u64, config: &LoadDatasetConfig) -> Self {
-        // Prefetch buffer: at most 4 batches ahead of the trainer.
-        // Two tasks per actor share this buffer so one task's I/O can
-        // overlap with the other's decode + GPU upload.
-        let (tx, rx) = mpsc::channel(4);
-
         // Fan out only as many loaders as we have real parallelism.
         // Wasm shares one JS event loop, so extra actors just add
         // contention without overlapping I/O.
@@ -76,7 +127,26 @@ impl SceneLoader {
         } else {
             std::thread::available_parallelism().map_or(8, |p| p.get())
         };
-        const TASKS_PER_ACTOR: usize = 2;
+        // Two tasks per actor share the prefetch buffer so one task's I/O
+        // can overlap with the other's decode + GPU upload.
+        Self::with_loaders(scene, seed, config, n_actors, 2)
+    }
+
+    /// Like [`Self::new`], but with a single loader task, so the order of the
+    /// batches only depends on `seed`. The parallel loaders race each other.
+    pub fn new_sequential(scene: &Scene, seed: u64, config: &LoadDatasetConfig) -> Self {
+        Self::with_loaders(scene, seed, config, 1, 1)
+    }
+
+    fn with_loaders(
+        scene: &Scene,
+        seed: u64,
+        config: &LoadDatasetConfig,
+        n_actors: usize,
+        tasks_per_actor: usize,
+    ) -> Self {
+        // Prefetch buffer: at most 4 batches ahead of the trainer.
+        let (tx, rx) = mpsc::channel(4);
 
         let views = scene.views.clone();
         let cache = Arc::new(Mutex::new(BatchCache::new(
@@ -84,18 +154,19 @@ impl SceneLoader {
             config.max_scene_batch_cache_size,
         )));
 
+        let tone_mapping = config.tone_mapping;
         let mut task_idx: u64 = 0;
         let actors: Vec<Actor> = (0..n_actors)
             .map(|i| {
                 let actor = Actor::new(&format!("dataloader-{i}"));
-                for _ in 0..TASKS_PER_ACTOR {
+                for _ in 0..tasks_per_actor {
                     let views = views.clone();
                     let cache = cache.clone();
                     let tx = tx.clone();
                     let task_seed = seed.wrapping_add(task_idx);
                     task_idx += 1;
                     actor
-                        .run(move || run_loader(views, cache, tx, task_seed))
+                        .run(move || run_loader(views, cache, tx, task_seed, tone_mapping))
                         .detach();
                 }
                 actor
@@ -105,15 +176,30 @@ impl SceneLoader {
         Self {
             rx,
             _actors: actors,
+            cache,
         }
     }
 
+    /// Current state of the decoded batch cache.
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().await.stats()
+    }
+
     pub async fn next_batch(&mut self) -> SceneBatch {
         self.rx
             .recv()
             .await
             .expect("Scene loader channel closed unexpectedly")
     }
+
+    /// The next `count` batches, eg. to accumulate gradients over.
+    pub async fn next_batches(&mut self, count: usize) -> Vec<SceneBatch> {
+        let mut batches = Vec::with_capacity(count);
+        for _ in 0..count {
+            batches.push(self.next_batch().await);
+        }
+        batches
+    }
 }
 
 async fn run_loader(
@@ -121,6 +207,7 @@ async fn run_loader(
     cache: Arc<Mutex<BatchCache>>,
     tx: mpsc::Sender<SceneBatch>,
     seed: u64,
+    tone_mapping: ToneMapping,
 ) {
     let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
     let mut shuffled: Vec<usize> = Vec::new();
@@ -142,12 +229,16 @@ async fn run_loader(
                 .await
                 .expect("Scene loader failed to load an image");
             let sample = view_to_sample_image(raw, view.image.alpha_mode());
+            let (sample, hdr) = split_hdr_sample(sample, tone_mapping);
             let (img_packed, has_alpha) = sample_to_packed_data(sample);
             let batch = Arc::new(SceneBatch {
                 img_packed,
                 has_alpha,
                 alpha_mode: view.image.alpha_mode(),
                 camera: view.camera,
+                view_index: index,
+                hdr,
+                depth: None,
             });
             cache.lock().await.insert(index, batch.clone());
             batch
@@ -161,3 +252,88 @@ async fn run_loader(
         brush_async::yield_now().await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SceneLoader;
+    use crate::config::{EvalSplitStrategy, LoadDatasetConfig};
+    use crate::hdr::ToneMapping;
+    use crate::load_image::LoadImage;
+    use crate::scene::{Scene, SceneView};
+    use brush_render::camera::Camera;
+    use brush_render::kernels::camera_model::CameraModel;
+    use brush_vfs::BrushVfs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn cache_keeps_decoded_images_bounded() {
+        const NUM_VIEWS: usize = 50;
+        const CACHED: u64 = 5;
+
+        let files = (0..NUM_VIEWS)
+            .map(|i| {
+                let mut png = Cursor::new(vec![]);
+                image::RgbaImage::new(8, 4)
+                    .write_to(&mut png, image::ImageFormat::Png)
+                    .expect("Failed to encode png");
+                (PathBuf::from(format!("img_{i}.png")), png.into_inner())
+            })
+            .collect();
+        let vfs = Arc::new(BrushVfs::create_test_vfs_with_data(files));
+        let views = (0..NUM_VIEWS)
+            .map(|i| SceneView {
+                image: LoadImage::new(
+                    vfs.clone(),
+                    PathBuf::from(format!("img_{i}.png")),
+                    None,
+                    1920,
+                    None,
+                ),
+                camera: Camera::new(
+                    glam::Vec3::ZERO,
+                    glam::Quat::IDENTITY,
+                    0.5,
+                    0.5,
+                    glam::vec2(0.5, 0.5),
+                    CameraModel::Pinhole,
+                ),
+                intrinsics_size: None,
+            })
+            .collect();
+        let scene = Scene::new(views);
+
+        // Each packed 8x4 image is 128 bytes.
+        let config = LoadDatasetConfig {
+            max_frames: None,
+            max_resolution: 1920,
+            eval_split_every: None,
+            eval_split_strategy: EvalSplitStrategy::EveryN,
+            subsample_frames: None,
+            subsample_points: None,
+            alpha_mode: None,
+            alpha_as_mask: false,
+            tone_mapping: ToneMapping::None,
+            max_scene_batch_cache_size: CACHED * 128 + 1,
+        };
+        let mut loader = SceneLoader::new(&scene, 0, &config);
+        for _ in 0..4 * NUM_VIEWS {
+            let batch = loader.next_batch().await;
+            assert_eq!(batch.img_size(), [4, 8]);
+            let stats = loader.cache_stats().await;
+            assert!(stats.resident as u64 <= CACHED, "{stats:?}");
+            assert!(stats.resident_bytes <= CACHED * 128, "{stats:?}");
+        }
+
+        let stats = loader.cache_stats().await;
+        assert_eq!(stats.resident as u64, CACHED);
+        // Every view had to be decoded at least once, and the cache had to make room.
+        assert!(stats.misses >= NUM_VIEWS as u64);
+        assert!(stats.evictions > 0);
+    }
+}
@@ -0,0 +1,447 @@
+//! Dry run of the dataset loaders that reports everything wrong with a
+//! dataset at once, rather than failing (or silently skipping views) on the
+//! first problem during training.
+
+use crate::{
+    config::LoadDatasetConfig,
+    formats::{load_dataset, parse_missing_image_warning},
+    scene::SceneView,
+};
+use brush_vfs::BrushVfs;
+use std::{collections::HashMap, fmt, path::PathBuf, sync::Arc};
+use thiserror::Error;
+
+/// Sizes with an aspect ratio within this fraction are considered a uniform
+/// rescale of each other, e.g. colmap's downscaled `images_2` folders.
+const ASPECT_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Training works, but probably not as intended.
+    Warning,
+    /// Training fails, or silently drops views.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ValidationIssue {
+    #[error("dataset failed to load: {0}")]
+    LoadFailed(String),
+
+    #[error("--eval-split-every must be at least 1")]
+    InvalidEvalSplit,
+
+    #[error("'{name}' is referenced by the dataset but the image file is missing")]
+    MissingImage { name: String },
+
+    #[error("{path:?} can't be read: {error}")]
+    UnreadableImage { path: PathBuf, error: String },
+
+    #[error("{path:?} can't be read: {error}")]
+    UnreadableMask { path: PathBuf, error: String },
+
+    #[error("'{image}' has a degenerate focal length ({focal_x:.3}, {focal_y:.3}) px")]
+    DegenerateFocal {
+        image: String,
+        focal_x: f32,
+        focal_y: f32,
+    },
+
+    #[error(
+        "'{image}' is {}x{} but its intrinsics are for {}x{}",
+        actual.0, actual.1, intrinsics.0, intrinsics.1
+    )]
+    IntrinsicsMismatch {
+        image: String,
+        intrinsics: (u32, u32),
+        actual: (u32, u32),
+    },
+
+    #[error(
+        "the mask of '{image}' is {}x{} but the image is {}x{}",
+        mask.0, mask.1, actual.0, actual.1
+    )]
+    MaskMismatch {
+        image: String,
+        mask: (u32, u32),
+        actual: (u32, u32),
+    },
+
+    #[error("{count} views share the image name '{name}'")]
+    DuplicateName { name: String, count: usize },
+
+    #[error("{0}")]
+    LoaderWarning(String),
+}
+
+impl ValidationIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::IntrinsicsMismatch {
+                intrinsics, actual, ..
+            } => {
+                if same_aspect(*intrinsics, *actual) {
+                    Severity::Warning
+                } else {
+                    Severity::Error
+                }
+            }
+            Self::MaskMismatch { mask, actual, .. } => {
+                if same_aspect(*mask, *actual) {
+                    Severity::Warning
+                } else {
+                    Severity::Error
+                }
+            }
+            Self::DuplicateName { .. } | Self::LoaderWarning(_) => Severity::Warning,
+            Self::LoadFailed(_)
+            | Self::InvalidEvalSplit
+            | Self::MissingImage { .. }
+            | Self::UnreadableImage { .. }
+            | Self::UnreadableMask { .. }
+            | Self::DegenerateFocal { .. } => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub train_views: usize,
+    pub eval_views: usize,
+    pub eval_split_every: Option<usize>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether any issue would break training or drop views.
+    pub fn has_blocking_issues(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity() == Severity::Error)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} views: {} train, {} eval",
+            self.train_views + self.eval_views,
+            self.train_views,
+            self.eval_views
+        )?;
+        match self.eval_split_every {
+            Some(n) if self.eval_views > 0 => writeln!(f, "Eval split: every {n}th view")?,
+            _ if self.eval_views > 0 => writeln!(f, "Eval split: from the dataset")?,
+            _ => writeln!(f, "Eval split: none")?,
+        }
+
+        for severity in [Severity::Error, Severity::Warning] {
+            let issues: Vec<_> = self
+                .issues
+                .iter()
+                .filter(|issue| issue.severity() == severity)
+                .collect();
+            if issues.is_empty() {
+                continue;
+            }
+            let label = match severity {
+                Severity::Error => "Errors",
+                Severity::Warning => "Warnings",
+            };
+            writeln!(f, "{label} ({}):", issues.len())?;
+            for issue in issues {
+                writeln!(f, "  - {issue}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn same_aspect(a: (u32, u32), b: (u32, u32)) -> bool {
+    let lhs = a.0 as f64 * b.1 as f64;
+    let rhs = a.1 as f64 * b.0 as f64;
+    (lhs - rhs).abs() <= ASPECT_TOLERANCE * lhs.max(rhs)
+}
+
+/// Load `vfs` with the regular format loaders and check every view. Images are
+/// fully decoded, so this is slow for big datasets, but catches truncated files.
+pub async fn validate_dataset(
+    vfs: Arc<BrushVfs>,
+    load_args: &LoadDatasetConfig,
+) -> ValidationReport {
+    let mut report = ValidationReport {
+        eval_split_every: load_args.eval_split_every,
+        ..Default::default()
+    };
+
+    if load_args.eval_split_every == Some(0) {
+        report.issues.push(ValidationIssue::InvalidEvalSplit);
+        return report;
+    }
+
+    let result = match load_dataset(vfs, load_args).await {
+        Ok(result) => result,
+        Err(e) => {
+            report
+                .issues
+                .push(ValidationIssue::LoadFailed(e.to_string()));
+            return report;
+        }
+    };
+
+    for warning in result.warnings {
+        let issue = match parse_missing_image_warning(&warning) {
+            Some(name) => ValidationIssue::MissingImage {
+                name: name.to_owned(),
+            },
+            None => ValidationIssue::LoaderWarning(warning),
+        };
+        report.issues.push(issue);
+    }
+
+    let dataset = result.dataset;
+    report.train_views = dataset.train.views.len();
+    report.eval_views = dataset.eval.as_ref().map_or(0, |eval| eval.views.len());
+
+    let views = dataset
+        .train
+        .views
+        .iter()
+        .chain(dataset.eval.iter().flat_map(|eval| eval.views.iter()));
+
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for view in views {
+        *name_counts.entry(view.image.img_name()).or_default() += 1;
+        check_view(view, &mut report.issues).await;
+        brush_async::yield_now().await;
+    }
+
+    let mut duplicates: Vec<_> = name_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, count)| ValidationIssue::DuplicateName { name, count })
+        .collect();
+    duplicates.sort_by_key(|issue| issue.to_string());
+    report.issues.extend(duplicates);
+
+    report
+}
+
+async fn check_view(view: &SceneView, issues: &mut Vec<ValidationIssue>) {
+    let image = &view.image;
+    let name = image.img_name();
+
+    let actual = match image.dimensions().await {
+        Ok(dims) => dims,
+        Err(e) => {
+            issues.push(ValidationIssue::UnreadableImage {
+                path: image.path().to_path_buf(),
+                error: e.to_string(),
+            });
+            return;
+        }
+    };
+
+    if let Some(mask_dims) = image.mask_dimensions().await {
+        match mask_dims {
+            Ok(mask) if mask != actual => issues.push(ValidationIssue::MaskMismatch {
+                image: name.clone(),
+                mask,
+                actual,
+            }),
+            Ok(_) => {}
+            Err(e) => issues.push(ValidationIssue::UnreadableMask {
+                path: image.mask_path().unwrap_or(image.path()).to_path_buf(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    // Decoding also catches truncated files, which still have a valid header.
+    if let Err(e) = image.load().await {
+        issues.push(ValidationIssue::UnreadableImage {
+            path: image.path().to_path_buf(),
+            error: e.to_string(),
+        });
+        return;
+    }
+
+    if let Some(intrinsics) = view.intrinsics_size
+        && intrinsics != actual
+    {
+        issues.push(ValidationIssue::IntrinsicsMismatch {
+            image: name.clone(),
+            intrinsics,
+            actual,
+        });
+    }
+
+    let focal = view.camera.focal(glam::uvec2(actual.0, actual.1));
+    if !(focal.is_finite() && focal.min_element() >= 1.0) {
+        issues.push(ValidationIssue::DegenerateFocal {
+            image: name,
+            focal_x: focal.x,
+            focal_y: focal.y,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EvalSplitStrategy;
+    use crate::hdr::ToneMapping;
+    use std::io::Cursor;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn load_config() -> LoadDatasetConfig {
+        LoadDatasetConfig {
+            max_frames: None,
+            max_resolution: 1920,
+            eval_split_every: None,
+            eval_split_strategy: EvalSplitStrategy::EveryN,
+            subsample_frames: None,
+            subsample_points: None,
+            alpha_mode: None,
+            alpha_as_mask: false,
+            tone_mapping: ToneMapping::None,
+            max_scene_batch_cache_size: 0,
+        }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        image::RgbaImage::new(width, height)
+            .write_to(&mut data, image::ImageFormat::Png)
+            .expect("Failed to encode png");
+        data.into_inner()
+    }
+
+    fn frame(file_path: &str, extra: serde_json::Value) -> serde_json::Value {
+        let mut frame = serde_json::json!({
+            "file_path": file_path,
+            "transform_matrix": [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 4.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        });
+        frame
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        frame
+    }
+
+    fn transforms(frames: Vec<serde_json::Value>) -> (PathBuf, Vec<u8>) {
+        let json = serde_json::json!({ "camera_angle_x": 0.7, "frames": frames });
+        (
+            PathBuf::from("transforms.json"),
+            serde_json::to_vec(&json).unwrap(),
+        )
+    }
+
+    async fn validate(files: Vec<(PathBuf, Vec<u8>)>) -> ValidationReport {
+        let vfs = Arc::new(BrushVfs::create_test_vfs_with_data(files));
+        validate_dataset(vfs, &load_config()).await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn clean_dataset_has_no_issues() {
+        let report = validate(vec![
+            transforms(vec![
+                frame("a.png", serde_json::json!({})),
+                frame("b.png", serde_json::json!({})),
+            ]),
+            ("a.png".into(), png(8, 4)),
+            ("b.png".into(), png(8, 4)),
+        ])
+        .await;
+        assert_eq!(report.issues, vec![]);
+        assert_eq!(report.train_views, 2);
+        assert!(!report.has_blocking_issues());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn detects_broken_views() {
+        let report = validate(vec![
+            transforms(vec![
+                frame("ok.png", serde_json::json!({})),
+                frame("missing.png", serde_json::json!({})),
+                // Sized in the json, so the header isn't needed to load the dataset.
+                frame("garbage.png", serde_json::json!({ "w": 8.0, "h": 4.0 })),
+                frame("squashed.png", serde_json::json!({ "w": 8.0, "h": 8.0 })),
+                frame("half.png", serde_json::json!({ "w": 16.0, "h": 8.0 })),
+                frame(
+                    "zero_focal.png",
+                    serde_json::json!({ "fl_x": 0.0, "fl_y": 0.0, "camera_angle_x": null }),
+                ),
+                frame("sub/ok.png", serde_json::json!({})),
+                frame("masked.png", serde_json::json!({})),
+            ]),
+            ("ok.png".into(), png(8, 4)),
+            ("garbage.png".into(), b"not an image".to_vec()),
+            ("squashed.png".into(), png(8, 4)),
+            ("half.png".into(), png(8, 4)),
+            ("zero_focal.png".into(), png(8, 4)),
+            ("sub/ok.png".into(), png(8, 4)),
+            ("masked.png".into(), png(8, 4)),
+            ("masks/masked.png".into(), png(4, 4)),
+        ])
+        .await;
+
+        let mut missing = vec![];
+        let mut unreadable = vec![];
+        let mut degenerate = vec![];
+        let mut duplicates = vec![];
+        let mut mismatched = vec![];
+        let mut masks = vec![];
+        for issue in &report.issues {
+            match issue {
+                ValidationIssue::MissingImage { name } => missing.push(name.as_str()),
+                ValidationIssue::UnreadableImage { path, .. } => unreadable.push(path.clone()),
+                ValidationIssue::DegenerateFocal { image, .. } => degenerate.push(image.as_str()),
+                ValidationIssue::DuplicateName { name, count } => {
+                    duplicates.push((name.as_str(), *count));
+                }
+                ValidationIssue::IntrinsicsMismatch { image, .. } => {
+                    mismatched.push((image.as_str(), issue.severity()));
+                }
+                ValidationIssue::MaskMismatch { image, .. } => {
+                    masks.push((image.as_str(), issue.severity()));
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(missing, vec!["missing.png"]);
+        assert_eq!(unreadable, vec![PathBuf::from("garbage.png")]);
+        assert_eq!(degenerate, vec!["zero_focal.png"]);
+        assert_eq!(duplicates, vec![("ok.png", 2)]);
+        // A different aspect ratio can't be right, a uniform rescale might be.
+        assert_eq!(
+            mismatched,
+            vec![
+                ("squashed.png", Severity::Error),
+                ("half.png", Severity::Warning)
+            ]
+        );
+        assert_eq!(masks, vec![("masked.png", Severity::Error)]);
+        assert!(report.has_blocking_issues());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn unrecognized_dataset_fails_to_load() {
+        let report = validate(vec![("a.png".into(), png(8, 4))]).await;
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::LoadFailed(_)]
+        ));
+        assert!(report.has_blocking_issues());
+    }
+}
@@ -10,6 +10,9 @@
 //!   with optional background-compositing of GT (`gt_eff = gt + (1 - gt.a) * bg`)
 //!   and optional mask multiplication (`out = out * gt.a`) folded into the kernel.
 //! - [`image_loss_eval`]: forward-only loss map for non-differentiable backends.
+//! - [`ms_ssim`] / [`ms_ssim_eval`]: multi-scale SSIM over a pyramid of the
+//!   same kernels, with the GT downsampled in its packed form.
+//! - [`gt_alpha`]: the GT alpha, eg. to use as a mask.
 //!
 //! Backward recomputes SSIM partials inline so no per-pixel state survives
 //! across the autograd tape.
@@ -17,7 +20,7 @@
 use brush_cube::{MainBackend, MainBackendBase};
 use brush_render::burn_glue::{
     AutodiffMain, unwrap_ad_wgpu_float, unwrap_ad_wgpu_int, unwrap_wgpu_float, unwrap_wgpu_int,
-    wrap_ad_wgpu_float, wrap_wgpu_float,
+    wrap_ad_wgpu_float, wrap_wgpu_float, wrap_wgpu_int,
 };
 use burn::{
     backend::{
@@ -30,7 +33,7 @@ use burn::{
         tensor::{FloatTensor, IntTensor},
         wgpu::WgpuRuntime,
     },
-    tensor::{DType, Int, Shape, Tensor},
+    tensor::{DType, Int, Shape, Tensor, s},
 };
 use burn_cubecl::{
     CubeRuntime, fusion::FusionCubeRuntime, kernel::into_contiguous, tensor::CubeTensor,
@@ -49,17 +52,22 @@ mod kernels {
     use burn_cubecl::cubecl::frontend::IndexMutExpand;
     use burn_cubecl::cubecl::prelude::*;
 
-    /// 11-tap Gaussian weights at sigma = 1.5, normalised to sum to 1.
-    /// Called from `comptime!` so it runs once per kernel build, baking each
-    /// weight as an f32 literal into the generated kernel.
-    fn gauss_taps() -> [f32; 11] {
-        let sigma = 1.5_f32;
+    /// Gaussian weights for a `size`-tap window (odd, at most 11) at `sigma`
+    /// (passed as f32 bits so it can be a comptime value), laid out over the
+    /// fixed 11-tap footprint: taps outside the window are zero and the rest
+    /// are normalised to sum to 1. Called from `comptime!` so it runs once per
+    /// kernel build, baking each weight as an f32 literal into the kernel.
+    fn gauss_taps(size: u32, sigma_bits: u32) -> [f32; 11] {
+        let sigma = f32::from_bits(sigma_bits);
+        let radius = (size / 2) as f32;
         let mut w = [0.0_f32; 11];
         let mut sum = 0.0;
         for (i, w) in w.iter_mut().enumerate() {
             let x = i as f32 - 5.0;
-            *w = (-x * x / (2.0 * sigma * sigma)).exp();
-            sum += *w;
+            if x.abs() <= radius {
+                *w = (-x * x / (2.0 * sigma * sigma)).exp();
+                sum += *w;
+            }
         }
         for w in &mut w {
             *w /= sum;
@@ -162,8 +170,8 @@ mod kernels {
     }
 
     #[cube]
-    fn gw<F: Float>(#[comptime] i: u32) -> F {
-        F::new(comptime![gauss_taps()[i as usize]])
+    fn gw<F: Float>(#[comptime] i: u32, #[comptime] window: u32, #[comptime] sigma_bits: u32) -> F {
+        F::new(comptime![gauss_taps(window, sigma_bits)[i as usize]])
     }
 
     /// Forward: produce the L1 + SSIM loss map. When dispatched with `C = 4`,
@@ -176,6 +184,7 @@ mod kernels {
     ///   the source has real alpha and `bg != 0`; opaque/synthesised alpha or
     ///   zero bg make the math a no-op so callers gate it off to skip the work.
     /// - `mask`: multiply the loss-map output by `gt.a` per pixel.
+    /// - `window`, `sigma_bits`: the SSIM Gaussian window, see [`gauss_taps`].
     #[allow(clippy::assign_op_pattern)]
     #[cube(launch)]
     pub fn image_loss_forward_kernel<F: Float>(
@@ -191,6 +200,8 @@ mod kernels {
         bg_b: f32,
         #[comptime] composite: bool,
         #[comptime] mask: bool,
+        #[comptime] window: u32,
+        #[comptime] sigma_bits: u32,
     ) {
         let c = CUBE_POS_Z;
         let tile_y0 = CUBE_POS_Y * BLOCK_Y;
@@ -269,7 +280,7 @@ mod kernels {
                 let mut sum_xy = F::cast_from(0.0_f32);
                 #[unroll]
                 for d in 1u32..6u32 {
-                    let w_d = gw::<F>(comptime![5u32 - d]);
+                    let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
                     let il = (ly * SHARED_X + (lx - d)) as usize;
                     let ir = (ly * SHARED_X + (lx + d)) as usize;
                     let xl = s_tile[il * 2];
@@ -311,7 +322,7 @@ mod kernels {
         let mut out4 = F::cast_from(0.0_f32);
         #[unroll]
         for d in 1u32..6u32 {
-            let w_d = gw::<F>(comptime![5u32 - d]);
+            let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
             let bt = (((ly - d) * BLOCK_X + lx) * 5) as usize;
             let bb = (((ly + d) * BLOCK_X + lx) * 5) as usize;
             out0 += (x_conv[bt] + x_conv[bb]) * w_d;
@@ -382,6 +393,8 @@ mod kernels {
         bg_b: f32,
         #[comptime] composite: bool,
         #[comptime] mask: bool,
+        #[comptime] window: u32,
+        #[comptime] sigma_bits: u32,
     ) {
         let c = CUBE_POS_Z;
         let tile_y0 = CUBE_POS_Y * BLOCK_Y_BWD;
@@ -470,7 +483,7 @@ mod kernels {
                 let mut sum_xy = F::cast_from(0.0_f32);
                 #[unroll]
                 for d in 1u32..6u32 {
-                    let w_d = gw::<F>(comptime![5u32 - d]);
+                    let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
                     let il = ((row_y * EXT_X_BWD + (center - d)) * 2u32) as usize;
                     let ir = ((row_y * EXT_X_BWD + (center + d)) * 2u32) as usize;
                     let xl = buf_a[il];
@@ -520,7 +533,7 @@ mod kernels {
                 let mut out4 = F::cast_from(0.0_f32);
                 #[unroll]
                 for d in 1u32..6u32 {
-                    let w_d = gw::<F>(comptime![5u32 - d]);
+                    let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
                     let bt = (((center - d) * SHARED_X_BWD + part_x) * 5u32) as usize;
                     let bb = (((center + d) * SHARED_X_BWD + part_x) * 5u32) as usize;
                     out0 += (buf_b[bt] + buf_b[bb]) * w_d;
@@ -591,7 +604,7 @@ mod kernels {
                 let mut a2 = F::cast_from(0.0_f32);
                 #[unroll]
                 for d in 1u32..6u32 {
-                    let w_d = gw::<F>(comptime![5u32 - d]);
+                    let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
                     let il = ((ly_b * SHARED_X_BWD + (lx_b - d)) * 3u32) as usize;
                     let ir = ((ly_b * SHARED_X_BWD + (lx_b + d)) * 3u32) as usize;
                     a0 += (buf_a[il] + buf_a[ir]) * w_d;
@@ -620,7 +633,7 @@ mod kernels {
             let mut s2 = F::cast_from(0.0_f32);
             #[unroll]
             for d in 1u32..6u32 {
-                let w_d = gw::<F>(comptime![5u32 - d]);
+                let w_d = gw::<F>(comptime![5u32 - d], window, sigma_bits);
                 let bt = (((ly - d) * BLOCK_X_BWD + lx) * 3u32) as usize;
                 let bb = (((ly + d) * BLOCK_X_BWD + lx) * 3u32) as usize;
                 s0 += (buf_b[bt] + buf_b[bb]) * w_d;
@@ -694,6 +707,36 @@ mod kernels {
         out[base + 1] = F::cast_from(g);
         out[base + 2] = F::cast_from(b);
     }
+
+    /// Halve `gt_packed` by averaging each 2x2 block, per byte (alpha
+    /// included). An odd last row or column is dropped. Used to build the
+    /// MS-SSIM pyramid without unpacking the GT to f32.
+    #[allow(clippy::assign_op_pattern)]
+    #[cube(launch)]
+    pub fn downsample_gt_kernel(gt_packed: &Tensor<u32>, out: &mut Tensor<u32>, h: u32, w: u32) {
+        let pix_y = CUBE_POS_Y * BLOCK_Y + UNIT_POS_Y;
+        let pix_x = CUBE_POS_X * BLOCK_X + UNIT_POS_X;
+        if pix_x >= w || pix_y >= h {
+            terminate!();
+        }
+        let in_w = w * 2u32;
+        let row = pix_y * 2u32 * in_w + pix_x * 2u32;
+        let p00 = gt_packed[row as usize];
+        let p01 = gt_packed[(row + 1u32) as usize];
+        let p10 = gt_packed[(row + in_w) as usize];
+        let p11 = gt_packed[(row + in_w + 1u32) as usize];
+        let mut packed = 0u32;
+        #[unroll]
+        for byte in 0u32..4u32 {
+            let shift = byte * 8u32;
+            let sum = ((p00 >> shift) & 0xffu32)
+                + ((p01 >> shift) & 0xffu32)
+                + ((p10 >> shift) & 0xffu32)
+                + ((p11 >> shift) & 0xffu32);
+            packed = packed | (((sum + 2u32) / 4u32) << shift);
+        }
+        out[(pix_y * w + pix_x) as usize] = packed;
+    }
 }
 
 /// Image-loss configuration.
@@ -709,6 +752,40 @@ pub struct ImageLossConfig {
     pub composite_bg: Option<Vec3>,
     /// If true, multiply each loss-map pixel by `gt.a`.
     pub mask: bool,
+    pub ssim_window: SsimWindow,
+}
+
+/// The Gaussian window SSIM compares local statistics over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsimWindow {
+    /// Window width in pixels. Must be odd and between 3 and 11, the kernels
+    /// are built around an 11-tap footprint.
+    pub size: u32,
+    pub sigma: f32,
+}
+
+impl Default for SsimWindow {
+    /// The 11x11, sigma 1.5 window from the original SSIM paper.
+    fn default() -> Self {
+        Self {
+            size: 11,
+            sigma: 1.5,
+        }
+    }
+}
+
+impl SsimWindow {
+    pub const MAX_SIZE: u32 = 11;
+
+    fn comptime_args(self) -> (u32, u32) {
+        assert!(
+            self.size % 2 == 1 && (3..=Self::MAX_SIZE).contains(&self.size),
+            "SSIM window size must be odd and in 3..=11, got {}",
+            self.size
+        );
+        assert!(self.sigma > 0.0, "SSIM window sigma must be positive");
+        (self.size, self.sigma.to_bits())
+    }
 }
 
 /// Backend hooks for the loss kernels. When `pred` has 4 channels, the
@@ -730,6 +807,8 @@ pub trait LossOps<B: Backend> {
     ) -> FloatTensor<B>;
 
     fn unpack_gt_rgb(gt_packed: IntTensor<B>, composite_bg: Option<Vec3>) -> FloatTensor<B>;
+
+    fn downsample_gt(gt_packed: IntTensor<B>) -> IntTensor<B>;
 }
 
 fn alloc_zeros<R: CubeRuntime>(template: &CubeTensor<R>) -> CubeTensor<R> {
@@ -841,6 +920,7 @@ fn launch_image_forward<R: CubeRuntime>(
 
     let composite = cfg.composite_bg.is_some();
     let bg = cfg.composite_bg.unwrap_or(Vec3::ZERO);
+    let (window, sigma_bits) = cfg.ssim_window.comptime_args();
     let map = alloc_zeros(&pred);
     let client = pred.client.clone();
     kernels::image_loss_forward_kernel::launch::<f32, R>(
@@ -859,6 +939,8 @@ fn launch_image_forward<R: CubeRuntime>(
         bg.z,
         composite,
         cfg.mask,
+        window,
+        sigma_bits,
     );
     map
 }
@@ -880,6 +962,7 @@ fn launch_image_backward<R: CubeRuntime>(
 
     let composite = cfg.composite_bg.is_some();
     let bg = cfg.composite_bg.unwrap_or(Vec3::ZERO);
+    let (window, sigma_bits) = cfg.ssim_window.comptime_args();
     let dl_dpred = alloc_zeros(&pred);
     let client = pred.client.clone();
 
@@ -900,6 +983,8 @@ fn launch_image_backward<R: CubeRuntime>(
         bg.z,
         composite,
         cfg.mask,
+        window,
+        sigma_bits,
     );
     dl_dpred
 }
@@ -946,6 +1031,39 @@ fn launch_unpack_gt_rgb<R: CubeRuntime>(
     out
 }
 
+fn launch_downsample_gt<R: CubeRuntime>(gt_packed: CubeTensor<R>) -> CubeTensor<R> {
+    use burn_cubecl::cubecl::prelude::{CubeCount, CubeDim};
+
+    let gt_packed = into_contiguous(gt_packed);
+    let dims = gt_packed.shape().as_slice().to_vec();
+    assert_eq!(dims.len(), 2, "downsample_gt expects [H, W] gt_packed");
+    let (h, w) = ((dims[0] / 2) as u32, (dims[1] / 2) as u32);
+    assert!(h > 0 && w > 0, "downsample_gt needs at least a 2x2 image");
+
+    let client = gt_packed.client.clone();
+    let out = burn_cubecl::ops::numeric::zeros_client::<R>(
+        client.clone(),
+        gt_packed.device.clone(),
+        Shape::new([h as usize, w as usize]),
+        gt_packed.dtype,
+    );
+    let cube_count = CubeCount::Static(
+        w.div_ceil(kernels::BLOCK_X),
+        h.div_ceil(kernels::BLOCK_Y),
+        1,
+    );
+    kernels::downsample_gt_kernel::launch::<R>(
+        &client,
+        cube_count,
+        CubeDim::new_2d(kernels::BLOCK_X, kernels::BLOCK_Y),
+        gt_packed.into_tensor_arg(),
+        out.clone().into_tensor_arg(),
+        h,
+        w,
+    );
+    out
+}
+
 impl LossOps<Self> for MainBackendBase {
     fn image_loss_forward(
         pred: FloatTensor<Self>,
@@ -967,6 +1085,10 @@ impl LossOps<Self> for MainBackendBase {
     fn unpack_gt_rgb(gt_packed: IntTensor<Self>, composite_bg: Option<Vec3>) -> FloatTensor<Self> {
         launch_unpack_gt_rgb(gt_packed, composite_bg)
     }
+
+    fn downsample_gt(gt_packed: IntTensor<Self>) -> IntTensor<Self> {
+        launch_downsample_gt(gt_packed)
+    }
 }
 
 impl LossOps<Self> for Fusion<MainBackendBase> {
@@ -1035,6 +1157,23 @@ impl LossOps<Self> for Fusion<MainBackendBase> {
             },
         )
     }
+
+    fn downsample_gt(gt_packed: IntTensor<Self>) -> IntTensor<Self> {
+        let [gh, gw] = gt_packed.shape().dims();
+        let dtype = gt_packed.dtype;
+        dispatch_custom(
+            "downsample_gt",
+            [gt_packed],
+            Shape::new([gh / 2, gw / 2]),
+            dtype,
+            move |desc, h| {
+                let ([gt_packed], [out]) = desc.as_fixed();
+                let res =
+                    MainBackendBase::downsample_gt(h.get_int_tensor::<MainBackendBase>(gt_packed));
+                h.register_int_tensor::<MainBackendBase>(&out.id, res);
+            },
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -1128,3 +1267,95 @@ pub fn unpack_gt_rgb(gt_packed: Tensor<2, Int>, composite_bg: Option<Vec3>) -> T
     let out = <MainBackend as LossOps<MainBackend>>::unpack_gt_rgb(gt_p, composite_bg);
     wrap_wgpu_float(out)
 }
+
+/// Halve `gt_packed` by averaging 2x2 blocks, keeping the packed format. An
+/// odd last row or column is dropped. Works for GT on either an autodiff or a
+/// plain device, the result is always on the plain one (ints aren't tracked).
+pub fn downsample_gt(gt_packed: Tensor<2, Int>) -> Tensor<2, Int> {
+    let gt_p = unwrap_ad_wgpu_int(gt_packed);
+    wrap_wgpu_int(<MainBackend as LossOps<MainBackend>>::downsample_gt(gt_p))
+}
+
+/// Per-scale weights of MS-SSIM (Wang et al. 2003) for its three finest
+/// scales. Renormalised to sum to 1 over the scales that are used.
+const MS_SSIM_WEIGHTS: [f32; 3] = [0.0448, 0.2856, 0.3001];
+
+fn ms_ssim_with(
+    pred: Tensor<3>,
+    gt_packed: Tensor<2, Int>,
+    cfg: ImageLossConfig,
+    loss: impl Fn(Tensor<3>, Tensor<2, Int>, ImageLossConfig) -> Tensor<3>,
+    masked_mean: bool,
+) -> Tensor<1> {
+    let cfg = ImageLossConfig {
+        l1_weight: 0.0,
+        ssim_weight: 1.0,
+        ..cfg
+    };
+    let mut pred = pred.slice(s![.., .., 0..3]);
+    let mut gt_packed = gt_packed;
+    let mut weighted_log = None;
+    let mut weight_sum = 0.0;
+
+    for (scale, weight) in MS_SSIM_WEIGHTS.into_iter().enumerate() {
+        if scale > 0 {
+            let [h, w, c] = pred.dims();
+            // Stop early for images too small for another level.
+            if h < 2 * cfg.ssim_window.size as usize || w < 2 * cfg.ssim_window.size as usize {
+                break;
+            }
+            let (h, w) = (h / 2, w / 2);
+            pred = pred
+                .slice(s![0..h * 2, 0..w * 2, ..])
+                .reshape([h, 2, w, 2, c])
+                .mean_dim(3)
+                .mean_dim(1)
+                .reshape([h, w, c]);
+            gt_packed = downsample_gt(gt_packed);
+        }
+        // SSIM can go (slightly) negative for anti-correlated images, clamp
+        // before taking the weighted geometric mean.
+        let map = loss(pred.clone(), gt_packed.clone(), cfg);
+        let ssim = if masked_mean {
+            let valid = gt_alpha(gt_packed.clone()).sum() * 3.0;
+            map.sum() / valid.clamp_min(1e-6)
+        } else {
+            map.mean()
+        };
+        let term = ssim.clamp_min(1e-4).log() * weight;
+        weighted_log = Some(match weighted_log {
+            Some(acc) => acc + term,
+            None => term,
+        });
+        weight_sum += weight;
+    }
+
+    (weighted_log.expect("At least one scale is always evaluated") / weight_sum).exp()
+}
+
+/// Multi-scale SSIM between `pred` (`[H, W, C]`, RGB(A), alpha is ignored)
+/// and `gt_packed`, evaluated at up to 3 scales halving the resolution each
+/// time. Bg-compositing, masking and the SSIM window are taken from `cfg`;
+/// its loss weights are ignored. Returns a scalar in `(0, 1]`.
+///
+/// `pred` must be on an autodiff-enabled Wgpu device.
+pub fn ms_ssim(pred: Tensor<3>, gt_packed: Tensor<2, Int>, cfg: ImageLossConfig) -> Tensor<1> {
+    ms_ssim_with(pred, gt_packed, cfg, image_loss, false)
+}
+
+/// Forward-only [`ms_ssim`] for non-differentiable backends.
+///
+/// As this is used as a metric, with `cfg.mask` set each scale is averaged
+/// over the valid (GT alpha) pixels only, rather than over the whole image.
+pub fn ms_ssim_eval(pred: Tensor<3>, gt_packed: Tensor<2, Int>, cfg: ImageLossConfig) -> Tensor<1> {
+    ms_ssim_with(pred, gt_packed, cfg, image_loss_eval, cfg.mask)
+}
+
+/// The `[H, W]` alpha channel of `gt_packed`, in `[0, 1]`.
+pub fn gt_alpha(gt_packed: Tensor<2, Int>) -> Tensor<2> {
+    gt_packed
+        .bitwise_right_shift_scalar(24)
+        .bitwise_and_scalar(0xff)
+        .float()
+        / 255.0
+}
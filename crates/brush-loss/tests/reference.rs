@@ -2,10 +2,12 @@
 //!
 //! GT lives as `[H, W]` u32 packing `[r g b a]` u8. We feed deterministic u8
 //! data through `image_loss` and check structural properties (`SSIM(x, x) ≈ 1`,
-//! output range, backward produces finite gradients). Bit-exact reference
+//! output range, backward produces finite gradients, MS-SSIM behaviour). Bit-exact reference
 //! matching is covered by the integration training tests in `brush-bench-test`.
 
-use brush_loss::{ImageLossConfig, image_loss};
+use brush_loss::{
+    ImageLossConfig, SsimWindow, downsample_gt, image_loss, image_loss_eval, ms_ssim, ms_ssim_eval,
+};
 use burn::tensor::{Device, Int, Tensor, TensorData};
 use wasm_bindgen_test::wasm_bindgen_test;
 
@@ -50,6 +52,7 @@ fn ssim_only_cfg() -> ImageLossConfig {
         ssim_weight: 1.0,
         composite_bg: None,
         mask: false,
+        ssim_window: SsimWindow::default(),
     }
 }
 
@@ -119,6 +122,7 @@ async fn image_loss_backward_runs() {
             ssim_weight: -0.2,
             composite_bg: None,
             mask: false,
+            ssim_window: SsimWindow::default(),
         },
     );
     let grads = map.mean().backward();
@@ -162,7 +166,118 @@ async fn alpha_match_via_4ch_pred() {
             ssim_weight: 0.0,
             composite_bg: None,
             mask: false,
+            ssim_window: SsimWindow::default(),
         },
     );
     let _grads = map.mean().backward();
 }
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn ms_ssim_identical_inputs_is_one() {
+    let device =
+        burn::tensor::Device::from(brush_cube::test_helpers::test_device().await).autodiff();
+    let (h, w) = (64, 96);
+    let bytes = make_pattern(h, w, 11, 13);
+    let pred = pred_from_bytes(&bytes, h, w, &device).require_grad();
+    let gt = gt_packed_from_bytes(&bytes, h, w, &device);
+
+    let ms = ms_ssim(pred.clone(), gt, ssim_only_cfg());
+    let grads = ms.clone().backward();
+    assert!(
+        pred.grad(&grads).is_some(),
+        "MS-SSIM should be differentiable"
+    );
+    let ms: f32 = ms.into_scalar_async().await.expect("readback");
+    // The coarser GT levels are rounded back to u8, so allow a little slack.
+    assert!(
+        (ms - 1.0).abs() < 1e-3,
+        "MS-SSIM(x, x) should be 1, got {ms}"
+    );
+}
+
+/// A smooth image with a 1px checkerboard on top. The checkerboard averages
+/// out exactly over every 2x2 block, so it only exists at the finest scale.
+fn smooth_with_detail(h: usize, w: usize, detail: bool) -> Vec<u8> {
+    (0..h)
+        .flat_map(|y| (0..w).map(move |x| (y, x)))
+        .flat_map(|(y, x)| {
+            let smooth = 0.5 + 0.3 * (x as f32 / 6.0).sin() * (y as f32 / 5.0).cos();
+            let checker = if (x + y) % 2 == 0 { 0.15 } else { -0.15 };
+            let v = smooth + if detail { checker } else { 0.0 };
+            let v = (v * 255.0).round() as u8;
+            [v, v, v, 255]
+        })
+        .collect()
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn ms_ssim_blur_penalised_at_fine_scale() {
+    let device = burn::tensor::Device::from(brush_cube::test_helpers::test_device().await);
+    let (h, w) = (64, 64);
+    // The prediction is the GT with its fine detail blurred away.
+    let pred = pred_from_bytes(&smooth_with_detail(h, w, false), h, w, &device);
+    let gt_bytes = smooth_with_detail(h, w, true);
+    let gt = gt_packed_from_bytes(&gt_bytes, h, w, &device);
+
+    let plain: f32 = image_loss_eval(pred.clone(), gt.clone(), ssim_only_cfg())
+        .mean()
+        .into_scalar_async()
+        .await
+        .expect("readback");
+
+    let coarse_pred = pred
+        .clone()
+        .reshape([h / 2, 2, w / 2, 2, 3])
+        .mean_dim(3)
+        .mean_dim(1)
+        .reshape([h / 2, w / 2, 3]);
+    let coarse: f32 = image_loss_eval(coarse_pred, downsample_gt(gt.clone()), ssim_only_cfg())
+        .mean()
+        .into_scalar_async()
+        .await
+        .expect("readback");
+
+    let ms: f32 = ms_ssim_eval(pred, gt, ssim_only_cfg())
+        .into_scalar_async()
+        .await
+        .expect("readback");
+
+    assert!(
+        plain < coarse,
+        "blur should only hurt the fine scale: fine {plain}, coarse {coarse}"
+    );
+    assert!(
+        ms > plain,
+        "MS-SSIM should weigh the fine scale less than plain SSIM: ms {ms}, plain {plain}"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn ssim_window_size_changes_score() {
+    let device = burn::tensor::Device::from(brush_cube::test_helpers::test_device().await);
+    let (h, w) = (32, 48);
+    let pred = pred_from_bytes(&make_pattern(h, w, 7, 19), h, w, &device);
+    let gt = gt_packed_from_bytes(&make_pattern(h, w, 13, 7), h, w, &device);
+
+    let mut scores = vec![];
+    for size in [3, 7, 11] {
+        let cfg = ImageLossConfig {
+            ssim_window: SsimWindow { size, sigma: 1.5 },
+            ..ssim_only_cfg()
+        };
+        let ssim: f32 = image_loss_eval(pred.clone(), gt.clone(), cfg)
+            .mean()
+            .into_scalar_async()
+            .await
+            .expect("readback");
+        assert!(
+            ssim.is_finite(),
+            "SSIM with a {size}px window is not finite"
+        );
+        scores.push(ssim);
+    }
+    assert!(
+        scores.windows(2).all(|s| s[0] != s[1]),
+        "The window size should affect SSIM: {scores:?}"
+    );
+}
@@ -16,9 +16,18 @@ fn linear_global_id() -> usize {
     ABSOLUTE_POS
 }
 
+/// Inclusive scan of a workgroup, written to `output[id]`. When `exclusive`,
+/// it's written to `output[id + 1]` instead, which shifts the inclusive scan
+/// into an exclusive one.
 #[cube]
-fn group_scan(id: usize, gi: usize, x: u32, output: &mut Tensor<u32>) {
-    let mut bucket = Shared::new_slice(THREADS_PER_GROUP);
+fn group_scan<N: Numeric>(
+    id: usize,
+    gi: usize,
+    x: N,
+    output: &mut Tensor<N>,
+    #[comptime] exclusive: bool,
+) {
+    let mut bucket = Shared::<N>::new_slice(THREADS_PER_GROUP);
     bucket[gi] = x;
 
     let mut t = 1;
@@ -32,43 +41,165 @@ fn group_scan(id: usize, gi: usize, x: u32, output: &mut Tensor<u32>) {
         bucket[gi] = temp;
         t *= 2;
     }
+    if comptime![exclusive] {
+        if id == 0 {
+            output[0] = N::from_int(0);
+        }
+        if id + 1 < output.len() {
+            output[id + 1] = bucket[gi];
+        }
+    } else if id < output.len() {
+        output[id] = bucket[gi];
+    }
+}
+
+#[cube(launch)]
+pub fn prefix_sum_scan_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    output: &mut Tensor<N>,
+    #[comptime] exclusive: bool,
+) {
+    let id = linear_global_id();
+
+    let mut x = N::from_int(0);
+    if id < input.len() {
+        x = input[id];
+    }
+
+    group_scan(id, UNIT_POS as usize, x, output, exclusive);
+}
+
+/// Scan the totals of each group of `input`. When `input` is a shifted
+/// (exclusive) scan, a group's total is one element further along.
+#[cube(launch)]
+pub fn prefix_sum_scan_sums_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    output: &mut Tensor<N>,
+    #[comptime] exclusive: bool,
+) {
+    let id = linear_global_id();
+    // id * THREADS_PER_GROUP - 1, gated on id != 0 to avoid underflow.
+    let mut x = N::from_int(0);
+    if id != 0 {
+        let idx = if comptime![exclusive] {
+            id * THREADS_PER_GROUP
+        } else {
+            id * THREADS_PER_GROUP - 1
+        };
+        if idx < input.len() {
+            x = input[idx];
+        }
+    }
+    group_scan(id, UNIT_POS as usize, x, output, false);
+}
+
+#[cube(launch)]
+pub fn prefix_sum_add_scanned_sums_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    output: &mut Tensor<N>,
+    #[comptime] exclusive: bool,
+) {
+    let id = linear_global_id();
+    let workgroup_id = linear_workgroup_id();
+
+    // Exclusive scans were written shifted by one, follow them.
+    let out_id = if comptime![exclusive] { id + 1 } else { id };
+    if out_id < output.len() {
+        output[out_id] += input[workgroup_id];
+    }
+}
+
+/// Segmented version of [`group_scan`], where a nonzero flag starts a new
+/// segment and resets the running sum. Also writes whether any segment started
+/// in the group up to each element, which decides if later carries apply.
+#[cube]
+fn segmented_group_scan<N: Numeric>(
+    id: usize,
+    gi: usize,
+    x: N,
+    flag: u32,
+    output: &mut Tensor<N>,
+    out_flags: &mut Tensor<u32>,
+) {
+    let mut bucket = Shared::<N>::new_slice(THREADS_PER_GROUP);
+    let mut heads = Shared::<u32>::new_slice(THREADS_PER_GROUP);
+    bucket[gi] = x;
+    heads[gi] = flag;
+
+    let mut t = 1;
+    while t < THREADS_PER_GROUP {
+        sync_cube();
+        let mut temp = bucket[gi];
+        let mut head = heads[gi];
+        if gi >= t {
+            if head == 0u32 {
+                temp += bucket[gi - t];
+            }
+            head |= heads[gi - t];
+        }
+        sync_cube();
+        bucket[gi] = temp;
+        heads[gi] = head;
+        t *= 2;
+    }
     if id < output.len() {
         output[id] = bucket[gi];
+        out_flags[id] = heads[gi];
     }
 }
 
 #[cube(launch)]
-pub fn prefix_sum_scan_kernel(input: &Tensor<u32>, output: &mut Tensor<u32>) {
+pub fn segmented_scan_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    flags: &Tensor<u32>,
+    output: &mut Tensor<N>,
+    out_flags: &mut Tensor<u32>,
+) {
     let id = linear_global_id();
 
-    let mut x = 0u32;
+    let mut x = N::from_int(0);
+    let mut flag = 0u32;
     if id < input.len() {
         x = input[id];
+        flag = flags[id];
     }
 
-    group_scan(id, UNIT_POS as usize, x, output);
+    segmented_group_scan(id, UNIT_POS as usize, x, flag, output, out_flags);
 }
 
+/// Scan the (total, has segment start) pairs of each group of `input`.
 #[cube(launch)]
-pub fn prefix_sum_scan_sums_kernel(input: &Tensor<u32>, output: &mut Tensor<u32>) {
+pub fn segmented_scan_sums_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    flags: &Tensor<u32>,
+    output: &mut Tensor<N>,
+    out_flags: &mut Tensor<u32>,
+) {
     let id = linear_global_id();
     // id * THREADS_PER_GROUP - 1, gated on id != 0 to avoid underflow.
-    let mut x = 0u32;
+    let mut x = N::from_int(0);
+    let mut flag = 0u32;
     if id != 0 {
         let idx = id * THREADS_PER_GROUP - 1;
         if idx < input.len() {
             x = input[idx];
+            flag = flags[idx];
         }
     }
-    group_scan(id, UNIT_POS as usize, x, output);
+    segmented_group_scan(id, UNIT_POS as usize, x, flag, output, out_flags);
 }
 
+/// Add the carry of the previous groups, up to the first segment start.
 #[cube(launch)]
-pub fn prefix_sum_add_scanned_sums_kernel(input: &Tensor<u32>, output: &mut Tensor<u32>) {
+pub fn segmented_add_scanned_sums_kernel<N: Numeric>(
+    input: &Tensor<N>,
+    flags: &Tensor<u32>,
+    output: &mut Tensor<N>,
+) {
     let id = linear_global_id();
     let workgroup_id = linear_workgroup_id();
 
-    if id < output.len() {
+    if id < output.len() && flags[id] == 0u32 {
         output[id] += input[workgroup_id];
     }
 }
@@ -3,12 +3,38 @@ mod kernels;
 use brush_cube::calc_cube_count_1d;
 use brush_cube::create_tensor;
 use burn::backend::TensorMetadata;
+use burn::tensor::DType;
 use burn_cubecl::cubecl::CubeDim;
+use burn_cubecl::cubecl::prelude::Numeric;
 use burn_wgpu::CubeTensor;
 use burn_wgpu::WgpuRuntime;
 use kernels::THREADS_PER_GROUP;
 
+/// Inclusive prefix sum, `output[i]` is the sum of `input[0..=i]`. Works on
+/// u32/i32 and f32 tensors.
 pub fn prefix_sum(input: CubeTensor<WgpuRuntime>) -> CubeTensor<WgpuRuntime> {
+    prefix_sum_dtype(input, false)
+}
+
+/// Exclusive prefix sum, `output[i]` is the sum of `input[0..i]`, so
+/// `output[0]` is zero. Useful to get offset tables from counts directly.
+pub fn prefix_sum_exclusive(input: CubeTensor<WgpuRuntime>) -> CubeTensor<WgpuRuntime> {
+    prefix_sum_dtype(input, true)
+}
+
+fn prefix_sum_dtype(input: CubeTensor<WgpuRuntime>, exclusive: bool) -> CubeTensor<WgpuRuntime> {
+    match input.dtype {
+        DType::F32 => prefix_sum_impl::<f32>(input, exclusive),
+        // Two's complement sums are the same for signed & unsigned ints.
+        DType::I32 | DType::U32 => prefix_sum_impl::<u32>(input, exclusive),
+        dtype => panic!("Unsupported prefix sum dtype {dtype:?}"),
+    }
+}
+
+fn prefix_sum_impl<N: Numeric>(
+    input: CubeTensor<WgpuRuntime>,
+    exclusive: bool,
+) -> CubeTensor<WgpuRuntime> {
     assert!(input.is_contiguous(), "Please ensure input is contiguous");
 
     let num = input.shape()[0];
@@ -17,12 +43,13 @@ pub fn prefix_sum(input: CubeTensor<WgpuRuntime>) -> CubeTensor<WgpuRuntime> {
 
     let cube_dim = CubeDim::new_1d(THREADS_PER_GROUP as u32);
 
-    kernels::prefix_sum_scan_kernel::launch::<WgpuRuntime>(
+    kernels::prefix_sum_scan_kernel::launch::<N, WgpuRuntime>(
         &client,
         calc_cube_count_1d(num as u32, THREADS_PER_GROUP as u32),
         cube_dim,
         input.into_tensor_arg(),
         outputs.clone().into_tensor_arg(),
+        exclusive,
     );
 
     if num <= THREADS_PER_GROUP {
@@ -38,37 +65,40 @@ pub fn prefix_sum(input: CubeTensor<WgpuRuntime>) -> CubeTensor<WgpuRuntime> {
         work_size.push(work_sz);
     }
 
-    kernels::prefix_sum_scan_sums_kernel::launch::<WgpuRuntime>(
+    kernels::prefix_sum_scan_sums_kernel::launch::<N, WgpuRuntime>(
         &client,
         calc_cube_count_1d(work_size[0] as u32, THREADS_PER_GROUP as u32),
         cube_dim,
         outputs.clone().into_tensor_arg(),
         group_buffer[0].clone().into_tensor_arg(),
+        exclusive,
     );
 
     for l in 0..(group_buffer.len() - 1) {
-        kernels::prefix_sum_scan_sums_kernel::launch::<WgpuRuntime>(
+        kernels::prefix_sum_scan_sums_kernel::launch::<N, WgpuRuntime>(
             &client,
             calc_cube_count_1d(work_size[l + 1] as u32, THREADS_PER_GROUP as u32),
             cube_dim,
             group_buffer[l].clone().into_tensor_arg(),
             group_buffer[l + 1].clone().into_tensor_arg(),
+            false,
         );
     }
 
     for l in (1..group_buffer.len()).rev() {
         let work_sz = work_size[l - 1];
 
-        kernels::prefix_sum_add_scanned_sums_kernel::launch::<WgpuRuntime>(
+        kernels::prefix_sum_add_scanned_sums_kernel::launch::<N, WgpuRuntime>(
             &client,
             calc_cube_count_1d(work_sz as u32, THREADS_PER_GROUP as u32),
             cube_dim,
             group_buffer[l].clone().into_tensor_arg(),
             group_buffer[l - 1].clone().into_tensor_arg(),
+            false,
         );
     }
 
-    kernels::prefix_sum_add_scanned_sums_kernel::launch::<WgpuRuntime>(
+    kernels::prefix_sum_add_scanned_sums_kernel::launch::<N, WgpuRuntime>(
         &client,
         calc_cube_count_1d(
             (work_size[0] * THREADS_PER_GROUP) as u32,
@@ -77,16 +107,108 @@ pub fn prefix_sum(input: CubeTensor<WgpuRuntime>) -> CubeTensor<WgpuRuntime> {
         cube_dim,
         group_buffer[0].clone().into_tensor_arg(),
         outputs.clone().into_tensor_arg(),
+        exclusive,
     );
 
     outputs
 }
 
+/// Segmented inclusive prefix sum. A nonzero `segment_flags[i]` starts a new
+/// segment at `i`, resetting the running sum, so each segment is scanned
+/// independently. Works on u32/i32 and f32 values, flags are u32.
+pub fn segmented_prefix_sum(
+    input: CubeTensor<WgpuRuntime>,
+    segment_flags: CubeTensor<WgpuRuntime>,
+) -> CubeTensor<WgpuRuntime> {
+    match input.dtype {
+        DType::F32 => segmented_prefix_sum_impl::<f32>(input, segment_flags),
+        DType::I32 | DType::U32 => segmented_prefix_sum_impl::<u32>(input, segment_flags),
+        dtype => panic!("Unsupported prefix sum dtype {dtype:?}"),
+    }
+}
+
+fn segmented_prefix_sum_impl<N: Numeric>(
+    input: CubeTensor<WgpuRuntime>,
+    segment_flags: CubeTensor<WgpuRuntime>,
+) -> CubeTensor<WgpuRuntime> {
+    assert!(
+        input.is_contiguous() && segment_flags.is_contiguous(),
+        "Please ensure input and flags are contiguous"
+    );
+    assert_eq!(
+        input.shape(),
+        segment_flags.shape(),
+        "Input and flags must have the same shape"
+    );
+
+    let num = input.shape()[0];
+    let client = input.client.clone();
+    let device = input.device.clone();
+    let outputs = create_tensor([num], &device, input.dtype);
+    // Whether a segment started in the element's group up to the element.
+    let out_flags = create_tensor([num], &device, DType::U32);
+
+    let cube_dim = CubeDim::new_1d(THREADS_PER_GROUP as u32);
+
+    kernels::segmented_scan_kernel::launch::<N, WgpuRuntime>(
+        &client,
+        calc_cube_count_1d(num as u32, THREADS_PER_GROUP as u32),
+        cube_dim,
+        input.into_tensor_arg(),
+        segment_flags.into_tensor_arg(),
+        outputs.clone().into_tensor_arg(),
+        out_flags.clone().into_tensor_arg(),
+    );
+
+    if num <= THREADS_PER_GROUP {
+        return outputs;
+    }
+
+    // Each level holds the scanned (sum, has segment start) pairs of the groups
+    // of the level below.
+    let mut levels = vec![(outputs.clone(), out_flags)];
+    let mut work_sz = num;
+    while work_sz > THREADS_PER_GROUP {
+        work_sz = work_sz.div_ceil(THREADS_PER_GROUP);
+        let (below, below_flags) = levels.last().expect("At least one level").clone();
+        let sums = create_tensor([work_sz], &device, outputs.dtype);
+        let sum_flags = create_tensor([work_sz], &device, DType::U32);
+
+        kernels::segmented_scan_sums_kernel::launch::<N, WgpuRuntime>(
+            &client,
+            calc_cube_count_1d(work_sz as u32, THREADS_PER_GROUP as u32),
+            cube_dim,
+            below.into_tensor_arg(),
+            below_flags.into_tensor_arg(),
+            sums.clone().into_tensor_arg(),
+            sum_flags.clone().into_tensor_arg(),
+        );
+        levels.push((sums, sum_flags));
+    }
+
+    for l in (1..levels.len()).rev() {
+        let (carries, _) = levels[l].clone();
+        let (below, below_flags) = levels[l - 1].clone();
+        let below_len = below.shape()[0];
+
+        kernels::segmented_add_scanned_sums_kernel::launch::<N, WgpuRuntime>(
+            &client,
+            calc_cube_count_1d(below_len as u32, THREADS_PER_GROUP as u32),
+            cube_dim,
+            carries.into_tensor_arg(),
+            below_flags.into_tensor_arg(),
+            below.into_tensor_arg(),
+        );
+    }
+
+    outputs
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::prefix_sum;
+    use crate::{prefix_sum, prefix_sum_exclusive, segmented_prefix_sum};
     use brush_cube::{MainBackendBase, create_tensor_from_slice};
-    use burn::backend::ops::IntTensorOps;
+    use burn::backend::ops::{FloatTensorOps, IntTensorOps};
     use burn::tensor::DType;
     use burn_wgpu::{CubeTensor, WgpuRuntime};
     use wasm_bindgen_test::wasm_bindgen_test;
@@ -158,6 +280,121 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sum_exclusive() {
+        const ITERS: usize = 512 * 16 + 123;
+        let mut data = vec![];
+        for i in 0..ITERS {
+            data.push(2 + i as i32);
+            data.push(0);
+            data.push(32);
+            data.push(512);
+            data.push(30965);
+        }
+
+        let device = brush_cube::test_helpers::test_device().await;
+        // Cover both the single group and the multi level path.
+        for len in [4, 512, 513, data.len()] {
+            let data = &data[..len];
+            let keys = create_tensor_from_slice(data, &device, DType::I32);
+            let summed = read_i32(prefix_sum_exclusive(keys)).await;
+
+            let prefix_sum_ref: Vec<_> = data
+                .iter()
+                .scan(0, |x, y| {
+                    let prev = *x;
+                    *x += y;
+                    Some(prev)
+                })
+                .collect();
+            assert_eq!(summed, prefix_sum_ref);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sum_f32() {
+        const NUM: usize = 512 * 16 + 123;
+        let data: Vec<f32> = (0..NUM).map(|i| 0.5 + (i % 100) as f32 * 0.01).collect();
+
+        let device = brush_cube::test_helpers::test_device().await;
+        for exclusive in [false, true] {
+            let input = create_tensor_from_slice(&data, &device, DType::F32);
+            let summed = if exclusive {
+                prefix_sum_exclusive(input)
+            } else {
+                prefix_sum(input)
+            };
+            let summed = MainBackendBase::float_into_data(summed)
+                .await
+                .expect("readback")
+                .into_vec::<f32>()
+                .expect("Wrong type");
+
+            let mut total = 0.0f64;
+            for (i, (&summed, &x)) in summed.iter().zip(&data).enumerate() {
+                if !exclusive {
+                    total += x as f64;
+                }
+                // Accumulation order differs from the CPU, allow some drift.
+                let tol = 1e-5 * total.max(1.0);
+                assert!(
+                    (summed as f64 - total).abs() <= tol,
+                    "Mismatch at {i}: got {summed}, expected {total}"
+                );
+                if exclusive {
+                    total += x as f64;
+                }
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_segmented_sum() {
+        // Segments of varying length, some spanning several groups and levels.
+        let segment_lens = [1, 5, 511, 512, 513, 3000, 1, 1, 70_000, 17, 2048];
+        let mut data = vec![];
+        let mut flags = vec![];
+        for (s, &len) in segment_lens.iter().enumerate() {
+            for i in 0..len {
+                data.push(1 + ((i + s) % 7) as i32);
+                flags.push(u32::from(i == 0));
+            }
+        }
+
+        let device = brush_cube::test_helpers::test_device().await;
+        let input = create_tensor_from_slice(&data, &device, DType::I32);
+        let flags_tensor = create_tensor_from_slice(&flags, &device, DType::U32);
+        let summed = read_i32(segmented_prefix_sum(input, flags_tensor)).await;
+
+        let mut total = 0;
+        let reference: Vec<i32> = data
+            .iter()
+            .zip(&flags)
+            .map(|(&x, &flag)| {
+                if flag != 0 {
+                    total = 0;
+                }
+                total += x;
+                total
+            })
+            .collect();
+        assert_eq!(summed, reference);
+
+        // Without any flags it's a plain prefix sum, from the start of the data.
+        let data = &data[1000..1500];
+        let input = create_tensor_from_slice(data, &device, DType::I32);
+        let flags_tensor = create_tensor_from_slice(&vec![0u32; data.len()], &device, DType::U32);
+        let summed = read_i32(segmented_prefix_sum(input, flags_tensor)).await;
+        let reference: Vec<i32> = data
+            .iter()
+            .scan(0, |x, y| {
+                *x += y;
+                Some(*x)
+            })
+            .collect();
+        assert_eq!(summed, reference);
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_sum_large() {
         // Test with 20M elements to verify 2D dispatch works correctly.
@@ -0,0 +1,34 @@
+//! Render a PLY file to a PNG, without the rest of the Brush process loop.
+//!
+//! Usage: `cargo run -p brush-process --example render_ply -- input.ply output.png [distance]`
+//!
+//! The camera looks along +Z at the origin from `distance` units away.
+
+use brush_process::render::{RenderOptions, render_ply_to_image};
+use brush_render::{camera::Camera, kernels::camera_model::CameraModel};
+use glam::{Quat, Vec3};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(input), Some(output)) = (args.next(), args.next()) else {
+        anyhow::bail!("Usage: render_ply <input.ply> <output.png> [distance]");
+    };
+    let distance: f32 = args.next().map_or(Ok(5.0), |d| d.parse())?;
+
+    let device: burn::tensor::Device = brush_process::burn_init_setup().await.into();
+    let ply = tokio::fs::read(&input).await?;
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -distance),
+        Quat::IDENTITY,
+        0.8,
+        0.8,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let opts = RenderOptions::default();
+    let img = render_ply_to_image(&ply, &camera, glam::uvec2(1280, 1280), opts, &device).await?;
+    img.save(&output)?;
+    println!("Saved render to {output}");
+    Ok(())
+}
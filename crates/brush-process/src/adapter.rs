@@ -0,0 +1,124 @@
+//! Picking the GPU adapter to train on, for machines with more than one GPU.
+
+use thiserror::Error;
+
+/// The parts of an adapter needed to pick one. Implemented for
+/// [`wgpu::AdapterInfo`], and abstracted so selection can be tested without a GPU.
+pub trait AdapterDescription {
+    fn name(&self) -> &str;
+    /// One line summary for logs and error messages.
+    fn summary(&self) -> String;
+}
+
+impl AdapterDescription for wgpu::AdapterInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn summary(&self) -> String {
+        format!("{} ({:?}, {:?})", self.name, self.backend, self.device_type)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AdapterError {
+    #[error("No GPU adapter matches '{selector}'. Available adapters:\n{available}")]
+    NotFound { selector: String, available: String },
+    #[error("'{selector}' matches multiple GPU adapters, pick one by index:\n{available}")]
+    Ambiguous { selector: String, available: String },
+    #[error("Failed to create a device on the selected GPU adapter: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+}
+
+fn adapter_list(adapters: &[impl AdapterDescription]) -> String {
+    adapters
+        .iter()
+        .enumerate()
+        .map(|(i, a)| format!("  {i}: {}", a.summary()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Pick an adapter by `selector`: either its index in `adapters`, or a case
+/// insensitive part of its name that matches exactly one adapter.
+pub fn select_adapter(
+    selector: &str,
+    adapters: &[impl AdapterDescription],
+) -> Result<usize, AdapterError> {
+    let selector = selector.trim();
+    let matches: Vec<_> = if let Ok(index) = selector.parse::<usize>() {
+        (index < adapters.len())
+            .then_some(index)
+            .into_iter()
+            .collect()
+    } else {
+        let needle = selector.to_lowercase();
+        adapters
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| a.name().to_lowercase().contains(&needle))
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        [] => Err(AdapterError::NotFound {
+            selector: selector.to_owned(),
+            available: adapter_list(adapters),
+        }),
+        _ => Err(AdapterError::Ambiguous {
+            selector: selector.to_owned(),
+            available: adapter_list(adapters),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockAdapter(&'static str);
+
+    impl AdapterDescription for MockAdapter {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn summary(&self) -> String {
+            self.0.to_owned()
+        }
+    }
+
+    const ADAPTERS: [MockAdapter; 3] = [
+        MockAdapter("Intel(R) UHD Graphics 770"),
+        MockAdapter("NVIDIA GeForce RTX 4090"),
+        MockAdapter("NVIDIA GeForce RTX 3060"),
+    ];
+
+    #[test]
+    fn select_by_index() {
+        assert_eq!(select_adapter("1", &ADAPTERS).unwrap(), 1);
+        assert_eq!(select_adapter(" 0 ", &ADAPTERS).unwrap(), 0);
+        assert!(matches!(
+            select_adapter("3", &ADAPTERS),
+            Err(AdapterError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn select_by_name() {
+        assert_eq!(select_adapter("4090", &ADAPTERS).unwrap(), 1);
+        assert_eq!(select_adapter("intel", &ADAPTERS).unwrap(), 0);
+
+        let err = select_adapter("nvidia", &ADAPTERS).unwrap_err();
+        assert!(matches!(err, AdapterError::Ambiguous { .. }));
+        // The error lists the adapters so the user can pick one.
+        assert!(err.to_string().contains("2: NVIDIA GeForce RTX 3060"));
+
+        assert!(matches!(
+            select_adapter("radeon", &ADAPTERS),
+            Err(AdapterError::NotFound { .. })
+        ));
+    }
+}
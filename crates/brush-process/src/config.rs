@@ -1,5 +1,11 @@
+use std::path::{Path, PathBuf};
+
+use brush_dataset::Dataset;
+
+use crate::memory_budget::MemoryBudget;
 use clap::{Args, Parser};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 #[derive(Clone, Args, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -7,6 +13,21 @@ pub struct ProcessConfig {
     /// Random seed.
     #[arg(long, help_heading = "Process options", default_value = "42")]
     pub seed: u64,
+    /// Make runs with the same seed on the same device reproducible. Views are loaded
+    /// in a fixed order by a single loader, which can slow down training.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    pub deterministic: bool,
+    /// Keep a copy of the splats at every refine, and roll back to it when the splats
+    /// turn NaN. Costs the memory of one extra copy of the splats.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    pub rollback_on_nan: bool,
+    /// GPU memory to fit training in, in GB. Lowers --max-splats and --max-resolution
+    /// to fit, stops growing the splats while the renders need more memory than is
+    /// left for them, and skips views that would render over it. Checking the views
+    /// costs an extra projection pass and a blocking GPU readback for every view of
+    /// every step. The GPU's total memory isn't checked, wgpu doesn't expose it.
+    #[arg(long, help_heading = "Process options")]
+    pub memory_budget_gb: Option<f32>,
     /// Iteration to resume from
     #[arg(long, help_heading = "Process options", default_value = "0")]
     pub start_iter: u32,
@@ -18,9 +39,25 @@ pub struct ProcessConfig {
         value_parser = clap::value_parser!(u32).range(1..)
     )]
     pub eval_every: u32,
+    /// Stop training once this many evals in a row haven't improved the eval PSNR by
+    /// more than early-stop-min-delta. Unset trains for all steps.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub early_stop_patience: Option<u32>,
+    /// Smallest eval PSNR improvement, in dB, that counts as progress for
+    /// early-stop-patience.
+    #[arg(long, help_heading = "Process options", default_value = "0.05")]
+    pub early_stop_min_delta: f32,
     /// Save the rendered eval images to disk. Uses export-path for the file location.
     #[arg(long, help_heading = "Process options", default_value = "false")]
     pub eval_save_to_disk: bool,
+    /// Also compute LPIPS when evaluating. This loads the VGG weights, which are large
+    /// and slow down evals.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    pub eval_lpips: bool,
     /// Export every this many steps.
     #[arg(
         long,
@@ -45,6 +82,94 @@ pub struct ProcessConfig {
         default_value = "export_{iter}.ply"
     )]
     pub export_name: String,
+    /// File format of exported splats. Picked from the extension of export-name if not set.
+    #[arg(long, help_heading = "Process options", value_parser = export_format_parser())]
+    pub export_format: Option<brush_serde::ExportFormat>,
+    /// Save a checkpoint to resume training from every this many steps. Checkpoints are
+    /// written next to the exports, as checkpoint_{iter}.bin.
+    #[arg(long, help_heading = "Process options")]
+    pub checkpoint_every: Option<u32>,
+    /// Resume training from a checkpoint written with --checkpoint-every. The random
+    /// state and the position in the shuffled views aren't saved, so with --seed the
+    /// resumed run is reproducible, but doesn't match a run that never stopped.
+    #[arg(long, help_heading = "Process options")]
+    pub resume: Option<String>,
+    /// Serve a live view of training over HTTP and WebSocket on this port. Requires
+    /// the viewer-server feature.
+    #[arg(long, help_heading = "Process options")]
+    pub viewer_port: Option<u16>,
+    /// Address the viewer server listens on. Only accepts local connections by default,
+    /// use 0.0.0.0 to allow watching from other machines.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        default_value = "127.0.0.1",
+        requires = "viewer_port"
+    )]
+    pub viewer_host: String,
+    /// Send the splats to viewer clients every this many steps.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        default_value = "100",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub viewer_every: u32,
+    /// After training, fuse depth renders from the training cameras into a mesh and
+    /// write it to this path, relative to export-path. Written as PLY for a .ply
+    /// extension, OBJ otherwise.
+    #[arg(long, help_heading = "Process options")]
+    pub export_mesh: Option<String>,
+    /// Voxel resolution of the mesh along the longest axis of the scene bounds, at most 1024.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        default_value = "256",
+        value_parser = clap::value_parser!(u32).range(1..=crate::mesh::MAX_MESH_RESOLUTION as i64)
+    )]
+    pub mesh_resolution: u32,
+    /// Truncation distance used when fusing depth into the mesh, in voxels.
+    #[arg(long, help_heading = "Process options", default_value = "4.0")]
+    pub mesh_truncation: f32,
+    /// After training, render the frames of this camera path JSON to PNGs. Takes a list
+    /// of keyframes (time, position, rotation, fov) or a nerfstudio camera_path.json.
+    #[arg(long, help_heading = "Process options")]
+    pub render_path: Option<String>,
+    /// Folder to write the frames of the camera path to, relative to export-path.
+    #[arg(long, help_heading = "Process options", default_value = "frames/")]
+    pub render_out: String,
+    /// Width of the camera path frames.
+    #[arg(long, help_heading = "Process options", default_value = "1920")]
+    pub render_width: u32,
+    /// Height of the camera path frames.
+    #[arg(long, help_heading = "Process options", default_value = "1080")]
+    pub render_height: u32,
+    /// Frames per second to sample the camera path at.
+    #[arg(long, help_heading = "Process options", default_value = "30.0")]
+    pub render_fps: f32,
+}
+
+impl ProcessConfig {
+    /// The format to export splats in, either as set explicitly or inferred
+    /// from the extension of the export name.
+    pub fn export_format(&self) -> brush_serde::ExportFormat {
+        self.export_format
+            .or_else(|| brush_serde::ExportFormat::from_path(Path::new(&self.export_name)))
+            .unwrap_or_default()
+    }
+
+    /// The export name with its extension matching [`Self::export_format`]. An
+    /// explicitly set format replaces whatever extension the name has, so the
+    /// default `export_{iter}.ply` doesn't end up holding eg. spz data.
+    pub fn export_file_name(&self) -> String {
+        match self.export_format {
+            Some(format) => Path::new(&self.export_name)
+                .with_extension(format.extension())
+                .to_string_lossy()
+                .into_owned(),
+            None => self.export_name.clone(),
+        }
+    }
 }
 
 #[derive(Parser, Clone, Serialize, Deserialize)]
@@ -72,3 +197,362 @@ impl Default for TrainStreamConfig {
         Self::parse_from([""])
     }
 }
+
+/// A setting that training can't run with, or that's likely a mistake.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("--{0} must be at least 1")]
+    ZeroSetting(&'static str),
+
+    #[error(
+        "--refine-every ({refine_every}) is larger than --total-train-iters ({total}), so the splats are never refined"
+    )]
+    RefineAfterEnd { refine_every: u32, total: u32 },
+
+    #[error(
+        "--growth-stop-iter ({growth_stop_iter}) is past the end of training ({total}), so splats keep growing until the end"
+    )]
+    GrowthStopAfterEnd { growth_stop_iter: u32, total: u32 },
+
+    #[error(
+        "Initial point cloud has {init} points, exceeding --max-splats ({max_splats}). Subsampled to {max_splats}; the remaining points were discarded. Raise --max-splats to keep more."
+    )]
+    MaxSplatsBelowInit { max_splats: u32, init: usize },
+
+    #[error("--eval-split-every must be at least 2, with {0} no views are left to train on")]
+    EvalSplitTooSmall(usize),
+
+    #[error("--eval-split-every ({every}) is larger than the dataset ({views} views)")]
+    EvalSplitTooLarge { every: usize, views: usize },
+
+    #[error("No views are left to train on after splitting off the eval views")]
+    NoTrainViews,
+
+    #[error("--memory-budget-gb must be positive, got {0}")]
+    InvalidMemoryBudget(f32),
+
+    #[error("--{setting} ({value}) doesn't fit in --memory-budget-gb, lowered it to {cap}")]
+    OverMemoryBudget {
+        setting: &'static str,
+        value: u32,
+        cap: u32,
+    },
+
+    #[error("Export path {} isn't writable: {source}", path.display())]
+    ExportPathNotWritable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl TrainStreamConfig {
+    /// Check for settings training can't run with. This only looks at the settings
+    /// themselves, so it can run before the dataset is loaded.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let train = &self.train_config;
+        let process = &self.process_config;
+
+        let lod_steps = if train.lod_levels > 0 {
+            train.lod_refine_steps
+        } else {
+            1
+        };
+        for (name, value) in [
+            ("total-train-iters", train.total_train_iters),
+            ("refine-every", train.refine_every),
+            ("batch-size", train.batch_size),
+            ("max-splats", train.max_splats),
+            ("lod-refine-steps", lod_steps),
+            ("max-resolution", self.load_config.max_resolution),
+            ("eval-every", process.eval_every),
+            ("export-every", process.export_every),
+            ("viewer-every", process.viewer_every),
+            ("checkpoint-every", process.checkpoint_every.unwrap_or(1)),
+            ("render-width", process.render_width),
+            ("render-height", process.render_height),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::ZeroSetting(name));
+            }
+        }
+
+        if train.refine_every > train.total_train_iters {
+            return Err(ConfigError::RefineAfterEnd {
+                refine_every: train.refine_every,
+                total: train.total_train_iters,
+            });
+        }
+
+        if let Some(every) = self.load_config.eval_split_every
+            && every <= 1
+        {
+            return Err(ConfigError::EvalSplitTooSmall(every));
+        }
+
+        if let Some(gb) = process.memory_budget_gb
+            && (gb.is_nan() || gb <= 0.0)
+        {
+            return Err(ConfigError::InvalidMemoryBudget(gb));
+        }
+
+        Ok(())
+    }
+
+    /// Check the settings against the dataset they'll train on.
+    pub fn validate_dataset(&self, dataset: &Dataset) -> Result<(), ConfigError> {
+        let train_views = dataset.train.views.len();
+        let eval_views = dataset.eval.as_ref().map_or(0, |eval| eval.views.len());
+        if let Some(every) = self.load_config.eval_split_every
+            && every > train_views + eval_views
+        {
+            return Err(ConfigError::EvalSplitTooLarge {
+                every,
+                views: train_views + eval_views,
+            });
+        }
+        if train_views == 0 {
+            return Err(ConfigError::NoTrainViews);
+        }
+        Ok(())
+    }
+
+    /// Settings that training can run with, but likely aren't what was intended.
+    pub fn warnings(&self) -> Vec<ConfigError> {
+        let train = &self.train_config;
+        let mut warnings = vec![];
+        if train.growth_stop_iter > train.total_train_iters {
+            warnings.push(ConfigError::GrowthStopAfterEnd {
+                growth_stop_iter: train.growth_stop_iter,
+                total: train.total_train_iters,
+            });
+        }
+        warnings
+    }
+
+    /// Lower the settings that don't fit in `budget`. Returns a warning for
+    /// every setting that was lowered.
+    pub fn apply_memory_budget(&mut self, budget: &MemoryBudget) -> Vec<ConfigError> {
+        let mut warnings = vec![];
+        for (setting, value, cap) in [
+            (
+                "max-splats",
+                &mut self.train_config.max_splats,
+                budget.max_splats,
+            ),
+            (
+                "max-resolution",
+                &mut self.load_config.max_resolution,
+                budget.max_resolution,
+            ),
+        ] {
+            if *value > cap {
+                warnings.push(ConfigError::OverMemoryBudget {
+                    setting,
+                    value: *value,
+                    cap,
+                });
+                *value = cap;
+            }
+        }
+        warnings
+    }
+}
+
+/// Clap parser for a [`brush_serde::ExportFormat`], listing the format names in `--help`.
+pub fn export_format_parser()
+-> impl clap::builder::TypedValueParser<Value = brush_serde::ExportFormat> {
+    clap::builder::PossibleValuesParser::new(
+        brush_serde::ExportFormat::ALL.map(brush_serde::ExportFormat::name),
+    )
+    .try_map(|name| name.parse::<brush_serde::ExportFormat>())
+}
+
+/// Check exports can be written to `path`, creating it if needed.
+#[cfg(not(target_family = "wasm"))]
+pub fn validate_export_path(path: &Path) -> Result<(), ConfigError> {
+    let probe = path.join(".brush_write_check");
+    std::fs::create_dir_all(path)
+        .and_then(|()| std::fs::write(&probe, []))
+        .and_then(|()| std::fs::remove_file(&probe))
+        .map_err(|source| ConfigError::ExportPathNotWritable {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_dataset::load_image::LoadImage;
+    use brush_dataset::scene::SceneView;
+    use brush_render::camera::Camera;
+    use brush_vfs::BrushVfs;
+    use std::sync::Arc;
+
+    fn dataset(train: usize, eval: usize) -> Dataset {
+        let vfs = Arc::new(BrushVfs::create_test_vfs_with_data(vec![]));
+        let view = |i: usize| SceneView {
+            image: LoadImage::new(vfs.clone(), format!("{i}.png").into(), None, 64, None),
+            camera: Camera::default(),
+            intrinsics_size: None,
+        };
+        Dataset::from_views(
+            (0..train).map(view).collect(),
+            (train..train + eval).map(view).collect(),
+        )
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = TrainStreamConfig::default();
+        assert!(config.validate().is_ok());
+        assert!(config.validate_dataset(&dataset(8, 1)).is_ok());
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_export_file_name_matches_format() {
+        let mut config = TrainStreamConfig::default().process_config;
+        assert_eq!(config.export_file_name(), "export_{iter}.ply");
+
+        config.export_format = Some(brush_serde::ExportFormat::Spz);
+        assert_eq!(config.export_file_name(), "export_{iter}.spz");
+        assert_eq!(config.export_format(), brush_serde::ExportFormat::Spz);
+
+        config.export_name = "out/scene_{iter}".to_owned();
+        config.export_format = Some(brush_serde::ExportFormat::SafeTensors);
+        assert_eq!(config.export_file_name(), "out/scene_{iter}.safetensors");
+
+        // Without an explicit format, the name picks the format.
+        config.export_name = "scene.splat".to_owned();
+        config.export_format = None;
+        assert_eq!(config.export_file_name(), "scene.splat");
+        assert_eq!(config.export_format(), brush_serde::ExportFormat::Splat);
+    }
+
+    #[test]
+    fn test_memory_budget_lowers_settings() {
+        let mut config = TrainStreamConfig::default();
+        config.train_config.max_splats = 100_000;
+        let budget = MemoryBudget::from_gb(1.0);
+        let warnings = config.apply_memory_budget(&budget);
+        // The splats fit, the resolution doesn't.
+        assert_eq!(config.train_config.max_splats, 100_000);
+        assert_eq!(config.load_config.max_resolution, budget.max_resolution);
+        assert!(matches!(
+            warnings.as_slice(),
+            [ConfigError::OverMemoryBudget {
+                setting: "max-resolution",
+                value: 1920,
+                ..
+            }]
+        ));
+
+        config.process_config.memory_budget_gb = Some(0.0);
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidMemoryBudget(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let invalid: [(fn(&mut TrainStreamConfig), &str); 7] = [
+            (
+                |c| c.train_config.total_train_iters = 0,
+                "total-train-iters",
+            ),
+            (|c| c.train_config.refine_every = 0, "refine-every"),
+            (|c| c.load_config.max_resolution = 0, "max-resolution"),
+            (|c| c.process_config.export_every = 0, "export-every"),
+            (|c| c.process_config.eval_every = 0, "eval-every"),
+            (
+                |c| c.process_config.checkpoint_every = Some(0),
+                "checkpoint-every",
+            ),
+            (
+                |c| {
+                    c.train_config.lod_levels = 1;
+                    c.train_config.lod_refine_steps = 0;
+                },
+                "lod-refine-steps",
+            ),
+        ];
+        for (edit, name) in invalid {
+            let mut config = TrainStreamConfig::default();
+            edit(&mut config);
+            assert!(
+                matches!(config.validate(), Err(ConfigError::ZeroSetting(n)) if n == name),
+                "expected --{name} to be rejected"
+            );
+        }
+
+        let mut config = TrainStreamConfig::default();
+        config.train_config.total_train_iters = 100;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RefineAfterEnd {
+                refine_every: 200,
+                total: 100
+            })
+        ));
+
+        for every in [0, 1] {
+            let mut config = TrainStreamConfig::default();
+            config.load_config.eval_split_every = Some(every);
+            assert!(matches!(
+                config.validate(),
+                Err(ConfigError::EvalSplitTooSmall(e)) if e == every
+            ));
+        }
+    }
+
+    #[test]
+    fn test_dataset_mismatch() {
+        let mut config = TrainStreamConfig::default();
+        config.load_config.eval_split_every = Some(20);
+        assert!(matches!(
+            config.validate_dataset(&dataset(8, 1)),
+            Err(ConfigError::EvalSplitTooLarge {
+                every: 20,
+                views: 9
+            })
+        ));
+
+        let config = TrainStreamConfig::default();
+        assert!(matches!(
+            config.validate_dataset(&dataset(0, 1)),
+            Err(ConfigError::NoTrainViews)
+        ));
+    }
+
+    #[test]
+    fn test_growth_past_end_warns() {
+        let mut config = TrainStreamConfig::default();
+        config.train_config.total_train_iters = 1000;
+        assert!(config.validate().is_ok());
+        assert!(matches!(
+            config.warnings().as_slice(),
+            [ConfigError::GrowthStopAfterEnd {
+                growth_stop_iter: 15000,
+                total: 1000
+            }]
+        ));
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[test]
+    fn test_export_path_not_writable() {
+        let dir = std::env::temp_dir().join("brush_config_test_export");
+        assert!(validate_export_path(&dir).is_ok());
+
+        // A path below a file can't be created.
+        let file = dir.join("file");
+        std::fs::write(&file, []).unwrap();
+        assert!(matches!(
+            validate_export_path(&file.join("exports")),
+            Err(ConfigError::ExportPathNotWritable { .. })
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,85 @@
+//! Load and save splat files by path, with the format picked from the extension.
+
+use std::path::Path;
+
+use anyhow::Context;
+use brush_render::gaussian_splats::{SplatRenderMode, Splats};
+use brush_serde::{ExportFormat, ImportFormat, SplatMessage};
+use burn::tensor::Device;
+use tokio::io::BufReader;
+
+/// Load all splats of a file in any [`ImportFormat`].
+pub async fn load_splat_file(path: &Path) -> anyhow::Result<SplatMessage> {
+    let format = ImportFormat::from_path(path)
+        .with_context(|| format!("Unknown splat file format: {}", path.display()))?;
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    brush_serde::load_splat(BufReader::new(file), format, None)
+        .await
+        .with_context(|| format!("Failed to load {}", path.display()))
+}
+
+/// Load all splats of a file onto `device`, see [`load_splat_file`].
+pub async fn load_splats(path: &Path, device: &Device) -> anyhow::Result<Splats> {
+    let message = load_splat_file(path).await?;
+    let mode = message.meta.render_mode.unwrap_or(SplatRenderMode::Default);
+    Ok(message.data.into_splats(device, mode))
+}
+
+/// Convert the splat file at `input` to `output`. Without a `format`, it's picked from
+/// the extension of `output`. The up axis and appearance settings of the input are kept
+/// where the output format can store them. Returns the number of splats written.
+pub async fn convert_splat_file(
+    input: &Path,
+    output: &Path,
+    format: Option<ExportFormat>,
+    device: &Device,
+) -> anyhow::Result<u32> {
+    let format = match format {
+        Some(format) => format,
+        None => ExportFormat::from_path(output)
+            .with_context(|| format!("Unknown splat file format: {}", output.display()))?,
+    };
+    let message = load_splat_file(input).await?;
+    let meta = message.meta;
+    let mode = meta.render_mode.unwrap_or(SplatRenderMode::Default);
+    let splats = message.data.into_splats(device, mode);
+    let num_splats = splats.num_splats();
+
+    let data = brush_serde::splat_export(splats, meta.up_axis, &meta.appearance, format).await?;
+    tokio::fs::write(output, data)
+        .await
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(num_splats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_convert_round_trip() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/red_splat.ply");
+        let dir = std::env::temp_dir().join("brush_convert_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let spz = dir.join("red_splat.spz");
+        let count = convert_splat_file(&input, &spz, None, &device)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let back = dir.join("red_splat.ply");
+        convert_splat_file(&spz, &back, None, &device)
+            .await
+            .unwrap();
+        let message = load_splat_file(&back).await.unwrap();
+        assert_eq!(message.data.num_splats(), 1);
+
+        let unknown = convert_splat_file(&input, &dir.join("out.xyz"), None, &device).await;
+        assert!(unknown.is_err(), "Unknown extensions should be rejected");
+    }
+}
@@ -0,0 +1,76 @@
+//! Stopping training early once the eval PSNR stops improving.
+
+/// Tracks the eval PSNR to tell when training has plateaued, see
+/// [`crate::config::ProcessConfig::early_stop_patience`].
+pub struct PlateauDetector {
+    patience: u32,
+    min_delta: f32,
+    best: Option<f32>,
+    evals_without_improvement: u32,
+}
+
+impl PlateauDetector {
+    pub fn new(patience: u32, min_delta: f32) -> Self {
+        Self {
+            patience,
+            min_delta,
+            best: None,
+            evals_without_improvement: 0,
+        }
+    }
+
+    /// Record the average PSNR of an eval. Returns true once `patience` evals
+    /// in a row haven't improved on the best PSNR by more than `min_delta`.
+    pub fn update(&mut self, psnr: f32) -> bool {
+        let improved =
+            psnr.is_finite() && self.best.is_none_or(|best| psnr > best + self.min_delta);
+        if improved {
+            self.best = Some(psnr);
+            self.evals_without_improvement = 0;
+        } else {
+            self.evals_without_improvement += 1;
+        }
+        self.evals_without_improvement >= self.patience
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The iteration training stops at when evaluating every `eval_every` steps.
+    fn stop_iter(detector: &mut PlateauDetector, psnrs: &[f32], eval_every: u32) -> Option<u32> {
+        psnrs
+            .iter()
+            .zip(1..)
+            .find(|&(&psnr, _)| detector.update(psnr))
+            .map(|(_, i)| i * eval_every)
+    }
+
+    #[test]
+    fn test_stops_on_plateau() {
+        // Improves until the 4th eval, then only creeps up by less than the delta.
+        let psnrs = [20.0, 24.0, 26.0, 27.0, 27.02, 27.03, 27.01, 27.04];
+        let mut detector = PlateauDetector::new(3, 0.05);
+        assert_eq!(stop_iter(&mut detector, &psnrs, 1000), Some(7000));
+    }
+
+    #[test]
+    fn test_improvement_resets_patience() {
+        let psnrs = [20.0, 20.0, 21.0, 21.0, 21.0];
+        let mut detector = PlateauDetector::new(2, 0.05);
+        assert_eq!(stop_iter(&mut detector, &psnrs, 500), Some(2500));
+
+        // Steady progress never stops.
+        let psnrs: Vec<f32> = (0..20).map(|i| 20.0 + i as f32 * 0.1).collect();
+        let mut detector = PlateauDetector::new(2, 0.05);
+        assert_eq!(stop_iter(&mut detector, &psnrs, 500), None);
+    }
+
+    #[test]
+    fn test_non_finite_psnr_is_no_improvement() {
+        let mut detector = PlateauDetector::new(2, 0.0);
+        assert!(!detector.update(f32::NAN));
+        assert!(detector.update(f32::NAN));
+    }
+}
@@ -0,0 +1,63 @@
+//! Evaluate a trained splat file against a dataset, without training.
+
+use std::path::Path;
+
+use anyhow::Context;
+use brush_dataset::{config::LoadDatasetConfig, load_dataset};
+use brush_render::gaussian_splats::PreparedSplats;
+use brush_train::{
+    config::TrainConfig,
+    eval::{EvalReport, EvalViewReport, eval_stats},
+};
+use brush_vfs::DataSource;
+use burn::tensor::Device;
+
+use crate::convert::load_splats;
+
+/// Load the splats at `splats_path` and score them against the dataset at `source`. This
+/// uses the eval views when the dataset has an eval split, and all views otherwise.
+///
+/// SSIM is computed with the settings of `train_config`, like the evals during training.
+pub async fn eval_splat_file(
+    splats_path: &Path,
+    source: DataSource,
+    load_config: &LoadDatasetConfig,
+    train_config: &TrainConfig,
+    device: &Device,
+) -> anyhow::Result<EvalReport> {
+    let splats = load_splats(splats_path, device).await?;
+
+    let vfs = source.into_vfs().await?;
+    let load_result = load_dataset(vfs, load_config).await?;
+    for warning in load_result.warnings {
+        log::warn!("{warning}");
+    }
+    let dataset = load_result.dataset;
+    let scene = dataset.eval.unwrap_or(dataset.train);
+    anyhow::ensure!(!scene.views.is_empty(), "Dataset has no views to evaluate");
+
+    let num_splats = splats.num_splats();
+    let prepared = PreparedSplats::new(splats, None).await;
+
+    let mut report = EvalReport::default();
+    for view in &scene.views {
+        let gt_img = view.image.load().await?;
+        let sample = eval_stats(
+            &prepared,
+            &view.camera,
+            gt_img,
+            view.image.alpha_mode(),
+            train_config.ssim_mode,
+            train_config.ssim_window(),
+            None,
+            None,
+            device,
+        )
+        .await
+        .with_context(|| format!("Failed to evaluate {}", view.image.img_name()))?;
+        report
+            .views
+            .push(EvalViewReport::from_sample(view.image.img_name(), &sample, num_splats).await?);
+    }
+    Ok(report)
+}
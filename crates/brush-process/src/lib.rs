@@ -1,10 +1,22 @@
+pub mod adapter;
 pub mod args_file;
 pub mod config;
+#[cfg(not(target_family = "wasm"))]
+pub mod convert;
+pub mod early_stop;
+#[cfg(not(target_family = "wasm"))]
+pub mod eval;
+pub mod memory_budget;
+pub mod mesh;
 pub mod message;
+pub mod render;
 pub mod slot;
 pub mod train_stream;
+#[cfg(all(feature = "viewer-server", not(target_family = "wasm")))]
+pub mod viewer_server;
 
 pub use brush_vfs::DataSource;
+pub use tokio_util::sync::CancellationToken;
 
 use burn_wgpu::{
     AutoCompiler, RuntimeOptions, WgpuDevice,
@@ -13,11 +25,13 @@ use burn_wgpu::{
 use wgpu::{Adapter, Device, Queue};
 
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::pin::{Pin, pin};
 
 use anyhow::Error;
-use async_fn_stream::{TryStreamEmitter, try_fn_stream};
+use async_fn_stream::{TryStreamEmitter, fn_stream, try_fn_stream};
 use brush_render::gaussian_splats::{SplatRenderMode, Splats};
+use brush_serde::ImportFormat;
 use brush_vfs::SendNotWasm;
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::WgpuRuntime;
@@ -31,17 +45,60 @@ fn burn_options() -> RuntimeOptions {
 }
 
 pub async fn burn_init_setup() -> WgpuDevice {
-    burn_wgpu::init_setup_async::<AutoGraphicsApi>(&WgpuDevice::DefaultDevice, burn_options())
-        .await;
-    connect_device(WgpuDevice::DefaultDevice);
+    let setup =
+        burn_wgpu::init_setup_async::<AutoGraphicsApi>(&WgpuDevice::DefaultDevice, burn_options())
+            .await;
+    connect_device(WgpuDevice::DefaultDevice, &setup.device.limits());
     WgpuDevice::DefaultDevice
 }
 
+/// Like [`burn_init_setup`], but on the adapter picked by `selector`, see
+/// [`adapter::select_adapter`]. All adapters are logged, so users can see what
+/// to pick from.
+#[cfg(not(target_family = "wasm"))]
+pub async fn burn_init_setup_with_adapter(
+    selector: &str,
+) -> Result<WgpuDevice, adapter::AdapterError> {
+    use adapter::AdapterDescription;
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+    let mut adapters: Vec<_> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .await
+        .into_iter()
+        .filter(|a| a.get_info().backend == AutoGraphicsApi::backend())
+        .collect();
+    let infos: Vec<_> = adapters.iter().map(|a| a.get_info()).collect();
+    for (i, info) in infos.iter().enumerate() {
+        log::info!("GPU adapter {i}: {}", info.summary());
+    }
+
+    let index = adapter::select_adapter(selector, &infos)?;
+    log::info!("Using GPU adapter: {}", infos[index].summary());
+    let adapter = adapters.swap_remove(index);
+
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: Some("brush"),
+            required_features: adapter
+                .features()
+                .difference(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS),
+            required_limits: adapter.limits(),
+            memory_hints: wgpu::MemoryHints::MemoryUsage,
+            trace: wgpu::Trace::Off,
+            // SAFETY: Passthrough shaders are allowed.
+            experimental_features: unsafe { wgpu::ExperimentalFeatures::enabled() },
+        })
+        .await?;
+    Ok(burn_init_device(adapter, device, queue))
+}
+
 /// Initialize Burn with a wgpu setup the host already owns. Useful when
 /// integrating with an existing wgpu/WebGPU application that wants to share
 /// its device with Brush so tensor buffers can flow back into the host's
 /// render pipeline without copies.
 pub fn burn_init_device(adapter: Adapter, device: Device, queue: Queue) -> WgpuDevice {
+    let limits = device.limits();
     let setup = burn_wgpu::WgpuSetup {
         instance: wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle()), // unused... need to fix this in Burn.
         adapter,
@@ -50,7 +107,7 @@ pub fn burn_init_device(adapter: Adapter, device: Device, queue: Queue) -> WgpuD
         backend: AutoGraphicsApi::backend(),
     };
     let burn = burn_wgpu::init_device(setup, burn_options());
-    connect_device(burn.clone());
+    connect_device(burn.clone(), &limits);
     burn
 }
 
@@ -66,6 +123,10 @@ impl<T> ProcessStream for T where T: Stream<Item = Result<ProcessMessage, Error>
 pub struct RunningProcess {
     pub stream: Pin<Box<dyn ProcessStream>>,
     pub splat_view: Slot<Splats>,
+    /// Cancel to stop training before its next step. The stream then emits
+    /// [`message::TrainMessage::DoneTraining`] and ends, without a final export.
+    /// When viewing splat files, loading stops and the stream ends right away.
+    pub cancel: CancellationToken,
 }
 
 /// Convenience alias for the emitter `try_fn_stream` hands us inside
@@ -76,18 +137,80 @@ pub(crate) type Emitter = TryStreamEmitter<ProcessMessage, Error>;
 use tokio::sync::SetOnce;
 
 static DEVICE: SetOnce<WgpuDevice> = SetOnce::const_new();
+static STORAGE_BUFFER_LIMIT: SetOnce<u32> = SetOnce::const_new();
 
-pub(crate) fn connect_device(device: WgpuDevice) {
+/// The most storage buffers the training kernels bind in one shader stage.
+pub const REQUIRED_STORAGE_BUFFERS: u32 = 8;
+
+/// The most storage buffers viewing binds in one shader stage. Below
+/// [`REQUIRED_STORAGE_BUFFERS`] the renderer switches to its packed project &
+/// rasterize kernels, which leaves the radix sort's scatter pass as the widest.
+pub const VIEWER_STORAGE_BUFFERS: u32 = 6;
+
+pub(crate) fn connect_device(device: WgpuDevice, limits: &wgpu::Limits) {
     // Idempotent: a JS host can call `init()` and `init_existing()`, or a
     // dev-mode double-mount can re-run setup. Re-registering the same device
     // is fine; we only care that *some* device wins the race.
     let _ = DEVICE.set(device);
+    let _ = STORAGE_BUFFER_LIMIT.set(limits.max_storage_buffers_per_shader_stage);
+    brush_render::render::set_storage_buffer_limit(limits.max_storage_buffers_per_shader_stage);
 }
 
 pub async fn wait_for_device() -> &'static WgpuDevice {
     DEVICE.wait().await
 }
 
+/// An error for devices that can't bind `required` storage buffers per shader
+/// stage, like some Android GL & older Vulkan drivers.
+fn storage_buffer_error(limit: u32, required: u32) -> Option<Error> {
+    (limit < required).then(|| {
+        anyhow::anyhow!(
+            "This GPU only supports {limit} storage buffers per shader stage, while Brush \
+             needs {required}."
+        )
+    })
+}
+
+/// Splat files numbered like the frames of an animation, eg. `frame_0001.ply`,
+/// `frame_0002.ply`, in natural order. These are viewed as one animation, even
+/// when other files are around that would otherwise make this a dataset.
+fn splat_sequence(paths: &[PathBuf]) -> Option<Vec<PathBuf>> {
+    fn numbered_prefix(path: &Path) -> Option<&str> {
+        let stem = path.file_stem()?.to_str()?;
+        let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+        (prefix.len() < stem.len()).then_some(prefix)
+    }
+
+    let mut splats: Vec<_> = paths
+        .iter()
+        .filter(|p| ImportFormat::from_path(p).is_some())
+        .cloned()
+        .collect();
+    if splats.len() < 2 {
+        return None;
+    }
+    let prefix = numbered_prefix(&splats[0])?;
+    if !splats.iter().all(|p| numbered_prefix(p) == Some(prefix)) {
+        return None;
+    }
+    alphanumeric_sort::sort_path_slice(&mut splats);
+    Some(splats)
+}
+
+/// Items of `stream` until it ends or `cancel` is cancelled. Cancelling drops the pending
+/// item, so a file that's still downloading or parsing stops right away, rather than
+/// after its next chunk.
+fn until_cancelled<S: Stream + Unpin>(
+    mut stream: S,
+    cancel: CancellationToken,
+) -> impl Stream<Item = S::Item> {
+    fn_stream(|emitter| async move {
+        while let Some(Some(item)) = cancel.run_until_cancelled(stream.next()).await {
+            emitter.emit(item).await;
+        }
+    })
+}
+
 /// Create a running process from a datasource and args.
 ///
 /// The `config_fn` callback receives the initial config (loaded from
@@ -102,15 +225,17 @@ pub fn create_process<
     config_fn: Fun,
 ) -> RunningProcess {
     let (splat_tx, splat_view) = crate::slot::channel();
+    let cancel = CancellationToken::new();
 
-    let stream =
-        try_fn_stream(
-            |emitter| async move { run_process(source, config_fn, &emitter, splat_tx).await },
-        );
+    let stream = try_fn_stream({
+        let cancel = cancel.clone();
+        |emitter| async move { run_process(source, config_fn, &emitter, splat_tx, cancel).await }
+    });
 
     RunningProcess {
         stream: Box::pin(stream),
         splat_view,
+        cancel,
     }
 }
 
@@ -122,29 +247,38 @@ async fn run_process<
     config_fn: Fun,
     emitter: &Emitter,
     splat_view: SlotSender<Splats>,
+    cancel: CancellationToken,
 ) -> Result<(), Error> {
     log::info!("Starting process with source {source:?}");
     emitter.emit(ProcessMessage::NewProcess).await;
 
-    let vfs = source.clone().into_vfs().await?;
+    // Mounting a URL starts downloading it, which can take a while.
+    let Some(vfs) = cancel.run_until_cancelled(source.clone().into_vfs()).await else {
+        return Ok(());
+    };
+    let vfs = vfs?;
     let vfs_counts = vfs.file_count();
 
     if vfs_counts == 0 {
         return Err(anyhow::anyhow!("No files found."));
     }
 
-    let ply_count = vfs.files_with_extension("ply").count();
+    let splat_count = vfs
+        .file_paths()
+        .filter(|p| ImportFormat::from_path(p).is_some())
+        .count();
 
     log::info!(
-        "Mounted VFS with {} files. (plys: {})",
+        "Mounted VFS with {} files. (splat files: {})",
         vfs.file_count(),
-        ply_count
+        splat_count
     );
 
-    let is_training = vfs_counts != ply_count;
+    let paths: Vec<_> = vfs.file_paths().collect();
+    let sequence = splat_sequence(&paths);
+    let is_training = vfs_counts != splat_count && sequence.is_none();
 
     // Emit source info - just the display name
-    let paths: Vec<_> = vfs.file_paths().collect();
     let source_name = if let Some(base_path) = vfs.base_path() {
         base_path
             .file_name()
@@ -175,22 +309,44 @@ async fn run_process<
         })
         .await;
 
+    // Check the device is capable enough up front, rather than failing to
+    // create a pipeline halfway through.
+    let limit = *STORAGE_BUFFER_LIMIT.wait().await;
+    if is_training {
+        if let Some(error) = storage_buffer_error(limit, REQUIRED_STORAGE_BUFFERS) {
+            return Err(error.context("Training isn't supported on this device"));
+        }
+    } else if let Some(error) = storage_buffer_error(limit, VIEWER_STORAGE_BUFFERS) {
+        let error = error.context("Rendering might fail on this device");
+        emitter.emit(ProcessMessage::Warning { error }).await;
+    }
+
     if !is_training {
         let wgpu_device = wait_for_device().await;
         let device: burn::tensor::Device = wgpu_device.clone().into();
-        let mut paths: Vec<_> = vfs.file_paths().collect();
-        alphanumeric_sort::sort_path_slice(&mut paths);
+        let paths = if let Some(sequence) = sequence {
+            log::info!("Loading {} splat files as an animation", sequence.len());
+            sequence
+        } else {
+            let mut paths = paths;
+            alphanumeric_sort::sort_path_slice(&mut paths);
+            paths
+        };
         let client = WgpuRuntime::<AutoCompiler>::client(wgpu_device);
         let total_frames = paths.len() as u32;
 
         for (frame, path) in paths.iter().enumerate() {
-            log::info!("Loading single ply file");
+            log::info!("Loading splat file {path:?}");
 
-            let mut splat_stream = pin!(brush_serde::stream_splat_from_ply(
+            let format = ImportFormat::from_path(path)
+                .ok_or_else(|| anyhow::anyhow!("Unsupported splat file {path:?}"))?;
+            let splat_stream = pin!(brush_serde::stream_splat(
                 vfs.reader_at_path(path).await?,
+                format,
                 None,
                 true,
             ));
+            let mut splat_stream = pin!(until_cancelled(splat_stream, cancel.clone()));
 
             while let Some(message) = splat_stream.next().await {
                 let message = message?;
@@ -219,9 +375,15 @@ async fn run_process<
                         total_frames,
                         num_splats,
                         sh_degree,
+                        progress: message.meta.progress,
                     })
                     .await;
             }
+
+            if cancel.is_cancelled() {
+                log::info!("Loading cancelled");
+                return Ok(());
+            }
         }
 
         emitter.emit(ProcessMessage::DoneLoading).await;
@@ -234,8 +396,135 @@ async fn run_process<
             log::info!("config_fn returned None — aborting before training");
             return Ok(());
         };
-        train_stream(vfs, config, emitter, splat_view).await?;
+        train_stream(vfs, config, emitter, splat_view, cancel).await?;
     };
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_serde::SplatData;
+
+    #[test]
+    fn test_storage_buffer_error() {
+        assert!(storage_buffer_error(4, VIEWER_STORAGE_BUFFERS).is_some());
+        assert!(storage_buffer_error(VIEWER_STORAGE_BUFFERS, VIEWER_STORAGE_BUFFERS).is_none());
+        assert!(storage_buffer_error(VIEWER_STORAGE_BUFFERS, REQUIRED_STORAGE_BUFFERS).is_some());
+        assert!(storage_buffer_error(u32::MAX, REQUIRED_STORAGE_BUFFERS).is_none());
+    }
+
+    #[test]
+    fn test_splat_sequence_detection() {
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        let sequence = splat_sequence(&paths(&[
+            "frame_10.ply",
+            "frame_2.ply",
+            "notes.txt",
+            "frame_1.ply",
+        ]));
+        assert_eq!(
+            sequence,
+            Some(paths(&["frame_1.ply", "frame_2.ply", "frame_10.ply"]))
+        );
+
+        // A single file, unnumbered files or mixed names aren't a sequence.
+        assert_eq!(splat_sequence(&paths(&["frame_1.ply", "img.png"])), None);
+        assert_eq!(splat_sequence(&paths(&["a.ply", "b.ply"])), None);
+        assert_eq!(splat_sequence(&paths(&["a_1.ply", "b_2.ply"])), None);
+    }
+
+    #[tokio::test]
+    async fn test_view_ply_sequence() {
+        let device: burn::tensor::Device = burn_init_setup().await.into();
+
+        let dir = std::env::temp_dir().join("brush_ply_sequence_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // Frame `i` has `i + 1` splats. Name them so a plain string sort would
+        // put frame 10 before frame 2.
+        for (i, num) in [1, 2, 3, 10, 11].into_iter().enumerate() {
+            let splats = SplatData {
+                means: vec![0.0; 3 * (i + 1)],
+                rotations: None,
+                log_scales: None,
+                sh_coeffs: None,
+                raw_opacities: None,
+            }
+            .into_splats(&device, SplatRenderMode::Default);
+            let ply = brush_serde::splat_to_ply(splats, None).await.unwrap();
+            std::fs::write(dir.join(format!("frame_{num}.ply")), ply).unwrap();
+        }
+        std::fs::write(dir.join("readme.txt"), "Not a splat").unwrap();
+
+        let source = DataSource::Path(dir.to_string_lossy().into_owned());
+        let process = create_process(source, |config| async move { Some(config) });
+        let mut stream = process.stream;
+
+        let mut frames = vec![];
+        while let Some(message) = stream.next().await {
+            match message.unwrap() {
+                ProcessMessage::StartLoading { training, .. } => assert!(!training),
+                ProcessMessage::SplatsUpdated {
+                    frame,
+                    total_frames,
+                    num_splats,
+                    ..
+                } => frames.push((frame, total_frames, num_splats)),
+                _ => {}
+            }
+        }
+        assert_eq!(frames, (0..5).map(|i| (i, 5, i + 1)).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_ply_stream() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::time::{Duration, sleep, timeout};
+
+        // A PLY with only positions, which comes in slowly like a download.
+        let num_splats = 1 << 20;
+        let mut ply = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {num_splats}\n\
+             property float x\nproperty float y\nproperty float z\nend_header\n"
+        )
+        .into_bytes();
+        ply.resize(ply.len() + num_splats * 12, 0);
+        let (reader, mut writer) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            for chunk in ply.chunks(256 * 1024) {
+                if writer.write_all(chunk).await.is_err() {
+                    return;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+
+        let cancel = CancellationToken::new();
+        let stream = pin!(brush_serde::stream_splat(
+            reader,
+            ImportFormat::Ply,
+            None,
+            true
+        ));
+        let mut stream = pin!(until_cancelled(stream, cancel.clone()));
+        let first = stream
+            .next()
+            .await
+            .expect("Should load part of the file")
+            .unwrap();
+        assert!(
+            first.meta.progress > 0.0 && first.meta.progress < 1.0,
+            "Progress should follow the rows read: {}",
+            first.meta.progress
+        );
+
+        cancel.cancel();
+        let rest = timeout(Duration::from_millis(500), stream.next())
+            .await
+            .expect("Cancelling should stop the stream right away");
+        assert!(rest.is_none(), "No messages should follow cancelling");
+    }
+}
@@ -0,0 +1,90 @@
+//! Caps on the splat count, intersection buffers and training resolution derived
+//! from a GPU memory budget, for GPUs that would otherwise run out of memory
+//! mid-training.
+//!
+//! The budget is whatever the user passes. The total VRAM of the GPU isn't
+//! queried, wgpu doesn't expose it, so a budget larger than the GPU still runs
+//! out of memory.
+
+/// Bytes per splat while training: the 59 floats of parameters at SH degree 3,
+/// their gradients and two optimizer moments, plus the refine statistics and
+/// per-render projection buffers.
+const BYTES_PER_SPLAT: u64 = 1024;
+/// Bytes per splat-tile intersection: the tile and splat ids, double buffered
+/// by the sort.
+const BYTES_PER_INTERSECTION: u64 = 16;
+/// Bytes per pixel of a training view: the rendered image, its gradient, the
+/// ground truth and the SSIM intermediates.
+const BYTES_PER_PIXEL: u64 = 256;
+
+/// Percentage of the budget given to the splats, the intersections and the
+/// images. Adds up to 100.
+const SPLAT_SHARE: u64 = 50;
+const INTERSECTION_SHARE: u64 = 35;
+const PIXEL_SHARE: u64 = 15;
+
+/// The caps derived from a memory budget, see [`MemoryBudget::from_bytes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub max_splats: u32,
+    /// Most splat-tile intersections a single render can allocate buffers for.
+    pub max_intersections: u64,
+    /// Longest image edge to train at, assuming a 16:9 image.
+    pub max_resolution: u32,
+}
+
+impl MemoryBudget {
+    /// Split `bytes` between the splats, the intersection buffers and the
+    /// per-pixel buffers of the training images.
+    pub fn from_bytes(bytes: u64) -> Self {
+        let share = |percent: u64| bytes / 100 * percent;
+        let max_splats = (share(SPLAT_SHARE) / BYTES_PER_SPLAT).min(u32::MAX as u64) as u32;
+        let max_intersections = share(INTERSECTION_SHARE) / BYTES_PER_INTERSECTION;
+        // A 16:9 image with a long edge of `r` has r * r * 9 / 16 pixels.
+        let max_pixels = share(PIXEL_SHARE) / BYTES_PER_PIXEL;
+        let max_resolution = ((max_pixels * 16 / 9) as f64).sqrt() as u32;
+        Self {
+            max_splats: max_splats.max(1),
+            max_intersections: max_intersections.max(1),
+            max_resolution: max_resolution.max(1),
+        }
+    }
+
+    pub fn from_gb(gb: f32) -> Self {
+        Self::from_bytes((gb as f64 * 1024.0 * 1024.0 * 1024.0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_derivation() {
+        let budget = MemoryBudget::from_gb(4.0);
+        assert_eq!(budget.max_splats, 2_097_151);
+        assert_eq!(budget.max_intersections, 93_952_407);
+        assert_eq!(budget.max_resolution, 2115);
+
+        // Twice the memory fits twice the splats and intersections, and
+        // sqrt(2) times the resolution.
+        let double = MemoryBudget::from_gb(8.0);
+        assert!(double.max_splats.abs_diff(2 * budget.max_splats) <= 2);
+        assert!(
+            double
+                .max_intersections
+                .abs_diff(2 * budget.max_intersections)
+                <= 2
+        );
+        let ratio = double.max_resolution as f32 / budget.max_resolution as f32;
+        assert!((ratio - std::f32::consts::SQRT_2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_tiny_budget() {
+        let budget = MemoryBudget::from_bytes(0);
+        assert_eq!(budget.max_splats, 1);
+        assert_eq!(budget.max_intersections, 1);
+        assert_eq!(budget.max_resolution, 1);
+    }
+}
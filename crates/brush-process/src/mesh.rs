@@ -0,0 +1,489 @@
+//! Extract a triangle mesh from trained splats.
+//!
+//! Depth is rendered from each training camera and fused into a truncated
+//! signed distance field (TSDF) over the splat bounds. The zero level set is
+//! then polygonized with marching tetrahedra, the variant of marching cubes
+//! that splits each voxel cell into 6 tetrahedra and so doesn't need the big
+//! case tables.
+//!
+//! Distances are positive in front of the surface (towards the cameras) and
+//! negative behind it. Pixels the splats don't cover count as free space, which
+//! carves away anything the cameras look straight through.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use brush_render::{bounding_box::BoundingBox, camera::Camera, gaussian_splats::Splats};
+use glam::{UVec2, UVec3, Vec2, Vec3};
+use rayon::prelude::*;
+
+/// Pixels with less accumulated alpha than this are treated as empty.
+const MIN_SURFACE_ALPHA: f32 = 0.5;
+
+/// Most voxels along the longest axis. The grid holds two floats per voxel, so
+/// this is already ~8 GB.
+pub const MAX_MESH_RESOLUTION: u32 = 1024;
+
+/// The 6 tetrahedra a cell is split into, as cell corner indices where bit 0,
+/// 1 and 2 of the index are the x, y and z offset. All share the 0-7 diagonal
+/// so neighbouring cells agree on the triangulation of their shared faces.
+const CELL_TETS: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 1, 5, 7],
+    [0, 2, 3, 7],
+    [0, 2, 6, 7],
+    [0, 4, 5, 7],
+    [0, 4, 6, 7],
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    /// Encode as a Wavefront OBJ file.
+    pub fn to_obj(&self) -> Vec<u8> {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        for v in &self.vertices {
+            let _ = writeln!(out, "v {} {} {}", v.x, v.y, v.z);
+        }
+        for [a, b, c] in &self.triangles {
+            // OBJ indices are 1-based.
+            let _ = writeln!(out, "f {} {} {}", a + 1, b + 1, c + 1);
+        }
+        out.into_bytes()
+    }
+
+    /// Encode as a binary little endian PLY file.
+    pub fn to_ply(&self) -> Vec<u8> {
+        let header = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\nelement face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+            self.vertices.len(),
+            self.triangles.len()
+        );
+        let mut out = header.into_bytes();
+        for v in &self.vertices {
+            for c in v.to_array() {
+                out.extend(c.to_le_bytes());
+            }
+        }
+        for tri in &self.triangles {
+            out.push(3);
+            for i in tri {
+                out.extend(i.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Encode as PLY for a `.ply` path, and as OBJ otherwise.
+    pub fn encode_for_path(&self, path: &std::path::Path) -> Vec<u8> {
+        let is_ply = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ply"));
+        if is_ply { self.to_ply() } else { self.to_obj() }
+    }
+}
+
+/// A TSDF voxel grid. Values are stored at the grid points, normalized by
+/// the truncation distance to [-1, 1].
+pub struct TsdfVolume {
+    origin: Vec3,
+    voxel_size: f32,
+    dims: UVec3,
+    truncation: f32,
+    tsdf: Vec<f32>,
+    weights: Vec<f32>,
+}
+
+impl TsdfVolume {
+    /// Create an empty volume covering `bounds`, with `resolution` voxels along
+    /// the longest axis (at most [`MAX_MESH_RESOLUTION`]) and a truncation
+    /// distance of `truncation_voxels` voxels.
+    pub fn new(bounds: BoundingBox, resolution: u32, truncation_voxels: f32) -> Self {
+        let size = bounds.extent * 2.0;
+        let resolution = resolution.clamp(1, MAX_MESH_RESOLUTION);
+        let voxel_size = size.max_element().max(1e-6) / resolution as f32;
+        let dims = (size / voxel_size).ceil().as_uvec3().max(UVec3::ONE) + 1;
+        let count = dims.x as usize * dims.y as usize * dims.z as usize;
+
+        Self {
+            origin: bounds.min(),
+            voxel_size,
+            dims,
+            truncation: truncation_voxels.max(1.0) * voxel_size,
+            tsdf: vec![1.0; count],
+            weights: vec![0.0; count],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        let [dx, dy] = [self.dims.x as usize, self.dims.y as usize];
+        x as usize + dx * (y as usize + dy * z as usize)
+    }
+
+    fn point(&self, x: u32, y: u32, z: u32) -> Vec3 {
+        self.origin + Vec3::new(x as f32, y as f32, z as f32) * self.voxel_size
+    }
+
+    /// Fuse a depth render, as returned by [`Splats::render_depth`], into the volume.
+    /// `depth` holds `[height, width, 2]` (depth, alpha) values.
+    pub fn integrate(&mut self, camera: &Camera, img_size: UVec2, depth: &[f32]) {
+        assert_eq!(
+            depth.len(),
+            (img_size.x * img_size.y * 2) as usize,
+            "Depth render doesn't match the image size"
+        );
+
+        let world_to_local = camera.world_to_local();
+        let focal = camera.focal(img_size);
+        let center = camera.center(img_size);
+        let size = img_size.as_vec2();
+        // Camera space offset of one voxel step along x.
+        let step = world_to_local.transform_vector3(Vec3::X * self.voxel_size);
+        let (origin, voxel_size) = (self.origin, self.voxel_size);
+        let (dims, truncation) = (self.dims, self.truncation);
+        let slice_len = dims.x as usize * dims.y as usize;
+
+        // Slices along z are independent, fuse them in parallel.
+        self.tsdf
+            .par_chunks_mut(slice_len)
+            .zip(self.weights.par_chunks_mut(slice_len))
+            .enumerate()
+            .for_each(|(z, (tsdf, weights))| {
+                for y in 0..dims.y {
+                    let row_start = origin + Vec3::new(0.0, y as f32, z as f32) * voxel_size;
+                    let row = world_to_local.transform_point3(row_start);
+                    // Only visit the part of the row in front of the camera and
+                    // inside the image, most of the grid is outside a view.
+                    for x in frustum_range(row, step, focal, center, size, dims.x) {
+                        let p = row + step * x as f32;
+                        if p.z <= 0.0 {
+                            continue;
+                        }
+                        let u = focal.x * p.x / p.z + center.x;
+                        let v = focal.y * p.y / p.z + center.y;
+                        if u < 0.0 || v < 0.0 || u >= size.x || v >= size.y {
+                            continue;
+                        }
+
+                        let pix = (u as u32 + v as u32 * img_size.x) as usize * 2;
+                        let (surface_depth, alpha) = (depth[pix], depth[pix + 1]);
+
+                        let sdf = if alpha < MIN_SURFACE_ALPHA {
+                            truncation
+                        } else {
+                            surface_depth - p.z
+                        };
+                        // Too far behind the surface to know anything about.
+                        if sdf < -truncation {
+                            continue;
+                        }
+
+                        let i = x as usize + y as usize * dims.x as usize;
+                        let w = weights[i];
+                        let value = (sdf / truncation).min(1.0);
+                        tsdf[i] = (tsdf[i] * w + value) / (w + 1.0);
+                        weights[i] = w + 1.0;
+                    }
+                }
+            });
+    }
+
+    /// Polygonize the zero level set. Cells with any unobserved corner are
+    /// skipped. Triangles face towards the positive (observed, free) side.
+    pub fn extract_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::default();
+        let mut edge_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+
+        let mut edge_vertex = |mesh: &mut Mesh, a: (usize, Vec3, f32), b: (usize, Vec3, f32)| {
+            let key = (a.0.min(b.0), a.0.max(b.0));
+            *edge_vertices.entry(key).or_insert_with(|| {
+                let t = a.2 / (a.2 - b.2);
+                mesh.vertices.push(a.1.lerp(b.1, t));
+                mesh.vertices.len() as u32 - 1
+            })
+        };
+
+        for z in 0..self.dims.z.saturating_sub(1) {
+            for y in 0..self.dims.y.saturating_sub(1) {
+                for x in 0..self.dims.x.saturating_sub(1) {
+                    let corners: [(usize, Vec3, f32); 8] = std::array::from_fn(|c| {
+                        let c = c as u32;
+                        let (cx, cy, cz) = (x + (c & 1), y + ((c >> 1) & 1), z + ((c >> 2) & 1));
+                        let i = self.index(cx, cy, cz);
+                        (i, self.point(cx, cy, cz), self.tsdf[i])
+                    });
+
+                    if corners.iter().any(|c| self.weights[c.0] == 0.0) {
+                        continue;
+                    }
+                    let negative = corners.iter().filter(|c| c.2 < 0.0).count();
+                    if negative == 0 || negative == 8 {
+                        continue;
+                    }
+
+                    for tet in CELL_TETS {
+                        let verts = tet.map(|c| corners[c]);
+                        let (inside, outside): (Vec<_>, Vec<_>) =
+                            verts.iter().copied().partition(|v| v.2 < 0.0);
+
+                        let tris: Vec<[u32; 3]> = match (inside.len(), outside.len()) {
+                            (1, 3) | (3, 1) => {
+                                let (single, others) = if inside.len() == 1 {
+                                    (inside[0], &outside)
+                                } else {
+                                    (outside[0], &inside)
+                                };
+                                vec![[0, 1, 2].map(|i| edge_vertex(&mut mesh, single, others[i]))]
+                            }
+                            (2, 2) => {
+                                let ac = edge_vertex(&mut mesh, inside[0], outside[0]);
+                                let ad = edge_vertex(&mut mesh, inside[0], outside[1]);
+                                let bd = edge_vertex(&mut mesh, inside[1], outside[1]);
+                                let bc = edge_vertex(&mut mesh, inside[1], outside[0]);
+                                vec![[ac, ad, bd], [ac, bd, bc]]
+                            }
+                            _ => continue,
+                        };
+
+                        // Orient the triangles to face from the inside towards the outside.
+                        let centroid = |vs: &[(usize, Vec3, f32)]| {
+                            vs.iter().map(|v| v.1).sum::<Vec3>() / vs.len() as f32
+                        };
+                        let outward = centroid(&outside) - centroid(&inside);
+                        for [a, b, c] in tris {
+                            let [pa, pb, pc] = [a, b, c].map(|i| mesh.vertices[i as usize]);
+                            let normal = (pb - pa).cross(pc - pa);
+                            if normal.dot(outward) < 0.0 {
+                                mesh.triangles.push([a, c, b]);
+                            } else {
+                                mesh.triangles.push([a, b, c]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
+
+/// The voxels in `0..len` of a row starting at camera space point `start` and
+/// moving `step` per voxel that can project inside the image. Every constraint
+/// is linear along the row, so this is a conservative interval and callers
+/// still check each voxel.
+fn frustum_range(
+    start: Vec3,
+    step: Vec3,
+    focal: Vec2,
+    center: Vec2,
+    size: Vec2,
+    len: u32,
+) -> Range<u32> {
+    // Each constraint is a + b * x >= 0 for the point at voxel x.
+    let constraints = [
+        (start.z, step.z),
+        (
+            focal.x * start.x + center.x * start.z,
+            focal.x * step.x + center.x * step.z,
+        ),
+        (
+            (size.x - center.x) * start.z - focal.x * start.x,
+            (size.x - center.x) * step.z - focal.x * step.x,
+        ),
+        (
+            focal.y * start.y + center.y * start.z,
+            focal.y * step.y + center.y * step.z,
+        ),
+        (
+            (size.y - center.y) * start.z - focal.y * start.y,
+            (size.y - center.y) * step.z - focal.y * step.y,
+        ),
+    ];
+
+    let (mut lo, mut hi) = (0.0f32, len as f32);
+    for (a, b) in constraints {
+        if b > 0.0 {
+            lo = lo.max(-a / b);
+        } else if b < 0.0 {
+            hi = hi.min(-a / b);
+        } else if a < 0.0 {
+            return 0..0;
+        }
+    }
+    if lo.is_nan() || hi.is_nan() || lo > hi {
+        return 0..0;
+    }
+    // Widen by a voxel to stay on the safe side of rounding.
+    let end = ((hi.ceil() + 1.0).max(0.0) as u32).min(len);
+    let start = ((lo.floor() - 1.0).max(0.0) as u32).min(end);
+    start..end
+}
+
+/// Render depth from each camera and fuse it into a mesh of the splats within `bounds`.
+pub async fn extract_mesh(
+    splats: &Splats,
+    cameras: impl IntoIterator<Item = (Camera, UVec2)>,
+    bounds: BoundingBox,
+    resolution: u32,
+    truncation_voxels: f32,
+) -> anyhow::Result<Mesh> {
+    let mut volume = TsdfVolume::new(bounds, resolution, truncation_voxels);
+    let cameras: Vec<_> = cameras.into_iter().collect();
+    let num_views = cameras.len();
+    log::info!(
+        "Fusing {num_views} views into a {}x{}x{} volume",
+        volume.dims.x,
+        volume.dims.y,
+        volume.dims.z
+    );
+
+    for (i, (camera, img_size)) in cameras.into_iter().enumerate() {
+        let depth = splats
+            .render_depth(&camera, img_size)
+            .await
+            .into_data_async()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read back depth: {e:?}"))?
+            .into_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("Depth render isn't f32: {e:?}"))?;
+        volume.integrate(&camera, img_size, &depth);
+        log::info!("Fused view {}/{num_views}", i + 1);
+        brush_async::yield_now().await;
+    }
+
+    Ok(volume.extract_mesh())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_render::gaussian_splats::SplatRenderMode;
+    use brush_render::kernels::camera_model::CameraModel;
+    use burn::tensor::Tensor;
+    use glam::{Mat3, Quat};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn look_at_origin(position: Vec3) -> Camera {
+        let forward = -position.normalize();
+        let up = if forward.y.abs() > 0.9 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let right = up.cross(forward).normalize();
+        let down = forward.cross(right);
+        let rotation = Quat::from_mat3(&Mat3::from_cols(right, down, forward));
+        Camera::new(
+            position,
+            rotation,
+            1.0,
+            1.0,
+            glam::vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        )
+    }
+
+    #[test]
+    fn test_frustum_range() {
+        let focal = Vec2::splat(50.0);
+        let center = Vec2::new(32.0, 24.0);
+        let size = Vec2::new(64.0, 48.0);
+        let rows = [
+            (Vec3::new(-3.0, 0.1, 2.0), Vec3::new(0.05, 0.0, 0.0)),
+            (Vec3::new(-3.0, -0.2, -1.0), Vec3::new(0.04, 0.0, 0.03)),
+            (Vec3::new(2.0, 0.0, 5.0), Vec3::new(-0.03, 0.01, -0.05)),
+            (Vec3::new(0.0, 5.0, 1.0), Vec3::new(0.01, 0.0, 0.0)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::ZERO),
+        ];
+        for (start, step) in rows {
+            let range = frustum_range(start, step, focal, center, size, 200);
+            for x in 0..200 {
+                let p = start + step * x as f32;
+                let u = focal.x * p.x / p.z + center.x;
+                let v = focal.y * p.y / p.z + center.y;
+                let visible = p.z > 0.0 && u >= 0.0 && v >= 0.0 && u < size.x && v < size.y;
+                assert!(
+                    !visible || range.contains(&x),
+                    "voxel {x} of row {start} + {step} is visible but outside {range:?}"
+                );
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sphere_mesh() {
+        let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+        // Splats evenly spread over a unit sphere.
+        let count = 4000;
+        let golden = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+        let means: Vec<f32> = (0..count)
+            .flat_map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let r = (1.0 - y * y).sqrt();
+                let theta = golden * i as f32;
+                [r * theta.cos(), y, r * theta.sin()]
+            })
+            .collect();
+        let splats = Splats::from_tensor_data(
+            Tensor::<1>::from_floats(means.as_slice(), &device).reshape([count, 3]),
+            Tensor::<1>::from_floats(Quat::IDENTITY.to_array(), &device)
+                .unsqueeze_dim(0)
+                .repeat_dim(0, count),
+            Tensor::<2>::full([count, 3], 0.05f32.ln(), &device),
+            Tensor::<3>::ones([count, 1, 3], &device),
+            Tensor::<1>::full([count], 5.0, &device),
+            SplatRenderMode::Default,
+        );
+
+        let mut cameras = vec![];
+        for x in [-1.0, 1.0] {
+            for y in [-1.0, 1.0] {
+                for z in [-1.0, 1.0] {
+                    cameras.push(Vec3::new(x, y, z));
+                }
+            }
+        }
+        cameras.extend([Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z]);
+        let cameras = cameras
+            .into_iter()
+            .map(|dir| (look_at_origin(dir.normalize() * 3.5), glam::uvec2(128, 128)));
+
+        let bounds = BoundingBox::from_min_max(Vec3::splat(-1.25), Vec3::splat(1.25));
+        let mesh = extract_mesh(&splats, cameras, bounds, 64, 4.0)
+            .await
+            .unwrap();
+
+        assert!(!mesh.triangles.is_empty());
+        for v in &mesh.vertices {
+            let radius = v.length();
+            assert!(
+                (radius - 1.0).abs() < 0.08,
+                "Vertex {v} has radius {radius}, expected ~1"
+            );
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_mesh_encoding() {
+        let mesh = Mesh {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::Y],
+            triangles: vec![[0, 1, 2]],
+        };
+        let obj = String::from_utf8(mesh.to_obj()).unwrap();
+        assert!(obj.ends_with("f 1 2 3\n"));
+
+        let ply = mesh.to_ply();
+        assert!(ply.starts_with(b"ply\n"));
+        assert!(ply.ends_with(&[3, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]));
+    }
+}
@@ -1,5 +1,8 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use brush_train::eval::EvalReport;
+use brush_train::pose::RefinedPose;
 use brush_vfs::DataSource;
 use glam::Vec3;
 
@@ -21,6 +24,12 @@ pub enum TrainMessage {
         total_elapsed: web_time::Duration,
         /// If in LOD phase: `(current_lod_1_based, total_lod_levels)`.
         lod_progress: Option<(u32, u32)>,
+        /// Loss of the last step. Reading it back waits on the GPU, so it's
+        /// only set every few steps.
+        loss: Option<f32>,
+        num_visible: u32,
+        /// Most splat-tile intersections any view of the last step needed.
+        num_intersections: u32,
     },
     /// Some number of training steps are done.
     #[allow(unused)]
@@ -34,6 +43,21 @@ pub enum TrainMessage {
         iter: u32,
         avg_psnr: f32,
         avg_ssim: f32,
+        /// Average LPIPS, when evaluating with `eval_lpips`.
+        avg_lpips: Option<f32>,
+        /// Averages with the learned exposure correction applied, when
+        /// training with `learn_exposure`.
+        avg_corrected: Option<(f32, f32)>,
+        /// Per-view metrics of this eval.
+        report: Arc<EvalReport>,
+        /// The refined train view cameras, when training with `pose_refine`.
+        refined_poses: Option<Arc<Vec<RefinedPose>>>,
+    },
+    /// Splats were exported. Contains the serialized bytes in the configured
+    /// export format, as also written to disk.
+    SplatsExported {
+        iter: u32,
+        data: Vec<u8>,
     },
     DoneTraining,
 }
@@ -56,6 +80,8 @@ pub enum ProcessMessage {
         total_frames: u32,
         num_splats: u32,
         sh_degree: u32,
+        /// How much of the splat file is loaded so far, from 0 to 1. Always 1 while training.
+        progress: f32,
     },
     TrainMessage(TrainMessage),
     /// Some warning occurred during the process, but the process can continue.
@@ -0,0 +1,381 @@
+//! Render splat files straight to images, for using Brush as a renderer
+//! without running the full process, see [`crate::create_process`].
+
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+use anyhow::Context;
+#[cfg(not(target_family = "wasm"))]
+use brush_dataset::camera_path::{CameraPath, Keyframe};
+#[cfg(not(target_family = "wasm"))]
+use brush_render::bounding_box::BoundingBox;
+use brush_render::{
+    TextureMode,
+    camera::Camera,
+    gaussian_splats::{PreparedSplats, SplatRenderMode, Splats},
+};
+#[cfg(not(target_family = "wasm"))]
+use brush_train::train::get_splat_bounds;
+use burn::tensor::Device;
+#[cfg(not(target_family = "wasm"))]
+use glam::{Mat3, Quat};
+use glam::{UVec2, Vec3};
+use image::RgbaImage;
+
+/// Options matching the viewer's render settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// Color behind the splats.
+    pub background: Vec3,
+    /// Multiplier on the size of all splats, like the viewer's splat scale.
+    pub splat_scale: Option<f32>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            background: Vec3::ZERO,
+            splat_scale: None,
+        }
+    }
+}
+
+/// Load the splats of a PLY file and render them from `camera`, see
+/// [`render_splats_to_image`]. The camera is in the coordinates of the file,
+/// its up axis isn't applied.
+///
+/// `device` has to be initialized first, eg. with [`crate::burn_init_setup`].
+pub async fn render_ply_to_image(
+    ply_bytes: &[u8],
+    camera: &Camera,
+    img_size: UVec2,
+    opts: RenderOptions,
+    device: &Device,
+) -> anyhow::Result<RgbaImage> {
+    let message = brush_serde::load_splat_from_ply(ply_bytes, None)
+        .await
+        .context("Failed to load PLY")?;
+    let mode = message.meta.render_mode.unwrap_or(SplatRenderMode::Default);
+    let splats = message.data.into_splats(device, mode);
+    render_splats_to_image(splats, camera, img_size, opts).await
+}
+
+/// Render `splats` from `camera` and read the result back to an 8-bit image.
+/// The background is composited in, the alpha channel holds the splat coverage.
+pub async fn render_splats_to_image(
+    splats: Splats,
+    camera: &Camera,
+    img_size: UVec2,
+    opts: RenderOptions,
+) -> anyhow::Result<RgbaImage> {
+    let prepared = PreparedSplats::new(splats, opts.splat_scale).await;
+    render_prepared(&prepared, camera, img_size, opts.background).await
+}
+
+async fn render_prepared(
+    prepared: &PreparedSplats,
+    camera: &Camera,
+    img_size: UVec2,
+    background: Vec3,
+) -> anyhow::Result<RgbaImage> {
+    let (img, _) = prepared
+        .render(camera, img_size, background, TextureMode::Float)
+        .await;
+    let pixels: Vec<u8> = img
+        .into_data_async()
+        .await?
+        .into_vec::<f32>()?
+        .into_iter()
+        .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect();
+    RgbaImage::from_raw(img_size.x, img_size.y, pixels).context("Unexpected render size")
+}
+
+/// Load a PLY file, render it from the first camera of the [`CameraPath`] JSON file at
+/// `camera_path` and save the result to `out_path`, with the image format picked from its
+/// extension.
+#[cfg(not(target_family = "wasm"))]
+pub async fn render_ply_file(
+    ply_path: &Path,
+    camera_path: &Path,
+    img_size: UVec2,
+    opts: RenderOptions,
+    out_path: &Path,
+    device: &Device,
+) -> anyhow::Result<()> {
+    let ply = tokio::fs::read(ply_path)
+        .await
+        .with_context(|| format!("Failed to read {}", ply_path.display()))?;
+    let json = tokio::fs::read_to_string(camera_path)
+        .await
+        .with_context(|| format!("Failed to read {}", camera_path.display()))?;
+    let path = CameraPath::from_json(&json)?;
+    let camera = path.camera(path.start_time(), img_size);
+    let img = render_ply_to_image(&ply, &camera, img_size, opts, device).await?;
+    img.save(out_path)
+        .with_context(|| format!("Failed to write {}", out_path.display()))
+}
+
+/// Render the frames of a camera path at `fps` frames per second, and write them
+/// to `out_dir` as `frame_00000.png`, `frame_00001.png`, ... Returns the number
+/// of frames written.
+#[cfg(not(target_family = "wasm"))]
+pub async fn render_camera_path(
+    splats: Splats,
+    path: &CameraPath,
+    img_size: UVec2,
+    fps: f32,
+    opts: RenderOptions,
+    out_dir: &Path,
+) -> anyhow::Result<usize> {
+    tokio::fs::create_dir_all(out_dir)
+        .await
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+    let prepared = PreparedSplats::new(splats, opts.splat_scale).await;
+    let times = path.frame_times(fps);
+    for (frame, &time) in times.iter().enumerate() {
+        let camera = path.camera(time, img_size);
+        let img = render_prepared(&prepared, &camera, img_size, opts.background).await?;
+        let file = out_dir.join(format!("frame_{frame:05}.png"));
+        img.save(&file)
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+    }
+    Ok(times.len())
+}
+
+/// Load a splat file in any supported format and render it along the [`CameraPath`] JSON
+/// file at `camera_path`, see [`render_camera_path`].
+#[cfg(not(target_family = "wasm"))]
+pub async fn render_splat_file(
+    splats_path: &Path,
+    camera_path: &Path,
+    img_size: UVec2,
+    fps: f32,
+    opts: RenderOptions,
+    out_dir: &Path,
+    device: &Device,
+) -> anyhow::Result<usize> {
+    let json = tokio::fs::read_to_string(camera_path)
+        .await
+        .with_context(|| format!("Failed to read {}", camera_path.display()))?;
+    let path = CameraPath::from_json(&json)?;
+    let splats = crate::convert::load_splats(splats_path, device).await?;
+    render_camera_path(splats, &path, img_size, fps, opts, out_dir).await
+}
+
+/// Vertical field of view of orbit renders, in degrees.
+#[cfg(not(target_family = "wasm"))]
+const ORBIT_FOV: f32 = 50.0;
+/// Angle of the orbit above the center of the scene, in degrees.
+#[cfg(not(target_family = "wasm"))]
+const ORBIT_ELEVATION: f32 = 20.0;
+
+/// A circle of `frames` cameras around `bounds`, looking at its center. The orbit turns
+/// around `up` and is far enough out for the whole box to be in view. The keyframes are
+/// one second apart, so rendering the path at 1 fps gives one frame per camera.
+#[cfg(not(target_family = "wasm"))]
+pub fn orbit_path(bounds: BoundingBox, up: Vec3, frames: usize) -> CameraPath {
+    let up = up.try_normalize().unwrap_or(Vec3::NEG_Y);
+    let (side, front) = up.any_orthonormal_pair();
+    // A single splat has no extent, frame a unit box around it instead.
+    let size = match bounds.extent.length() {
+        len if len > 1e-6 => len,
+        _ => 1.0,
+    };
+    let radius = size / (ORBIT_FOV.to_radians() / 2.0).sin();
+    let elevation = ORBIT_ELEVATION.to_radians();
+
+    let keyframes = (0..frames.max(1))
+        .map(|frame| {
+            let azimuth = std::f32::consts::TAU * frame as f32 / frames.max(1) as f32;
+            let around = side * azimuth.cos() + front * azimuth.sin();
+            let position =
+                bounds.center + radius * (around * elevation.cos() + up * elevation.sin());
+
+            // Brush cameras look along +Z, with +Y pointing down.
+            let forward = (bounds.center - position).normalize();
+            let right = (-up).cross(forward).normalize();
+            let down = forward.cross(right);
+            Keyframe {
+                time: frame as f32,
+                position,
+                rotation: Quat::from_mat3(&Mat3::from_cols(right, down, forward)),
+                fov: ORBIT_FOV,
+            }
+        })
+        .collect();
+    CameraPath::new(keyframes).expect("Orbit keyframes are in order")
+}
+
+/// Load a splat file in any supported format and render `frames` frames of an orbit
+/// around it, see [`orbit_path`]. The orbit turns around the up axis stored in the file,
+/// and fits most of the splats, ignoring far away outliers. Returns the number of frames
+/// written.
+#[cfg(not(target_family = "wasm"))]
+pub async fn render_orbit_file(
+    splats_path: &Path,
+    frames: usize,
+    img_size: UVec2,
+    opts: RenderOptions,
+    out_dir: &Path,
+    device: &Device,
+) -> anyhow::Result<usize> {
+    let message = crate::convert::load_splat_file(splats_path).await?;
+    let up = message.meta.up_axis.unwrap_or(Vec3::NEG_Y);
+    let mode = message.meta.render_mode.unwrap_or(SplatRenderMode::Default);
+    let splats = message.data.into_splats(device, mode);
+    let bounds = get_splat_bounds(splats.clone(), 0.9).await;
+    let path = orbit_path(bounds, up, frames);
+    render_camera_path(splats, &path, img_size, 1.0, opts, out_dir).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_render::kernels::camera_model::CameraModel;
+    use glam::Quat;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    /// A single opaque red splat at the origin, with a standard deviation of
+    /// ~0.6 units.
+    static RED_SPLAT: &[u8] = include_bytes!("../test_data/red_splat.ply");
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, -4.0),
+            Quat::IDENTITY,
+            0.8,
+            0.8,
+            glam::vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        )
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_render_red_splat() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let size = glam::uvec2(64, 64);
+        let opts = RenderOptions {
+            background: Vec3::new(0.0, 0.0, 1.0),
+            splat_scale: None,
+        };
+        let img = render_ply_to_image(RED_SPLAT, &camera(), size, opts, &device)
+            .await
+            .unwrap();
+        assert_eq!(img.dimensions(), (64, 64));
+
+        // Red in the middle, the blue background in the corners.
+        let [r, g, b, a] = img.get_pixel(32, 32).0;
+        assert!(
+            r > 240 && g < 10 && b < 15 && a > 240,
+            "center {:?}",
+            [r, g, b, a]
+        );
+        for (x, y) in [(0, 0), (63, 0), (0, 63), (63, 63)] {
+            let [r, g, b, a] = img.get_pixel(x, y).0;
+            assert!(
+                r < 15 && g == 0 && b > 240 && a < 15,
+                "corner {:?}",
+                [r, g, b, a]
+            );
+        }
+
+        // Shrinking the splats leaves less of the image red.
+        let red = |img: &RgbaImage| img.pixels().map(|p| u32::from(p.0[0])).sum::<u32>();
+        let small = RenderOptions {
+            splat_scale: Some(0.5),
+            ..opts
+        };
+        let small_img = render_ply_to_image(RED_SPLAT, &camera(), size, small, &device)
+            .await
+            .unwrap();
+        assert!(red(&small_img) < red(&img));
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[tokio::test]
+    async fn test_render_camera_path() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let message = brush_serde::load_splat_from_ply(RED_SPLAT, None)
+            .await
+            .unwrap();
+        let splats = message.data.into_splats(&device, SplatRenderMode::Default);
+        let path = CameraPath::from_json(include_str!("../test_data/orbit_path.json")).unwrap();
+
+        let dir = std::env::temp_dir().join("brush_camera_path_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let size = glam::uvec2(48, 32);
+        let frames = render_camera_path(splats, &path, size, 2.0, RenderOptions::default(), &dir)
+            .await
+            .unwrap();
+        assert_eq!(frames, 3);
+
+        // Every keyframe looks at the splat from a different side.
+        for frame in 0..3 {
+            let img = image::open(dir.join(format!("frame_{frame:05}.png")))
+                .unwrap()
+                .into_rgba8();
+            assert_eq!(img.dimensions(), (48, 32));
+            let [r, g, b, _] = img.get_pixel(24, 16).0;
+            assert!(
+                r > 200 && g < 20 && b < 20,
+                "frame {frame}: {:?}",
+                [r, g, b]
+            );
+        }
+        assert!(!dir.join("frame_00003.png").exists());
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[tokio::test]
+    async fn test_render_orbit() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let dir = std::env::temp_dir().join("brush_orbit_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let ply = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/red_splat.ply");
+        let size = glam::uvec2(16, 16);
+        let frames = render_orbit_file(&ply, 4, size, RenderOptions::default(), &dir, &device)
+            .await
+            .unwrap();
+        assert_eq!(frames, 4);
+
+        // The splat stays in the middle of every frame.
+        for frame in 0..4 {
+            let img = image::open(dir.join(format!("frame_{frame:05}.png")))
+                .unwrap()
+                .into_rgba8();
+            assert_eq!(img.dimensions(), (16, 16));
+            let [r, g, b, _] = img.get_pixel(8, 8).0;
+            assert!(
+                r > 200 && g < 20 && b < 20,
+                "frame {frame}: {:?}",
+                [r, g, b]
+            );
+        }
+        assert!(!dir.join("frame_00004.png").exists());
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[test]
+    fn test_orbit_path_looks_at_center() {
+        let bounds = BoundingBox::from_min_max(Vec3::new(1.0, 2.0, 3.0), Vec3::new(3.0, 4.0, 5.0));
+        let path = orbit_path(bounds, Vec3::NEG_Y, 8);
+        assert_eq!(path.frame_times(1.0).len(), 8);
+        let radius = (path.keyframes()[0].position - bounds.center).length();
+        for keyframe in path.keyframes() {
+            let to_center = (bounds.center - keyframe.position).normalize();
+            let forward = keyframe.rotation * Vec3::Z;
+            assert!(
+                forward.dot(to_center) > 0.999,
+                "Camera should face the center"
+            );
+            let distance = (keyframe.position - bounds.center).length();
+            assert!((distance - radius).abs() < 1e-3, "Orbit should be a circle");
+            // Above the center, which is towards -Y here.
+            assert!(keyframe.position.y < bounds.center.y);
+        }
+    }
+}
@@ -1,19 +1,30 @@
 use crate::{
     Emitter,
-    config::TrainStreamConfig,
+    config::{ConfigError, TrainStreamConfig},
+    early_stop::PlateauDetector,
+    memory_budget::MemoryBudget,
     message::{ProcessMessage, TrainMessage},
     slot::SlotSender,
     wait_for_device,
 };
 use anyhow::Context;
-use brush_dataset::{load_dataset, scene::Scene, scene_loader::SceneLoader};
-use brush_render::gaussian_splats::{SplatRenderMode, Splats};
-use brush_rerun::visualize_tools::VisualizeTools;
+use brush_dataset::{
+    load_dataset,
+    scene::{Scene, SceneBatch},
+    scene_loader::SceneLoader,
+};
+use brush_render::gaussian_splats::{PreparedSplats, SplatRenderMode, Splats};
+use brush_rerun::{RerunConfig, visualize_tools::VisualizeTools};
+#[cfg(not(target_family = "wasm"))]
+use brush_serde::AppearanceMetadata;
 use brush_train::{
-    RandomSplatsConfig, create_random_splats,
-    eval::eval_stats,
+    RandomSplatsConfig,
+    config::TrainConfig,
+    create_random_splats,
+    eval::{EvalReport, EvalViewReport, eval_stats},
     lod::{compute_pup_scores, decimate_to_count},
     msg::RefineStats,
+    pose::RefinedPose,
     to_init_splats,
     train::{BOUND_PERCENTILE, SplatTrainer, get_splat_bounds},
 };
@@ -22,20 +33,25 @@ use burn::module::AutodiffModule;
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{AutoCompiler, WgpuRuntime};
 use rand::SeedableRng;
-use std::{path::PathBuf, sync::Arc};
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+use tokio_util::sync::CancellationToken;
 
 #[allow(unused)]
 use std::path::Path;
 
+#[cfg(all(feature = "viewer-server", not(target_family = "wasm")))]
+use crate::viewer_server::{ViewerFrame, ViewerServer};
+
 use tracing::{Instrument, trace_span};
 use web_time::{Duration, Instant};
 
 #[allow(clippy::large_stack_frames)]
 pub(crate) async fn train_stream(
     vfs: Arc<BrushVfs>,
-    train_stream_config: TrainStreamConfig,
+    mut train_stream_config: TrainStreamConfig,
     emitter: &Emitter,
     slot: SlotSender<Splats>,
+    cancel: CancellationToken,
 ) -> anyhow::Result<()> {
     log::info!("Start of training stream");
 
@@ -47,10 +63,71 @@ pub(crate) async fn train_stream(
         }))
         .await;
 
+    // Catch bad settings before spending time on loading the dataset.
+    train_stream_config.validate()?;
+    for warning in train_stream_config.warnings() {
+        emitter
+            .emit(ProcessMessage::Warning {
+                error: warning.into(),
+            })
+            .await;
+    }
+
+    let memory_budget = train_stream_config
+        .process_config
+        .memory_budget_gb
+        .map(MemoryBudget::from_gb);
+    if let Some(budget) = &memory_budget {
+        log::info!(
+            "Memory budget allows {} splats, {} intersections per render and a max resolution of {}",
+            budget.max_splats,
+            budget.max_intersections,
+            budget.max_resolution
+        );
+        for warning in train_stream_config.apply_memory_budget(budget) {
+            emitter
+                .emit(ProcessMessage::Warning {
+                    error: warning.into(),
+                })
+                .await;
+        }
+    }
+    let max_intersections = memory_budget.map(|b| b.max_intersections);
+
+    // Get the dataset name from the base path (if available) for interpolation.
+    let dataset_name = vfs
+        .base_path()
+        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "dataset".to_owned());
+
+    // Interpolate {dataset} in the export path.
+    let export_path_str = train_stream_config
+        .process_config
+        .export_path
+        .replace("{dataset}", &dataset_name);
+
+    // Resolve relative to the dataset's parent directory if available, otherwise CWD.
+    let base_path = vfs
+        .base_path()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let export_path = base_path.join(&export_path_str);
+    // Normalize path components
+    let export_path: PathBuf = export_path.components().collect();
+
+    #[cfg(not(target_family = "wasm"))]
+    crate::config::validate_export_path(&export_path)?;
+
     let process_config = &train_stream_config.process_config;
     log::info!("Using seed {}", process_config.seed);
 
-    let wgpu_device = wait_for_device().await;
+    // Cancelling only stops the training loop between steps, so race the slow
+    // setup before it against the token too.
+    let Some(wgpu_device) = cancel.run_until_cancelled(wait_for_device()).await else {
+        log::info!("Training cancelled while waiting for the device");
+        return Ok(());
+    };
     // Splats live on the inner (non-autodiff) device between steps; each
     // training step lifts them via [`lift_splats_to_autodiff`] then strips
     // back via `.valid()`. Going through `Module::train()` would hit
@@ -60,9 +137,13 @@ pub(crate) async fn train_stream(
     let mut rng = rand::rngs::StdRng::from_seed([process_config.seed as u8; 32]);
 
     log::info!("Loading dataset");
-    let load_result = load_dataset(vfs.clone(), &train_stream_config.load_config)
-        .instrument(trace_span!("Load dataset"))
-        .await?;
+    let load = load_dataset(vfs.clone(), &train_stream_config.load_config)
+        .instrument(trace_span!("Load dataset"));
+    let Some(load_result) = cancel.run_until_cancelled(load).await else {
+        log::info!("Training cancelled while loading the dataset");
+        return Ok(());
+    };
+    let load_result = load_result?;
 
     // Emit any warnings from dataset loading.
     for warning in load_result.warnings {
@@ -74,6 +155,7 @@ pub(crate) async fn train_stream(
     }
 
     let dataset = load_result.dataset;
+    train_stream_config.validate_dataset(&dataset)?;
 
     log::info!("Log scene to rerun");
     if let Err(error) = visualize.log_scene(
@@ -112,10 +194,11 @@ pub(crate) async fn train_stream(
         if data.num_splats() < original {
             emitter
                 .emit(ProcessMessage::Warning {
-                    error: anyhow::anyhow!(
-                        "Initial point cloud has {original} points, exceeding --max-splats ({max_splats}). Subsampled to {}; the remaining points were discarded. Raise --max-splats to keep more.",
-                        data.num_splats()
-                    ),
+                    error: ConfigError::MaxSplatsBelowInit {
+                        max_splats: max_splats as u32,
+                        init: original,
+                    }
+                    .into(),
                 })
                 .await;
         }
@@ -159,6 +242,7 @@ pub(crate) async fn train_stream(
             total_frames: 1,
             num_splats: init_splats.num_splats(),
             sh_degree: init_splats.sh_degree(),
+            progress: 1.0,
         })
         .await;
 
@@ -169,44 +253,73 @@ pub(crate) async fn train_stream(
     client.memory_cleanup();
 
     let mut eval_scene = dataset.eval;
+    let lpips = process_config
+        .eval_lpips
+        .then(|| lpips::load_vgg_lpips(&device));
 
     let mut train_duration = Duration::from_secs(0);
-    let mut dataloader = SceneLoader::new(&dataset.train, 42, &train_stream_config.load_config);
+    let new_loader = |scene: &Scene| {
+        let load_config = &train_stream_config.load_config;
+        if process_config.deterministic {
+            SceneLoader::new_sequential(scene, process_config.seed, load_config)
+        } else {
+            SceneLoader::new(scene, process_config.seed, load_config)
+        }
+    };
+    let mut dataloader = new_loader(&dataset.train);
     let bounds = get_splat_bounds(init_splats.clone(), BOUND_PERCENTILE).await;
 
     // Per-train-view (world center, focal-px at native res) for the
     // Mip-Splatting 3D filter (always on).
-    let mut view_cams: Vec<(glam::Vec3, f32)> = Vec::with_capacity(dataset.train.views.len());
-    for view in dataset.train.views.iter() {
-        let (w, h) = view.image.dimensions().await.unwrap_or((1, 1));
-        let focal = view.camera.focal(glam::uvec2(w, h)).x;
-        view_cams.push((view.camera.position, focal));
-    }
+    // Reads the header of every image, which can take a while for big datasets.
+    let read_view_cams = async {
+        let mut view_cams = Vec::with_capacity(dataset.train.views.len());
+        for view in dataset.train.views.iter() {
+            let (w, h) = view.image.dimensions().await.unwrap_or((1, 1));
+            let focal = view.camera.focal(glam::uvec2(w, h)).x;
+            view_cams.push((view.camera.position, focal));
+        }
+        view_cams
+    };
+    let Some(view_cams): Option<Vec<(glam::Vec3, f32)>> =
+        cancel.run_until_cancelled(read_view_cams).await
+    else {
+        log::info!("Training cancelled while reading the view sizes");
+        return Ok(());
+    };
 
-    let mut trainer = SplatTrainer::new(&train_stream_config.train_config, &device, bounds);
+    let mut trainer = SplatTrainer::new(&train_stream_config.train_config, &device, bounds)
+        .with_seed(process_config.seed)
+        .with_max_intersections(max_intersections);
     trainer.set_view_cams(view_cams.clone());
 
-    // Get the dataset name from the base path (if available) for interpolation.
-    let dataset_name = vfs
-        .base_path()
-        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned()))
-        .unwrap_or_else(|| "dataset".to_owned());
+    #[allow(unused_mut)]
+    let mut start_iter = train_stream_config.process_config.start_iter;
 
-    // Interpolate {dataset} in the export path.
-    let export_path_str = train_stream_config
-        .process_config
-        .export_path
-        .replace("{dataset}", &dataset_name);
-
-    // Resolve relative to the dataset's parent directory if available, otherwise CWD.
-    let base_path = vfs
-        .base_path()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_else(|| PathBuf::from("."));
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(resume) = &train_stream_config.process_config.resume {
+        let (resumed, resumed_splats, iter) = SplatTrainer::load_checkpoint(
+            &train_stream_config.train_config,
+            Path::new(resume),
+            &device,
+        )
+        .map_err(|e| anyhow::anyhow!("{e:?}"))
+        .with_context(|| format!("Failed to load checkpoint {resume}"))?;
+        log::info!("Resuming training from iteration {iter}");
+
+        // The checkpoint doesn't hold the trainer's RNG, the device RNG or the
+        // loader's place in the shuffle, so all of these restart from the seed.
+        // Resuming the same checkpoint is reproducible, but won't take the same
+        // random draws as a run that went straight through.
+        trainer = resumed
+            .with_seed(process_config.seed.wrapping_add(iter as u64))
+            .with_max_intersections(max_intersections);
+        trainer.set_view_cams(view_cams.clone());
+        splats = resumed_splats;
+        slot.set(0, splats.clone());
+        start_iter = iter;
+    }
 
-    let export_path = base_path.join(&export_path_str);
-    // Normalize path components
-    let export_path: PathBuf = export_path.components().collect();
     let sh_degree = init_splats.sh_degree();
 
     let training_steps = train_stream_config.train_config.total_train_iters;
@@ -216,8 +329,47 @@ pub(crate) async fn train_stream(
 
     let process_config = &train_stream_config.process_config;
 
+    #[cfg(all(feature = "viewer-server", not(target_family = "wasm")))]
+    let viewer = if let Some(port) = process_config.viewer_port {
+        let host = &process_config.viewer_host;
+        match ViewerServer::start(host, port, process_config.export_format()).await {
+            Ok(server) => Some(server),
+            Err(e) => {
+                let error = anyhow::Error::from(e)
+                    .context(format!("Failed to start viewer server on {host}:{port}"));
+                emitter.emit(ProcessMessage::Warning { error }).await;
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    #[cfg(not(all(feature = "viewer-server", not(target_family = "wasm"))))]
+    if process_config.viewer_port.is_some() {
+        let error =
+            anyhow::anyhow!("--viewer-port needs a native build with the viewer-server feature");
+        emitter.emit(ProcessMessage::Warning { error }).await;
+    }
+
+    // Last splats known to be finite, and the iteration they're from.
+    let mut last_good: Option<(u32, Splats)> = None;
+    // Only warn once about skipping growth, it'll likely keep happening.
+    let mut warned_growth_skipped = false;
+    // Views skipped for needing more intersections than the budget allows,
+    // warned about once each.
+    let mut warned_over_budget = HashSet::new();
+    let mut plateau = process_config
+        .early_stop_patience
+        .map(|patience| PlateauDetector::new(patience, process_config.early_stop_min_delta));
+
     log::info!("Start training loop.");
-    for iter in process_config.start_iter..train_stream_config.train_config.total_iters() {
+    for iter in start_iter..train_stream_config.train_config.total_iters() {
+        if cancel.is_cancelled() {
+            log::info!("Training cancelled at iteration {iter}");
+            break;
+        }
+
         let target_lod = if lod_levels == 0 || iter < training_steps {
             0u32
         } else {
@@ -227,27 +379,40 @@ pub(crate) async fn train_stream(
         if target_lod > current_lod {
             #[cfg(not(target_family = "wasm"))]
             {
+                let export_name = process_config.export_file_name();
                 let (name, exp_iter, exp_total) = if current_lod == 0 {
-                    (process_config.export_name.clone(), iter, training_steps)
+                    (export_name, iter, training_steps)
                 } else {
-                    let lod_name = process_config
-                        .export_name
-                        .replace(".ply", &format!("_lod{current_lod}.ply"));
+                    let lod_name = lod_export_name(&export_name, current_lod);
                     (lod_name, lod_refine_steps, lod_refine_steps)
                 };
-                let res = export_checkpoint(
-                    splats.clone(),
-                    &export_path,
-                    &name,
-                    exp_iter,
-                    exp_total,
-                    up_axis,
-                )
+                let res = async {
+                    let appearance = appearance_metadata(&trainer).await?;
+                    export_checkpoint(
+                        splats.clone(),
+                        &export_path,
+                        &name,
+                        process_config.export_format(),
+                        exp_iter,
+                        exp_total,
+                        up_axis,
+                        &appearance,
+                    )
+                    .await
+                }
                 .await
                 .with_context(|| "Export at LOD boundary failed");
 
-                if let Err(error) = res {
-                    emitter.emit(ProcessMessage::Warning { error }).await;
+                match res {
+                    Ok(data) => {
+                        emitter
+                            .emit(ProcessMessage::TrainMessage(TrainMessage::SplatsExported {
+                                iter,
+                                data,
+                            }))
+                            .await;
+                    }
+                    Err(error) => emitter.emit(ProcessMessage::Warning { error }).await,
                 }
             }
 
@@ -274,13 +439,20 @@ pub(crate) async fn train_stream(
             let cumulative_scale = (lod_img_pct as f32 / 100.0).powi(current_lod as i32);
             dataloader = if lod_img_pct < 100 {
                 let lod_scene = dataset.train.clone().with_image_scale(cumulative_scale);
-                SceneLoader::new(&lod_scene, 42, &train_stream_config.load_config)
+                new_loader(&lod_scene)
             } else {
-                SceneLoader::new(&dataset.train, 42, &train_stream_config.load_config)
+                new_loader(&dataset.train)
             };
 
             let bounds = get_splat_bounds(splats.clone(), BOUND_PERCENTILE).await;
-            trainer = SplatTrainer::new(&train_stream_config.train_config, &device, bounds);
+            // The splats are already trained, don't warm up their SH bands again.
+            let lod_config = TrainConfig {
+                sh_warmup_every: None,
+                ..train_stream_config.train_config.clone()
+            };
+            trainer = SplatTrainer::new(&lod_config, &device, bounds)
+                .with_seed(process_config.seed.wrapping_add(current_lod as u64))
+                .with_max_intersections(max_intersections);
             trainer.set_view_cams(view_cams.clone());
 
             log::info!(
@@ -291,39 +463,102 @@ pub(crate) async fn train_stream(
 
         let step_time = Instant::now();
 
-        let batch = dataloader
-            .next_batch()
+        let batch_size = train_stream_config.train_config.batch_size.max(1) as usize;
+        let mut batches = dataloader
+            .next_batches(batch_size)
             .instrument(trace_span!("Wait for next data batch"))
             .await;
 
+        // Skip views whose render would need more intersections than the budget
+        // leaves room for, rather than running out of memory allocating them.
+        if let Some(max) = max_intersections {
+            // The loader can hand out the same view again before it has been
+            // through all of them, so count distinct views.
+            let mut skipped_views = HashSet::new();
+            loop {
+                let (kept, skipped) = split_over_budget(&splats, batches, max).await;
+                for &(view_index, count) in &skipped {
+                    if warned_over_budget.insert(view_index) {
+                        let name = dataset.train.views[view_index].image.img_name();
+                        let error = anyhow::anyhow!(
+                            "View {name} needs {count} intersections at iteration {iter}, more than fit in --memory-budget-gb ({max}), skipping it"
+                        );
+                        emitter.emit(ProcessMessage::Warning { error }).await;
+                    }
+                }
+                skipped_views.extend(skipped.iter().map(|&(view_index, _)| view_index));
+                if !kept.is_empty() {
+                    batches = kept;
+                    break;
+                }
+                if skipped_views.len() >= dataset.train.views.len() {
+                    anyhow::bail!(
+                        "No view renders within --memory-budget-gb at iteration {iter}, lower --max-resolution or raise --memory-budget-gb"
+                    );
+                }
+                batches = dataloader
+                    .next_batches(batch_size)
+                    .instrument(trace_span!("Wait for next data batch"))
+                    .await;
+            }
+        }
+
         // Lift splats onto the autodiff graph for this step, run training,
         // then strip back to inner so the viewer slot sees plain splats.
         // `step` immediately replaces `splats` with the returned value, so we
         // can move it here instead of cloning every iteration.
         let diff_splats = brush_render_bwd::burn_glue::lift_splats_to_autodiff(splats);
-        let (new_diff_splats, stats) = trainer.step(batch, diff_splats).await;
+        let (new_diff_splats, stats) = trainer.step_batch(batches, diff_splats).await;
         splats = new_diff_splats.valid();
 
-        // Phase-local iteration for refine gating
-        let phase_iter = if current_lod == 0 {
-            iter
-        } else {
-            (iter - training_steps) % lod_refine_steps
-        };
-        let phase_total = if current_lod == 0 {
-            training_steps
+        let step_ok = stats.non_finite_views.is_empty();
+        if !step_ok {
+            let names: Vec<_> = stats
+                .non_finite_views
+                .iter()
+                .map(|&i| dataset.train.views[i].image.img_name())
+                .collect();
+            let mut error = anyhow::anyhow!(
+                "Loss is NaN or infinite at iteration {iter} for {}, skipped the update",
+                names.join(", ")
+            );
+            if let Some((good_iter, good_splats)) = &last_good
+                && !splats_are_finite(&splats).await
+            {
+                splats = good_splats.clone();
+                trainer.reset_splat_state();
+                error = error.context(format!(
+                    "The splats went NaN, rolled back to iteration {good_iter}"
+                ));
+            }
+            emitter.emit(ProcessMessage::Warning { error }).await;
+        }
+
+        // LOD phases refine on their own schedule, over most of the phase.
+        let refine_due = if current_lod == 0 {
+            train_stream_config.train_config.refine_due(iter)
         } else {
-            lod_refine_steps
+            let phase_iter = (iter - training_steps) % lod_refine_steps;
+            let phase_progress = (phase_iter as f32 / lod_refine_steps as f32).clamp(0.0, 1.0);
+            phase_iter > 0
+                && phase_iter.is_multiple_of(train_stream_config.train_config.refine_every)
+                && phase_progress <= 0.95
         };
-        let phase_progress = (phase_iter as f32 / phase_total as f32).clamp(0.0, 1.0);
 
         let refine_start = Instant::now();
-        let refine = if phase_iter > 0
-            && phase_iter.is_multiple_of(train_stream_config.train_config.refine_every)
-            && phase_progress <= 0.95
-        {
+        let refine = if step_ok && refine_due {
             let (new_splats, refine_stats) = trainer.refine(iter, splats).await;
             splats = new_splats;
+            if refine_stats.growth_skipped && !warned_growth_skipped {
+                warned_growth_skipped = true;
+                let error = anyhow::anyhow!(
+                    "Rendering needs more intersections than fit in --memory-budget-gb, stopped growing the splats until it fits again"
+                );
+                emitter.emit(ProcessMessage::Warning { error }).await;
+            }
+            if process_config.rollback_on_nan && splats_are_finite(&splats).await {
+                last_good = Some((iter, splats.clone()));
+            }
             refine_stats
         } else {
             RefineStats {
@@ -332,6 +567,8 @@ pub(crate) async fn train_stream(
                 num_split_high_grad: 0,
                 num_pruned: 0,
                 num_pruned_non_finite: 0,
+                opacity_reset: false,
+                growth_skipped: false,
                 total_splats: splats.num_splats(),
             }
         };
@@ -340,10 +577,10 @@ pub(crate) async fn train_stream(
 
         // We just finished iter 'iter', now starting iter + 1.
         let iter = iter + 1;
-        let is_last_step = iter == train_stream_config.train_config.total_iters();
 
         let step_dur = step_time.elapsed();
         train_duration += step_dur;
+        let mut stop_early = false;
 
         // Do evals. We skip this for LODs as it'd be confusing for rerun, but, could
         // revisit this.
@@ -356,20 +593,63 @@ pub(crate) async fn train_stream(
                 .eval_save_to_disk
                 .then(|| export_path.clone());
 
+            let refined_poses = if train_stream_config.train_config.pose_refine {
+                Some(refined_poses(&trainer, &dataset.train).await)
+            } else {
+                None
+            };
+
             let eval = run_eval(
                 &device,
                 emitter,
                 &visualize,
                 splats.clone(),
+                trainer.mean_exposure().as_ref(),
+                lpips.as_ref(),
                 iter,
                 eval_scene,
                 save_path,
-                train_stream_config.rerun_config.rerun_max_img_size,
+                refined_poses,
+                &train_stream_config.rerun_config,
+                &train_stream_config.train_config,
             )
             .await
             .with_context(|| format!("Failed evaluation at iteration {iter}"));
 
-            if let Err(error) = eval {
+            match eval {
+                Ok(Some(psnr)) => {
+                    if let Some(plateau) = plateau.as_mut()
+                        && plateau.update(psnr)
+                    {
+                        log::info!("Eval PSNR plateaued, stopping training at iteration {iter}");
+                        stop_early = true;
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => emitter.emit(ProcessMessage::Warning { error }).await,
+            }
+        }
+        // Stopping early wraps up as if this was the last step, skipping the LOD phases.
+        let is_last_step = iter == train_stream_config.train_config.total_iters() || stop_early;
+
+        // Save training state to resume from. LOD phases rebuild the trainer, so
+        // only checkpoint the main training phase.
+        #[cfg(not(target_family = "wasm"))]
+        if current_lod == 0
+            && let Some(every) = process_config.checkpoint_every
+            && iter.is_multiple_of(every)
+        {
+            let path = export_path.join(format!("checkpoint_{iter}"));
+            let res = async {
+                tokio::fs::create_dir_all(&export_path).await?;
+                trainer
+                    .save_checkpoint(&splats, iter, &path)
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+            }
+            .await
+            .with_context(|| format!("Saving checkpoint at iteration {iter} failed"));
+
+            if let Err(error) = res {
                 emitter.emit(ProcessMessage::Warning { error }).await;
             }
         }
@@ -378,36 +658,62 @@ pub(crate) async fn train_stream(
         #[cfg(not(target_family = "wasm"))]
         {
             let should_export = if current_lod == 0 {
-                iter % process_config.export_every == 0 || (is_last_step && lod_levels == 0)
+                iter % process_config.export_every == 0
+                    || (is_last_step && (lod_levels == 0 || stop_early))
             } else {
                 is_last_step
             };
             if should_export {
+                let export_name = process_config.export_file_name();
                 let (name, exp_iter, exp_total) = if current_lod == 0 {
-                    (process_config.export_name.clone(), iter, training_steps)
+                    (export_name, iter, training_steps)
                 } else {
-                    let lod_name = process_config
-                        .export_name
-                        .replace(".ply", &format!("_lod{current_lod}.ply"));
+                    let lod_name = lod_export_name(&export_name, current_lod);
                     (lod_name, lod_refine_steps, lod_refine_steps)
                 };
-                let res = export_checkpoint(
-                    splats.clone(),
-                    &export_path,
-                    &name,
-                    exp_iter,
-                    exp_total,
-                    up_axis,
-                )
+                let res = async {
+                    let appearance = appearance_metadata(&trainer).await?;
+                    export_checkpoint(
+                        splats.clone(),
+                        &export_path,
+                        &name,
+                        process_config.export_format(),
+                        exp_iter,
+                        exp_total,
+                        up_axis,
+                        &appearance,
+                    )
+                    .await
+                }
                 .await
                 .with_context(|| format!("Export at iteration {iter} failed"));
 
-                if let Err(error) = res {
-                    emitter.emit(ProcessMessage::Warning { error }).await;
+                match res {
+                    Ok(data) => {
+                        emitter
+                            .emit(ProcessMessage::TrainMessage(TrainMessage::SplatsExported {
+                                iter,
+                                data,
+                            }))
+                            .await;
+                    }
+                    Err(error) => emitter.emit(ProcessMessage::Warning { error }).await,
                 }
             }
         }
 
+        #[cfg(all(feature = "viewer-server", not(target_family = "wasm")))]
+        if let Some(viewer) = &viewer
+            && (iter.is_multiple_of(process_config.viewer_every) || is_last_step)
+        {
+            viewer.publish(ViewerFrame {
+                iter,
+                splats: splats.clone(),
+                stats: stats.clone(),
+                up_axis,
+            });
+        }
+
         // --- Rerun logging ---
         {
             let rerun_config = &train_stream_config.rerun_config;
@@ -466,6 +772,8 @@ pub(crate) async fn train_stream(
         }
 
         const UPDATE_EVERY: u32 = 5;
+        // Reading back the loss waits for the GPU to catch up, so do it less often.
+        const LOSS_EVERY: u32 = 50;
         if iter % UPDATE_EVERY == 0 || is_last_step {
             emitter
                 .emit(ProcessMessage::SplatsUpdated {
@@ -474,6 +782,7 @@ pub(crate) async fn train_stream(
                     total_frames: 1,
                     num_splats: refine.total_splats,
                     sh_degree,
+                    progress: 1.0,
                 })
                 .await;
 
@@ -483,16 +792,67 @@ pub(crate) async fn train_stream(
                 None
             };
 
+            let loss = if iter % LOSS_EVERY == 0 || is_last_step {
+                stats.loss.clone().into_scalar_async::<f32>().await.ok()
+            } else {
+                None
+            };
+
             emitter
                 .emit(ProcessMessage::TrainMessage(TrainMessage::TrainStep {
                     iter,
                     total_elapsed: train_duration,
                     lod_progress,
+                    loss,
+                    num_visible: stats.num_visible,
+                    num_intersections: stats.num_intersections,
                 }))
                 .await;
         }
 
         brush_async::yield_now().await;
+
+        if stop_early {
+            break;
+        }
+    }
+
+    // A cancelled run skips the work after training, it should end promptly.
+    #[cfg(not(target_family = "wasm"))]
+    if !cancel.is_cancelled()
+        && let Some(mesh_name) = &process_config.export_mesh
+    {
+        let res = export_mesh(
+            &splats,
+            &dataset.train,
+            &export_path.join(mesh_name),
+            process_config.mesh_resolution,
+            process_config.mesh_truncation,
+        )
+        .await
+        .with_context(|| format!("Mesh export to {mesh_name} failed"));
+
+        if let Err(error) = res {
+            emitter.emit(ProcessMessage::Warning { error }).await;
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    if !cancel.is_cancelled()
+        && let Some(path_file) = &process_config.render_path
+    {
+        let res = render_path(
+            splats.clone(),
+            Path::new(path_file),
+            &export_path.join(&process_config.render_out),
+            process_config,
+        )
+        .await
+        .with_context(|| format!("Rendering camera path {path_file} failed"));
+
+        if let Err(error) = res {
+            emitter.emit(ProcessMessage::Warning { error }).await;
+        }
     }
 
     emitter
@@ -507,41 +867,59 @@ async fn run_eval(
     emitter: &Emitter,
     visualize: &VisualizeTools,
     splats: Splats,
+    exposure: Option<&burn::tensor::Tensor<2>>,
+    lpips: Option<&lpips::LpipsModel>,
     iter: u32,
     eval_scene: &Scene,
     save_path: Option<PathBuf>,
-    rerun_max_img_size: u32,
-) -> Result<(), anyhow::Error> {
+    refined_poses: Option<Vec<RefinedPose>>,
+    rerun_config: &RerunConfig,
+    train_config: &TrainConfig,
+) -> Result<Option<f32>, anyhow::Error> {
     if eval_scene.views.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
-    let mut psnr = 0.0;
-    let mut ssim = 0.0;
-    let mut count = 0;
+    let mut report = EvalReport {
+        iter,
+        views: vec![],
+    };
+    let mut corrected: Option<(f32, f32)> = None;
     log::info!("Running evaluation for iteration {iter}");
 
+    let num_splats = splats.num_splats();
+    let prepared = PreparedSplats::new(splats, None).await;
+
     for (i, view) in eval_scene.views.iter().enumerate() {
         brush_async::yield_now().await;
 
         let eval_img = view.image.load().await?;
         let sample = eval_stats(
-            splats.clone(),
+            &prepared,
             &view.camera,
             eval_img,
             view.image.alpha_mode(),
+            train_config.ssim_mode,
+            train_config.ssim_window(),
+            exposure.cloned(),
+            lpips,
             device,
         )
         .await
         .context("Failed to run eval for sample.")?;
 
-        count += 1;
-        psnr += sample.psnr.clone().into_scalar_async::<f32>().await?;
-        ssim += sample.ssim.clone().into_scalar_async::<f32>().await?;
+        let img_name = view.image.img_name();
+        report
+            .views
+            .push(EvalViewReport::from_sample(img_name.clone(), &sample, num_splats).await?);
+        if let (Some(c_psnr), Some(c_ssim)) = (&sample.psnr_corrected, &sample.ssim_corrected) {
+            let (sum_psnr, sum_ssim) = corrected.get_or_insert((0.0, 0.0));
+            *sum_psnr += c_psnr.clone().into_scalar_async::<f32>().await?;
+            *sum_ssim += c_ssim.clone().into_scalar_async::<f32>().await?;
+        }
 
         #[cfg(not(target_family = "wasm"))]
         if let Some(path) = &save_path {
-            let img_name = view.image.img_name();
             let path = path
                 .join(format!("eval_{iter}"))
                 .join(format!("{img_name}.png"));
@@ -552,21 +930,140 @@ async fn run_eval(
         let _ = save_path;
 
         visualize
-            .log_eval_sample(iter, i as u32, sample, rerun_max_img_size)
+            .log_eval_sample(
+                iter,
+                i as u32,
+                sample,
+                rerun_config.rerun_max_img_size,
+                rerun_config.rerun_log_tile_counts,
+            )
             .await?;
     }
-    psnr /= count as f32;
-    ssim /= count as f32;
+    let count = report.views.len() as f32;
+    let (psnr, ssim) = (report.avg_psnr(), report.avg_ssim());
+    let avg_corrected = corrected.map(|(p, s)| (p / count, s / count));
+
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(path) = &save_path {
+        let dir = path.join(format!("eval_{iter}"));
+        report.save_to_dir(&dir).await?;
+        if let Some(poses) = &refined_poses {
+            let txt = brush_train::pose::to_colmap_images_txt(poses);
+            tokio::fs::write(dir.join("images.txt"), txt).await?;
+        }
+    }
+
     visualize.log_eval_stats(iter, psnr, ssim)?;
     emitter
         .emit(ProcessMessage::TrainMessage(TrainMessage::EvalResult {
             iter,
             avg_psnr: psnr,
             avg_ssim: ssim,
+            avg_lpips: report.avg_lpips(),
+            avg_corrected,
+            report: Arc::new(report),
+            refined_poses: refined_poses.map(Arc::new),
         }))
         .await;
 
-    Ok(())
+    Ok(Some(psnr))
+}
+
+/// Split `batches` into the views that render within `max_intersections` and
+/// the `(view index, intersections)` of those that don't. Counting only runs
+/// the project pass, so this catches a view before its render allocates the
+/// intersection buffers.
+async fn split_over_budget(
+    splats: &Splats,
+    batches: Vec<SceneBatch>,
+    max_intersections: u64,
+) -> (Vec<SceneBatch>, Vec<(usize, u32)>) {
+    let mut kept = Vec::with_capacity(batches.len());
+    let mut skipped = vec![];
+    for batch in batches {
+        let [h, w] = batch.img_size();
+        let img_size = glam::uvec2(w as u32, h as u32);
+        let count = splats.count_intersections(&batch.camera, img_size).await;
+        if count as u64 > max_intersections {
+            skipped.push((batch.view_index, count));
+        } else {
+            kept.push(batch);
+        }
+    }
+    (kept, skipped)
+}
+
+/// Whether all splat parameters are finite. Reads back from the GPU.
+async fn splats_are_finite(splats: &Splats) -> bool {
+    let non_finite = splats.transforms.val().is_finite().bool_not().int().sum()
+        + splats.sh_coeffs.val().is_finite().bool_not().int().sum()
+        + splats
+            .raw_opacities
+            .val()
+            .is_finite()
+            .bool_not()
+            .int()
+            .sum();
+    non_finite
+        .into_scalar_async::<i32>()
+        .await
+        .is_ok_and(|count| count == 0)
+}
+
+/// The train view cameras with their learned pose corrections.
+async fn refined_poses(trainer: &SplatTrainer, scene: &Scene) -> Vec<RefinedPose> {
+    let mut poses = Vec::with_capacity(scene.views.len());
+    for (i, view) in scene.views.iter().enumerate() {
+        let camera = trainer.refined_camera(i, &view.camera).await;
+        poses.push(RefinedPose {
+            name: view.image.img_name(),
+            rotation_deg: camera
+                .rotation
+                .angle_between(view.camera.rotation)
+                .to_degrees(),
+            translation: camera.position.distance(view.camera.position),
+            camera,
+        });
+    }
+    poses
+}
+
+/// Name of the export for a LOD level, eg. `export_{iter}_lod1.ply`.
+#[cfg(not(target_family = "wasm"))]
+fn lod_export_name(export_name: &str, lod: u32) -> String {
+    let path = Path::new(export_name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => format!(
+            "{}_lod{lod}.{}",
+            stem.to_string_lossy(),
+            ext.to_string_lossy()
+        ),
+        _ => format!("{export_name}_lod{lod}"),
+    }
+}
+
+/// The training background & learned per-view color corrections, to store
+/// alongside the exported splats.
+#[cfg(not(target_family = "wasm"))]
+async fn appearance_metadata(trainer: &SplatTrainer) -> Result<AppearanceMetadata, anyhow::Error> {
+    let background = trainer.export_background().await;
+    let mut view_corrections = vec![];
+    for correction in (0..).map_while(|i| trainer.exposure(i)) {
+        let data = correction
+            .into_data_async()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read back color correction: {e:?}"))?
+            .into_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("Color correction isn't f32: {e:?}"))?;
+        let correction = data
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Color correction isn't [3, 4]"))?;
+        view_corrections.push(correction);
+    }
+    Ok(AppearanceMetadata {
+        background,
+        view_corrections,
+    })
 }
 
 // TODO: Want to support this on WASM somehow. Maybe have user pick a file once,
@@ -576,20 +1073,93 @@ async fn export_checkpoint(
     splats: Splats,
     export_path: &Path,
     export_name: &str,
+    format: brush_serde::ExportFormat,
     iter: u32,
     total_steps: u32,
     up_axis: Option<glam::Vec3>,
-) -> Result<(), anyhow::Error> {
+    appearance: &AppearanceMetadata,
+) -> Result<Vec<u8>, anyhow::Error> {
     tokio::fs::create_dir_all(&export_path)
         .await
         .with_context(|| format!("Creating export directory {}", export_path.display()))?;
     let digits = ((total_steps as f64).log10().floor() as usize) + 1;
     let export_name = export_name.replace("{iter}", &format!("{iter:0digits$}"));
-    let splat_data = brush_serde::splat_to_ply(splats, up_axis)
+    let splat_data = brush_serde::splat_export(splats, up_axis, appearance, format)
         .await
         .context("Serializing splat data")?;
-    tokio::fs::write(export_path.join(&export_name), splat_data)
+    tokio::fs::write(export_path.join(&export_name), &splat_data)
+        .await
+        .context(format!("Failed to export splats {export_path:?}"))?;
+    Ok(splat_data)
+}
+
+#[cfg(not(target_family = "wasm"))]
+async fn render_path(
+    splats: Splats,
+    path_file: &Path,
+    out_dir: &Path,
+    process_config: &crate::config::ProcessConfig,
+) -> Result<(), anyhow::Error> {
+    let json = tokio::fs::read_to_string(path_file).await?;
+    let path = brush_dataset::camera_path::CameraPath::from_json(&json)?;
+    let img_size = glam::uvec2(process_config.render_width, process_config.render_height);
+    let frames = crate::render::render_camera_path(
+        splats,
+        &path,
+        img_size,
+        process_config.render_fps,
+        crate::render::RenderOptions::default(),
+        out_dir,
+    )
+    .await?;
+    log::info!(
+        "Rendered {frames} camera path frames to {}",
+        out_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(not(target_family = "wasm"))]
+async fn export_mesh(
+    splats: &Splats,
+    scene: &Scene,
+    path: &Path,
+    resolution: u32,
+    truncation: f32,
+) -> Result<(), anyhow::Error> {
+    // Depth renders are read back for every view, so cap their size.
+    const MAX_DEPTH_SIZE: u32 = 1024;
+
+    log::info!("Extracting mesh from {} views", scene.views.len());
+    let mut cameras = Vec::with_capacity(scene.views.len());
+    for view in scene.views.iter() {
+        let (w, h) = view.image.dimensions().await?;
+        let scale = (MAX_DEPTH_SIZE as f32 / w.max(h) as f32).min(1.0);
+        let img_size = (glam::vec2(w as f32, h as f32) * scale)
+            .round()
+            .as_uvec2()
+            .max(glam::UVec2::ONE);
+        cameras.push((view.camera, img_size));
+    }
+
+    // Leave out far away floaters, they'd waste most of the voxels.
+    let bounds = get_splat_bounds(splats.clone(), 0.99).await;
+    let bounds = brush_render::bounding_box::BoundingBox {
+        center: bounds.center,
+        extent: bounds.extent * 1.05,
+    };
+    let mesh = crate::mesh::extract_mesh(splats, cameras, bounds, resolution, truncation).await?;
+    log::info!(
+        "Mesh has {} vertices and {} triangles",
+        mesh.vertices.len(),
+        mesh.triangles.len()
+    );
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, mesh.encode_for_path(path))
         .await
-        .context(format!("Failed to export ply {export_path:?}"))?;
+        .with_context(|| format!("Failed to write mesh {}", path.display()))?;
     Ok(())
 }
@@ -0,0 +1,307 @@
+//! A small HTTP + WebSocket server to watch training live. It only listens on
+//! localhost unless `--viewer-host` says otherwise, as anyone who can connect
+//! can download the splats.
+//!
+//! The train loop publishes frames into a watch channel, which never blocks and
+//! drops frames that weren't picked up yet. A separate task reads back the loss
+//! and serializes the splats, and every connected client then receives a JSON
+//! stats message followed by a binary message holding the exported splat file.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use brush_render::gaussian_splats::Splats;
+use brush_serde::{AppearanceMetadata, ExportFormat};
+use brush_train::msg::TrainStepStats;
+use futures_util::SinkExt;
+use glam::Vec3;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::Message;
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>Brush training</title></head>
+<body>
+<pre id="stats">Waiting for training...</pre>
+<script>
+const ws = new WebSocket(`ws://${location.host}/`);
+ws.onmessage = (e) => {
+  if (typeof e.data === "string") {
+    document.getElementById("stats").textContent = JSON.stringify(JSON.parse(e.data), null, 2);
+  }
+};
+</script>
+</body>
+</html>
+"#;
+
+/// The state of training to send to clients.
+#[derive(Clone)]
+pub struct ViewerFrame {
+    pub iter: u32,
+    pub splats: Splats,
+    pub stats: TrainStepStats,
+    pub up_axis: Option<Vec3>,
+}
+
+#[derive(Serialize)]
+struct StatsMessage {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    iter: u32,
+    loss: f32,
+    num_splats: u32,
+    num_visible: u32,
+    format: &'static str,
+}
+
+struct EncodedFrame {
+    stats: String,
+    splats: Vec<u8>,
+}
+
+pub struct ViewerServer {
+    frames: watch::Sender<Option<ViewerFrame>>,
+    addr: SocketAddr,
+}
+
+impl ViewerServer {
+    /// Start serving on `host` & `port`. Port 0 picks a free port, see [`Self::local_addr`].
+    pub async fn start(host: &str, port: u16, format: ExportFormat) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((host, port)).await?;
+        let addr = listener.local_addr()?;
+
+        let (frames, frame_rx) = watch::channel(None);
+        let encoded = Arc::new(watch::channel(None).0);
+
+        tokio::spawn(encode_frames(frame_rx, encoded.clone(), format));
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_connection(stream, encoded.subscribe()));
+                    }
+                    Err(e) => log::warn!("Viewer server failed to accept connection: {e}"),
+                }
+            }
+        });
+
+        log::info!("Serving training viewer on http://{addr}");
+        Ok(Self { frames, addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Publish a new frame. Never waits on clients, if the previous frame wasn't
+    /// sent yet it's replaced.
+    pub fn publish(&self, frame: ViewerFrame) {
+        self.frames.send_replace(Some(frame));
+    }
+}
+
+async fn encode_frames(
+    mut frames: watch::Receiver<Option<ViewerFrame>>,
+    encoded: Arc<watch::Sender<Option<Arc<EncodedFrame>>>>,
+    format: ExportFormat,
+) {
+    while frames.changed().await.is_ok() {
+        let Some(frame) = frames.borrow_and_update().clone() else {
+            continue;
+        };
+
+        // Skip the read back & serialization when nobody is watching.
+        if encoded.receiver_count() == 0 {
+            continue;
+        }
+
+        let loss = match frame.stats.loss.into_scalar_async::<f32>().await {
+            Ok(loss) => loss,
+            Err(e) => {
+                log::warn!("Viewer server failed to read loss: {e:?}");
+                continue;
+            }
+        };
+        let stats = StatsMessage {
+            kind: "stats",
+            iter: frame.iter,
+            loss,
+            num_splats: frame.splats.num_splats(),
+            num_visible: frame.stats.num_visible,
+            format: format.extension(),
+        };
+        let appearance = AppearanceMetadata::default();
+        let export = brush_serde::splat_export(frame.splats, frame.up_axis, &appearance, format);
+        let splats = match export.await {
+            Ok(splats) => splats,
+            Err(e) => {
+                log::warn!("Viewer server failed to serialize splats: {e}");
+                continue;
+            }
+        };
+        let stats = serde_json::to_string(&stats).expect("Stats serialize to JSON");
+        encoded.send_replace(Some(Arc::new(EncodedFrame { stats, splats })));
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    mut frames: watch::Receiver<Option<Arc<EncodedFrame>>>,
+) {
+    // Peek at the request to tell WebSocket upgrades from plain page loads.
+    let mut buf = [0; 4096];
+    let Ok(n) = stream.peek(&mut buf).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+
+    if !request.contains("upgrade: websocket") {
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{INDEX_HTML}",
+            INDEX_HTML.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        return;
+    }
+
+    let mut ws = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            log::warn!("Viewer server handshake failed: {e}");
+            return;
+        }
+    };
+
+    // Send the latest frame right away, then every new one.
+    frames.mark_changed();
+    while frames.changed().await.is_ok() {
+        let Some(frame) = frames.borrow_and_update().clone() else {
+            continue;
+        };
+        let sent = async {
+            ws.send(Message::text(frame.stats.clone())).await?;
+            ws.send(Message::binary(frame.splats.clone())).await
+        };
+        if sent.await.is_err() {
+            // Client went away.
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{ProcessMessage, TrainMessage};
+    use crate::{DataSource, burn_init_setup, create_process};
+    use futures_util::StreamExt;
+    use std::cell::Cell;
+    use std::path::Path;
+    use tokio::time::{Duration, sleep};
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// A tiny nerfstudio dataset of a few colored views.
+    fn write_dataset(dir: &Path, views: usize) {
+        let frames: Vec<_> = (0..views)
+            .map(|i| {
+                serde_json::json!({
+                    "file_path": format!("./train/r_{i}"),
+                    "transform_matrix": [
+                        [1.0, 0.0, 0.0, i as f32 * 0.1],
+                        [0.0, 1.0, 0.0, 0.0],
+                        [0.0, 0.0, 1.0, 4.0],
+                        [0.0, 0.0, 0.0, 1.0],
+                    ],
+                })
+            })
+            .collect();
+        let transforms = serde_json::json!({ "camera_angle_x": 0.7, "frames": frames });
+        std::fs::create_dir_all(dir.join("train")).unwrap();
+        std::fs::write(
+            dir.join("transforms_train.json"),
+            serde_json::to_vec(&transforms).unwrap(),
+        )
+        .unwrap();
+        for i in 0..views {
+            let img = image::RgbImage::from_fn(32, 32, |x, y| {
+                image::Rgb([(x * 8) as u8, (y * 8) as u8, (i * 60) as u8])
+            });
+            img.save(dir.join(format!("train/r_{i}.png"))).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_viewer_server_sends_stats() {
+        burn_init_setup().await;
+
+        let dir = std::env::temp_dir().join("brush_viewer_server_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        write_dataset(&dir, 3);
+
+        // Grab a free port up front, the server only logs the one it picked.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let total_iters = 500;
+        let source = DataSource::Path(dir.to_string_lossy().into_owned());
+        let process = create_process(source, move |mut config| async move {
+            config.train_config.total_train_iters = total_iters;
+            config.process_config.viewer_port = Some(port);
+            config.process_config.viewer_every = 10;
+            Some(config)
+        });
+        let cancel = process.cancel.clone();
+        let mut stream = process.stream;
+
+        let received = Cell::new(false);
+        let training = async {
+            let mut received_while_training = false;
+            while let Some(message) = stream.next().await {
+                if let ProcessMessage::TrainMessage(TrainMessage::DoneTraining) = message.unwrap() {
+                    received_while_training = received.get();
+                }
+            }
+            received_while_training
+        };
+        let client = async {
+            // The server starts once the dataset is loaded.
+            let url = format!("ws://127.0.0.1:{port}/");
+            let mut client = loop {
+                match tokio_tungstenite::connect_async(&url).await {
+                    Ok((client, _)) => break client,
+                    Err(_) => sleep(Duration::from_millis(50)).await,
+                }
+            };
+            let Some(Ok(Message::Text(text))) = client.next().await else {
+                panic!("Expected a stats message");
+            };
+            let Some(Ok(Message::Binary(data))) = client.next().await else {
+                panic!("Expected a splat message");
+            };
+            // Got what we came for, no need to train any further.
+            received.set(true);
+            cancel.cancel();
+            (
+                serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+                data,
+            )
+        };
+        let (received_while_training, (json, data)) = tokio::join!(training, client);
+
+        assert!(
+            received_while_training,
+            "The frame should arrive while still training"
+        );
+        assert_eq!(json["type"], "stats");
+        let iter = json["iter"].as_u64().unwrap();
+        assert!(iter > 0 && iter < total_iters as u64, "iter {iter}");
+        assert!(json["num_splats"].as_u64().unwrap() > 0);
+        assert!(json["loss"].as_f64().unwrap().is_finite());
+        assert!(data.starts_with(b"ply"));
+    }
+}
@@ -179,6 +179,8 @@ pub struct SplatOutputDiff {
     /// Rendered image, on the autodiff graph (this is what the loss backprops through).
     pub img: Tensor<3>,
     pub num_visible: u32,
+    /// Number of splat-tile intersections the render allocated buffers for.
+    pub num_intersections: u32,
     /// Per-splat visibility aux — on the **inner** backend (no gradients).
     pub visible: Tensor<1>,
     /// Per-splat max screen radius aux — on the **inner** backend (no gradients).
@@ -307,6 +309,7 @@ pub async fn render_splats_with_pass(
     output.clone().validate().await;
 
     let num_visible = output.aux.num_visible;
+    let num_intersections = output.aux.num_intersections;
     let visible_inner = output.aux.visible.clone();
     let max_radius_inner = output.aux.max_radius.clone();
 
@@ -335,6 +338,7 @@ pub async fn render_splats_with_pass(
     SplatOutputDiff {
         img: wrap_ad_wgpu_float(img_ad),
         num_visible,
+        num_intersections,
         // `visible` / `max_radius` are render aux — they only feed refine
         // bookkeeping and never get a backward. Hand them back on the inner
         // backend directly so callers don't have to strip autodiff off them.
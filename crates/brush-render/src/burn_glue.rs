@@ -300,10 +300,11 @@ impl SplatOps for Fusion<MainBackendBase> {
             }
         }
 
+        // The picking pass outputs u32 ids instead of a float image.
         let out_img_ir = TensorIr::uninit(
             client.create_empty_handle(),
             out.out_img.shape(),
-            DType::F32,
+            out.out_img.dtype(),
         );
         let visible_ir = TensorIr::uninit(
             client.create_empty_handle(),
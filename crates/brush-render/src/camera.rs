@@ -1,5 +1,5 @@
 use crate::kernels::camera_model::CameraModel::{
-    KannalaBrandt4, Pinhole, RadialTangential8, ThinPrismFisheye,
+    KannalaBrandt4, Orthographic, Pinhole, RadialTangential8, ThinPrismFisheye,
 };
 use crate::kernels::camera_model::kannala_brandt_4::KannalaBrandt4Params;
 use crate::kernels::camera_model::pinhole::PinholeParams;
@@ -37,6 +37,24 @@ impl Camera {
         }
     }
 
+    /// An orthographic camera looking along its local +z axis, seeing a `width` x `height`
+    /// region in world units. The extent is stored in the fov fields.
+    pub fn orthographic(
+        position: glam::Vec3,
+        rotation: glam::Quat,
+        width: f64,
+        height: f64,
+    ) -> Self {
+        Self::new(
+            position,
+            rotation,
+            width,
+            height,
+            glam::vec2(0.5, 0.5),
+            Orthographic,
+        )
+    }
+
     /// Check if the camera has valid (non-nan/inf) settings.
     pub fn is_valid(&self) -> bool {
         self.fov_x.is_finite()
@@ -95,6 +113,8 @@ pub fn fov_to_focal(fov: f64, pixels: u32, model: &CameraModel) -> f64 {
             r * rt8_radial(r, p)
         }
         ThinPrismFisheye(p) => kb4_d(half_fov, &p.kb4),
+        // The "fov" is the view extent in world units, so the focal length is in pixels per unit.
+        Orthographic => half_fov,
     };
 
     r_pix / projected
@@ -113,6 +133,7 @@ pub fn focal_to_fov(focal: f64, pixels: u32, model: &CameraModel) -> f64 {
             r_undist.atan()
         }
         ThinPrismFisheye(p) => kb4_invert_d(r_norm, &p.kb4),
+        Orthographic => r_norm,
     };
 
     2.0 * half_fov
@@ -242,7 +263,8 @@ pub fn calculate_jacobian_clamp_limits(
         // Fisheye models project the full hemisphere without the perspective
         // singularity, so their Jacobians aren't clamped (their kernels ignore
         // these limits); leave them at zero.
-        KannalaBrandt4(_) | ThinPrismFisheye(_) => {}
+        // An orthographic Jacobian is constant, so there's nothing to clamp either.
+        KannalaBrandt4(_) | ThinPrismFisheye(_) | Orthographic => {}
     }
 
     JacobianClampLimits {
@@ -2,16 +2,18 @@ use burn::{
     Tensor,
     backend::Dispatch,
     module::{Module, Param, ParamId},
-    tensor::{Device, Gradients, TensorData, activation::sigmoid, s},
+    tensor::{Bool, Device, Gradients, Int, TensorData, activation::sigmoid, s},
 };
 use clap::ValueEnum;
-use glam::Vec3;
+use glam::{Affine3A, Mat4, Vec3};
 use tracing::trace_span;
 
 use crate::{
     RenderAux, SplatOps,
+    bounding_box::BoundingBox,
+    burn_glue::{detach_autodiff, resolve_to_cube_float, unwrap_wgpu_float, wrap_wgpu_int},
     camera::Camera,
-    sh::{sh_coeffs_for_degree, sh_degree_from_coeffs},
+    sh::{sh_coeffs_for_degree, sh_degree_from_coeffs, sh_rotation_matrix},
 };
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum, serde::Serialize, serde::Deserialize)]
@@ -36,15 +38,33 @@ pub enum RasterPass {
     /// makes the analytical backward agree with finite-diff at the cutoff,
     /// at the cost of a sub-1/255 forward shift on edge pixels.
     BackwardSmoothCutoff,
+    /// Forward only, outputs f32 `[H, W, 2]` of expected depth & alpha
+    /// instead of color. No backward bookkeeping.
+    Depth,
+    /// Forward only, outputs u32 `[H, W, 1]` of the global id of the splat
+    /// contributing most to each pixel, `u32::MAX` where nothing was hit.
+    Pick,
+    /// Forward only, outputs u32 `[H, W, 1]` of the number of splats blended
+    /// into each pixel. Used to visualize overdraw.
+    Overdraw,
 }
 
 impl RasterPass {
     pub const fn bwd_info(self) -> bool {
-        !matches!(self, Self::Forward)
+        matches!(self, Self::Backward | Self::BackwardSmoothCutoff)
+    }
+    pub const fn depth(self) -> bool {
+        matches!(self, Self::Depth)
     }
     pub const fn smooth_cutoff(self) -> bool {
         matches!(self, Self::BackwardSmoothCutoff)
     }
+    pub const fn pick(self) -> bool {
+        matches!(self, Self::Pick)
+    }
+    pub const fn overdraw(self) -> bool {
+        matches!(self, Self::Overdraw)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -54,6 +74,24 @@ pub enum TextureMode {
     Float,
 }
 
+/// Which splats to keep when cleaning up a scene, see [`Splats::filter`].
+/// Every criterion is optional, a default filter keeps everything.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SplatFilter {
+    /// Only keep splats with their mean inside this box.
+    pub crop: Option<BoundingBox>,
+    /// Only keep splats with at least this opacity.
+    pub min_opacity: Option<f32>,
+    /// Only keep splats with their largest world-space scale at most this.
+    pub max_scale: Option<f32>,
+}
+
+impl SplatFilter {
+    pub fn is_active(&self) -> bool {
+        self.crop.is_some() || self.min_opacity.is_some() || self.max_scale.is_some()
+    }
+}
+
 /// Gaussian splat parameters.
 ///
 /// `transforms` stores means(3) + rotations(4) + log scales(3) = 10 floats per splat
@@ -142,6 +180,17 @@ impl Splats {
         )
     }
 
+    /// Drop the SH bands above `max_degree`, to render with fewer bands on slow devices.
+    /// The render kernels evaluate as many bands as there are coefficients, so this skips
+    /// the higher bands entirely. Splats of a lower degree are returned as is.
+    pub fn clamp_sh_degree(self, max_degree: u32) -> Self {
+        if self.sh_degree() <= max_degree {
+            self
+        } else {
+            self.with_sh_degree(max_degree)
+        }
+    }
+
     /// Set the SH degree of this splat to be equal to `sh_degree`
     pub fn with_sh_degree(mut self, sh_degree: u32) -> Self {
         let n_coeffs = sh_coeffs_for_degree(sh_degree) as usize;
@@ -258,6 +307,110 @@ impl Splats {
         self.transforms.dims()[0] as u32
     }
 
+    /// These splats with `transform` applied, eg. to bake the viewer's model
+    /// transform into an export. The means get the full transform, while the
+    /// rotations, scales and view dependent colors are rotated and scaled.
+    /// A splat can't be scaled non-uniformly along an arbitrary axis, so a
+    /// non-uniform scale is approximated by its geometric mean.
+    pub fn transformed(&self, transform: Affine3A) -> Self {
+        let device = self.device();
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let uniform_scale = (scale.x * scale.y * scale.z).abs().cbrt();
+
+        // Row vector math, so multiply by the transposed matrices. Glam
+        // matrices are column major, so their data read as row major is
+        // already transposed.
+        let matrix_t =
+            Tensor::<1>::from_floats(transform.matrix3.to_cols_array(), &device).reshape([3, 3]);
+        let translation = Tensor::<1>::from_floats(translation.to_array(), &device).reshape([1, 3]);
+        let means = self.means().matmul(matrix_t) + translation;
+
+        // Left multiply the (w, x, y, z) quaternions by `rotation`.
+        let [rx, ry, rz, rw] = rotation.to_array();
+        let quat_mul = Mat4::from_cols_array_2d(&[
+            [rw, -rx, -ry, -rz],
+            [rx, rw, -rz, ry],
+            [ry, rz, rw, -rx],
+            [rz, -ry, rx, rw],
+        ])
+        .transpose();
+        let quat_mul_t =
+            Tensor::<1>::from_floats(quat_mul.to_cols_array(), &device).reshape([4, 4]);
+        let rotations = self.rotations().matmul(quat_mul_t);
+
+        let log_scales = self.log_scales() + uniform_scale.ln();
+        let transforms = Tensor::cat(vec![means, rotations, log_scales], 1);
+
+        let [n, n_coeffs, _] = self.sh_coeffs.dims();
+        let sh_rotation = Tensor::<1>::from_floats(
+            sh_rotation_matrix(self.sh_degree(), rotation).as_slice(),
+            &device,
+        )
+        .reshape([n_coeffs, n_coeffs]);
+        // Rotate the coefficients of every color channel, as rows of `[n * 3, n_coeffs]`.
+        let sh_coeffs = self
+            .sh_coeffs
+            .val()
+            .swap_dims(1, 2)
+            .reshape([n * 3, n_coeffs])
+            .matmul(sh_rotation.transpose())
+            .reshape([n, 3, n_coeffs])
+            .swap_dims(1, 2);
+
+        Self {
+            transforms: Param::initialized(self.transforms.id, transforms.detach().require_grad()),
+            sh_coeffs: Param::initialized(self.sh_coeffs.id, sh_coeffs.detach().require_grad()),
+            raw_opacities: self.raw_opacities.clone(),
+            render_mip: self.render_mip,
+            min_scale: self.min_scale.clone().map(|f| f * uniform_scale),
+        }
+    }
+
+    /// Only keep the splats passing `filter`, eg. to remove floaters outside a
+    /// region of interest.
+    pub async fn filter(&self, filter: &SplatFilter) -> Self {
+        if !filter.is_active() {
+            return self.clone();
+        }
+
+        let device = self.device();
+        let mut masks: Vec<Tensor<1, Bool>> = vec![];
+        if let Some(crop) = filter.crop {
+            let min = Tensor::<1>::from_floats(crop.min().to_array(), &device).reshape([1, 3]);
+            let max = Tensor::<1>::from_floats(crop.max().to_array(), &device).reshape([1, 3]);
+            let means = self.means();
+            let outside = (means.clone() - min)
+                .lower_elem(0.0)
+                .bool_or((means - max).greater_elem(0.0))
+                .any_dim(1)
+                .squeeze_dim(1);
+            masks.push(outside);
+        }
+        if let Some(min_opacity) = filter.min_opacity {
+            masks.push(self.opacities().lower_elem(min_opacity));
+        }
+        if let Some(max_scale) = filter.max_scale {
+            masks.push(
+                self.scales()
+                    .greater_elem(max_scale)
+                    .any_dim(1)
+                    .squeeze_dim(1),
+            );
+        }
+        let reject = masks
+            .into_iter()
+            .reduce(Tensor::bool_or)
+            .expect("Active filter has at least one criterion");
+
+        let keep = reject.bool_not().argwhere_async().await.squeeze_dim::<1>(1);
+        let mut splats = self.clone();
+        splats.transforms = splats.transforms.map(|t| t.select(0, keep.clone()));
+        splats.sh_coeffs = splats.sh_coeffs.map(|c| c.select(0, keep.clone()));
+        splats.raw_opacities = splats.raw_opacities.map(|o| o.select(0, keep.clone()));
+        splats.min_scale = splats.min_scale.map(|f| f.select(0, keep));
+        splats
+    }
+
     pub fn sh_degree(&self) -> u32 {
         let [_, n_coeffs, _] = self.sh_coeffs.dims();
         sh_degree_from_coeffs(n_coeffs as u32)
@@ -359,6 +512,277 @@ impl Splats {
 
         grads
     }
+
+    /// Render the expected depth of the splats on a non-differentiable device.
+    ///
+    /// Returns an `[H, W, 2]` tensor of (depth, alpha). Depth is the alpha
+    /// weighted view-space depth, normalized by alpha, and 0 where nothing was
+    /// hit.
+    pub async fn render_depth(&self, camera: &Camera, img_size: glam::UVec2) -> Tensor<3> {
+        let (transforms, raw_opacities) = match &self.min_scale {
+            Some(f) => fold_min_scale(self.transforms.val(), self.raw_opacities.val(), f.clone()),
+            None => (self.transforms.val(), self.raw_opacities.val()),
+        };
+        let render_mode = if self.render_mip {
+            SplatRenderMode::Mip
+        } else {
+            SplatRenderMode::Default
+        };
+        let output = <Dispatch as SplatOps>::render(
+            camera,
+            img_size,
+            transforms.into_dispatch(),
+            self.sh_coeffs.val().into_dispatch(),
+            raw_opacities.into_dispatch(),
+            render_mode,
+            Vec3::ZERO,
+            RasterPass::Depth,
+        )
+        .await;
+        output.validate_counts();
+        Tensor::from_dispatch(output.out_img)
+    }
+
+    /// Render the expected depth `sum(alpha_i * T_i * depth_i)` of the splats as
+    /// an `[H, W]` tensor. Unlike [`Self::render_depth`] this isn't normalized
+    /// by alpha, so partially covered pixels are pulled towards zero.
+    pub async fn render_expected_depth(&self, camera: &Camera, img_size: glam::UVec2) -> Tensor<2> {
+        let output = self.render_depth(camera, img_size).await;
+        let [h, w, _] = output.dims();
+        let depth = output.clone().slice(s![.., .., 0..1]);
+        let alpha = output.slice(s![.., .., 1..2]);
+        (depth * alpha).reshape([h, w])
+    }
+
+    /// Render the id of the splat with the highest contribution to each pixel
+    /// as an `[H, W]` u32 tensor, `u32::MAX` where no splat was hit.
+    pub async fn render_pick(&self, camera: &Camera, img_size: glam::UVec2) -> Tensor<2, Int> {
+        self.render_u32(camera, img_size, RasterPass::Pick).await
+    }
+
+    /// Render the number of splats blended into each pixel, `[H, W]`. Shows
+    /// where the rasterizer spends its time, e.g. as an overdraw heatmap.
+    pub async fn render_overdraw(&self, camera: &Camera, img_size: glam::UVec2) -> Tensor<2, Int> {
+        self.render_u32(camera, img_size, RasterPass::Overdraw)
+            .await
+    }
+
+    /// Number of tile intersections a render of `img_size` from `camera` would
+    /// sort. Only runs the project & cull pass, so this is cheap to check
+    /// before a render that might not fit in memory.
+    pub async fn count_intersections(&self, camera: &Camera, img_size: glam::UVec2) -> u32 {
+        let (transforms, raw_opacities) = match &self.min_scale {
+            Some(f) => fold_min_scale(self.transforms.val(), self.raw_opacities.val(), f.clone()),
+            None => (self.transforms.val(), self.raw_opacities.val()),
+        };
+        let render_mode = if self.render_mip {
+            SplatRenderMode::Mip
+        } else {
+            SplatRenderMode::Default
+        };
+        let (_, num_intersections) = crate::render::count_intersections(
+            camera,
+            img_size,
+            resolve_to_cube_float(detach_autodiff(transforms)),
+            resolve_to_cube_float(detach_autodiff(raw_opacities)),
+            render_mode,
+        )
+        .await;
+        num_intersections
+    }
+
+    async fn render_u32(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        pass: RasterPass,
+    ) -> Tensor<2, Int> {
+        let (transforms, raw_opacities) = match &self.min_scale {
+            Some(f) => fold_min_scale(self.transforms.val(), self.raw_opacities.val(), f.clone()),
+            None => (self.transforms.val(), self.raw_opacities.val()),
+        };
+        let render_mode = if self.render_mip {
+            SplatRenderMode::Mip
+        } else {
+            SplatRenderMode::Default
+        };
+        let output = <Dispatch as SplatOps>::render(
+            camera,
+            img_size,
+            transforms.into_dispatch(),
+            self.sh_coeffs.val().into_dispatch(),
+            raw_opacities.into_dispatch(),
+            render_mode,
+            Vec3::ZERO,
+            pass,
+        )
+        .await;
+        output.validate_counts();
+        // The output is a u32 tensor, but goes through the float slot of the render output.
+        let ids: Tensor<3> = Tensor::from_dispatch(output.out_img);
+        wrap_wgpu_int(unwrap_wgpu_float(ids)).reshape([img_size.y as usize, img_size.x as usize])
+    }
+
+    /// Id of the splat with the highest contribution to `pixel`, if any.
+    pub async fn pick(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        pixel: glam::UVec2,
+    ) -> Option<u32> {
+        if pixel.x >= img_size.x || pixel.y >= img_size.y {
+            return None;
+        }
+        let (x, y) = (pixel.x as usize, pixel.y as usize);
+        let id = self
+            .render_pick(camera, img_size)
+            .await
+            .slice(s![y..y + 1, x..x + 1])
+            .into_data_async()
+            .await
+            .ok()?
+            .into_vec::<u32>()
+            .ok()?[0];
+        (id != u32::MAX).then_some(id)
+    }
+}
+
+/// Splats with their render inputs resolved: the min-scale floor folded in,
+/// the splat scale applied and the values validated. Rendering many views of
+/// the same splats through this skips redoing that work (and the validation
+/// readbacks) for every camera, see [`Splats::render_batch`].
+#[derive(Clone, Debug)]
+pub struct PreparedSplats {
+    transforms: Tensor<2>,
+    sh_coeffs: Tensor<3>,
+    raw_opacities: Tensor<1>,
+    render_mode: SplatRenderMode,
+    num_splats: u32,
+}
+
+impl PreparedSplats {
+    pub async fn new(splats: Splats, splat_scale: Option<f32>) -> Self {
+        splats.clone().validate_values().await;
+
+        let num_splats = splats.num_splats();
+        let sh_coeffs = splats.sh_coeffs.into_value();
+
+        // Fold the 3D-filter floor into scales/opacity first (the floor is part of
+        // the splat's definition, so eval/viewer render with it just like training).
+        let (transforms, raw_opacities) = match &splats.min_scale {
+            Some(f) => fold_min_scale(
+                splats.transforms.val(),
+                splats.raw_opacities.val(),
+                f.clone(),
+            ),
+            None => (splats.transforms.val(), splats.raw_opacities.val()),
+        };
+
+        let transforms = if let Some(scale) = splat_scale {
+            let adjusted = transforms.clone().slice(s![.., 7..10]) + scale.ln();
+            transforms.slice_assign(s![.., 7..10], adjusted)
+        } else {
+            transforms
+        };
+
+        let render_mode = if splats.render_mip {
+            SplatRenderMode::Mip
+        } else {
+            SplatRenderMode::Default
+        };
+
+        Self {
+            transforms,
+            sh_coeffs,
+            raw_opacities,
+            render_mode,
+            num_splats,
+        }
+    }
+
+    pub fn num_splats(&self) -> u32 {
+        self.num_splats
+    }
+
+    /// Render the prepared splats from `camera`, see [`render_splats`].
+    pub async fn render(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        background: Vec3,
+        texture_mode: TextureMode,
+    ) -> (Tensor<3>, RenderAux) {
+        let use_float = matches!(texture_mode, TextureMode::Float);
+
+        // Float mode needs `Backward` (f32 image + per-splat bookkeeping); Packed
+        // mode goes through the packed u8 path. Neither inference path uses the
+        // smooth cutoff — that's reserved for the gradient-check tests.
+        let pass = if use_float {
+            RasterPass::Backward
+        } else {
+            RasterPass::Forward
+        };
+        // Route through the `#[backend_extension]`-generated `Dispatch` impl: it
+        // unwraps these dispatch primitives to the Wgpu backend, runs the render,
+        // and re-wraps the `RenderOutput` via its `ExtensionType` derive.
+        let output = <Dispatch as SplatOps>::render(
+            camera,
+            img_size,
+            self.transforms.clone().into_dispatch(),
+            self.sh_coeffs.clone().into_dispatch(),
+            self.raw_opacities.clone().into_dispatch(),
+            self.render_mode,
+            background,
+            pass,
+        )
+        .await;
+
+        output.clone().validate().await;
+
+        let img_size = output.aux.img_size;
+        let num_visible = output.aux.num_visible;
+        let num_intersections = output.aux.num_intersections;
+
+        let aux = RenderAux {
+            num_visible,
+            num_intersections,
+            visible: Tensor::from_dispatch(output.aux.visible),
+            max_radius: Tensor::from_dispatch(output.aux.max_radius),
+            tile_offsets: Tensor::from_dispatch(output.aux.tile_offsets),
+            img_size,
+        };
+
+        (Tensor::from_dispatch(output.out_img), aux)
+    }
+}
+
+impl Splats {
+    /// Render the splats from several cameras at once. The splats are only
+    /// prepared once (see [`PreparedSplats`]) and each camera then just runs
+    /// the projection, sort & rasterization.
+    ///
+    /// Projection, sorting and rasterization still run per camera, so on a
+    /// 50 view eval the saving is the 49 redundant min-scale folds. With
+    /// `debug-validation` enabled it also skips the value readbacks, which
+    /// otherwise stall the GPU on every view and dominate the eval time.
+    pub async fn render_batch(
+        &self,
+        cameras: &[Camera],
+        img_size: glam::UVec2,
+        background: Vec3,
+        texture_mode: TextureMode,
+    ) -> Vec<(Tensor<3>, RenderAux)> {
+        let prepared = PreparedSplats::new(self.clone(), None).await;
+        let mut out = Vec::with_capacity(cameras.len());
+        for camera in cameras {
+            out.push(
+                prepared
+                    .render(camera, img_size, background, texture_mode)
+                    .await,
+            );
+        }
+        out
+    }
 }
 
 /// Render splats on a non-differentiable device.
@@ -370,73 +794,48 @@ pub async fn render_splats(
     splat_scale: Option<f32>,
     texture_mode: TextureMode,
 ) -> (Tensor<3>, RenderAux) {
-    splats.clone().validate_values().await;
-
-    let sh_coeffs = splats.sh_coeffs.into_value();
-
-    // Fold the 3D-filter floor into scales/opacity first (the floor is part of
-    // the splat's definition, so eval/viewer render with it just like training).
-    let (transforms, raw_opacities) = match &splats.min_scale {
-        Some(f) => fold_min_scale(
-            splats.transforms.val(),
-            splats.raw_opacities.val(),
-            f.clone(),
-        ),
-        None => (splats.transforms.val(), splats.raw_opacities.val()),
-    };
-
-    let transforms = if let Some(scale) = splat_scale {
-        let adjusted = transforms.clone().slice(s![.., 7..10]) + scale.ln();
-        transforms.slice_assign(s![.., 7..10], adjusted)
-    } else {
-        transforms
-    };
-
-    let render_mode = if splats.render_mip {
-        SplatRenderMode::Mip
-    } else {
-        SplatRenderMode::Default
-    };
-
-    let use_float = matches!(texture_mode, TextureMode::Float);
+    PreparedSplats::new(splats, splat_scale)
+        .await
+        .render(camera, img_size, background, texture_mode)
+        .await
+}
 
-    // Float mode needs `Backward` (f32 image + per-splat bookkeeping); Packed
-    // mode goes through the packed u8 path. Neither inference path uses the
-    // smooth cutoff — that's reserved for the gradient-check tests.
-    let pass = if use_float {
-        RasterPass::Backward
-    } else {
-        RasterPass::Forward
-    };
-    // Route through the `#[backend_extension]`-generated `Dispatch` impl: it
-    // unwraps these dispatch primitives to the Wgpu backend, runs the render,
-    // and re-wraps the `RenderOutput` via its `ExtensionType` derive.
-    let output = <Dispatch as SplatOps>::render(
+/// Render splats at `factor` times `img_size` and box-downsample the result
+/// back to `img_size`. This anti-aliases thin and sub-pixel splats, at the cost
+/// of `factor²` more pixels to rasterize.
+///
+/// The downsample works on f32 colors, so this always returns a
+/// [`TextureMode::Float`] image.
+pub async fn render_splats_supersampled(
+    splats: Splats,
+    camera: &Camera,
+    img_size: glam::UVec2,
+    background: Vec3,
+    splat_scale: Option<f32>,
+    factor: u32,
+) -> Tensor<3> {
+    let factor = factor.max(1);
+    let (img, _) = render_splats(
+        splats,
         camera,
-        img_size,
-        transforms.into_dispatch(),
-        sh_coeffs.into_dispatch(),
-        raw_opacities.into_dispatch(),
-        render_mode,
+        img_size * factor,
         background,
-        pass,
+        splat_scale,
+        TextureMode::Float,
     )
     .await;
+    box_downsample(img, factor as usize)
+}
 
-    output.clone().validate().await;
-
-    let img_size = output.aux.img_size;
-    let num_visible = output.aux.num_visible;
-    let num_intersections = output.aux.num_intersections;
-
-    let aux = RenderAux {
-        num_visible,
-        num_intersections,
-        visible: Tensor::from_dispatch(output.aux.visible),
-        max_radius: Tensor::from_dispatch(output.aux.max_radius),
-        tile_offsets: Tensor::from_dispatch(output.aux.tile_offsets),
-        img_size,
-    };
-
-    (Tensor::from_dispatch(output.out_img), aux)
+/// Average each `factor x factor` block of an `[H * factor, W * factor, C]` image.
+pub fn box_downsample(img: Tensor<3>, factor: usize) -> Tensor<3> {
+    if factor <= 1 {
+        return img;
+    }
+    let [h, w, c] = img.dims();
+    let (h, w) = (h / factor, w / factor);
+    img.reshape([h, factor, w, factor, c])
+        .mean_dim(3)
+        .mean_dim(1)
+        .reshape([h, w, c])
 }
@@ -1,4 +1,5 @@
 pub mod kannala_brandt_4;
+pub mod orthographic;
 pub mod pinhole;
 pub mod radial_tangential_8;
 pub mod thin_prism_fisheye;
@@ -7,11 +8,15 @@ use burn_cubecl::cubecl;
 use burn_cubecl::cubecl::prelude::*;
 
 use crate::kernels::camera_model::CameraModel::{
-    KannalaBrandt4, Pinhole, RadialTangential8, ThinPrismFisheye,
+    KannalaBrandt4, Orthographic, Pinhole, RadialTangential8, ThinPrismFisheye,
 };
 use crate::kernels::camera_model::kannala_brandt_4::{
     KannalaBrandt4Params, calculate_project_jacobian_kb4, calculate_projection_vjp_kb4, project_kb4,
 };
+use crate::kernels::camera_model::orthographic::{
+    calculate_project_jacobian_orthographic, calculate_projection_vjp_orthographic,
+    project_orthographic,
+};
 use crate::kernels::camera_model::pinhole::{
     PinholeParams, calculate_project_jacobian_pinhole, calculate_projection_vjp_pinhole,
     project_pinhole,
@@ -34,6 +39,8 @@ pub enum CameraModel {
     KannalaBrandt4(KannalaBrandt4Params),
     RadialTangential8(RadialTangential8Params),
     ThinPrismFisheye(ThinPrismFisheyeParams),
+    /// Parallel projection, eg. for top-down renders. See [`crate::camera::Camera::orthographic`].
+    Orthographic,
 }
 
 #[derive(CubeLaunch, CubeType, Debug, Clone, Copy)]
@@ -56,6 +63,7 @@ pub fn project(
         KannalaBrandt4(params) => project_kb4(point, pinhole_params, params),
         RadialTangential8(params) => project_rt8(point, pinhole_params, params),
         ThinPrismFisheye(params) => project_tpf(point, pinhole_params, params),
+        Orthographic => project_orthographic(point, pinhole_params),
     }
 }
 
@@ -74,6 +82,7 @@ pub fn calculate_project_jacobian(
             calculate_project_jacobian_rt8(point, jacobian_clamp_limits, pinhole_params, params)
         }
         ThinPrismFisheye(params) => calculate_project_jacobian_tpf(point, pinhole_params, params),
+        Orthographic => calculate_project_jacobian_orthographic(pinhole_params),
     }
 }
 
@@ -121,6 +130,7 @@ pub fn calculate_projection_vjp(
             v_mean2d,
             params,
         ),
+        Orthographic => calculate_projection_vjp_orthographic(u.pinhole_params, v_mean2d),
     }
 }
 
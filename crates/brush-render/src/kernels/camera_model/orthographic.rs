@@ -0,0 +1,28 @@
+use crate::kernels::camera_model::pinhole::PinholeParams;
+use brush_cube::{Mat2x3, Vec2, Vec3A};
+use burn_cubecl::cubecl;
+use burn_cubecl::cubecl::prelude::*;
+
+// Parallel rays along +z: the focal length is in pixels per world unit and
+// depth doesn't affect the projected position or size.
+#[cube]
+pub fn project_orthographic(point: Vec3A, params: PinholeParams) -> (f32, f32) {
+    let u = params.fx * point.x() + params.cx;
+    let v = params.fy * point.y() + params.cy;
+    (u, v)
+}
+
+#[cube]
+pub fn calculate_project_jacobian_orthographic(params: PinholeParams) -> Mat2x3 {
+    Mat2x3 {
+        c0: Vec2::new(params.fx, 0.0),
+        c1: Vec2::new(0.0, params.fy),
+        c2: Vec2::new(0.0, 0.0),
+    }
+}
+
+// The Jacobian is constant, so the cov2d gradient doesn't flow into the mean.
+#[cube]
+pub fn calculate_projection_vjp_orthographic(params: PinholeParams, v_mean2d: Vec2) -> Vec3A {
+    Vec3A::new(params.fx * v_mean2d.x(), params.fy * v_mean2d.y(), 0.0)
+}
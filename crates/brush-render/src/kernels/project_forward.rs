@@ -37,6 +37,85 @@ pub fn project_forward_kernel(
         terminate!();
     }
 
+    let (depth, num_tiles_hit, radius) = project_and_cull(
+        transforms,
+        raw_opacities,
+        0usize,
+        global_gid,
+        u,
+        mip_splatting,
+        camera_model,
+    );
+
+    intersect_counts[global_gid as usize] = num_tiles_hit;
+    Atomic::fetch_add(&num_intersections[0], num_tiles_hit);
+    max_radius[global_gid as usize] = radius;
+
+    let write_id = Atomic::fetch_add(&num_visible[0], 1u32);
+    global_from_compact_gid[write_id as usize] = global_gid;
+    depths[write_id as usize] = depth;
+}
+
+/// `project_forward_kernel` for devices that can only bind a few storage
+/// buffers per stage. The same logical buffers are packed into 3 bindings,
+/// each logical buffer starting at a multiple of `stride`:
+///
+/// - `splat_data`: the transforms, followed by the raw opacities at `10 * total_splats`.
+/// - `splat_out`: depths, then max radius.
+/// - `ids_out`: the `[num_visible, num_intersections]` counters, then the
+///   global from compact gids, then the intersect counts. Everything is
+///   written atomically, as the counters share the binding.
+#[cube(launch)]
+pub fn project_forward_packed_kernel(
+    splat_data: &Tensor<f32>,
+    splat_out: &mut Tensor<f32>,
+    ids_out: &mut Tensor<Atomic<u32>>,
+    stride: u32,
+    u: ProjectUniforms,
+    #[comptime] mip_splatting: bool,
+    #[comptime] camera_model: CameraModel,
+) {
+    let global_gid = ABSOLUTE_POS as u32;
+    if global_gid >= u.total_splats {
+        terminate!();
+    }
+
+    let (depth, num_tiles_hit, radius) = project_and_cull(
+        splat_data,
+        splat_data,
+        (u.total_splats * 10u32) as usize,
+        global_gid,
+        u,
+        mip_splatting,
+        camera_model,
+    );
+
+    Atomic::store(
+        &ids_out[(stride * 2u32 + global_gid) as usize],
+        num_tiles_hit,
+    );
+    Atomic::fetch_add(&ids_out[1], num_tiles_hit);
+    splat_out[(stride + global_gid) as usize] = radius;
+
+    let write_id = Atomic::fetch_add(&ids_out[0], 1u32);
+    Atomic::store(&ids_out[(stride + write_id) as usize], global_gid);
+    splat_out[write_id as usize] = depth;
+}
+
+/// Project & cull splat `global_gid`, terminating the unit if it isn't
+/// visible. Returns its view-space depth, the number of tiles it touches, and
+/// its screen-space radius. The raw opacity is read at `opac_base + gid`, so
+/// the packed kernel can store it behind the transforms.
+#[cube]
+fn project_and_cull(
+    transforms: &Tensor<f32>,
+    raw_opacities: &Tensor<f32>,
+    opac_base: usize,
+    global_gid: u32,
+    u: ProjectUniforms,
+    #[comptime] mip_splatting: bool,
+    #[comptime] camera_model: CameraModel,
+) -> (f32, u32, f32) {
     // means(3) + quats(4) + log_scales(3)
     let base = (global_gid * 10u32) as usize;
 
@@ -45,7 +124,7 @@ pub fn project_forward_kernel(
         terminate!();
     }
     match camera_model {
-        CameraModel::Pinhole => {
+        CameraModel::Pinhole | CameraModel::Orthographic => {
             if mean_c.z() < 0.01f32 {
                 terminate!();
             }
@@ -72,7 +151,7 @@ pub fn project_forward_kernel(
         terminate!();
     }
 
-    let raw_opac = raw_opacities[global_gid as usize];
+    let raw_opac = raw_opacities[opac_base + global_gid as usize];
     if !is_finite_f32(raw_opac) {
         terminate!();
     }
@@ -113,13 +192,7 @@ pub fn project_forward_kernel(
     let bb = get_tile_bbox(mean2d_x, mean2d_y, ex, ey, u.tile_bw, u.tile_bh);
     let num_tiles_hit = count_contributing_tiles(bb, mean2d_x, mean2d_y, conic, power_threshold);
 
-    intersect_counts[global_gid as usize] = num_tiles_hit;
-    Atomic::fetch_add(&num_intersections[0], num_tiles_hit);
-
     // Screen-space radius (pixels) for the small-splat prior.
-    max_radius[global_gid as usize] = f32::max(ex / img_w_f, ey / img_h_f);
-
-    let write_id = Atomic::fetch_add(&num_visible[0], 1u32);
-    global_from_compact_gid[write_id as usize] = global_gid;
-    depths[write_id as usize] = mean_c.z();
+    let radius = f32::max(ex / img_w_f, ey / img_h_f);
+    (mean_c.z(), num_tiles_hit, radius)
 }
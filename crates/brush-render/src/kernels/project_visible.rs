@@ -37,7 +37,70 @@ pub fn project_visible_kernel(
     }
 
     let global_gid = global_from_compact_gid[compact_gid as usize];
+    let splat = project_splat(
+        transforms,
+        coeffs,
+        raw_opacities,
+        0usize,
+        global_gid,
+        u,
+        mip_splatting,
+        sh_degree,
+        camera_model,
+    );
+    write_projected_splat(projected, compact_gid, splat);
+}
+
+/// `project_visible_kernel` with the raw opacities stored behind the
+/// transforms in `splat_data`, like `project_forward_packed_kernel`, to save a
+/// storage binding.
+#[allow(clippy::semicolon_if_nothing_returned)]
+#[cube(launch)]
+pub fn project_visible_packed_kernel(
+    splat_data: &Tensor<f32>,
+    coeffs: &Tensor<f32>,
+    global_from_compact_gid: &Tensor<u32>,
+    projected: &mut Tensor<f32>,
+    u: ProjectUniforms,
+    #[comptime] mip_splatting: bool,
+    #[comptime] sh_degree: u32,
+    #[comptime] camera_model: CameraModel,
+) {
+    let compact_gid = ABSOLUTE_POS as u32;
+    if compact_gid >= u.num_visible {
+        terminate!();
+    }
+
+    let global_gid = global_from_compact_gid[compact_gid as usize];
+    let splat = project_splat(
+        splat_data,
+        coeffs,
+        splat_data,
+        (u.total_splats * 10u32) as usize,
+        global_gid,
+        u,
+        mip_splatting,
+        sh_degree,
+        camera_model,
+    );
+    write_projected_splat(projected, compact_gid, splat);
+}
 
+/// Project a visible splat to its screen-space conic & view-dependent color.
+/// The raw opacity is read at `opac_base + global_gid`.
+#[cube]
+#[allow(clippy::too_many_arguments)]
+fn project_splat(
+    transforms: &Tensor<f32>,
+    coeffs: &Tensor<f32>,
+    raw_opacities: &Tensor<f32>,
+    opac_base: usize,
+    global_gid: u32,
+    u: ProjectUniforms,
+    #[comptime] mip_splatting: bool,
+    #[comptime] sh_degree: u32,
+    #[comptime] camera_model: CameraModel,
+) -> Splat {
     // means(3) + quats(4) + log_scales(3)
     let base = (global_gid * 10u32) as usize;
     let mean = Vec3A::new(transforms[base], transforms[base + 1], transforms[base + 2]);
@@ -48,7 +111,7 @@ pub fn project_visible_kernel(
     let mean_c = world_to_cam(mean, u);
     let raw_cov = calc_cov2d(scale, quat, mean_c, u, camera_model);
     let (cov, filter_comp) = compensate_cov2d(raw_cov, mip_splatting);
-    let opac = sigmoid(raw_opacities[global_gid as usize]) * filter_comp;
+    let opac = sigmoid(raw_opacities[opac_base + global_gid as usize]) * filter_comp;
     let conic = cov.inverse();
 
     let (mean2d_x, mean2d_y) = project(mean_c, u.pinhole_params, camera_model);
@@ -70,19 +133,15 @@ pub fn project_visible_kernel(
     let cg_c = clamp(select(is_finite_f32(cg), cg, 0.0f32), -100.0f32, 100.0f32);
     let cb_c = clamp(select(is_finite_f32(cb), cb, 0.0f32), -100.0f32, 100.0f32);
 
-    write_projected_splat(
-        projected,
-        compact_gid,
-        Splat {
-            xy_x: mean2d_x,
-            xy_y: mean2d_y,
-            conic_x: conic.c00,
-            conic_y: conic.c01,
-            conic_z: conic.c11,
-            color_a: opac,
-            color_r: cr_c,
-            color_g: cg_c,
-            color_b: cb_c,
-        },
-    );
+    Splat {
+        xy_x: mean2d_x,
+        xy_y: mean2d_y,
+        conic_x: conic.c00,
+        conic_y: conic.c01,
+        conic_z: conic.c11,
+        color_a: opac,
+        color_r: cr_c,
+        color_g: cg_c,
+        color_b: cb_c,
+    }
 }
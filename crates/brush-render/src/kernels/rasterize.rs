@@ -10,6 +10,21 @@
 //! the last splat any pixel actually consumed" so the backward kernel's
 //! outer loop ends early. When `bwd_info=false` the kernel writes a
 //! packed u8x4 to `out_img` and skips the backward bookkeeping.
+//!
+//! `depth_out` swaps the color accumulation for the (sorted) view-space depth
+//! of each splat, and writes 2 f32s per pixel: the alpha-normalized expected
+//! depth and the final alpha. The background is ignored in this mode.
+//!
+//! `pick_info` skips the color output and instead writes the global gid of
+//! the splat with the highest contribution (`alpha * T`) to each pixel into
+//! `out_img_packed`, or `u32::MAX` where no splat contributed. Used for picking.
+//!
+//! `count_out` likewise skips the color output and writes the number of splats
+//! blended into each pixel to `out_img_packed`. Used for the overdraw debug view.
+//!
+//! `rasterize_packed_kernel` is the forward color path on its own, binding
+//! just the 4 storage buffers it reads & writes for devices that can't bind
+//! all 8 of `rasterize_kernel`.
 
 use burn_cubecl::cubecl;
 use burn_cubecl::cubecl::cube;
@@ -30,10 +45,14 @@ pub fn rasterize_kernel(
     out_img_packed: &mut Tensor<u32>,
     out_img_f32: &mut Tensor<f32>,
     global_from_compact_gid: &Tensor<u32>,
+    depths: &Tensor<f32>,
     visible: &mut Tensor<f32>,
     u: RasterizeUniforms,
     #[comptime] bwd_info: bool,
     #[comptime] smooth_cutoff: bool,
+    #[comptime] depth_out: bool,
+    #[comptime] pick_info: bool,
+    #[comptime] count_out: bool,
 ) {
     let global_id = ABSOLUTE_POS as u32;
     let (pix_x, pix_y) = map_1d_to_2d(global_id, u.tile_bw);
@@ -45,12 +64,17 @@ pub fn rasterize_kernel(
     let tile_id = tile_loc_x + tile_loc_y * u.tile_bw;
     let inside = pix_x < u.img_w && pix_y < u.img_h;
 
-    // Workgroup-shared splat batch + bookkeeping. The bwd-only `load_gid`
-    // gets a comptime-tiny size when `bwd_info=false` so we don't pay
+    // Workgroup-shared splat batch + bookkeeping. `load_gid` is only needed
+    // for bwd & pick, and gets a comptime-tiny size otherwise so we don't pay
     // 1 KiB of static shared mem on the forward-only variant.
     let mut local_batch = Shared::new_slice((TILE_SIZE * PROJECTED_LANES) as usize);
-    let mut load_gid =
-        Shared::new_slice(comptime![if bwd_info { TILE_SIZE } else { 1u32 }] as usize);
+    let mut load_gid = Shared::new_slice(comptime![if bwd_info || pick_info {
+        TILE_SIZE
+    } else {
+        1u32
+    }] as usize);
+    let mut load_depth =
+        Shared::new_slice(comptime![if depth_out { TILE_SIZE } else { 1u32 }] as usize);
     let num_done_atomic = Shared::<[Atomic<u32>]>::new_slice(1usize);
     let max_useful_isect = Shared::<[Atomic<u32>]>::new_slice(1usize);
     let mut range = Shared::new_slice(2usize);
@@ -77,6 +101,9 @@ pub fn rasterize_kernel(
     let mut pix_b = 0.0f32;
     let mut done = !inside;
     let mut last_useful_isect = range_lo;
+    let mut pick_vis = 0.0f32;
+    let mut pick_gid = 0xffffffffu32;
+    let mut count = 0u32;
 
     if done {
         Atomic::fetch_add(&num_done_atomic[0], 1u32);
@@ -103,9 +130,12 @@ pub fn rasterize_kernel(
             for lane in 0..PROJECTED_LANES_USIZE {
                 local_batch[dst_base + lane] = projected[src_base + lane];
             }
-            if comptime![bwd_info] {
+            if comptime![bwd_info || pick_info] {
                 load_gid[local_idx as usize] = global_from_compact_gid[compact_gid as usize];
             }
+            if comptime![depth_out] {
+                load_depth[local_idx as usize] = depths[compact_gid as usize];
+            }
         }
         sync_cube();
 
@@ -142,9 +172,20 @@ pub fn rasterize_kernel(
                         visible[load_gid[t as usize] as usize] = 1.0f32;
                     }
                     let vis = alpha_eff * t_acc;
-                    pix_r += max(local_batch[dst_base + 6], 0.0f32) * vis;
-                    pix_g += max(local_batch[dst_base + 7], 0.0f32) * vis;
-                    pix_b += max(local_batch[dst_base + 8], 0.0f32) * vis;
+                    if comptime![pick_info] {
+                        if vis > pick_vis {
+                            pick_vis = vis;
+                            pick_gid = load_gid[t as usize];
+                        }
+                    } else if comptime![count_out] {
+                        count += 1u32;
+                    } else if comptime![depth_out] {
+                        pix_r += load_depth[t as usize] * vis;
+                    } else {
+                        pix_r += max(local_batch[dst_base + 6], 0.0f32) * vis;
+                        pix_g += max(local_batch[dst_base + 7], 0.0f32) * vis;
+                        pix_b += max(local_batch[dst_base + 8], 0.0f32) * vis;
+                    }
                     t_acc = next_t;
                     last_useful_isect = batch_start + t + 1u32;
                 }
@@ -157,7 +198,22 @@ pub fn rasterize_kernel(
         batch_start += TILE_SIZE;
     }
 
-    if inside {
+    if comptime![depth_out] {
+        if inside {
+            let final_a = 1.0f32 - t_acc;
+            let base = (pix_id * 2u32) as usize;
+            out_img_f32[base] = select(final_a > 0.0f32, pix_r / final_a, 0.0f32);
+            out_img_f32[base + 1] = final_a;
+        }
+    } else if comptime![pick_info] {
+        if inside {
+            out_img_packed[pix_id as usize] = pick_gid;
+        }
+    } else if comptime![count_out] {
+        if inside {
+            out_img_packed[pix_id as usize] = count;
+        }
+    } else if inside {
         let final_r = pix_r + t_acc * u.bg_r;
         let final_g = pix_g + t_acc * u.bg_g;
         let final_b = pix_b + t_acc * u.bg_b;
@@ -169,12 +225,7 @@ pub fn rasterize_kernel(
             out_img_f32[base + 2] = final_b;
             out_img_f32[base + 3] = final_a;
         } else {
-            let r = clamp(final_r * 255.0f32, 0.0f32, 255.0f32) as u32;
-            let g = clamp(final_g * 255.0f32, 0.0f32, 255.0f32) as u32;
-            let b = clamp(final_b * 255.0f32, 0.0f32, 255.0f32) as u32;
-            let a = clamp(final_a * 255.0f32, 0.0f32, 255.0f32) as u32;
-            let packed = r | (g << 8u32) | (b << 16u32) | (a << 24u32);
-            out_img_packed[pix_id as usize] = packed;
+            out_img_packed[pix_id as usize] = pack_rgba(final_r, final_g, final_b, final_a);
         }
     }
 
@@ -186,3 +237,117 @@ pub fn rasterize_kernel(
         }
     }
 }
+
+#[cube(launch)]
+pub fn rasterize_packed_kernel(
+    compact_gid_from_isect: &Tensor<u32>,
+    tile_offsets: &Tensor<u32>,
+    projected: &Tensor<f32>,
+    out_img_packed: &mut Tensor<u32>,
+    u: RasterizeUniforms,
+) {
+    let global_id = ABSOLUTE_POS as u32;
+    let (pix_x, pix_y) = map_1d_to_2d(global_id, u.tile_bw);
+    let pix_id = pix_x + pix_y * u.img_w;
+    let pixel_coord_x = pix_x as f32 + 0.5f32;
+    let pixel_coord_y = pix_y as f32 + 0.5f32;
+    let tile_id = pix_x / TILE_WIDTH + (pix_y / TILE_WIDTH) * u.tile_bw;
+    let inside = pix_x < u.img_w && pix_y < u.img_h;
+
+    let mut local_batch = Shared::new_slice((TILE_SIZE * PROJECTED_LANES) as usize);
+    let num_done_atomic = Shared::<[Atomic<u32>]>::new_slice(1usize);
+    let mut range = Shared::new_slice(2usize);
+
+    let local_idx = UNIT_POS;
+    if local_idx == 0u32 {
+        range[0] = tile_offsets[(tile_id * 2u32) as usize];
+        range[1] = tile_offsets[(tile_id * 2u32 + 1u32) as usize];
+        Atomic::store(&num_done_atomic[0], 0u32);
+    }
+    let range_lo = workgroup_uniform_load(&range[0]);
+    let range_hi = workgroup_uniform_load(&range[1]);
+
+    let mut t_acc = 1.0f32;
+    let mut pix_r = 0.0f32;
+    let mut pix_g = 0.0f32;
+    let mut pix_b = 0.0f32;
+    let mut done = !inside;
+
+    if done {
+        Atomic::fetch_add(&num_done_atomic[0], 1u32);
+    }
+    sync_cube();
+
+    let mut batch_start = range_lo;
+    while batch_start < range_hi {
+        if workgroup_uniform_load_atomic(&num_done_atomic[0]) >= TILE_SIZE {
+            break;
+        }
+        let remaining = min(TILE_SIZE, range_hi - batch_start);
+        if local_idx < remaining {
+            let compact_gid = compact_gid_from_isect[(batch_start + local_idx) as usize];
+            let src_base = (compact_gid * PROJECTED_LANES) as usize;
+            let dst_base = (local_idx * PROJECTED_LANES) as usize;
+            #[unroll]
+            for lane in 0..PROJECTED_LANES_USIZE {
+                local_batch[dst_base + lane] = projected[src_base + lane];
+            }
+        }
+        sync_cube();
+
+        let was_done = done;
+        let mut t = 0u32;
+        while !done && t < remaining {
+            let dst_base = (t * PROJECTED_LANES) as usize;
+            let conic = Sym2 {
+                c00: local_batch[dst_base + 2],
+                c01: local_batch[dst_base + 3],
+                c11: local_batch[dst_base + 4],
+            };
+            let sigma = calc_sigma(
+                pixel_coord_x,
+                pixel_coord_y,
+                conic,
+                local_batch[dst_base],
+                local_batch[dst_base + 1],
+            );
+            let alpha = min(0.999f32, local_batch[dst_base + 5] * f32::exp(-sigma));
+            if sigma >= 0.0f32 && alpha >= ALPHA_CUTOFF_MID {
+                let next_t = t_acc * (1.0f32 - alpha);
+                if next_t <= 1.0e-4f32 {
+                    done = true;
+                } else {
+                    let vis = alpha * t_acc;
+                    pix_r += max(local_batch[dst_base + 6], 0.0f32) * vis;
+                    pix_g += max(local_batch[dst_base + 7], 0.0f32) * vis;
+                    pix_b += max(local_batch[dst_base + 8], 0.0f32) * vis;
+                    t_acc = next_t;
+                }
+            }
+            t += 1u32;
+        }
+        if !was_done && done {
+            Atomic::fetch_add(&num_done_atomic[0], 1u32);
+        }
+        batch_start += TILE_SIZE;
+    }
+
+    if inside {
+        out_img_packed[pix_id as usize] = pack_rgba(
+            pix_r + t_acc * u.bg_r,
+            pix_g + t_acc * u.bg_g,
+            pix_b + t_acc * u.bg_b,
+            1.0f32 - t_acc,
+        );
+    }
+}
+
+/// Quantize a color to u8x4, red in the lowest byte.
+#[cube]
+fn pack_rgba(r: f32, g: f32, b: f32, a: f32) -> u32 {
+    let r = clamp(r * 255.0f32, 0.0f32, 255.0f32) as u32;
+    let g = clamp(g * 255.0f32, 0.0f32, 255.0f32) as u32;
+    let b = clamp(b * 255.0f32, 0.0f32, 255.0f32) as u32;
+    let a = clamp(a * 255.0f32, 0.0f32, 255.0f32) as u32;
+    r | (g << 8u32) | (b << 16u32) | (a << 24u32)
+}
@@ -13,11 +13,11 @@ use crate::{
 use brush_cube::create_tensor;
 use brush_cube::{MainBackendBase, calc_cube_count_1d};
 use brush_prefix_sum::prefix_sum;
-use brush_sort::radix_argsort;
+use brush_sort::{SortOrder, radix_argsort};
 use burn::backend::TensorMetadata;
 use burn::backend::ops::TransactionPrimitive;
 use burn::backend::ops::{FloatTensorOps, IntTensorOps, TransactionOps};
-use burn::backend::tensor::FloatTensor;
+use burn::backend::tensor::{FloatTensor, IntTensor};
 use burn::tensor::{DType, FloatDType, IntDType};
 use burn_cubecl::cubecl::CubeDim;
 use burn_cubecl::kernel::into_contiguous;
@@ -25,6 +25,25 @@ use burn_wgpu::WgpuRuntime;
 use glam::{Vec3, uvec2};
 use kernels::types::RasterizeUniformsLaunch;
 use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// The most storage buffers the regular project & rasterize kernels bind in
+/// one shader stage.
+pub const UNPACKED_STORAGE_BUFFERS: u32 = 8;
+
+static STORAGE_BUFFER_LIMIT: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// Record how many storage buffers per shader stage the device can bind. On
+/// devices below [`UNPACKED_STORAGE_BUFFERS`], [`RasterPass::Forward`] renders
+/// use the packed kernels, see [`RasterPass::ForwardPacked`].
+pub fn set_storage_buffer_limit(limit: u32) {
+    STORAGE_BUFFER_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// Logical buffers packed into one binding start at multiples of this many
+/// elements, so each can be sliced out on its own (256 bytes is wgpu's
+/// storage buffer offset alignment).
+const PACKED_ALIGN: usize = 64;
 
 #[doc(hidden)]
 pub fn calc_tile_bounds(img_size: glam::UVec2) -> glam::UVec2 {
@@ -52,6 +71,12 @@ impl SplatOps for MainBackendBase {
         );
         let bwd_info = pass.bwd_info();
         let smooth_cutoff = pass.smooth_cutoff();
+        let depth_out = pass.depth();
+        let pick_info = pass.pick();
+        let count_out = pass.overdraw();
+        let packed = pass == RasterPass::ForwardPacked
+            || (pass == RasterPass::Forward
+                && STORAGE_BUFFER_LIMIT.load(Ordering::Relaxed) < UNPACKED_STORAGE_BUFFERS);
 
         let transforms = into_contiguous(transforms);
         let sh_coeffs = into_contiguous(sh_coeffs);
@@ -66,104 +91,35 @@ impl SplatOps for MainBackendBase {
         let sh_degree = sh_degree_from_coeffs(sh_coeffs.shape()[1] as u32);
         let mip_splat = matches!(render_mode, SplatRenderMode::Mip);
 
-        let half_max_render_fov =
-            ((camera.fov_x as f32).hypot(camera.fov_y as f32) * 1.05).min(2.0 * PI - 1e-6) * 0.5;
-        let pinhole_params = camera.build_pinhole_params(img_size);
-
-        let mut project_uniforms = shaders::helpers::ProjectUniforms {
-            viewmat: glam::Mat4::from(camera.world_to_local()).to_cols_array_2d(),
-            camera_model: camera.camera_model,
-            half_max_render_fov,
-            pinhole_params,
-            camera_position: [camera.position.x, camera.position.y, camera.position.z, 0.0],
-            img_size: img_size.into(),
-            tile_bounds: calc_tile_bounds(img_size).into(),
-            sh_degree,
-            total_splats,
-            num_visible: 0, // num_visible — not yet known.
-            jacobian_clamp_limits: calculate_jacobian_clamp_limits(
-                img_size,
-                pinhole_params,
-                camera.camera_model,
-            ),
-        };
+        let mut project_uniforms = project_uniforms(camera, img_size, total_splats, sh_degree);
 
         let device = transforms.device.clone();
         let client = transforms.client.clone();
 
-        let (
+        // The packed kernels read the raw opacities from behind the transforms.
+        let splat_data = packed.then(|| {
+            let flat = Self::float_reshape(transforms.clone(), [total_splats as usize * 10].into());
+            Self::float_cat(vec![flat, raw_opacities.clone()], 0)
+        });
+
+        let ProjectForward {
             global_from_presort_gid,
             depths,
             intersect_counts,
             max_radius,
-            num_visible_buf,
-            num_intersections_buf,
-        ) = {
-            let project_uniforms: &shaders::helpers::ProjectUniforms = &project_uniforms;
-            let _span = tracing::trace_span!("ProjectSplats").entered();
-
-            let total_splats = project_uniforms.total_splats as usize;
-            let num_visible_buf = Self::int_zeros([1].into(), &device, IntDType::U32);
-            let num_intersections_buf = Self::int_zeros([1].into(), &device, IntDType::U32);
-            let intersect_counts = Self::int_zeros([total_splats].into(), &device, IntDType::U32);
-            let max_radius = Self::float_zeros([total_splats].into(), &device, FloatDType::F32);
-
-            let global_from_presort_gid = create_tensor([total_splats], &device, DType::U32);
-            let depths = create_tensor([total_splats], &device, DType::F32);
-
-            let uniforms = project_uniforms.to_launch_object();
-
-            kernels::project_forward::project_forward_kernel::launch::<WgpuRuntime>(
-                &client,
-                calc_cube_count_1d(
-                    project_uniforms.total_splats,
-                    kernels::project_forward::WG_SIZE,
-                ),
-                CubeDim::new_1d(kernels::project_forward::WG_SIZE),
-                transforms.clone().into_tensor_arg(),
-                raw_opacities.clone().into_tensor_arg(),
-                global_from_presort_gid.clone().into_tensor_arg(),
-                depths.clone().into_tensor_arg(),
-                num_visible_buf.clone().into_tensor_arg(),
-                intersect_counts.clone().into_tensor_arg(),
-                num_intersections_buf.clone().into_tensor_arg(),
-                max_radius.clone().into_tensor_arg(),
-                uniforms,
-                mip_splat,
-                camera.camera_model,
-            );
-            (
-                global_from_presort_gid,
-                depths,
-                intersect_counts,
-                max_radius,
-                num_visible_buf,
-                num_intersections_buf,
-            )
-        };
-
+            count_bufs,
+        } = project_forward(
+            &project_uniforms,
+            &transforms,
+            &raw_opacities,
+            splat_data.as_ref(),
+            mip_splat,
+        );
         // Read both atomic counts in one transaction BEFORE the sort.
         let (num_visible, num_intersections) = if total_splats == 0 {
             (0, 0)
         } else {
-            let tp = TransactionPrimitive::<Self>::new(
-                vec![],
-                vec![],
-                vec![num_visible_buf, num_intersections_buf],
-                vec![],
-            );
-            let data = <Self as TransactionOps<Self>>::tr_execute(tp)
-                .await
-                .expect("Failed to read counts");
-            let num_visible = data.read_ints[0]
-                .clone()
-                .into_vec::<u32>()
-                .expect("num_visible")[0];
-            let num_intersections = data.read_ints[1]
-                .clone()
-                .into_vec::<u32>()
-                .expect("num_intersections")[0];
-            (num_visible, num_intersections)
+            read_counts(count_bufs).await
         };
 
         project_uniforms.num_visible = num_visible;
@@ -173,13 +129,14 @@ impl SplatOps for MainBackendBase {
         let tile_bounds: glam::UVec2 = project_uniforms.tile_bounds.into();
         let num_visible_sz = (num_visible as usize).max(1);
 
-        let global_from_compact_gid = {
+        // The sorted keys are the per compact gid depths, used for depth output.
+        let (compact_depths, global_from_compact_gid) = {
             let depths = Self::float_slice(depths, &[(0..num_visible_sz).into()]);
             let global_from_presort_gid =
                 Self::int_slice(global_from_presort_gid, &[(0..num_visible_sz).into()]);
-            let (_, global_from_compact_gid) = tracing::trace_span!("DepthSort")
-                .in_scope(|| radix_argsort(depths, global_from_presort_gid, 32));
-            global_from_compact_gid
+            tracing::trace_span!("DepthSort").in_scope(|| {
+                radix_argsort(depths, global_from_presort_gid, 32, SortOrder::Ascending)
+            })
         };
         let compact_counts = Self::int_gather(0, intersect_counts, global_from_compact_gid.clone());
         let cum_tiles_hit =
@@ -191,20 +148,38 @@ impl SplatOps for MainBackendBase {
         );
         tracing::trace_span!("ProjectVisible").in_scope(|| {
             let uniforms = project_uniforms.to_launch_object();
-            kernels::project_visible::project_visible_kernel::launch::<WgpuRuntime>(
-                &client,
-                calc_cube_count_1d(num_visible, kernels::project_visible::WG_SIZE),
-                CubeDim::new_1d(kernels::project_visible::WG_SIZE),
-                transforms.into_tensor_arg(),
-                sh_coeffs.into_tensor_arg(),
-                raw_opacities.into_tensor_arg(),
-                global_from_compact_gid.clone().into_tensor_arg(),
-                projected_splats.clone().into_tensor_arg(),
-                uniforms,
-                mip_splat,
-                sh_degree,
-                camera.camera_model,
-            );
+            let cube_count = calc_cube_count_1d(num_visible, kernels::project_visible::WG_SIZE);
+            let cube_dim = CubeDim::new_1d(kernels::project_visible::WG_SIZE);
+            if let Some(splat_data) = splat_data {
+                kernels::project_visible::project_visible_packed_kernel::launch::<WgpuRuntime>(
+                    &client,
+                    cube_count,
+                    cube_dim,
+                    splat_data.into_tensor_arg(),
+                    sh_coeffs.into_tensor_arg(),
+                    global_from_compact_gid.clone().into_tensor_arg(),
+                    projected_splats.clone().into_tensor_arg(),
+                    uniforms,
+                    mip_splat,
+                    sh_degree,
+                    camera.camera_model,
+                );
+            } else {
+                kernels::project_visible::project_visible_kernel::launch::<WgpuRuntime>(
+                    &client,
+                    cube_count,
+                    cube_dim,
+                    transforms.into_tensor_arg(),
+                    sh_coeffs.into_tensor_arg(),
+                    raw_opacities.into_tensor_arg(),
+                    global_from_compact_gid.clone().into_tensor_arg(),
+                    projected_splats.clone().into_tensor_arg(),
+                    uniforms,
+                    mip_splat,
+                    sh_degree,
+                    camera.camera_model,
+                );
+            }
         });
         let num_tiles = tile_bounds.x * tile_bounds.y;
         let buffer_size = (num_intersections as usize).max(1);
@@ -226,7 +201,14 @@ impl SplatOps for MainBackendBase {
         });
         let bits = u32::BITS - num_tiles.leading_zeros();
         let (tile_id_from_isect, compact_gid_from_isect) = tracing::trace_span!("Tile sort")
-            .in_scope(|| radix_argsort(tile_id_from_isect, compact_gid_from_isect, bits));
+            .in_scope(|| {
+                radix_argsort(
+                    tile_id_from_isect,
+                    compact_gid_from_isect,
+                    bits,
+                    SortOrder::Ascending,
+                )
+            });
         let cube_dim = CubeDim::new_1d(256);
         let tile_offsets = Self::int_zeros(
             [tile_bounds.y as usize, tile_bounds.x as usize, 2].into(),
@@ -244,13 +226,24 @@ impl SplatOps for MainBackendBase {
                 tile_offsets.clone().into_tensor_arg(),
             );
         });
-        let out_dim = if bwd_info { 4 } else { 1 };
+        let out_dim = if depth_out {
+            2
+        } else if bwd_info {
+            4
+        } else {
+            1
+        };
+        // Picking & overdraw write u32 ids / counts rather than (packed) colors.
         let out_img = create_tensor(
             [img_size.y as usize, img_size.x as usize, out_dim],
             &device,
-            DType::F32,
+            if pick_info || count_out {
+                DType::U32
+            } else {
+                DType::F32
+            },
         );
-        let (out_packed_arg, out_f32_arg) = if bwd_info {
+        let (out_packed_arg, out_f32_arg) = if bwd_info || depth_out {
             (create_tensor([1], &device, DType::U32), out_img.clone())
         } else {
             (out_img.clone(), create_tensor([1], &device, DType::F32))
@@ -273,24 +266,43 @@ impl SplatOps for MainBackendBase {
                 background.y,
                 background.z,
             );
-            kernels::rasterize::rasterize_kernel::launch::<WgpuRuntime>(
-                &client,
-                calc_cube_count_1d(
-                    num_tiles * (shaders::helpers::TILE_WIDTH * shaders::helpers::TILE_WIDTH),
-                    shaders::helpers::TILE_WIDTH * shaders::helpers::TILE_WIDTH,
-                ),
-                CubeDim::new_1d(shaders::helpers::TILE_SIZE),
-                compact_gid_from_isect.clone().into_tensor_arg(),
-                tile_offsets.clone().into_tensor_arg(),
-                projected_splats.clone().into_tensor_arg(),
-                out_packed_arg.into_tensor_arg(),
-                out_f32_arg.into_tensor_arg(),
-                global_from_compact_gid.clone().into_tensor_arg(),
-                visible.clone().into_tensor_arg(),
-                uniforms,
-                bwd_info,
-                smooth_cutoff,
+            let cube_count = calc_cube_count_1d(
+                num_tiles * (shaders::helpers::TILE_WIDTH * shaders::helpers::TILE_WIDTH),
+                shaders::helpers::TILE_WIDTH * shaders::helpers::TILE_WIDTH,
             );
+            let cube_dim = CubeDim::new_1d(shaders::helpers::TILE_SIZE);
+            if packed {
+                kernels::rasterize::rasterize_packed_kernel::launch::<WgpuRuntime>(
+                    &client,
+                    cube_count,
+                    cube_dim,
+                    compact_gid_from_isect.clone().into_tensor_arg(),
+                    tile_offsets.clone().into_tensor_arg(),
+                    projected_splats.clone().into_tensor_arg(),
+                    out_packed_arg.into_tensor_arg(),
+                    uniforms,
+                );
+            } else {
+                kernels::rasterize::rasterize_kernel::launch::<WgpuRuntime>(
+                    &client,
+                    cube_count,
+                    cube_dim,
+                    compact_gid_from_isect.clone().into_tensor_arg(),
+                    tile_offsets.clone().into_tensor_arg(),
+                    projected_splats.clone().into_tensor_arg(),
+                    out_packed_arg.into_tensor_arg(),
+                    out_f32_arg.into_tensor_arg(),
+                    global_from_compact_gid.clone().into_tensor_arg(),
+                    compact_depths.into_tensor_arg(),
+                    visible.clone().into_tensor_arg(),
+                    uniforms,
+                    bwd_info,
+                    smooth_cutoff,
+                    depth_out,
+                    pick_info,
+                    count_out,
+                );
+            }
         });
         RenderOutput {
             out_img,
@@ -309,3 +321,168 @@ impl SplatOps for MainBackendBase {
         }
     }
 }
+
+/// Uniforms for the project passes of a render of `img_size` from `camera`.
+/// `num_visible` is filled in once the project & cull pass counted it.
+fn project_uniforms(
+    camera: &Camera,
+    img_size: glam::UVec2,
+    total_splats: u32,
+    sh_degree: u32,
+) -> shaders::helpers::ProjectUniforms {
+    let half_max_render_fov =
+        ((camera.fov_x as f32).hypot(camera.fov_y as f32) * 1.05).min(2.0 * PI - 1e-6) * 0.5;
+    let pinhole_params = camera.build_pinhole_params(img_size);
+    shaders::helpers::ProjectUniforms {
+        viewmat: glam::Mat4::from(camera.world_to_local()).to_cols_array_2d(),
+        camera_model: camera.camera_model,
+        half_max_render_fov,
+        pinhole_params,
+        camera_position: [camera.position.x, camera.position.y, camera.position.z, 0.0],
+        img_size: img_size.into(),
+        tile_bounds: calc_tile_bounds(img_size).into(),
+        sh_degree,
+        total_splats,
+        num_visible: 0,
+        jacobian_clamp_limits: calculate_jacobian_clamp_limits(
+            img_size,
+            pinhole_params,
+            camera.camera_model,
+        ),
+    }
+}
+
+/// Output of the project & cull pass. The visible & intersection counts are
+/// still on the GPU, see [`read_counts`].
+struct ProjectForward {
+    global_from_presort_gid: IntTensor<MainBackendBase>,
+    depths: FloatTensor<MainBackendBase>,
+    intersect_counts: IntTensor<MainBackendBase>,
+    max_radius: FloatTensor<MainBackendBase>,
+    count_bufs: Vec<IntTensor<MainBackendBase>>,
+}
+
+/// Launch the project & cull pass. With `splat_data` set this uses the packed
+/// kernel, which reads the transforms & raw opacities from it.
+fn project_forward(
+    uniforms: &shaders::helpers::ProjectUniforms,
+    transforms: &FloatTensor<MainBackendBase>,
+    raw_opacities: &FloatTensor<MainBackendBase>,
+    splat_data: Option<&FloatTensor<MainBackendBase>>,
+    mip_splat: bool,
+) -> ProjectForward {
+    let _span = tracing::trace_span!("ProjectSplats").entered();
+    let device = transforms.device.clone();
+    let client = transforms.client.clone();
+
+    let total_splats = uniforms.total_splats as usize;
+    let cube_count = calc_cube_count_1d(uniforms.total_splats, kernels::project_forward::WG_SIZE);
+    let cube_dim = CubeDim::new_1d(kernels::project_forward::WG_SIZE);
+    let launch_uniforms = uniforms.to_launch_object();
+
+    if let Some(splat_data) = splat_data {
+        let stride = total_splats.max(1).next_multiple_of(PACKED_ALIGN);
+        // Max radius isn't written for culled splats, so has to start zeroed.
+        let splat_out = MainBackendBase::float_zeros([2 * stride].into(), &device, FloatDType::F32);
+        let ids_out = MainBackendBase::int_zeros([3 * stride].into(), &device, IntDType::U32);
+
+        kernels::project_forward::project_forward_packed_kernel::launch::<WgpuRuntime>(
+            &client,
+            cube_count,
+            cube_dim,
+            splat_data.clone().into_tensor_arg(),
+            splat_out.clone().into_tensor_arg(),
+            ids_out.clone().into_tensor_arg(),
+            stride as u32,
+            launch_uniforms,
+            mip_splat,
+            uniforms.camera_model,
+        );
+        ProjectForward {
+            global_from_presort_gid: MainBackendBase::int_slice(
+                ids_out.clone(),
+                &[(stride..2 * stride).into()],
+            ),
+            depths: MainBackendBase::float_slice(splat_out.clone(), &[(0..stride).into()]),
+            intersect_counts: MainBackendBase::int_slice(
+                ids_out.clone(),
+                &[(2 * stride..3 * stride).into()],
+            ),
+            max_radius: MainBackendBase::float_slice(
+                splat_out,
+                &[(stride..stride + total_splats).into()],
+            ),
+            count_bufs: vec![MainBackendBase::int_slice(ids_out, &[(0..2).into()])],
+        }
+    } else {
+        let num_visible_buf = MainBackendBase::int_zeros([1].into(), &device, IntDType::U32);
+        let num_intersections_buf = MainBackendBase::int_zeros([1].into(), &device, IntDType::U32);
+        let intersect_counts =
+            MainBackendBase::int_zeros([total_splats].into(), &device, IntDType::U32);
+        let max_radius =
+            MainBackendBase::float_zeros([total_splats].into(), &device, FloatDType::F32);
+
+        let global_from_presort_gid = create_tensor([total_splats], &device, DType::U32);
+        let depths = create_tensor([total_splats], &device, DType::F32);
+
+        kernels::project_forward::project_forward_kernel::launch::<WgpuRuntime>(
+            &client,
+            cube_count,
+            cube_dim,
+            transforms.clone().into_tensor_arg(),
+            raw_opacities.clone().into_tensor_arg(),
+            global_from_presort_gid.clone().into_tensor_arg(),
+            depths.clone().into_tensor_arg(),
+            num_visible_buf.clone().into_tensor_arg(),
+            intersect_counts.clone().into_tensor_arg(),
+            num_intersections_buf.clone().into_tensor_arg(),
+            max_radius.clone().into_tensor_arg(),
+            launch_uniforms,
+            mip_splat,
+            uniforms.camera_model,
+        );
+        ProjectForward {
+            global_from_presort_gid,
+            depths,
+            intersect_counts,
+            max_radius,
+            count_bufs: vec![num_visible_buf, num_intersections_buf],
+        }
+    }
+}
+
+/// Read back the number of visible splats and their tile intersections.
+async fn read_counts(count_bufs: Vec<IntTensor<MainBackendBase>>) -> (u32, u32) {
+    let tp = TransactionPrimitive::<MainBackendBase>::new(vec![], vec![], count_bufs, vec![]);
+    let data = <MainBackendBase as TransactionOps<MainBackendBase>>::tr_execute(tp)
+        .await
+        .expect("Failed to read counts");
+    let counts: Vec<u32> = data
+        .read_ints
+        .into_iter()
+        .flat_map(|data| data.into_vec::<u32>().expect("counts"))
+        .collect();
+    (counts[0], counts[1])
+}
+
+/// Count the splats visible from `camera` at `img_size` and their tile
+/// intersections, without rendering. A render allocates its sort buffers per
+/// intersection, this only runs the project & cull pass to see how many that'll be.
+pub async fn count_intersections(
+    camera: &Camera,
+    img_size: glam::UVec2,
+    transforms: FloatTensor<MainBackendBase>,
+    raw_opacities: FloatTensor<MainBackendBase>,
+    render_mode: SplatRenderMode,
+) -> (u32, u32) {
+    let transforms = into_contiguous(transforms);
+    let raw_opacities = into_contiguous(raw_opacities);
+    let total_splats = transforms.shape()[0] as u32;
+    if total_splats == 0 {
+        return (0, 0);
+    }
+    let uniforms = project_uniforms(camera, img_size, total_splats, 0);
+    let mip_splat = matches!(render_mode, SplatRenderMode::Mip);
+    let projected = project_forward(&uniforms, &transforms, &raw_opacities, None, mip_splat);
+    read_counts(projected.count_bufs).await
+}
@@ -81,16 +81,16 @@ pub struct RenderAux {
 }
 
 impl RenderAux {
-    /// Calculate tile depth map for visualization.
-    pub fn calc_tile_depth(&self) -> Tensor<2, Int> {
-        use crate::shaders::helpers::TILE_WIDTH;
+    /// Number of splat intersections per tile as a `[tiles_y, tiles_x]` heatmap.
+    ///
+    /// This is computed from `tile_offsets` with tensor ops, so it stays on the
+    /// GPU and only costs anything when asked for.
+    pub fn tile_counts(&self) -> Tensor<2, Int> {
         use burn::tensor::s;
 
-        let tile_offsets = self.tile_offsets.clone();
-        let max = tile_offsets.clone().slice(s![.., .., 1]);
-        let min = tile_offsets.slice(s![.., .., 0]);
-        let [w, h] = self.img_size.into();
-        let [ty, tx] = [h.div_ceil(TILE_WIDTH), w.div_ceil(TILE_WIDTH)];
-        (max - min).reshape([ty as usize, tx as usize])
+        let [ty, tx, _] = self.tile_offsets.dims();
+        let end = self.tile_offsets.clone().slice(s![.., .., 1]);
+        let start = self.tile_offsets.clone().slice(s![.., .., 0]);
+        (end - start).reshape([ty, tx])
     }
 }
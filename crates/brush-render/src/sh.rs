@@ -1,6 +1,6 @@
 use crate::shaders;
 
-use glam::Vec3;
+use glam::{Quat, Vec3};
 const SH_C0: f32 = shaders::SH_C0;
 
 pub const fn sh_coeffs_for_degree(degree: u32) -> u32 {
@@ -29,3 +29,161 @@ pub fn rgb_to_sh(rgb: Vec3) -> Vec3 {
         channel_to_sh(rgb.z),
     )
 }
+
+pub fn sh_to_channel(sh: f32) -> f32 {
+    sh * SH_C0 + 0.5
+}
+
+pub fn sh_to_rgb(sh: Vec3) -> Vec3 {
+    glam::vec3(
+        sh_to_channel(sh.x),
+        sh_to_channel(sh.y),
+        sh_to_channel(sh.z),
+    )
+}
+
+/// The SH basis functions up to `degree` at the unit direction `dir`, in the
+/// order of the coefficients. Mirrors `sh_coeffs_to_color` in the render kernel.
+pub fn sh_basis(degree: u32, dir: Vec3) -> Vec<f32> {
+    let (x, y, z) = (dir.x, dir.y, dir.z);
+    let mut basis = vec![SH_C0];
+    if degree >= 1 {
+        let f0a = 0.488_602_5;
+        basis.extend([-f0a * y, f0a * z, -f0a * x]);
+    }
+    if degree >= 2 {
+        let z2 = z * z;
+        let f0b = -1.092_548_5 * z;
+        let f1a = 0.546_274_24;
+        let fc1 = x * x - y * y;
+        let fs1 = 2.0 * x * y;
+        let p_sh6 = 0.946_174_7 * z2 - 0.315_391_57;
+        basis.extend([f1a * fs1, f0b * y, p_sh6, f0b * x, f1a * fc1]);
+
+        if degree >= 3 {
+            let f0c = -2.285_229 * z2 + 0.457_045_8;
+            let f1b = 1.445_305_7 * z;
+            let f2a = -0.590_043_6;
+            let fc2 = x * fc1 - y * fs1;
+            let fs2 = x * fs1 + y * fc1;
+            let p_sh12 = z * (1.865_881_7 * z2 - 1.119_529);
+            basis.extend([
+                f2a * fs2,
+                f1b * fs1,
+                f0c * y,
+                p_sh12,
+                f0c * x,
+                f1b * fc1,
+                f2a * fc2,
+            ]);
+
+            if degree >= 4 {
+                let f0d = z * (-4.683_326 * z2 + 2.007_139_6);
+                let f1c = 3.311_611_4 * z2 - 0.473_087_35;
+                let f2b = -1.770_130_8 * z;
+                let f3a = 0.625_835_75;
+                let fc3 = x * fc2 - y * fs2;
+                let fs3 = x * fs2 + y * fc2;
+                let p_sh20 = 1.984_313_5 * z * p_sh12 - 1.006_230_6 * p_sh6;
+                basis.extend([
+                    f3a * fs3,
+                    f2b * fs2,
+                    f1c * fs1,
+                    f0d * y,
+                    p_sh20,
+                    f0d * x,
+                    f1c * fc1,
+                    f2b * fc2,
+                    f3a * fc3,
+                ]);
+            }
+        }
+    }
+    basis
+}
+
+/// Row-major `[n, n]` matrix, for the `n` coefficients of `degree`, that
+/// rotates SH coefficients along with `rotation`: the rotated coefficients give
+/// the same color in direction `rotation * dir` as the originals in `dir`.
+///
+/// Each band rotates on its own, so the matrix is block diagonal. A band's
+/// block is fit by least squares on a spread of sample directions, which is
+/// exact as rotated band `l` functions are again band `l` functions.
+pub fn sh_rotation_matrix(degree: u32, rotation: Quat) -> Vec<f32> {
+    let n = sh_coeffs_for_degree(degree) as usize;
+    let mut matrix = vec![0.0; n * n];
+    matrix[0] = 1.0;
+
+    // Fibonacci sphere, comfortably more directions than the largest band has functions.
+    let num_dirs = 4 * (2 * degree as usize + 1);
+    let dirs: Vec<Vec3> = (0..num_dirs)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / num_dirs as f32;
+            let r = (1.0 - z * z).sqrt();
+            let phi = i as f32 * std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+            Vec3::new(r * phi.cos(), r * phi.sin(), z)
+        })
+        .collect();
+    let basis: Vec<_> = dirs.iter().map(|&d| sh_basis(degree, d)).collect();
+    let inv_rotation = rotation.inverse();
+    let rotated: Vec<_> = dirs
+        .iter()
+        .map(|&d| sh_basis(degree, inv_rotation * d))
+        .collect();
+
+    for band in 1..=degree as usize {
+        let start = band * band;
+        let size = 2 * band + 1;
+        // Solve `B^T B D = B^T B'`, with `B` the band's basis at the sample
+        // directions and `B'` the basis at the un-rotated directions.
+        let mut lhs = vec![0.0f64; size * size];
+        let mut rhs = vec![0.0f64; size * size];
+        for (b, b_rot) in basis.iter().zip(&rotated) {
+            for i in 0..size {
+                for j in 0..size {
+                    let bi = b[start + i] as f64;
+                    lhs[i * size + j] += bi * b[start + j] as f64;
+                    rhs[i * size + j] += bi * b_rot[start + j] as f64;
+                }
+            }
+        }
+        let block = solve(lhs, rhs, size);
+        for i in 0..size {
+            for j in 0..size {
+                matrix[(start + i) * n + start + j] = block[i * size + j] as f32;
+            }
+        }
+    }
+    matrix
+}
+
+/// Solve `a x = b` for the row-major `[n, n]` matrices `a` and `b`, with
+/// Gaussian elimination and partial pivoting.
+fn solve(mut a: Vec<f64>, mut b: Vec<f64>, n: usize) -> Vec<f64> {
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))
+            .expect("Non empty range");
+        for k in 0..n {
+            a.swap(col * n + k, pivot * n + k);
+            b.swap(col * n + k, pivot * n + k);
+        }
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let factor = a[row * n + col] / a[col * n + col];
+            for k in 0..n {
+                a[row * n + k] -= factor * a[col * n + k];
+                b[row * n + k] -= factor * b[col * n + k];
+            }
+        }
+    }
+    for row in 0..n {
+        let diag = a[row * n + row];
+        for k in 0..n {
+            b[row * n + k] /= diag;
+        }
+    }
+    b
+}
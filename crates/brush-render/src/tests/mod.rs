@@ -4,12 +4,17 @@ use crate::kernels::camera_model::kannala_brandt_4::KannalaBrandt4Params;
 use crate::kernels::camera_model::radial_tangential_8::RadialTangential8Params;
 use crate::kernels::camera_model::thin_prism_fisheye::ThinPrismFisheyeParams;
 use crate::{
-    TextureMode,
+    SplatOps, TextureMode,
+    bounding_box::BoundingBox,
+    burn_glue::{unwrap_wgpu_float, wrap_wgpu_int},
     camera::Camera,
-    gaussian_splats::{SplatRenderMode, Splats, render_splats},
+    gaussian_splats::{
+        RasterPass, SplatFilter, SplatRenderMode, Splats, render_splats, render_splats_supersampled,
+    },
 };
 use assert_approx_eq::assert_approx_eq;
-use burn::tensor::{Distribution, Tensor};
+use burn::backend::Dispatch;
+use burn::tensor::{Distribution, Tensor, s};
 use glam::Vec3;
 use wasm_bindgen_test::wasm_bindgen_test;
 
@@ -119,6 +124,635 @@ async fn renders_many_splats() {
     assert!(any_nonbg, "30M splats rendered to an entirely empty image");
 }
 
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn renders_depth() {
+    // A single splat 5 units in front of the camera, covering the center but
+    // not the corners of the image.
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -5.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+    let splats = Splats::from_tensor_data(
+        Tensor::<2>::zeros([1, 3], &device),
+        Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array()], &device),
+        Tensor::<2>::full([1, 3], 0.2f32.ln(), &device),
+        Tensor::<3>::ones([1, 1, 3], &device),
+        Tensor::<1>::full([1], 5.0, &device),
+        SplatRenderMode::Default,
+    );
+    let output = splats.render_depth(&cam, img_size).await;
+    assert_eq!(output.dims(), [64, 64, 2]);
+
+    let pixels = read_finite(output).await;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * 64 + x) * 2;
+        (pixels[i], pixels[i + 1])
+    };
+
+    let (depth, alpha) = pixel(32, 32);
+    assert!(alpha > 0.9, "Center pixel should be covered, alpha {alpha}");
+    assert_approx_eq!(depth, 5.0, 1e-3);
+
+    let (depth, alpha) = pixel(0, 0);
+    assert_eq!(alpha, 0.0);
+    assert_eq!(depth, 0.0);
+
+    // The expected depth is weighted by alpha, instead of normalized by it.
+    let expected = splats.render_expected_depth(&cam, img_size).await;
+    assert_eq!(expected.dims(), [64, 64]);
+    let expected = read_finite(expected.unsqueeze_dim(2)).await;
+    let (depth, alpha) = pixel(32, 32);
+    assert_approx_eq!(expected[32 * 64 + 32], depth * alpha, 1e-4);
+    assert_eq!(expected[0], 0.0);
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn renders_pick_ids() {
+    // Two splats 5 units in front of the camera, one left and one right of center.
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -5.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+    let splats = Splats::from_tensor_data(
+        Tensor::<2>::from_floats([[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]], &device),
+        Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array(); 2], &device),
+        Tensor::<2>::full([2, 3], 0.2f32.ln(), &device),
+        Tensor::<3>::ones([2, 1, 3], &device),
+        Tensor::<1>::full([2], 5.0, &device),
+        SplatRenderMode::Default,
+    );
+    let ids = splats.render_pick(&cam, img_size).await;
+    assert_eq!(ids.dims(), [64, 64]);
+    let ids = ids
+        .into_data_async()
+        .await
+        .expect("readback")
+        .into_vec::<u32>()
+        .expect("data vec");
+
+    // x = -1 and x = 1 project to ~25 pixels left and right of the center.
+    assert_eq!(ids[32 * 64 + 7], 0);
+    assert_eq!(ids[32 * 64 + 57], 1);
+    // Nothing is hit in between or in the corners.
+    assert_eq!(ids[32 * 64 + 32], u32::MAX);
+    assert_eq!(ids[0], u32::MAX);
+
+    assert_eq!(
+        splats.pick(&cam, img_size, glam::uvec2(57, 32)).await,
+        Some(1)
+    );
+    assert_eq!(splats.pick(&cam, img_size, glam::uvec2(0, 0)).await, None);
+    assert_eq!(splats.pick(&cam, img_size, glam::uvec2(64, 0)).await, None);
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn renders_overdraw_count() {
+    // One large opaque splat in front of the camera, covering the whole view.
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -5.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+    let splats = Splats::from_tensor_data(
+        Tensor::<2>::from_floats([[0.0, 0.0, 0.0]], &device),
+        Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array()], &device),
+        Tensor::<2>::full([1, 3], 10.0f32.ln(), &device),
+        Tensor::<3>::ones([1, 1, 3], &device),
+        Tensor::<1>::full([1], 5.0, &device),
+        SplatRenderMode::Default,
+    );
+    let counts = splats.render_overdraw(&cam, img_size).await;
+    assert_eq!(counts.dims(), [64, 64]);
+    let counts = counts
+        .into_data_async()
+        .await
+        .expect("readback")
+        .into_vec::<u32>()
+        .expect("data vec");
+    let ids = splats
+        .render_pick(&cam, img_size)
+        .await
+        .into_data_async()
+        .await
+        .expect("readback")
+        .into_vec::<u32>()
+        .expect("data vec");
+
+    assert!(
+        ids.iter().all(|&id| id == 0),
+        "the splat should cover the whole image"
+    );
+    assert!(
+        counts.iter().all(|&c| c == 1),
+        "every covered pixel should blend exactly one splat"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn orthographic_spacing_is_depth_independent() {
+    // An 8x8 unit view on 64x64 pixels, so 8 pixels per unit.
+    let cam = Camera::orthographic(glam::vec3(0.0, 0.0, -10.0), glam::Quat::IDENTITY, 8.0, 8.0);
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+    let mut rows = vec![];
+    for z in [-8.0f32, 0.0, 8.0] {
+        // A row of splats one unit apart.
+        let means = [-2.0, -1.0, 0.0, 1.0, 2.0].map(|x| [x, 0.0, z]);
+        let splats = Splats::from_tensor_data(
+            Tensor::<2>::from_floats(means, &device),
+            Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array(); 5], &device),
+            Tensor::<2>::full([5, 3], 0.15f32.ln(), &device),
+            Tensor::<3>::ones([5, 1, 3], &device),
+            Tensor::<1>::full([5], 5.0, &device),
+            SplatRenderMode::Default,
+        );
+        let ids = splats
+            .render_pick(&cam, img_size)
+            .await
+            .into_data_async()
+            .await
+            .expect("readback")
+            .into_vec::<u32>()
+            .expect("data vec");
+        rows.push(ids[32 * 64..33 * 64].to_vec());
+    }
+
+    // Splat i sits at x = i - 2, so it's centered on pixel 8 * (i - 2) + 32.
+    for (i, col) in [16, 24, 32, 40, 48].into_iter().enumerate() {
+        assert_eq!(rows[0][col], i as u32);
+    }
+    assert_eq!(rows[0][4], u32::MAX);
+    assert_eq!(rows[0], rows[1]);
+    assert_eq!(rows[0], rows[2]);
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn orthographic_radius_is_depth_independent() {
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+    // Screen-space radius of a single splat at depth `z`, seen from z = -10.
+    let radius_at = async |cam: &Camera, z: f32| {
+        let splats = Splats::from_tensor_data(
+            Tensor::<2>::from_floats([[0.0, 0.0, z]], &device),
+            Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array()], &device),
+            Tensor::<2>::full([1, 3], 0.25f32.ln(), &device),
+            Tensor::<3>::ones([1, 1, 3], &device),
+            Tensor::<1>::full([1], 5.0, &device),
+            SplatRenderMode::Default,
+        );
+        let (_, aux) =
+            render_splats(splats, cam, img_size, Vec3::ZERO, None, TextureMode::Float).await;
+        aux.max_radius
+            .into_data_async()
+            .await
+            .expect("readback")
+            .into_vec::<f32>()
+            .expect("data vec")[0]
+    };
+
+    let ortho = Camera::orthographic(glam::vec3(0.0, 0.0, -10.0), glam::Quat::IDENTITY, 8.0, 8.0);
+    let near = radius_at(&ortho, -5.0).await;
+    let far = radius_at(&ortho, 2.0).await;
+    assert!(near > 0.0, "splat culled in orthographic view");
+    assert_approx_eq!(near, far, 1e-3);
+
+    let persp = Camera::new(
+        glam::vec3(0.0, 0.0, -10.0),
+        glam::Quat::IDENTITY,
+        1.0,
+        1.0,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let near = radius_at(&persp, -5.0).await;
+    let far = radius_at(&persp, 2.0).await;
+    assert!(
+        near > far * 1.5,
+        "perspective radius {near} should shrink with depth, got {far}"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn supersampling_smooths_small_splat() {
+    // 8 pixels per unit, with a sub-pixel splat centered on pixel (32, 32).
+    let cam = Camera::orthographic(glam::vec3(0.0, 0.0, -10.0), glam::Quat::IDENTITY, 8.0, 8.0);
+    let img_size = glam::uvec2(64, 64);
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    let splats = Splats::from_tensor_data(
+        Tensor::<2>::from_floats([[0.0625, 0.0625, 0.0]], &device),
+        Tensor::<2>::from_floats([glam::Quat::IDENTITY.to_array()], &device),
+        Tensor::<2>::full([1, 3], 0.02f32.ln(), &device),
+        Tensor::<3>::ones([1, 1, 3], &device),
+        Tensor::<1>::full([1], 5.0, &device),
+        SplatRenderMode::Default,
+    );
+
+    let (native, _) = render_splats(
+        splats.clone(),
+        &cam,
+        img_size,
+        Vec3::ZERO,
+        None,
+        TextureMode::Float,
+    )
+    .await;
+    let ssaa = render_splats_supersampled(splats, &cam, img_size, Vec3::ZERO, None, 2).await;
+    assert_eq!(ssaa.dims(), [64, 64, 4]);
+
+    // Largest alpha step between neighbouring pixels along the splat's row.
+    let max_step = |img: Vec<f32>| {
+        let alpha: Vec<f32> = (0..64).map(|x| img[(32 * 64 + x) * 4 + 3]).collect();
+        alpha
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0f32, f32::max)
+    };
+    let native_step = max_step(read_finite(native).await);
+    let ssaa_step = max_step(read_finite(ssaa).await);
+    assert!(ssaa_step > 0.1, "splat missing from supersampled render");
+    assert!(
+        ssaa_step < native_step * 0.8,
+        "supersampled step {ssaa_step} not smoother than native {native_step}"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn render_batch_matches_single_renders() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    let scene = rng_scene(64, 1.5, (-3.0, -1.5), (0.2, 0.9), 7);
+    let splats = scene_to_splats(&scene, &device);
+    let img_size = glam::uvec2(48, 32);
+    let cameras: Vec<Camera> = [-0.3f32, 0.0, 0.4]
+        .into_iter()
+        .map(|x| {
+            Camera::new(
+                glam::vec3(x, 0.0, -4.0),
+                glam::Quat::IDENTITY,
+                0.8,
+                0.6,
+                glam::vec2(0.5, 0.5),
+                CameraModel::Pinhole,
+            )
+        })
+        .collect();
+
+    let batch = splats
+        .render_batch(&cameras, img_size, Vec3::ZERO, TextureMode::Float)
+        .await;
+    assert_eq!(batch.len(), cameras.len());
+    for ((img, _), cam) in batch.into_iter().zip(&cameras) {
+        let single = render_scene(&scene, cam, img_size, &device).await;
+        assert_eq!(max_abs_diff(&read_finite(img).await, &single), 0.0);
+    }
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn clamped_sh_degree_matches_zeroed_bands() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    let scene = rng_scene(64, 1.5, (-3.0, -1.5), (0.2, 0.9), 11);
+    let base = scene_to_splats(&scene, &device).with_sh_degree(3);
+    let n = scene.len();
+    let rest = Tensor::<3>::random([n, 15, 3], Distribution::Uniform(-0.5, 0.5), &device);
+    let dc = base.sh_coeffs.val().slice(s![.., 0..1]);
+    let splats = Splats {
+        sh_coeffs: base
+            .sh_coeffs
+            .clone()
+            .map(|_| Tensor::cat(vec![dc.clone(), rest], 1)),
+        ..base.clone()
+    };
+    let zeroed = Splats {
+        sh_coeffs: base
+            .sh_coeffs
+            .clone()
+            .map(|_| Tensor::cat(vec![dc.clone(), Tensor::zeros([n, 15, 3], &device)], 1)),
+        ..base
+    };
+
+    let cam = Camera::new(
+        glam::vec3(0.3, -0.2, -4.0),
+        glam::Quat::IDENTITY,
+        0.8,
+        0.6,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let img_size = glam::uvec2(48, 32);
+    let render = async |splats: Splats| {
+        let (img, _) =
+            render_splats(splats, &cam, img_size, Vec3::ZERO, None, TextureMode::Float).await;
+        read_finite(img).await
+    };
+
+    let clamped = splats.clone().clamp_sh_degree(0);
+    assert_eq!(clamped.sh_degree(), 0);
+    let clamped = render(clamped).await;
+    assert!(max_abs_diff(&clamped, &render(zeroed).await) < 1e-5);
+    // The higher bands do change the colors when they're evaluated.
+    assert!(max_abs_diff(&clamped, &render(splats).await) > 1e-3);
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn packed_bindings_match_forward() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    // A few splats sit behind the camera so the packed cull is exercised too.
+    let scene = rng_scene(300, 4.5, (-3.0, -1.0), (0.2, 0.9), 5);
+    let splats = scene_to_splats(&scene, &device).with_sh_degree(1);
+    let cam = Camera::new(
+        glam::vec3(0.3, -0.2, -4.0),
+        glam::Quat::IDENTITY,
+        0.8,
+        0.6,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    // Not a multiple of the tile size, so edge tiles are partially covered.
+    let img_size = glam::uvec2(70, 45);
+
+    let render = async |pass: RasterPass| {
+        let output = <Dispatch as SplatOps>::render(
+            &cam,
+            img_size,
+            splats.transforms.val().into_dispatch(),
+            splats.sh_coeffs.val().into_dispatch(),
+            splats.raw_opacities.val().into_dispatch(),
+            SplatRenderMode::Default,
+            Vec3::new(0.1, 0.2, 0.3),
+            pass,
+        )
+        .await;
+        output.validate_counts();
+        let counts = (output.aux.num_visible, output.aux.num_intersections);
+        let img: Tensor<3> = Tensor::from_dispatch(output.out_img);
+        let pixels = wrap_wgpu_int::<3>(unwrap_wgpu_float(img))
+            .into_data_async()
+            .await
+            .expect("readback")
+            .into_vec::<u32>()
+            .expect("data vec");
+        (counts, pixels)
+    };
+
+    let (counts, forward) = render(RasterPass::Forward).await;
+    let (packed_counts, packed) = render(RasterPass::ForwardPacked).await;
+    assert!(counts.0 > 0, "Nothing visible to compare");
+    assert_eq!(
+        counts, packed_counts,
+        "Packed project pass culled differently"
+    );
+    assert_eq!(forward, packed, "Packed kernels rendered a different image");
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn count_intersections_matches_render() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    let scene = rng_scene(300, 4.5, (-3.0, -1.0), (0.2, 0.9), 7);
+    let splats = scene_to_splats(&scene, &device);
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -4.0),
+        glam::Quat::IDENTITY,
+        0.8,
+        0.6,
+        glam::vec2(0.5, 0.5),
+        CameraModel::Pinhole,
+    );
+    let img_size = glam::uvec2(64, 48);
+
+    let output = <Dispatch as SplatOps>::render(
+        &cam,
+        img_size,
+        splats.transforms.val().into_dispatch(),
+        splats.sh_coeffs.val().into_dispatch(),
+        splats.raw_opacities.val().into_dispatch(),
+        SplatRenderMode::Default,
+        Vec3::ZERO,
+        RasterPass::Forward,
+    )
+    .await;
+    output.validate_counts();
+    let counted = splats.count_intersections(&cam, img_size).await;
+    assert!(counted > 0, "Nothing intersected to compare");
+    assert_eq!(
+        counted, output.aux.num_intersections,
+        "Counting intersections disagrees with the render"
+    );
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn filter_crops_and_thresholds_splats() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    // A 5x5 grid of splats in the z = 0 plane, one unit apart.
+    let means: Vec<[f32; 3]> = (0..25)
+        .map(|i| [(i % 5) as f32 - 2.0, (i / 5) as f32 - 2.0, 0.0])
+        .collect();
+    let raw_opacities: Vec<f32> = (0..25)
+        .map(|i| if i % 2 == 0 { 2.0 } else { -4.0 })
+        .collect();
+    let splats = Splats::from_raw(
+        means.concat(),
+        [glam::Quat::IDENTITY.to_array(); 25].concat(),
+        vec![0.1f32.ln(); 25 * 3],
+        vec![0.5; 25 * 3],
+        raw_opacities,
+        SplatRenderMode::Default,
+        &device,
+    );
+
+    let crop = BoundingBox::from_min_max(glam::vec3(-1.5, -0.5, -1.0), glam::vec3(1.5, 2.5, 1.0));
+    let cropped = splats
+        .filter(&SplatFilter {
+            crop: Some(crop),
+            ..Default::default()
+        })
+        .await;
+    // x in {-1, 0, 1} and y in {0, 1, 2}.
+    assert_eq!(cropped.num_splats(), 9);
+    let kept = cropped
+        .means()
+        .into_data_async()
+        .await
+        .expect("readback")
+        .into_vec::<f32>()
+        .expect("data vec");
+    for mean in kept.chunks(3) {
+        let mean = glam::Vec3::from_slice(mean);
+        assert!(mean.cmpge(crop.min()).all() && mean.cmple(crop.max()).all());
+    }
+
+    // Half the splats are (almost) transparent.
+    let opaque = splats
+        .filter(&SplatFilter {
+            min_opacity: Some(0.5),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(opaque.num_splats(), 13);
+
+    // Nothing is larger than the threshold, and an empty filter keeps everything.
+    let small = splats
+        .filter(&SplatFilter {
+            max_scale: Some(0.2),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(small.num_splats(), 25);
+    assert_eq!(
+        splats.filter(&SplatFilter::default()).await.num_splats(),
+        25
+    );
+}
+
+async fn read_vec<const D: usize>(tensor: Tensor<D>) -> Vec<f32> {
+    tensor
+        .into_data_async()
+        .await
+        .expect("readback")
+        .into_vec::<f32>()
+        .expect("data vec")
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn transformed_by_identity_is_noop() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    let n = 16;
+    let splats = Splats::from_tensor_data(
+        Tensor::random([n, 3], Distribution::Normal(0.0, 1.0), &device),
+        Tensor::random([n, 4], Distribution::Normal(0.0, 1.0), &device),
+        Tensor::random([n, 3], Distribution::Normal(-2.0, 0.5), &device),
+        Tensor::random([n, 16, 3], Distribution::Normal(0.0, 0.5), &device),
+        Tensor::random([n], Distribution::Normal(0.0, 1.0), &device),
+        SplatRenderMode::Default,
+    );
+    let transformed = splats.transformed(glam::Affine3A::IDENTITY);
+
+    let before = read_vec(splats.transforms.val()).await;
+    let after = read_vec(transformed.transforms.val()).await;
+    assert!(before.iter().zip(&after).all(|(a, b)| (a - b).abs() < 1e-5));
+    let before = read_vec(splats.sh_coeffs.val()).await;
+    let after = read_vec(transformed.sh_coeffs.val()).await;
+    assert!(before.iter().zip(&after).all(|(a, b)| (a - b).abs() < 1e-4));
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn transformed_rotates_splats() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    // Degree 1 coefficients are stored for the `-y`, `z` and `-x` basis functions.
+    let splats = Splats::from_raw(
+        vec![1.0, 0.0, 0.0],
+        vec![1.0, 0.0, 0.0, 0.0],
+        vec![0.0, 0.0, 0.0],
+        [[0.5; 3], [1.0; 3], [2.0; 3], [3.0; 3]].concat(),
+        vec![0.0],
+        SplatRenderMode::Default,
+        &device,
+    );
+    // Turn 90 degrees about Z and double the size.
+    let transform = glam::Affine3A::from_scale_rotation_translation(
+        Vec3::splat(2.0),
+        glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        glam::vec3(0.0, 0.0, 1.0),
+    );
+    let transformed = splats.transformed(transform);
+
+    let means = read_vec(transformed.means()).await;
+    assert_approx_eq!(means[0], 0.0, 1e-5);
+    assert_approx_eq!(means[1], 2.0, 1e-5);
+    assert_approx_eq!(means[2], 1.0, 1e-5);
+    let rotation = read_vec(transformed.rotations()).await;
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    for (r, expected) in rotation.iter().zip([half, 0.0, 0.0, half]) {
+        assert_approx_eq!(*r, expected, 1e-5);
+    }
+    for s in read_vec(transformed.log_scales()).await {
+        assert_approx_eq!(s, 2.0f32.ln(), 1e-5);
+    }
+
+    // Rotating x onto y moves the x coefficient into the y slot, and the y
+    // coefficient into the x slot with its sign flipped. The DC term stays.
+    let coeffs = read_vec(transformed.sh_coeffs.val()).await;
+    for (c, expected) in coeffs.chunks(3).zip([0.5, 3.0, 2.0, -1.0]) {
+        for channel in c {
+            assert_approx_eq!(*channel, expected, 1e-4);
+        }
+    }
+}
+
+#[test]
+fn sh_rotation_preserves_colors() {
+    use crate::sh::{sh_basis, sh_coeffs_for_degree, sh_rotation_matrix};
+
+    let degree = 3;
+    let n = sh_coeffs_for_degree(degree) as usize;
+    let coeffs: Vec<f32> = (0..n).map(|i| ((i * 7 % 5) as f32 - 2.0) * 0.3).collect();
+    let rotation = glam::Quat::from_euler(glam::EulerRot::XYZ, 0.4, -1.1, 2.3);
+    let matrix = sh_rotation_matrix(degree, rotation);
+    let rotated: Vec<f32> = (0..n)
+        .map(|i| (0..n).map(|j| matrix[i * n + j] * coeffs[j]).sum())
+        .collect();
+
+    let color = |coeffs: &[f32], dir: Vec3| -> f32 {
+        sh_basis(degree, dir)
+            .iter()
+            .zip(coeffs)
+            .map(|(b, c)| b * c)
+            .sum()
+    };
+    for dir in [
+        Vec3::X,
+        Vec3::new(0.3, -0.8, 0.5).normalize(),
+        Vec3::new(-0.6, 0.1, -0.7).normalize(),
+    ] {
+        assert_approx_eq!(color(&coeffs, dir), color(&rotated, rotation * dir), 1e-4);
+    }
+}
+
+#[wasm_bindgen_test(unsupported = tokio::test)]
+async fn tile_counts_from_offsets() {
+    let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+    // 2x3 tiles, with [start, end) ranges into the sorted intersections.
+    let offsets = [[[0, 4], [4, 4], [4, 9]], [[0, 0], [9, 10], [10, 17]]];
+    let aux = crate::RenderAux {
+        num_visible: 0,
+        num_intersections: 17,
+        visible: Tensor::zeros([0], &device),
+        max_radius: Tensor::zeros([0], &device),
+        tile_offsets: Tensor::from_ints(offsets, &device),
+        img_size: glam::uvec2(48, 32),
+    };
+    let counts = aux.tile_counts();
+    assert_eq!(counts.dims(), [2, 3]);
+    let counts = counts
+        .into_data_async()
+        .await
+        .expect("readback")
+        .convert::<i32>()
+        .into_vec::<i32>()
+        .expect("data vec");
+    assert_eq!(counts, vec![4, 0, 5, 0, 1, 7]);
+}
+
 // ---------- Shared helpers for the stress / invariance tests ----------
 
 // Pull pixels off device and assert no NaNs/infs.
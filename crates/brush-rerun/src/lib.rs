@@ -36,6 +36,9 @@ pub struct RerunConfig {
         value_parser = clap::value_parser!(u32).range(1..)
     )]
     pub rerun_log_distribution_every: u32,
+    /// Log a per-tile splat count heatmap alongside each eval render.
+    #[arg(long, help_heading = "Rerun options", default_value = "false")]
+    pub rerun_log_tile_counts: bool,
     /// The maximum size of images from the dataset logged to rerun.
     #[arg(long, help_heading = "Rerun options", default_value = "512")]
     pub rerun_max_img_size: u32,
@@ -357,6 +357,7 @@ mod visualize_tools_impl {
             index: u32,
             eval: EvalSample,
             max_img_size: u32,
+            log_tile_counts: bool,
         ) -> Result<()> {
             if !self.rec.is_enabled() {
                 return Ok(());
@@ -387,6 +388,19 @@ mod visualize_tools_impl {
                 &rerun::Image::from_rgb24(render_img.into_vec(), [rw, rh]),
             )?;
 
+            if log_tile_counts {
+                let counts = eval.render_aux.tile_counts().into_data_async().await?;
+                let shape = counts.shape.iter().map(|&d| d as u64).collect::<Vec<_>>();
+                let counts = counts.convert::<u32>().into_vec::<u32>()?;
+                self.rec.log(
+                    format!("eval/view_{index}/tile_counts"),
+                    &rerun::Tensor::new(rerun::datatypes::TensorData::new(
+                        shape,
+                        rerun::datatypes::TensorBuffer::U32(counts.into()),
+                    )),
+                )?;
+            }
+
             // GT never changes. Log it once as static per view.
             let first_gt = {
                 let mut logged = self.gt_logged.lock().expect("gt_logged poisoned");
@@ -737,6 +751,7 @@ mod visualize_tools_impl {
             _index: u32,
             _eval: EvalSample,
             _max_img_size: u32,
+            _log_tile_counts: bool,
         ) -> Result<()> {
             Ok(())
         }
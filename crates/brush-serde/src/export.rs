@@ -1,13 +1,19 @@
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
 use std::vec;
 
 use brush_render::gaussian_splats::Splats;
 use brush_render::sh::sh_coeffs_for_degree;
-use burn::tensor::Transaction;
+use burn::tensor::{Transaction, s};
 use glam::Vec3;
 use serde::ser::SerializeStruct;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_ply::{SerializeError, SerializeOptions};
 use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::import::AppearanceMetadata;
 
 #[derive(Debug, Error)]
 pub enum ExportError {
@@ -17,26 +23,106 @@ pub enum ExportError {
     DataConversion,
     #[error("PLY serialization failed: {0}")]
     Serialize(#[from] SerializeError),
+    #[error("Failed to compress splat data: {0}")]
+    Compress(#[from] std::io::Error),
+    #[error("Failed to write splat data: {0}")]
+    Write(std::io::Error),
+    #[error("safetensors serialization failed: {0}")]
+    SafeTensors(#[from] safetensors::SafeTensorError),
+    #[error("Splat position {0} is too far from the origin to store in an spz file")]
+    SpzPositionRange(f32),
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown export format '{0}', expected one of ply, ply-ascii, spz, splat or safetensors")]
+pub struct UnknownExportFormat(String);
+
+/// File format to export splats as.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    /// Uncompressed binary PLY, as written by the reference 3DGS implementation.
+    #[default]
+    Ply,
+    /// Plain text PLY with the same properties, for external tools and diffing. Several
+    /// times larger than the binary PLY.
+    PlyAscii,
+    /// Niantic's gzipped & quantized layout. Roughly 10x smaller than a PLY.
+    Spz,
+    /// antimatter15's packed 32 bytes per splat layout. Drops all view dependent color.
+    Splat,
+    /// The raw splat tensors, for loading into Python training code. See [`crate::safetensor`].
+    #[serde(rename = "safetensors")]
+    SafeTensors,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 5] = [
+        Self::Ply,
+        Self::PlyAscii,
+        Self::Spz,
+        Self::Splat,
+        Self::SafeTensors,
+    ];
+
+    /// Name of the format on the command line, the same as in config files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ply => "ply",
+            Self::PlyAscii => "ply-ascii",
+            Self::Spz => "spz",
+            Self::Splat => "splat",
+            Self::SafeTensors => "safetensors",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ply | Self::PlyAscii => "ply",
+            Self::Spz => "spz",
+            Self::Splat => "splat",
+            Self::SafeTensors => "safetensors",
+        }
+    }
+
+    /// The format matching the extension of `path`, if it's one we can export. A `.ply`
+    /// path is always binary.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL.into_iter().find(|f| f.extension() == ext)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = UnknownExportFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownExportFormat(s.to_owned()))
+    }
 }
 
 // Dynamic PLY structure that only includes needed SH coefficients
 #[derive(Debug)]
-struct DynamicPlyGaussian {
-    x: f32,
-    y: f32,
-    z: f32,
-    scale_0: f32,
-    scale_1: f32,
-    scale_2: f32,
-    opacity: f32,
-    rot_0: f32,
-    rot_1: f32,
-    rot_2: f32,
-    rot_3: f32,
-    f_dc_0: f32,
-    f_dc_1: f32,
-    f_dc_2: f32,
-    rest_coeffs: Vec<f32>,
+pub(crate) struct DynamicPlyGaussian {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) z: f32,
+    pub(crate) scale_0: f32,
+    pub(crate) scale_1: f32,
+    pub(crate) scale_2: f32,
+    pub(crate) opacity: f32,
+    pub(crate) rot_0: f32,
+    pub(crate) rot_1: f32,
+    pub(crate) rot_2: f32,
+    pub(crate) rot_3: f32,
+    pub(crate) f_dc_0: f32,
+    pub(crate) f_dc_1: f32,
+    pub(crate) f_dc_2: f32,
+    /// Rest coefficients in channel-major order (all red, then green, then blue).
+    pub(crate) rest_coeffs: Vec<f32>,
 }
 
 impl Serialize for DynamicPlyGaussian {
@@ -76,15 +162,25 @@ impl Serialize for DynamicPlyGaussian {
 }
 
 #[derive(Serialize)]
-struct DynamicPly {
-    vertex: Vec<DynamicPlyGaussian>,
+pub(crate) struct DynamicPly {
+    pub(crate) vertex: Vec<DynamicPlyGaussian>,
+}
+
+pub(crate) async fn read_splat_data(splats: Splats) -> Result<DynamicPly, ExportError> {
+    let num_splats = splats.num_splats() as usize;
+    if num_splats == 0 {
+        return Ok(DynamicPly { vertex: vec![] });
+    }
+    read_splat_chunk(&splats, 0..num_splats).await
 }
 
-async fn read_splat_data(splats: Splats) -> Result<DynamicPly, ExportError> {
+/// Read back the splats in `range` from the GPU.
+async fn read_splat_chunk(splats: &Splats, range: Range<usize>) -> Result<DynamicPly, ExportError> {
+    let rows = || s![range.start..range.end];
     let data = Transaction::default()
-        .register(splats.transforms.val())
-        .register(splats.raw_opacities.val())
-        .register(splats.sh_coeffs.val().permute([0, 2, 1])) // Permute to inria format ([n, channel, coeffs]).
+        .register(splats.transforms.val().slice(rows()))
+        .register(splats.raw_opacities.val().slice(rows()))
+        .register(splats.sh_coeffs.val().slice(rows()).permute([0, 2, 1])) // Permute to inria format ([n, channel, coeffs]).
         .execute_async()
         .await
         .map_err(|_fetch| ExportError::FetchFailed)?;
@@ -116,9 +212,8 @@ async fn read_splat_data(splats: Splats) -> Result<DynamicPly, ExportError> {
     let coeffs_per_channel = sh_coeffs_for_degree(sh_degree) as usize;
     let rest_coeffs_per_channel = coeffs_per_channel - 1;
 
-    let vertices = (0..splats.num_splats())
+    let vertices = (0..range.len())
         .map(|i| {
-            let i = i as usize;
             // Read SH data from [coeffs, channel] format
             let sh_start = i * sh_coeffs_num * 3;
             let sh_end = (i + 1) * sh_coeffs_num * 3;
@@ -178,12 +273,60 @@ async fn read_splat_data(splats: Splats) -> Result<DynamicPly, ExportError> {
 }
 
 pub async fn splat_to_ply(splats: Splats, up_axis: Option<Vec3>) -> Result<Vec<u8>, ExportError> {
-    // Fold any 3D-filter floor into the stored scales/opacity so the ply holds
-    // ordinary derived values — the floor is never written as a separate field.
-    let splats = splats.bake_min_scale();
-    let sh_degree = splats.sh_degree();
-    let ply = read_splat_data(splats.clone()).await?;
+    splat_to_ply_with_appearance(splats, up_axis, &AppearanceMetadata::default()).await
+}
+
+/// Like [`splat_to_ply`], also recording the training appearance settings as
+/// `brush_*` header comments, see [`AppearanceMetadata`].
+pub async fn splat_to_ply_with_appearance(
+    splats: Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+) -> Result<Vec<u8>, ExportError> {
+    let mut data = vec![];
+    stream_ply(
+        splats,
+        up_axis,
+        appearance,
+        false,
+        PLY_CHUNK_SIZE,
+        &mut data,
+    )
+    .await?;
+    Ok(data)
+}
+
+/// Like [`splat_to_ply_with_appearance`], but writes a `format ascii 1.0` PLY with one
+/// line of whitespace separated values per splat.
+pub async fn splat_to_ascii_ply(
+    splats: Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+) -> Result<Vec<u8>, ExportError> {
+    let mut data = vec![];
+    stream_ply(splats, up_axis, appearance, true, PLY_CHUNK_SIZE, &mut data).await?;
+    Ok(data)
+}
 
+/// Like [`splat_to_ply_with_appearance`], but reads the splats back and writes them to
+/// `writer` a chunk at a time, so the whole file is never held in memory.
+pub async fn stream_splat_to_ply<W: AsyncWrite + Unpin>(
+    splats: Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+    writer: &mut W,
+) -> Result<(), ExportError> {
+    stream_ply(splats, up_axis, appearance, false, PLY_CHUNK_SIZE, writer).await
+}
+
+/// Number of splats read back and serialized at a time when writing a PLY.
+const PLY_CHUNK_SIZE: usize = 1 << 16;
+
+fn ply_comments(
+    splats: &Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+) -> Vec<String> {
     let render_mode_str = if splats.render_mip { "mip" } else { "default" };
 
     let mut comments = vec!["Exported from Brush".to_owned()];
@@ -192,13 +335,101 @@ pub async fn splat_to_ply(splats: Splats, up_axis: Option<Vec3>) -> Result<Vec<u
     } else {
         comments.push("Vertical axis: y".to_owned());
     }
-    comments.push(format!("SH degree: {sh_degree}"));
+    comments.push(format!("SH degree: {}", splats.sh_degree()));
     comments.push(format!("SplatRenderMode: {render_mode_str}"));
+    comments.extend(appearance.to_comments());
+    comments
+}
+
+async fn stream_ply<W: AsyncWrite + Unpin>(
+    splats: Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+    ascii: bool,
+    chunk_size: usize,
+    writer: &mut W,
+) -> Result<(), ExportError> {
+    // Fold any 3D-filter floor into the stored scales/opacity so the ply holds
+    // ordinary derived values — the floor is never written as a separate field.
+    let splats = splats.bake_min_scale();
+    let comments = ply_comments(&splats, up_axis, appearance);
+    let options = || {
+        if ascii {
+            SerializeOptions::ascii()
+        } else {
+            SerializeOptions::binary_le()
+        }
+    };
+
+    let num_splats = splats.num_splats() as usize;
+    if num_splats == 0 {
+        let ply = DynamicPly { vertex: vec![] };
+        let data = serde_ply::to_bytes(&ply, options().with_comments(comments))?;
+        writer.write_all(&data).await.map_err(ExportError::Write)?;
+        return writer.flush().await.map_err(ExportError::Write);
+    }
+
+    // serde-ply only writes whole files. Serialize each chunk as a file of its own, and
+    // stitch the bodies together under the first header, patched to the total count.
+    for start in (0..num_splats).step_by(chunk_size) {
+        let end = (start + chunk_size).min(num_splats);
+        let chunk = read_splat_chunk(&splats, start..end).await?;
+        let options = if start == 0 {
+            options().with_comments(comments.clone())
+        } else {
+            options()
+        };
+        let data = serde_ply::to_bytes(&chunk, options)?;
+
+        const HEADER_END: &[u8] = b"\nend_header\n";
+        let body_start = data
+            .windows(HEADER_END.len())
+            .position(|w| w == HEADER_END)
+            .expect("serde-ply always writes a header")
+            + HEADER_END.len();
+
+        if start == 0 {
+            // Lines start after a newline, so a comment can't match this.
+            let count_line = format!("\nelement vertex {end}\n");
+            let header = std::str::from_utf8(&data[..body_start])
+                .ok()
+                .filter(|header| header.matches(&count_line).count() == 1)
+                .map(|header| {
+                    header.replacen(&count_line, &format!("\nelement vertex {num_splats}\n"), 1)
+                })
+                .ok_or_else(|| {
+                    <SerializeError as serde::ser::Error>::custom(
+                        "Unexpected PLY header, can't patch in the vertex count",
+                    )
+                })?;
+            writer
+                .write_all(header.as_bytes())
+                .await
+                .map_err(ExportError::Write)?;
+        }
+        writer
+            .write_all(&data[body_start..])
+            .await
+            .map_err(ExportError::Write)?;
+    }
+    writer.flush().await.map_err(ExportError::Write)
+}
 
-    Ok(serde_ply::to_bytes(
-        &ply,
-        SerializeOptions::binary_le().with_comments(comments),
-    )?)
+/// Serialize splats to the given [`ExportFormat`]. The appearance settings are
+/// only stored in PLY files, the other formats have no place for them.
+pub async fn splat_export(
+    splats: Splats,
+    up_axis: Option<Vec3>,
+    appearance: &AppearanceMetadata,
+    format: ExportFormat,
+) -> Result<Vec<u8>, ExportError> {
+    match format {
+        ExportFormat::Ply => splat_to_ply_with_appearance(splats, up_axis, appearance).await,
+        ExportFormat::PlyAscii => splat_to_ascii_ply(splats, up_axis, appearance).await,
+        ExportFormat::Spz => crate::spz::splat_to_spz(splats).await,
+        ExportFormat::Splat => crate::packed_splat::splat_to_packed(splats).await,
+        ExportFormat::SafeTensors => crate::safetensor::splat_to_safetensors(splats).await,
+    }
 }
 
 #[cfg(test)]
@@ -241,6 +472,36 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_export_format_from_path() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out/export_100.ply")),
+            Some(ExportFormat::Ply)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("scene.SPZ")),
+            Some(ExportFormat::Spz)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("scene.splat")),
+            Some(ExportFormat::Splat)
+        );
+        assert_eq!(ExportFormat::from_path(Path::new("scene.obj")), None);
+        assert_eq!(ExportFormat::from_path(Path::new("scene")), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_export_format_from_str() {
+        for format in ExportFormat::ALL {
+            assert_eq!(format.name().parse::<ExportFormat>().ok(), Some(format));
+        }
+        assert_eq!(
+            "PLY-ASCII".parse::<ExportFormat>().ok(),
+            Some(ExportFormat::PlyAscii)
+        );
+        assert!("obj".parse::<ExportFormat>().is_err());
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_sh_degree_exports() {
         let _device = brush_cube::test_helpers::test_device().await;
@@ -312,6 +573,82 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_streamed_ply_matches_full_serialization() {
+        use crate::test_utils::create_test_splats_with_count;
+
+        let _device = brush_cube::test_helpers::test_device().await;
+        let splats = create_test_splats_with_count(2, 10);
+        let appearance = AppearanceMetadata::default();
+        let up_axis = Some(Vec3::NEG_Y);
+
+        // Serialize everything in one go, as export used to.
+        let baked = splats.clone().bake_min_scale();
+        let comments = ply_comments(&baked, up_axis, &appearance);
+        let ply = read_splat_data(baked).await.unwrap();
+        for ascii in [false, true] {
+            let options = if ascii {
+                SerializeOptions::ascii()
+            } else {
+                SerializeOptions::binary_le()
+            };
+            let reference = serde_ply::to_bytes(&ply, options.with_comments(comments.clone()))
+                .expect("Failed to serialize splats");
+
+            // Chunks that split the splats unevenly, evenly, and not at all.
+            for chunk_size in [1, 3, 5, 9, 10, 64] {
+                let mut streamed = vec![];
+                stream_ply(
+                    splats.clone(),
+                    up_axis,
+                    &appearance,
+                    ascii,
+                    chunk_size,
+                    &mut streamed,
+                )
+                .await
+                .unwrap();
+                assert!(
+                    streamed == reference,
+                    "Streamed PLY with chunks of {chunk_size} (ascii: {ascii}) differs"
+                );
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_ascii_roundtrip() {
+        use crate::test_utils::create_test_splats_with_count;
+
+        let _device = brush_cube::test_helpers::test_device().await;
+        let splats = create_test_splats_with_count(2, 4);
+        let appearance = AppearanceMetadata::default();
+
+        let ascii = splat_to_ascii_ply(splats.clone(), None, &appearance)
+            .await
+            .unwrap();
+        let text = String::from_utf8(ascii.clone()).expect("ASCII PLY should be valid UTF-8");
+        assert!(text.contains("format ascii 1.0"));
+        assert!(text.contains("property float f_rest_23"));
+        let header_end = text.find("end_header\n").unwrap() + "end_header\n".len();
+        assert_eq!(text[header_end..].lines().count(), 4);
+
+        let binary = splat_to_ply(splats, None).await.unwrap();
+        let from_ascii = load_splat_from_ply(Cursor::new(ascii), None)
+            .await
+            .unwrap()
+            .data;
+        let from_binary = load_splat_from_ply(Cursor::new(binary), None)
+            .await
+            .unwrap()
+            .data;
+        assert_eq!(from_ascii.means, from_binary.means);
+        assert_eq!(from_ascii.rotations, from_binary.rotations);
+        assert_eq!(from_ascii.log_scales, from_binary.log_scales);
+        assert_eq!(from_ascii.sh_coeffs, from_binary.sh_coeffs);
+        assert_eq!(from_ascii.raw_opacities, from_binary.raw_opacities);
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_export_roundtrip_multiple_splats() {
         use crate::test_utils::create_test_splats_with_count;
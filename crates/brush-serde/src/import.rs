@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::pin::pin;
 use std::time::Duration;
 
@@ -14,13 +15,152 @@ use tokio_stream::{Stream, StreamExt};
 
 use crate::ply_gaussian::{PlyGaussian, QuantSh, QuantSplat};
 
-type StreamEmitter = TryStreamEmitter<SplatMessage, DeserializeError>;
+pub(crate) type StreamEmitter = TryStreamEmitter<SplatMessage, DeserializeError>;
+
+/// Splat file formats that can be imported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImportFormat {
+    /// PLY, either the reference 3DGS layout or `SuperSplat`'s compressed PLY.
+    Ply,
+    /// Niantic's gzipped & quantized layout.
+    Spz,
+    /// antimatter15's packed 32 bytes per splat layout.
+    Splat,
+    /// mkkellogg's sectioned & quantized layout from `GaussianSplats3D`.
+    KSplat,
+    /// Raw splat tensors, see [`crate::safetensor`].
+    SafeTensors,
+}
+
+impl ImportFormat {
+    pub const ALL: [Self; 5] = [
+        Self::Ply,
+        Self::Spz,
+        Self::Splat,
+        Self::KSplat,
+        Self::SafeTensors,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ply => "ply",
+            Self::Spz => "spz",
+            Self::Splat => "splat",
+            Self::KSplat => "ksplat",
+            Self::SafeTensors => "safetensors",
+        }
+    }
+
+    /// The format matching the extension of `path`, if it's one we can import.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL.into_iter().find(|f| f.extension() == ext)
+    }
+}
 
 pub struct ParseMetadata {
     pub up_axis: Option<Vec3>,
     pub render_mode: Option<SplatRenderMode>,
     pub total_splats: u32,
     pub progress: f32,
+    /// Appearance settings of the training run, for files written by Brush.
+    pub appearance: AppearanceMetadata,
+    /// SH degree of the file, known from the header before any splats are read.
+    pub sh_degree: u32,
+    /// Number of higher order SH coefficients per splat over all three
+    /// channels, like the `f_rest_N` properties of a PLY.
+    pub rest_coeff_count: usize,
+}
+
+/// SH degree fully covered by `rest_coeff_count` higher order coefficients
+/// (over all three channels). Coefficients of an incomplete band are ignored.
+pub(crate) fn sh_degree_from_rest_coeffs(rest_coeff_count: usize) -> u32 {
+    ((rest_coeff_count / 3 + 1).isqrt() - 1) as u32
+}
+
+/// Appearance settings used while training, stored as `brush_*` PLY header
+/// comments. External viewers don't know about these, but without them a
+/// reload can't reproduce what Brush showed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AppearanceMetadata {
+    /// Background color the splats were trained against.
+    pub background: Option<Vec3>,
+    /// Learned `[3, 4]` row-major color correction of each training view.
+    pub view_corrections: Vec<[f32; 12]>,
+}
+
+impl AppearanceMetadata {
+    const BACKGROUND: &str = "brush_background";
+    const VIEW_CORRECTION: &str = "brush_view_correction";
+    // Sanity limit so a corrupt index can't allocate a huge table.
+    const MAX_VIEWS: usize = 1 << 20;
+
+    pub fn is_empty(&self) -> bool {
+        self.background.is_none() && self.view_corrections.is_empty()
+    }
+
+    /// The header comments, eg. `brush_background 0 0 0`.
+    pub fn to_comments(&self) -> Vec<String> {
+        let join = |vals: &[f32]| {
+            vals.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut comments = vec![];
+        if let Some(bg) = self.background {
+            comments.push(format!("{} {}", Self::BACKGROUND, join(&bg.to_array())));
+        }
+        for (i, correction) in self.view_corrections.iter().enumerate() {
+            comments.push(format!(
+                "{} {i} {}",
+                Self::VIEW_CORRECTION,
+                join(correction)
+            ));
+        }
+        comments
+    }
+
+    /// Parse the `brush_*` comments, ignoring anything else or malformed.
+    pub fn from_comments(comments: &[String]) -> Self {
+        let mut appearance = Self::default();
+        let mut corrections = vec![];
+        for comment in comments {
+            let mut parts = comment.split_whitespace();
+            match parts.next() {
+                Some(Self::BACKGROUND) => {
+                    let vals: Vec<f32> = parts.filter_map(|p| p.parse().ok()).collect();
+                    if let &[r, g, b] = vals.as_slice() {
+                        appearance.background = Some(Vec3::new(r, g, b));
+                    }
+                }
+                Some(Self::VIEW_CORRECTION) => {
+                    let Some(Ok(index)) = parts.next().map(str::parse::<usize>) else {
+                        continue;
+                    };
+                    let vals: Vec<f32> = parts.filter_map(|p| p.parse().ok()).collect();
+                    if index < Self::MAX_VIEWS
+                        && let Ok(correction) = <[f32; 12]>::try_from(vals)
+                    {
+                        corrections.push((index, correction));
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Views missing from the file keep an identity correction.
+        if let Some(max) = corrections.iter().map(|(i, _)| *i).max() {
+            let mut identity = [0.0; 12];
+            identity[0] = 1.0;
+            identity[5] = 1.0;
+            identity[10] = 1.0;
+            appearance.view_corrections = vec![identity; max + 1];
+            for (i, correction) in corrections {
+                appearance.view_corrections[i] = correction;
+            }
+        }
+        appearance
+    }
 }
 
 /// Raw splat data parsed from a PLY file.
@@ -101,20 +241,20 @@ enum PlyFormat {
     SuperSplatCompressed,
 }
 
-struct TimedUpdate {
+pub(crate) struct TimedUpdate {
     last_update: web_time::Instant,
     update_every: Option<web_time::Duration>,
 }
 
 impl TimedUpdate {
-    fn new(update_every: Option<web_time::Duration>) -> Self {
+    pub(crate) fn new(update_every: Option<web_time::Duration>) -> Self {
         Self {
             last_update: web_time::Instant::now(),
             update_every,
         }
     }
 
-    fn should_update(&mut self, perc_done: f32) -> bool {
+    pub(crate) fn should_update(&mut self, perc_done: f32) -> bool {
         // Don't bother updating if we're almost done
         if perc_done >= 0.95 {
             return false;
@@ -180,6 +320,79 @@ pub async fn load_splat_from_ply<T: AsyncRead + Unpin>(
     splat
 }
 
+/// Load splats from a file in any of the supported [`ImportFormat`]s.
+pub async fn load_splat<T: AsyncRead + Unpin>(
+    reader: T,
+    format: ImportFormat,
+    subsample_points: Option<u32>,
+) -> Result<SplatMessage, DeserializeError> {
+    let stream = stream_splat(reader, format, subsample_points, false);
+    let Some(splat) = pin!(stream).next().await else {
+        return Err(DeserializeError::custom(
+            "Couldn't load single splat from file",
+        ));
+    };
+    splat
+}
+
+/// Stream splats from a file in any of the supported [`ImportFormat`]s. When
+/// `streaming` is set, intermediate results are emitted while loading.
+pub fn stream_splat<T: AsyncRead + Unpin>(
+    reader: T,
+    format: ImportFormat,
+    subsample_points: Option<u32>,
+    streaming: bool,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(move |emitter| async move {
+        match format {
+            ImportFormat::Ply => {
+                let stream = stream_splat_from_ply(reader, subsample_points, streaming);
+                forward_stream(stream, &emitter).await
+            }
+            ImportFormat::Splat => {
+                let stream = crate::packed_splat::stream_splat_from_splat(
+                    reader,
+                    subsample_points,
+                    streaming,
+                );
+                forward_stream(stream, &emitter).await
+            }
+            ImportFormat::KSplat => {
+                let stream =
+                    crate::ksplat::stream_splat_from_ksplat(reader, subsample_points, streaming);
+                forward_stream(stream, &emitter).await
+            }
+            ImportFormat::Spz | ImportFormat::SafeTensors => {
+                // Neither format can be read incrementally (spz is a single gzip stream), so
+                // there's nothing to show until it's all loaded.
+                let mut message = if format == ImportFormat::Spz {
+                    crate::spz::load_splat_from_spz(reader).await?
+                } else {
+                    crate::safetensor::load_splat_from_safetensors(reader).await?
+                };
+                if let Some(every) = subsample_points.filter(|&s| s > 1) {
+                    let max_splats = message.data.num_splats() / every as usize;
+                    message.data = message.data.subsample(max_splats);
+                    message.meta.total_splats = message.data.num_splats() as u32;
+                }
+                emitter.emit(message).await;
+                Ok(())
+            }
+        }
+    })
+}
+
+async fn forward_stream(
+    stream: impl Stream<Item = Result<SplatMessage, DeserializeError>>,
+    emitter: &StreamEmitter,
+) -> Result<(), DeserializeError> {
+    let mut stream = pin!(stream);
+    while let Some(message) = stream.next().await {
+        emitter.emit(message?).await;
+    }
+    Ok(())
+}
+
 pub fn stream_splat_from_ply<T: AsyncRead + Unpin>(
     mut reader: T,
     subsample_points: Option<u32>,
@@ -237,6 +450,8 @@ pub fn stream_splat_from_ply<T: AsyncRead + Unpin>(
             })
             .next_back();
 
+        let appearance = AppearanceMetadata::from_comments(&header.comments);
+
         // Check whether there is a vertex header that has at least XYZ.
         let has_vertex = header.elem_defs.iter().any(|el| el.name == "vertex");
 
@@ -265,6 +480,7 @@ pub fn stream_splat_from_ply<T: AsyncRead + Unpin>(
                     up_axis,
                     &emitter,
                     render_mode,
+                    &appearance,
                     &mut updater,
                 )
                 .await?;
@@ -277,6 +493,7 @@ pub fn stream_splat_from_ply<T: AsyncRead + Unpin>(
                     up_axis,
                     emitter,
                     render_mode,
+                    &appearance,
                     updater,
                 )
                 .await?;
@@ -286,7 +503,7 @@ pub fn stream_splat_from_ply<T: AsyncRead + Unpin>(
     })
 }
 
-fn progress(index: usize, len: usize) -> f32 {
+pub(crate) fn progress(index: usize, len: usize) -> f32 {
     ((index + 1) as f32) / len as f32
 }
 
@@ -303,6 +520,7 @@ async fn parse_ply<T: AsyncRead + Unpin>(
     up_axis: Option<Vec3>,
     emitter: &StreamEmitter,
     render_mode: Option<SplatRenderMode>,
+    appearance: &AppearanceMetadata,
     update: &mut TimedUpdate,
 ) -> Result<(), DeserializeError> {
     let header = file
@@ -314,15 +532,25 @@ async fn parse_ply<T: AsyncRead + Unpin>(
     let total_splats = vertex.count;
     let max_splats = total_splats / subsample;
 
-    let sh_count = vertex
+    // Properties are read by name, anything else (eg. normals) is ignored.
+    // PlyGaussian has room for 72 rest coefficients, ie. up to degree 4.
+    let rest_coeff_count = vertex
         .properties
         .iter()
-        .filter(|x| {
-            x.name.starts_with("f_rest_")
-                || x.name.starts_with("f_dc_")
-                || matches!(x.name.as_str(), "r" | "g" | "b" | "red" | "green" | "blue")
-        })
-        .count();
+        .filter(|x| x.name.starts_with("f_rest_"))
+        .count()
+        .min(72);
+    let sh_degree = sh_degree_from_rest_coeffs(rest_coeff_count);
+    // Files can have both f_dc_N and rgb colors, either way that's one base color.
+    let has_color = vertex.properties.iter().any(|x| {
+        x.name.starts_with("f_dc_")
+            || matches!(x.name.as_str(), "r" | "g" | "b" | "red" | "green" | "blue")
+    });
+    let sh_count = if has_color || rest_coeff_count > 0 {
+        3 + rest_coeff_count
+    } else {
+        0
+    };
 
     let mut data = SplatData {
         means: vec_exact(max_splats * 3),
@@ -388,6 +616,9 @@ async fn parse_ply<T: AsyncRead + Unpin>(
                 up_axis,
                 progress: progress(row_index, total_splats),
                 render_mode,
+                appearance: appearance.clone(),
+                sh_degree,
+                rest_coeff_count,
             };
 
             if row_index == total_splats {
@@ -412,6 +643,7 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
     up_axis: Option<Vec3>,
     emitter: StreamEmitter,
     render_mode: Option<SplatRenderMode>,
+    appearance: &AppearanceMetadata,
     mut update: TimedUpdate,
 ) -> Result<(), DeserializeError> {
     #[derive(Default, Deserialize)]
@@ -493,6 +725,8 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
         .elem_defs
         .get(2)
         .cloned();
+    let rest_coeff_count = sh_vals.as_ref().map_or(0, |sh| sh.properties.len());
+    let sh_degree = sh_degree_from_rest_coeffs(rest_coeff_count);
 
     while let Some(element) = file.current_element()
         && element.name == "vertex"
@@ -533,6 +767,9 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
                 up_axis,
                 progress,
                 render_mode,
+                appearance: appearance.clone(),
+                sh_degree,
+                rest_coeff_count,
             };
 
             let data = SplatData {
@@ -583,6 +820,9 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
             up_axis,
             progress: 1.0,
             render_mode,
+            appearance: appearance.clone(),
+            sh_degree,
+            rest_coeff_count,
         };
         let data = SplatData {
             means,
@@ -600,7 +840,7 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::export::splat_to_ply;
+    use crate::export::{splat_to_ply, splat_to_ply_with_appearance};
     use crate::test_utils::{create_test_splats, create_test_splats_with_count};
     use brush_render::sh::sh_coeffs_for_degree;
     use std::io::Cursor;
@@ -644,6 +884,102 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sh_degree_from_header() {
+        for (rest, degree) in [(0, 0), (9, 1), (45, 3)] {
+            let mut ply = "ply\nformat ascii 1.0\nelement vertex 1\n".to_owned();
+            for name in ["x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2"]
+                .map(str::to_owned)
+                .into_iter()
+                .chain((0..rest).map(|i| format!("f_rest_{i}")))
+            {
+                ply += &format!("property float {name}\n");
+            }
+            ply += "end_header\n";
+            ply += &vec!["0"; 6 + rest].join(" ");
+            ply += "\n";
+
+            let message = load_splat_from_ply(Cursor::new(ply.into_bytes()), None)
+                .await
+                .unwrap();
+            assert_eq!(message.meta.rest_coeff_count, rest);
+            assert_eq!(message.meta.sh_degree, degree);
+        }
+
+        // Leftovers of an incomplete band don't count.
+        assert_eq!(sh_degree_from_rest_coeffs(24), 2);
+        assert_eq!(sh_degree_from_rest_coeffs(30), 2);
+        assert_eq!(sh_degree_from_rest_coeffs(72), 4);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_import_extra_properties() {
+        // Columns in an unusual order, with normals and vendor properties mixed in.
+        let ply = "ply
+format ascii 1.0
+comment Exported by some other tool
+element vertex 2
+property float nx
+property float opacity
+property float f_dc_1
+property float z
+property uchar segment
+property float rot_0
+property float scale_2
+property float x
+property float ny
+property float f_dc_0
+property float rot_3
+property float scale_0
+property float rot_1
+property float y
+property float confidence
+property float f_dc_2
+property float scale_1
+property float rot_2
+property float nz
+end_header
+0 0.5 0.2 3 7 1 -3 1 0 0.1 0.4 -1 0.2 2 0.9 0.3 -2 0.3 1
+1 1.5 1.2 6 8 2 -6 4 1 1.1 1.4 -4 1.2 5 0.8 1.3 -5 1.3 0
+";
+        let message = load_splat_from_ply(Cursor::new(ply.as_bytes().to_vec()), None)
+            .await
+            .unwrap();
+        let data = message.data;
+        assert_eq!(data.means, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(
+            data.log_scales.unwrap(),
+            vec![-1.0, -2.0, -3.0, -4.0, -5.0, -6.0]
+        );
+        assert_eq!(
+            data.rotations.unwrap(),
+            vec![1.0, 0.2, 0.3, 0.4, 2.0, 1.2, 1.3, 1.4]
+        );
+        assert_eq!(data.raw_opacities.unwrap(), vec![0.5, 1.5]);
+        assert_eq!(data.sh_coeffs.unwrap(), vec![0.1, 0.2, 0.3, 1.1, 1.2, 1.3]);
+
+        // Both f_dc_N and rgb colors are still just one base color per splat.
+        let ply = "ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+property float z
+property float f_dc_0
+property float f_dc_1
+property float f_dc_2
+property uchar red
+property uchar green
+property uchar blue
+end_header
+0 0 0 0.1 0.2 0.3 255 0 0
+";
+        let message = load_splat_from_ply(Cursor::new(ply.as_bytes().to_vec()), None)
+            .await
+            .unwrap();
+        assert_eq!(message.data.sh_coeffs.unwrap().len(), 3);
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_import_with_subsample() {
         let _device = brush_cube::test_helpers::test_device().await;
@@ -725,4 +1061,60 @@ mod tests {
         assert!((imported_up.y - custom_up.y).abs() < 1e-5);
         assert!((imported_up.z - custom_up.z).abs() < 1e-5);
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_appearance_round_trip() {
+        let _device = brush_cube::test_helpers::test_device().await;
+        let mut correction = [0.0; 12];
+        correction[0] = 1.25;
+        correction[3] = -0.125;
+        correction[5] = 0.75;
+        correction[10] = 1.0;
+        let appearance = AppearanceMetadata {
+            background: Some(Vec3::new(0.0, 0.5, 1.0)),
+            view_corrections: vec![correction; 3],
+        };
+        let ply_bytes = splat_to_ply_with_appearance(create_test_splats(1), None, &appearance)
+            .await
+            .unwrap();
+        let header = String::from_utf8_lossy(&ply_bytes[..ply_bytes.len().min(4096)]);
+        assert!(header.contains("comment brush_background 0 0.5 1"));
+
+        let imported = load_splat_from_ply(Cursor::new(ply_bytes), None)
+            .await
+            .unwrap();
+        assert_eq!(imported.meta.appearance, appearance);
+        assert_eq!(imported.data.num_splats(), 1);
+
+        // Files without the comments, like the ones other tools write.
+        let plain = splat_to_ply(create_test_splats(1), None).await.unwrap();
+        let imported = load_splat_from_ply(Cursor::new(plain), None).await.unwrap();
+        assert!(imported.meta.appearance.is_empty());
+        let fixture = load_splat_from_ply(&include_bytes!("../test_data/fixture.ply")[..], None)
+            .await
+            .unwrap();
+        assert!(fixture.meta.appearance.is_empty());
+    }
+
+    #[test]
+    fn test_appearance_comments_malformed() {
+        let comments = [
+            "brush_background 1 2",
+            "brush_view_correction x 1 0 0 0 0 1 0 0 0 0 1 0",
+            "brush_view_correction 1 1 0 0 0 0 1 0 0 0 0 1",
+            "brush_view_correction 2 2 0 0 0 0 2 0 0 0 0 2 0",
+            "Exported from Brush",
+        ]
+        .map(str::to_owned);
+        let appearance = AppearanceMetadata::from_comments(&comments);
+        assert_eq!(appearance.background, None);
+        // View 1 has a value short, views without a valid correction get the
+        // identity.
+        let identity = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let scaled = [2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0];
+        assert_eq!(
+            appearance.view_corrections,
+            vec![identity, identity, scaled]
+        );
+    }
 }
@@ -0,0 +1,407 @@
+//! mkkellogg's `.ksplat` format, see <https://github.com/mkkellogg/GaussianSplats3D>.
+//!
+//! A 4096 byte header is followed by a 1024 byte header per section, and then
+//! the data of each section. Splats in a section are grouped in spatial buckets.
+//! With compression level 0 every attribute is a full float. Levels 1 and 2
+//! store positions as 16-bit offsets from the bucket center and scales,
+//! rotations and SH coefficients as half floats (level 2 stores SH as bytes).
+//! Colors are always stored as rgba bytes.
+
+use std::time::Duration;
+
+use async_fn_stream::try_fn_stream;
+use brush_render::gaussian_splats::inverse_sigmoid;
+use brush_render::sh::rgb_to_sh;
+use glam::{Quat, Vec3};
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::Stream;
+
+use crate::import::{
+    AppearanceMetadata, ParseMetadata, SplatData, SplatMessage, TimedUpdate, progress,
+    sh_degree_from_rest_coeffs,
+};
+use crate::quant::f16_to_f32;
+
+const HEADER_SIZE: usize = 4096;
+const SECTION_HEADER_SIZE: usize = 1024;
+const DEFAULT_SCALE_RANGE: u32 = 32767;
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+fn f32_at(b: &[u8], i: usize) -> f32 {
+    f32::from_bits(u32_at(b, i))
+}
+
+fn f16_at(b: &[u8], i: usize) -> f32 {
+    f16_to_f32(u16_at(b, i))
+}
+
+fn slice(bytes: &[u8], start: usize, len: usize) -> Result<&[u8], DeserializeError> {
+    bytes
+        .get(start..start + len)
+        .ok_or_else(|| DeserializeError::custom("ksplat data is truncated"))
+}
+
+fn sh_floats_for_degree(degree: u32) -> Result<usize, DeserializeError> {
+    match degree {
+        0 => Ok(0),
+        1 => Ok(9),
+        2 => Ok(24),
+        3 => Ok(45),
+        _ => Err(DeserializeError::custom(format!(
+            "Invalid ksplat SH degree {degree}"
+        ))),
+    }
+}
+
+struct Section<'a> {
+    splat_count: usize,
+    bucket_size: usize,
+    full_bucket_count: usize,
+    partial_lengths: Vec<usize>,
+    bucket_centers: &'a [u8],
+    bucket_storage: usize,
+    half_block: f32,
+    scale_range: f32,
+    sh_floats: usize,
+    splats: &'a [u8],
+}
+
+impl Section<'_> {
+    fn bucket_of(&self, index: usize) -> usize {
+        let full = self.full_bucket_count * self.bucket_size;
+        if index < full {
+            return index / self.bucket_size;
+        }
+        let mut start = full;
+        for (i, len) in self.partial_lengths.iter().enumerate() {
+            if index < start + len {
+                return self.full_bucket_count + i;
+            }
+            start += len;
+        }
+        self.full_bucket_count + self.partial_lengths.len().saturating_sub(1)
+    }
+}
+
+struct Decoder {
+    compression: u16,
+    sh_min: f32,
+    sh_max: f32,
+}
+
+impl Decoder {
+    fn bytes_per_splat(&self, sh_floats: usize) -> usize {
+        match self.compression {
+            0 => 44 + sh_floats * 4,
+            1 => 24 + sh_floats * 2,
+            _ => 24 + sh_floats,
+        }
+    }
+
+    fn decode(&self, section: &Section<'_>, index: usize, data: &mut SplatData) {
+        let record = &section.splats[index * self.bytes_per_splat(section.sh_floats)..];
+
+        let (mean, scale, quat, rgba_at, sh_at) = if self.compression == 0 {
+            let mean = Vec3::new(f32_at(record, 0), f32_at(record, 4), f32_at(record, 8));
+            let scale = Vec3::new(f32_at(record, 12), f32_at(record, 16), f32_at(record, 20));
+            let [w, x, y, z] = [24, 28, 32, 36].map(|i| f32_at(record, i));
+            (mean, scale, Quat::from_xyzw(x, y, z, w), 40, 44)
+        } else {
+            let bucket = section.bucket_of(index);
+            let center = &section.bucket_centers[bucket * section.bucket_storage..];
+            let mean = Vec3::new(f32_at(center, 0), f32_at(center, 4), f32_at(center, 8));
+            let offset = Vec3::new(
+                u16_at(record, 0) as f32,
+                u16_at(record, 2) as f32,
+                u16_at(record, 4) as f32,
+            ) - section.scale_range;
+            let mean = mean + offset * (section.half_block / section.scale_range);
+            let scale = Vec3::new(f16_at(record, 6), f16_at(record, 8), f16_at(record, 10));
+            let [w, x, y, z] = [12, 14, 16, 18].map(|i| f16_at(record, i));
+            (mean, scale, Quat::from_xyzw(x, y, z, w), 20, 24)
+        };
+
+        data.means.extend(mean.to_array());
+        if let Some(log_scales) = &mut data.log_scales {
+            log_scales.extend(scale.to_array().map(|s| s.max(f32::MIN_POSITIVE).ln()));
+        }
+        if let Some(rotations) = &mut data.rotations {
+            let quat = quat.normalize();
+            rotations.extend([quat.w, quat.x, quat.y, quat.z]);
+        }
+
+        let byte = |i: usize| record[rgba_at + i] as f32 / 255.0;
+        if let Some(opacities) = &mut data.raw_opacities {
+            opacities.push(inverse_sigmoid(byte(3).clamp(1e-4, 1.0 - 1e-4)));
+        }
+        if let Some(sh_coeffs) = &mut data.sh_coeffs {
+            sh_coeffs.extend(rgb_to_sh(Vec3::new(byte(0), byte(1), byte(2))).to_array());
+            // Rest coefficients are stored coefficient major, like brush.
+            sh_coeffs.extend((0..section.sh_floats).map(|i| match self.compression {
+                0 => f32_at(record, sh_at + i * 4),
+                1 => f16_at(record, sh_at + i * 2),
+                _ => {
+                    let t = record[sh_at + i] as f32 / 255.0;
+                    self.sh_min + t * (self.sh_max - self.sh_min)
+                }
+            }));
+        }
+    }
+}
+
+/// Stream splats from a `.ksplat` file. When streaming, a message is emitted
+/// as sections finish decoding.
+pub fn stream_splat_from_ksplat<T: AsyncRead + Unpin>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    streaming: bool,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(|emitter| async move {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        let header = slice(&bytes, 0, HEADER_SIZE)?;
+        let (major, minor) = (header[0], header[1]);
+        if major != 0 || minor < 1 {
+            return Err(DeserializeError::custom(format!(
+                "Unsupported ksplat version {major}.{minor}"
+            )));
+        }
+        let max_section_count = u32_at(header, 4) as usize;
+        let section_count = u32_at(header, 8) as usize;
+        let total = u32_at(header, 16) as usize;
+        let compression = u16_at(header, 20);
+        if compression > 2 {
+            return Err(DeserializeError::custom(format!(
+                "Unsupported ksplat compression level {compression}"
+            )));
+        }
+        let (sh_min, sh_max) = match (f32_at(header, 36), f32_at(header, 40)) {
+            (min, max) if min < max => (min, max),
+            _ => (-1.5, 1.5),
+        };
+        let decoder = Decoder {
+            compression,
+            sh_min,
+            sh_max,
+        };
+
+        let subsample = subsample_points.unwrap_or(1) as usize;
+        let mut update = TimedUpdate::new(streaming.then(|| Duration::from_millis(500)));
+
+        let mut data = SplatData {
+            means: vec![],
+            rotations: Some(vec![]),
+            log_scales: Some(vec![]),
+            sh_coeffs: Some(vec![]),
+            raw_opacities: Some(vec![]),
+        };
+
+        let mut section_start = HEADER_SIZE + max_section_count * SECTION_HEADER_SIZE;
+        let mut row_count = 0;
+        let mut rest_coeff_count = 0;
+
+        for s in 0..section_count {
+            let header = slice(
+                &bytes,
+                HEADER_SIZE + s * SECTION_HEADER_SIZE,
+                SECTION_HEADER_SIZE,
+            )?;
+            let splat_count = u32_at(header, 0) as usize;
+            let max_splat_count = u32_at(header, 4) as usize;
+            let bucket_count = u32_at(header, 12) as usize;
+            let bucket_storage = u16_at(header, 20) as usize;
+            let partial_count = u32_at(header, 36) as usize;
+            let scale_range = match u32_at(header, 24) {
+                0 => DEFAULT_SCALE_RANGE,
+                r => r,
+            };
+            let sh_floats = sh_floats_for_degree(u16_at(header, 40) as u32)?;
+
+            let partial_bytes = slice(&bytes, section_start, partial_count * 4)?;
+            let partial_lengths: Vec<usize> = partial_bytes
+                .chunks_exact(4)
+                .map(|b| u32_at(b, 0) as usize)
+                .collect();
+            let centers_start = section_start + partial_count * 4;
+            let bucket_centers = slice(&bytes, centers_start, bucket_count * bucket_storage)?;
+            if compression > 0 && bucket_storage < 12 {
+                return Err(DeserializeError::custom(
+                    "Invalid ksplat bucket storage size",
+                ));
+            }
+
+            let splats_start = centers_start + bucket_count * bucket_storage;
+            let splats_len = decoder.bytes_per_splat(sh_floats) * max_splat_count;
+            if splat_count > max_splat_count {
+                return Err(DeserializeError::custom(
+                    "Invalid ksplat section splat count",
+                ));
+            }
+
+            // Compressed positions are relative to a bucket center, so every
+            // splat has to land in one of the stored buckets.
+            let bucket_size = u32_at(header, 8) as usize;
+            let full_bucket_count = u32_at(header, 32) as usize;
+            if compression > 0 {
+                let bucketed = partial_lengths
+                    .iter()
+                    .fold(full_bucket_count.saturating_mul(bucket_size), |sum, len| {
+                        sum.saturating_add(*len)
+                    });
+                if full_bucket_count.saturating_add(partial_count) > bucket_count
+                    || (bucket_size == 0 && full_bucket_count > 0)
+                    || bucketed < splat_count
+                {
+                    return Err(DeserializeError::custom("Invalid ksplat bucket counts"));
+                }
+            }
+
+            let section = Section {
+                splat_count,
+                bucket_size,
+                full_bucket_count,
+                partial_lengths,
+                bucket_centers,
+                bucket_storage,
+                half_block: f32_at(header, 16) / 2.0,
+                scale_range: scale_range as f32,
+                sh_floats,
+                splats: slice(&bytes, splats_start, splats_len)?,
+            };
+
+            // Sections in one file have to share an SH degree to fit in one SplatData.
+            if s > 0 && data.num_splats() > 0 {
+                let per_splat = data.sh_coeffs.as_ref().map_or(0, Vec::len) / data.num_splats();
+                if per_splat != 3 + sh_floats {
+                    return Err(DeserializeError::custom(
+                        "ksplat sections with different SH degrees aren't supported",
+                    ));
+                }
+            }
+            rest_coeff_count = sh_floats;
+
+            for i in 0..section.splat_count {
+                row_count += 1;
+                if row_count % subsample == 0 {
+                    decoder.decode(&section, i, &mut data);
+                }
+            }
+            section_start = splats_start + splats_len;
+
+            let perc = progress(row_count, total);
+            if s + 1 < section_count && update.should_update(perc) && data.num_splats() > 0 {
+                let meta = ParseMetadata {
+                    up_axis: None,
+                    render_mode: None,
+                    total_splats: (total / subsample) as u32,
+                    progress: perc,
+                    appearance: AppearanceMetadata::default(),
+                    sh_degree: sh_degree_from_rest_coeffs(rest_coeff_count),
+                    rest_coeff_count,
+                };
+                emitter
+                    .emit(SplatMessage {
+                        meta,
+                        data: data.clone(),
+                    })
+                    .await;
+            }
+            brush_async::yield_now().await;
+        }
+
+        if data.num_splats() == 0 {
+            return Err(DeserializeError::custom("Empty .ksplat file"));
+        }
+
+        let meta = ParseMetadata {
+            up_axis: None,
+            render_mode: None,
+            total_splats: data.num_splats() as u32,
+            progress: 1.0,
+            appearance: AppearanceMetadata::default(),
+            sh_degree: sh_degree_from_rest_coeffs(rest_coeff_count),
+            rest_coeff_count,
+        };
+        emitter.emit(SplatMessage { meta, data }).await;
+        Ok(())
+    })
+}
+
+pub async fn load_splat_from_ksplat<T: AsyncRead + Unpin>(
+    reader: T,
+    subsample_points: Option<u32>,
+) -> Result<SplatMessage, DeserializeError> {
+    crate::import::load_splat(
+        reader,
+        crate::import::ImportFormat::KSplat,
+        subsample_points,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_ksplat_matches_reference_ply() {
+        let ply = crate::load_splat_from_ply(&include_bytes!("../test_data/fixture.ply")[..], None)
+            .await
+            .unwrap()
+            .data;
+        let ksplat =
+            load_splat_from_ksplat(&include_bytes!("../test_data/fixture.ksplat")[..], None)
+                .await
+                .unwrap()
+                .data;
+        // 16-bit positions over a 5 unit block.
+        crate::test_utils::assert_splat_data_close(&ply, &ksplat, 1e-4);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_ksplat_errors() {
+        let bytes = include_bytes!("../test_data/fixture.ksplat");
+        let sub = load_splat_from_ksplat(&bytes[..], Some(3)).await.unwrap();
+        assert_eq!(sub.data.num_splats(), 100);
+
+        assert!(
+            load_splat_from_ksplat(&bytes[..bytes.len() - 10], None)
+                .await
+                .is_err()
+        );
+        assert!(load_splat_from_ksplat(&bytes[..100], None).await.is_err());
+
+        // Corrupt bucket counts in the first section header.
+        let section = HEADER_SIZE;
+        for (offset, value) in [(12, 0), (32, u32::MAX), (36, 1000), (8, 0)] {
+            let mut corrupt = bytes.to_vec();
+            corrupt[section + offset..section + offset + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(
+                load_splat_from_ksplat(&corrupt[..], None).await.is_err(),
+                "Bucket field at {offset} set to {value} should be rejected"
+            );
+        }
+
+        let mut bad_version = bytes.to_vec();
+        bad_version[0] = 1;
+        assert!(
+            load_splat_from_ksplat(&bad_version[..], None)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -2,15 +2,27 @@
 
 pub mod export;
 pub mod import;
+pub mod ksplat;
+pub mod packed_splat;
 pub mod ply_gaussian;
 pub mod quant;
+pub mod safetensor;
+pub mod spz;
 
 // Re-export main functionality
-pub use export::{ExportError, splat_to_ply};
+pub use export::{
+    ExportError, ExportFormat, UnknownExportFormat, splat_export, splat_to_ascii_ply, splat_to_ply,
+    splat_to_ply_with_appearance, stream_splat_to_ply,
+};
 pub use import::{
-    ParseMetadata, SplatData, SplatMessage, load_splat_from_ply, stream_splat_from_ply,
+    AppearanceMetadata, ImportFormat, ParseMetadata, SplatData, SplatMessage, load_splat,
+    load_splat_from_ply, stream_splat, stream_splat_from_ply,
 };
+pub use ksplat::{load_splat_from_ksplat, stream_splat_from_ksplat};
+pub use packed_splat::{load_splat_from_splat, stream_splat_from_splat};
 pub use ply_gaussian::PlyGaussian;
+pub use safetensor::{SplatsSafetensors, load_splat_from_safetensors, splat_to_safetensors};
+pub use spz::load_splat_from_spz;
 
 // Re-export serde-ply types for compatibility
 pub use serde_ply::DeserializeError;
@@ -19,10 +31,69 @@ pub use serde_ply::DeserializeError;
 #[allow(unused)]
 mod test_utils {
     use brush_render::gaussian_splats::{SplatRenderMode, Splats};
-    use brush_render::sh::sh_coeffs_for_degree;
+    use brush_render::sh::{sh_coeffs_for_degree, sh_to_channel};
     use burn::backend::wgpu::WgpuDevice;
     use burn::tensor::Device;
 
+    use crate::SplatData;
+    use crate::quant::sigmoid;
+
+    /// Assert two sets of splat data describe the same splats, up to the byte
+    /// quantization of colors & rotations in the packed formats. `pos_tol` is
+    /// the allowed error on the means.
+    pub fn assert_splat_data_close(reference: &SplatData, other: &SplatData, pos_tol: f32) {
+        assert_eq!(reference.num_splats(), other.num_splats());
+
+        let close = |a: &[f32], b: &[f32], tol: f32, what: &str| {
+            assert_eq!(a.len(), b.len(), "{what} length mismatch");
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                assert!((a - b).abs() <= tol, "{what} mismatch at {i}: {a} vs {b}");
+            }
+        };
+        let map = |v: &Option<Vec<f32>>, f: fn(f32) -> f32| -> Vec<f32> {
+            v.as_ref()
+                .expect("Missing field")
+                .iter()
+                .map(|&x| f(x))
+                .collect()
+        };
+
+        close(&reference.means, &other.means, pos_tol, "means");
+        // Scales may be stored as half floats.
+        close(
+            &map(&reference.log_scales, |x| x),
+            &map(&other.log_scales, |x| x),
+            1e-3,
+            "log scales",
+        );
+        close(
+            &map(&reference.sh_coeffs, sh_to_channel),
+            &map(&other.sh_coeffs, sh_to_channel),
+            1.0 / 255.0,
+            "colors",
+        );
+        close(
+            &map(&reference.raw_opacities, sigmoid),
+            &map(&other.raw_opacities, sigmoid),
+            1.0 / 255.0,
+            "opacities",
+        );
+
+        let ref_rots = map(&reference.rotations, |x| x);
+        let other_rots = map(&other.rotations, |x| x);
+        for (i, (a, b)) in ref_rots
+            .chunks_exact(4)
+            .zip(other_rots.chunks_exact(4))
+            .enumerate()
+        {
+            let norm = |q: &[f32]| q.iter().map(|c| c * c).sum::<f32>().sqrt();
+            let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+            // q and -q are the same rotation.
+            let cos = dot.abs() / (norm(a) * norm(b));
+            assert!(cos > 0.999, "rotation mismatch at {i}: {a:?} vs {b:?}");
+        }
+    }
+
     pub fn create_test_splats(sh_degree: u32) -> Splats {
         create_test_splats_with_count(sh_degree, 1)
     }
@@ -0,0 +1,243 @@
+//! antimatter15's `.splat` format, as used by <https://github.com/antimatter15/splat>.
+//!
+//! There's no header, just 32 bytes per splat: position (f32 x3), linear scale
+//! (f32 x3), rgba (u8 x4) and a normalized wxyz rotation (u8 x4). Only the
+//! base color is stored, view dependent SH coefficients are dropped.
+
+use std::time::Duration;
+
+use async_fn_stream::try_fn_stream;
+use brush_render::gaussian_splats::{Splats, inverse_sigmoid};
+use brush_render::sh::{rgb_to_sh, sh_to_rgb};
+use glam::{Quat, Vec3};
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::Stream;
+
+use crate::export::{DynamicPlyGaussian, ExportError, read_splat_data};
+use crate::import::{AppearanceMetadata, ParseMetadata, SplatData, SplatMessage, TimedUpdate};
+use crate::quant::{sigmoid, to_u8};
+
+pub(crate) const BYTES_PER_SPLAT: usize = 32;
+
+fn encode(vertices: &mut [DynamicPlyGaussian]) -> Vec<u8> {
+    // Like the reference converter, put the largest & most opaque splats first
+    // so a progressive loader shows the most important parts of the scene first.
+    let importance =
+        |v: &DynamicPlyGaussian| (v.scale_0 + v.scale_1 + v.scale_2).exp() * sigmoid(v.opacity);
+    vertices.sort_by(|a, b| importance(b).total_cmp(&importance(a)));
+
+    let mut data = Vec::with_capacity(vertices.len() * BYTES_PER_SPLAT);
+    for v in vertices.iter() {
+        for f in [
+            v.x,
+            v.y,
+            v.z,
+            v.scale_0.exp(),
+            v.scale_1.exp(),
+            v.scale_2.exp(),
+        ] {
+            data.extend(f.to_le_bytes());
+        }
+        let rgb = sh_to_rgb(Vec3::new(v.f_dc_0, v.f_dc_1, v.f_dc_2));
+        data.extend([rgb.x, rgb.y, rgb.z, sigmoid(v.opacity)].map(|c| to_u8(c * 255.0)));
+        data.extend([v.rot_0, v.rot_1, v.rot_2, v.rot_3].map(|r| to_u8(r * 128.0 + 128.0)));
+    }
+    data
+}
+
+pub async fn splat_to_packed(splats: Splats) -> Result<Vec<u8>, ExportError> {
+    let splats = splats.bake_min_scale();
+    let mut ply = read_splat_data(splats).await?;
+    Ok(encode(&mut ply.vertex))
+}
+
+/// Decode one 32 byte record, appending it to `data`.
+fn decode_record(record: &[u8], data: &mut SplatData) {
+    let float =
+        |i: usize| f32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+    let byte = |i: usize| record[i] as f32 / 255.0;
+
+    data.means.extend([float(0), float(4), float(8)]);
+    if let Some(log_scales) = &mut data.log_scales {
+        log_scales.extend([float(12), float(16), float(20)].map(|s| s.max(f32::MIN_POSITIVE).ln()));
+    }
+    if let Some(sh_coeffs) = &mut data.sh_coeffs {
+        sh_coeffs.extend(rgb_to_sh(Vec3::new(byte(24), byte(25), byte(26))).to_array());
+    }
+    if let Some(opacities) = &mut data.raw_opacities {
+        opacities.push(inverse_sigmoid(byte(27).clamp(1e-4, 1.0 - 1e-4)));
+    }
+    if let Some(rotations) = &mut data.rotations {
+        let [w, x, y, z] = [28, 29, 30, 31].map(|i| (record[i] as f32 - 128.0) / 128.0);
+        let quat = Quat::from_xyzw(x, y, z, w).normalize();
+        rotations.extend([quat.w, quat.x, quat.y, quat.z]);
+    }
+}
+
+/// Stream splats from a `.splat` file. The format has no header, so the total
+/// count isn't known upfront and intermediate messages report a progress of 0.
+pub fn stream_splat_from_splat<T: AsyncRead + Unpin>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    streaming: bool,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(|emitter| async move {
+        let subsample = subsample_points.unwrap_or(1) as usize;
+        let mut update = TimedUpdate::new(streaming.then(|| Duration::from_millis(1500)));
+
+        let mut data = SplatData {
+            means: vec![],
+            rotations: Some(vec![]),
+            log_scales: Some(vec![]),
+            sh_coeffs: Some(vec![]),
+            raw_opacities: Some(vec![]),
+        };
+
+        let mut buf = Vec::new();
+        let mut row_count = 0;
+
+        loop {
+            buf.reserve(8 * 1024 * 1024);
+            let done = reader.read_buf(&mut buf).await? == 0;
+
+            let whole = buf.len() - buf.len() % BYTES_PER_SPLAT;
+            for record in buf[..whole].chunks_exact(BYTES_PER_SPLAT) {
+                row_count += 1;
+                if row_count % subsample == 0 {
+                    decode_record(record, &mut data);
+                }
+            }
+            buf.drain(..whole);
+
+            if done {
+                break;
+            }
+
+            if update.should_update(0.0) && data.num_splats() > 0 {
+                let meta = ParseMetadata {
+                    up_axis: None,
+                    render_mode: None,
+                    total_splats: data.num_splats() as u32,
+                    progress: 0.0,
+                    appearance: AppearanceMetadata::default(),
+                    sh_degree: 0,
+                    rest_coeff_count: 0,
+                };
+                emitter
+                    .emit(SplatMessage {
+                        meta,
+                        data: data.clone(),
+                    })
+                    .await;
+            }
+            brush_async::yield_now().await;
+        }
+
+        if !buf.is_empty() {
+            return Err(DeserializeError::custom(
+                "Invalid .splat file: size is not a multiple of 32 bytes",
+            ));
+        }
+        if data.num_splats() == 0 {
+            return Err(DeserializeError::custom("Empty .splat file"));
+        }
+
+        let meta = ParseMetadata {
+            up_axis: None,
+            render_mode: None,
+            total_splats: data.num_splats() as u32,
+            progress: 1.0,
+            appearance: AppearanceMetadata::default(),
+            sh_degree: 0,
+            rest_coeff_count: 0,
+        };
+        emitter.emit(SplatMessage { meta, data }).await;
+        Ok(())
+    })
+}
+
+pub async fn load_splat_from_splat<T: AsyncRead + Unpin>(
+    reader: T,
+    subsample_points: Option<u32>,
+) -> Result<SplatMessage, DeserializeError> {
+    crate::import::load_splat(reader, crate::import::ImportFormat::Splat, subsample_points).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_splat_matches_reference_ply() {
+        let ply = crate::load_splat_from_ply(&include_bytes!("../test_data/fixture.ply")[..], None)
+            .await
+            .unwrap()
+            .data;
+        let splat = load_splat_from_splat(&include_bytes!("../test_data/fixture.splat")[..], None)
+            .await
+            .unwrap()
+            .data;
+        crate::test_utils::assert_splat_data_close(&ply, &splat, 1e-6);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_splat_streaming_and_errors() {
+        let bytes = include_bytes!("../test_data/fixture.splat");
+        let sub = load_splat_from_splat(&bytes[..], Some(2)).await.unwrap();
+        assert_eq!(sub.data.num_splats(), 150);
+
+        assert!(load_splat_from_splat(&bytes[..100], None).await.is_err());
+        assert!(load_splat_from_splat(&[][..], None).await.is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_packed_export_layout() {
+        let _device = brush_cube::test_helpers::test_device().await;
+        let splats = create_test_splats_with_count(2, 5);
+        let bytes = splat_to_packed(splats).await.unwrap();
+        assert_eq!(bytes.len(), 5 * BYTES_PER_SPLAT);
+
+        // Test splats grow with their index, so the last one sorts first.
+        let x = f32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        assert_eq!(x, 4.0);
+        // Identity rotation.
+        assert_eq!(&bytes[28..32], &[255, 128, 128, 128]);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_packed_roundtrip() {
+        let _device = brush_cube::test_helpers::test_device().await;
+        let splats = create_test_splats_with_count(1, 6);
+        let original: Vec<f32> = splats
+            .means()
+            .into_data_async()
+            .await
+            .unwrap()
+            .into_vec()
+            .unwrap();
+
+        let bytes = splat_to_packed(splats).await.unwrap();
+        let imported = load_splat_from_splat(&bytes[..], None).await.unwrap().data;
+        assert_eq!(imported.num_splats(), 6);
+
+        // Export sorts the largest splats first, which reverses the test splats.
+        let imported: Vec<f32> = imported
+            .means
+            .chunks_exact(3)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        assert_eq!(imported.len(), original.len());
+        for (i, (a, b)) in original.iter().zip(&imported).enumerate() {
+            assert!((a - b).abs() < 1e-6, "mean mismatch at {i}: {a} vs {b}");
+        }
+    }
+}
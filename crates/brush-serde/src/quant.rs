@@ -6,6 +6,29 @@ fn unpack_unorm(packed: u32, bits: u32) -> f32 {
     packed as f32 / max_value as f32
 }
 
+pub(crate) fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Rounds and clamps a float in [0, 255] to a byte.
+pub(crate) fn to_u8(x: f32) -> u8 {
+    x.round().clamp(0.0, 255.0) as u8
+}
+
+/// Decode an IEEE 754 half precision float.
+pub(crate) fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let frac = (bits & 0x3ff) as f32;
+    let mag = match exp {
+        0 => frac * 2f32.powi(-24),
+        31 if bits & 0x3ff == 0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f32.powi(exp - 15),
+    };
+    sign * mag
+}
+
 pub(crate) fn decode_vec_11_10_11(value: u32) -> glam::Vec3 {
     let first = (value >> 21) & 0x7FF; // First 11 bits
     let second = (value >> 11) & 0x3FF; // Next 10 bits
@@ -96,6 +119,17 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_f16_decode() {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 1365.0 / 4096.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
     #[wasm_bindgen_test(unsupported = test)]
     fn test_decode_quat() {
         let test_val = (512 << 20) | (512 << 10) | 512;
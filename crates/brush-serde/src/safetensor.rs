@@ -0,0 +1,361 @@
+//! Raw splat tensors in the [safetensors](https://github.com/huggingface/safetensors)
+//! format, to move splats between Brush and Python training code without any
+//! lossy conversion.
+//!
+//! Every tensor is stored as little endian `F32`, with `N` the number of splats:
+//!
+//! | name          | shape       | contents                                        |
+//! |---------------|-------------|-------------------------------------------------|
+//! | `means`       | `[N, 3]`    | positions                                       |
+//! | `log_scales`  | `[N, 3]`    | log of the scale along each axis                |
+//! | `rotation`    | `[N, 4]`    | quaternions as `w, x, y, z`                     |
+//! | `sh_coeffs`   | `[N, C, 3]` | SH coefficients, `C = (degree + 1)²` per channel |
+//! | `raw_opacity` | `[N]`       | opacities before the sigmoid                    |
+//!
+//! This matches the layout of gsplat's `sh0` and `shN` concatenated along the
+//! coefficient axis. Only `means` is required, missing tensors get the same
+//! defaults as other formats. The render mode is stored as `render_mode`
+//! (`default` or `mip`) in the header metadata.
+
+use std::collections::HashMap;
+
+use brush_render::gaussian_splats::{SplatRenderMode, Splats};
+use brush_render::sh::sh_coeffs_for_degree;
+use burn::tensor::Transaction;
+use safetensors::tensor::TensorView;
+use safetensors::{Dtype, SafeTensors};
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::export::ExportError;
+use crate::import::{AppearanceMetadata, ParseMetadata, SplatData, SplatMessage};
+
+const MEANS: &str = "means";
+const LOG_SCALES: &str = "log_scales";
+const ROTATION: &str = "rotation";
+const SH_COEFFS: &str = "sh_coeffs";
+const RAW_OPACITY: &str = "raw_opacity";
+const RENDER_MODE: &str = "render_mode";
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// Serialize `data`, with `sh_degree` giving the coefficient count of `sh_coeffs`.
+/// It can't be derived from the data when there are no splats.
+fn encode(data: &SplatData, sh_degree: u32, mip: bool) -> Result<Vec<u8>, ExportError> {
+    let n = data.num_splats();
+    let n_coeffs = sh_coeffs_for_degree(sh_degree) as usize;
+
+    let fields = [
+        (MEANS, Some(&data.means), vec![n, 3]),
+        (LOG_SCALES, data.log_scales.as_ref(), vec![n, 3]),
+        (ROTATION, data.rotations.as_ref(), vec![n, 4]),
+        (SH_COEFFS, data.sh_coeffs.as_ref(), vec![n, n_coeffs, 3]),
+        (RAW_OPACITY, data.raw_opacities.as_ref(), vec![n]),
+    ];
+    let bytes: Vec<_> = fields
+        .into_iter()
+        .filter_map(|(name, values, shape)| Some((name, to_bytes(values?), shape)))
+        .collect();
+    let views = bytes
+        .iter()
+        .map(|(name, bytes, shape)| Ok((*name, TensorView::new(Dtype::F32, shape.clone(), bytes)?)))
+        .collect::<Result<Vec<_>, safetensors::SafeTensorError>>()?;
+
+    let render_mode = if mip { "mip" } else { "default" };
+    let metadata = HashMap::from([(RENDER_MODE.to_owned(), render_mode.to_owned())]);
+    Ok(safetensors::serialize(views, Some(metadata))?)
+}
+
+/// Serialize splats to safetensors, see the [module docs](self) for the layout.
+pub async fn splat_to_safetensors(splats: Splats) -> Result<Vec<u8>, ExportError> {
+    // There's no field for the 3D-filter floor, fold it in.
+    let splats = splats.bake_min_scale();
+    let mip = splats.render_mip;
+    let sh_degree = splats.sh_degree();
+    let data = Transaction::default()
+        .register(splats.means())
+        .register(splats.rotations())
+        .register(splats.log_scales())
+        .register(splats.sh_coeffs.val())
+        .register(splats.raw_opacities.val())
+        .execute_async()
+        .await
+        .map_err(|_fetch| ExportError::FetchFailed)?;
+    let [means, rotations, log_scales, sh_coeffs, raw_opacities]: [Vec<f32>; 5] = data
+        .into_iter()
+        .map(|x| x.into_vec().map_err(|_convert| ExportError::DataConversion))
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .map_err(|_convert| ExportError::DataConversion)?;
+
+    encode(
+        &SplatData {
+            means,
+            rotations: Some(rotations),
+            log_scales: Some(log_scales),
+            sh_coeffs: Some(sh_coeffs),
+            raw_opacities: Some(raw_opacities),
+        },
+        sh_degree,
+        mip,
+    )
+}
+
+/// Read the tensor `name` if present, checking it's f32 with `n` rows of `row_len` values.
+fn read_tensor(
+    tensors: &SafeTensors,
+    name: &str,
+    n: usize,
+    row_len: usize,
+) -> Result<Option<Vec<f32>>, DeserializeError> {
+    let Ok(view) = tensors.tensor(name) else {
+        return Ok(None);
+    };
+    if view.dtype() != Dtype::F32 {
+        return Err(DeserializeError::custom(format!(
+            "safetensors tensor {name} must be F32, got {:?}",
+            view.dtype()
+        )));
+    }
+    let shape = view.shape();
+    if shape.first() != Some(&n) || shape.iter().product::<usize>() != n * row_len {
+        return Err(DeserializeError::custom(format!(
+            "safetensors tensor {name} has shape {shape:?}, expected {n} rows of {row_len} values"
+        )));
+    }
+    Ok(Some(from_bytes(view.data())))
+}
+
+fn decode(bytes: &[u8]) -> Result<SplatMessage, DeserializeError> {
+    let invalid = |e: safetensors::SafeTensorError| {
+        DeserializeError::custom(format!("Invalid safetensors file: {e}"))
+    };
+    let (_, metadata) = SafeTensors::read_metadata(bytes).map_err(invalid)?;
+    let tensors = SafeTensors::deserialize(bytes).map_err(invalid)?;
+
+    let n = tensors
+        .tensor(MEANS)
+        .map_err(|_missing| DeserializeError::custom("safetensors file has no means tensor"))?
+        .shape()
+        .first()
+        .copied()
+        .unwrap_or(0);
+    let means = read_tensor(&tensors, MEANS, n, 3)?.unwrap_or_default();
+
+    let n_coeffs = match tensors.tensor(SH_COEFFS) {
+        Ok(view) => match view.shape() {
+            [_, c, 3] => *c,
+            shape => {
+                return Err(DeserializeError::custom(format!(
+                    "safetensors tensor {SH_COEFFS} has shape {shape:?}, expected [N, C, 3]"
+                )));
+            }
+        },
+        Err(_) => 1,
+    };
+    let sh_degree = (n_coeffs.isqrt() as u32).saturating_sub(1);
+    if sh_coeffs_for_degree(sh_degree) as usize != n_coeffs || sh_degree > 4 {
+        return Err(DeserializeError::custom(format!(
+            "safetensors file has {n_coeffs} SH coefficients per channel, which isn't a full SH degree"
+        )));
+    }
+
+    let render_mode =
+        metadata
+            .metadata()
+            .as_ref()
+            .and_then(|m| match m.get(RENDER_MODE)?.as_str() {
+                "mip" => Some(SplatRenderMode::Mip),
+                "default" => Some(SplatRenderMode::Default),
+                _ => None,
+            });
+
+    Ok(SplatMessage {
+        meta: ParseMetadata {
+            up_axis: None,
+            render_mode,
+            total_splats: n as u32,
+            progress: 1.0,
+            appearance: AppearanceMetadata::default(),
+            sh_degree,
+            rest_coeff_count: (n_coeffs - 1) * 3,
+        },
+        data: SplatData {
+            means,
+            rotations: read_tensor(&tensors, ROTATION, n, 4)?,
+            log_scales: read_tensor(&tensors, LOG_SCALES, n, 3)?,
+            sh_coeffs: read_tensor(&tensors, SH_COEFFS, n, n_coeffs * 3)?,
+            raw_opacities: read_tensor(&tensors, RAW_OPACITY, n, 1)?,
+        },
+    })
+}
+
+/// Load splats from a safetensors file, see the [module docs](self) for the layout.
+pub async fn load_splat_from_safetensors<T: AsyncRead + Unpin>(
+    mut reader: T,
+) -> Result<SplatMessage, DeserializeError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).await?;
+    decode(&bytes)
+}
+
+/// Safetensors conversions on [`Splats`] itself. Splats live in brush-render, which
+/// doesn't know about file formats, so these are provided as a trait here.
+pub trait SplatsSafetensors: Sized {
+    /// Serialize to safetensors, like [`splat_to_safetensors`]. Reading the splats
+    /// back from the GPU is async, so this is too.
+    fn to_safetensors(&self) -> impl Future<Output = Result<Vec<u8>, ExportError>>;
+
+    /// Create splats on `device` from a safetensors file.
+    fn from_safetensors(
+        bytes: &[u8],
+        device: &burn::tensor::Device,
+    ) -> Result<Self, DeserializeError>;
+}
+
+impl SplatsSafetensors for Splats {
+    async fn to_safetensors(&self) -> Result<Vec<u8>, ExportError> {
+        splat_to_safetensors(self.clone()).await
+    }
+
+    fn from_safetensors(
+        bytes: &[u8],
+        device: &burn::tensor::Device,
+    ) -> Result<Self, DeserializeError> {
+        let message = decode(bytes)?;
+        if message.meta.total_splats == 0 {
+            return Err(DeserializeError::custom("safetensors file has no splats"));
+        }
+        let mode = message.meta.render_mode.unwrap_or(SplatRenderMode::Default);
+        Ok(message.data.into_splats(device, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use std::io::Cursor;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    async fn to_vec<const D: usize>(t: burn::Tensor<D>) -> Vec<f32> {
+        t.into_data_async().await.unwrap().into_vec().unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_safetensors_roundtrip() {
+        let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+        for degree in [0, 1, 3, 4] {
+            let original = create_test_splats_with_count(degree, 8);
+            let bytes = splat_to_safetensors(original.clone()).await.unwrap();
+            let message = load_splat_from_safetensors(Cursor::new(bytes))
+                .await
+                .unwrap();
+            assert_eq!(message.meta.total_splats, 8);
+            assert_eq!(message.meta.sh_degree, degree);
+            assert_eq!(message.meta.render_mode, Some(SplatRenderMode::Default));
+
+            // Nothing is quantized, so everything comes back exactly.
+            let imported = message.data.into_splats(&device, SplatRenderMode::Default);
+            assert_eq!(imported.sh_degree(), degree);
+            assert_eq!(
+                to_vec(imported.transforms.val()).await,
+                to_vec(original.transforms.val()).await
+            );
+            assert_eq!(
+                to_vec(imported.sh_coeffs.val()).await,
+                to_vec(original.sh_coeffs.val()).await
+            );
+            assert_eq!(
+                to_vec(imported.raw_opacities.val()).await,
+                to_vec(original.raw_opacities.val()).await
+            );
+
+            // The same through the methods on Splats.
+            let bytes = original.to_safetensors().await.unwrap();
+            let imported = Splats::from_safetensors(&bytes, &device).unwrap();
+            assert_eq!(imported.sh_degree(), degree);
+            assert_eq!(
+                to_vec(imported.transforms.val()).await,
+                to_vec(original.transforms.val()).await
+            );
+
+            // Without splats the SH degree still has to come back.
+            let empty = SplatData {
+                means: vec![],
+                rotations: Some(vec![]),
+                log_scales: Some(vec![]),
+                sh_coeffs: Some(vec![]),
+                raw_opacities: Some(vec![]),
+            };
+            let bytes = encode(&empty, degree, false).unwrap();
+            assert!(
+                Splats::from_safetensors(&bytes, &device).is_err(),
+                "Splats can't be empty"
+            );
+            let message = decode(&bytes).unwrap();
+            assert_eq!(message.meta.total_splats, 0);
+            assert_eq!(message.meta.sh_degree, degree);
+            assert_eq!(message.data.sh_coeffs, Some(vec![]));
+        }
+    }
+
+    #[test]
+    fn test_safetensors_large() {
+        // A million splats at SH degree 1, about 100 MB of tensor data.
+        let n = 1_000_000;
+        let data = SplatData {
+            means: (0..n * 3).map(|i| i as f32).collect(),
+            rotations: Some([1.0, 0.0, 0.0, 0.0].repeat(n)),
+            log_scales: Some(vec![-2.0; n * 3]),
+            sh_coeffs: Some((0..n * 4 * 3).map(|i| (i % 7) as f32 * 0.1).collect()),
+            raw_opacities: Some((0..n).map(|i| (i % 11) as f32).collect()),
+        };
+        let bytes = encode(&data, 1, true).unwrap();
+        let message = decode(&bytes).unwrap();
+
+        assert_eq!(message.meta.total_splats, n as u32);
+        assert_eq!(message.meta.sh_degree, 1);
+        assert_eq!(message.meta.rest_coeff_count, 9);
+        assert_eq!(message.meta.render_mode, Some(SplatRenderMode::Mip));
+        assert!(message.data.means == data.means, "means differ");
+        assert!(message.data.sh_coeffs == data.sh_coeffs, "sh coeffs differ");
+        assert!(
+            message.data.raw_opacities == data.raw_opacities,
+            "opacities differ"
+        );
+    }
+
+    #[test]
+    fn test_safetensors_errors() {
+        let tensor = |name: &str, dtype: Dtype, shape: Vec<usize>, bytes: &[u8]| {
+            let view = TensorView::new(dtype, shape, bytes).unwrap();
+            safetensors::serialize([(name.to_owned(), view)], None).unwrap()
+        };
+
+        // Only means are required, the rest gets defaults.
+        let message = decode(&tensor(MEANS, Dtype::F32, vec![2, 3], &[0; 24])).unwrap();
+        assert_eq!(message.meta.total_splats, 2);
+        assert_eq!(message.meta.sh_degree, 0);
+        assert!(message.data.rotations.is_none());
+
+        assert!(decode(&tensor(ROTATION, Dtype::F32, vec![2, 4], &[0; 32])).is_err());
+        assert!(decode(&tensor(MEANS, Dtype::F64, vec![1, 3], &[0; 24])).is_err());
+        assert!(decode(&tensor(MEANS, Dtype::F32, vec![2, 2], &[0; 16])).is_err());
+        assert!(decode(b"not safetensors").is_err());
+    }
+}
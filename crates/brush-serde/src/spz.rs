@@ -0,0 +1,414 @@
+//! Niantic's `.spz` format, see <https://github.com/nianticlabs/spz>.
+//!
+//! A gzipped stream of a 16 byte header followed by all positions, alphas,
+//! colors, scales, rotations and SH coefficients, each as one contiguous block.
+//! Positions are 24-bit fixed point, everything else is quantized to a byte.
+//! The exporter lowers the fractional bits of the positions until the furthest
+//! splat fits, scenes past about ±8 million units can't be stored.
+//!
+//! spz stores splats in a right-up-back frame, whereas Brush (like the
+//! reference PLY files) uses right-down-front. Both the exporter and importer
+//! flip the y and z axes, including the matching sign flips of the quaternion
+//! and SH coefficients, so files line up with other spz viewers.
+
+use std::io::{Read, Write};
+
+use brush_render::gaussian_splats::{SplatRenderMode, Splats, inverse_sigmoid};
+use brush_render::sh::sh_coeffs_for_degree;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::export::{DynamicPlyGaussian, ExportError, read_splat_data};
+use crate::import::{AppearanceMetadata, ParseMetadata, SplatData, SplatMessage};
+use crate::quant::{sigmoid, to_u8};
+
+const MAGIC: u32 = 0x5053_474e; // "NGSP"
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 16;
+/// Fractional bits of the 24-bit fixed point positions, lowered for scenes that
+/// don't fit at this precision.
+const FRACTIONAL_BITS: u8 = 12;
+const MAX_FIXED: f32 = ((1 << 23) - 1) as f32;
+const FLAG_ANTIALIASED: u8 = 0x1;
+const COLOR_SCALE: f32 = 0.15;
+const SH1_BITS: u32 = 5;
+const SH_REST_BITS: u32 = 4;
+/// spz has no room for degree 4 coefficients, those are dropped on export.
+const MAX_SH_DEGREE: u32 = 3;
+
+/// Sign of each rest SH basis function when flipping the y and z axes.
+const SH_FLIPS: [f32; 15] = [
+    -1.0, -1.0, 1.0, // Degree 1
+    -1.0, 1.0, 1.0, -1.0, 1.0, // Degree 2
+    -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, // Degree 3
+];
+
+fn quantize_sh(x: f32, bits: u32) -> u8 {
+    let bucket = 1 << (8 - bits);
+    let q = (x * 128.0).round() as i32 + 128;
+    let q = (q + bucket / 2) / bucket * bucket;
+    q.clamp(0, 255) as u8
+}
+
+/// Most fractional bits that still fit every position in 24 bits.
+fn fractional_bits(vertices: &[DynamicPlyGaussian]) -> Result<u8, ExportError> {
+    let max_abs = vertices
+        .iter()
+        .flat_map(|v| [v.x, v.y, v.z])
+        .map(f32::abs)
+        .fold(0.0, f32::max);
+    (0..=FRACTIONAL_BITS)
+        .rev()
+        .find(|&bits| (max_abs * (1 << bits) as f32).round() <= MAX_FIXED)
+        .ok_or(ExportError::SpzPositionRange(max_abs))
+}
+
+fn encode(
+    vertices: &[DynamicPlyGaussian],
+    sh_degree: u32,
+    mip: bool,
+) -> Result<Vec<u8>, ExportError> {
+    let sh_degree = sh_degree.min(MAX_SH_DEGREE);
+    let sh_dim = sh_coeffs_for_degree(sh_degree) as usize - 1;
+    let n = vertices.len();
+    let fractional_bits = fractional_bits(vertices)?;
+
+    let mut data = Vec::with_capacity(HEADER_SIZE + n * (9 + 1 + 3 + 3 + 3 + sh_dim * 3));
+    data.extend(MAGIC.to_le_bytes());
+    data.extend(VERSION.to_le_bytes());
+    data.extend((n as u32).to_le_bytes());
+    data.extend([
+        sh_degree as u8,
+        fractional_bits,
+        if mip { FLAG_ANTIALIASED } else { 0 },
+        0,
+    ]);
+
+    let fixed_scale = (1 << fractional_bits) as f32;
+    for v in vertices {
+        for p in [v.x, -v.y, -v.z] {
+            let fixed = (p * fixed_scale).round() as i32;
+            data.extend(&fixed.to_le_bytes()[..3]);
+        }
+    }
+    data.extend(vertices.iter().map(|v| to_u8(sigmoid(v.opacity) * 255.0)));
+    for v in vertices {
+        for dc in [v.f_dc_0, v.f_dc_1, v.f_dc_2] {
+            data.push(to_u8(dc * COLOR_SCALE * 255.0 + 0.5 * 255.0));
+        }
+    }
+    for v in vertices {
+        for s in [v.scale_0, v.scale_1, v.scale_2] {
+            data.push(to_u8((s + 10.0) * 16.0));
+        }
+    }
+    for v in vertices {
+        // Flip y & z, and keep w positive so it can be reconstructed from xyz.
+        let (w, x, y, z) = (v.rot_0, v.rot_1, -v.rot_2, -v.rot_3);
+        let sign = if w < 0.0 { -1.0 } else { 1.0 };
+        for c in [x, y, z] {
+            data.push(to_u8(c * sign * 127.5 + 127.5));
+        }
+    }
+    for v in vertices {
+        let rest_per_channel = v.rest_coeffs.len() / 3;
+        for (j, flip) in SH_FLIPS.iter().enumerate().take(sh_dim) {
+            let bits = if j < 3 { SH1_BITS } else { SH_REST_BITS };
+            for c in 0..3 {
+                data.push(quantize_sh(
+                    v.rest_coeffs[c * rest_per_channel + j] * flip,
+                    bits,
+                ));
+            }
+        }
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data)?;
+    Ok(encoder.finish()?)
+}
+
+pub async fn splat_to_spz(splats: Splats) -> Result<Vec<u8>, ExportError> {
+    // spz has no field for the 3D-filter floor either, fold it in.
+    let splats = splats.bake_min_scale();
+    let sh_degree = splats.sh_degree();
+    let mip = splats.render_mip;
+    let ply = read_splat_data(splats).await?;
+    encode(&ply.vertex, sh_degree, mip)
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], DeserializeError> {
+    if data.len() < len {
+        return Err(DeserializeError::custom("spz data is truncated"));
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn unpack_quat_smallest_three(r: &[u8]) -> [f32; 4] {
+    const MASK: u32 = (1 << 9) - 1;
+    let mut comp = u32::from_le_bytes([r[0], r[1], r[2], r[3]]);
+    let largest = (comp >> 30) as usize;
+    // Components in x, y, z, w order.
+    let mut quat = [0.0; 4];
+    let mut sum_squares = 0.0;
+    for i in (0..4).rev() {
+        if i != largest {
+            let mag = comp & MASK;
+            let negative = (comp >> 9) & 0x1 == 1;
+            comp >>= 10;
+            let val = std::f32::consts::FRAC_1_SQRT_2 * mag as f32 / MASK as f32;
+            quat[i] = if negative { -val } else { val };
+            sum_squares += quat[i] * quat[i];
+        }
+    }
+    quat[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+    quat
+}
+
+fn decode(bytes: &[u8]) -> Result<SplatMessage, DeserializeError> {
+    let mut data = bytes;
+    let header = take(&mut data, HEADER_SIZE)?;
+    let word =
+        |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+
+    if word(0) != MAGIC {
+        return Err(DeserializeError::custom("Not an spz file"));
+    }
+    let version = word(4);
+    if !(2..=3).contains(&version) {
+        return Err(DeserializeError::custom(format!(
+            "Unsupported spz version {version}"
+        )));
+    }
+    let n = word(8) as usize;
+    let sh_degree = header[12] as u32;
+    if sh_degree > MAX_SH_DEGREE {
+        return Err(DeserializeError::custom(format!(
+            "Invalid spz SH degree {sh_degree}"
+        )));
+    }
+    let fractional_bits = header[13];
+    if fractional_bits > 24 {
+        return Err(DeserializeError::custom(format!(
+            "Invalid spz fractional bits {fractional_bits}"
+        )));
+    }
+    let fixed_scale = (1u32 << fractional_bits) as f32;
+    let render_mode = if header[14] & FLAG_ANTIALIASED != 0 {
+        SplatRenderMode::Mip
+    } else {
+        SplatRenderMode::Default
+    };
+    let sh_dim = sh_coeffs_for_degree(sh_degree) as usize - 1;
+
+    let positions = take(&mut data, n * 9)?;
+    let alphas = take(&mut data, n)?;
+    let colors = take(&mut data, n * 3)?;
+    let scales = take(&mut data, n * 3)?;
+    let rot_size = if version >= 3 { 4 } else { 3 };
+    let rotations = take(&mut data, n * rot_size)?;
+    let sh = take(&mut data, n * sh_dim * 3)?;
+
+    let means = positions
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, b)| {
+            // Sign extend the 24 bit value.
+            let fixed = i32::from_le_bytes([b[0], b[1], b[2], 0]) << 8 >> 8;
+            let flip = if i % 3 == 0 { 1.0 } else { -1.0 };
+            fixed as f32 / fixed_scale * flip
+        })
+        .collect();
+    let raw_opacities = alphas
+        .iter()
+        .map(|&a| inverse_sigmoid((a as f32 / 255.0).clamp(1e-4, 1.0 - 1e-4)))
+        .collect();
+    let log_scales = scales.iter().map(|&s| s as f32 / 16.0 - 10.0).collect();
+    let rotations = rotations
+        .chunks_exact(rot_size)
+        .flat_map(|r| {
+            let [x, y, z, w] = if version >= 3 {
+                unpack_quat_smallest_three(r)
+            } else {
+                let [x, y, z] = [r[0], r[1], r[2]].map(|c| c as f32 / 127.5 - 1.0);
+                let w = (1.0 - (x * x + y * y + z * z)).max(0.0).sqrt();
+                [x, y, z, w]
+            };
+            [w, x, -y, -z]
+        })
+        .collect();
+
+    let coeffs_per_splat = (sh_dim + 1) * 3;
+    let mut sh_coeffs = Vec::with_capacity(n * coeffs_per_splat);
+    for i in 0..n {
+        sh_coeffs.extend(
+            colors[i * 3..(i + 1) * 3]
+                .iter()
+                .map(|&c| (c as f32 / 255.0 - 0.5) / COLOR_SCALE),
+        );
+        let rest = &sh[i * sh_dim * 3..(i + 1) * sh_dim * 3];
+        for (j, c) in rest.iter().enumerate() {
+            sh_coeffs.push((*c as f32 - 128.0) / 128.0 * SH_FLIPS[j / 3]);
+        }
+    }
+
+    Ok(SplatMessage {
+        meta: ParseMetadata {
+            up_axis: None,
+            render_mode: Some(render_mode),
+            total_splats: n as u32,
+            progress: 1.0,
+            appearance: AppearanceMetadata::default(),
+            sh_degree,
+            rest_coeff_count: sh_dim * 3,
+        },
+        data: SplatData {
+            means,
+            rotations: Some(rotations),
+            log_scales: Some(log_scales),
+            sh_coeffs: Some(sh_coeffs),
+            raw_opacities: Some(raw_opacities),
+        },
+    })
+}
+
+/// Load splats from a (gzipped) spz file.
+pub async fn load_splat_from_spz<T: AsyncRead + Unpin>(
+    mut reader: T,
+) -> Result<SplatMessage, DeserializeError> {
+    let mut compressed = vec![];
+    reader.read_to_end(&mut compressed).await?;
+    let mut bytes = vec![];
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut bytes)
+        .map_err(|e| DeserializeError::custom(format!("Failed to decompress spz: {e}")))?;
+    decode(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use std::io::Cursor;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    async fn to_vec<const D: usize>(t: burn::Tensor<D>) -> Vec<f32> {
+        t.into_data_async().await.unwrap().into_vec().unwrap()
+    }
+
+    fn assert_close(a: &[f32], b: &[f32], tol: f32, what: &str) {
+        assert_eq!(a.len(), b.len(), "{what} length mismatch");
+        for (i, (a, b)) in a.iter().zip(b).enumerate() {
+            assert!((a - b).abs() < tol, "{what} mismatch at {i}: {a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn test_quantize_sh_buckets() {
+        assert_eq!(quantize_sh(0.0, 8), 128);
+        assert_eq!(quantize_sh(10.0, 8), 255);
+        assert_eq!(quantize_sh(-10.0, 8), 0);
+        // 4 bits -> buckets of 16.
+        assert_eq!(quantize_sh(0.1, SH_REST_BITS) % 16, 0);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_spz_roundtrip() {
+        let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+
+        // spz only covers SH coefficients in [-1, 1], which the test splats stay within up to degree 2.
+        for degree in [0, 1, 2] {
+            let original = create_test_splats_with_count(degree, 8);
+            let bytes = splat_to_spz(original.clone()).await.unwrap();
+            let message = load_splat_from_spz(Cursor::new(bytes)).await.unwrap();
+            assert_eq!(message.meta.total_splats, 8);
+
+            let imported = message.data.into_splats(&device, SplatRenderMode::Default);
+            assert_eq!(imported.sh_degree(), degree);
+
+            // 12 fractional bits -> ~2.4e-4 position precision.
+            assert_close(
+                &to_vec(imported.means()).await,
+                &to_vec(original.means()).await,
+                1e-3,
+                "means",
+            );
+            assert_close(
+                &to_vec(imported.opacities()).await,
+                &to_vec(original.opacities()).await,
+                1.0 / 255.0,
+                "opacities",
+            );
+            // Scales are stored in steps of 1/16 in log space.
+            assert_close(
+                &to_vec(imported.log_scales()).await,
+                &to_vec(original.log_scales()).await,
+                1.0 / 32.0 + 1e-4,
+                "log scales",
+            );
+            // Rest coefficients get 4 bits, so allow for half a bucket of error.
+            assert_close(
+                &to_vec(imported.sh_coeffs.val()).await,
+                &to_vec(original.sh_coeffs.val()).await,
+                0.07,
+                "sh coeffs",
+            );
+        }
+    }
+
+    #[test]
+    fn test_spz_header_errors() {
+        let header = |sh_degree: u8, fractional_bits: u8| {
+            [
+                &MAGIC.to_le_bytes()[..],
+                &VERSION.to_le_bytes(),
+                &0u32.to_le_bytes(),
+                &[sh_degree, fractional_bits, 0, 0],
+            ]
+            .concat()
+        };
+        assert!(decode(&header(0, FRACTIONAL_BITS)).is_ok());
+        assert!(decode(&header(4, FRACTIONAL_BITS)).is_err());
+        assert!(decode(&header(0, 32)).is_err());
+        assert!(decode(&header(0, 255)).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_spz_far_positions() {
+        let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+        let splats = |means: Vec<f32>| {
+            let n = means.len() / 3;
+            Splats::from_raw(
+                means,
+                [1.0, 0.0, 0.0, 0.0].repeat(n),
+                vec![-2.0; n * 3],
+                vec![0.5; n * 3],
+                vec![1.0; n],
+                SplatRenderMode::Default,
+                &device,
+            )
+        };
+
+        // Past the +-2048 that fits at 12 fractional bits, so this needs fewer.
+        let means = vec![0.25, -1.5, 3.0, 5000.0, -3000.5, 0.125];
+        let bytes = splat_to_spz(splats(means.clone())).await.unwrap();
+        let message = load_splat_from_spz(Cursor::new(bytes)).await.unwrap();
+        // 5000 fits with 10 fractional bits.
+        assert_close(&message.data.means, &means, 1.0 / 1024.0, "means");
+
+        let err = splat_to_spz(splats(vec![0.0, 1e7, 0.0])).await;
+        assert!(
+            matches!(err, Err(ExportError::SpzPositionRange(_))),
+            "Positions past 24 bits can't be stored"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+"""Generate small splat fixtures holding the same content in several formats.
+
+Writes `fixture.ply` (the reference), `fixture.splat` (antimatter15 layout) and
+`fixture.ksplat` (mkkellogg layout, compression level 1). Only uses the
+standard library, run with `python generate_fixtures.py`.
+"""
+
+import math
+import random
+import struct
+
+COUNT = 300
+SH_C0 = 0.28209479177387814
+
+random.seed(1234)
+
+
+def sigmoid(x):
+    return 1.0 / (1.0 + math.exp(-x))
+
+
+def f32(x):
+    return struct.unpack("<f", struct.pack("<f", x))[0]
+
+
+splats = []
+for _ in range(COUNT):
+    q = [random.gauss(0, 1) for _ in range(4)]
+    n = math.sqrt(sum(c * c for c in q))
+    splats.append(
+        {
+            "pos": [f32(random.uniform(-2.0, 2.0)) for _ in range(3)],
+            "log_scale": [f32(random.uniform(-5.0, -1.0)) for _ in range(3)],
+            "rot": [f32(c / n) for c in q],
+            "dc": [f32(random.uniform(-1.5, 1.5)) for _ in range(3)],
+            "opacity": f32(random.uniform(-3.0, 3.0)),
+        }
+    )
+
+
+def to_u8(x):
+    return max(0, min(255, round(x)))
+
+
+def write_ply():
+    props = ["x", "y", "z", "scale_0", "scale_1", "scale_2", "opacity"]
+    props += ["rot_0", "rot_1", "rot_2", "rot_3", "f_dc_0", "f_dc_1", "f_dc_2"]
+    header = "ply\nformat binary_little_endian 1.0\n"
+    header += f"element vertex {COUNT}\n"
+    header += "".join(f"property float {p}\n" for p in props)
+    header += "end_header\n"
+    body = b""
+    for s in splats:
+        vals = s["pos"] + s["log_scale"] + [s["opacity"]] + s["rot"] + s["dc"]
+        body += struct.pack("<14f", *vals)
+    with open("fixture.ply", "wb") as f:
+        f.write(header.encode() + body)
+
+
+def rgba(s):
+    rgb = [(0.5 + SH_C0 * c) * 255.0 for c in s["dc"]]
+    return bytes(to_u8(c) for c in rgb + [sigmoid(s["opacity"]) * 255.0])
+
+
+def write_splat():
+    body = b""
+    for s in splats:
+        scale = [math.exp(c) for c in s["log_scale"]]
+        body += struct.pack("<6f", *(s["pos"] + scale))
+        body += rgba(s)
+        body += bytes(to_u8(c * 128.0 + 128.0) for c in s["rot"])
+    with open("fixture.splat", "wb") as f:
+        f.write(body)
+
+
+def write_ksplat():
+    header_size = 4096
+    section_header_size = 1024
+    bucket_size = 256
+    block_size = 5.0
+    scale_range = 32767
+
+    full_buckets = COUNT // bucket_size
+    partial = COUNT - full_buckets * bucket_size
+    partial_lengths = [partial] if partial > 0 else []
+    bucket_count = full_buckets + len(partial_lengths)
+
+    # Center each bucket on the mean of its splats.
+    centers = []
+    for b in range(bucket_count):
+        members = splats[b * bucket_size : (b + 1) * bucket_size]
+        centers.append(
+            [f32(sum(s["pos"][i] for s in members) / len(members)) for i in range(3)]
+        )
+
+    header = bytearray(header_size)
+    header[0] = 0  # Version major
+    header[1] = 1  # Version minor
+    struct.pack_into("<IIII", header, 4, 1, 1, COUNT, COUNT)
+    struct.pack_into("<H", header, 20, 1)  # Compression level
+    struct.pack_into("<3f", header, 24, 0.0, 0.0, 0.0)  # Scene center
+
+    section = bytearray(section_header_size)
+    struct.pack_into("<IIII", section, 0, COUNT, COUNT, bucket_size, bucket_count)
+    struct.pack_into("<f", section, 16, block_size)
+    struct.pack_into("<H", section, 20, 12)  # Bytes per bucket
+    struct.pack_into("<I", section, 24, scale_range)
+    struct.pack_into("<II", section, 32, full_buckets, len(partial_lengths))
+    struct.pack_into("<H", section, 40, 0)  # SH degree
+
+    data = b"".join(struct.pack("<I", n) for n in partial_lengths)
+    data += b"".join(struct.pack("<3f", *c) for c in centers)
+
+    half_block = block_size / 2.0
+    for i, s in enumerate(splats):
+        center = centers[i // bucket_size]
+        for c in range(3):
+            rel = (s["pos"][c] - center[c]) / half_block
+            data += struct.pack("<H", max(0, min(65535, round(rel * scale_range + scale_range))))
+        data += struct.pack("<3e", *(math.exp(c) for c in s["log_scale"]))
+        data += struct.pack("<4e", *s["rot"])
+        data += rgba(s)
+
+    with open("fixture.ksplat", "wb") as f:
+        f.write(bytes(header) + bytes(section) + data)
+
+
+write_ply()
+write_splat()
+write_ksplat()
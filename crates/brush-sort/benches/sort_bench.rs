@@ -13,7 +13,7 @@
 use std::sync::Arc;
 
 use brush_cube::CubeTensor;
-use brush_sort::radix_argsort;
+use brush_sort::{SortOrder, radix_argsort, radix_sort_keys};
 use burn::backend::wgpu::WgpuDevice;
 use burn::tensor::{DType, Shape};
 use burn_cubecl::cubecl::Runtime;
@@ -92,17 +92,24 @@ fn upload_u32(device: &WgpuDevice, data: &[u32]) -> CubeTensor<WgpuRuntime> {
 fn run_sort(device: &WgpuDevice, inputs: &(Vec<u32>, Vec<u32>), bits: u32) {
     let keys = upload_u32(device, &inputs.0);
     let values = upload_u32(device, &inputs.1);
-    let (sorted_keys, sorted_values) = radix_argsort(keys, values, bits);
+    let (sorted_keys, sorted_values) = radix_argsort(keys, values, bits, SortOrder::Ascending);
     // Force completion: read both buffers back so the GPU finishes before we
     // return from the bencher closure.
     let client = WgpuRuntime::<AutoCompiler>::client(device);
     let _ = block_on(client.read_async(vec![sorted_keys.handle, sorted_values.handle]));
 }
 
+fn run_sort_keys(device: &WgpuDevice, inputs: &(Vec<u32>, Vec<u32>), bits: u32) {
+    let keys = upload_u32(device, &inputs.0);
+    let sorted_keys = radix_sort_keys(keys, bits, SortOrder::Ascending);
+    let client = WgpuRuntime::<AutoCompiler>::client(device);
+    let _ = block_on(client.read_async(vec![sorted_keys.handle]));
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[divan::bench_group(max_time = 4)]
 mod sort_bench {
-    use crate::{KeyKind, SIZES, device, make_inputs, run_sort};
+    use crate::{KeyKind, SIZES, device, make_inputs, run_sort, run_sort_keys};
 
     #[divan::bench(args = SIZES)]
     fn radix_argsort_10bit(bencher: divan::Bencher, size: usize) {
@@ -117,4 +124,11 @@ mod sort_bench {
         let inputs = make_inputs(size, KeyKind::Random32);
         bencher.bench_local(move || run_sort(&dev, &inputs, 32));
     }
+
+    #[divan::bench(args = SIZES)]
+    fn radix_sort_keys_32bit(bencher: divan::Bencher, size: usize) {
+        let dev = device();
+        let inputs = make_inputs(size, KeyKind::Random32);
+        bencher.bench_local(move || run_sort_keys(&dev, &inputs, 32));
+    }
 }
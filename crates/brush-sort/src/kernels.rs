@@ -25,12 +25,25 @@ fn div_ceil(a: u32, b: u32) -> u32 {
     (a + b - 1u32) / b
 }
 
+/// The 4 bit digit of `key` sorted on this pass. Descending sorts use the
+/// complemented digit, so the largest keys end up in the first bins.
+#[cube]
+fn key_digit(key: u32, shift: u32, #[comptime] descending: bool) -> u32 {
+    let digit = (key >> shift) & 0xfu32;
+    if comptime![descending] {
+        0xfu32 - digit
+    } else {
+        digit
+    }
+}
+
 #[cube(launch)]
 pub fn sort_count_kernel(
     num_keys_arr: &Tensor<u32>,
     src: &Tensor<u32>,
     counts: &mut Tensor<u32>,
     shift: u32,
+    #[comptime] descending: bool,
 ) {
     let num_keys = num_keys_arr[0];
 
@@ -52,7 +65,7 @@ pub fn sort_count_kernel(
 
     for _ in 0u32..ELEMENTS_PER_THREAD {
         if data_index < num_keys {
-            let local_key = (src[data_index as usize] >> shift) & 0xfu32;
+            let local_key = key_digit(src[data_index as usize], shift, descending);
             Atomic::fetch_add(&histogram[local_key as usize], 1u32);
         }
         data_index += WG;
@@ -308,6 +321,8 @@ pub fn sort_scatter_kernel(
     out: &mut Tensor<u32>,
     out_values: &mut Tensor<u32>,
     shift: u32,
+    #[comptime] descending: bool,
+    #[comptime] with_values: bool,
 ) {
     let num_keys = num_keys_arr[0];
     let num_wgs = div_ceil(num_keys, BLOCK_SIZE);
@@ -321,7 +336,8 @@ pub fn sort_scatter_kernel(
     let num_subgroups = WG / PLANE_DIM;
 
     let mut lds_keys = Shared::new_slice(WG_USIZE);
-    let mut lds_values = Shared::new_slice(WG_USIZE);
+    // Key-only sorts never touch the values, keep the shared slice tiny.
+    let mut lds_values = Shared::new_slice(comptime![if with_values { WG } else { 1u32 }] as usize);
     let mut lds_scratch = Shared::new_slice(WG_USIZE);
     let mut bin_offset_cache = Shared::new_slice(WG_USIZE);
     let local_histogram = Shared::<[Atomic<u32>]>::new_slice(BIN_COUNT_USIZE);
@@ -340,17 +356,24 @@ pub fn sort_scatter_kernel(
             Atomic::store(&local_histogram[UNIT_POS as usize], 0u32);
         }
 
-        let mut local_key = 0xFFFFFFFFu32;
+        // Padding keys have to land in the last bin so they're never written.
+        let mut local_key = if comptime![descending] {
+            0u32
+        } else {
+            0xFFFFFFFFu32
+        };
         let mut local_value = 0u32;
 
         if data_index < num_keys {
             local_key = src[data_index as usize];
-            local_value = values[data_index as usize];
+            if comptime![with_values] {
+                local_value = values[data_index as usize];
+            }
         }
 
         let mut bit_shift = 0u32;
         while bit_shift < BITS_PER_PASS {
-            let key_index = (local_key >> shift) & 0xfu32;
+            let key_index = key_digit(local_key, shift, descending);
             let bit_key = (key_index >> bit_shift) & 3u32;
             let packed_input = 1u32 << (bit_key * 8u32);
 
@@ -392,15 +415,19 @@ pub fn sort_scatter_kernel(
             let key_offset = (local_sum >> (bit_key * 8u32)) & 0xffu32;
 
             lds_keys[key_offset as usize] = local_key;
-            lds_values[key_offset as usize] = local_value;
+            if comptime![with_values] {
+                lds_values[key_offset as usize] = local_value;
+            }
             sync_cube();
             local_key = lds_keys[UNIT_POS as usize];
-            local_value = lds_values[UNIT_POS as usize];
+            if comptime![with_values] {
+                local_value = lds_values[UNIT_POS as usize];
+            }
 
             bit_shift += 2u32;
         }
 
-        let key_index = (local_key >> shift) & 0xfu32;
+        let key_index = key_digit(local_key, shift, descending);
         Atomic::fetch_add(&local_histogram[key_index as usize], 1u32);
         sync_cube();
 
@@ -431,7 +458,9 @@ pub fn sort_scatter_kernel(
         let total_offset = global_offset + local_offset;
         if total_offset < num_keys {
             out[total_offset as usize] = local_key;
-            out_values[total_offset as usize] = local_value;
+            if comptime![with_values] {
+                out_values[total_offset as usize] = local_value;
+            }
         }
         if UNIT_POS < BIN_COUNT {
             bin_offset_cache[UNIT_POS as usize] +=
@@ -441,3 +470,59 @@ pub fn sort_scatter_kernel(
         data_index += WG;
     }
 }
+
+/// Map f32 bit patterns to u32 keys that sort in the same order as the floats
+/// (or back when `inverse` is set). Positive floats get their sign bit set so
+/// they sort after the negatives, negative floats get all bits flipped so larger
+/// magnitudes sort first.
+#[cube(launch)]
+pub fn flip_float_keys_kernel(
+    src: &Tensor<u32>,
+    out: &mut Tensor<u32>,
+    num_keys: u32,
+    #[comptime] inverse: bool,
+) {
+    let index = CUBE_POS as u32 * WG + UNIT_POS;
+    if index >= num_keys {
+        terminate!();
+    }
+    let bits = src[index as usize];
+    // Going back, the sign bit of the key is flipped compared to the float.
+    let negative = if comptime![inverse] {
+        bits >> 31u32 == 0u32
+    } else {
+        bits >> 31u32 == 1u32
+    };
+    out[index as usize] = bits ^ select(negative, 0xffffffffu32, 0x80000000u32);
+}
+
+/// Fill `out` with `0..num`.
+#[cube(launch)]
+pub fn iota_kernel(out: &mut Tensor<u32>, num: u32) {
+    let index = CUBE_POS as u32 * WG + UNIT_POS;
+    if index >= num {
+        terminate!();
+    }
+    out[index as usize] = index;
+}
+
+/// Strided gather, `out[i * out_stride + out_offset] = src[j * stride + offset]`
+/// where `j` is `indices[i]`.
+#[cube(launch)]
+pub fn gather_words_kernel(
+    src: &Tensor<u32>,
+    indices: &Tensor<u32>,
+    out: &mut Tensor<u32>,
+    num: u32,
+    stride: u32,
+    offset: u32,
+    out_stride: u32,
+    out_offset: u32,
+) {
+    let index = CUBE_POS as u32 * WG + UNIT_POS;
+    if index >= num {
+        terminate!();
+    }
+    let src_index = indices[index as usize] * stride + offset;
+    out[(index * out_stride + out_offset) as usize] = src[src_index as usize];
+}
@@ -12,24 +12,62 @@ use burn_wgpu::WgpuRuntime;
 
 use kernels::{BIN_COUNT, BLOCK_SIZE, WG};
 
+/// Order of the keys after sorting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    /// Largest keys first. Equal keys still keep their input order.
+    Descending,
+}
+
 /// Perform a radix argsort on the input keys and values.
+///
+/// Keys are compared as raw u32 bit patterns. Signed integers or floats only
+/// sort correctly while they're all non-negative, use [`radix_argsort_f32`]
+/// for floats that can be negative.
 pub fn radix_argsort(
     input_keys: CubeTensor<WgpuRuntime>,
     input_values: CubeTensor<WgpuRuntime>,
     sorting_bits: u32,
+    order: SortOrder,
 ) -> (CubeTensor<WgpuRuntime>, CubeTensor<WgpuRuntime>) {
     assert_eq!(
         input_keys.shape()[0],
         input_values.shape()[0],
         "Input keys and values must have the same number of elements"
     );
-    assert!(sorting_bits <= 32, "Can only sort up to 32 bits");
     assert!(
-        input_keys.is_contiguous(),
+        input_values.is_contiguous(),
         "Please ensure input keys are contiguous"
     );
+    let (keys, values) = radix_sort_impl(input_keys, Some(input_values), sorting_bits, order);
+    (keys, values.expect("Sorted values"))
+}
+
+/// Perform a radix sort on just the keys. Skips the values entirely, which
+/// saves a buffer and the memory traffic of scattering them each pass.
+pub fn radix_sort_keys(
+    input_keys: CubeTensor<WgpuRuntime>,
+    sorting_bits: u32,
+    order: SortOrder,
+) -> CubeTensor<WgpuRuntime> {
+    radix_sort_impl(input_keys, None, sorting_bits, order).0
+}
+
+fn radix_sort_impl(
+    input_keys: CubeTensor<WgpuRuntime>,
+    input_values: Option<CubeTensor<WgpuRuntime>>,
+    sorting_bits: u32,
+    order: SortOrder,
+) -> (CubeTensor<WgpuRuntime>, Option<CubeTensor<WgpuRuntime>>) {
+    assert!(sorting_bits <= 32, "Can only sort up to 32 bits");
+    debug_assert!(
+        matches!(input_keys.dtype(), DType::U32 | DType::I32 | DType::F32),
+        "Keys must be 32-bit words, sorted as u32 bit patterns"
+    );
     assert!(
-        input_values.is_contiguous(),
+        input_keys.is_contiguous(),
         "Please ensure input keys are contiguous"
     );
 
@@ -46,17 +84,34 @@ pub fn radix_argsort(
     let num_reduce_wgs_count = num_wgs_count.div_ceil(BLOCK_SIZE) * BIN_COUNT;
 
     let cube_dim = CubeDim::new_1d(WG);
+    let descending = order == SortOrder::Descending;
+    let with_values = input_values.is_some();
 
     let num_keys_buf = create_tensor_from_slice(&[max_n as i32], &device, DType::I32);
     let num_wgs = calc_cube_count_1d(max_n, BLOCK_SIZE);
     let num_reduce_wgs = calc_cube_count_1d(num_reduce_wgs_count, 1);
 
+    // Size `reduced_buf` to the real number of per-chunk totals. The
+    // sort_scan kernel walks the whole buffer in BLOCK_SIZE chunks,
+    // so we allocate `num_reduce_wgs_count` slots (rounded up to a
+    // BLOCK_SIZE boundary so the final chunk's load/store can be gated
+    // by a simple `< num_reduce_wgs` check).
+    let reduced_buf_size = num_reduce_wgs_count.div_ceil(BLOCK_SIZE).max(1) * BLOCK_SIZE;
+
+    // These are fully overwritten every pass, so can be shared by all of them.
+    let count_buf = create_tensor([(max_needed_wgs as usize) * 16], &device, DType::I32);
+    let reduced_buf = create_tensor([reduced_buf_size as usize], &device, DType::I32);
+    // The kernel still needs something bound for the values when there are none.
+    let dummy_values = create_tensor([1], &device, DType::U32);
+
     let mut cur_keys = input_keys;
     let mut cur_vals = input_values;
+    // Passes ping-pong between two buffers. The input is never written to, so
+    // the second buffer is only allocated once the first pass is done with it.
+    let mut spare_keys = None;
+    let mut spare_vals = None;
 
     for pass in 0..sorting_bits.div_ceil(4) {
-        let count_buf = create_tensor([(max_needed_wgs as usize) * 16], &device, DType::I32);
-
         kernels::sort_count_kernel::launch::<WgpuRuntime>(
             &client,
             num_wgs.clone(),
@@ -65,45 +120,44 @@ pub fn radix_argsort(
             cur_keys.clone().into_tensor_arg(),
             count_buf.clone().into_tensor_arg(),
             pass * 4,
+            descending,
+        );
+        kernels::sort_reduce_kernel::launch::<WgpuRuntime>(
+            &client,
+            num_reduce_wgs.clone(),
+            cube_dim,
+            num_keys_buf.clone().into_tensor_arg(),
+            count_buf.clone().into_tensor_arg(),
+            reduced_buf.clone().into_tensor_arg(),
+        );
+        kernels::sort_scan_kernel::launch::<WgpuRuntime>(
+            &client,
+            CubeCount::Static(1, 1, 1),
+            cube_dim,
+            num_keys_buf.clone().into_tensor_arg(),
+            reduced_buf.clone().into_tensor_arg(),
+        );
+        kernels::sort_scan_add_kernel::launch::<WgpuRuntime>(
+            &client,
+            num_reduce_wgs.clone(),
+            cube_dim,
+            num_keys_buf.clone().into_tensor_arg(),
+            reduced_buf.clone().into_tensor_arg(),
+            count_buf.clone().into_tensor_arg(),
         );
 
-        {
-            // Size `reduced_buf` to the real number of per-chunk totals. The
-            // sort_scan kernel walks the whole buffer in BLOCK_SIZE chunks,
-            // so we allocate `num_reduce_wgs_count` slots (rounded up to a
-            // BLOCK_SIZE boundary so the final chunk's load/store can be gated
-            // by a simple `< num_reduce_wgs` check).
-            let reduced_buf_size = num_reduce_wgs_count.div_ceil(BLOCK_SIZE).max(1) * BLOCK_SIZE;
-            let reduced_buf = create_tensor([reduced_buf_size as usize], &device, DType::I32);
-
-            kernels::sort_reduce_kernel::launch::<WgpuRuntime>(
-                &client,
-                num_reduce_wgs.clone(),
-                cube_dim,
-                num_keys_buf.clone().into_tensor_arg(),
-                count_buf.clone().into_tensor_arg(),
-                reduced_buf.clone().into_tensor_arg(),
-            );
-            kernels::sort_scan_kernel::launch::<WgpuRuntime>(
-                &client,
-                CubeCount::Static(1, 1, 1),
-                cube_dim,
-                num_keys_buf.clone().into_tensor_arg(),
-                reduced_buf.clone().into_tensor_arg(),
-            );
-
-            kernels::sort_scan_add_kernel::launch::<WgpuRuntime>(
-                &client,
-                num_reduce_wgs.clone(),
-                cube_dim,
-                num_keys_buf.clone().into_tensor_arg(),
-                reduced_buf.clone().into_tensor_arg(),
-                count_buf.clone().into_tensor_arg(),
-            );
-        }
-
-        let output_keys = create_tensor([max_n as usize], &device, cur_keys.dtype());
-        let output_values = create_tensor([max_n as usize], &device, cur_vals.dtype());
+        let output_keys = spare_keys
+            .take()
+            .unwrap_or_else(|| create_tensor([max_n as usize], &device, cur_keys.dtype()));
+        let output_values = cur_vals.as_ref().map(|v| {
+            spare_vals
+                .take()
+                .unwrap_or_else(|| create_tensor([max_n as usize], &device, v.dtype()))
+        });
+        let (values_arg, out_values_arg) = match (&cur_vals, &output_values) {
+            (Some(cur), Some(out)) => (cur.clone(), out.clone()),
+            _ => (dummy_values.clone(), dummy_values.clone()),
+        };
 
         kernels::sort_scatter_kernel::launch::<WgpuRuntime>(
             &client,
@@ -111,25 +165,150 @@ pub fn radix_argsort(
             cube_dim,
             num_keys_buf.clone().into_tensor_arg(),
             cur_keys.clone().into_tensor_arg(),
-            cur_vals.clone().into_tensor_arg(),
+            values_arg.into_tensor_arg(),
             count_buf.clone().into_tensor_arg(),
             output_keys.clone().into_tensor_arg(),
-            output_values.clone().into_tensor_arg(),
+            out_values_arg.into_tensor_arg(),
             pass * 4,
+            descending,
+            with_values,
         );
 
-        cur_keys = output_keys;
-        cur_vals = output_values;
+        let prev_keys = std::mem::replace(&mut cur_keys, output_keys);
+        let prev_vals = std::mem::replace(&mut cur_vals, output_values);
+        if pass > 0 {
+            spare_keys = Some(prev_keys);
+            spare_vals = prev_vals;
+        }
     }
     (cur_keys, cur_vals)
 }
 
+/// Perform a radix argsort on f32 keys. Unlike bit-casting the floats and using
+/// [`radix_argsort`], this orders negative keys correctly. Returns the sorted
+/// f32 keys and the values.
+pub fn radix_argsort_f32(
+    input_keys: CubeTensor<WgpuRuntime>,
+    input_values: CubeTensor<WgpuRuntime>,
+) -> (CubeTensor<WgpuRuntime>, CubeTensor<WgpuRuntime>) {
+    assert_eq!(input_keys.dtype(), DType::F32, "Keys must be f32");
+
+    let keys = flip_float_keys(input_keys, false, DType::I32);
+    let (keys, values) = radix_argsort(keys, input_values, 32, SortOrder::Ascending);
+    (flip_float_keys(keys, true, DType::F32), values)
+}
+
+/// Perform a radix argsort on 64-bit keys, given as a `[N, 2]` tensor of
+/// (low, high) u32 words. Sorts on the low `sorting_bits` of the combined key,
+/// first by the low word and then stably by the high word. Returns the sorted
+/// `[N, 2]` keys and the values.
+pub fn radix_argsort_u64(
+    input_keys: CubeTensor<WgpuRuntime>,
+    input_values: CubeTensor<WgpuRuntime>,
+    sorting_bits: u32,
+    order: SortOrder,
+) -> (CubeTensor<WgpuRuntime>, CubeTensor<WgpuRuntime>) {
+    assert!(sorting_bits <= 64, "Can only sort up to 64 bits");
+    assert!(
+        input_keys.shape().num_dims() == 2 && input_keys.shape()[1] == 2,
+        "64-bit keys must be [N, 2] (low, high) words"
+    );
+    assert_eq!(
+        input_keys.shape()[0],
+        input_values.shape()[0],
+        "Input keys and values must have the same number of elements"
+    );
+    assert!(
+        input_keys.is_contiguous() && input_values.is_contiguous(),
+        "Please ensure input keys and values are contiguous"
+    );
+
+    let n = input_keys.shape()[0];
+    let device = input_keys.device.clone();
+
+    // Sort the indices of the keys rather than the values, so the high words
+    // can be looked up for the second sort.
+    let indices = create_tensor([n], &device, DType::U32);
+    kernels::iota_kernel::launch::<WgpuRuntime>(
+        &input_keys.client,
+        calc_cube_count_1d(n as u32, WG),
+        CubeDim::new_1d(WG),
+        indices.clone().into_tensor_arg(),
+        n as u32,
+    );
+
+    let low = create_tensor([n], &device, DType::U32);
+    gather_words(&input_keys, &indices, &low, (2, 0), (1, 0));
+    let (_, mut perm) = radix_argsort(low, indices, sorting_bits.min(32), order);
+    if sorting_bits > 32 {
+        let high = create_tensor([n], &device, DType::U32);
+        gather_words(&input_keys, &perm, &high, (2, 1), (1, 0));
+        perm = radix_argsort(high, perm, sorting_bits - 32, order).1;
+    }
+
+    let keys = create_tensor([n, 2], &device, input_keys.dtype());
+    for word in 0..2 {
+        gather_words(&input_keys, &perm, &keys, (2, word), (2, word));
+    }
+    let values = create_tensor([n], &device, input_values.dtype());
+    gather_words(&input_values, &perm, &values, (1, 0), (1, 0));
+    (keys, values)
+}
+
+/// Gather `src[indices[i] * stride + offset]` into `out[i * out_stride + out_offset]`.
+fn gather_words(
+    src: &CubeTensor<WgpuRuntime>,
+    indices: &CubeTensor<WgpuRuntime>,
+    out: &CubeTensor<WgpuRuntime>,
+    (stride, offset): (u32, u32),
+    (out_stride, out_offset): (u32, u32),
+) {
+    let num = indices.shape()[0] as u32;
+    kernels::gather_words_kernel::launch::<WgpuRuntime>(
+        &src.client,
+        calc_cube_count_1d(num, WG),
+        CubeDim::new_1d(WG),
+        src.clone().into_tensor_arg(),
+        indices.clone().into_tensor_arg(),
+        out.clone().into_tensor_arg(),
+        num,
+        stride,
+        offset,
+        out_stride,
+        out_offset,
+    );
+}
+
+fn flip_float_keys(
+    keys: CubeTensor<WgpuRuntime>,
+    inverse: bool,
+    dtype: DType,
+) -> CubeTensor<WgpuRuntime> {
+    assert!(
+        keys.is_contiguous(),
+        "Please ensure input keys are contiguous"
+    );
+    let num_keys = keys.shape()[0] as u32;
+    let out = create_tensor([num_keys as usize], &keys.device, dtype);
+
+    kernels::flip_float_keys_kernel::launch::<WgpuRuntime>(
+        &keys.client,
+        calc_cube_count_1d(num_keys, WG),
+        CubeDim::new_1d(WG),
+        keys.clone().into_tensor_arg(),
+        out.clone().into_tensor_arg(),
+        num_keys,
+        inverse,
+    );
+    out
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::radix_argsort;
+    use crate::{SortOrder, radix_argsort, radix_argsort_f32, radix_argsort_u64, radix_sort_keys};
     use brush_cube::{MainBackendBase, create_tensor_from_slice};
-    use burn::backend::ops::IntTensorOps;
-    use burn::tensor::DType;
+    use burn::backend::ops::{FloatTensorOps, IntTensorOps};
+    use burn::tensor::{DType, Shape};
     use burn_wgpu::{CubeTensor, WgpuRuntime};
     use rand::RngExt;
     use wasm_bindgen_test::wasm_bindgen_test;
@@ -177,7 +356,7 @@ mod tests {
 
             let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
             let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
-            let (ret_keys, ret_values) = radix_argsort(keys, values, 32);
+            let (ret_keys, ret_values) = radix_argsort(keys, values, 32, SortOrder::Ascending);
 
             let ret_keys = read_i32(ret_keys).await;
             let ret_values = read_i32(ret_values).await;
@@ -199,6 +378,132 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sorting_descending() {
+        // Unique keys so the order matches a reversed argsort exactly, spread
+        // over more than one block and all 32 bits.
+        let mut rng = rand::rng();
+        let mut keys_inp: Vec<u32> = (0..5000u32).map(|i| i * 858_993).collect();
+        for i in (1..keys_inp.len()).rev() {
+            keys_inp.swap(i, rng.random_range(0..=i));
+        }
+        let values_inp: Vec<u32> = (0..keys_inp.len() as u32).collect();
+
+        let device = brush_cube::test_helpers::test_device().await;
+        let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
+        let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
+        let (ret_keys, ret_values) = radix_argsort(keys, values, 32, SortOrder::Descending);
+
+        let ret_keys = read_i32(ret_keys).await;
+        let ret_values = read_i32(ret_values).await;
+
+        let inds: Vec<usize> = argsort(&keys_inp).into_iter().rev().collect();
+        for ((key, val), ind) in ret_keys.iter().zip(&ret_values).zip(inds) {
+            assert_eq!(*key as u32, keys_inp[ind]);
+            assert_eq!(*val as u32, values_inp[ind]);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sorting_keys_only() {
+        let mut rng = rand::rng();
+        let keys_inp: Vec<u32> = (0..50_000).map(|_| rng.random_range(0..1 << 20)).collect();
+
+        let device = brush_cube::test_helpers::test_device().await;
+        for order in [SortOrder::Ascending, SortOrder::Descending] {
+            let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
+            let ret_keys = read_i32(radix_sort_keys(keys, 20, order)).await;
+
+            let mut ref_keys = keys_inp.clone();
+            ref_keys.sort_unstable();
+            if order == SortOrder::Descending {
+                ref_keys.reverse();
+            }
+            let ret_keys: Vec<u32> = ret_keys.iter().map(|&k| k as u32).collect();
+            assert_eq!(ret_keys, ref_keys);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sorting_u64() {
+        // Composite (tile, depth) style keys, with few distinct high words so
+        // the low words decide the order within each.
+        let mut rng = rand::rng();
+        let keys_inp: Vec<u64> = (0..20_000)
+            .map(|_| (rng.random_range(0..1000u64) << 32) | rng.random::<u32>() as u64)
+            .collect();
+        let words: Vec<u32> = keys_inp
+            .iter()
+            .flat_map(|&k| [k as u32, (k >> 32) as u32])
+            .collect();
+        let values_inp: Vec<u32> = (0..keys_inp.len() as u32).collect();
+
+        let device = brush_cube::test_helpers::test_device().await;
+        for order in [SortOrder::Ascending, SortOrder::Descending] {
+            let keys = create_tensor_from_slice(&words, &device, DType::I32);
+            let keys = MainBackendBase::int_reshape(keys, Shape::new([keys_inp.len(), 2]));
+            let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
+            let (ret_keys, ret_values) = radix_argsort_u64(keys, values, 42, order);
+
+            let ret_keys = read_i32(ret_keys).await;
+            let ret_values = read_i32(ret_values).await;
+
+            let mut inds: Vec<usize> = (0..keys_inp.len()).collect();
+            if order == SortOrder::Descending {
+                inds.sort_by_key(|&i| std::cmp::Reverse(keys_inp[i]));
+            } else {
+                inds.sort_by_key(|&i| keys_inp[i]);
+            }
+            for (i, ind) in inds.into_iter().enumerate() {
+                let key =
+                    ret_keys[2 * i] as u32 as u64 | ((ret_keys[2 * i + 1] as u32 as u64) << 32);
+                assert_eq!(key, keys_inp[ind]);
+                assert_eq!(ret_values[i] as u32, values_inp[ind]);
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_sorting_f32() {
+        let mut rng = rand::rng();
+        let mut keys_inp: Vec<f32> = (0..5000)
+            .map(|_| rng.random_range(-1000.0..1000.0))
+            .collect();
+        keys_inp.extend([
+            0.0,
+            -0.0,
+            f32::MIN_POSITIVE,
+            -f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::MIN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            1e-20,
+            -1e-20,
+        ]);
+        let values_inp: Vec<i32> = (0..keys_inp.len() as i32).collect();
+
+        let device = brush_cube::test_helpers::test_device().await;
+        let keys = create_tensor_from_slice(&keys_inp, &device, DType::F32);
+        let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
+        let (ret_keys, ret_values) = radix_argsort_f32(keys, values);
+
+        let ret_keys = MainBackendBase::float_into_data(ret_keys)
+            .await
+            .expect("readback")
+            .to_vec::<f32>()
+            .expect("Wrong type");
+        let ret_values = read_i32(ret_values).await;
+
+        let mut ref_inds: Vec<usize> = (0..keys_inp.len()).collect();
+        ref_inds.sort_by(|&a, &b| keys_inp[a].total_cmp(&keys_inp[b]));
+
+        for ((key, val), ref_ind) in ret_keys.iter().zip(&ret_values).zip(ref_inds) {
+            assert_eq!(key.to_bits(), keys_inp[ref_ind].to_bits());
+            assert_eq!(keys_inp[*val as usize].to_bits(), key.to_bits());
+        }
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_sorting_big() {
         // Simulate some data as one might find for a bunch of gaussians.
@@ -220,7 +525,7 @@ mod tests {
         let device = brush_cube::test_helpers::test_device().await;
         let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
         let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
-        let (ret_keys, ret_values) = radix_argsort(keys, values, 32);
+        let (ret_keys, ret_values) = radix_argsort(keys, values, 32, SortOrder::Ascending);
 
         let ret_keys = read_i32(ret_keys).await;
         let ret_values = read_i32(ret_values).await;
@@ -256,7 +561,7 @@ mod tests {
         let device = brush_cube::test_helpers::test_device().await;
         let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
         let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
-        let (ret_keys, ret_values) = radix_argsort(keys, values, 32);
+        let (ret_keys, ret_values) = radix_argsort(keys, values, 32, SortOrder::Ascending);
 
         let ret_keys_slice = read_i32(ret_keys).await;
         let ret_values_slice = read_i32(ret_values).await;
@@ -316,7 +621,7 @@ mod tests {
         let device = brush_cube::test_helpers::test_device().await;
         let keys = create_tensor_from_slice(&keys_inp, &device, DType::I32);
         let values = create_tensor_from_slice(&values_inp, &device, DType::I32);
-        let (ret_keys, ret_values) = radix_argsort(keys, values, 32);
+        let (ret_keys, ret_values) = radix_argsort(keys, values, 32, SortOrder::Ascending);
 
         let ret_keys_slice = read_i32(ret_keys).await;
         let ret_values_slice = read_i32(ret_values).await;
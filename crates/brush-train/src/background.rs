@@ -0,0 +1,50 @@
+//! Background handling during training, see [`crate::config::TrainConfig::background`].
+//!
+//! Training against a fixed background lets the splats "explain" the
+//! background color, eg. a black background behind a white sky ends up as dense
+//! white splats. A random background each step, or a learned one, avoids this.
+
+use burn::{
+    module::{Module, Param, ParamId},
+    tensor::{Device, Tensor, s},
+};
+use clap::ValueEnum;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackgroundMode {
+    /// Use `background-color`, with `background-noise-strength` noise added each step.
+    #[default]
+    Color,
+    /// Black, with `background-noise-strength` noise added each step.
+    Black,
+    /// White, with `background-noise-strength` noise added each step.
+    White,
+    /// A new uniformly random color each step.
+    Random,
+    /// A single color optimized along with the splats, starting at `background-color`.
+    Learned,
+}
+
+/// The learned background color, as a module so it can be optimized.
+#[derive(Module, Debug)]
+pub(crate) struct LearnedBackground {
+    pub color: Param<Tensor<1>>,
+}
+
+pub(crate) fn background_param(color: Vec3, device: &Device) -> Param<Tensor<1>> {
+    Param::initialized(
+        ParamId::new(),
+        Tensor::from_floats(color.to_array(), device),
+    )
+}
+
+/// Composite an `[H, W, 4]` render made on a black background over a `[3]` background color.
+pub(crate) fn composite_background(img: Tensor<3>, background: Tensor<1>) -> Tensor<3> {
+    let rgb = img.clone().slice(s![.., .., 0..3]);
+    let alpha = img.clone().slice(s![.., .., 3..4]);
+    let rgb = rgb + (1.0 - alpha) * background.reshape([1, 1, 3]);
+    Tensor::cat(vec![rgb, img.slice(s![.., .., 3..])], 2)
+}
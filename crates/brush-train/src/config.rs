@@ -1,7 +1,44 @@
+use brush_loss::SsimWindow;
 use brush_render::gaussian_splats::SplatRenderMode;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use serde::{Deserialize, Serialize};
 
+use crate::background::BackgroundMode;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SsimMode {
+    /// SSIM at the full resolution.
+    #[default]
+    Ssim,
+    /// SSIM at the full, half and quarter resolution, combined with the
+    /// standard MS-SSIM weights. Less sensitive to fine detail than plain SSIM.
+    MsSsim,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptimizerKind {
+    /// Adam, with the learning rates scaled per parameter.
+    #[default]
+    Adam,
+    /// SGD with momentum, with the same learning rate scaling. Plain gradients are
+    /// much smaller than Adam's normalized steps, so this needs larger learning rates.
+    Sgd,
+}
+
+fn parse_ssim_window_size(s: &str) -> Result<u32, String> {
+    let size: u32 = s.parse().map_err(|e| format!("{e}"))?;
+    if size % 2 == 1 && (3..=SsimWindow::MAX_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "must be an odd number between 3 and {}",
+            SsimWindow::MAX_SIZE
+        ))
+    }
+}
+
 #[derive(Clone, Parser, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct TrainConfig {
@@ -12,6 +49,15 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options")]
     pub render_mode: Option<SplatRenderMode>,
 
+    /// Optimizer for the splat parameters. The exposure, pose & background
+    /// corrections always use Adam.
+    #[arg(long, help_heading = "Training options", default_value = "adam")]
+    pub optimizer: OptimizerKind,
+
+    /// Momentum of the SGD optimizer.
+    #[arg(long, help_heading = "Training options", default_value = "0.9")]
+    pub sgd_momentum: f32,
+
     /// Start learning rate for the mean parameters.
     #[arg(long, help_heading = "Training options", default_value = "2e-5")]
     pub lr_mean: f64,
@@ -20,6 +66,17 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "2e-7")]
     pub lr_mean_end: f64,
 
+    /// Ramp the mean and scale learning rates up linearly from zero over this
+    /// many steps, before the mean decay starts.
+    #[arg(long, help_heading = "Training options", default_value = "0")]
+    pub warmup_steps: u32,
+
+    /// Experimental: scale the mean learning rate of each splat by its
+    /// screen-space gradient since the last refine, relative to the average
+    /// splat, so splats in poorly fit regions move faster.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub adaptive_mean_lr: bool,
+
     /// How much noise to add to the mean parameters of low opacity gaussians.
     #[arg(long, help_heading = "Training options", default_value = "50.0")]
     pub mean_noise_weight: f32,
@@ -32,6 +89,15 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "10.0")]
     pub lr_coeffs_sh_scale: f32,
 
+    /// Start out rendering only the base color, and enable one more SH degree every
+    /// this many steps. Unset trains all SH degrees from the start.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub sh_warmup_every: Option<u32>,
+
     /// Learning rate for the opacity parameter.
     #[arg(long, help_heading = "Training options", default_value = "0.012")]
     pub lr_opac: f64,
@@ -44,6 +110,31 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "2e-3")]
     pub lr_rotation: f64,
 
+    /// Number of views to average the loss over for each optimizer step. The
+    /// views are rendered one by one, but memory use still grows with the
+    /// batch size as all views are kept around for the backward pass.
+    #[arg(long, help_heading = "Training options", default_value = "1")]
+    pub batch_size: u32,
+
+    /// Learn a per-view affine color correction to compensate for exposure
+    /// and white balance changes between images. The correction is only used
+    /// during training and isn't part of the exported splats.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub learn_exposure: bool,
+
+    /// Learning rate for the per-view color correction.
+    #[arg(long, help_heading = "Training options", default_value = "1e-3")]
+    pub lr_exposure: f64,
+
+    /// Refine the camera pose of each training view along with the splats, to
+    /// correct slightly off poses from eg. COLMAP.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub pose_refine: bool,
+
+    /// Learning rate for the per-view pose corrections.
+    #[arg(long, help_heading = "Training options", default_value = "1e-4")]
+    pub lr_pose: f64,
+
     /// Max nr. of splats. This is only an upper bound, the actual final number of splats is NOT determined by this.
     #[arg(long, help_heading = "Refine options", default_value = "10000000")]
     pub max_splats: u32,
@@ -58,6 +149,15 @@ pub struct TrainConfig {
     )]
     pub refine_every: u32,
 
+    /// Iteration to start refining at. Refines before this are skipped, to let the
+    /// initial splats settle before densifying.
+    #[arg(long, help_heading = "Refine options", default_value = "0")]
+    pub refine_start_iter: u32,
+
+    /// Iteration after which refining stops. Defaults to 95% of total-train-iters.
+    #[arg(long, help_heading = "Refine options")]
+    pub refine_stop_iter: Option<u32>,
+
     /// Threshold to control splat growth. Lower means faster growth.
     #[arg(long, help_heading = "Refine options", default_value = "0.0025")]
     pub growth_grad_threshold: f32,
@@ -77,14 +177,70 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Refine options", default_value = "0.5")]
     pub split_at_screen_size: f32,
 
+    /// How much the largest axis of both halves of a split splat shrinks. The
+    /// default 1/√2 roughly conserves the splat's volume. Clamped to 0.25..=0.9,
+    /// as barely shrinking leaves two overlapping copies of the splat.
+    #[arg(long, help_heading = "Refine options", default_value = "0.70710678")]
+    pub split_scale_factor: f32,
+
+    /// Keep the opacity of split splats as-is, instead of lowering it so the two
+    /// halves are about as opaque as the splat they came from.
+    #[arg(long, help_heading = "Refine options", default_value = "false")]
+    pub split_keep_opacity: bool,
+
     /// Weight of SSIM loss (compared to l1 loss)
     #[clap(long, help_heading = "Training options", default_value = "0.2")]
     pub ssim_weight: f32,
 
+    /// Weight of the L1 loss between the rendered expected depth and the depth
+    /// target of a view, for views that have one.
+    #[arg(long, help_heading = "Training options", default_value = "0.0")]
+    pub depth_loss_weight: f32,
+
+    /// Weight of a penalty on splats whose largest scale is more than
+    /// `max_scale_ratio` times their smallest. Long, thin splats show up as
+    /// spiky artifacts in novel views.
+    #[arg(long, help_heading = "Training options", default_value = "0.0")]
+    pub scale_reg_weight: f32,
+
+    /// Ratio between the largest and smallest scale of a splat above which
+    /// `scale_reg_weight` kicks in.
+    #[arg(long, help_heading = "Training options", default_value = "10.0")]
+    pub max_scale_ratio: f32,
+
+    /// Width of the Gaussian window SSIM is computed over. Must be odd, at most 11.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        default_value = "11",
+        value_parser = parse_ssim_window_size
+    )]
+    pub ssim_window_size: u32,
+
+    /// Standard deviation of the Gaussian SSIM window.
+    #[arg(long, help_heading = "Training options", default_value = "1.5")]
+    pub ssim_sigma: f32,
+
+    /// Whether to use single scale SSIM, or multi-scale SSIM over 3 scales.
+    /// Also used for the SSIM reported by evaluation.
+    #[arg(long, help_heading = "Training options", default_value = "ssim")]
+    pub ssim_mode: SsimMode,
+
     /// Factor of the opacity decay.
     #[arg(long, help_heading = "Training options", default_value = "0.004")]
     pub opac_decay: f32,
 
+    /// Reset the opacity of all splats to at most 0.01 every this many steps, as in
+    /// the original 3DGS paper, so splats that aren't needed fade out and get pruned.
+    /// Applied at the first refine on or after each multiple, while splats are still
+    /// growing (see `growth_stop_iter`). Unset disables the reset.
+    #[arg(
+        long,
+        help_heading = "Refine options",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub opacity_reset_every: Option<u32>,
+
     /// Weight of l1 loss on alpha if input view has transparency.
     #[arg(long, help_heading = "Refine options", default_value = "0.1")]
     pub match_alpha_weight: f32,
@@ -92,6 +248,10 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Refine options", default_value = "0.0")]
     pub lpips_loss_weight: f32,
 
+    /// Background used during training.
+    #[arg(long, help_heading = "Training options", default_value = "color")]
+    pub background: BackgroundMode,
+
     /// Base background color (R,G,B) used during training.
     #[arg(
         long,
@@ -107,6 +267,15 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "0.1")]
     pub background_noise_strength: f32,
 
+    /// Check the loss for NaN/Inf every this many steps, and skip the update of a step
+    /// whose loss isn't finite. Each check reads the loss back from the GPU. 0 disables it.
+    #[arg(long, help_heading = "Training options", default_value = "10")]
+    pub nan_check_every: u32,
+
+    /// Learning rate for the learned background color.
+    #[arg(long, help_heading = "Training options", default_value = "1e-2")]
+    pub lr_background: f64,
+
     /// Number of LOD levels to generate after initial training (0 = disabled).
     #[arg(long, help_heading = "LOD options", default_value = "0")]
     pub lod_levels: u32,
@@ -138,6 +307,55 @@ impl Default for TrainConfig {
 }
 
 impl TrainConfig {
+    /// The `background_color` as a vector, black if it's malformed.
+    pub fn base_background(&self) -> glam::Vec3 {
+        match self.background_color.as_slice() {
+            &[r, g, b] => glam::Vec3::new(r, g, b),
+            _ => glam::Vec3::ZERO,
+        }
+    }
+
+    /// The background at the start of training, `None` for a random background.
+    pub fn initial_background(&self) -> Option<glam::Vec3> {
+        match self.background {
+            BackgroundMode::Color | BackgroundMode::Learned => Some(self.base_background()),
+            BackgroundMode::Black => Some(glam::Vec3::ZERO),
+            BackgroundMode::White => Some(glam::Vec3::ONE),
+            BackgroundMode::Random => None,
+        }
+    }
+
+    /// The Gaussian window used for SSIM, in training and evaluation.
+    pub fn ssim_window(&self) -> SsimWindow {
+        SsimWindow {
+            size: self.ssim_window_size,
+            sigma: self.ssim_sigma,
+        }
+    }
+
+    /// How far step `iter` is into the learning rate warmup, from 0 to 1.
+    pub fn lr_warmup(&self, iter: u32) -> f64 {
+        if iter < self.warmup_steps {
+            iter as f64 / self.warmup_steps as f64
+        } else {
+            1.0
+        }
+    }
+
+    /// The last iteration of the main training phase that refines.
+    pub fn refine_stop_iter(&self) -> u32 {
+        self.refine_stop_iter
+            .unwrap_or((self.total_train_iters as f32 * 0.95) as u32)
+    }
+
+    /// Whether to refine after step `iter` of the main training phase.
+    pub fn refine_due(&self, iter: u32) -> bool {
+        iter > 0
+            && iter.is_multiple_of(self.refine_every)
+            && iter >= self.refine_start_iter
+            && iter <= self.refine_stop_iter()
+    }
+
     pub fn total_iters(&self) -> u32 {
         self.total_train_iters + self.lod_levels * self.lod_refine_steps
     }
@@ -2,66 +2,303 @@
 use std::path::Path;
 
 use anyhow::Result;
+use brush_dataset::hdr::is_hdr;
 use brush_dataset::scene::{sample_to_packed_data, view_to_sample_image};
-use brush_loss::{ImageLossConfig, image_loss_eval};
+use brush_loss::{ImageLossConfig, SsimWindow, gt_alpha, image_loss_eval, ms_ssim_eval};
 use brush_render::camera::Camera;
-use brush_render::gaussian_splats::Splats;
-use brush_render::{AlphaMode, RenderAux, TextureMode, render_splats};
-use burn::tensor::{Device, Int, Tensor, s};
+use brush_render::gaussian_splats::PreparedSplats;
+use brush_render::{AlphaMode, RenderAux, TextureMode};
+use burn::tensor::{Device, Int, Tensor, TensorData, s};
 use glam::Vec3;
 use image::DynamicImage;
+use lpips::LpipsModel;
+use serde::Serialize;
+
+use crate::config::SsimMode;
+use crate::exposure::apply_color_correction;
 
 pub struct EvalSample {
     pub gt_img: DynamicImage,
     pub rendered: Tensor<3>,
     pub psnr: Tensor<1>,
     pub ssim: Tensor<1>,
+    /// PSNR & SSIM of the render after applying the learned exposure
+    /// correction. Only set when a correction was passed to [`eval_stats`].
+    pub psnr_corrected: Option<Tensor<1>>,
+    pub ssim_corrected: Option<Tensor<1>>,
+    /// Only set when an LPIPS model was passed to [`eval_stats`].
+    pub lpips: Option<Tensor<1>>,
+    /// Time spent rendering the view, not including the metrics.
+    pub render_time: web_time::Duration,
     pub render_aux: RenderAux,
 }
 
+/// Evaluate the splats against a ground truth view. The plain PSNR & SSIM are
+/// always computed on the uncorrected render so numbers stay comparable between
+/// runs. When an `exposure` correction (see [`crate::train::SplatTrainer::mean_exposure`])
+/// is given, the metrics are additionally computed on the corrected render.
+///
+/// LPIPS needs the (large) VGG weights, so it's only computed when a model is passed in.
+///
+/// For [`AlphaMode::Masked`] views with a mask, only the unmasked pixels are scored.
+///
+/// SSIM is computed the same way as in training, see
+/// [`crate::config::TrainConfig::ssim_mode`] & [`crate::config::TrainConfig::ssim_window`].
+///
+/// The splats are passed in prepared, so evaluating many views only prepares them once.
 pub async fn eval_stats(
-    splats: Splats,
+    splats: &PreparedSplats,
     gt_cam: &Camera,
     gt_img: DynamicImage,
     alpha_mode: AlphaMode,
+    ssim_mode: SsimMode,
+    ssim_window: SsimWindow,
+    exposure: Option<Tensor<2>>,
+    lpips: Option<&LpipsModel>,
     device: &Device,
 ) -> Result<EvalSample> {
     let res = glam::uvec2(gt_img.width(), gt_img.height());
 
-    let (gt_packed_data, _has_alpha) =
-        sample_to_packed_data(view_to_sample_image(gt_img.clone(), alpha_mode));
-    let gt_packed: Tensor<2, Int> = Tensor::from_data(gt_packed_data, device);
+    let gt_sample = view_to_sample_image(gt_img.clone(), alpha_mode);
+    let hdr = is_hdr(&gt_sample);
+    let gt_rgb = lpips.is_some().then(|| gt_sample.to_rgb32f());
+    let gt = MetricsGt::new(&gt_sample, alpha_mode, device);
 
     // Render on reference black background.
-    let (img, render_aux) =
-        render_splats(splats, gt_cam, res, Vec3::ZERO, None, TextureMode::Float).await;
+    let render_start = web_time::Instant::now();
+    let (img, render_aux) = splats
+        .render(gt_cam, res, Vec3::ZERO, TextureMode::Float)
+        .await;
+    let render_time = render_start.elapsed();
     let render_rgb = img.slice(s![.., .., 0..3]);
 
-    // Simulate an 8-bit roundtrip for fair comparison.
-    let render_rgb = (render_rgb * 255.0).round() / 255.0;
-
-    let cfg = |l1, ssim| ImageLossConfig {
-        l1_weight: l1,
-        ssim_weight: ssim,
-        composite_bg: None,
-        mask: false,
+    // Simulate an 8-bit roundtrip for fair comparison. HDR ground truth isn't
+    // 8-bit, so neither should the render be.
+    let render_rgb = if hdr {
+        render_rgb
+    } else {
+        (render_rgb * 255.0).round() / 255.0
     };
-    // MSE = mean(L1^2) since |a - b|^2 == (a - b)^2.
-    let mse = image_loss_eval(render_rgb.clone(), gt_packed.clone(), cfg(1.0, 0.0))
-        .powi_scalar(2)
-        .mean();
-    let psnr = mse.recip().log() * 10.0 / std::f32::consts::LN_10;
-    let ssim = image_loss_eval(render_rgb.clone(), gt_packed, cfg(0.0, 1.0)).mean();
+
+    let (psnr, ssim) = psnr_ssim(render_rgb.clone(), &gt, ssim_mode, ssim_window);
+    let (psnr_corrected, ssim_corrected) = exposure.map_or((None, None), |exposure| {
+        let corrected = apply_color_correction(render_rgb.clone(), exposure);
+        let corrected = if hdr {
+            corrected
+        } else {
+            (corrected.clamp(0.0, 1.0) * 255.0).round() / 255.0
+        };
+        let (psnr, ssim) = psnr_ssim(corrected, &gt, ssim_mode, ssim_window);
+        (Some(psnr), Some(ssim))
+    });
+
+    let lpips = lpips.zip(gt_rgb).map(|(model, gt_rgb)| {
+        let gt_rgb = TensorData::new(gt_rgb.into_vec(), [1, res.y as usize, res.x as usize, 3]);
+        let gt_rgb = Tensor::from_data(gt_rgb, device);
+        let render_rgb = render_rgb.clone().unsqueeze();
+        match &gt.mask {
+            Some((mask, _)) => {
+                model.lpips_masked(render_rgb, gt_rgb, mask.clone().squeeze_dim(2).unsqueeze())
+            }
+            None => model.lpips(render_rgb, gt_rgb),
+        }
+    });
 
     Ok(EvalSample {
         gt_img,
         psnr,
         ssim,
+        psnr_corrected,
+        ssim_corrected,
+        lpips,
+        render_time,
         rendered: render_rgb,
         render_aux,
     })
 }
 
+/// The ground truth of an eval view, as needed for the PSNR & SSIM.
+struct MetricsGt {
+    packed: Tensor<2, Int>,
+    /// The packed GT is clamped to [0, 1], so for HDR views the MSE is
+    /// computed against the linear GT instead.
+    hdr: Option<Tensor<3>>,
+    /// For masked views, the `[H, W, 1]` mask and the packed GT with the
+    /// masked out colors cleared.
+    mask: Option<(Tensor<3>, Tensor<2, Int>)>,
+}
+
+impl MetricsGt {
+    fn new(gt_sample: &DynamicImage, alpha_mode: AlphaMode, device: &Device) -> Self {
+        let hdr = is_hdr(gt_sample).then(|| {
+            let gt_rgb = gt_sample.to_rgb32f();
+            let shape = [gt_rgb.height() as usize, gt_rgb.width() as usize, 3];
+            Tensor::from_data(TensorData::new(gt_rgb.into_vec(), shape), device)
+        });
+        let (packed, has_alpha) = sample_to_packed_data(gt_sample.clone());
+        let packed: Tensor<2, Int> = Tensor::from_data(packed, device);
+        let mask = (has_alpha && alpha_mode == AlphaMode::Masked).then(|| {
+            // Masked samples keep their original colors under the mask,
+            // premultiplying clears them.
+            let cleared = view_to_sample_image(gt_sample.clone(), AlphaMode::Transparent);
+            let (cleared, _) = sample_to_packed_data(cleared);
+            let mask = gt_alpha(packed.clone()).unsqueeze_dim(2);
+            (mask, Tensor::from_data(cleared, device))
+        });
+        Self { packed, hdr, mask }
+    }
+}
+
+/// PSNR & SSIM of `render_rgb` against the GT.
+///
+/// Masked views are only scored on their valid pixels. The squared error is
+/// weighted by the mask and averaged over the valid pixels. For SSIM, the
+/// masked out pixels are cleared in both images first, so they don't leak into
+/// the windows of the valid pixels.
+fn psnr_ssim(
+    render_rgb: Tensor<3>,
+    gt: &MetricsGt,
+    ssim_mode: SsimMode,
+    ssim_window: SsimWindow,
+) -> (Tensor<1>, Tensor<1>) {
+    let cfg = |l1, ssim, mask| ImageLossConfig {
+        l1_weight: l1,
+        ssim_weight: ssim,
+        composite_bg: None,
+        mask,
+        ssim_window,
+    };
+    let psnr = |mse: Tensor<1>| mse.recip().log() * 10.0 / std::f32::consts::LN_10;
+    let sq_err = match &gt.hdr {
+        Some(hdr) => (render_rgb.clone() - hdr.clone()).powi_scalar(2),
+        // |a - b|^2 == (a - b)^2.
+        None => image_loss_eval(render_rgb.clone(), gt.packed.clone(), cfg(1.0, 0.0, false))
+            .powi_scalar(2),
+    };
+
+    let Some((mask, cleared)) = &gt.mask else {
+        let ssim = match ssim_mode {
+            SsimMode::Ssim => {
+                image_loss_eval(render_rgb, gt.packed.clone(), cfg(0.0, 1.0, false)).mean()
+            }
+            SsimMode::MsSsim => ms_ssim_eval(render_rgb, gt.packed.clone(), cfg(0.0, 1.0, false)),
+        };
+        return (psnr(sq_err.mean()), ssim);
+    };
+
+    let valid = (mask.clone().sum() * 3.0).clamp_min(1e-6);
+    let mse = (sq_err * mask.clone()).sum() / valid.clone();
+    let render_rgb = render_rgb * mask.clone();
+    let ssim = match ssim_mode {
+        // The mask flag multiplies the SSIM map by the mask.
+        SsimMode::Ssim => {
+            image_loss_eval(render_rgb, cleared.clone(), cfg(0.0, 1.0, true)).sum() / valid
+        }
+        SsimMode::MsSsim => ms_ssim_eval(render_rgb, cleared.clone(), cfg(0.0, 1.0, true)),
+    };
+    (psnr(mse), ssim)
+}
+
+/// The metrics of a single eval view.
+#[derive(Clone, Debug, Serialize)]
+pub struct EvalViewReport {
+    pub name: String,
+    pub psnr: f32,
+    pub ssim: f32,
+    pub lpips: Option<f32>,
+    pub render_time_ms: f32,
+    pub num_splats: u32,
+}
+
+impl EvalViewReport {
+    /// Read back the metrics of an [`EvalSample`].
+    pub async fn from_sample(name: String, sample: &EvalSample, num_splats: u32) -> Result<Self> {
+        let lpips = match &sample.lpips {
+            Some(lpips) => Some(lpips.clone().into_scalar_async::<f32>().await?),
+            None => None,
+        };
+        Ok(Self {
+            name,
+            psnr: sample.psnr.clone().into_scalar_async::<f32>().await?,
+            ssim: sample.ssim.clone().into_scalar_async::<f32>().await?,
+            lpips,
+            render_time_ms: sample.render_time.as_secs_f32() * 1000.0,
+            num_splats,
+        })
+    }
+}
+
+/// Per-view metrics of an eval run, to compare against other methods.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EvalReport {
+    pub iter: u32,
+    pub views: Vec<EvalViewReport>,
+}
+
+impl EvalReport {
+    fn mean(&self, metric: impl Fn(&EvalViewReport) -> f32) -> f32 {
+        self.views.iter().map(metric).sum::<f32>() / self.views.len().max(1) as f32
+    }
+
+    pub fn avg_psnr(&self) -> f32 {
+        self.mean(|v| v.psnr)
+    }
+
+    pub fn avg_ssim(&self) -> f32 {
+        self.mean(|v| v.ssim)
+    }
+
+    /// Average LPIPS, if it was computed for all views.
+    pub fn avg_lpips(&self) -> Option<f32> {
+        let all = !self.views.is_empty() && self.views.iter().all(|v| v.lpips.is_some());
+        all.then(|| self.mean(|v| v.lpips.unwrap_or_default()))
+    }
+
+    /// The per-view metrics, along with their averages over the scene.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct WithAverages<'a> {
+            iter: u32,
+            avg_psnr: f32,
+            avg_ssim: f32,
+            avg_lpips: Option<f32>,
+            views: &'a [EvalViewReport],
+        }
+        let report = WithAverages {
+            iter: self.iter,
+            avg_psnr: self.avg_psnr(),
+            avg_ssim: self.avg_ssim(),
+            avg_lpips: self.avg_lpips(),
+            views: &self.views,
+        };
+        serde_json::to_string_pretty(&report).expect("Eval report serializes to JSON")
+    }
+
+    /// One row per view. LPIPS is left empty when it wasn't computed.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("name,psnr,ssim,lpips,render_time_ms,num_splats\n");
+        for view in &self.views {
+            // Quote names so commas in file names don't break the columns.
+            let name = format!("\"{}\"", view.name.replace('"', "\"\""));
+            let lpips = view.lpips.map(|l| l.to_string()).unwrap_or_default();
+            csv += &format!(
+                "{name},{},{},{lpips},{},{}\n",
+                view.psnr, view.ssim, view.render_time_ms, view.num_splats
+            );
+        }
+        csv
+    }
+
+    /// Write the report to `eval_report.json` and `eval_report.csv` in `dir`.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn save_to_dir(&self, dir: &Path) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(dir.join("eval_report.json"), self.to_json()).await?;
+        tokio::fs::write(dir.join("eval_report.csv"), self.to_csv()).await?;
+        Ok(())
+    }
+}
+
 impl EvalSample {
     #[cfg(not(target_family = "wasm"))]
     pub async fn save_to_disk(&self, path: &Path) -> anyhow::Result<()> {
@@ -81,3 +318,122 @@ impl EvalSample {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{EvalReport, EvalViewReport, MetricsGt, psnr_ssim};
+    use crate::config::SsimMode;
+    use brush_dataset::scene::view_to_sample_image;
+    use brush_loss::SsimWindow;
+    use brush_render::AlphaMode;
+    use burn::tensor::{Device, Tensor, TensorData};
+    use image::{DynamicImage, RgbImage, RgbaImage};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn view(name: &str, psnr: f32, ssim: f32, lpips: Option<f32>) -> EvalViewReport {
+        EvalViewReport {
+            name: name.to_owned(),
+            psnr,
+            ssim,
+            lpips,
+            render_time_ms: 2.0,
+            num_splats: 1000,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_report_json_roundtrip() {
+        let report = EvalReport {
+            iter: 500,
+            views: vec![
+                view("a.png", 20.0, 0.5, Some(0.25)),
+                view("b, c.png", 30.0, 0.75, Some(0.5)),
+            ],
+        };
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["iter"], 500);
+        assert_eq!(json["avg_psnr"], 25.0);
+        assert_eq!(json["avg_ssim"], 0.625);
+        assert_eq!(json["avg_lpips"], 0.375);
+        let views = json["views"].as_array().unwrap();
+        assert_eq!(views.len(), 2);
+        assert_eq!(views[1]["name"], "b, c.png");
+        assert_eq!(views[1]["psnr"], 30.0);
+        assert_eq!(views[0]["lpips"], 0.25);
+
+        // Without LPIPS for every view there's no meaningful average.
+        let report = EvalReport {
+            iter: 500,
+            views: vec![
+                view("a.png", 20.0, 0.5, None),
+                view("b.png", 30.0, 0.75, Some(0.5)),
+            ],
+        };
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert!(json["avg_lpips"].is_null());
+        assert!(json["views"][0]["lpips"].is_null());
+
+        let csv = report.to_csv();
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], "\"a.png\",20,0.5,,2,1000");
+    }
+
+    /// Arbitrary but deterministic byte for pixel `(x, y)`, channel `c`.
+    fn noise(x: u32, y: u32, c: u32) -> u8 {
+        ((x * 37 + y * 91 + c * 53).wrapping_mul(2_654_435_761) >> 24) as u8
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_masked_metrics_ignore_masked_pixels() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let (w, h, half) = (64, 48, 32);
+
+        let render: Vec<f32> = (0..h)
+            .flat_map(|y| (0..w).flat_map(move |x| (0..3).map(move |c| (x, y, c))))
+            .map(|(x, y, c)| f32::from(noise(x, y, c)) / 255.0)
+            .collect();
+        let render = Tensor::<3>::from_data(
+            TensorData::new(render, [h as usize, w as usize, 3]),
+            &device,
+        );
+        // The GT is the render plus some noise on the left half, and garbage
+        // masked out on the right half.
+        let gt_pixel = |x: u32, y: u32| -> [u8; 3] {
+            std::array::from_fn(|c| {
+                noise(x, y, c as u32).saturating_add(noise(y, x, c as u32) % 32)
+            })
+        };
+        let gt_full = RgbaImage::from_fn(w, h, |x, y| {
+            if x < half {
+                let [r, g, b] = gt_pixel(x, y);
+                image::Rgba([r, g, b, 255])
+            } else {
+                image::Rgba([noise(x + 7, y * 3, 5), 255, noise(y, x, 1), 0])
+            }
+        });
+        let gt_half = RgbImage::from_fn(half, h, |x, y| image::Rgb(gt_pixel(x, y)));
+
+        let metrics = |render, gt: DynamicImage| {
+            let gt = view_to_sample_image(gt, AlphaMode::Masked);
+            let gt = MetricsGt::new(&gt, AlphaMode::Masked, &device);
+            psnr_ssim(render, &gt, SsimMode::Ssim, SsimWindow::default())
+        };
+        let (psnr, ssim) = metrics(render.clone(), gt_full.into());
+        let (half_psnr, half_ssim) = metrics(
+            render.slice([0..h as usize, 0..half as usize, 0..3]),
+            gt_half.into(),
+        );
+
+        let psnr = psnr.into_scalar_async::<f32>().await.unwrap();
+        let half_psnr = half_psnr.into_scalar_async::<f32>().await.unwrap();
+        let ssim = ssim.into_scalar_async::<f32>().await.unwrap();
+        let half_ssim = half_ssim.into_scalar_async::<f32>().await.unwrap();
+        assert!((psnr - half_psnr).abs() < 1e-3, "{psnr} vs {half_psnr}");
+        assert!((ssim - half_ssim).abs() < 1e-4, "{ssim} vs {half_ssim}");
+        assert!(ssim < 0.99, "The valid half should not match perfectly");
+    }
+}
@@ -0,0 +1,49 @@
+//! Learned per-view color correction, see [`crate::config::TrainConfig::learn_exposure`].
+//!
+//! Each training view gets a `[3, 4]` affine transform which is applied to the
+//! rendered RGB before computing the losses. This lets the optimizer explain
+//! exposure & white balance changes between images with a few numbers per view,
+//! instead of baking them into the splats as floaters and muddy colors.
+
+use burn::{
+    module::{Module, Param, ParamId},
+    tensor::{Device, Tensor, s},
+};
+
+/// A single view's color correction, as a module so it can be optimized.
+#[derive(Module, Debug)]
+pub(crate) struct ViewExposure {
+    pub correction: Param<Tensor<2>>,
+}
+
+/// The identity `[3, 4]` color correction.
+pub fn identity_correction(device: &Device) -> Tensor<2> {
+    Tensor::from_floats(
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ],
+        device,
+    )
+}
+
+pub(crate) fn identity_param(device: &Device) -> Param<Tensor<2>> {
+    Param::initialized(ParamId::new(), identity_correction(device))
+}
+
+/// Apply a `[3, 4]` affine color correction to an `[H, W, C]` image. Only the
+/// first 3 (RGB) channels are corrected, any further channels pass through.
+pub fn apply_color_correction(img: Tensor<3>, correction: Tensor<2>) -> Tensor<3> {
+    let [h, w, c] = img.dims();
+    let rgb = img.clone().slice(s![.., .., 0..3]).reshape([h * w, 3]);
+    let matrix = correction.clone().slice(s![.., 0..3]);
+    let offset = correction.slice(s![.., 3..4]).reshape([1, 3]);
+    let rgb = (rgb.matmul(matrix.transpose()) + offset).reshape([h, w, 3]);
+
+    if c > 3 {
+        Tensor::cat(vec![rgb, img.slice(s![.., .., 3..])], 2)
+    } else {
+        rgb
+    }
+}
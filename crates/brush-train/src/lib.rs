@@ -1,13 +1,16 @@
 #![recursion_limit = "256"]
 
+pub mod background;
 pub mod config;
 pub mod eval;
+pub mod exposure;
 pub mod lod;
 pub mod msg;
+pub mod pose;
 pub mod train;
 
-mod adam_scaled;
 mod multinomial;
+mod optimizer;
 mod quat_vec;
 mod stats;
 
@@ -1,5 +1,5 @@
 use brush_dataset::scene::{sample_to_packed_data, view_to_sample_image};
-use brush_loss::{ImageLossConfig, image_loss};
+use brush_loss::{ImageLossConfig, SsimWindow, image_loss};
 use brush_render::gaussian_splats::Splats;
 use brush_render_bwd::render_splats;
 use burn::{
@@ -107,6 +107,7 @@ pub async fn compute_pup_scores(
             ssim_weight: 0.0,
             composite_bg: None,
             mask: false,
+            ssim_window: SsimWindow::default(),
         };
         let loss = image_loss(pred_rgb, gt_packed, l1_cfg).mean();
         let mut grads = loss.backward();
@@ -10,12 +10,19 @@ pub struct RefineStats {
     pub num_pruned: u32,
     /// Subset of `num_pruned` whose params went non-finite (NaN/Inf).
     pub num_pruned_non_finite: u32,
+    /// Whether the opacities were reset, see `TrainConfig::opacity_reset_every`.
+    pub opacity_reset: bool,
+    /// Whether growth was skipped because the renders already need more
+    /// intersections than fit in memory, see `SplatTrainer::with_max_intersections`.
+    pub growth_skipped: bool,
     pub total_splats: u32,
 }
 
 #[derive(Clone)]
 pub struct TrainStepStats {
     pub num_visible: u32,
+    /// Most splat-tile intersections any view of the step needed.
+    pub num_intersections: u32,
     pub lr_mean: f64,
     pub lr_rotation: f64,
     pub lr_scale: f64,
@@ -24,4 +31,8 @@ pub struct TrainStepStats {
     // Non-autodiff inner tensor; consumers read the scalar lazily so disabled
     // logging doesn't force a GPU readback.
     pub loss: Tensor<1>,
+    /// Views of the batch whose loss wasn't finite, as indices into the
+    /// scene. When any are found the step is skipped and the splats are
+    /// unchanged. Only checked every `nan_check_every` steps.
+    pub non_finite_views: Vec<usize>,
 }
@@ -1,7 +1,6 @@
-pub(crate) fn multinomial_sample(weights: &[f32], n: u32) -> Vec<i32> {
-    let mut rng = rand::rng();
+pub(crate) fn multinomial_sample(weights: &[f32], n: u32, rng: &mut impl rand::Rng) -> Vec<i32> {
     rand::seq::index::sample_weighted(
-        &mut rng,
+        rng,
         weights.len(),
         |i| {
             if weights[i].is_finite() && weights[i] >= 0.0 {
@@ -34,7 +33,7 @@ mod tests {
     fn test_multinomial_sampling() {
         // Test the complete multinomial sampling workflow (samples indices without replacement)
         let weights = vec![0.1, 0.3, 0.4, 0.2];
-        let samples = multinomial_sample(&weights, 3);
+        let samples = multinomial_sample(&weights, 3, &mut rand::rng());
 
         assert_eq!(samples.len(), 3);
         for &sample in &samples {
@@ -48,7 +47,7 @@ mod tests {
 
         // Test edge case: sampling all indices
         let single_weight = vec![1.0];
-        let single_samples = multinomial_sample(&single_weight, 1);
+        let single_samples = multinomial_sample(&single_weight, 1, &mut rand::rng());
         assert_eq!(single_samples.len(), 1);
         assert_eq!(single_samples[0], 0);
     }
@@ -57,7 +56,7 @@ mod tests {
     fn test_nan_weight_handling() {
         // Test that NaN weights are handled (converted to 0.0)
         let weights_with_nan = vec![0.5, f32::NAN, 0.3, 0.2];
-        let samples = multinomial_sample(&weights_with_nan, 2);
+        let samples = multinomial_sample(&weights_with_nan, 2, &mut rand::rng());
 
         assert_eq!(samples.len(), 2);
         // Should never sample index 1 (NaN weight becomes 0.0)
@@ -72,9 +71,20 @@ mod tests {
     fn test_all_zero_weights() {
         // Discovered behavior: returns empty vec when all weights are zero
         let zero_weights = vec![0.0, 0.0, 0.0];
-        let result = multinomial_sample(&zero_weights, 1);
+        let result = multinomial_sample(&zero_weights, 1, &mut rand::rng());
 
         // Function returns empty vector when it cannot sample any valid indices
         assert_eq!(result.len(), 0);
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_seeded_sampling_is_reproducible() {
+        use rand::SeedableRng;
+
+        let weights: Vec<f32> = (0..1000).map(|i| (i % 7) as f32).collect();
+        let sample =
+            |seed| multinomial_sample(&weights, 100, &mut rand::rngs::StdRng::seed_from_u64(seed));
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+    }
 }
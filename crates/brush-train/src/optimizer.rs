@@ -1,3 +1,4 @@
+use crate::config::OptimizerKind;
 use burn::{
     config::Config,
     grad_clipping::GradientClippingConfig,
@@ -12,15 +13,18 @@ use burn::{
     tensor::{Device, ElementConversion, Tensor},
 };
 
-/// Adam with per-parameter second-moment reduction (via [`AdamState::reduce_moment_2`]).
+/// Adam or SGD with per-component learning rate scaling (via [`OptimState::scaling`]).
+/// Adam can also reduce its second moment per parameter (via [`OptimState::reduce_moment_2`]).
 #[derive(Clone)]
-pub(crate) struct AdamScaled {
-    momentum: AdaptiveMomentum,
+pub(crate) struct ScaledOptimizer {
+    update: Update,
     weight_decay: Option<WeightDecay>,
 }
 
 #[derive(Config, Debug)]
-pub(crate) struct AdamScaledConfig {
+pub(crate) struct ScaledOptimizerConfig {
+    #[config(default = "OptimizerKind::Adam")]
+    kind: OptimizerKind,
     #[config(default = 0.9)]
     beta_1: f32,
     #[config(default = 0.999)]
@@ -28,10 +32,19 @@ pub(crate) struct AdamScaledConfig {
     /// A value required for numerical stability.
     #[config(default = 1e-5)]
     epsilon: f32,
+    /// Momentum factor for SGD.
+    #[config(default = 0.9)]
+    sgd_momentum: f32,
     weight_decay: Option<WeightDecayConfig>,
     grad_clipping: Option<GradientClippingConfig>,
 }
 
+#[derive(Clone)]
+enum Update {
+    Adam(AdaptiveMomentum),
+    Sgd(SgdMomentum),
+}
+
 #[derive(Clone)]
 struct AdaptiveMomentum {
     beta_1: f32,
@@ -39,9 +52,17 @@ struct AdaptiveMomentum {
     epsilon: f32,
 }
 
+#[derive(Clone)]
+struct SgdMomentum {
+    momentum: f32,
+}
+
 /// Per-parameter momentum state. When `reduce_moment_2` is set on the owning
-/// [`AdamState`], `moment_2` has size 1 in trailing dims; `map_opt` callers
+/// [`OptimState`], `moment_2` has size 1 in trailing dims; `map_opt` callers
 /// must stay shape-agnostic along those.
+///
+/// SGD keeps its velocity in `moment_1`, and doesn't use `moment_2`. It's
+/// still kept (with size 1 trailing dims) so all states can be mapped alike.
 #[derive(Record, Clone)]
 pub(crate) struct MomentumState<const D: usize> {
     pub moment_1: Tensor<D>,
@@ -62,7 +83,7 @@ impl<const D: usize> MomentumState<D> {
 
 /// Per-parameter optimizer state.
 #[derive(Record, Clone)]
-pub(crate) struct AdamState<const D: usize> {
+pub(crate) struct OptimState<const D: usize> {
     pub momentum: Option<MomentumState<D>>,
     /// Per-component learning rate scaling (e.g. different LR for means vs
     /// rotations vs scales within the transforms tensor).
@@ -73,14 +94,20 @@ pub(crate) struct AdamState<const D: usize> {
     pub reduce_moment_2: bool,
 }
 
-impl AdamScaledConfig {
-    pub(crate) fn init<M: AutodiffModule>(&self) -> OptimizerAdaptor<AdamScaled, M> {
-        let optim = AdamScaled {
-            momentum: AdaptiveMomentum {
+impl ScaledOptimizerConfig {
+    pub(crate) fn init<M: AutodiffModule>(&self) -> OptimizerAdaptor<ScaledOptimizer, M> {
+        let update = match self.kind {
+            OptimizerKind::Adam => Update::Adam(AdaptiveMomentum {
                 beta_1: self.beta_1,
                 beta_2: self.beta_2,
                 epsilon: self.epsilon,
-            },
+            }),
+            OptimizerKind::Sgd => Update::Sgd(SgdMomentum {
+                momentum: self.sgd_momentum,
+            }),
+        };
+        let optim = ScaledOptimizer {
+            update,
             weight_decay: self.weight_decay.as_ref().map(WeightDecay::new),
         };
         let mut optim = OptimizerAdaptor::from(optim);
@@ -91,8 +118,8 @@ impl AdamScaledConfig {
     }
 }
 
-impl SimpleOptimizer for AdamScaled {
-    type State<const D: usize> = AdamState<D>;
+impl SimpleOptimizer for ScaledOptimizer {
+    type State<const D: usize> = OptimState<D>;
 
     fn step<const D: usize>(
         &self,
@@ -114,9 +141,12 @@ impl SimpleOptimizer for AdamScaled {
             grad = weight_decay.transform(grad, tensor.clone());
         }
 
-        let (grad, state_momentum) = self.momentum.transform(&grad, state_momentum, reduce);
+        let (grad, state_momentum) = match &self.update {
+            Update::Adam(momentum) => momentum.transform(&grad, state_momentum, reduce),
+            Update::Sgd(momentum) => momentum.transform(grad, state_momentum),
+        };
 
-        let state = AdamState {
+        let state = OptimState {
             momentum: Some(state_momentum),
             scaling: scaling.clone(),
             reduce_moment_2: reduce,
@@ -211,3 +241,27 @@ impl AdaptiveMomentum {
         (grad, state)
     }
 }
+
+impl SgdMomentum {
+    /// Heavy ball momentum, `v = momentum * v + grad`. Returns `v` as the update direction.
+    fn transform<const D: usize>(
+        &self,
+        grad: Tensor<D>,
+        momentum_state: Option<MomentumState<D>>,
+    ) -> (Tensor<D>, MomentumState<D>) {
+        let state = if let Some(mut state) = momentum_state {
+            state.moment_1 = state.moment_1.mul_scalar(self.momentum).add(grad);
+            state.time += 1;
+            state
+        } else {
+            let mut unused_shape = [1; D];
+            unused_shape[0] = grad.dims()[0];
+            MomentumState {
+                moment_2: Tensor::zeros(unused_shape, &grad.device()),
+                moment_1: grad,
+                time: 1,
+            }
+        };
+        (state.moment_1.clone(), state)
+    }
+}
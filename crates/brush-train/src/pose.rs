@@ -0,0 +1,198 @@
+//! Learned per-view camera pose corrections, see [`crate::config::TrainConfig::pose_refine`].
+//!
+//! Each training view gets a `[6]` correction: a rotation vector followed by a
+//! translation, both in the camera's local frame. Rather than differentiating
+//! the rasterizer w.r.t. the view matrix, the correction is applied to the
+//! splats: rendering with a camera moved by `delta` gives the same image as
+//! rendering with the original camera, after moving the splats by `delta⁻¹`
+//! relative to the camera. The splat transforms are differentiable already, so
+//! the gradient reaches the pose through autodiff.
+//!
+//! SH colors are still evaluated in the uncorrected frame. Corrections are
+//! small, so this barely changes the view dependent colors.
+
+use brush_render::camera::Camera;
+use burn::{
+    module::{Module, Param, ParamId},
+    tensor::{Device, Tensor, s},
+};
+use glam::{Mat3, Quat, Vec3};
+
+use crate::quat_vec::quaternion_vec_multiply;
+
+/// A single view's pose correction, as a module so it can be optimized.
+#[derive(Module, Debug)]
+pub(crate) struct ViewPose {
+    pub delta: Param<Tensor<1>>,
+}
+
+pub(crate) fn identity_pose_param(device: &Device) -> Param<Tensor<1>> {
+    Param::initialized(ParamId::new(), Tensor::zeros([6], device))
+}
+
+// The rotation of a rotation vector `r`, as `normalize(1, r / 2)`. This matches
+// the exponential map to first order, and unlike it is smooth around zero.
+fn delta_rotation(rotation: Vec3) -> Quat {
+    let half = rotation / 2.0;
+    Quat::from_xyzw(half.x, half.y, half.z, 1.0).normalize()
+}
+
+/// Apply a `[6]` pose correction to a camera.
+pub fn refine_camera(camera: &Camera, delta: &[f32]) -> Camera {
+    let rotation = delta_rotation(Vec3::from_slice(&delta[0..3]));
+    let translation = Vec3::from_slice(&delta[3..6]);
+    Camera {
+        position: camera.position + camera.rotation * translation,
+        rotation: (camera.rotation * rotation).normalize(),
+        ..*camera
+    }
+}
+
+// Hamilton product of a `[1, 4]` quaternion with `[N, 4]` quaternions, all `[w, x, y, z]`.
+fn quaternion_multiply(a: Tensor<2>, b: Tensor<2>) -> Tensor<2> {
+    let c = |q: &Tensor<2>, i: usize| q.clone().slice(s![.., i..i + 1]);
+    let (aw, ax, ay, az) = (c(&a, 0), c(&a, 1), c(&a, 2), c(&a, 3));
+    let (bw, bx, by, bz) = (c(&b, 0), c(&b, 1), c(&b, 2), c(&b, 3));
+
+    let w = aw.clone() * bw.clone()
+        - ax.clone() * bx.clone()
+        - ay.clone() * by.clone()
+        - az.clone() * bz.clone();
+    let x = aw.clone() * bx.clone() + ax.clone() * bw.clone() + ay.clone() * bz.clone()
+        - az.clone() * by.clone();
+    let y = aw.clone() * by.clone() - ax.clone() * bz.clone()
+        + ay.clone() * bw.clone()
+        + az.clone() * bx.clone();
+    let z = aw * bz + ax * by - ay * bx + az * bw;
+    Tensor::cat(vec![w, x, y, z], 1)
+}
+
+/// Move `[N, 10]` splat transforms such that rendering them from `camera`
+/// matches rendering the original transforms from the camera corrected by `delta`.
+pub(crate) fn apply_pose_correction(
+    transforms: Tensor<2>,
+    camera: &Camera,
+    delta: Tensor<1>,
+) -> Tensor<2> {
+    let device = delta.device();
+
+    // Same as `delta_rotation`, on the autodiff graph.
+    let half = delta.clone().slice(s![0..3]).reshape([1, 3]) / 2.0;
+    let rotation = Tensor::cat(vec![Tensor::ones([1, 1], &device), half], 1);
+    let rotation = rotation.clone() / rotation.powi_scalar(2).sum().sqrt().reshape([1, 1]);
+    let translation = delta.slice(s![3..6]).reshape([1, 3]);
+
+    // Rows are multiplied from the left, so this is the transposed camera rotation.
+    let cam_rot_t =
+        Tensor::<2>::from_floats(Mat3::from_quat(camera.rotation).to_cols_array_2d(), &device);
+    // The inverse correction in world space, cam_rot * rotation⁻¹ * cam_rot⁻¹.
+    let world_rot = Tensor::cat(
+        vec![
+            rotation.clone().slice(s![.., 0..1]),
+            -rotation.slice(s![.., 1..4]).matmul(cam_rot_t.clone()),
+        ],
+        1,
+    );
+    let identity = Tensor::<2>::from_floats(Mat3::IDENTITY.to_cols_array_2d(), &device);
+    let world_mat_t = quaternion_vec_multiply(world_rot.clone().repeat_dim(0, 3), identity);
+
+    let center = Tensor::<2>::from_floats([camera.position.to_array()], &device);
+    let offset = translation.matmul(cam_rot_t).matmul(world_mat_t.clone());
+
+    let means = transforms.clone().slice(s![.., 0..3]);
+    let means = (means - center.clone()).matmul(world_mat_t) + center - offset;
+    let quats = quaternion_multiply(world_rot, transforms.clone().slice(s![.., 3..7]));
+    Tensor::cat(vec![means, quats, transforms.slice(s![.., 7..10])], 1)
+}
+
+/// A train view's camera after pose refinement.
+#[derive(Clone, Debug)]
+pub struct RefinedPose {
+    pub name: String,
+    pub camera: Camera,
+    /// Size of the correction, in degrees and world units.
+    pub rotation_deg: f32,
+    pub translation: f32,
+}
+
+/// Write poses as a COLMAP `images.txt`. All images reference camera 1, as
+/// pose refinement doesn't change the intrinsics.
+pub fn to_colmap_images_txt(poses: &[RefinedPose]) -> String {
+    let mut txt = String::from(
+        "# Image list with two lines of data per image:\n\
+         #   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n\
+         #   POINTS2D[] as (X, Y, POINT3D_ID)\n",
+    );
+    for (i, pose) in poses.iter().enumerate() {
+        let (_, quat, tvec) = pose.camera.world_to_local().to_scale_rotation_translation();
+        txt += &format!(
+            "{} {} {} {} {} {} {} {} 1 {}\n\n",
+            i + 1,
+            quat.w,
+            quat.x,
+            quat.y,
+            quat.z,
+            tvec.x,
+            tvec.y,
+            tvec.z,
+            pose.name
+        );
+    }
+    txt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_pose_correction, refine_camera};
+    use brush_render::camera::Camera;
+    use brush_render::kernels::camera_model::CameraModel;
+    use burn::tensor::Tensor;
+    use glam::{Quat, Vec3};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg(target_family = "wasm")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_pose_correction_matches_camera() {
+        let device: burn::tensor::Device = brush_cube::test_helpers::test_device().await.into();
+        let camera = Camera::new(
+            Vec3::new(0.5, -1.0, 2.0),
+            Quat::from_euler(glam::EulerRot::XYZ, 0.3, -0.2, 0.1),
+            0.8,
+            0.8,
+            glam::vec2(0.5, 0.5),
+            CameraModel::Pinhole,
+        );
+        let delta = [0.05, -0.02, 0.03, 0.1, 0.2, -0.1];
+        let refined = refine_camera(&camera, &delta);
+
+        let mean = Vec3::new(0.3, 0.7, -0.4);
+        let quat = Quat::from_euler(glam::EulerRot::XYZ, 0.5, 0.1, -0.3);
+        let transforms = Tensor::<2>::from_floats(
+            [[
+                mean.x, mean.y, mean.z, quat.w, quat.x, quat.y, quat.z, 0.0, 0.0, 0.0,
+            ]],
+            &device,
+        );
+        let moved = apply_pose_correction(transforms, &camera, Tensor::from_floats(delta, &device));
+        let moved: Vec<f32> = moved
+            .into_data_async()
+            .await
+            .expect("readback")
+            .into_vec()
+            .expect("Wrong type");
+
+        // The moved splat seen from the camera is the original seen from the refined camera.
+        let local = camera
+            .world_to_local()
+            .transform_point3(Vec3::from_slice(&moved[0..3]));
+        let local_ref = refined.world_to_local().transform_point3(mean);
+        assert!((local - local_ref).length() < 1e-5);
+
+        let moved_quat = Quat::from_xyzw(moved[4], moved[5], moved[6], moved[3]);
+        let local_quat = camera.rotation.inverse() * moved_quat;
+        let local_quat_ref = refined.rotation.inverse() * quat;
+        assert!(local_quat.angle_between(local_quat_ref) < 1e-3);
+    }
+}
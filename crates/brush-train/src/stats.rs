@@ -1,9 +1,11 @@
 use burn::{
     prelude::Int,
+    record::Record,
     tensor::{Bool, Device, Tensor},
 };
 use tracing::trace_span;
 
+#[derive(Record)]
 pub(crate) struct RefineRecord {
     // Helper tensors for accumulating the viewspace_xy gradients and the number
     // of observations per gaussian. Used in pruning and densification.
@@ -49,6 +51,18 @@ impl RefineRecord {
         self.max_screen_size = screen_radius.max_pair(self.max_screen_size.clone());
     }
 
+    /// Per-splat factors for the mean learning rate, see
+    /// [`crate::config::TrainConfig::adaptive_mean_lr`]: the square root of the
+    /// refine weight relative to the average over the splats seen so far,
+    /// clamped to `[0.25, 4]`.
+    pub(crate) fn mean_lr_factors(&self) -> Tensor<1> {
+        let num_seen = self.vis_mask().float().sum().clamp_min(1.0);
+        let avg = (self.refine_weight_norm.clone().sum() / num_seen).clamp_min(1e-12);
+        (self.refine_weight_norm.clone() / avg)
+            .sqrt()
+            .clamp(0.25, 4.0)
+    }
+
     pub(crate) fn vis_mask(&self) -> Tensor<1, Bool> {
         self.vis_weight.clone().greater_elem(0.0)
     }
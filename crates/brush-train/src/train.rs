@@ -1,10 +1,13 @@
 use std::f32::consts::FRAC_1_SQRT_2;
 
 use crate::{
-    adam_scaled::{AdamScaled, AdamScaledConfig, AdamState},
-    config::TrainConfig,
+    background::{BackgroundMode, LearnedBackground, background_param, composite_background},
+    config::{SsimMode, TrainConfig},
+    exposure::{ViewExposure, apply_color_correction, identity_param},
     msg::{RefineStats, TrainStepStats},
     multinomial::multinomial_sample,
+    optimizer::{OptimState, ScaledOptimizer, ScaledOptimizerConfig},
+    pose::{ViewPose, apply_pose_correction, identity_pose_param, refine_camera},
     quat_vec::quaternion_vec_multiply,
     splat_init::bounds_from_pos,
     stats::RefineRecord,
@@ -12,7 +15,9 @@ use crate::{
 use brush_dataset::scene::SceneBatch;
 use brush_loss::{ImageLossConfig, image_loss};
 use brush_render::gaussian_splats::Splats;
-use brush_render::{AlphaMode, bounding_box::BoundingBox, sh::sh_coeffs_for_degree};
+use brush_render::{
+    AlphaMode, bounding_box::BoundingBox, camera::Camera, sh::sh_coeffs_for_degree, shaders::SH_C0,
+};
 use brush_render_bwd::render_splats;
 use burn::{
     backend::wgpu::{AutoCompiler, WgpuDevice, WgpuRuntime},
@@ -20,8 +25,9 @@ use burn::{
         LrScheduler,
         exponential::{ExponentialLrScheduler, ExponentialLrSchedulerConfig},
     },
-    module::{AutodiffModule, ParamId},
+    module::{AutodiffModule, Param, ParamId},
     optim::{GradientsParams, Optimizer, adaptor::OptimizerAdaptor, record::AdaptorRecord},
+    record::Record,
     tensor::{
         Bool, Device, Distribution, IndexingUpdateOp, Int, Tensor, TensorData, activation::sigmoid,
         s,
@@ -30,11 +36,17 @@ use burn::{
 
 use burn_cubecl::cubecl::Runtime;
 use hashbrown::{HashMap, HashSet};
+use rand::{RngExt, SeedableRng, rngs::StdRng};
 use tracing::{Instrument, trace_span};
 
 pub const BOUND_PERCENTILE: f32 = 0.8;
 
 const MIN_OPACITY: f32 = 1.0 / 255.0;
+/// Opacity the splats are clamped down to by an opacity reset.
+const RESET_OPACITY: f32 = 0.01;
+/// Range `split_scale_factor` is clamped to.
+const MIN_SPLIT_SCALE_FACTOR: f32 = 0.25;
+const MAX_SPLIT_SCALE_FACTOR: f32 = 0.9;
 
 /// Fraction of training after which the Mip-Splatting 3D-filter floor stops
 /// being recomputed and is held frozen (still applied), so splats settle
@@ -48,7 +60,20 @@ const MIN_SCALE_FREEZE_FRAC: f32 = 0.9;
 /// to well-behaved splats, so not a tunable.
 const MIN_SCALE_FACTOR: f32 = 0.1;
 
-type OptimizerType = OptimizerAdaptor<AdamScaled, Splats>;
+type OptimizerType = OptimizerAdaptor<ScaledOptimizer, Splats>;
+type ExposureOptimizerType = OptimizerAdaptor<ScaledOptimizer, ViewExposure>;
+type BackgroundOptimizerType = OptimizerAdaptor<ScaledOptimizer, LearnedBackground>;
+type PoseOptimizerType = OptimizerAdaptor<ScaledOptimizer, ViewPose>;
+
+// The loss of a single view in a batch, with the aux needed for the refine stats.
+struct ViewLoss {
+    loss: Tensor<1>,
+    refine_weight_holder: Tensor<1>,
+    visible: Tensor<1>,
+    max_radius: Tensor<1>,
+    num_visible: u32,
+    num_intersections: u32,
+}
 
 pub struct SplatTrainer {
     config: TrainConfig,
@@ -59,20 +84,140 @@ pub struct SplatTrainer {
     bounds: BoundingBox,
     step_count: u32,
     max_sh_degree: u32,
+    // Drives the background noise and refine sampling, see `Self::with_seed`.
+    rng: StdRng,
     /// Per-train-view (world center, focal in px at native res) for the
     /// Mip-Splatting 3D filter. Empty disables it. The floor itself lives on
     /// the splats (recomputed at each refine), not here.
     view_cams: Vec<(glam::Vec3, f32)>,
+    /// Learned `[3, 4]` color correction per train view, indexed by
+    /// `SceneBatch::view_index`. Grows as views are seen, empty unless
+    /// `config.learn_exposure` is set.
+    exposures: Vec<Param<Tensor<2>>>,
+    exposure_optim: Option<ExposureOptimizerType>,
+    /// `[6]` per-train-view pose corrections, indexed by view index. Only used
+    /// when `config.pose_refine` is set.
+    poses: Vec<Param<Tensor<1>>>,
+    pose_optim: Option<PoseOptimizerType>,
+    /// `[3]` background color, only set with [`BackgroundMode::Learned`].
+    background: Option<Param<Tensor<1>>>,
+    background_optim: Option<BackgroundOptimizerType>,
+    /// Growth is skipped while a render needs more intersections than this,
+    /// see [`Self::with_max_intersections`].
+    max_intersections: Option<u64>,
+    /// Most intersections any view needed since the last refine.
+    peak_intersections: u32,
     #[cfg(not(target_family = "wasm"))]
     lpips: Option<lpips::LpipsModel>,
 }
 
+/// Everything needed to pick training back up where it left off.
+#[derive(Record)]
+struct CheckpointRecord {
+    transforms: Param<Tensor<2>>,
+    sh_coeffs: Param<Tensor<3>>,
+    raw_opacities: Param<Tensor<1>>,
+    render_mip: bool,
+    min_scale: Option<Tensor<1>>,
+    optim: Option<HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>>,
+    exposures: Vec<Param<Tensor<2>>>,
+    exposure_optim: Option<HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>>,
+    poses: Vec<Param<Tensor<1>>>,
+    pose_optim: Option<HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>>,
+    background: Option<Param<Tensor<1>>>,
+    background_optim: Option<HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>>,
+    sched_mean: <ExponentialLrScheduler as LrScheduler>::Record,
+    refine_record: Option<RefineRecord>,
+    /// Bounds center followed by extent.
+    bounds: Vec<f32>,
+    step_count: u32,
+    max_sh_degree: u32,
+    iter: u32,
+}
+
+/// Mean over the splats of how far their largest to smallest scale ratio goes
+/// over `max_ratio`, in log space. Splats under the ratio get no gradient.
+fn scale_ratio_penalty(log_scales: Tensor<2>, max_ratio: f32) -> Tensor<1> {
+    let log_ratio = log_scales.clone().max_dim(1) - log_scales.min_dim(1);
+    (log_ratio - max_ratio.max(1.0).ln()).clamp_min(0.0).mean()
+}
+
+/// L1 loss between the rendered expected depth and `gt_depth`, over the pixels
+/// with a positive, finite depth. Depths are taken relative to the mean target
+/// depth, so the loss doesn't depend on the scale of the scene. `None` when no
+/// pixel has a valid depth.
+async fn depth_loss(
+    splats: Splats,
+    camera: &Camera,
+    img_size: glam::UVec2,
+    gt_depth: TensorData,
+) -> Option<Tensor<1>> {
+    let shape = [gt_depth.shape[0], gt_depth.shape[1]];
+    let values = gt_depth
+        .into_vec::<f32>()
+        .expect("Depth targets must be f32");
+    let is_valid = |d: f32| d.is_finite() && d > 0.0;
+    let valid_count = values.iter().filter(|&&d| is_valid(d)).count();
+    if valid_count == 0 {
+        return None;
+    }
+    let scale = values.iter().copied().filter(|&d| is_valid(d)).sum::<f32>() / valid_count as f32;
+
+    let device = splats.device();
+    let mask: Vec<f32> = values.iter().map(|&d| f32::from(is_valid(d))).collect();
+    let target: Vec<f32> = values
+        .iter()
+        .map(|&d| if is_valid(d) { d / scale } else { 0.0 })
+        .collect();
+    let mask = Tensor::<2>::from_data(TensorData::new(mask, shape), &device);
+    let target = Tensor::<2>::from_data(TensorData::new(target, shape), &device);
+
+    let pred = render_expected_depth(splats, camera, img_size, scale).await;
+    Some(((pred - target).abs() * mask).sum() / valid_count as f32)
+}
+
+/// Render the expected view-space depth `sum(alpha_i * T_i * z_i)` divided by
+/// `depth_scale`, on the autodiff graph. The depth of each splat is rendered as
+/// its (view independent) color, so the regular rasterizer and its backward
+/// pass can be used.
+async fn render_expected_depth(
+    splats: Splats,
+    camera: &Camera,
+    img_size: glam::UVec2,
+    depth_scale: f32,
+) -> Tensor<2> {
+    let device = splats.device();
+    let world_to_local = camera.world_to_local();
+    let z_row = world_to_local.matrix3.row(2);
+    let z_row = Tensor::<1>::from_floats([z_row.x, z_row.y, z_row.z], &device).reshape([3, 1]);
+    let means = splats.transforms.val().slice(s![.., 0..3]);
+    let depth = (means.matmul(z_row) + world_to_local.translation.z) / depth_scale;
+    // Undo the SH to color mapping, `color = dc * SH_C0 + 0.5`.
+    let dc = (depth - 0.5) / SH_C0;
+    let sh_coeffs = dc.unsqueeze_dim::<3>(2).repeat_dim(2, 3);
+    let depth_splats = Splats {
+        sh_coeffs: Param::initialized(ParamId::new(), sh_coeffs),
+        ..splats
+    };
+    let out = render_splats(depth_splats, camera, img_size, glam::Vec3::ZERO).await;
+    out.img.slice(s![.., .., 0..1]).squeeze_dim(2)
+}
+
 fn inv_sigmoid(x: Tensor<1>) -> Tensor<1> {
     (x.clone() / (1.0f32 - x)).log()
 }
 
-fn create_optimizer_from_config() -> OptimizerType {
-    AdamScaledConfig::new().with_epsilon(1e-15).init()
+fn create_adam_optimizer<M: AutodiffModule>() -> OptimizerAdaptor<ScaledOptimizer, M> {
+    ScaledOptimizerConfig::new().with_epsilon(1e-15).init()
+}
+
+/// The optimizer for the splats, as picked by [`TrainConfig::optimizer`].
+fn create_splat_optimizer(config: &TrainConfig) -> OptimizerType {
+    ScaledOptimizerConfig::new()
+        .with_kind(config.optimizer)
+        .with_sgd_momentum(config.sgd_momentum)
+        .with_epsilon(1e-15)
+        .init()
 }
 
 /// Per-splat world-space scale floor for the Mip-Splatting 3D filter:
@@ -118,8 +263,9 @@ pub async fn get_splat_bounds(splats: Splats, percentile: f32) -> BoundingBox {
 impl SplatTrainer {
     #[allow(unused_variables)]
     pub fn new(config: &TrainConfig, device: &Device, bounds: BoundingBox) -> Self {
-        let decay =
-            (config.lr_mean_end / config.lr_mean).powf(1.0 / config.total_train_iters as f64);
+        // The decay starts after the warmup, and still ends at lr_mean_end.
+        let decay_iters = config.total_train_iters.saturating_sub(config.warmup_steps);
+        let decay = (config.lr_mean_end / config.lr_mean).powf(1.0 / decay_iters.max(1) as f64);
         let lr_mean = ExponentialLrSchedulerConfig::new(config.lr_mean, decay);
 
         let ssim_enabled = config.ssim_weight > 0.0;
@@ -133,6 +279,9 @@ impl SplatTrainer {
         #[cfg(not(target_family = "wasm"))]
         let lpips = (config.lpips_loss_weight > 0.0).then(|| lpips::load_vgg_lpips(device));
 
+        let background = (config.background == BackgroundMode::Learned)
+            .then(|| background_param(config.base_background(), &device.clone().inner()));
+
         Self {
             config,
             sched_mean: lr_mean.init().expect("Mean lr schedule must be valid."),
@@ -142,27 +291,276 @@ impl SplatTrainer {
             bounds,
             step_count: 0,
             max_sh_degree: 0,
+            rng: StdRng::seed_from_u64(rand::random()),
             view_cams: Vec::new(),
+            exposures: Vec::new(),
+            exposure_optim: None,
+            poses: Vec::new(),
+            pose_optim: None,
+            background,
+            background_optim: None,
+            max_intersections: None,
+            peak_intersections: 0,
             #[cfg(not(target_family = "wasm"))]
             lpips,
         }
     }
 
+    /// Seed the trainer's own randomness (background noise and which splats
+    /// get sampled for growth). The GPU noise on the means comes from the
+    /// device, see `Device::seed`. With both seeded, runs are reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Stop growing the splats while rendering a view needs more than `max`
+    /// splat-tile intersections, to keep the intersection buffers within a
+    /// memory budget. Pruned splats are still replaced.
+    pub fn with_max_intersections(mut self, max: Option<u64>) -> Self {
+        self.max_intersections = max;
+        self
+    }
+
+    /// Drop the optimizer state of the splats and the refine statistics. Needed
+    /// when swapping in splats from before the last refine, e.g. to recover
+    /// from NaNs, as their count no longer matches.
+    pub fn reset_splat_state(&mut self) {
+        self.optim = None;
+        self.refine_record = None;
+    }
+
+    /// Save the splats and all training state (optimizer moments, lr schedule,
+    /// refine statistics and bounds) after `iter` steps. Uses burn's binary
+    /// file recorder, which adds a `.bin` extension to `path`.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn save_checkpoint(
+        &self,
+        splats: &Splats,
+        iter: u32,
+        path: &std::path::Path,
+    ) -> Result<(), burn::record::RecorderError> {
+        use burn::record::{BinFileRecorder, FullPrecisionSettings, Recorder};
+
+        let bounds = [self.bounds.center, self.bounds.extent]
+            .iter()
+            .flat_map(|v| v.to_array())
+            .collect();
+        let record = CheckpointRecord {
+            transforms: splats.transforms.clone(),
+            sh_coeffs: splats.sh_coeffs.clone(),
+            raw_opacities: splats.raw_opacities.clone(),
+            render_mip: splats.render_mip,
+            min_scale: splats.min_scale.clone(),
+            optim: self.optim.as_ref().map(|o| o.to_record()),
+            exposures: self.exposures.clone(),
+            exposure_optim: self.exposure_optim.as_ref().map(|o| o.to_record()),
+            poses: self.poses.clone(),
+            pose_optim: self.pose_optim.as_ref().map(|o| o.to_record()),
+            background: self.background.clone(),
+            background_optim: self.background_optim.as_ref().map(|o| o.to_record()),
+            sched_mean: self.sched_mean.to_record(),
+            refine_record: self.refine_record.as_ref().map(|r| RefineRecord {
+                refine_weight_norm: r.refine_weight_norm.clone(),
+                vis_weight: r.vis_weight.clone(),
+                max_screen_size: r.max_screen_size.clone(),
+            }),
+            bounds,
+            step_count: self.step_count,
+            max_sh_degree: self.max_sh_degree,
+            iter,
+        };
+        BinFileRecorder::<FullPrecisionSettings>::new().record(record, path.to_path_buf())
+    }
+
+    /// Restore a trainer from [`Self::save_checkpoint`]. Returns the trainer,
+    /// the splats and the iteration to continue from. View cameras aren't part
+    /// of the checkpoint, see [`Self::set_view_cams`]. Neither is the RNG state,
+    /// reseed with [`Self::with_seed`] to make the resumed run reproducible.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load_checkpoint(
+        config: &TrainConfig,
+        path: &std::path::Path,
+        device: &Device,
+    ) -> Result<(Self, Splats, u32), burn::record::RecorderError> {
+        use burn::record::{BinFileRecorder, FullPrecisionSettings, Recorder};
+
+        let record: CheckpointRecord =
+            BinFileRecorder::<FullPrecisionSettings>::new().load(path.to_path_buf(), device)?;
+
+        let [cx, cy, cz, ex, ey, ez] = record.bounds[..] else {
+            return Err(burn::record::RecorderError::DeserializeError(
+                "Invalid bounds in checkpoint".to_owned(),
+            ));
+        };
+        let bounds = BoundingBox {
+            center: glam::vec3(cx, cy, cz),
+            extent: glam::vec3(ex, ey, ez),
+        };
+
+        let mut trainer = Self::new(config, device, bounds);
+        trainer.sched_mean = trainer.sched_mean.load_record(record.sched_mean);
+        trainer.optim = record
+            .optim
+            .map(|optim| create_splat_optimizer(config).load_record(optim));
+        trainer.exposures = record.exposures;
+        trainer.exposure_optim = record
+            .exposure_optim
+            .map(|optim| create_adam_optimizer().load_record(optim));
+        trainer.poses = record.poses;
+        trainer.pose_optim = record
+            .pose_optim
+            .map(|optim| create_adam_optimizer().load_record(optim));
+        if record.background.is_some() {
+            trainer.background = record.background;
+        }
+        trainer.background_optim = record
+            .background_optim
+            .map(|optim| create_adam_optimizer().load_record(optim));
+        trainer.refine_record = record.refine_record;
+        trainer.step_count = record.step_count;
+        trainer.max_sh_degree = record.max_sh_degree;
+
+        let splats = Splats {
+            transforms: record.transforms.map(|t| t.detach().require_grad()),
+            sh_coeffs: record.sh_coeffs.map(|t| t.detach().require_grad()),
+            raw_opacities: record.raw_opacities.map(|t| t.detach().require_grad()),
+            render_mip: record.render_mip,
+            min_scale: record.min_scale,
+        };
+        Ok((trainer, splats, record.iter))
+    }
+
     /// Supply per-train-view (world center, focal-px at native res) to enable
     /// the Mip-Splatting 3D filter (gated on `config.min_scale_factor > 0`).
     pub fn set_view_cams(&mut self, view_cams: Vec<(glam::Vec3, f32)>) {
         self.view_cams = view_cams;
     }
 
-    pub async fn step(&mut self, batch: SceneBatch, splats: Splats) -> (Splats, TrainStepStats) {
-        let mut splats = splats;
+    /// The learned `[3, 4]` color correction of a train view, if any.
+    pub fn exposure(&self, view_index: usize) -> Option<Tensor<2>> {
+        self.exposures.get(view_index).map(|e| e.val())
+    }
 
-        // Track max SH degree from the first splats we see.
-        if self.step_count == 0 {
-            self.max_sh_degree = splats.sh_degree();
+    /// The average learned color correction over all train views. Held out views
+    /// have no correction of their own, this is the best guess for them.
+    pub fn mean_exposure(&self) -> Option<Tensor<2>> {
+        if self.exposures.is_empty() {
+            return None;
         }
-        self.step_count += 1;
+        let all: Vec<Tensor<2>> = self.exposures.iter().map(|e| e.val()).collect();
+        Some(Tensor::stack::<3>(all, 0).mean_dim(0).reshape([3, 4]))
+    }
+
+    /// Get the color correction of a view on the autodiff graph, creating an
+    /// identity correction the first time a view is seen.
+    fn view_exposure(&mut self, view_index: usize, device: &Device) -> ViewExposure {
+        use brush_render::burn_glue::lift_to_autodiff;
+
+        if self.exposures.len() <= view_index {
+            let inner = device.clone().inner();
+            self.exposures
+                .resize_with(view_index + 1, || identity_param(&inner));
+        }
+        let (id, correction, _) = self.exposures[view_index].clone().consume();
+        ViewExposure {
+            correction: Param::initialized(id, lift_to_autodiff(correction).require_grad()),
+        }
+    }
+
+    /// A train view's camera with its learned pose correction applied. Returns
+    /// the camera unchanged when the view has no correction.
+    pub async fn refined_camera(&self, view_index: usize, camera: &Camera) -> Camera {
+        let Some(pose) = self.poses.get(view_index) else {
+            return *camera;
+        };
+        let delta = pose.val().into_data_async().await.ok();
+        let delta = delta.and_then(|d| d.into_vec::<f32>().ok());
+        delta.map_or(*camera, |delta| refine_camera(camera, &delta))
+    }
+
+    /// Get the pose correction of a view on the autodiff graph, creating an
+    /// identity correction the first time a view is seen.
+    fn view_pose(&mut self, view_index: usize, device: &Device) -> ViewPose {
+        use brush_render::burn_glue::lift_to_autodiff;
+
+        if self.poses.len() <= view_index {
+            let inner = device.clone().inner();
+            self.poses
+                .resize_with(view_index + 1, || identity_pose_param(&inner));
+        }
+        let (id, delta, _) = self.poses[view_index].clone().consume();
+        ViewPose {
+            delta: Param::initialized(id, lift_to_autodiff(delta).require_grad()),
+        }
+    }
 
+    /// The background to store with exported splats. `None` when training with
+    /// a random background, as there's no single color to show them on.
+    pub async fn export_background(&self) -> Option<glam::Vec3> {
+        let Some(background) = &self.background else {
+            return self.config.initial_background();
+        };
+        let color = background.val().into_data_async().await.ok()?;
+        let color = color.into_vec::<f32>().ok()?;
+        Some(glam::Vec3::from_slice(&color).clamp(glam::Vec3::ZERO, glam::Vec3::ONE))
+    }
+
+    /// The learned background on the autodiff graph.
+    fn learned_background(&self) -> Option<LearnedBackground> {
+        use brush_render::burn_glue::lift_to_autodiff;
+
+        let (id, color, _) = self.background.clone()?.consume();
+        Some(LearnedBackground {
+            color: Param::initialized(id, lift_to_autodiff(color).require_grad()),
+        })
+    }
+
+    /// The SH degree rendered in the next step, see [`TrainConfig::sh_warmup_every`].
+    pub fn active_sh_degree(&self) -> u32 {
+        match self.config.sh_warmup_every {
+            Some(every) => (self.step_count / every).min(self.max_sh_degree),
+            None => self.max_sh_degree,
+        }
+    }
+
+    /// The (unscaled) mean learning rate for step `iter`. Follows the warmup
+    /// ramp first, and only then starts stepping the decay schedule.
+    fn next_lr_mean(&mut self, iter: u32) -> f64 {
+        if iter < self.config.warmup_steps {
+            self.config.lr_mean * self.config.lr_warmup(iter)
+        } else {
+            self.sched_mean.step()
+        }
+    }
+
+    /// Background to render the next view on, with the configured noise.
+    fn sample_background(&mut self) -> glam::Vec3 {
+        let noise = self.config.background_noise_strength;
+        let rng = &mut self.rng;
+        // A learned background is composited onto the render, so render on black.
+        match self.config.background {
+            BackgroundMode::Color => {
+                sample_background_color(self.config.base_background(), noise, rng)
+            }
+            BackgroundMode::Black => sample_background_color(glam::Vec3::ZERO, noise, rng),
+            BackgroundMode::White => sample_background_color(glam::Vec3::ONE, noise, rng),
+            BackgroundMode::Random => sample_background_color(glam::Vec3::splat(0.5), 0.5, rng),
+            BackgroundMode::Learned => glam::Vec3::ZERO,
+        }
+    }
+
+    /// Render a single view and compute its loss, on the autodiff graph.
+    async fn view_loss(
+        &self,
+        batch: SceneBatch,
+        background: glam::Vec3,
+        splats: &Splats,
+        active_sh_degree: u32,
+        exposure: Option<&ViewExposure>,
+        pose: Option<&ViewPose>,
+        learned_background: Option<&LearnedBackground>,
+    ) -> ViewLoss {
         let [img_h, img_w] = batch.img_size();
         let camera = batch.camera;
 
@@ -178,86 +576,280 @@ impl SplatTrainer {
         let gt_packed: Tensor<2, Int> =
             Tensor::from_data(batch.img_packed, &device.clone().inner());
         let img_size = glam::uvec2(img_w as u32, img_h as u32);
-        let base = &self.config.background_color;
-        let base_bg = glam::Vec3::new(base[0], base[1], base[2]);
-        let background = sample_background_color(base_bg, self.config.background_noise_strength);
 
-        let median_scale = self.bounds.median_size();
-
-        let (mut grads, visible, num_visible, loss_inner) = {
-            // The splats already carry their 3D-filter floor (set at refine);
-            // the render path folds it in. Optimizer/refine work on raw params.
-            let render_input = splats.clone();
-            let diff_out = render_splats(render_input, &camera, img_size, background)
-                .instrument(trace_span!("Forward"))
-                .await;
+        // The splats already carry their 3D-filter floor (set at refine);
+        // the render path folds it in. Optimizer/refine work on raw params.
+        let mut render_input = splats.clone();
+        if active_sh_degree < splats.sh_degree() {
+            // Only render the active SH bands. The backward of the slice
+            // leaves the gradient of the other bands at zero, so they don't
+            // move and their Adam moments stay clean until they're enabled.
+            let num_coeffs = sh_coeffs_for_degree(active_sh_degree) as usize;
+            render_input.sh_coeffs = Param::initialized(
+                splats.sh_coeffs.id,
+                splats.sh_coeffs.val().slice(s![.., 0..num_coeffs, ..]),
+            );
+        }
+        if let Some(pose) = pose {
+            // Move the splats instead of the camera, see `crate::pose`.
+            render_input.transforms = Param::initialized(
+                splats.transforms.id,
+                apply_pose_correction(render_input.transforms.val(), &camera, pose.delta.val()),
+            );
+        }
+        let depth_input = (self.config.depth_loss_weight > 0.0 && batch.depth.is_some())
+            .then(|| render_input.clone());
+        let diff_out = render_splats(render_input, &camera, img_size, background)
+            .instrument(trace_span!("Forward"))
+            .await;
+
+        let pred_image = match learned_background {
+            Some(background) => composite_background(diff_out.img, background.color.val()),
+            None => diff_out.img,
+        };
+        // Correct the render to match this view's exposure, so the splats
+        // don't have to.
+        let pred_image = match exposure {
+            Some(exposure) => apply_color_correction(pred_image, exposure.correction.val()),
+            None => pred_image,
+        };
+        // HDR views are compared after tone mapping, see `brush_dataset::hdr`.
+        // The packed GT already is tone mapped.
+        let hdr = batch.hdr.map(|hdr| {
+            let gt: Tensor<3> = Tensor::from_data(hdr.img, &device);
+            (gt, hdr.tone_mapping)
+        });
+        let pred_image = match &hdr {
+            Some((_, tone_mapping)) => {
+                let rgb = tone_mapping.apply_tensor(pred_image.clone().slice(s![.., .., 0..3]));
+                pred_image.slice_assign(s![.., .., 0..3], rgb)
+            }
+            None => pred_image,
+        };
+        let refine_weight_holder = diff_out.refine_weight_holder;
+        let visible = diff_out.visible;
+        let max_radius = diff_out.max_radius;
+
+        // RGB loss is `(1 - w) * L1 + (-w) * SSIM` per pixel. Bg
+        // compositing always runs in the kernel; for synthesised opaque
+        // alpha or zero bg it's a no-op. Mask multiplies the loss-map
+        // by `gt.a`; for synthesised opaque alpha that's a no-op too.
+        // Alpha matching needs a real alpha source (synthesised
+        // a = 1 would pull predicted alpha to fully opaque); we feed
+        // `pred` with 4 channels and the kernel's `c == 3` workgroup
+        // emits `|pred.a - gt.a|` into the alpha channel.
+        let masked_alpha = batch.alpha_mode == AlphaMode::Masked;
+        let (l1_w, ssim_w) = if self.ssim_enabled {
+            (1.0 - self.config.ssim_weight, -self.config.ssim_weight)
+        } else {
+            (1.0, 0.0)
+        };
+        let do_alpha_match = has_alpha && !masked_alpha && self.config.match_alpha_weight > 0.0;
+        // Only composite when there's a real alpha channel and a non-zero
+        // bg to mix in; the kernel skips the per-pixel `(1-a)*bg` math
+        // entirely when this is None. With a learned background the GT
+        // stays on black, so transparent regions pull the learned color to black.
+        let composite_bg = (has_alpha && background != glam::Vec3::ZERO).then_some(background);
+        // MS-SSIM isn't a per-pixel quantity, so it's added on below instead.
+        let ms_ssim = self.ssim_enabled && self.config.ssim_mode == SsimMode::MsSsim;
+        let cfg = ImageLossConfig {
+            // For HDR views the L1 term is computed below, on the linear GT.
+            l1_weight: if hdr.is_some() { 0.0 } else { l1_w },
+            ssim_weight: if ms_ssim { 0.0 } else { ssim_w },
+            composite_bg,
+            mask: masked_alpha,
+            ssim_window: self.config.ssim_window(),
+        };
+        let pred_for_loss = if do_alpha_match {
+            pred_image.clone()
+        } else {
+            pred_image.clone().slice(s![.., .., 0..3])
+        };
+        let loss_map = image_loss(pred_for_loss, gt_packed.clone(), cfg);
+
+        let mut loss = if do_alpha_match {
+            let rgb = loss_map.clone().slice(s![.., .., 0..3]).mean();
+            let alpha = loss_map.slice(s![.., .., 3..4]).mean();
+            rgb + alpha * self.config.match_alpha_weight
+        } else {
+            loss_map.mean()
+        };
+        if ms_ssim {
+            loss = loss + brush_loss::ms_ssim(pred_image.clone(), gt_packed.clone(), cfg) * ssim_w;
+        }
 
-            let pred_image = diff_out.img;
-            let refine_weight_holder = diff_out.refine_weight_holder;
-            let visible = diff_out.visible;
-            let max_radius = diff_out.max_radius;
-
-            // RGB loss is `(1 - w) * L1 + (-w) * SSIM` per pixel. Bg
-            // compositing always runs in the kernel; for synthesised opaque
-            // alpha or zero bg it's a no-op. Mask multiplies the loss-map
-            // by `gt.a`; for synthesised opaque alpha that's a no-op too.
-            // Alpha matching needs a real alpha source (synthesised
-            // a = 1 would pull predicted alpha to fully opaque); we feed
-            // `pred` with 4 channels and the kernel's `c == 3` workgroup
-            // emits `|pred.a - gt.a|` into the alpha channel.
-            let masked_alpha = batch.alpha_mode == AlphaMode::Masked;
-            let (l1_w, ssim_w) = if self.ssim_enabled {
-                (1.0 - self.config.ssim_weight, -self.config.ssim_weight)
-            } else {
-                (1.0, 0.0)
-            };
-            let do_alpha_match = has_alpha && !masked_alpha && self.config.match_alpha_weight > 0.0;
-            // Only composite when there's a real alpha channel and a non-zero
-            // bg to mix in; the kernel skips the per-pixel `(1-a)*bg` math
-            // entirely when this is None.
-            let composite_bg = (has_alpha && background != glam::Vec3::ZERO).then_some(background);
-            let cfg = ImageLossConfig {
-                l1_weight: l1_w,
-                ssim_weight: ssim_w,
-                composite_bg,
-                mask: masked_alpha,
-            };
-            let pred_for_loss = if do_alpha_match {
-                pred_image.clone()
-            } else {
-                pred_image.clone().slice(s![.., .., 0..3])
+        // The packed GT is quantized & clamped to [0, 1], so compare the
+        // linear GT here instead, which keeps the highlights above 1.
+        if let Some((gt, tone_mapping)) = hdr {
+            let gt_rgb = gt.clone().slice(s![.., .., 0..3]);
+            let gt_alpha = gt.slice(s![.., .., 3..4]);
+            let gt_rgb = match composite_bg {
+                Some(bg) => {
+                    let bg =
+                        Tensor::<1>::from_floats([bg.x, bg.y, bg.z], &device).reshape([1, 1, 3]);
+                    gt_rgb + (gt_alpha.clone().neg() + 1.0) * bg
+                }
+                None => gt_rgb,
             };
-            let loss_map = image_loss(pred_for_loss, gt_packed.clone(), cfg);
-
-            // `loss` is only reassigned by the LPIPS path below, which is
-            // compiled out on wasm — so `mut` is unused there.
-            #[cfg_attr(target_family = "wasm", allow(unused_mut))]
-            let mut loss = if do_alpha_match {
-                let rgb = loss_map.clone().slice(s![.., .., 0..3]).mean();
-                let alpha = loss_map.slice(s![.., .., 3..4]).mean();
-                rgb + alpha * self.config.match_alpha_weight
+            let diff = (pred_image.clone().slice(s![.., .., 0..3])
+                - tone_mapping.apply_tensor(gt_rgb))
+            .abs();
+            let diff = if masked_alpha { diff * gt_alpha } else { diff };
+            loss = loss + diff.mean() * l1_w;
+        }
+
+        // LPIPS still needs an f32 RGB tensor for VGG. Materialising it
+        // here costs ~99 MB at 4K, only when LPIPS is enabled.
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(lpips) = &self.lpips {
+            let gt_rgb = brush_loss::unpack_gt_rgb(gt_packed.clone(), composite_bg);
+            let gt_rgb_diff: Tensor<3> = Tensor::from_inner(gt_rgb);
+            let pred_rgb = pred_image.clone().slice(s![.., .., 0..3]).unsqueeze_dim(0);
+            let gt_rgb_diff = gt_rgb_diff.unsqueeze_dim(0);
+
+            let lpips_loss = if masked_alpha && has_alpha {
+                // Don't pull the masked out regions towards the GT, same as
+                // the mask on the image loss.
+                let mask = gt_packed
+                    .clone()
+                    .bitwise_right_shift_scalar(24)
+                    .bitwise_and_scalar(0xff)
+                    .float()
+                    / 255.0;
+                let mask: Tensor<3> = Tensor::from_inner(mask.unsqueeze_dim(0));
+                lpips.lpips_masked(pred_rgb, gt_rgb_diff, mask)
             } else {
-                loss_map.mean()
+                lpips.lpips(pred_rgb, gt_rgb_diff)
             };
+            loss = loss + lpips_loss * self.config.lpips_loss_weight;
+        }
 
-            // LPIPS still needs an f32 RGB tensor for VGG. Materialising it
-            // here costs ~99 MB at 4K, only when LPIPS is enabled.
-            #[cfg(not(target_family = "wasm"))]
-            if let Some(lpips) = &self.lpips {
-                let gt_rgb = brush_loss::unpack_gt_rgb(gt_packed.clone(), composite_bg);
-                let gt_rgb_diff: Tensor<3> = Tensor::from_inner(gt_rgb);
-                loss = loss
-                    + lpips.lpips(
-                        pred_image.clone().slice(s![.., .., 0..3]).unsqueeze_dim(0),
-                        gt_rgb_diff.unsqueeze_dim(0),
-                    ) * self.config.lpips_loss_weight;
+        if let Some((gt_depth, input)) = batch.depth.zip(depth_input)
+            && let Some(depth_loss) = depth_loss(input, &camera, img_size, gt_depth)
+                .instrument(trace_span!("Depth loss"))
+                .await
+        {
+            loss = loss + depth_loss * self.config.depth_loss_weight;
+        }
+
+        ViewLoss {
+            loss,
+            refine_weight_holder,
+            visible,
+            max_radius,
+            num_visible: diff_out.num_visible,
+            num_intersections: diff_out.num_intersections,
+        }
+    }
+
+    pub async fn step(&mut self, batch: SceneBatch, splats: Splats) -> (Splats, TrainStepStats) {
+        self.step_batch(vec![batch], splats).await
+    }
+
+    /// Take a single optimizer step on the average loss of several views, see
+    /// [`TrainConfig::batch_size`]. The views are rendered one after another
+    /// and can have different resolutions, but the autodiff graphs of all views
+    /// stay alive until the backward pass, so memory use grows with the number of views.
+    pub async fn step_batch(
+        &mut self,
+        batches: Vec<SceneBatch>,
+        splats: Splats,
+    ) -> (Splats, TrainStepStats) {
+        assert!(!batches.is_empty(), "Need at least one view to take a step");
+        let mut splats = splats;
+
+        // Track max SH degree from the first splats we see.
+        if self.step_count == 0 {
+            self.max_sh_degree = splats.sh_degree();
+        }
+        let active_sh_degree = self.active_sh_degree();
+        let lr_mean = self.next_lr_mean(self.step_count);
+        let lr_scale = self.config.lr_scale * self.config.lr_warmup(self.step_count);
+        self.step_count += 1;
+
+        let device = splats.device();
+        let learned_background = self.learned_background();
+        let median_scale = self.bounds.median_size();
+
+        // A view can show up more than once in a batch, it then uses the same correction.
+        let mut exposures = HashMap::new();
+        let mut poses = HashMap::new();
+        for batch in &batches {
+            let view_index = batch.view_index;
+            if self.config.learn_exposure && !exposures.contains_key(&view_index) {
+                let exposure = self.view_exposure(view_index, &device);
+                exposures.insert(view_index, exposure);
+            }
+            if self.config.pose_refine && !poses.contains_key(&view_index) {
+                let pose = self.view_pose(view_index, &device);
+                poses.insert(view_index, pose);
             }
+        }
+
+        let num_views = batches.len();
+        let view_indices: Vec<usize> = batches.iter().map(|b| b.view_index).collect();
+        let mut views = Vec::with_capacity(num_views);
+        for batch in batches {
+            let view_index = batch.view_index;
+            let background = self.sample_background();
+            let view = self
+                .view_loss(
+                    batch,
+                    background,
+                    &splats,
+                    active_sh_degree,
+                    exposures.get(&view_index),
+                    poses.get(&view_index),
+                    learned_background.as_ref(),
+                )
+                .await;
+            views.push(view);
+        }
+        let num_intersections = views.iter().map(|v| v.num_intersections).max().unwrap_or(0);
+        self.peak_intersections = self.peak_intersections.max(num_intersections);
+
+        let (mut grads, visible, num_visible, loss_inner) = {
+            let loss = views
+                .iter()
+                .map(|v| v.loss.clone())
+                .reduce(|a, b| a + b)
+                .expect("Need at least one view")
+                / num_views as f32;
+            let loss = if self.config.scale_reg_weight > 0.0 {
+                loss + scale_ratio_penalty(splats.log_scales(), self.config.max_scale_ratio)
+                    * self.config.scale_reg_weight
+            } else {
+                loss
+            };
 
             // Strip the autodiff graph off the loss so consumers can read the
             // scalar later without keeping the backward pass alive.
             let loss_inner = loss.clone().inner();
+
+            let check_every = self.config.nan_check_every;
+            if check_every > 0 && self.step_count.is_multiple_of(check_every) {
+                let non_finite_views = non_finite_views(&loss_inner, &views, &view_indices).await;
+                if !non_finite_views.is_empty() {
+                    // Skip the backward pass and optimizer, so neither the
+                    // splats nor the optimizer state see the NaNs.
+                    let stats = TrainStepStats {
+                        num_visible: 0,
+                        num_intersections,
+                        lr_mean: lr_mean * median_scale as f64,
+                        lr_rotation: self.config.lr_rotation,
+                        lr_scale,
+                        lr_coeffs: self.config.lr_coeffs_dc,
+                        lr_opac: self.config.lr_opac,
+                        loss: loss_inner,
+                        non_finite_views,
+                    };
+                    return (splats, stats);
+                }
+            }
             let mut grads = splats.bwd_validate(loss).await;
 
+            let mut visible: Option<Tensor<1>> = None;
+            let mut num_visible = 0;
             trace_span!("Housekeeping").in_scope(|| {
                 // Refine state accumulates on the inner (non-autodiff) device
                 // so we can mix it with `.inner()`-stripped gradients/aux
@@ -265,20 +857,35 @@ impl SplatTrainer {
                 // the residual `checkpointing` flag that bare `.inner()`
                 // leaves behind (see `brush_render::burn_glue`).
                 use brush_render::burn_glue::detach_autodiff;
-                let refine_weight = refine_weight_holder
-                    .grad_remove(&mut grads)
-                    .expect("XY gradients need to be calculated.");
                 let device = splats.device().inner();
-                let record = self
-                    .refine_record
-                    .get_or_insert_with(|| RefineRecord::new(splats.num_splats(), &device));
-                // `visible` / `max_radius` already arrive on the inner backend;
-                // only the freshly-extracted `refine_weight` gradient needs the
-                // autodiff stripped off.
-                record.gather_stats(detach_autodiff(refine_weight), visible.clone(), max_radius);
+                for view in views {
+                    // Undo the loss averaging, so the refine weights don't depend on the batch size.
+                    let refine_weight = view
+                        .refine_weight_holder
+                        .grad_remove(&mut grads)
+                        .expect("XY gradients need to be calculated.")
+                        * num_views as f32;
+                    let record = self
+                        .refine_record
+                        .get_or_insert_with(|| RefineRecord::new(splats.num_splats(), &device));
+                    // `visible` / `max_radius` already arrive on the inner backend;
+                    // only the freshly-extracted `refine_weight` gradient needs the
+                    // autodiff stripped off.
+                    record.gather_stats(
+                        detach_autodiff(refine_weight),
+                        view.visible.clone(),
+                        view.max_radius,
+                    );
+                    visible = Some(match visible.take() {
+                        Some(visible) => visible.max_pair(view.visible),
+                        None => view.visible,
+                    });
+                    num_visible = num_visible.max(view.num_visible);
+                }
             });
+            let visible = visible.expect("Need at least one view");
 
-            (grads, visible, diff_out.num_visible, loss_inner)
+            (grads, visible, num_visible, loss_inner)
         };
 
         // OptimizerAdaptor strips autodiff before calling SimpleOptimizer::step,
@@ -298,9 +905,9 @@ impl SplatTrainer {
                 let sh_lr_scales = Tensor::<1>::from_floats(scales.as_slice(), &opt_device)
                     .reshape([1, num_coeffs as i32, 1]);
 
-                create_optimizer_from_config().load_record(HashMap::from([(
+                create_splat_optimizer(&self.config).load_record(HashMap::from([(
                     splats.sh_coeffs.id,
-                    AdaptorRecord::from_state(AdamState {
+                    AdaptorRecord::from_state(OptimState {
                         momentum: None,
                         scaling: Some(sh_lr_scales),
                         reduce_moment_2: true,
@@ -308,7 +915,7 @@ impl SplatTrainer {
                 )]))
             });
 
-        let lr_mean = self.sched_mean.step() * median_scale as f64;
+        let lr_mean = lr_mean * median_scale as f64;
 
         // Update per-component LR scaling for the transforms param.
         // transforms layout: means(3) + rotations(4) + log_scales(3)
@@ -324,24 +931,61 @@ impl SplatTrainer {
                 self.config.lr_rotation as f32,
                 self.config.lr_rotation as f32,
                 self.config.lr_rotation as f32,
-                self.config.lr_scale as f32,
-                self.config.lr_scale as f32,
-                self.config.lr_scale as f32,
+                lr_scale as f32,
+                lr_scale as f32,
+                lr_scale as f32,
             ];
-            let transform_scaling =
+            let mut transform_scaling =
                 Tensor::<1>::from_floats(lr_values.as_slice(), &opt_device).reshape([1, 10]);
+            if self.config.adaptive_mean_lr
+                && let Some(record) = &self.refine_record
+            {
+                let factors = record
+                    .mean_lr_factors()
+                    .unsqueeze_dim::<2>(1)
+                    .repeat_dim(1, 3);
+                let others = Tensor::ones([splats.num_splats() as usize, 7], &opt_device);
+                transform_scaling = Tensor::cat(vec![factors, others], 1) * transform_scaling;
+            }
             let mut record = optimizer.to_record();
             let existing = record.remove(&splats.transforms.id);
             let momentum = existing.and_then(|r| r.into_state::<2>().momentum);
             record.insert(
                 splats.transforms.id,
-                AdaptorRecord::from_state(AdamState {
+                AdaptorRecord::from_state(OptimState {
                     momentum,
                     scaling: Some(transform_scaling),
                     reduce_moment_2: false,
                 }),
             );
-            *optimizer = create_optimizer_from_config().load_record(record);
+            *optimizer = create_splat_optimizer(&self.config).load_record(record);
+        }
+
+        for (view_index, exposure) in exposures {
+            let optimizer = self
+                .exposure_optim
+                .get_or_insert_with(create_adam_optimizer);
+            let grad_exposure =
+                GradientsParams::from_params(&mut grads, &exposure, &[exposure.correction.id]);
+            let exposure = optimizer.step(self.config.lr_exposure, exposure, grad_exposure);
+            self.exposures[view_index] = exposure.valid().correction;
+        }
+
+        for (view_index, pose) in poses {
+            let optimizer = self.pose_optim.get_or_insert_with(create_adam_optimizer);
+            let grad_pose = GradientsParams::from_params(&mut grads, &pose, &[pose.delta.id]);
+            let pose = optimizer.step(self.config.lr_pose, pose, grad_pose);
+            self.poses[view_index] = pose.valid().delta;
+        }
+
+        if let Some(background) = learned_background {
+            let optimizer = self
+                .background_optim
+                .get_or_insert_with(create_adam_optimizer);
+            let grad_background =
+                GradientsParams::from_params(&mut grads, &background, &[background.color.id]);
+            let background = optimizer.step(self.config.lr_background, background, grad_background);
+            self.background = Some(background.valid().color);
         }
 
         splats = trace_span!("Optimizer step").in_scope(|| {
@@ -400,12 +1044,14 @@ impl SplatTrainer {
 
         let stats = TrainStepStats {
             num_visible,
+            num_intersections,
             lr_mean,
             lr_rotation: self.config.lr_rotation,
-            lr_scale: self.config.lr_scale,
+            lr_scale,
             lr_coeffs: self.config.lr_coeffs_dc,
             lr_opac: self.config.lr_opac,
             loss: loss_inner,
+            non_finite_views: vec![],
         };
 
         (splats, stats)
@@ -520,6 +1166,11 @@ impl SplatTrainer {
 
         let (mut splats, refiner, pruned_count) =
             prune_points(splats, &mut record, refiner, prune_mask).await;
+
+        let growth_skipped = self
+            .max_intersections
+            .is_some_and(|max| self.peak_intersections as u64 > max);
+        self.peak_intersections = 0;
         let mut split_inds = HashSet::new();
 
         // Always replace dead gaussians, so that the pruned budget is reused.
@@ -535,7 +1186,8 @@ impl SplatTrainer {
                 .expect("Failed to get weights")
                 .into_vec::<f32>()
                 .expect("Failed to read weights");
-            let resampled_inds = multinomial_sample(&resampled_weights, pruned_count);
+            let resampled_inds =
+                multinomial_sample(&resampled_weights, pruned_count, &mut self.rng);
             split_inds.extend(resampled_inds);
         }
 
@@ -544,7 +1196,7 @@ impl SplatTrainer {
         // shrink the children down to `split_at_screen_size` on screen — see
         // `refine_splats`. Capped by the remaining `max_splats` budget.
         let pre_oversized = split_inds.len();
-        if self.config.split_at_screen_size > 0.0 {
+        if self.config.split_at_screen_size > 0.0 && !growth_skipped {
             let oversized = refiner.above_screen_size(self.config.split_at_screen_size);
             let oversized_inds = oversized.argwhere_async().await;
             if oversized_inds.dims()[0] > 0 {
@@ -572,7 +1224,7 @@ impl SplatTrainer {
         let num_split_oversized = (split_inds.len() - pre_oversized) as u32;
 
         let pre_high_grad = split_inds.len();
-        if iter < self.config.growth_stop_iter {
+        if iter < self.config.growth_stop_iter && !growth_skipped {
             let above_threshold = refiner.above_threshold(self.config.growth_grad_threshold);
 
             let threshold_count = above_threshold
@@ -604,7 +1256,7 @@ impl SplatTrainer {
                     .expect("Failed to get weights")
                     .into_vec::<f32>()
                     .expect("Failed to read weights");
-                let growth_inds = multinomial_sample(&weights, grow_count);
+                let growth_inds = multinomial_sample(&weights, grow_count, &mut self.rng);
                 split_inds.extend(growth_inds);
             }
         }
@@ -614,7 +1266,16 @@ impl SplatTrainer {
         // Per-splat max on-screen extent, used by `refine_splats` to cap the
         // split shrink so oversized splats' children land at `split_at_screen_size`.
         let screen_sizes = refiner.max_screen_size.clone();
-        splats = self.refine_splats(&device, record, splats, split_inds, screen_sizes, iter);
+        let opacity_reset = self.opacity_reset_due(iter);
+        splats = self.refine_splats(
+            &device,
+            record,
+            splats,
+            split_inds,
+            screen_sizes,
+            iter,
+            opacity_reset,
+        );
 
         // Update current bounds based on the splats.
         self.bounds = get_splat_bounds(splats.clone(), BOUND_PERCENTILE).await;
@@ -643,19 +1304,33 @@ impl SplatTrainer {
                 num_split_high_grad,
                 num_pruned: pruned_count,
                 num_pruned_non_finite,
+                opacity_reset,
+                growth_skipped,
                 total_splats: splat_count,
             },
         )
     }
 
+    /// Whether the refine at `iter` should reset opacities. Refines run every
+    /// `refine_every` steps, so this picks the first one on or after each
+    /// multiple of `opacity_reset_every`.
+    fn opacity_reset_due(&self, iter: u32) -> bool {
+        self.config.opacity_reset_every.is_some_and(|every| {
+            iter >= every
+                && iter < self.config.growth_stop_iter
+                && iter % every < self.config.refine_every
+        })
+    }
+
     fn refine_splats(
         &mut self,
         device: &Device,
-        mut record: HashMap<ParamId, AdaptorRecord<AdamScaled>>,
+        mut record: HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>,
         mut splats: Splats,
         split_inds: HashSet<i32>,
         screen_sizes: Tensor<1>,
         iter: u32,
+        opacity_reset: bool,
     ) -> Splats {
         let refine_count = split_inds.len();
 
@@ -679,13 +1354,17 @@ impl SplatTrainer {
 
             let cur_scales = cur_log_scale.clone().exp();
 
-            let cur_opac = sigmoid(cur_raw_opac.clone());
-            let inv_opac: Tensor<1> = 1.0 - cur_opac;
-            // Post-split child opacity as a power law in transmittance,
-            // p = 0.5 would keep the transmittance for cloning splats but as we offset them
-            // choose a higher p.
-            let new_opac: Tensor<1> = 1.0 - inv_opac.powf_scalar(FRAC_1_SQRT_2);
-            let new_raw_opac = inv_sigmoid(new_opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY));
+            let new_raw_opac = if self.config.split_keep_opacity {
+                cur_raw_opac.clone()
+            } else {
+                let cur_opac = sigmoid(cur_raw_opac.clone());
+                let inv_opac: Tensor<1> = 1.0 - cur_opac;
+                // Post-split child opacity as a power law in transmittance,
+                // p = 0.5 would keep the transmittance for cloning splats but as we offset them
+                // choose a higher p.
+                let new_opac: Tensor<1> = 1.0 - inv_opac.powf_scalar(FRAC_1_SQRT_2);
+                inv_sigmoid(new_opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY))
+            };
 
             // Smooth covariance-aware split. Per-axis shrink + mass-conserving
             // deterministic offset (one child at +offset, the other at -offset).
@@ -695,10 +1374,14 @@ impl SplatTrainer {
             let max_scale_sq = cur_scales_sq.clone().max_dim(1).clamp_min(1e-30);
             let ratio = cur_scales_sq / max_scale_sq;
             // Max-axis shrink factor `k` (per splat). The standard split uses
-            // 1/√2 (mass-conserving). When `split_at_screen_size` is set, splats
-            // that are too big on screen shrink harder so their children land at
-            // (at most) the cap: `k = min(1/√2, split_at_screen_size / screen)`.
-            // Splats already within √2× of the cap are unaffected (min → 1/√2).
+            // `split_scale_factor`, 1/√2 by default (mass-conserving). When
+            // `split_at_screen_size` is set, splats that are too big on screen
+            // shrink harder so their children land at (at most) the cap:
+            // `k = min(split_scale_factor, split_at_screen_size / screen)`.
+            let split_k = self
+                .config
+                .split_scale_factor
+                .clamp(MIN_SPLIT_SCALE_FACTOR, MAX_SPLIT_SCALE_FACTOR);
             let k_per_axis: Tensor<2> = if self.config.split_at_screen_size > 0.0 {
                 let k_max = screen_sizes
                     .select(0, refine_inds.clone())
@@ -706,10 +1389,10 @@ impl SplatTrainer {
                     .clamp_min(1e-6)
                     .recip()
                     .mul_scalar(self.config.split_at_screen_size)
-                    .clamp_max(FRAC_1_SQRT_2);
+                    .clamp_max(split_k);
                 -(ratio * (-k_max + 1.0)) + 1.0
             } else {
-                -(ratio * (1.0_f32 - FRAC_1_SQRT_2)) + 1.0
+                -(ratio * (1.0 - split_k)) + 1.0
             };
             let offset_factor = (-k_per_axis.clone().powi_scalar(2) + 1.0)
                 .clamp_min(0.0)
@@ -802,14 +1485,32 @@ impl SplatTrainer {
             inv_sigmoid(new_opac.clamp(1e-12, 1.0 - 1e-12))
         });
 
-        self.optim = Some(create_optimizer_from_config().load_record(record));
+        if opacity_reset {
+            use brush_render::burn_glue::detach_autodiff;
+            let max_raw_opac = (RESET_OPACITY / (1.0 - RESET_OPACITY)).ln();
+            // The Adam moments of the clamped splats point towards their old
+            // opacity, so clear them along with the reset.
+            let keep_moments = detach_autodiff(
+                splats
+                    .raw_opacities
+                    .val()
+                    .lower_equal_elem(max_raw_opac)
+                    .float(),
+            );
+            splats.raw_opacities = splats.raw_opacities.map(|f| f.clamp_max(max_raw_opac));
+            map_opt(splats.raw_opacities.id, &mut record, &|m: Tensor<1>| {
+                m * keep_moments.clone()
+            });
+        }
+
+        self.optim = Some(create_splat_optimizer(&self.config).load_record(record));
         splats
     }
 }
 
 fn map_splats_and_opt(
     mut splats: Splats,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled>>,
+    record: &mut HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>,
     map_transforms: impl FnOnce(Tensor<2>) -> Tensor<2>,
     map_sh_coeffs: impl FnOnce(Tensor<3>) -> Tensor<3>,
     map_opac: impl FnOnce(Tensor<1>) -> Tensor<1>,
@@ -832,10 +1533,10 @@ fn map_splats_and_opt(
 /// `reduce_moment_2`.
 fn map_opt<const D: usize>(
     param_id: ParamId,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled>>,
+    record: &mut HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>,
     map_fn: &impl Fn(Tensor<D>) -> Tensor<D>,
 ) {
-    let mut state: AdamState<D> = record
+    let mut state: OptimState<D> = record
         .remove(&param_id)
         .expect("failed to get optimizer record")
         .into_state();
@@ -855,7 +1556,7 @@ fn map_opt<const D: usize>(
 //   mask: bool[n]. If True, prune this Gaussian.
 async fn prune_points(
     mut splats: Splats,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled>>,
+    record: &mut HashMap<ParamId, AdaptorRecord<ScaledOptimizer>>,
     mut refiner: RefineRecord,
     prune: Tensor<1, Bool>,
 ) -> (Splats, RefineRecord, u32) {
@@ -900,13 +1601,33 @@ async fn prune_points(
     (splats, refiner, start_splats - new_points)
 }
 
+/// The views in `view_indices` with a non-finite loss. Only reads the per
+/// view losses back when the mean `loss` isn't finite.
+async fn non_finite_views(
+    loss: &Tensor<1>,
+    views: &[ViewLoss],
+    view_indices: &[usize],
+) -> Vec<usize> {
+    let is_finite = |v: f32| v.is_finite();
+    let mean = loss.clone().into_scalar_async::<f32>().await;
+    if mean.is_ok_and(is_finite) {
+        return vec![];
+    }
+    let mut non_finite = vec![];
+    for (view, &view_index) in views.iter().zip(view_indices) {
+        let view_loss = view.loss.clone().inner().into_scalar_async::<f32>().await;
+        if !view_loss.is_ok_and(is_finite) {
+            non_finite.push(view_index);
+        }
+    }
+    non_finite
+}
+
 /// Sample a background color: base + uniform noise in [-strength, +strength], clamped to [0, 1].
-fn sample_background_color(base: glam::Vec3, strength: f32) -> glam::Vec3 {
+fn sample_background_color(base: glam::Vec3, strength: f32, rng: &mut StdRng) -> glam::Vec3 {
     if strength <= 0.0 {
         return base.clamp(glam::Vec3::ZERO, glam::Vec3::ONE);
     }
-    use rand::RngExt as _;
-    let mut rng = rand::rng();
     let noise = glam::Vec3::new(
         rng.random_range(-strength..strength),
         rng.random_range(-strength..strength),
@@ -1,19 +1,24 @@
 mod data_source;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fmt::Debug,
     io::{self, Cursor, Error},
     path::{Path, PathBuf},
-    sync::Arc,
+    pin::Pin,
+    sync::{Arc, RwLock, RwLockReadGuard},
+    task::{Context, Poll, ready},
 };
 
 use async_zip::base::read::stream::ZipFileReader;
 use path_clean::PathClean;
 use thiserror::Error;
 use tokio::{
-    io::{AsyncBufRead, AsyncRead, AsyncReadExt, BufReader},
-    sync::Mutex,
+    io::{
+        AsyncBufRead, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader, ReadBuf,
+        SeekFrom,
+    },
+    sync::{Mutex, watch},
 };
 use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 
@@ -32,7 +37,88 @@ impl<T: Send> SendNotWasm for T {}
 pub trait DynRead: AsyncBufRead + SendNotWasm + Unpin {}
 impl<T: AsyncBufRead + SendNotWasm + Unpin> DynRead for T {}
 
-type StreamingReader = Arc<Mutex<Option<Box<dyn DynRead>>>>;
+/// A reader that can also seek, which allows reading zip entries on demand.
+pub trait DynSeekRead: DynRead + AsyncSeek {}
+impl<T: DynRead + AsyncSeek> DynSeekRead for T {}
+
+type StreamingReader = Arc<std::sync::Mutex<StreamingState>>;
+type SeekZipReader = async_zip::tokio::read::seek::ZipFileReader<Box<dyn DynSeekRead>>;
+
+/// Extensions of single file splat formats. These are read as a stream rather than
+/// as an archive.
+const SPLAT_EXTENSIONS: [&str; 5] = ["ply", "spz", "splat", "ksplat", "safetensors"];
+
+/// Magic bytes of a gzip stream, as used by spz files.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether `peek` looks like the start of a safetensors file: a little endian
+/// u64 header length followed by the JSON header.
+fn is_safetensors(peek: &[u8]) -> bool {
+    peek.len() > 8 && peek[8] == b'{' && peek[4..8] == [0; 4]
+}
+
+/// State of a single streamed file.
+///
+/// The first read streams straight from the source while keeping a copy of the
+/// data, so later reads can be served from memory. This means the whole file
+/// stays in memory for as long as the VFS lives, which is fine for the typical
+/// single splat file, but costs as much memory as the file is large.
+enum StreamingState {
+    /// Not (fully) read yet.
+    Pending(Box<dyn DynRead>),
+    /// Currently being read for the first time.
+    Reading,
+    /// Read fully once, later reads come from this copy.
+    Buffered(Arc<Vec<u8>>),
+}
+
+/// Reader for the first read of a streamed file. Records all data passing
+/// through, and on EOF stores it as [`StreamingState::Buffered`]. If dropped
+/// before EOF, the recorded data is put back in front of the rest of the stream.
+struct RecordingReader {
+    inner: Option<Box<dyn DynRead>>,
+    data: Vec<u8>,
+    state: StreamingReader,
+}
+
+impl AsyncRead for RecordingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let Some(inner) = this.inner.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+
+        let before = buf.filled().len();
+        let had_space = buf.remaining() > 0;
+        ready!(Pin::new(inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+
+        if read.is_empty() && had_space {
+            this.inner = None;
+            let data = Arc::new(std::mem::take(&mut this.data));
+            *this.state.lock().expect("Streaming state poisoned") = StreamingState::Buffered(data);
+        } else {
+            this.data.extend_from_slice(read);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for RecordingReader {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            let data = std::mem::take(&mut self.data);
+            let reader: Box<dyn DynRead> = Box::new(AsyncReadExt::chain(Cursor::new(data), inner));
+            if let Ok(mut state) = self.state.lock() {
+                *state = StreamingState::Pending(reader);
+            }
+        }
+    }
+}
 
 /// Wrapper so `Cursor` can use `Arc<Vec<u8>>` without cloning.
 struct ArcVec(Arc<Vec<u8>>);
@@ -57,9 +143,11 @@ impl PathKey {
     }
 
     fn from_path(path: &Path) -> Self {
+        // Clean relative to the root, so `..` segments that would climb above
+        // it (eg. `sparse/../../images/a.jpg`) are dropped instead of kept.
         // Lossily convert rather than panicking on non-UTF-8 filenames; the key
         // is only used for case-insensitive lookups.
-        Self::from_str(&path.clean().to_string_lossy())
+        Self::from_str(&Path::new("/").join(path).clean().to_string_lossy())
     }
 }
 
@@ -99,10 +187,15 @@ where
 enum VfsContainer {
     /// Raw data stored in memory (from zip files)
     InMemory {
-        entries: HashMap<PathBuf, Arc<Vec<u8>>>,
+        entries: RwLock<HashMap<PathBuf, Arc<Vec<u8>>>>,
+    },
+    /// A zip archive on a seekable source. Entries are decompressed on demand.
+    Zip {
+        archive: Arc<Mutex<SeekZipReader>>,
+        entries: HashMap<PathBuf, usize>,
     },
-    /// A single file being streamed. The reader can only be consumed once.
-    Streaming { reader: StreamingReader },
+    /// A single file being streamed. Kept in memory after the first full read.
+    Streaming { state: StreamingReader },
     /// Native directory - reads from disk on demand
     #[cfg(not(target_family = "wasm"))]
     Directory { base_path: PathBuf },
@@ -117,6 +210,7 @@ impl Debug for VfsContainer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InMemory { .. } => f.debug_struct("InMemory").finish(),
+            Self::Zip { .. } => f.debug_struct("Zip").finish(),
             Self::Streaming { .. } => f.debug_struct("Streaming").finish(),
             Self::Directory { .. } => f.debug_struct("Directory").finish(),
         }
@@ -125,8 +219,10 @@ impl Debug for VfsContainer {
 
 #[derive(Debug)]
 pub struct BrushVfs {
-    lookup: HashMap<PathKey, PathBuf>,
+    lookup: RwLock<HashMap<PathKey, PathBuf>>,
     container: VfsContainer,
+    /// Bumped whenever files are added or a rescan finds changes.
+    generation: watch::Sender<u64>,
 }
 
 fn lookup_from_paths(paths: &[PathBuf]) -> HashMap<PathKey, PathBuf> {
@@ -139,27 +235,104 @@ fn lookup_from_paths(paths: &[PathBuf]) -> HashMap<PathKey, PathBuf> {
         .collect()
 }
 
+/// List all files below `dir`, relative to `dir`.
+#[cfg(not(target_family = "wasm"))]
+async fn walk_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(path) = stack.pop() {
+        let mut read_dir = tokio::fs::read_dir(&path).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path.clone());
+            } else {
+                let path = path
+                    .strip_prefix(dir)
+                    .map_err(|_e| io::ErrorKind::InvalidInput)?
+                    .to_path_buf();
+                paths.push(path);
+            }
+
+            brush_async::yield_now().await;
+        }
+    }
+    Ok(paths)
+}
+
 fn zip_error(e: async_zip::error::ZipError) -> io::Error {
     io::Error::new(io::ErrorKind::InvalidData, e)
 }
 
+fn corrupt_archive(e: impl std::fmt::Display) -> VfsConstructError {
+    VfsConstructError::CorruptArchive(format!(
+        "Archive appears truncated or corrupt, was the download interrupted? ({e})"
+    ))
+}
+
+/// Whether the data after the last local entry holds a complete end of central
+/// directory record. The streaming zip reader stops at the central directory
+/// without reading it, so a cut off archive would otherwise go unnoticed.
+fn has_complete_eocd(tail: &[u8]) -> bool {
+    const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const EOCD_LEN: usize = 22;
+
+    tail.windows(4)
+        .rposition(|w| w == EOCD_SIGNATURE)
+        .is_some_and(|pos| {
+            let comment_len = tail
+                .get(pos + 20..pos + 22)
+                .map_or(usize::MAX, |b| u16::from_le_bytes([b[0], b[1]]) as usize);
+            tail.len().saturating_sub(pos + EOCD_LEN) >= comment_len
+        })
+}
+
 #[derive(Debug, Error)]
 pub enum VfsConstructError {
     #[error("I/O error while constructing BrushVfs.")]
     IoError(#[from] std::io::Error),
     #[error("Got a status page instead of content: \n\n {0}")]
     ReceivedHTML(String),
-    #[error("Unknown data type. Only zip and ply files are supported")]
+    #[error("{0}")]
+    CorruptArchive(String),
+    #[error(
+        "Unknown data type. Only zip, ply, spz, splat, ksplat and safetensors files are supported"
+    )]
     UnknownDataType,
 }
 
 impl BrushVfs {
+    fn new(lookup: HashMap<PathKey, PathBuf>, container: VfsContainer) -> Self {
+        Self {
+            lookup: RwLock::new(lookup),
+            container,
+            generation: watch::Sender::new(0),
+        }
+    }
+
+    fn lookup(&self) -> RwLockReadGuard<'_, HashMap<PathKey, PathBuf>> {
+        self.lookup.read().expect("VFS lookup poisoned")
+    }
+
     pub fn file_count(&self) -> usize {
-        self.lookup.len()
+        self.lookup().len()
     }
 
     pub fn file_paths(&self) -> impl Iterator<Item = PathBuf> {
-        self.lookup.values().cloned()
+        self.lookup()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Subscribe to changes of the file list. The value is bumped whenever a
+    /// file is inserted, or a rescan of a watched directory finds changes, and
+    /// can be used as a signal to reload the dataset.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
     }
 
     pub async fn from_reader(
@@ -172,40 +345,79 @@ impl BrushVfs {
         let mut reader: Box<dyn DynRead> =
             Box::new(AsyncReadExt::chain(Cursor::new(peek.clone()), reader));
 
-        if peek.starts_with(b"ply") {
-            // For single PLY files, keep the reader for streaming
-            let path = PathBuf::from(name.unwrap_or_else(|| "input.ply".to_owned()));
+        // .splat and .ksplat files have no (reliable) magic bytes, so rely on the name.
+        let named_splat = name.as_ref().is_some_and(|n| {
+            Path::new(n)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| SPLAT_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        });
 
-            Ok(Self {
-                lookup: lookup_from_paths(std::slice::from_ref(&path)),
-                container: VfsContainer::Streaming {
-                    reader: Arc::new(Mutex::new(Some(reader))),
+        let default_name = if peek.starts_with(b"ply") {
+            Some("input.ply")
+        } else if peek.starts_with(&GZIP_MAGIC) {
+            Some("input.spz")
+        } else if is_safetensors(&peek) {
+            Some("input.safetensors")
+        } else {
+            None
+        };
+
+        if default_name.is_some() || (named_splat && !peek.starts_with(b"PK")) {
+            // For single splat files, keep the reader for streaming
+            let path = PathBuf::from(
+                name.unwrap_or_else(|| default_name.unwrap_or("input.ply").to_owned()),
+            );
+
+            Ok(Self::new(
+                lookup_from_paths(std::slice::from_ref(&path)),
+                VfsContainer::Streaming {
+                    state: Arc::new(std::sync::Mutex::new(StreamingState::Pending(reader))),
                 },
-            })
+            ))
         } else if peek.starts_with(b"PK") {
-            let mut zip_reader = ZipFileReader::new(reader.compat());
+            let mut zip_reader = ZipFileReader::new((&mut reader).compat());
             let mut entries = HashMap::new();
 
-            while let Some(mut entry) = zip_reader.next_with_entry().await.map_err(zip_error)? {
+            while let Some(mut entry) = zip_reader
+                .next_with_entry()
+                .await
+                .map_err(corrupt_archive)?
+            {
                 if let Ok(filename) = entry.reader().entry().filename().clone().as_str() {
                     let mut data = vec![];
                     let mut reader = entry.reader_mut().compat();
-                    reader.read_to_end(&mut data).await?;
+                    reader.read_to_end(&mut data).await.map_err(|e| {
+                        if e.kind() == io::ErrorKind::UnexpectedEof {
+                            corrupt_archive(e)
+                        } else {
+                            e.into()
+                        }
+                    })?;
                     entries.insert(PathBuf::from(filename), Arc::new(data));
-                    zip_reader = entry.skip().await.map_err(zip_error)?;
+                    zip_reader = entry.skip().await.map_err(corrupt_archive)?;
                 } else {
-                    zip_reader = entry.skip().await.map_err(zip_error)?;
+                    zip_reader = entry.skip().await.map_err(corrupt_archive)?;
                 }
 
                 brush_async::yield_now().await;
             }
 
+            // The reader stopped after the central directory signature, check the rest is intact.
+            let mut tail = b"PK\x01\x02".to_vec();
+            reader.read_to_end(&mut tail).await?;
+            if !has_complete_eocd(&tail) {
+                return Err(corrupt_archive("missing end of central directory"));
+            }
+
             let path_bufs = entries.keys().cloned().collect::<Vec<_>>();
 
-            Ok(Self {
-                lookup: lookup_from_paths(&path_bufs),
-                container: VfsContainer::InMemory { entries },
-            })
+            Ok(Self::new(
+                lookup_from_paths(&path_bufs),
+                VfsContainer::InMemory {
+                    entries: RwLock::new(entries),
+                },
+            ))
         } else if peek.starts_with(b"<!DOCTYPE html>") {
             let mut html = String::new();
             reader.read_to_string(&mut html).await?;
@@ -215,6 +427,46 @@ impl BrushVfs {
         }
     }
 
+    /// Like [`Self::from_reader`], but zip archives aren't read into memory.
+    /// Only the central directory is read upfront, entries are decompressed
+    /// when they're requested.
+    pub async fn from_seekable_reader(
+        mut reader: impl DynSeekRead + 'static,
+        name: Option<String>,
+    ) -> Result<Self, VfsConstructError> {
+        let peek = read_at_most(&mut reader, 64).await?;
+        reader.seek(SeekFrom::Start(0)).await?;
+
+        if !peek.starts_with(b"PK") {
+            return Self::from_reader(reader, name).await;
+        }
+
+        let reader: Box<dyn DynSeekRead> = Box::new(reader);
+        let archive = SeekZipReader::with_tokio(reader)
+            .await
+            .map_err(corrupt_archive)?;
+
+        let entries: HashMap<_, _> = archive
+            .file()
+            .entries()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let filename = entry.filename().as_str().ok()?;
+                Some((PathBuf::from(filename), index))
+            })
+            .collect();
+        let path_bufs = entries.keys().cloned().collect::<Vec<_>>();
+
+        Ok(Self::new(
+            lookup_from_paths(&path_bufs),
+            VfsContainer::Zip {
+                archive: Arc::new(Mutex::new(archive)),
+                entries,
+            },
+        ))
+    }
+
     #[cfg(not(target_family = "wasm"))]
     pub async fn from_path(dir: &Path) -> Result<Self, VfsConstructError> {
         if dir.is_file() {
@@ -223,43 +475,16 @@ impl BrushVfs {
             let file = tokio::fs::File::open(dir).await?;
             let reader = BufReader::new(file);
             let name = dir.file_name().and_then(|n| n.to_str()).map(String::from);
-            Self::from_reader(reader, name).await
+            Self::from_seekable_reader(reader, name).await
         } else {
             // Make a VFS with all files contained in the directory.
-            async fn walk_dir(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
-                let dir = PathBuf::from(dir.as_ref());
-
-                let mut paths = Vec::new();
-                let mut stack = vec![dir.clone()];
-
-                while let Some(path) = stack.pop() {
-                    let mut read_dir = tokio::fs::read_dir(&path).await?;
-
-                    while let Some(entry) = read_dir.next_entry().await? {
-                        let path = entry.path();
-                        if path.is_dir() {
-                            stack.push(path.clone());
-                        } else {
-                            let path = path
-                                .strip_prefix(dir.clone())
-                                .map_err(|_e| io::ErrorKind::InvalidInput)?
-                                .to_path_buf();
-                            paths.push(path);
-                        }
-
-                        brush_async::yield_now().await;
-                    }
-                }
-                Ok(paths)
-            }
-
             let files = walk_dir(dir).await?;
-            Ok(Self {
-                lookup: lookup_from_paths(&files),
-                container: VfsContainer::Directory {
+            Ok(Self::new(
+                lookup_from_paths(&files),
+                VfsContainer::Directory {
                     base_path: dir.to_path_buf(),
                 },
-            })
+            ))
         }
     }
 
@@ -272,56 +497,168 @@ impl BrushVfs {
             VfsConstructError::IoError(io::Error::other("Failed to list directory contents"))
         })?;
 
-        Ok(Self {
-            lookup: lookup_from_paths(&paths),
-            container: VfsContainer::Directory { dir_handle },
-        })
+        Ok(Self::new(
+            lookup_from_paths(&paths),
+            VfsContainer::Directory { dir_handle },
+        ))
     }
 
-    pub fn files_with_extension<'a>(
-        &'a self,
-        extension: &'a str,
-    ) -> impl Iterator<Item = PathBuf> + 'a {
+    pub fn files_with_extension(&self, extension: &str) -> impl Iterator<Item = PathBuf> {
         let extension = extension.to_lowercase();
 
-        self.lookup.values().filter_map(move |path| {
-            let ext = path
-                .extension()
-                .and_then(|ext| ext.to_str())?
-                .to_lowercase();
-            (ext == extension).then(|| path.clone())
-        })
+        self.lookup()
+            .values()
+            .filter_map(|path| {
+                let ext = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())?
+                    .to_lowercase();
+                (ext == extension).then(|| path.clone())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 
-    pub fn files_ending_in<'a>(&'a self, end_path: &str) -> impl Iterator<Item = &'a Path> + 'a {
-        let end_keyed = PathKey::from_str(end_path).0;
+    /// All files whose path ends in `end_path`, on a path component boundary.
+    /// Leading `./` and `../` segments of `end_path` are ignored, as they can't
+    /// be part of a stored path.
+    pub fn files_ending_in(&self, end_path: &str) -> impl Iterator<Item = PathBuf> {
+        let mut end_keyed = PathKey::from_str(end_path).0;
+        while let Some(rest) = end_keyed
+            .strip_prefix("/../")
+            .or_else(|| end_keyed.strip_prefix("/./"))
+        {
+            end_keyed = format!("/{rest}");
+        }
+
+        self.lookup()
+            .iter()
+            .filter(|kv| kv.0.0.ends_with(&end_keyed))
+            .map(|kv| kv.1.clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// The stored path of the file at `path`, matched case insensitively. `..`
+    /// segments are resolved, and are clamped at the root of the VFS.
+    pub fn find_path(&self, path: &Path) -> Option<PathBuf> {
+        self.lookup().get(&PathKey::from_path(path)).cloned()
+    }
 
-        self.lookup
+    /// List the direct children of `dir`: files, and subdirectories derived from
+    /// the paths of the files below them. An empty path lists the root. Results
+    /// are sorted and keep the original casing of the paths.
+    pub fn entries_in_dir(&self, dir: &Path) -> impl Iterator<Item = PathBuf> {
+        let dir = dir.clean();
+        let dir_key = if dir == Path::new(".") || dir == Path::new("/") {
+            String::new()
+        } else {
+            PathKey::from_path(&dir).0.trim_end_matches('/').to_owned()
+        };
+        let depth = dir_key.split('/').filter(|c| !c.is_empty()).count();
+
+        self.lookup()
             .iter()
-            .filter(move |kv| kv.0.0.ends_with(&end_keyed))
-            .map(|kv| kv.1.as_path())
+            .filter(|(key, _)| {
+                key.0
+                    .strip_prefix(&dir_key)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .map(|(_, path)| path.components().take(depth + 1).collect::<PathBuf>())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
     }
 
     /// Iterate over all files in the VFS.
-    pub fn iter_files<'a>(&'a self) -> impl Iterator<Item = &'a Path> + 'a {
-        self.lookup.values().map(|path| path.as_path())
+    pub fn iter_files(&self) -> impl Iterator<Item = PathBuf> {
+        self.file_paths()
+    }
+
+    /// Add a file to an in-memory VFS, reading `reader` to the end. A file
+    /// already at this path (compared case-insensitively) is replaced.
+    pub async fn insert_reader(
+        &self,
+        path: &Path,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> io::Result<()> {
+        let VfsContainer::InMemory { entries } = &self.container else {
+            return Err(Error::new(
+                io::ErrorKind::Unsupported,
+                "Files can only be added to an in-memory VFS",
+            ));
+        };
+
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+
+        let path = path.clean();
+        let key = PathKey::from_path(&path);
+        let mut lookup = self.lookup.write().expect("VFS lookup poisoned");
+        let mut entries = entries.write().expect("VFS entries poisoned");
+        if let Some(old) = lookup.insert(key, path.clone()) {
+            entries.remove(&old);
+        }
+        entries.insert(path, Arc::new(data));
+        drop(entries);
+        drop(lookup);
+
+        self.generation.send_modify(|g| *g += 1);
+        Ok(())
+    }
+
+    /// Re-read the file list of a directory VFS. Returns whether anything
+    /// changed, in which case subscribers are notified. Other kinds of VFS
+    /// never change on disk, so this does nothing for them.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn rescan(&self) -> io::Result<bool> {
+        let VfsContainer::Directory { base_path } = &self.container else {
+            return Ok(false);
+        };
+
+        let lookup = lookup_from_paths(&walk_dir(base_path).await?);
+        let mut current = self.lookup.write().expect("VFS lookup poisoned");
+        if *current == lookup {
+            return Ok(false);
+        }
+        *current = lookup;
+        drop(current);
+
+        self.generation.send_modify(|g| *g += 1);
+        Ok(true)
+    }
+
+    /// Rescan a directory VFS every `interval`, notifying subscribers when files
+    /// are added or removed. Only returns if reading the directory fails, so
+    /// this should be spawned as a background task.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn watch(&self, interval: std::time::Duration) -> io::Result<()> {
+        loop {
+            tokio::time::sleep(interval).await;
+            self.rescan().await?;
+        }
     }
 
     pub async fn reader_at_path(&self, path: &Path) -> io::Result<Box<dyn DynRead>> {
         let key = PathKey::from_path(path);
 
-        let resolved = self.lookup.get(&key).or_else(|| {
-            // Datasets (e.g. a NeRFStudio transforms.json) sometimes reference
-            // files by absolute path. If we loaded a directory and that path
-            // points inside it, strip the directory prefix and resolve it
-            // within the VFS. Files outside the VFS are never read.
-            let base = PathKey::from_path(&self.base_path()?);
-            let rel = key.0.strip_prefix(&base.0)?;
-            // Only a match on a path-component boundary counts.
-            rel.starts_with('/')
-                .then(|| self.lookup.get(&PathKey(rel.to_owned())))
-                .flatten()
-        });
+        let resolved = {
+            let lookup = self.lookup();
+            lookup
+                .get(&key)
+                .or_else(|| {
+                    // Datasets (e.g. a NeRFStudio transforms.json) sometimes reference
+                    // files by absolute path. If we loaded a directory and that path
+                    // points inside it, strip the directory prefix and resolve it
+                    // within the VFS. Files outside the VFS are never read.
+                    let base = PathKey::from_path(&self.base_path()?);
+                    let rel = key.0.strip_prefix(&base.0)?;
+                    // Only a match on a path-component boundary counts.
+                    rel.starts_with('/')
+                        .then(|| lookup.get(&PathKey(rel.to_owned())))
+                        .flatten()
+                })
+                .cloned()
+        };
 
         let path = resolved.ok_or_else(|| {
             Error::new(
@@ -329,22 +666,56 @@ impl BrushVfs {
                 format!("File not found: {}", path.display()),
             )
         })?;
+        let path = path.as_path();
 
         match &self.container {
             VfsContainer::InMemory { entries } => {
-                let data = entries.get(path).expect("Unreachable").clone();
+                let data = entries
+                    .read()
+                    .expect("VFS entries poisoned")
+                    .get(path)
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("File not found: {}", path.display()),
+                        )
+                    })?;
                 let reader: Box<dyn DynRead> = Box::new(Cursor::new(ArcVec(data)));
                 Ok(reader)
             }
-            VfsContainer::Streaming { reader } => {
-                // Streaming reader can only be consumed once
-                let reader: Box<dyn DynRead> = reader
-                    .lock()
-                    .await
-                    .take()
-                    .ok_or_else(|| Error::other("Streaming file has already been read"))?;
+            VfsContainer::Zip { archive, entries } => {
+                let index = *entries.get(path).expect("Unreachable");
+                // The source can only be at one position at a time, so decompress
+                // the whole entry while holding the lock.
+                let mut archive = archive.lock().await;
+                let entry = archive.reader_with_entry(index).await.map_err(zip_error)?;
+                let mut data = vec![];
+                entry.compat().read_to_end(&mut data).await?;
+                let reader: Box<dyn DynRead> = Box::new(Cursor::new(data));
                 Ok(reader)
             }
+            VfsContainer::Streaming { state } => {
+                let mut guard = state.lock().expect("Streaming state poisoned");
+                match std::mem::replace(&mut *guard, StreamingState::Reading) {
+                    StreamingState::Pending(inner) => {
+                        let reader: Box<dyn DynRead> = Box::new(BufReader::new(RecordingReader {
+                            inner: Some(inner),
+                            data: vec![],
+                            state: state.clone(),
+                        }));
+                        Ok(reader)
+                    }
+                    StreamingState::Buffered(data) => {
+                        *guard = StreamingState::Buffered(data.clone());
+                        let reader: Box<dyn DynRead> = Box::new(Cursor::new(ArcVec(data)));
+                        Ok(reader)
+                    }
+                    StreamingState::Reading => {
+                        Err(Error::other("Streaming file is still being read"))
+                    }
+                }
+            }
             #[cfg(not(target_family = "wasm"))]
             VfsContainer::Directory { base_path } => {
                 let total_path = base_path.join(path);
@@ -392,34 +763,44 @@ impl BrushVfs {
     }
 
     pub fn empty() -> Self {
-        Self {
-            lookup: HashMap::new(),
-            container: VfsContainer::InMemory {
-                entries: HashMap::new(),
+        Self::new(
+            HashMap::new(),
+            VfsContainer::InMemory {
+                entries: RwLock::new(HashMap::new()),
             },
-        }
+        )
     }
 
     /// Create a test VFS from file paths with empty content.
     #[doc(hidden)]
     pub fn create_test_vfs(paths: Vec<PathBuf>) -> Self {
+        Self::create_test_vfs_with_data(paths.into_iter().map(|p| (p, vec![])).collect())
+    }
+
+    /// Create a test VFS from file paths and their content.
+    #[doc(hidden)]
+    pub fn create_test_vfs_with_data(files: Vec<(PathBuf, Vec<u8>)>) -> Self {
+        let paths: Vec<_> = files.iter().map(|(p, _)| p.clone()).collect();
         let lookup = lookup_from_paths(&paths);
 
-        let entries = paths
+        let entries = files
             .into_iter()
-            .filter(|p| p.extension().is_some())
-            .map(|p| (p, Arc::new(Vec::new())))
+            .filter(|(p, _)| p.extension().is_some())
+            .map(|(p, data)| (p.clean(), Arc::new(data)))
             .collect();
 
-        Self {
+        Self::new(
             lookup,
-            container: VfsContainer::InMemory { entries },
-        }
+            VfsContainer::InMemory {
+                entries: RwLock::new(entries),
+            },
+        )
     }
 
     pub fn base_path(&self) -> Option<PathBuf> {
         match &self.container {
             VfsContainer::InMemory { .. } => None,
+            VfsContainer::Zip { .. } => None,
             VfsContainer::Streaming { .. } => None,
             #[cfg(not(target_family = "wasm"))]
             VfsContainer::Directory { base_path } => Some(base_path.clone()),
@@ -514,6 +895,152 @@ mod tests {
         );
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_truncated_zip() {
+        let zip_data = create_test_zip().await;
+
+        // Cut off inside the central directory, and inside the last entry.
+        for len in [zip_data.len() - 10, zip_data.len() / 2] {
+            let truncated = zip_data[..len].to_vec();
+            assert!(matches!(
+                BrushVfs::from_reader(Cursor::new(truncated.clone()), None).await,
+                Err(VfsConstructError::CorruptArchive(_))
+            ));
+            assert!(matches!(
+                BrushVfs::from_seekable_reader(Cursor::new(truncated), None).await,
+                Err(VfsConstructError::CorruptArchive(_))
+            ));
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_streaming_file_reads_twice() {
+        let data = b"ply\nformat ascii 1.0\nend_header\n".repeat(100);
+        let vfs = BrushVfs::from_reader(Cursor::new(data.clone()), None)
+            .await
+            .unwrap();
+        let path = Path::new("input.ply");
+
+        // A partial read, eg. sniffing the header, doesn't lose any data.
+        let mut header = [0; 3];
+        vfs.reader_at_path(path)
+            .await
+            .unwrap()
+            .read_exact(&mut header)
+            .await
+            .unwrap();
+        assert_eq!(&header, b"ply");
+
+        // Reading while another read is in progress fails.
+        let mut first = vfs.reader_at_path(path).await.unwrap();
+        assert!(vfs.reader_at_path(path).await.is_err());
+
+        let mut content = vec![];
+        first.read_to_end(&mut content).await.unwrap();
+        assert_eq!(content, data);
+        drop(first);
+
+        // After a full read, the file is served from memory.
+        for _ in 0..2 {
+            let mut content = vec![];
+            vfs.reader_at_path(path)
+                .await
+                .unwrap()
+                .read_to_end(&mut content)
+                .await
+                .unwrap();
+            assert_eq!(content, data);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_entries_in_dir() {
+        let vfs = BrushVfs::create_test_vfs(vec![
+            PathBuf::from("transforms.json"),
+            PathBuf::from("Images/a.png"),
+            PathBuf::from("Images/b.png"),
+            PathBuf::from("Images/masks/a.png"),
+            PathBuf::from("sparse/0/cameras.bin"),
+        ]);
+
+        let list = |dir: &str| vfs.entries_in_dir(Path::new(dir)).collect::<Vec<_>>();
+
+        assert_eq!(
+            list(""),
+            ["Images", "sparse", "transforms.json"].map(PathBuf::from)
+        );
+        assert_eq!(
+            list("images"),
+            ["Images/a.png", "Images/b.png", "Images/masks"].map(PathBuf::from)
+        );
+        assert_eq!(list("Images/masks/"), [PathBuf::from("Images/masks/a.png")]);
+        assert_eq!(list("sparse"), [PathBuf::from("sparse/0")]);
+        // Files and unknown directories have no children.
+        assert!(list("transforms.json").is_empty());
+        assert!(list("Imag").is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_relative_parent_paths() {
+        let vfs = BrushVfs::create_test_vfs(vec![
+            PathBuf::from("Images/frame001.jpg"),
+            PathBuf::from("sparse/0/cameras.txt"),
+        ]);
+
+        let found = Some(PathBuf::from("Images/frame001.jpg"));
+        assert_eq!(
+            vfs.find_path(Path::new("sparse/../images/frame001.jpg")),
+            found
+        );
+        // `..` above the root is clamped to the root.
+        assert_eq!(
+            vfs.find_path(Path::new("sparse/../../images/frame001.jpg")),
+            found
+        );
+        assert_eq!(vfs.find_path(Path::new("frame001.jpg")), None);
+
+        let ending_in = |name: &str| vfs.files_ending_in(name).collect::<Vec<_>>();
+        assert_eq!(
+            ending_in("../images/frame001.jpg"),
+            [found.clone().unwrap()]
+        );
+        assert_eq!(ending_in("./../images/frame001.jpg"), [found.unwrap()]);
+        assert!(ending_in("../other/frame001.jpg").is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_seekable_zip_reads_lazily() {
+        let zip_data = create_test_zip().await;
+        let vfs = BrushVfs::from_seekable_reader(Cursor::new(zip_data), None)
+            .await
+            .unwrap();
+        assert!(matches!(vfs.container, VfsContainer::Zip { .. }));
+        assert_eq!(vfs.file_count(), 2);
+
+        // Entries can be read repeatedly, and in any order.
+        for path in ["data.json", "TEST.TXT", "data.json"] {
+            let mut content = String::new();
+            vfs.reader_at_path(Path::new(path))
+                .await
+                .unwrap()
+                .read_to_string(&mut content)
+                .await
+                .unwrap();
+            let expected = if path == "data.json" {
+                "{\"key\": \"value\"}"
+            } else {
+                "hello world"
+            };
+            assert_eq!(content, expected);
+        }
+
+        // Anything else falls back to the regular reader.
+        let vfs = BrushVfs::from_seekable_reader(Cursor::new(b"ply\nend_header\n".to_vec()), None)
+            .await
+            .unwrap();
+        assert!(vfs.reader_at_path(Path::new("input.ply")).await.is_ok());
+    }
+
     #[cfg(not(target_family = "wasm"))]
     #[tokio::test]
     async fn test_absolute_path_resolves_within_directory() {
@@ -563,6 +1090,93 @@ mod tests {
         tokio::fs::remove_dir_all(&dir).await.unwrap();
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_insert_reader() {
+        let vfs = BrushVfs::empty();
+        let mut rx = vfs.subscribe();
+
+        vfs.insert_reader(Path::new("images/a.png"), Cursor::new(b"first"))
+            .await
+            .unwrap();
+        assert!(rx.has_changed().unwrap());
+        rx.mark_unchanged();
+        assert_eq!(
+            vfs.files_with_extension("png").collect::<Vec<_>>(),
+            [PathBuf::from("images/a.png")]
+        );
+
+        // A path differing only in case replaces the existing file.
+        vfs.insert_reader(Path::new("IMAGES/A.png"), Cursor::new(b"second"))
+            .await
+            .unwrap();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(vfs.file_count(), 1);
+        let mut content = String::new();
+        vfs.reader_at_path(Path::new("images/a.png"))
+            .await
+            .unwrap()
+            .read_to_string(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, "second");
+
+        // Streamed files can't be added to.
+        let streamed = BrushVfs::from_reader(Cursor::new(b"ply\nend_header\n".to_vec()), None)
+            .await
+            .unwrap();
+        assert!(
+            streamed
+                .insert_reader(Path::new("b.png"), Cursor::new(b""))
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[tokio::test]
+    async fn test_watch_directory_picks_up_new_files() {
+        let dir = std::env::temp_dir().join("brush_vfs_watch_test_dir");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(dir.join("a.png"), b"a").await.unwrap();
+
+        let vfs = Arc::new(BrushVfs::from_path(&dir).await.unwrap());
+        let mut rx = vfs.subscribe();
+        assert_eq!(vfs.files_with_extension("png").count(), 1);
+
+        // Nothing changed on disk, so no notification.
+        assert!(!vfs.rescan().await.unwrap());
+        assert!(!rx.has_changed().unwrap());
+
+        let watcher = vfs.clone();
+        let watch = tokio::spawn(async move {
+            watcher
+                .watch(std::time::Duration::from_millis(10))
+                .await
+                .unwrap();
+        });
+
+        tokio::fs::create_dir_all(dir.join("images")).await.unwrap();
+        tokio::fs::write(dir.join("images/b.png"), b"b")
+            .await
+            .unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.changed())
+            .await
+            .expect("no rescan notification")
+            .unwrap();
+        watch.abort();
+
+        let mut files: Vec<_> = vfs.files_with_extension("png").collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [PathBuf::from("a.png"), PathBuf::from("images/b.png")]
+        );
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_format_detection_and_errors() {
         // Test PLY format
@@ -581,7 +1195,31 @@ mod tests {
             .unwrap();
         assert_eq!(content, "ply\nformat ascii 1.0\nend_header\nvertex data");
 
+        // Splat formats without magic bytes are detected by name, spz by its gzip header.
+        let vfs = BrushVfs::from_reader(Cursor::new(vec![0u8; 32]), Some("scene.splat".into()))
+            .await
+            .unwrap();
+        assert!(vfs.reader_at_path(Path::new("scene.splat")).await.is_ok());
+        let vfs = BrushVfs::from_reader(Cursor::new(vec![0x1f, 0x8b, 0x08]), None)
+            .await
+            .unwrap();
+        assert!(vfs.reader_at_path(Path::new("input.spz")).await.is_ok());
+        let mut safetensors = 2u64.to_le_bytes().to_vec();
+        safetensors.extend(b"{}");
+        let vfs = BrushVfs::from_reader(Cursor::new(safetensors), None)
+            .await
+            .unwrap();
+        assert!(
+            vfs.reader_at_path(Path::new("input.safetensors"))
+                .await
+                .is_ok()
+        );
+
         // Test error cases
+        assert!(matches!(
+            BrushVfs::from_reader(Cursor::new(b"unknown"), Some("scene.txt".into())).await,
+            Err(VfsConstructError::UnknownDataType)
+        ));
         assert!(matches!(
             BrushVfs::from_reader(Cursor::new(b"unknown"), None).await,
             Err(VfsConstructError::UnknownDataType)
@@ -1,11 +1,12 @@
 use std::io::{self};
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncReadExt;
-use tokio::io::{AsyncBufRead, AsyncRead};
+use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
 
 // TODO: Really these should each hold their respective params but bit of an annoying refactor. We just need
 // basic params.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ColmapCameraModel {
     SimplePinhole,
     Pinhole,
@@ -55,6 +56,38 @@ impl ColmapCameraModel {
         }
     }
 
+    fn id(&self) -> i32 {
+        match self {
+            Self::SimplePinhole => 0,
+            Self::Pinhole => 1,
+            Self::SimpleRadial => 2,
+            Self::Radial => 3,
+            Self::OpenCV => 4,
+            Self::OpenCvFishEye => 5,
+            Self::FullOpenCV => 6,
+            Self::Fov => 7,
+            Self::SimpleRadialFisheye => 8,
+            Self::RadialFisheye => 9,
+            Self::ThinPrismFisheye => 10,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::SimplePinhole => "SIMPLE_PINHOLE",
+            Self::Pinhole => "PINHOLE",
+            Self::SimpleRadial => "SIMPLE_RADIAL",
+            Self::Radial => "RADIAL",
+            Self::OpenCV => "OPENCV",
+            Self::OpenCvFishEye => "OPENCV_FISHEYE",
+            Self::FullOpenCV => "FULL_OPENCV",
+            Self::Fov => "FOV",
+            Self::SimpleRadialFisheye => "SIMPLE_RADIAL_FISHEYE",
+            Self::RadialFisheye => "RADIAL_FISHEYE",
+            Self::ThinPrismFisheye => "THIN_PRISM_FISHEYE",
+        }
+    }
+
     fn num_params(&self) -> usize {
         match self {
             Self::SimplePinhole => 3,
@@ -72,7 +105,7 @@ impl ColmapCameraModel {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ColmapCamera {
     pub id: i32,
     pub model: ColmapCameraModel,
@@ -81,7 +114,7 @@ pub struct ColmapCamera {
     pub params: Vec<f64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Image {
     pub id: i32,
     pub tvec: glam::Vec3,
@@ -92,13 +125,13 @@ pub struct Image {
     pub points: Option<ImagePointData>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ImagePointData {
     pub xys: Vec<glam::Vec2>,
     pub point3d_ids: Vec<i64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Point3D {
     pub id: i64,
     pub xyz: glam::Vec3,
@@ -106,7 +139,7 @@ pub struct Point3D {
     pub aux: Option<Point3DAux>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Point3DAux {
     pub error: f64,
     pub image_ids: Vec<i32>,
@@ -438,7 +471,7 @@ async fn read_images_binary<R: AsyncBufRead + Unpin>(
                     reader.read_f64_le().await? as f32,
                     reader.read_f64_le().await? as f32,
                 ));
-                point3d_ids.push(reader.read_i64().await?);
+                point3d_ids.push(reader.read_i64_le().await?);
             }
             Some(ImagePointData { xys, point3d_ids })
         } else {
@@ -447,7 +480,7 @@ async fn read_images_binary<R: AsyncBufRead + Unpin>(
                 let (_, _, _) = (
                     reader.read_f64_le().await?,
                     reader.read_f64_le().await?,
-                    reader.read_i64().await?,
+                    reader.read_i64_le().await?,
                 );
             }
             None
@@ -551,7 +584,7 @@ async fn read_points3d_binary<R: AsyncRead + Unpin>(
     let num_points = reader.read_u64_le().await?;
 
     for _ in 0..num_points {
-        let point3d_id = reader.read_i64().await?;
+        let point3d_id = reader.read_i64_le().await?;
         let xyz = glam::Vec3::new(
             reader.read_f64_le().await? as f32,
             reader.read_f64_le().await? as f32,
@@ -599,6 +632,208 @@ async fn read_points3d_binary<R: AsyncRead + Unpin>(
     Ok(points3d)
 }
 
+// COLMAP leaves the reprojection error at -1 when it hasn't been computed.
+const UNKNOWN_ERROR: f64 = -1.0;
+
+fn join<T: std::fmt::Display>(values: impl IntoIterator<Item = T>) -> String {
+    values
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn write_cameras_text<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    cameras: &[ColmapCamera],
+) -> io::Result<()> {
+    let header = format!(
+        "# Camera list with one line of data per camera:\n\
+         #   CAMERA_ID, MODEL, WIDTH, HEIGHT, PARAMS[]\n\
+         # Number of cameras: {}\n",
+        cameras.len()
+    );
+    writer.write_all(header.as_bytes()).await?;
+
+    for camera in cameras {
+        let line = format!(
+            "{} {} {} {} {}\n",
+            camera.id,
+            camera.model.name(),
+            camera.width,
+            camera.height,
+            join(&camera.params)
+        );
+        writer.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn write_cameras_binary<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    cameras: &[ColmapCamera],
+) -> io::Result<()> {
+    writer.write_u64_le(cameras.len() as u64).await?;
+
+    for camera in cameras {
+        if camera.params.len() != camera.model.num_params() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "camera id {}: got {} params, expected {} for model {}",
+                    camera.id,
+                    camera.params.len(),
+                    camera.model.num_params(),
+                    camera.model.name()
+                ),
+            ));
+        }
+        writer.write_i32_le(camera.id).await?;
+        writer.write_i32_le(camera.model.id()).await?;
+        writer.write_u64_le(camera.width).await?;
+        writer.write_u64_le(camera.height).await?;
+        for &param in &camera.params {
+            writer.write_f64_le(param).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn write_images_text<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    images: &[Image],
+) -> io::Result<()> {
+    let header = format!(
+        "# Image list with two lines of data per image:\n\
+         #   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n\
+         #   POINTS2D[] as (X, Y, POINT3D_ID)\n\
+         # Number of images: {}\n",
+        images.len()
+    );
+    writer.write_all(header.as_bytes()).await?;
+
+    for image in images {
+        let (q, t) = (image.quat, image.tvec);
+        let mut lines = format!(
+            "{} {} {} {} {} {} {} {} {} {}\n",
+            image.id, q.w, q.x, q.y, q.z, t.x, t.y, t.z, image.camera_id, image.name
+        );
+        // The points line is always written, even when empty, as COLMAP expects
+        // two lines per image.
+        if let Some(points) = &image.points {
+            lines += &join(
+                points
+                    .xys
+                    .iter()
+                    .zip(&points.point3d_ids)
+                    .map(|(xy, id)| format!("{} {} {id}", xy.x, xy.y)),
+            );
+        }
+        lines.push('\n');
+        writer.write_all(lines.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn write_images_binary<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    images: &[Image],
+) -> io::Result<()> {
+    writer.write_u64_le(images.len() as u64).await?;
+
+    for image in images {
+        if image.name.contains('\0') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("image name {:?} contains a null byte", image.name),
+            ));
+        }
+
+        writer.write_i32_le(image.id).await?;
+        let (q, t) = (image.quat, image.tvec);
+        for v in [q.w, q.x, q.y, q.z, t.x, t.y, t.z] {
+            writer.write_f64_le(v as f64).await?;
+        }
+        writer.write_i32_le(image.camera_id).await?;
+        writer.write_all(image.name.as_bytes()).await?;
+        writer.write_u8(b'\0').await?;
+
+        let (xys, point3d_ids) = image
+            .points
+            .as_ref()
+            .map_or((&[][..], &[][..]), |p| (&p.xys[..], &p.point3d_ids[..]));
+        writer.write_u64_le(xys.len() as u64).await?;
+        for (xy, &id) in xys.iter().zip(point3d_ids) {
+            writer.write_f64_le(xy.x as f64).await?;
+            writer.write_f64_le(xy.y as f64).await?;
+            writer.write_i64_le(id).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn write_points3d_text<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    points3d: &[Point3D],
+) -> io::Result<()> {
+    let header = format!(
+        "# 3D point list with one line of data per point:\n\
+         #   POINT3D_ID, X, Y, Z, R, G, B, ERROR, TRACK[] as (IMAGE_ID, POINT2D_IDX)\n\
+         # Number of points: {}\n",
+        points3d.len()
+    );
+    writer.write_all(header.as_bytes()).await?;
+
+    for point in points3d {
+        let [r, g, b] = point.rgb;
+        let mut line = format!(
+            "{} {} {} {} {r} {g} {b} ",
+            point.id, point.xyz.x, point.xyz.y, point.xyz.z
+        );
+        match &point.aux {
+            Some(aux) => {
+                line += &aux.error.to_string();
+                for (image_id, idx) in aux.image_ids.iter().zip(&aux.point2d_idxs) {
+                    line += &format!(" {image_id} {idx}");
+                }
+            }
+            None => line += &UNKNOWN_ERROR.to_string(),
+        }
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn write_points3d_binary<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    points3d: &[Point3D],
+) -> io::Result<()> {
+    writer.write_u64_le(points3d.len() as u64).await?;
+
+    for point in points3d {
+        writer.write_i64_le(point.id).await?;
+        for v in point.xyz.to_array() {
+            writer.write_f64_le(v as f64).await?;
+        }
+        writer.write_all(&point.rgb).await?;
+
+        let (error, image_ids, point2d_idxs) = point
+            .aux
+            .as_ref()
+            .map_or((UNKNOWN_ERROR, &[][..], &[][..]), |aux| {
+                (aux.error, &aux.image_ids[..], &aux.point2d_idxs[..])
+            });
+        writer.write_f64_le(error).await?;
+        writer.write_u64_le(image_ids.len() as u64).await?;
+        for (&image_id, &idx) in image_ids.iter().zip(point2d_idxs) {
+            writer.write_i32_le(image_id).await?;
+            writer.write_i32_le(idx).await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn read_cameras<R: AsyncBufRead + Unpin>(
     reader: R,
     binary: bool,
@@ -634,6 +869,50 @@ pub async fn read_points3d<R: AsyncBufRead + Unpin>(
     }
 }
 
+/// Write cameras in the format of COLMAP's `cameras.txt` or `cameras.bin`.
+pub async fn write_cameras<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    cameras: &[ColmapCamera],
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_cameras_binary(&mut writer, cameras).await?;
+    } else {
+        write_cameras_text(&mut writer, cameras).await?;
+    }
+    writer.flush().await
+}
+
+/// Write images in the format of COLMAP's `images.txt` or `images.bin`. Images
+/// without point data are written with no 2D points.
+pub async fn write_images<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    images: &[Image],
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_images_binary(&mut writer, images).await?;
+    } else {
+        write_images_text(&mut writer, images).await?;
+    }
+    writer.flush().await
+}
+
+/// Write points in the format of COLMAP's `points3D.txt` or `points3D.bin`.
+/// Points without aux data are written with an unknown error and empty track.
+pub async fn write_points3d<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    points3d: &[Point3D],
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_points3d_binary(&mut writer, points3d).await?;
+    } else {
+        write_points3d_text(&mut writer, points3d).await?;
+    }
+    writer.flush().await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -830,4 +1109,149 @@ mod tests {
         let image = &images[0];
         assert_eq!(image.camera_id, camera.id);
     }
+
+    const CAMERAS_TXT: &str = "1 PINHOLE 800 600 500.0 500.0 400.0 300.0\n\
+                               2 OPENCV 640 480 450.0 451.0 320.0 240.0 0.1 0.2 0.3 0.4\n\
+                               3 SIMPLE_RADIAL 1920 1080 1200.5 960.0 540.0 -0.0125\n";
+
+    const IMAGES_TXT: &str = "1 0.7071 0.0 0.0 0.7071 1.0 2.0 3.0 1 image1.jpg\n\
+                              100.0 200.0 1 150.0 250.0 2 200.0 300.0 -1\n\
+                              2 1.0 0.0 0.0 0.0 0.0 0.0 0.0 1 image2.jpg\n\
+                              \n\
+                              3 -0.5 0.5 -0.5 0.5 -5.25 6.0 7.0 2 sub/image3.png\n\
+                              12.5 7.75 4294967296\n";
+
+    const POINTS3D_TXT: &str = "1 1.5 2.5 3.5 255 128 64 0.1 1 100 2 200\n\
+                                2 -1.0 0.0 1.0 0 255 0 0.05 3 50 4 75 5 125\n\
+                                4294967296 0.125 -0.25 8.0 1 2 3 1.5\n";
+
+    async fn roundtrip<T>(
+        items: &[T],
+        write: impl AsyncFn(&mut Vec<u8>, &[T]) -> io::Result<()>,
+        read: impl AsyncFn(Cursor<Vec<u8>>) -> io::Result<Vec<T>>,
+    ) -> Vec<T> {
+        let mut bytes = Vec::new();
+        write(&mut bytes, items).await.unwrap();
+        read(Cursor::new(bytes)).await.unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_cameras_roundtrip() {
+        let cameras = read_cameras(Cursor::new(CAMERAS_TXT), false).await.unwrap();
+        assert_eq!(cameras.len(), 3);
+
+        for binary in [true, false] {
+            let read_back = roundtrip(
+                &cameras,
+                async |w, c| write_cameras(w, c, binary).await,
+                async |r| read_cameras(r, binary).await,
+            )
+            .await;
+            assert_eq!(read_back, cameras);
+        }
+
+        // Binary params have to match the model, or COLMAP would misread the rest.
+        let mut bad = cameras[0].clone();
+        bad.params.pop();
+        assert!(write_cameras(Vec::new(), &[bad], true).await.is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_images_roundtrip() {
+        let images = read_images(Cursor::new(IMAGES_TXT), false, true)
+            .await
+            .unwrap();
+        assert_eq!(images.len(), 3);
+
+        for binary in [true, false] {
+            let read_back = roundtrip(
+                &images,
+                async |w, i| write_images(w, i, binary).await,
+                async |r| read_images(r, binary, true).await,
+            )
+            .await;
+            assert_eq!(read_back, images);
+        }
+
+        // Images read without points are written with an empty points list.
+        let images = read_images(Cursor::new(IMAGES_TXT), false, false)
+            .await
+            .unwrap();
+        let read_back = roundtrip(
+            &images,
+            async |w, i| write_images(w, i, true).await,
+            async |r| read_images(r, true, true).await,
+        )
+        .await;
+        assert!(
+            read_back
+                .iter()
+                .all(|i| i.points.as_ref().unwrap().xys.is_empty())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_points3d_roundtrip() {
+        let points = read_points3d(Cursor::new(POINTS3D_TXT), false, true)
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[2].aux.as_ref().unwrap().image_ids.len(), 0);
+
+        for binary in [true, false] {
+            let read_back = roundtrip(
+                &points,
+                async |w, p| write_points3d(w, p, binary).await,
+                async |r| read_points3d(r, binary, true).await,
+            )
+            .await;
+            assert_eq!(read_back, points);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_quaternion_w_first() {
+        // glam stores quaternions xyzw, COLMAP writes them wxyz. Use distinct
+        // components, including a negative w, so any mixup shows.
+        let quat = glam::quat(0.1, -0.2, 0.3, -0.9);
+        let image = Image {
+            id: 7,
+            tvec: glam::vec3(1.0, 2.0, 3.0),
+            quat,
+            camera_id: 1,
+            name: "a.jpg".to_owned(),
+            points: None,
+        };
+
+        let mut text = Vec::new();
+        write_images(&mut text, std::slice::from_ref(&image), false)
+            .await
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let line = text.lines().find(|l| !l.starts_with('#')).unwrap();
+        assert_eq!(line, "7 -0.9 0.1 -0.2 0.3 1 2 3 1 a.jpg");
+
+        // Binary: u64 count, i32 id, then qw qx qy qz as f64.
+        let mut bin = Vec::new();
+        write_images(&mut bin, std::slice::from_ref(&image), true)
+            .await
+            .unwrap();
+        let quat_bytes: Vec<f32> = bin[12..44]
+            .chunks(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()) as f32)
+            .collect();
+        assert_eq!(quat_bytes, vec![-0.9, 0.1, -0.2, 0.3]);
+        // Name is null terminated right after the camera id.
+        assert_eq!(&bin[44 + 24 + 4..][..6], b"a.jpg\0");
+
+        for binary in [true, false] {
+            let read_back = roundtrip(
+                std::slice::from_ref(&image),
+                async |w, i| write_images(w, i, binary).await,
+                async |r| read_images(r, binary, false).await,
+            )
+            .await;
+            assert_eq!(read_back[0].quat, quat);
+        }
+    }
 }
@@ -3,6 +3,7 @@
 use burn::nn::PaddingConfig2d;
 use burn::nn::conv::Conv2d;
 use burn::nn::conv::Conv2dConfig;
+use burn::nn::pool::AvgPool2dConfig;
 use burn::nn::pool::MaxPool2d;
 use burn::nn::pool::MaxPool2dConfig;
 use burn::tensor::Device;
@@ -71,8 +72,36 @@ fn norm_vec(vec: Tensor<4>) -> Tensor<4> {
 impl LpipsModel {
     /// Calculate the lpips. Imgs are in NCHW order. Inputs should be 0-1 normalised.
     pub fn lpips(&self, imgs_a: Tensor<4>, imgs_b: Tensor<4>) -> Tensor<1> {
+        self.lpips_impl(imgs_a, imgs_b, None)
+    }
+
+    /// Like [`Self::lpips`], but only compares the regions where `mask` ([N, H, W], 0-1) is set.
+    ///
+    /// Masked out pixels are cleared in both images, so they can't leak into
+    /// the features through the VGG receptive field. The mask is then average
+    /// pooled down to each block's resolution to weight the spatial mean.
+    pub fn lpips_masked(&self, imgs_a: Tensor<4>, imgs_b: Tensor<4>, mask: Tensor<3>) -> Tensor<1> {
+        self.lpips_impl(imgs_a, imgs_b, Some(mask))
+    }
+
+    fn lpips_impl(
+        &self,
+        imgs_a: Tensor<4>,
+        imgs_b: Tensor<4>,
+        mask: Option<Tensor<3>>,
+    ) -> Tensor<1> {
         let device = imgs_a.device();
 
+        // [N, H, W] -> [N, H, W, 1] to broadcast against the NHWC images.
+        let mask = mask.map(|m| m.unsqueeze_dim::<4>(3));
+        let (imgs_a, imgs_b) = match &mask {
+            Some(m) => (imgs_a * m.clone(), imgs_b * m.clone()),
+            None => (imgs_a, imgs_b),
+        };
+        // And to [N, 1, H, W] to weight the features.
+        let mut mask = mask.map(|m| m.permute([0, 3, 1, 2]));
+        let mask_pool = AvgPool2dConfig::new([2, 2]).with_strides([2, 2]).init();
+
         // Convert NHWC to NCHW and to [-1, 1].
         let imgs_a = imgs_a.permute([0, 3, 1, 2]) * 2.0 - 1.0;
         let imgs_b = imgs_b.permute([0, 3, 1, 2]) * 2.0 - 1.0;
@@ -90,6 +119,7 @@ impl LpipsModel {
             if i != 0 {
                 imgs_a = self.max_pool.forward(imgs_a);
                 imgs_b = self.max_pool.forward(imgs_b);
+                mask = mask.map(|m| mask_pool.forward(m));
             }
 
             // Process each part through the block
@@ -101,8 +131,15 @@ impl LpipsModel {
 
             let diff = (normed_a - normed_b).powi_scalar(2);
             let class = head.forward(diff);
-            // Add spatial mean.
-            loss = loss + class.mean_dim(2).mean_dim(3).reshape([1]);
+            // Add (weighted) spatial mean.
+            let mean = match &mask {
+                Some(m) => {
+                    let weighted = (class * m.clone()).sum_dim(2).sum_dim(3);
+                    weighted / (m.clone().sum_dim(2).sum_dim(3) + 1e-6)
+                }
+                None => class.mean_dim(2).mean_dim(3),
+            };
+            loss = loss + mean.reshape([1]);
         }
         loss
     }
@@ -159,7 +196,7 @@ pub fn load_vgg_lpips(device: &Device) -> LpipsModel {
 #[cfg(test)]
 mod tests {
     use super::load_vgg_lpips;
-    use burn::tensor::{Device, Tensor, TensorData};
+    use burn::tensor::{Device, Tensor, TensorData, s};
     use wasm_bindgen_test::wasm_bindgen_test;
 
     #[cfg(target_family = "wasm")]
@@ -198,6 +235,41 @@ mod tests {
         assert!((ab - ba).abs() < 1e-5, "asymmetric: ab = {ab}, ba = {ba}");
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_masked_ignores_masked_pixels() {
+        let device: Device = brush_cube::test_helpers::test_device().await.into();
+        let image1 = image::load_from_memory(APPLE_PNG).expect("Failed to load apple.png");
+        let image2 = image::load_from_memory(PEAR_PNG).expect("Failed to load pear.png");
+        let apple = image_to_tensor(&device, &image1);
+        let pear = image_to_tensor(&device, &image2);
+        let model = load_vgg_lpips(&device);
+
+        // Only the left half is compared.
+        let [n, h, w, _] = apple.dims();
+        let mask = Tensor::<3>::zeros([n, h, w], &device)
+            .slice_assign(s![.., .., 0..w / 2], Tensor::ones([n, h, w / 2], &device));
+        let base = read_scalar(model.lpips_masked(apple.clone(), pear.clone(), mask.clone())).await;
+
+        // Replacing the masked out right half doesn't change anything.
+        let right = Tensor::ones([n, h, w - w / 2, 3], &device);
+        let changed_masked = pear.clone().slice_assign(s![.., .., w / 2.., ..], right);
+        let masked =
+            read_scalar(model.lpips_masked(apple.clone(), changed_masked, mask.clone())).await;
+        assert!(
+            (masked - base).abs() < 1e-5,
+            "masked change: {base} -> {masked}"
+        );
+
+        // Replacing the compared left half does.
+        let left = Tensor::ones([n, h, w / 2, 3], &device);
+        let changed_unmasked = pear.slice_assign(s![.., .., 0..w / 2, ..], left);
+        let unmasked = read_scalar(model.lpips_masked(apple, changed_unmasked, mask)).await;
+        assert!(
+            (unmasked - base).abs() > 1e-3,
+            "unmasked change: {base} -> {unmasked}"
+        );
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn test_matches_pytorch_reference() {
         let device: Device = brush_cube::test_helpers::test_device().await.into();
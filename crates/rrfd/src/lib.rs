@@ -73,10 +73,50 @@ pub async fn pick_directory() -> Result<PathBuf, PickFileError> {
     }
 }
 
-/// Saves data to a file and returns the filename the data was saved too.
+/// A location picked by the user to save a file to.
+pub struct SaveTarget {
+    /// Name of the file, including the extension the user picked.
+    pub name: String,
+    #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+    path: PathBuf,
+}
+
+impl SaveTarget {
+    /// Write `data` to the picked location.
+    pub async fn save(self, data: Vec<u8>) -> Result<(), PickFileError> {
+        #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+        {
+            tokio::fs::write(&self.path, data).await?;
+            Ok(())
+        }
+
+        #[cfg(target_family = "wasm")]
+        {
+            wasm::save_file(&self.name, &data).await
+        }
+
+        #[cfg(target_os = "android")]
+        {
+            let _ = data;
+            panic!("No saving on Android yet.")
+        }
+    }
+
+    /// Open the picked location for writing, to stream large files to disk rather than
+    /// building them in memory first.
+    #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+    pub async fn create(self) -> Result<tokio::fs::File, PickFileError> {
+        Ok(tokio::fs::File::create(&self.path).await?)
+    }
+}
+
+/// Ask the user where to save a file, without writing anything yet. Useful
+/// when the content depends on the picked name, eg. its extension.
+///
+/// On the web there's no save dialog, the file is downloaded as `default_name`.
 ///
 /// Nb: Does not work on Android currently.
-pub async fn save_file(default_name: &str, data: Vec<u8>) -> Result<(), PickFileError> {
+pub async fn pick_save_target(default_name: &str) -> Result<SaveTarget, PickFileError> {
     #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
     {
         let file = rfd::AsyncFileDialog::new()
@@ -85,20 +125,29 @@ pub async fn save_file(default_name: &str, data: Vec<u8>) -> Result<(), PickFile
             .await
             .ok_or(PickFileError::NoFileSelected)?;
 
-        tokio::fs::write(file.path(), data).await?;
-
-        Ok(())
+        Ok(SaveTarget {
+            name: file.file_name(),
+            path: file.path().to_path_buf(),
+        })
     }
 
     #[cfg(target_family = "wasm")]
     {
-        wasm::save_file(default_name, &data).await
+        Ok(SaveTarget {
+            name: default_name.to_owned(),
+        })
     }
 
     #[cfg(target_os = "android")]
     {
         let _ = default_name;
-        let _ = data;
         panic!("No saving on Android yet.")
     }
 }
+
+/// Saves data to a file and returns the filename the data was saved too.
+///
+/// Nb: Does not work on Android currently.
+pub async fn save_file(default_name: &str, data: Vec<u8>) -> Result<(), PickFileError> {
+    pick_save_target(default_name).await?.save(data).await
+}